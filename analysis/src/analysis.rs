@@ -5,6 +5,8 @@ use crate::analyzer::core_analyzer::Analyzer;
 use crate::measure_time;
 use crate::utils::computing_device::ComputingDevice;
 use crate::utils::features::*;
+use crate::utils::loudness::LoudnessResult;
+use crate::utils::rhythm::RhythmResult;
 
 #[derive(Debug, Clone, Copy)]
 pub struct AudioStat {
@@ -39,10 +41,15 @@ pub struct AnalysisResult {
     pub perceptual_sharpness: f32,
     pub perceptual_loudness: [f32; 24],
     pub mfcc: [f32; 13],
+    pub loudness: Option<LoudnessResult>,
+    pub rhythm: Option<RhythmResult>,
 }
 
+/// Analyzes an audio file, or only the part between the two offsets of
+/// `range` in seconds, as for the tracks of a CUE sheet.
 pub fn analyze_audio(
     file_path: &str,
+    range: Option<(f64, f64)>,
     window_size: usize,
     overlap_size: usize,
     computing_device: ComputingDevice,
@@ -55,6 +62,9 @@ pub fn analyze_audio(
         None,
         cancel_token,
     );
+    if let Some((start, end)) = range {
+        analyzer.set_range(start, end);
+    }
 
     let audio_desc = measure_time!(
         &format!("[{:?}] Analyzer", computing_device),
@@ -66,6 +76,7 @@ pub fn analyze_audio(
     };
 
     let audio_desc = audio_desc.expect("Audio desc should not be none");
+    let rhythm = analyzer.rhythm();
 
     let amp_spectrum = amp_spectrum(&audio_desc.spectrum, window_size);
 
@@ -107,6 +118,8 @@ pub fn analyze_audio(
         .try_into()
         .expect("Expected a Vec of length 13");
 
+    let loudness = analyzer.loudness();
+
     // Create and return the analysis result
     Ok(Some(AnalysisResult {
         stat: AudioStat {
@@ -134,6 +147,8 @@ pub fn analyze_audio(
         perceptual_spread,
         perceptual_sharpness,
         mfcc,
+        loudness,
+        rhythm,
     }))
 }
 
@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 
-use log::debug;
+use log::{debug, warn};
 
 use rubato::{FftFixedInOut, Resampler};
 use rustfft::num_complex::Complex;
@@ -8,8 +8,9 @@ use symphonia::core::audio::{AudioBuffer, AudioBufferRef, Signal};
 use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
 use symphonia::core::conv::IntoSample;
 use symphonia::core::errors::Error;
-use symphonia::core::formats::FormatReader;
+use symphonia::core::formats::{FormatReader, SeekMode, SeekTo};
 use symphonia::core::sample::Sample;
+use symphonia::core::units::{Time, TimeBase};
 use symphonia::default::get_codecs;
 use tokio_util::sync::CancellationToken;
 
@@ -19,6 +20,8 @@ use crate::analyzer::sub_analyzer::SubAnalyzer;
 use crate::utils::audio_description::AudioDescription;
 use crate::utils::audio_metadata_reader::*;
 use crate::utils::computing_device::ComputingDevice;
+use crate::utils::loudness::{LoudnessMeter, LoudnessResult};
+use crate::utils::rhythm::{RhythmMeter, RhythmResult};
 
 macro_rules! check_cancellation {
     ($self:expr) => {
@@ -60,6 +63,19 @@ pub struct Analyzer {
     pub resampler: Option<FftFixedInOut<f32>>,
     pub resampler_output_buffer: Vec<Vec<f32>>,
     sub_analyzer: Arc<Mutex<dyn SubAnalyzer>>,
+
+    // Loudness measurement runs on the original signal, before resampling
+    loudness_meter: Option<LoudnessMeter>,
+    frame_buffer: Vec<f32>,
+    // Tempo and key estimation needs a finer time resolution than the windows
+    rhythm_meter: Option<RhythmMeter>,
+
+    // Part of the file to analyze in seconds, for tracks of a CUE sheet
+    range: Option<(f64, f64)>,
+    range_frames: Option<(u64, u64)>,
+    time_base: Option<TimeBase>,
+    // Frame position of the packet being processed
+    position: u64,
 }
 
 impl Analyzer {
@@ -108,9 +124,23 @@ impl Analyzer {
             } else {
                 Arc::new(Mutex::new(CpuSubAnalyzer::new(window_size)))
             },
+
+            loudness_meter: None,
+            frame_buffer: Vec::new(),
+            rhythm_meter: None,
+
+            range: None,
+            range_frames: None,
+            time_base: None,
+            position: 0,
         }
     }
 
+    /// Limits the analysis to the audio between `start` and `end`, in seconds.
+    pub fn set_range(&mut self, start: f64, end: f64) {
+        self.range = Some((start, end));
+    }
+
     pub fn process(&mut self, file_path: &str) -> Option<AudioDescription> {
         let mut format = get_format(file_path).expect("no supported audio tracks");
         let track = format
@@ -129,6 +159,26 @@ impl Analyzer {
             .expect("unsupported codec");
 
         let track_id = track.id;
+        self.time_base = track.codec_params.time_base;
+
+        if let Some((start, end)) = self.range {
+            let seeked = format.seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: Time::from(start),
+                    track_id: Some(track_id),
+                },
+            );
+            if let Err(e) = seeked {
+                warn!("Failed to seek to {}s in {}: {}", start, file_path, e);
+                return None;
+            }
+            decoder.reset();
+
+            let rate = sample_rate as f64;
+            self.range_frames = Some(((start * rate).round() as u64, (end * rate).round() as u64));
+            self.duration_in_seconds = end.min(duration_in_seconds) - start;
+        }
 
         if self.is_cancelled || (self.fn_is_cancelled)() {
             return None;
@@ -147,6 +197,39 @@ impl Analyzer {
         })
     }
 
+    /// Returns the loudness of the last processed file, if any audio was decoded.
+    pub fn loudness(&self) -> Option<LoudnessResult> {
+        self.loudness_meter.as_ref().map(|meter| meter.finalize())
+    }
+
+    /// Returns the tempo and key of the last processed file, if any audio was decoded.
+    pub fn rhythm(&self) -> Option<RhythmResult> {
+        self.rhythm_meter.as_ref().map(|meter| meter.finalize())
+    }
+
+    fn frame_position(&self, ts: u64) -> u64 {
+        match self.time_base {
+            Some(time_base) => {
+                let time = time_base.calc_time(ts);
+                ((time.seconds as f64 + time.frac) * self.sample_rate as f64).round() as u64
+            }
+            None => ts,
+        }
+    }
+
+    /// Returns the frames of a buffer of `frames` frames that lie within the
+    /// range being analyzed.
+    fn frames_in_range(&self, frames: usize) -> (usize, usize) {
+        match self.range_frames {
+            Some((start, end)) => {
+                let first = start.saturating_sub(self.position).min(frames as u64);
+                let last = end.saturating_sub(self.position).min(frames as u64);
+                (first as usize, last as usize)
+            }
+            None => (0, frames),
+        }
+    }
+
     fn process_audio_chunk(&mut self, chunk: &[f32], force: bool) {
         Arc::clone(&self.sub_analyzer)
             .lock()
@@ -161,11 +244,33 @@ impl Analyzer {
         let frames = buf.frames();
         let num_channels = buf.spec().channels.count();
 
-        for frame_idx in 0..frames {
-            let mixed_sample: f32 = (0..num_channels)
-                .map(|ch| IntoSample::<f32>::into_sample(buf.chan(ch)[frame_idx]))
-                .sum::<f32>()
-                / num_channels as f32;
+        let meter_outdated = self
+            .loudness_meter
+            .as_ref()
+            .is_none_or(|meter| meter.channel_count() != num_channels);
+        if meter_outdated {
+            self.loudness_meter = Some(LoudnessMeter::new(buf.spec().rate, num_channels));
+        }
+        if self.rhythm_meter.is_none() {
+            self.rhythm_meter = Some(RhythmMeter::new(buf.spec().rate));
+        }
+
+        let (first_frame, last_frame) = self.frames_in_range(frames);
+        for frame_idx in first_frame..last_frame {
+            self.frame_buffer.clear();
+            self.frame_buffer.extend(
+                (0..num_channels).map(|ch| IntoSample::<f32>::into_sample(buf.chan(ch)[frame_idx])),
+            );
+
+            if let Some(meter) = self.loudness_meter.as_mut() {
+                meter.push_frame(&self.frame_buffer);
+            }
+
+            let mixed_sample: f32 = self.frame_buffer.iter().sum::<f32>() / num_channels as f32;
+
+            if let Some(meter) = self.rhythm_meter.as_mut() {
+                meter.push_sample(mixed_sample);
+            }
 
             self.sample_buffer.push(mixed_sample);
             self.total_samples += 1;
@@ -222,6 +327,14 @@ impl Analyzer {
                 continue;
             }
 
+            if let Some((_, end)) = self.range_frames {
+                self.position = self.frame_position(packet.ts());
+                if self.position >= end {
+                    debug!("End of range");
+                    break;
+                }
+            }
+
             // Decode the packet into audio samples.
             let decoded = match decoder.decode(&packet) {
                 Ok(decoded) => decoded,
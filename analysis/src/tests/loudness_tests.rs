@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use crate::utils::loudness::{aggregate_loudness, loudness_to_replay_gain, LoudnessMeter};
+
+    fn measure_sine(
+        amplitude: f32,
+        frequency: f32,
+        sample_rate: u32,
+        seconds: f32,
+    ) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::new(sample_rate, 2);
+        let total = (sample_rate as f32 * seconds) as usize;
+
+        for i in 0..total {
+            let sample = amplitude * (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin();
+            meter.push_frame(&[sample, sample]);
+        }
+
+        meter
+    }
+
+    #[test]
+    fn test_full_scale_sine_loudness() {
+        // A 997Hz sine at 0 dBFS in both channels measures 0 LUFS (EBU Tech 3341)
+        let result = measure_sine(1.0, 997.0, 48000, 5.0).finalize();
+        let loudness = result.integrated_loudness.unwrap();
+
+        assert!(loudness.abs() < 0.1, "Unexpected loudness: {}", loudness);
+    }
+
+    #[test]
+    fn test_loudness_independent_of_sample_rate() {
+        let a = measure_sine(0.5, 1000.0, 44100, 5.0).finalize();
+        let b = measure_sine(0.5, 1000.0, 96000, 5.0).finalize();
+
+        let a = a.integrated_loudness.unwrap();
+        let b = b.integrated_loudness.unwrap();
+
+        assert!((a - b).abs() < 0.1, "Loudness mismatch: {} vs {}", a, b);
+        assert!((a + 6.02).abs() < 0.1, "Unexpected loudness: {}", a);
+    }
+
+    #[test]
+    fn test_true_peak() {
+        let result = measure_sine(0.5, 1000.0, 48000, 1.0).finalize();
+
+        assert!(
+            (result.true_peak - 0.5).abs() < 0.01,
+            "Unexpected true peak: {}",
+            result.true_peak
+        );
+    }
+
+    #[test]
+    fn test_silence_is_gated() {
+        let mut meter = LoudnessMeter::new(48000, 2);
+        for _ in 0..48000 {
+            meter.push_frame(&[0.0, 0.0]);
+        }
+
+        let result = meter.finalize();
+        assert!(result.integrated_loudness.is_none());
+        assert_eq!(result.true_peak, 0.0);
+        assert_eq!(result.leading_silence, 0.0);
+        assert_eq!(result.trailing_silence, 0.0);
+    }
+
+    #[test]
+    fn test_silence_profile() {
+        let mut meter = LoudnessMeter::new(48000, 2);
+        let push_silence = |meter: &mut LoudnessMeter, seconds: usize| {
+            for _ in 0..48000 * seconds {
+                meter.push_frame(&[0.0, 0.0]);
+            }
+        };
+
+        push_silence(&mut meter, 2);
+        for i in 0..48000 * 5 {
+            let sample = 0.5 * (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin();
+            meter.push_frame(&[sample, sample]);
+        }
+        push_silence(&mut meter, 3);
+
+        let result = meter.finalize();
+        assert!(
+            (result.leading_silence - 2.0).abs() < 0.11,
+            "Unexpected leading silence: {}",
+            result.leading_silence
+        );
+        assert!(
+            (result.trailing_silence - 3.0).abs() < 0.11,
+            "Unexpected trailing silence: {}",
+            result.trailing_silence
+        );
+    }
+
+    #[test]
+    fn test_aggregate_loudness() {
+        let same = aggregate_loudness(&[(-14.0, 180.0), (-14.0, 240.0)]).unwrap();
+        assert!((same + 14.0).abs() < 1e-9);
+
+        let mixed = aggregate_loudness(&[(-10.0, 100.0), (-20.0, 100.0)]).unwrap();
+        assert!(mixed > -15.0 && mixed < -10.0);
+
+        assert!(aggregate_loudness(&[]).is_none());
+        assert!((loudness_to_replay_gain(-23.0) - 5.0).abs() < 1e-9);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod analyzer_tests;
 pub mod fft_tests;
+pub mod loudness_tests;
+pub mod rhythm_tests;
@@ -0,0 +1,167 @@
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use crate::utils::key::{detect_key, Mode, MusicalKey};
+    use crate::utils::rhythm::RhythmMeter;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// Decaying noise bursts on every beat, over a quiet sustained tone.
+    fn click_track(bpm: f32, sample_rate: u32, seconds: f32) -> RhythmMeter {
+        let mut meter = RhythmMeter::new(sample_rate);
+        let beat = (60.0 / bpm * sample_rate as f32) as usize;
+        let total = (sample_rate as f32 * seconds) as usize;
+
+        // Deterministic noise keeps the test reproducible
+        let mut seed: u32 = 1;
+        for i in 0..total {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            let noise = (seed >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+
+            let since_beat = (i % beat) as f32 / sample_rate as f32;
+            let click = noise * (-since_beat * 40.0).exp();
+            let tone = 0.05 * (2.0 * PI * 220.0 * i as f32 / sample_rate as f32).sin();
+
+            meter.push_sample(0.5 * click + tone);
+        }
+
+        meter
+    }
+
+    fn chord(frequencies: &[f32], seconds: f32) -> RhythmMeter {
+        let mut meter = RhythmMeter::new(SAMPLE_RATE);
+        let total = (SAMPLE_RATE as f32 * seconds) as usize;
+
+        for i in 0..total {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let sample: f32 = frequencies
+                .iter()
+                .map(|f| (2.0 * PI * f * t).sin())
+                .sum::<f32>()
+                / frequencies.len() as f32;
+            meter.push_sample(0.5 * sample);
+        }
+
+        meter
+    }
+
+    fn assert_bpm(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("Expected a tempo");
+        assert!(
+            (actual - expected).abs() < 1.0,
+            "Unexpected tempo: {} instead of {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_click_track_tempo() {
+        assert_bpm(click_track(120.0, SAMPLE_RATE, 20.0).finalize().bpm, 120.0);
+        assert_bpm(click_track(93.0, SAMPLE_RATE, 20.0).finalize().bpm, 93.0);
+        assert_bpm(click_track(150.0, SAMPLE_RATE, 20.0).finalize().bpm, 150.0);
+    }
+
+    #[test]
+    fn test_tempo_independent_of_sample_rate() {
+        assert_bpm(click_track(128.0, 48000, 20.0).finalize().bpm, 128.0);
+        assert_bpm(click_track(128.0, 96000, 20.0).finalize().bpm, 128.0);
+    }
+
+    #[test]
+    fn test_short_signal_has_no_tempo() {
+        assert!(click_track(120.0, SAMPLE_RATE, 2.0)
+            .finalize()
+            .bpm
+            .is_none());
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut meter = RhythmMeter::new(SAMPLE_RATE);
+        for _ in 0..SAMPLE_RATE * 10 {
+            meter.push_sample(0.0);
+        }
+
+        let result = meter.finalize();
+        assert!(result.bpm.is_none());
+        assert!(result.key.is_none());
+    }
+
+    #[test]
+    fn test_chord_key() {
+        // C major triad, C4 E4 G4 with C3 in the bass
+        let key = chord(&[130.81, 261.63, 329.63, 392.0], 5.0)
+            .finalize()
+            .key
+            .unwrap()
+            .key;
+        assert_eq!(key, MusicalKey::new(0, Mode::Major));
+
+        // A minor triad, A3 C4 E4 with A2 in the bass
+        let key = chord(&[110.0, 220.0, 261.63, 329.63], 5.0)
+            .finalize()
+            .key
+            .unwrap()
+            .key;
+        assert_eq!(key, MusicalKey::new(9, Mode::Minor));
+    }
+
+    #[test]
+    fn test_detect_key_from_profile() {
+        // Pitch classes of the D major scale
+        let mut profile = [0.0; 12];
+        for pitch_class in [2, 4, 6, 7, 9, 11, 1] {
+            profile[pitch_class] = 1.0;
+        }
+        profile[2] = 2.0;
+        profile[9] = 1.5;
+
+        let estimate = detect_key(&profile).unwrap();
+        assert_eq!(estimate.key, MusicalKey::new(2, Mode::Major));
+        assert!(detect_key(&[1.0; 12]).is_none());
+    }
+
+    #[test]
+    fn test_camelot() {
+        let c_major = MusicalKey::new(0, Mode::Major);
+        let a_minor = MusicalKey::new(9, Mode::Minor);
+
+        assert_eq!(c_major.camelot(), "8B");
+        assert_eq!(a_minor.camelot(), "8A");
+        assert_eq!(MusicalKey::new(11, Mode::Major).camelot(), "1B");
+        assert_eq!(MusicalKey::new(4, Mode::Minor).camelot(), "9A");
+
+        for index in 0..24 {
+            let key = MusicalKey::from_camelot_index(index).unwrap();
+            assert_eq!(key.camelot_index(), index);
+        }
+        assert!(MusicalKey::from_camelot_index(24).is_none());
+
+        assert!(c_major.is_compatible(&a_minor));
+        assert!(c_major.is_compatible(&MusicalKey::new(7, Mode::Major)));
+        assert!(c_major.is_compatible(&MusicalKey::new(5, Mode::Major)));
+        assert!(!c_major.is_compatible(&MusicalKey::new(2, Mode::Major)));
+        assert!(!c_major.is_compatible(&MusicalKey::new(0, Mode::Minor)));
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let parse = |s: &str| s.parse::<MusicalKey>().ok();
+
+        assert_eq!(parse("8A"), Some(MusicalKey::new(9, Mode::Minor)));
+        assert_eq!(parse("12b"), Some(MusicalKey::new(4, Mode::Major)));
+        assert_eq!(parse("Am"), Some(MusicalKey::new(9, Mode::Minor)));
+        assert_eq!(parse("F#"), Some(MusicalKey::new(6, Mode::Major)));
+        assert_eq!(parse("Bbm"), Some(MusicalKey::new(10, Mode::Minor)));
+        assert_eq!(parse("Bm"), Some(MusicalKey::new(11, Mode::Minor)));
+        assert_eq!(parse("C# minor"), Some(MusicalKey::new(1, Mode::Minor)));
+        assert_eq!(parse("Eb Major"), Some(MusicalKey::new(3, Mode::Major)));
+        assert_eq!(parse("13A"), None);
+        assert_eq!(parse("H"), None);
+        assert_eq!(parse(""), None);
+
+        assert_eq!(MusicalKey::new(10, Mode::Minor).to_string(), "Bbm");
+    }
+}
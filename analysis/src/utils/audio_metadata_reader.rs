@@ -1,33 +1,15 @@
-use anyhow::Context;
+use std::path::Path;
+
 use anyhow::Result;
 
-use symphonia::core::formats::FormatOptions;
 use symphonia::core::formats::FormatReader;
 use symphonia::core::formats::Track;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
-use symphonia::core::probe::Hint;
-
-pub fn get_format(file_path: &str) -> Result<Box<dyn FormatReader>> {
-    // Open the media source.
-    let src = std::fs::File::open(file_path).with_context(|| "failed to open media")?;
 
-    // Create the media source stream.
-    let mss = MediaSourceStream::new(Box::new(src), Default::default());
+use formats::registry;
 
-    // Create a probe hint using the file's extension.
-    let mut hint = Hint::new();
-    let ext = file_path.split('.').last().unwrap_or_default();
-    hint.with_extension(ext);
-
-    // Use the default options for metadata and format readers.
-    let meta_opts: MetadataOptions = Default::default();
-    let fmt_opts: FormatOptions = Default::default();
-
-    // Probe the media source.
-    let probed = symphonia::default::get_probe()
-        .format(&hint, mss, &fmt_opts, &meta_opts)
-        .with_context(|| "unsupported format")?;
+pub fn get_format(file_path: &str) -> Result<Box<dyn FormatReader>> {
+    // Probe the media source through the shared format registry.
+    let probed = registry().probe(Path::new(file_path))?;
 
     // Get the instantiated format reader.
     let format = probed.format;
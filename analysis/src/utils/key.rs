@@ -0,0 +1,239 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+
+// Musical key estimation from a pitch class profile, using the
+// Krumhansl-Kessler key profiles.
+
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+const PITCH_CLASS_NAMES: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+/// A key, as a tonic pitch class (0 is C) and a mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MusicalKey {
+    pub tonic: u8,
+    pub mode: Mode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEstimate {
+    pub key: MusicalKey,
+    /// Correlation between the pitch class profile and the key profile
+    pub strength: f64,
+}
+
+impl MusicalKey {
+    pub fn new(tonic: u8, mode: Mode) -> Self {
+        MusicalKey {
+            tonic: tonic % 12,
+            mode,
+        }
+    }
+
+    /// Position on the Camelot wheel, from 1 to 12. Minor keys are `A`,
+    /// major keys `B`, so A minor is 8A and C major is 8B.
+    pub fn camelot_number(&self) -> u8 {
+        let tonic = self.tonic as u32;
+        let number = match self.mode {
+            Mode::Major => (7 * tonic + 7) % 12,
+            Mode::Minor => (7 * tonic + 4) % 12,
+        };
+
+        number as u8 + 1
+    }
+
+    /// Orders keys along the Camelot wheel, from 0 (1A) to 23 (12B).
+    pub fn camelot_index(&self) -> u8 {
+        (self.camelot_number() - 1) * 2
+            + match self.mode {
+                Mode::Minor => 0,
+                Mode::Major => 1,
+            }
+    }
+
+    pub fn from_camelot_index(index: u8) -> Option<Self> {
+        if index >= 24 {
+            return None;
+        }
+
+        let mode = match index % 2 {
+            0 => Mode::Minor,
+            _ => Mode::Major,
+        };
+        Self::from_camelot(index / 2 + 1, mode)
+    }
+
+    pub fn from_camelot(number: u8, mode: Mode) -> Option<Self> {
+        if !(1..=12).contains(&number) {
+            return None;
+        }
+
+        // 7 is its own inverse modulo 12
+        let offset = match mode {
+            Mode::Major => 8,
+            Mode::Minor => 5,
+        };
+        let tonic = (7 * (number as u32 + 12 - offset)) % 12;
+
+        Some(MusicalKey::new(tonic as u8, mode))
+    }
+
+    pub fn camelot(&self) -> String {
+        let letter = match self.mode {
+            Mode::Minor => 'A',
+            Mode::Major => 'B',
+        };
+        format!("{}{}", self.camelot_number(), letter)
+    }
+
+    /// Keys that mix harmonically with this one: the key itself, its
+    /// neighbours on the Camelot wheel and its relative major or minor.
+    pub fn compatible_keys(&self) -> [MusicalKey; 4] {
+        let number = self.camelot_number();
+        let previous = if number == 1 { 12 } else { number - 1 };
+        let next = if number == 12 { 1 } else { number + 1 };
+        let relative = match self.mode {
+            Mode::Major => Mode::Minor,
+            Mode::Minor => Mode::Major,
+        };
+
+        [
+            *self,
+            Self::from_camelot(previous, self.mode).unwrap(),
+            Self::from_camelot(next, self.mode).unwrap(),
+            Self::from_camelot(number, relative).unwrap(),
+        ]
+    }
+
+    pub fn is_compatible(&self, other: &MusicalKey) -> bool {
+        self.compatible_keys().contains(other)
+    }
+}
+
+impl fmt::Display for MusicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = PITCH_CLASS_NAMES[self.tonic as usize];
+        match self.mode {
+            Mode::Major => write!(f, "{}", name),
+            Mode::Minor => write!(f, "{}m", name),
+        }
+    }
+}
+
+impl FromStr for MusicalKey {
+    type Err = Error;
+
+    /// Parses Camelot codes (`8A`) and key names as written in tags
+    /// (`Am`, `A minor`, `F#`, `Bbmin`, `C# Major`).
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        if let Some(key) = parse_camelot(s) {
+            return Ok(key);
+        }
+
+        let mut chars = s.chars();
+        let tonic = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => bail!("Invalid key: {}", s),
+        };
+
+        let rest = chars.as_str();
+        let (tonic, rest) = if let Some(rest) = rest.strip_prefix(['#', '♯']) {
+            (tonic + 1, rest)
+        } else if let Some(rest) = rest.strip_prefix(['b', '♭']) {
+            (tonic + 11, rest)
+        } else {
+            (tonic, rest)
+        };
+
+        let mode = match rest.trim().to_lowercase().as_str() {
+            "" | "maj" | "major" => Mode::Major,
+            "m" | "min" | "minor" => Mode::Minor,
+            _ => bail!("Invalid key: {}", s),
+        };
+
+        Ok(MusicalKey::new(tonic, mode))
+    }
+}
+
+fn parse_camelot(s: &str) -> Option<MusicalKey> {
+    let letter = s.chars().last()?;
+    let number: u8 = s[..s.len() - letter.len_utf8()].parse().ok()?;
+
+    let mode = match letter.to_ascii_uppercase() {
+        'A' => Mode::Minor,
+        'B' => Mode::Major,
+        _ => return None,
+    };
+
+    MusicalKey::from_camelot(number, mode)
+}
+
+fn correlation(x: &[f64; 12], y: &[f64; 12], rotation: usize) -> f64 {
+    let mean_x = x.iter().sum::<f64>() / 12.0;
+    let mean_y = y.iter().sum::<f64>() / 12.0;
+
+    let mut covariance = 0.0;
+    let mut variance_x = 0.0;
+    let mut variance_y = 0.0;
+    for i in 0..12 {
+        let dx = x[(i + rotation) % 12] - mean_x;
+        let dy = y[i] - mean_y;
+        covariance += dx * dy;
+        variance_x += dx * dx;
+        variance_y += dy * dy;
+    }
+
+    if variance_x == 0.0 || variance_y == 0.0 {
+        return 0.0;
+    }
+
+    covariance / (variance_x * variance_y).sqrt()
+}
+
+/// Picks the key whose profile correlates best with a pitch class profile
+/// indexed from C. Returns `None` for a flat profile, such as silence.
+pub fn detect_key(pitch_classes: &[f64; 12]) -> Option<KeyEstimate> {
+    let max = pitch_classes.iter().cloned().fold(0.0, f64::max);
+    let min = pitch_classes.iter().cloned().fold(f64::INFINITY, f64::min);
+    if max <= 0.0 || max - min <= max * 1e-6 {
+        return None;
+    }
+
+    let mut best: Option<KeyEstimate> = None;
+    for tonic in 0..12 {
+        for (mode, profile) in [(Mode::Major, &MAJOR_PROFILE), (Mode::Minor, &MINOR_PROFILE)] {
+            let strength = correlation(pitch_classes, profile, tonic);
+            if best.is_none_or(|x| strength > x.strength) {
+                best = Some(KeyEstimate {
+                    key: MusicalKey::new(tonic as u8, mode),
+                    strength,
+                });
+            }
+        }
+    }
+
+    best
+}
@@ -0,0 +1,367 @@
+use std::f64::consts::PI;
+
+// EBU R128 / ITU-R BS.1770-4 loudness measurement.
+//
+// K-weighting coefficients are derived for arbitrary sample rates following
+// the same approach as libebur128 (MIT).
+
+/// Gating block length in seconds.
+const BLOCK_DURATION: f64 = 0.4;
+/// Number of 100ms steps per gating block (75% overlap).
+const BLOCK_STEPS: usize = 4;
+/// Absolute gate threshold in LUFS.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Relative gate threshold in LU.
+const RELATIVE_GATE: f64 = -10.0;
+/// Oversampling factor used for true peak estimation.
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+/// Number of FIR taps per polyphase branch of the true peak interpolator.
+const TRUE_PEAK_TAPS_PER_PHASE: usize = 12;
+
+/// Steps quieter than this are considered silent, in LUFS.
+const SILENCE_GATE: f64 = -60.0;
+/// Steps this far below the integrated loudness are considered silent, in LU.
+const RELATIVE_SILENCE_GATE: f64 = -30.0;
+
+/// ReplayGain 2.0 reference loudness in LUFS.
+pub const REPLAY_GAIN_REFERENCE: f64 = -18.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad { b, a, z: [0.0; 2] }
+    }
+
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        // Transposed direct form II
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[1] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[2] * y;
+        y
+    }
+}
+
+/// The two-stage K-weighting filter (high shelf followed by a high pass).
+#[derive(Debug, Clone, Copy)]
+struct KWeightingFilter {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeightingFilter {
+    fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate as f64;
+
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * f0 / rate).tan();
+        let vh = 10.0_f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        KWeightingFilter { shelf, high_pass }
+    }
+
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+/// Polyphase windowed-sinc interpolator used to estimate inter-sample peaks.
+#[derive(Debug, Clone)]
+struct TruePeakInterpolator {
+    phases: Vec<[f64; TRUE_PEAK_TAPS_PER_PHASE]>,
+    history: [f64; TRUE_PEAK_TAPS_PER_PHASE],
+    position: usize,
+}
+
+impl TruePeakInterpolator {
+    fn new() -> Self {
+        let total_taps = TRUE_PEAK_OVERSAMPLING * TRUE_PEAK_TAPS_PER_PHASE;
+        let center = (total_taps - 1) as f64 / 2.0;
+
+        let mut phases = vec![[0.0; TRUE_PEAK_TAPS_PER_PHASE]; TRUE_PEAK_OVERSAMPLING];
+        for n in 0..total_taps {
+            let t = (n as f64 - center) / TRUE_PEAK_OVERSAMPLING as f64;
+            let sinc = if t.abs() < 1e-12 {
+                1.0
+            } else {
+                (PI * t).sin() / (PI * t)
+            };
+            // Blackman window
+            let w = 0.42 - 0.5 * (2.0 * PI * n as f64 / (total_taps - 1) as f64).cos()
+                + 0.08 * (4.0 * PI * n as f64 / (total_taps - 1) as f64).cos();
+
+            phases[n % TRUE_PEAK_OVERSAMPLING][n / TRUE_PEAK_OVERSAMPLING] = sinc * w;
+        }
+
+        TruePeakInterpolator {
+            phases,
+            history: [0.0; TRUE_PEAK_TAPS_PER_PHASE],
+            position: 0,
+        }
+    }
+
+    /// Pushes a sample and returns the largest absolute value of the
+    /// interpolated signal around it.
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        self.history[self.position] = x;
+        self.position = (self.position + 1) % TRUE_PEAK_TAPS_PER_PHASE;
+
+        let mut peak = x.abs();
+        for phase in self.phases.iter() {
+            let mut acc = 0.0;
+            for (i, coefficient) in phase.iter().enumerate() {
+                let idx =
+                    (self.position + TRUE_PEAK_TAPS_PER_PHASE - 1 - i) % TRUE_PEAK_TAPS_PER_PHASE;
+                acc += coefficient * self.history[idx];
+            }
+            peak = peak.max(acc.abs());
+        }
+
+        peak
+    }
+}
+
+/// Channel weighting as defined in ITU-R BS.1770, assuming the channel
+/// ordering used by symphonia (FL, FR, FC, LFE, RL, RR, ...).
+fn channel_weight(channel: usize, channel_count: usize) -> f64 {
+    if channel_count >= 5 {
+        match channel {
+            0..=2 => 1.0,
+            3 if channel_count == 6 => 0.0,
+            _ => 1.41,
+        }
+    } else {
+        1.0
+    }
+}
+
+/// Streaming loudness meter producing integrated loudness and true peak.
+pub struct LoudnessMeter {
+    channel_count: usize,
+    filters: Vec<KWeightingFilter>,
+    interpolators: Vec<TruePeakInterpolator>,
+    weights: Vec<f64>,
+
+    sample_rate: u32,
+    total_frames: u64,
+    step_size: usize,
+    step_position: usize,
+    step_energy: f64,
+    recent_steps: Vec<f64>,
+    step_energies: Vec<f64>,
+    block_energies: Vec<f64>,
+
+    true_peak: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessResult {
+    /// Gated integrated loudness in LUFS, `None` if the signal is silent.
+    pub integrated_loudness: Option<f64>,
+    /// True peak as a linear amplitude (1.0 == 0 dBTP).
+    pub true_peak: f64,
+    /// Length of the silence before the music starts, in seconds.
+    pub leading_silence: f64,
+    /// Length of the silence (or quiet fade out) after the music ends, in seconds.
+    pub trailing_silence: f64,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channel_count: usize) -> Self {
+        let channel_count = channel_count.max(1);
+
+        LoudnessMeter {
+            channel_count,
+            filters: vec![KWeightingFilter::new(sample_rate); channel_count],
+            interpolators: vec![TruePeakInterpolator::new(); channel_count],
+            weights: (0..channel_count)
+                .map(|ch| channel_weight(ch, channel_count))
+                .collect(),
+
+            sample_rate,
+            total_frames: 0,
+            step_size: ((sample_rate as f64 * BLOCK_DURATION) / BLOCK_STEPS as f64).round()
+                as usize,
+            step_position: 0,
+            step_energy: 0.0,
+            recent_steps: Vec::with_capacity(BLOCK_STEPS),
+            step_energies: Vec::new(),
+            block_energies: Vec::new(),
+
+            true_peak: 0.0,
+        }
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Feeds a single frame, one sample per channel.
+    #[inline]
+    pub fn push_frame(&mut self, frame: &[f32]) {
+        for (ch, &sample) in frame.iter().take(self.channel_count).enumerate() {
+            let sample = sample as f64;
+
+            let peak = self.interpolators[ch].process(sample);
+            if peak > self.true_peak {
+                self.true_peak = peak;
+            }
+
+            let weighted = self.filters[ch].process(sample);
+            self.step_energy += self.weights[ch] * weighted * weighted;
+        }
+
+        self.total_frames += 1;
+        self.step_position += 1;
+        if self.step_position >= self.step_size {
+            self.finish_step();
+        }
+    }
+
+    fn finish_step(&mut self) {
+        let mean_energy = self.step_energy / self.step_size as f64;
+        self.step_energy = 0.0;
+        self.step_position = 0;
+        self.step_energies.push(mean_energy);
+
+        if self.recent_steps.len() == BLOCK_STEPS {
+            self.recent_steps.remove(0);
+        }
+        self.recent_steps.push(mean_energy);
+
+        if self.recent_steps.len() == BLOCK_STEPS {
+            let block_energy = self.recent_steps.iter().sum::<f64>() / BLOCK_STEPS as f64;
+            self.block_energies.push(block_energy);
+        }
+    }
+
+    pub fn finalize(&self) -> LoudnessResult {
+        let integrated_loudness = gated_mean_energy(&self.block_energies).map(energy_to_loudness);
+        let (leading_silence, trailing_silence) = self.silence(integrated_loudness);
+
+        LoudnessResult {
+            integrated_loudness,
+            true_peak: self.true_peak,
+            leading_silence,
+            trailing_silence,
+        }
+    }
+
+    /// Finds the silent head and tail of the signal with a 100ms resolution.
+    /// Returns `(0.0, 0.0)` if the whole signal is silent.
+    fn silence(&self, integrated_loudness: Option<f64>) -> (f64, f64) {
+        let threshold = integrated_loudness
+            .map(|x| (x + RELATIVE_SILENCE_GATE).max(SILENCE_GATE))
+            .unwrap_or(SILENCE_GATE);
+
+        let mut steps = self.step_energies.clone();
+        if self.step_position > 0 {
+            steps.push(self.step_energy / self.step_position as f64);
+        }
+
+        let is_loud = |e: &f64| *e > 0.0 && energy_to_loudness(*e) > threshold;
+        let (Some(first), Some(last)) = (
+            steps.iter().position(is_loud),
+            steps.iter().rposition(is_loud),
+        ) else {
+            return (0.0, 0.0);
+        };
+
+        let step_duration = self.step_size as f64 / self.sample_rate as f64;
+        let total_duration = self.total_frames as f64 / self.sample_rate as f64;
+
+        let leading = first as f64 * step_duration;
+        let trailing = (total_duration - (last + 1) as f64 * step_duration).max(0.0);
+
+        (leading, trailing)
+    }
+}
+
+fn energy_to_loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+fn gated_mean_energy(block_energies: &[f64]) -> Option<f64> {
+    let absolute_gated: Vec<f64> = block_energies
+        .iter()
+        .copied()
+        .filter(|&e| e > 0.0 && energy_to_loudness(e) > ABSOLUTE_GATE)
+        .collect();
+
+    if absolute_gated.is_empty() {
+        return None;
+    }
+
+    let relative_threshold =
+        energy_to_loudness(absolute_gated.iter().sum::<f64>() / absolute_gated.len() as f64)
+            + RELATIVE_GATE;
+
+    let relative_gated: Vec<f64> = absolute_gated
+        .into_iter()
+        .filter(|&e| energy_to_loudness(e) > relative_threshold)
+        .collect();
+
+    if relative_gated.is_empty() {
+        return None;
+    }
+
+    Some(relative_gated.iter().sum::<f64>() / relative_gated.len() as f64)
+}
+
+/// Aggregates the loudness of several tracks (e.g. an album), weighting each
+/// track by its duration. Returns the combined loudness in LUFS.
+pub fn aggregate_loudness(tracks: &[(f64, f64)]) -> Option<f64> {
+    let total_duration: f64 = tracks.iter().map(|(_, duration)| duration).sum();
+    if total_duration <= 0.0 {
+        return None;
+    }
+
+    let energy = tracks
+        .iter()
+        .map(|(loudness, duration)| 10.0_f64.powf((loudness + 0.691) / 10.0) * duration)
+        .sum::<f64>()
+        / total_duration;
+
+    if energy > 0.0 {
+        Some(energy_to_loudness(energy))
+    } else {
+        None
+    }
+}
+
+/// Converts an integrated loudness value into a ReplayGain 2.0 gain in dB.
+pub fn loudness_to_replay_gain(loudness: f64) -> f64 {
+    REPLAY_GAIN_REFERENCE - loudness
+}
@@ -3,4 +3,7 @@ pub mod audio_metadata_reader;
 pub mod computing_device;
 pub mod features;
 pub mod hanning_window;
+pub mod key;
+pub mod loudness;
 pub mod measure_time_utils;
+pub mod rhythm;
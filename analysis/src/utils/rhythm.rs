@@ -0,0 +1,240 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::utils::key::{detect_key, KeyEstimate};
+
+// Tempo and key estimation from a short-time spectrum.
+//
+// The tempo is the autocorrelation peak of the onset strength envelope
+// (half-wave rectified log spectral flux), weighted towards 120 BPM to
+// resolve octave ambiguities. The key is matched against a pitch class
+// profile accumulated from the spectral peaks of every frame.
+
+/// Rate the signal is decimated to before analysis, in Hz.
+const TARGET_RATE: u32 = 11025;
+const FRAME_SIZE: usize = 2048;
+/// About 11.6ms at the target rate.
+const HOP_SIZE: usize = 128;
+
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 200.0;
+/// Center of the log-normal tempo prior, in BPM.
+const PRIOR_BPM: f64 = 120.0;
+/// Standard deviation of the tempo prior, in octaves.
+const PRIOR_WIDTH: f64 = 1.0;
+/// Shortest signal a tempo is estimated for, in seconds.
+const MIN_TEMPO_DURATION: f64 = 5.0;
+/// Length of the moving average removed from the onset envelope, in seconds.
+const ONSET_MEAN_DURATION: f64 = 0.5;
+
+/// Range of frequencies contributing to the pitch class profile, in Hz.
+const MIN_PITCH_FREQUENCY: f32 = 100.0;
+const MAX_PITCH_FREQUENCY: f32 = 2000.0;
+
+/// Streaming tempo and key estimator, fed with a mono signal.
+pub struct RhythmMeter {
+    decimation: usize,
+    decimation_sum: f32,
+    decimation_count: usize,
+    frame_rate: f64,
+
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    samples: Vec<f32>,
+    fft_buffer: Vec<Complex<f32>>,
+    magnitudes: Vec<f32>,
+    previous_log_magnitudes: Option<Vec<f32>>,
+    onset_envelope: Vec<f32>,
+
+    bin_pitch_classes: Vec<Option<usize>>,
+    pitch_classes: [f64; 12],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RhythmResult {
+    /// Estimated tempo in beats per minute, `None` if the signal is too short
+    /// or has no rhythmic content.
+    pub bpm: Option<f64>,
+    /// Estimated key, `None` if the signal has no tonal content.
+    pub key: Option<KeyEstimate>,
+}
+
+impl RhythmMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        let decimation = ((sample_rate as f64 / TARGET_RATE as f64).round() as usize).max(1);
+        let rate = sample_rate as f32 / decimation as f32;
+
+        let bin_pitch_classes = (0..=FRAME_SIZE / 2)
+            .map(|bin| {
+                let frequency = bin as f32 * rate / FRAME_SIZE as f32;
+                if !(MIN_PITCH_FREQUENCY..=MAX_PITCH_FREQUENCY).contains(&frequency) {
+                    return None;
+                }
+
+                let midi = 69.0 + 12.0 * (frequency / 440.0).log2();
+                Some(midi.round() as usize % 12)
+            })
+            .collect();
+
+        RhythmMeter {
+            decimation,
+            decimation_sum: 0.0,
+            decimation_count: 0,
+            frame_rate: rate as f64 / HOP_SIZE as f64,
+
+            fft: FftPlanner::new().plan_fft_forward(FRAME_SIZE),
+            window: (0..FRAME_SIZE)
+                .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FRAME_SIZE as f32).cos())
+                .collect(),
+            samples: Vec::with_capacity(FRAME_SIZE),
+            fft_buffer: vec![Complex::new(0.0, 0.0); FRAME_SIZE],
+            magnitudes: vec![0.0; FRAME_SIZE / 2 + 1],
+            previous_log_magnitudes: None,
+            onset_envelope: Vec::new(),
+
+            bin_pitch_classes,
+            pitch_classes: [0.0; 12],
+        }
+    }
+
+    /// Feeds a single mono sample.
+    #[inline]
+    pub fn push_sample(&mut self, sample: f32) {
+        self.decimation_sum += sample;
+        self.decimation_count += 1;
+        if self.decimation_count < self.decimation {
+            return;
+        }
+
+        self.samples
+            .push(self.decimation_sum / self.decimation_count as f32);
+        self.decimation_sum = 0.0;
+        self.decimation_count = 0;
+
+        if self.samples.len() == FRAME_SIZE {
+            self.process_frame();
+            self.samples.drain(..HOP_SIZE);
+        }
+    }
+
+    fn process_frame(&mut self) {
+        for (i, value) in self.fft_buffer.iter_mut().enumerate() {
+            *value = Complex::new(self.samples[i] * self.window[i], 0.0);
+        }
+        self.fft.process(&mut self.fft_buffer);
+
+        let scale = 2.0 / FRAME_SIZE as f32;
+        for (magnitude, value) in self.magnitudes.iter_mut().zip(&self.fft_buffer) {
+            *magnitude = value.norm() * scale;
+        }
+
+        // Only spectral peaks count towards the pitch class profile, so the
+        // leakage of strong partials does not spill into neighbouring classes
+        for bin in 1..self.magnitudes.len() - 1 {
+            let Some(pitch_class) = self.bin_pitch_classes[bin] else {
+                continue;
+            };
+
+            let magnitude = self.magnitudes[bin];
+            if magnitude > self.magnitudes[bin - 1] && magnitude >= self.magnitudes[bin + 1] {
+                self.pitch_classes[pitch_class] += magnitude as f64;
+            }
+        }
+
+        let log_magnitudes: Vec<f32> = self
+            .magnitudes
+            .iter()
+            .map(|x| (1.0 + 100.0 * x).ln())
+            .collect();
+
+        if let Some(previous) = &self.previous_log_magnitudes {
+            let flux = log_magnitudes
+                .iter()
+                .zip(previous)
+                .map(|(current, previous)| (current - previous).max(0.0))
+                .sum();
+            self.onset_envelope.push(flux);
+        }
+
+        self.previous_log_magnitudes = Some(log_magnitudes);
+    }
+
+    pub fn finalize(&self) -> RhythmResult {
+        RhythmResult {
+            bpm: self.tempo(),
+            key: detect_key(&self.pitch_classes),
+        }
+    }
+
+    fn tempo(&self) -> Option<f64> {
+        let envelope = &self.onset_envelope;
+        if (envelope.len() as f64) < MIN_TEMPO_DURATION * self.frame_rate {
+            return None;
+        }
+
+        // Remove the local mean so slow changes of loudness do not correlate
+        let radius = (ONSET_MEAN_DURATION * self.frame_rate / 2.0).round() as usize;
+        let mut prefix_sum = vec![0.0; envelope.len() + 1];
+        for (i, x) in envelope.iter().enumerate() {
+            prefix_sum[i + 1] = prefix_sum[i] + *x as f64;
+        }
+
+        let onsets: Vec<f64> = (0..envelope.len())
+            .map(|i| {
+                let start = i.saturating_sub(radius);
+                let end = (i + radius + 1).min(envelope.len());
+                let mean = (prefix_sum[end] - prefix_sum[start]) / (end - start) as f64;
+                (envelope[i] as f64 - mean).max(0.0)
+            })
+            .collect();
+
+        if onsets.iter().all(|x| *x == 0.0) {
+            return None;
+        }
+
+        let autocorrelation = |lag: usize| -> f64 {
+            let count = onsets.len() - lag;
+            onsets[..count]
+                .iter()
+                .zip(&onsets[lag..])
+                .map(|(a, b)| a * b)
+                .sum::<f64>()
+                / count as f64
+        };
+
+        let min_lag = (60.0 * self.frame_rate / MAX_BPM).floor() as usize;
+        let max_lag = (60.0 * self.frame_rate / MIN_BPM).ceil() as usize;
+        let correlations: Vec<f64> = (min_lag - 1..=max_lag + 1).map(autocorrelation).collect();
+
+        let (index, _) = (1..correlations.len() - 1)
+            .map(|i| {
+                let bpm = 60.0 * self.frame_rate / (min_lag - 1 + i) as f64;
+                let octaves = (bpm / PRIOR_BPM).log2() / PRIOR_WIDTH;
+                (i, correlations[i] * (-0.5 * octaves * octaves).exp())
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        if correlations[index] <= 0.0 {
+            return None;
+        }
+
+        // Refine the lag between frames with a parabola through the peak
+        let (a, b, c) = (
+            correlations[index - 1],
+            correlations[index],
+            correlations[index + 1],
+        );
+        let denominator = a - 2.0 * b + c;
+        let offset = if denominator < 0.0 {
+            (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+
+        let lag = (min_lag - 1 + index) as f64 + offset;
+        Some(60.0 * self.frame_rate / lag)
+    }
+}
@@ -0,0 +1,117 @@
+use prettytable::{row, Table};
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use database::actions::history_import::{import_play_history, parse_history, HistorySource};
+use database::connection::MainDbConnection;
+
+/// Unmatched tracks printed after an import, the report file has all of them.
+const PRINTED_UNMATCHED: usize = 20;
+
+pub struct ImportHistoryOptions<'a> {
+    pub source: &'a str,
+    pub files: &'a [PathBuf],
+    pub report: Option<&'a PathBuf>,
+}
+
+pub async fn import_history(main_db: &MainDbConnection, options: ImportHistoryOptions<'_>) {
+    let ImportHistoryOptions {
+        source,
+        files,
+        report,
+    } = options;
+
+    let source: HistorySource = match source.parse() {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let mut listens = Vec::new();
+    for file in files {
+        let content = match fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", file.display(), e);
+                return;
+            }
+        };
+
+        match parse_history(source, &content) {
+            Ok(x) => listens.extend(x),
+            Err(e) => {
+                eprintln!("Failed to read {}: {:#}", file.display(), e);
+                return;
+            }
+        }
+    }
+
+    let result = match import_play_history(main_db, source, listens).await {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Failed to import play history: {:#}", e);
+            return;
+        }
+    };
+
+    let unmatched_plays: usize = result.unmatched.iter().map(|x| x.plays).sum();
+    println!(
+        "Read {} plays: {} imported, {} already in the history, {} unmatched",
+        result.total, result.imported, result.duplicates, unmatched_plays
+    );
+
+    if !result.unmatched.is_empty() {
+        let mut table = Table::new();
+        table.add_row(row!["Plays", "Artist", "Title", "Album"]);
+        for item in result.unmatched.iter().take(PRINTED_UNMATCHED) {
+            table.add_row(row![
+                item.plays,
+                item.artist,
+                item.title,
+                item.album.as_deref().unwrap_or_default()
+            ]);
+        }
+        table.printstd();
+
+        if result.unmatched.len() > PRINTED_UNMATCHED {
+            println!(
+                "{} more unmatched tracks",
+                result.unmatched.len() - PRINTED_UNMATCHED
+            );
+        }
+    }
+
+    if let Some(report) = report {
+        let unmatched: Vec<_> = result
+            .unmatched
+            .iter()
+            .map(|x| {
+                json!({
+                    "artist": x.artist,
+                    "title": x.title,
+                    "album": x.album,
+                    "plays": x.plays,
+                })
+            })
+            .collect();
+
+        let json = json!({
+            "total": result.total,
+            "imported": result.imported,
+            "duplicates": result.duplicates,
+            "unmatched": unmatched,
+        });
+
+        let written = File::create(report).and_then(|mut file| {
+            file.write_all(serde_json::to_string_pretty(&json).unwrap().as_bytes())
+        });
+
+        if let Err(e) = written {
+            eprintln!("Failed to write report: {}", e);
+        }
+    }
+}
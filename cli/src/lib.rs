@@ -1,4 +1,5 @@
 pub mod analysis;
+pub mod history;
 pub mod index;
 pub mod mix;
 pub mod playback;
@@ -12,6 +12,7 @@ use database::actions::metadata::{
 use database::actions::search::search_for;
 use database::connection::{connect_main_db, connect_recommendation_db};
 use rune::analysis::*;
+use rune::history::{import_history, ImportHistoryOptions};
 use rune::index::index_audio_library;
 use rune::mix::{mixes, RecommendMixOptions};
 use rune::playback::*;
@@ -115,6 +116,21 @@ enum Commands {
         #[arg(short, long, default_value_t = 10)]
         num: usize,
     },
+
+    /// Import listening history from exported files
+    ImportHistory {
+        /// The service the files were exported from (lastfm, listenbrainz or spotify)
+        #[arg(short, long)]
+        source: String,
+
+        /// The exported files
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+
+        /// Write the import report, including all unmatched tracks, to this JSON file
+        #[arg(short, long)]
+        report: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -282,5 +298,20 @@ async fn main() {
                 error!("Search failed: {}", e);
             }
         },
+        Commands::ImportHistory {
+            source,
+            files,
+            report,
+        } => {
+            import_history(
+                &main_db,
+                ImportHistoryOptions {
+                    source,
+                    files,
+                    report: report.as_ref(),
+                },
+            )
+            .await;
+        }
     }
 }
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -16,7 +17,9 @@ use tokio_util::sync::CancellationToken;
 use analysis::analysis::{analyze_audio, normalize_analysis_result, NormalizedAnalysisResult};
 use analysis::utils::computing_device::ComputingDevice;
 
-use crate::entities::{media_analysis, media_files};
+use crate::actions::file::get_track_range;
+use crate::actions::rhythm::insert_rhythm_result;
+use crate::entities::{media_analysis, media_files, media_loudness, media_rhythm};
 use crate::parallel_media_files_processing;
 
 pub fn empty_progress_callback(_processed: usize, _total: usize) {}
@@ -53,7 +56,7 @@ where
         batch_size
     );
 
-    let existed_ids: Vec<i32> = media_analysis::Entity::find()
+    let analyzed_ids: Vec<i32> = media_analysis::Entity::find()
         .select_only()
         .column(media_analysis::Column::FileId)
         .distinct()
@@ -61,6 +64,31 @@ where
         .all(main_db)
         .await?;
 
+    // Files analyzed before loudness measurement existed are analyzed again
+    let measured_ids: HashSet<i32> = media_loudness::Entity::find()
+        .select_only()
+        .column(media_loudness::Column::FileId)
+        .into_tuple::<i32>()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .collect();
+
+    // And so are files analyzed before tempo and key detection existed
+    let rhythm_ids: HashSet<i32> = media_rhythm::Entity::find()
+        .select_only()
+        .column(media_rhythm::Column::FileId)
+        .into_tuple::<i32>()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .collect();
+
+    let existed_ids: Vec<i32> = analyzed_ids
+        .into_iter()
+        .filter(|id| measured_ids.contains(id) && rhythm_ids.contains(id))
+        .collect();
+
     let cursor_query =
         media_files::Entity::find().filter(media_files::Column::Id.is_not_in(existed_ids));
 
@@ -111,9 +139,10 @@ fn analysis_file(
     // Construct the full path to the file
     let file_path = lib_path.join(&file.directory).join(&file.file_name);
 
-    // Perform audio analysis
+    // Perform audio analysis, tracks of a CUE sheet only cover a part of the file
     let analysis_result = analyze_audio(
         file_path.to_str().expect("Unable to convert file path"),
+        get_track_range(file),
         1024, // Example window size
         512,  // Example overlap size
         computing_device,
@@ -130,7 +159,9 @@ fn analysis_file(
     Ok(Some(normalize_analysis_result(&analysis_result)))
 }
 
-/// Insert the normalized analysis result into the database.
+/// Insert the normalized analysis result, the loudness measurement and the
+/// tempo and key into the database, replacing any previous result for the
+/// same file.
 ///
 /// # Arguments
 /// * `main_db` - A reference to the database connection.
@@ -170,10 +201,44 @@ async fn insert_analysis_result(
         new_analysis.mfcc~N = ActiveValue::Set(Decimal::from_f32(result.raw.mfcc[N]));
     });
 
+    media_analysis::Entity::delete_many()
+        .filter(media_analysis::Column::FileId.eq(file_id))
+        .exec(main_db)
+        .await?;
+
     media_analysis::Entity::insert(new_analysis)
         .exec(main_db)
         .await?;
 
+    let loudness = result.raw.loudness;
+    let new_loudness = media_loudness::ActiveModel {
+        file_id: ActiveValue::Set(file_id),
+        integrated_loudness: ActiveValue::Set(
+            loudness
+                .and_then(|x| x.integrated_loudness)
+                .and_then(Decimal::from_f64),
+        ),
+        true_peak: ActiveValue::Set(loudness.and_then(|x| Decimal::from_f64(x.true_peak))),
+        leading_silence: ActiveValue::Set(
+            loudness.and_then(|x| Decimal::from_f64(x.leading_silence)),
+        ),
+        trailing_silence: ActiveValue::Set(
+            loudness.and_then(|x| Decimal::from_f64(x.trailing_silence)),
+        ),
+        ..Default::default()
+    };
+
+    media_loudness::Entity::delete_many()
+        .filter(media_loudness::Column::FileId.eq(file_id))
+        .exec(main_db)
+        .await?;
+
+    media_loudness::Entity::insert(new_loudness)
+        .exec(main_db)
+        .await?;
+
+    insert_rhythm_result(main_db, file_id, result.raw.rhythm).await?;
+
     Ok(())
 }
 
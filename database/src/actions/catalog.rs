@@ -0,0 +1,517 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::SelectStatement;
+use sea_orm::{Condition, QueryOrder, QuerySelect, QueryTrait};
+
+use crate::actions::cover_art::get_magic_cover_art_id;
+use crate::entities::{
+    albums, artists, media_file_albums, media_file_artists, media_file_playlists, media_file_stats,
+    media_files, media_metadata, play_events, playlists,
+};
+
+/// An artist with the number of albums it appears on.
+#[derive(Debug, Clone)]
+pub struct ArtistSummary {
+    pub id: i32,
+    pub name: String,
+    pub album_count: usize,
+}
+
+/// An album with the totals of its tracks.
+#[derive(Debug, Clone, Default)]
+pub struct AlbumSummary {
+    pub id: i32,
+    pub name: String,
+    /// The artist of most tracks of the album
+    pub artist_id: Option<i32>,
+    pub artist: String,
+    pub song_count: usize,
+    pub duration: f64,
+    pub cover_art_id: Option<i32>,
+    /// Times any track of the album was played through
+    pub play_count: i64,
+    /// Start time of the latest play of the album, in RFC 3339
+    pub last_played: Option<String>,
+    /// The album has a liked track
+    pub liked: bool,
+}
+
+/// A playlist with the totals of its items.
+#[derive(Debug, Clone)]
+pub struct PlaylistSummary {
+    pub id: i32,
+    pub name: String,
+    pub song_count: usize,
+    pub duration: f64,
+    pub cover_art_id: Option<i32>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// The collections a track belongs to and how it was rated.
+#[derive(Debug, Clone, Default)]
+pub struct TrackLinks {
+    pub album_id: Option<i32>,
+    pub artist_ids: Vec<i32>,
+    pub liked: bool,
+    pub play_count: i32,
+    pub liked_at: Option<String>,
+}
+
+/// The part of a track a filter looks at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackField {
+    /// Any metadata value of the track
+    Any,
+    /// Path of the file, relative to the library
+    Path,
+    /// A directory containing the file, at any depth
+    Base,
+    /// The value of a metadata key, such as `artist` or `track_title`
+    Metadata(String),
+}
+
+/// Restricts tracks to those with a field matching a value.
+#[derive(Debug, Clone)]
+pub struct TrackFilter {
+    pub field: TrackField,
+    pub value: String,
+    /// Matches values containing `value`, ignoring ASCII case, rather than
+    /// values equal to it
+    pub partial: bool,
+    /// Keeps the tracks that do not match instead
+    pub negated: bool,
+}
+
+/// Lists every artist by name, with the number of albums it appears on.
+pub async fn get_artist_summaries(main_db: &DatabaseConnection) -> Result<Vec<ArtistSummary>> {
+    let artists = artists::Entity::find()
+        .order_by_asc(artists::Column::Name)
+        .all(main_db)
+        .await?;
+
+    let album_ids = get_artist_album_ids(main_db, None).await?;
+
+    Ok(artists
+        .into_iter()
+        .map(|artist| ArtistSummary {
+            album_count: album_ids.get(&artist.id).map_or(0, |x| x.len()),
+            id: artist.id,
+            name: artist.name,
+        })
+        .collect())
+}
+
+/// Lists the albums an artist appears on, by name.
+pub async fn get_album_ids_by_artist(
+    main_db: &DatabaseConnection,
+    artist_id: i32,
+) -> Result<Vec<i32>> {
+    let album_ids = get_artist_album_ids(main_db, Some(artist_id))
+        .await?
+        .remove(&artist_id)
+        .unwrap_or_default();
+
+    let albums = albums::Entity::find()
+        .filter(albums::Column::Id.is_in(album_ids))
+        .order_by_asc(albums::Column::Name)
+        .all(main_db)
+        .await?;
+
+    Ok(albums.into_iter().map(|x| x.id).collect())
+}
+
+async fn get_artist_album_ids(
+    main_db: &DatabaseConnection,
+    artist_id: Option<i32>,
+) -> Result<HashMap<i32, HashSet<i32>>> {
+    let mut query = media_file_artists::Entity::find();
+    if let Some(artist_id) = artist_id {
+        query = query.filter(media_file_artists::Column::ArtistId.eq(artist_id));
+    }
+    let artist_links = query.all(main_db).await?;
+
+    let file_ids: Vec<i32> = artist_links.iter().map(|x| x.media_file_id).collect();
+    let mut album_query = media_file_albums::Entity::find();
+    if artist_id.is_some() {
+        album_query = album_query.filter(media_file_albums::Column::MediaFileId.is_in(file_ids));
+    }
+    let file_albums: HashMap<i32, i32> = album_query
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.media_file_id, x.album_id))
+        .collect();
+
+    let mut result: HashMap<i32, HashSet<i32>> = HashMap::new();
+    for link in artist_links {
+        if let Some(album_id) = file_albums.get(&link.media_file_id) {
+            result.entry(link.artist_id).or_default().insert(*album_id);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Lists the tracks of an album, in no particular order.
+pub async fn get_track_ids_by_album(
+    main_db: &DatabaseConnection,
+    album_id: i32,
+) -> Result<Vec<i32>> {
+    let links = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::AlbumId.eq(album_id))
+        .all(main_db)
+        .await?;
+
+    Ok(links.into_iter().map(|x| x.media_file_id).collect())
+}
+
+/// Summarizes the given albums, or all albums if `album_ids` is `None`.
+/// Albums are returned by name.
+pub async fn get_album_summaries(
+    main_db: &DatabaseConnection,
+    album_ids: Option<Vec<i32>>,
+) -> Result<Vec<AlbumSummary>> {
+    let magic_cover_art_id = get_magic_cover_art_id(main_db).await;
+
+    let mut album_query = albums::Entity::find().order_by_asc(albums::Column::Name);
+    let mut link_query = media_file_albums::Entity::find();
+    if let Some(album_ids) = &album_ids {
+        album_query = album_query.filter(albums::Column::Id.is_in(album_ids.clone()));
+        link_query = link_query.filter(media_file_albums::Column::AlbumId.is_in(album_ids.clone()));
+    }
+
+    let albums = album_query.all(main_db).await?;
+    let album_links = link_query.all(main_db).await?;
+    let file_ids: Vec<i32> = album_links.iter().map(|x| x.media_file_id).collect();
+
+    let files: HashMap<i32, media_files::Model> = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(file_ids.clone()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+    let track_links = get_track_links(main_db, &file_ids).await?;
+    let last_played = get_last_played(main_db, &file_ids).await?;
+
+    let mut tracks_by_album: HashMap<i32, Vec<i32>> = HashMap::new();
+    for link in album_links {
+        tracks_by_album
+            .entry(link.album_id)
+            .or_default()
+            .push(link.media_file_id);
+    }
+
+    let mut artist_counts: HashMap<i32, HashMap<i32, usize>> = HashMap::new();
+    let mut summaries = Vec::with_capacity(albums.len());
+    for album in albums {
+        let mut summary = AlbumSummary {
+            id: album.id,
+            name: album.name,
+            ..Default::default()
+        };
+
+        for file_id in tracks_by_album.remove(&album.id).unwrap_or_default() {
+            let Some(file) = files.get(&file_id) else {
+                continue;
+            };
+
+            summary.song_count += 1;
+            summary.duration += file.duration.to_f64().unwrap_or_default();
+            if summary.cover_art_id.is_none() && file.cover_art_id != magic_cover_art_id {
+                summary.cover_art_id = file.cover_art_id;
+            }
+
+            if let Some(links) = track_links.get(&file_id) {
+                summary.play_count += links.play_count as i64;
+                summary.liked |= links.liked;
+                for artist_id in &links.artist_ids {
+                    *artist_counts
+                        .entry(album.id)
+                        .or_default()
+                        .entry(*artist_id)
+                        .or_default() += 1;
+                }
+            }
+
+            if let Some(started_at) = last_played.get(&file_id) {
+                if summary.last_played.as_ref() < Some(started_at) {
+                    summary.last_played = Some(started_at.clone());
+                }
+            }
+        }
+
+        summary.artist_id = artist_counts.get(&album.id).and_then(|counts| {
+            counts
+                .iter()
+                .max_by_key(|(id, count)| (**count, -**id))
+                .map(|(id, _)| *id)
+        });
+
+        summaries.push(summary);
+    }
+
+    let artist_ids: Vec<i32> = summaries.iter().filter_map(|x| x.artist_id).collect();
+    let artist_names: HashMap<i32, String> = artists::Entity::find()
+        .filter(artists::Column::Id.is_in(artist_ids))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.name))
+        .collect();
+
+    for summary in summaries.iter_mut() {
+        if let Some(name) = summary.artist_id.and_then(|id| artist_names.get(&id)) {
+            summary.artist = name.clone();
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Finds the album, artists and stats of each track.
+pub async fn get_track_links(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, TrackLinks>> {
+    let mut result: HashMap<i32, TrackLinks> = file_ids
+        .iter()
+        .map(|id| (*id, TrackLinks::default()))
+        .collect();
+
+    let album_links = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?;
+    for link in album_links {
+        if let Some(links) = result.get_mut(&link.media_file_id) {
+            links.album_id = Some(link.album_id);
+        }
+    }
+
+    let artist_links = media_file_artists::Entity::find()
+        .filter(media_file_artists::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .order_by_asc(media_file_artists::Column::Id)
+        .all(main_db)
+        .await?;
+    for link in artist_links {
+        if let Some(links) = result.get_mut(&link.media_file_id) {
+            links.artist_ids.push(link.artist_id);
+        }
+    }
+
+    let stats = media_file_stats::Entity::find()
+        .filter(media_file_stats::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?;
+    for stat in stats {
+        if let Some(links) = result.get_mut(&stat.media_file_id) {
+            links.liked = stat.liked;
+            links.play_count = stat.played_through;
+            links.liked_at = stat.liked.then_some(stat.updated_at);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Lists the liked tracks, the most recently rated first.
+pub async fn get_liked_track_ids(main_db: &DatabaseConnection) -> Result<Vec<i32>> {
+    let stats = media_file_stats::Entity::find()
+        .filter(media_file_stats::Column::Liked.eq(true))
+        .order_by_desc(media_file_stats::Column::UpdatedAt)
+        .all(main_db)
+        .await?;
+
+    Ok(stats.into_iter().map(|x| x.media_file_id).collect())
+}
+
+async fn get_last_played(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, String>> {
+    let rows: Vec<(i32, Option<String>)> = play_events::Entity::find()
+        .select_only()
+        .column(play_events::Column::MediaFileId)
+        .column_as(play_events::Column::StartedAt.max(), "started_at")
+        .filter(play_events::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .group_by(play_events::Column::MediaFileId)
+        .into_tuple()
+        .all(main_db)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, started_at)| started_at.map(|x| (id, x)))
+        .collect())
+}
+
+/// Summarizes the given playlists, or all playlists if `playlist_ids` is
+/// `None`. Playlists are returned by name.
+pub async fn get_playlist_summaries(
+    main_db: &DatabaseConnection,
+    playlist_ids: Option<Vec<i32>>,
+) -> Result<Vec<PlaylistSummary>> {
+    let magic_cover_art_id = get_magic_cover_art_id(main_db).await;
+
+    let mut playlist_query = playlists::Entity::find().order_by_asc(playlists::Column::Name);
+    let mut item_query =
+        media_file_playlists::Entity::find().order_by_asc(media_file_playlists::Column::Position);
+    if let Some(playlist_ids) = &playlist_ids {
+        playlist_query = playlist_query.filter(playlists::Column::Id.is_in(playlist_ids.clone()));
+        item_query =
+            item_query.filter(media_file_playlists::Column::PlaylistId.is_in(playlist_ids.clone()));
+    }
+
+    let playlists = playlist_query.all(main_db).await?;
+    let items = item_query.all(main_db).await?;
+
+    let file_ids: HashSet<i32> = items.iter().map(|x| x.media_file_id).collect();
+    let files: HashMap<i32, media_files::Model> = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(file_ids))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    let mut items_by_playlist: HashMap<i32, Vec<i32>> = HashMap::new();
+    for item in items {
+        items_by_playlist
+            .entry(item.playlist_id)
+            .or_default()
+            .push(item.media_file_id);
+    }
+
+    Ok(playlists
+        .into_iter()
+        .map(|playlist| {
+            let tracks: Vec<&media_files::Model> = items_by_playlist
+                .remove(&playlist.id)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|id| files.get(id))
+                .collect();
+
+            PlaylistSummary {
+                id: playlist.id,
+                name: playlist.name,
+                song_count: tracks.len(),
+                duration: tracks
+                    .iter()
+                    .map(|x| x.duration.to_f64().unwrap_or_default())
+                    .sum(),
+                cover_art_id: tracks
+                    .iter()
+                    .filter_map(|x| x.cover_art_id)
+                    .find(|x| Some(*x) != magic_cover_art_id),
+                created_at: playlist.created_at,
+                updated_at: playlist.updated_at,
+            }
+        })
+        .collect())
+}
+
+/// Finds the tracks matching every filter, ordered by path.
+pub async fn find_track_ids(
+    main_db: &DatabaseConnection,
+    filters: &[TrackFilter],
+) -> Result<Vec<i32>> {
+    let ids: Vec<i32> = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .filter(track_condition(filters))
+        .order_by_asc(media_files::Column::Directory)
+        .order_by_asc(media_files::Column::FileName)
+        .into_tuple()
+        .all(main_db)
+        .await?;
+
+    Ok(ids)
+}
+
+/// Lists the distinct values of a metadata key among the tracks matching
+/// every filter, sorted.
+pub async fn get_metadata_values(
+    main_db: &DatabaseConnection,
+    meta_key: &str,
+    filters: &[TrackFilter],
+) -> Result<Vec<String>> {
+    let files = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .filter(track_condition(filters))
+        .into_query();
+
+    let values: Vec<String> = media_metadata::Entity::find()
+        .select_only()
+        .column(media_metadata::Column::MetaValue)
+        .filter(media_metadata::Column::MetaKey.eq(meta_key))
+        .filter(media_metadata::Column::FileId.in_subquery(files))
+        .distinct()
+        .order_by_asc(media_metadata::Column::MetaValue)
+        .into_tuple()
+        .all(main_db)
+        .await?;
+
+    Ok(values)
+}
+
+fn track_condition(filters: &[TrackFilter]) -> Condition {
+    filters.iter().fold(Condition::all(), |condition, filter| {
+        let matched = match &filter.field {
+            TrackField::Any => Condition::all()
+                .add(media_files::Column::Id.in_subquery(metadata_query(None, filter))),
+            TrackField::Metadata(key) => Condition::all().add(
+                media_files::Column::Id.in_subquery(metadata_query(Some(key.as_str()), filter)),
+            ),
+            TrackField::Path if filter.partial => Condition::any()
+                .add(media_files::Column::Directory.contains(filter.value.as_str()))
+                .add(media_files::Column::FileName.contains(filter.value.as_str())),
+            TrackField::Path => {
+                let (directory, file_name) =
+                    filter.value.rsplit_once('/').unwrap_or(("", &filter.value));
+                Condition::all()
+                    .add(media_files::Column::Directory.eq(directory))
+                    .add(media_files::Column::FileName.eq(file_name))
+            }
+            TrackField::Base => {
+                let base = filter.value.trim_matches('/');
+                if base.is_empty() {
+                    Condition::all()
+                } else {
+                    Condition::any()
+                        .add(media_files::Column::Directory.eq(base))
+                        .add(media_files::Column::Directory.starts_with(format!("{}/", base)))
+                }
+            }
+        };
+
+        condition.add(if filter.negated {
+            matched.not()
+        } else {
+            matched
+        })
+    })
+}
+
+fn metadata_query(meta_key: Option<&str>, filter: &TrackFilter) -> SelectStatement {
+    let mut query = media_metadata::Entity::find()
+        .select_only()
+        .column(media_metadata::Column::FileId);
+    if let Some(meta_key) = meta_key {
+        query = query.filter(media_metadata::Column::MetaKey.eq(meta_key));
+    }
+
+    let value = if filter.partial {
+        media_metadata::Column::MetaValue.contains(filter.value.as_str())
+    } else {
+        media_metadata::Column::MetaValue.eq(filter.value.as_str())
+    };
+
+    query.filter(value).into_query()
+}
@@ -4,6 +4,7 @@ use anyhow::Result;
 use async_trait::async_trait;
 use thiserror::Error;
 
+use crate::actions::mixes::GroupedQuery;
 use crate::connection::MainDbConnection;
 
 #[derive(Debug, Clone)]
@@ -55,6 +56,7 @@ impl FromStr for CollectionQueryType {
             "album" => Ok(CollectionQueryType::Album),
             "playlist" => Ok(CollectionQueryType::Playlist),
             "mix" => Ok(CollectionQueryType::Mix),
+            "genre" => Ok(CollectionQueryType::Genre),
             _ => Err(ParseCollectionTypeError::InvalidType),
         }
     }
@@ -78,7 +80,7 @@ impl fmt::Display for CollectionQueryType {
 pub struct UnifiedCollection {
     pub id: i32,
     pub name: String,
-    pub queries: Vec<(String, String)>,
+    pub queries: Vec<GroupedQuery>,
     pub collection_type: CollectionQueryType,
     pub readonly: bool,
 }
@@ -134,7 +136,7 @@ impl FromStr for CollectionQueryListMode {
 #[async_trait]
 pub trait CollectionQuery: Send + Sync + 'static {
     fn collection_type() -> CollectionQueryType;
-    async fn query_builder(main_db: &MainDbConnection, id: i32) -> Result<Vec<(String, String)>>;
+    async fn query_builder(main_db: &MainDbConnection, id: i32) -> Result<Vec<GroupedQuery>>;
     async fn count_by_first_letter(main_db: &MainDbConnection) -> Result<Vec<(String, i32)>>;
     async fn get_groups(
         main_db: &MainDbConnection,
@@ -234,8 +236,12 @@ macro_rules! collection_query {
             async fn query_builder(
                 _main_db: &MainDbConnection,
                 id: i32,
-            ) -> Result<Vec<(String, String)>> {
-                Ok([($query_operator, id.to_string())].to_vec())
+            ) -> Result<Vec<$crate::actions::mixes::GroupedQuery>> {
+                Ok(vec![$crate::actions::mixes::GroupedQuery::new(
+                    $query_operator,
+                    id.to_string(),
+                    0,
+                )])
             }
 
             async fn count_by_first_letter(
@@ -0,0 +1,917 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use log::{error, info, warn};
+use rust_decimal::prelude::ToPrimitive;
+use rusty_chromaprint::Configuration;
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveValue, TransactionTrait};
+use tokio_util::sync::CancellationToken;
+
+use tag_editor::music_brainz::fingerprint::calc_fingerprint;
+
+use crate::actions::analysis::AggregatedAnalysisResult;
+use crate::actions::collection::CollectionQueryType;
+use crate::actions::search::remove_term;
+use crate::actions::utils::LOSSLESS_EXTENSIONS;
+use crate::entities::{
+    media_analysis, media_file_playlists, media_file_stats, media_files, mix_queries, play_events,
+    playback_queue,
+};
+
+/// Seconds two files may differ by to be compared by their audio.
+const DURATION_TOLERANCE: f64 = 2.0;
+
+/// Share of matching fingerprint bits above which two files are considered
+/// the same recording. Unrelated recordings match about half of the bits.
+const FINGERPRINT_SIMILARITY_THRESHOLD: f64 = 0.9;
+
+/// Fingerprint items two recordings may be shifted by, one item covers
+/// roughly 0.12 seconds.
+const FINGERPRINT_MAX_OFFSET: usize = 16;
+
+/// Fingerprints shorter than this are too short to be compared.
+const FINGERPRINT_MIN_LENGTH: usize = 50;
+
+/// Mean relative difference of the analysis features below which two files
+/// are considered the same recording.
+const ANALYSIS_DIFFERENCE_THRESHOLD: f32 = 0.02;
+
+/// Directory below the library root that merged duplicates are moved to.
+/// Scans and the library watcher skip `.rune`, so they are not added back.
+const MERGED_DUPLICATES_DIR: &str = ".rune/duplicates";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DuplicateMatch {
+    FileHash,
+    Fingerprint,
+    Analysis,
+}
+
+/// Files that hold the same recording.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub matched_by: DuplicateMatch,
+    /// The recommended file to keep, picked by codec and bitrate
+    pub keeper_id: i32,
+    pub file_ids: Vec<i32>,
+}
+
+/// Ranks files by whether they are lossless, their bitrate and their sample
+/// rate.
+fn quality_score(lib_path: &Path, file: &media_files::Model) -> (bool, u64, i32) {
+    let is_lossless = LOSSLESS_EXTENSIONS.contains(&file.extension.to_lowercase().as_str());
+
+    let duration = file.duration.to_f64().unwrap_or_default();
+    let size = lib_path
+        .join(&file.directory)
+        .join(&file.file_name)
+        .metadata()
+        .map(|x| x.len())
+        .unwrap_or_default();
+    let bitrate = if duration > 0.0 {
+        (size as f64 * 8.0 / duration) as u64
+    } else {
+        0
+    };
+
+    (is_lossless, bitrate, file.sample_rate)
+}
+
+/// Picks the file with the best quality, the oldest one wins ties.
+fn pick_keeper(lib_path: &Path, files: &[&media_files::Model]) -> i32 {
+    files
+        .iter()
+        .max_by(|a, b| {
+            quality_score(lib_path, a)
+                .cmp(&quality_score(lib_path, b))
+                .then(b.id.cmp(&a.id))
+        })
+        .map(|x| x.id)
+        .unwrap_or_default()
+}
+
+fn find_root(parents: &mut [usize], x: usize) -> usize {
+    let mut root = x;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[x] = root;
+    root
+}
+
+/// Groups files whose durations are close and that `is_same` considers
+/// equal. `files` must be sorted by duration. Returns groups of indices
+/// with at least two members.
+fn cluster_by_duration<F>(files: &[&media_files::Model], mut is_same: F) -> Vec<Vec<usize>>
+where
+    F: FnMut(&media_files::Model, &media_files::Model) -> bool,
+{
+    let durations: Vec<f64> = files
+        .iter()
+        .map(|x| x.duration.to_f64().unwrap_or_default())
+        .collect();
+    let mut parents: Vec<usize> = (0..files.len()).collect();
+
+    for i in 0..files.len() {
+        for j in (i + 1)..files.len() {
+            if durations[j] - durations[i] > DURATION_TOLERANCE {
+                break;
+            }
+
+            if find_root(&mut parents, i) == find_root(&mut parents, j) {
+                continue;
+            }
+
+            if is_same(files[i], files[j]) {
+                let root_i = find_root(&mut parents, i);
+                let root_j = find_root(&mut parents, j);
+                parents[root_j] = root_i;
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..files.len() {
+        let root = find_root(&mut parents, i);
+        clusters.entry(root).or_default().push(i);
+    }
+
+    let mut clusters: Vec<Vec<usize>> = clusters.into_values().filter(|x| x.len() > 1).collect();
+    clusters.sort();
+    clusters
+}
+
+/// Compares two raw fingerprints bit by bit, allowing a small shift between
+/// them to cover different amounts of leading silence.
+fn fingerprint_similarity(a: &[u32], b: &[u32]) -> f64 {
+    let mut best: f64 = 0.0;
+
+    for offset in 0..=FINGERPRINT_MAX_OFFSET {
+        for (x, y) in [(a, b), (b, a)] {
+            if offset >= x.len() {
+                continue;
+            }
+
+            let x = &x[offset..];
+            let len = x.len().min(y.len());
+            if len < FINGERPRINT_MIN_LENGTH {
+                continue;
+            }
+
+            let errors: u32 = x
+                .iter()
+                .zip(y.iter())
+                .map(|(p, q)| (p ^ q).count_ones())
+                .sum();
+            let similarity = 1.0 - errors as f64 / (len as f64 * 32.0);
+            best = best.max(similarity);
+        }
+    }
+
+    best
+}
+
+fn analysis_difference(a: &[f32; 61], b: &[f32; 61]) -> f32 {
+    let total: f32 = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x - y).abs() / (x.abs() + y.abs() + f32::EPSILON))
+        .sum();
+
+    total / a.len() as f32
+}
+
+fn is_cancelled(cancel_token: Option<&CancellationToken>) -> bool {
+    cancel_token.map(|x| x.is_cancelled()).unwrap_or(false)
+}
+
+/// Turns clusters of `files` into duplicate groups and returns the files
+/// that take part in the next pass: every ungrouped file and the keeper of
+/// every group.
+fn collect_groups<'a>(
+    lib_path: &Path,
+    files: &[&'a media_files::Model],
+    clusters: Vec<Vec<usize>>,
+    matched_by: DuplicateMatch,
+    groups: &mut Vec<DuplicateGroup>,
+) -> Vec<&'a media_files::Model> {
+    let mut merged: HashSet<i32> = HashSet::new();
+
+    for cluster in clusters {
+        let members: Vec<&media_files::Model> = cluster.iter().map(|&i| files[i]).collect();
+        let keeper_id = pick_keeper(lib_path, &members);
+
+        merged.extend(members.iter().map(|x| x.id).filter(|&id| id != keeper_id));
+        groups.push(DuplicateGroup {
+            matched_by,
+            keeper_id,
+            file_ids: members.iter().map(|x| x.id).collect(),
+        });
+    }
+
+    files
+        .iter()
+        .filter(|x| !merged.contains(&x.id))
+        .copied()
+        .collect()
+}
+
+/// Finds files that hold the same recording.
+///
+/// Files are matched by their hash first, then by their Chromaprint
+/// fingerprint and finally by their analysis results. A group found by one
+/// pass is represented by its keeper in the following passes. Only files
+/// with a similar duration are compared by their audio, CUE tracks are
+/// skipped since they share a file.
+pub async fn find_duplicate_files(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    cancel_token: Option<&CancellationToken>,
+) -> Result<Vec<DuplicateGroup>> {
+    let files = media_files::Entity::find()
+        .filter(media_files::Column::CueTrack.is_null())
+        .all(main_db)
+        .await?;
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+
+    info!("Finding duplicates by file hash");
+    let mut by_hash: HashMap<&str, Vec<&media_files::Model>> = HashMap::new();
+    for file in files.iter() {
+        by_hash.entry(&file.file_hash).or_default().push(file);
+    }
+
+    let mut remaining: Vec<&media_files::Model> = Vec::new();
+    for (_, members) in by_hash {
+        if members.len() == 1 {
+            remaining.push(members[0]);
+            continue;
+        }
+
+        let keeper_id = pick_keeper(lib_path, &members);
+        remaining.extend(members.iter().find(|x| x.id == keeper_id));
+        groups.push(DuplicateGroup {
+            matched_by: DuplicateMatch::FileHash,
+            keeper_id,
+            file_ids: members.iter().map(|x| x.id).collect(),
+        });
+    }
+
+    remaining.sort_by(|a, b| a.duration.cmp(&b.duration).then(a.id.cmp(&b.id)));
+
+    info!("Finding duplicates by fingerprint");
+    let config = Configuration::default();
+    let mut fingerprints: HashMap<i32, Option<Vec<u32>>> = HashMap::new();
+    let mut fingerprint_of = |file: &media_files::Model| -> Option<Vec<u32>> {
+        fingerprints
+            .entry(file.id)
+            .or_insert_with(|| {
+                let path = lib_path.join(&file.directory).join(&file.file_name);
+                match calc_fingerprint(&path, &config) {
+                    Ok((fingerprint, _)) => Some(fingerprint),
+                    Err(e) => {
+                        warn!("Failed to fingerprint {}: {:?}", path.display(), e);
+                        None
+                    }
+                }
+            })
+            .clone()
+    };
+
+    let clusters = cluster_by_duration(&remaining, |a, b| {
+        if is_cancelled(cancel_token) {
+            return false;
+        }
+
+        match (fingerprint_of(a), fingerprint_of(b)) {
+            (Some(a), Some(b)) => {
+                fingerprint_similarity(&a, &b) >= FINGERPRINT_SIMILARITY_THRESHOLD
+            }
+            _ => false,
+        }
+    });
+
+    if is_cancelled(cancel_token) {
+        info!("Duplicate detection cancelled.");
+        return Ok(groups);
+    }
+
+    let remaining = collect_groups(
+        lib_path,
+        &remaining,
+        clusters,
+        DuplicateMatch::Fingerprint,
+        &mut groups,
+    );
+
+    info!("Finding duplicates by analysis results");
+    let remaining_ids: Vec<i32> = remaining.iter().map(|x| x.id).collect();
+    let vectors: HashMap<i32, [f32; 61]> = media_analysis::Entity::find()
+        .filter(media_analysis::Column::FileId.is_in(remaining_ids))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| {
+            let file_id = x.file_id;
+            let result: AggregatedAnalysisResult = x.into();
+            (file_id, result.into())
+        })
+        .collect();
+
+    let clusters = cluster_by_duration(&remaining, |a, b| {
+        match (vectors.get(&a.id), vectors.get(&b.id)) {
+            (Some(a), Some(b)) => analysis_difference(a, b) <= ANALYSIS_DIFFERENCE_THRESHOLD,
+            _ => false,
+        }
+    });
+
+    collect_groups(
+        lib_path,
+        &remaining,
+        clusters,
+        DuplicateMatch::Analysis,
+        &mut groups,
+    );
+
+    Ok(groups)
+}
+
+/// Picks a path below `.rune/duplicates` for a merged file, keeping its
+/// directory. Files merged earlier under the same name are not replaced.
+fn merged_duplicate_path(lib_path: &Path, file: &media_files::Model) -> PathBuf {
+    let directory = lib_path.join(MERGED_DUPLICATES_DIR).join(&file.directory);
+    let file_name = Path::new(&file.file_name);
+    let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+
+    let mut path = directory.join(file_name);
+    let mut index = 2;
+    while path.exists() {
+        path = match file_name.extension() {
+            Some(extension) => directory.join(format!(
+                "{} ({}).{}",
+                stem,
+                index,
+                extension.to_string_lossy()
+            )),
+            None => directory.join(format!("{} ({})", stem, index)),
+        };
+        index += 1;
+    }
+
+    path
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)?;
+
+    Ok(())
+}
+
+/// Puts moved files back where they were, after the merge failed.
+fn restore_moved_files(moved: &[(PathBuf, PathBuf)]) {
+    for (original, merged) in moved.iter().rev() {
+        if let Err(e) = fs::rename(merged, original) {
+            error!(
+                "Failed to restore {} from {}: {:?}",
+                original.display(),
+                merged.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Merges duplicates into the file that is kept.
+///
+/// Play counts are summed up, likes, playlist memberships, queue entries and
+/// mix queries move to the kept file, then the duplicate rows are removed.
+/// The duplicate files are moved to `.rune/duplicates` below the library
+/// root, keeping their directory, so later scans don't add them back.
+/// Deleting them for good is up to the user. When a file can not be moved,
+/// nothing is merged.
+///
+/// CUE tracks share their file with the other tracks of the sheet, they are
+/// never merged.
+pub async fn merge_duplicate_files(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    keeper_id: i32,
+    duplicate_ids: &[i32],
+) -> Result<()> {
+    let duplicate_ids: HashSet<i32> = duplicate_ids
+        .iter()
+        .copied()
+        .filter(|&id| id != keeper_id)
+        .collect();
+
+    if duplicate_ids.is_empty() {
+        return Ok(());
+    }
+
+    let txn = main_db.begin().await?;
+
+    match media_files::Entity::find_by_id(keeper_id).one(&txn).await? {
+        Some(keeper) if keeper.cue_track.is_some() => bail!("CUE tracks can not be merged"),
+        Some(_) => {}
+        None => bail!("File to keep not found"),
+    }
+
+    let duplicates: Vec<media_files::Model> = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(duplicate_ids))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .filter(|file| {
+            if file.cue_track.is_some() {
+                warn!("Not merging CUE track {}", file.id);
+            }
+            file.cue_track.is_none()
+        })
+        .collect();
+
+    let mut duplicate_ids: Vec<i32> = duplicates.iter().map(|x| x.id).collect();
+    duplicate_ids.sort();
+
+    if duplicate_ids.is_empty() {
+        return Ok(());
+    }
+
+    // Stats
+    let stats = media_file_stats::Entity::find()
+        .filter(
+            media_file_stats::Column::MediaFileId
+                .is_in(duplicate_ids.iter().copied().chain([keeper_id])),
+        )
+        .all(&txn)
+        .await?;
+
+    if !stats.is_empty() {
+        let liked = stats.iter().any(|x| x.liked);
+        let skipped: i32 = stats.iter().map(|x| x.skipped).sum();
+        let played_through: i32 = stats.iter().map(|x| x.played_through).sum();
+
+        media_file_stats::Entity::delete_many()
+            .filter(media_file_stats::Column::MediaFileId.is_in(duplicate_ids.clone()))
+            .exec(&txn)
+            .await?;
+
+        match stats.into_iter().find(|x| x.media_file_id == keeper_id) {
+            Some(keeper_stats) => {
+                let mut active_model: media_file_stats::ActiveModel = keeper_stats.into();
+                active_model.liked = ActiveValue::Set(liked);
+                active_model.skipped = ActiveValue::Set(skipped);
+                active_model.played_through = ActiveValue::Set(played_through);
+                active_model.updated_at = ActiveValue::Set(Utc::now().to_rfc3339());
+                active_model.update(&txn).await?;
+            }
+            None => {
+                let new_stats = media_file_stats::ActiveModel {
+                    media_file_id: ActiveValue::Set(keeper_id),
+                    liked: ActiveValue::Set(liked),
+                    skipped: ActiveValue::Set(skipped),
+                    played_through: ActiveValue::Set(played_through),
+                    updated_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+                    ..Default::default()
+                };
+                new_stats.insert(&txn).await?;
+            }
+        }
+    }
+
+    // Playlists, a playlist that already holds the kept file only loses the
+    // duplicate entry
+    let mut keeper_playlists: HashSet<i32> = media_file_playlists::Entity::find()
+        .filter(media_file_playlists::Column::MediaFileId.eq(keeper_id))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|x| x.playlist_id)
+        .collect();
+
+    let playlist_items = media_file_playlists::Entity::find()
+        .filter(media_file_playlists::Column::MediaFileId.is_in(duplicate_ids.clone()))
+        .all(&txn)
+        .await?;
+
+    for item in playlist_items {
+        if keeper_playlists.insert(item.playlist_id) {
+            let mut active_model: media_file_playlists::ActiveModel = item.into();
+            active_model.media_file_id = ActiveValue::Set(keeper_id);
+            active_model.update(&txn).await?;
+        } else {
+            item.delete(&txn).await?;
+        }
+    }
+
+    // Play history, removing the duplicates would cascade to their events
+    play_events::Entity::update_many()
+        .col_expr(play_events::Column::MediaFileId, Expr::value(keeper_id))
+        .filter(play_events::Column::MediaFileId.is_in(duplicate_ids.clone()))
+        .exec(&txn)
+        .await?;
+
+    // Playback queue
+    playback_queue::Entity::update_many()
+        .col_expr(playback_queue::Column::MediaFileId, Expr::value(keeper_id))
+        .filter(playback_queue::Column::MediaFileId.is_in(duplicate_ids.clone()))
+        .exec(&txn)
+        .await?;
+
+    // Mixes that reference a single track
+    mix_queries::Entity::update_many()
+        .col_expr(
+            mix_queries::Column::Parameter,
+            Expr::value(keeper_id.to_string()),
+        )
+        .filter(mix_queries::Column::Operator.eq("lib::track"))
+        .filter(mix_queries::Column::Parameter.is_in(duplicate_ids.iter().map(|x| x.to_string())))
+        .exec(&txn)
+        .await?;
+
+    for duplicate in duplicates.iter() {
+        media_files::Entity::delete_by_id(duplicate.id)
+            .exec(&txn)
+            .await?;
+        remove_term(&txn, CollectionQueryType::Track, duplicate.id).await?;
+    }
+
+    // Files are moved last, so the rows stay when a move fails. Files that
+    // are already gone only lose their row.
+    let mut moved: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for duplicate in duplicates.iter() {
+        let path = lib_path
+            .join(&duplicate.directory)
+            .join(&duplicate.file_name);
+        if !path.exists() {
+            continue;
+        }
+
+        let merged_path = merged_duplicate_path(lib_path, duplicate);
+        if let Err(e) = move_file(&path, &merged_path) {
+            restore_moved_files(&moved);
+            return Err(e.context(format!("Failed to move duplicate file: {}", path.display())));
+        }
+        moved.push((path, merged_path));
+    }
+
+    if let Err(e) = txn.commit().await {
+        restore_moved_files(&moved);
+        return Err(e.into());
+    }
+
+    info!(
+        "Merged {} duplicates into file {}",
+        duplicates.len(),
+        keeper_id
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::prelude::FromPrimitive;
+
+    use crate::connection::connect_test_main_db;
+    use crate::entities::playlists;
+
+    use super::*;
+
+    fn file(id: i32, extension: &str, duration: f64, sample_rate: i32) -> media_files::Model {
+        media_files::Model {
+            id,
+            file_name: format!("{}.{}", id, extension),
+            directory: "Artist/Album".to_string(),
+            extension: extension.to_string(),
+            file_hash: format!("hash-{}", id),
+            last_modified: "2024-01-01T00:00:00+00:00".to_string(),
+            cover_art_id: None,
+            sample_rate,
+            duration: Decimal::from_f64(duration).unwrap(),
+            cue_track: None,
+            start_offset: None,
+            end_offset: None,
+        }
+    }
+
+    async fn insert_file(db: &DatabaseConnection, file: &media_files::Model) {
+        media_files::ActiveModel {
+            id: ActiveValue::Set(file.id),
+            file_name: ActiveValue::Set(file.file_name.clone()),
+            directory: ActiveValue::Set(file.directory.clone()),
+            extension: ActiveValue::Set(file.extension.clone()),
+            file_hash: ActiveValue::Set(file.file_hash.clone()),
+            last_modified: ActiveValue::Set(file.last_modified.clone()),
+            cover_art_id: ActiveValue::Set(None),
+            sample_rate: ActiveValue::Set(file.sample_rate),
+            duration: ActiveValue::Set(file.duration),
+            cue_track: ActiveValue::Set(file.cue_track),
+            start_offset: ActiveValue::Set(file.start_offset),
+            end_offset: ActiveValue::Set(file.end_offset),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_stats(db: &DatabaseConnection, file_id: i32, liked: bool, played: i32) {
+        media_file_stats::ActiveModel {
+            media_file_id: ActiveValue::Set(file_id),
+            liked: ActiveValue::Set(liked),
+            skipped: ActiveValue::Set(1),
+            played_through: ActiveValue::Set(played),
+            updated_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_playlist_item(db: &DatabaseConnection, playlist_id: i32, file_id: i32) {
+        media_file_playlists::ActiveModel {
+            playlist_id: ActiveValue::Set(playlist_id),
+            media_file_id: ActiveValue::Set(file_id),
+            position: ActiveValue::Set(0),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    /// Writes the files of `files` below a new library root.
+    fn create_library(files: &[&media_files::Model]) -> tempfile::TempDir {
+        let lib_path = tempfile::tempdir().unwrap();
+        for file in files {
+            let directory = lib_path.path().join(&file.directory);
+            fs::create_dir_all(&directory).unwrap();
+            fs::write(directory.join(&file.file_name), file.file_hash.as_bytes()).unwrap();
+        }
+        lib_path
+    }
+
+    #[test]
+    fn test_fingerprint_similarity() {
+        let a: Vec<u32> = (0..100)
+            .map(|x: u32| x.wrapping_mul(2_654_435_761))
+            .collect();
+
+        assert_eq!(fingerprint_similarity(&a, &a), 1.0);
+        // Leading silence shifts the fingerprint
+        assert_eq!(fingerprint_similarity(&a, &a[3..]), 1.0);
+        assert_eq!(fingerprint_similarity(&[0; 100], &[u32::MAX; 100]), 0.0);
+        // Too short to be compared
+        assert_eq!(fingerprint_similarity(&a[..20], &a[..20]), 0.0);
+    }
+
+    #[test]
+    fn test_analysis_difference() {
+        let a = [1.0; 61];
+        let mut b = [1.0; 61];
+
+        assert_eq!(analysis_difference(&a, &b), 0.0);
+
+        b[0] = 3.0;
+        let difference = analysis_difference(&a, &b);
+        assert!((difference - 0.5 / 61.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_cluster_by_duration() {
+        let files = [
+            file(1, "mp3", 100.0, 44100),
+            file(2, "mp3", 101.0, 44100),
+            file(3, "mp3", 103.5, 44100),
+            file(4, "mp3", 200.0, 44100),
+        ];
+        let files: Vec<&media_files::Model> = files.iter().collect();
+
+        // Only files with a close duration are compared
+        let mut compared = Vec::new();
+        let clusters = cluster_by_duration(&files, |a, b| {
+            compared.push((a.id, b.id));
+            true
+        });
+        assert_eq!(clusters, vec![vec![0, 1]]);
+        assert_eq!(compared, vec![(1, 2)]);
+
+        assert!(cluster_by_duration(&files, |_, _| false).is_empty());
+    }
+
+    #[test]
+    fn test_cluster_by_duration_is_transitive() {
+        let files = [
+            file(1, "mp3", 100.0, 44100),
+            file(2, "mp3", 101.5, 44100),
+            file(3, "mp3", 103.0, 44100),
+        ];
+        let files: Vec<&media_files::Model> = files.iter().collect();
+
+        let clusters = cluster_by_duration(&files, |_, _| true);
+        assert_eq!(clusters, vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn test_pick_keeper() {
+        let lib_path = Path::new("/nonexistent");
+        let mp3 = file(1, "mp3", 100.0, 44100);
+        let flac = file(2, "FLAC", 100.0, 44100);
+        let high_rate = file(3, "mp3", 100.0, 48000);
+        let same_as_mp3 = file(4, "mp3", 100.0, 44100);
+
+        assert_eq!(pick_keeper(lib_path, &[&mp3, &flac, &high_rate]), 2);
+        assert_eq!(pick_keeper(lib_path, &[&mp3, &high_rate]), 3);
+        // The oldest file wins ties
+        assert_eq!(pick_keeper(lib_path, &[&same_as_mp3, &mp3]), 1);
+    }
+
+    #[test]
+    fn test_collect_groups() {
+        let lib_path = Path::new("/nonexistent");
+        let files = [
+            file(1, "mp3", 100.0, 44100),
+            file(2, "flac", 100.0, 44100),
+            file(3, "mp3", 150.0, 44100),
+        ];
+        let files: Vec<&media_files::Model> = files.iter().collect();
+
+        let mut groups = Vec::new();
+        let remaining = collect_groups(
+            lib_path,
+            &files,
+            vec![vec![0, 1]],
+            DuplicateMatch::Fingerprint,
+            &mut groups,
+        );
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].matched_by, DuplicateMatch::Fingerprint);
+        assert_eq!(groups[0].keeper_id, 2);
+        assert_eq!(groups[0].file_ids, vec![1, 2]);
+        // The keeper stands for its group in the next pass
+        let remaining: Vec<i32> = remaining.iter().map(|x| x.id).collect();
+        assert_eq!(remaining, vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_duplicate_files() {
+        let db = connect_test_main_db().await;
+
+        let keeper = file(1, "flac", 100.0, 44100);
+        let duplicate = file(2, "mp3", 100.0, 44100);
+        let mut cue_track = file(3, "flac", 100.0, 44100);
+        cue_track.cue_track = Some(1);
+        for file in [&keeper, &duplicate, &cue_track] {
+            insert_file(&db, file).await;
+        }
+        let lib_path = create_library(&[&keeper, &duplicate, &cue_track]);
+        let album_path = lib_path.path().join("Artist/Album");
+        // A duplicate of the same name merged earlier
+        let merged_path = lib_path.path().join(".rune/duplicates/Artist/Album");
+        fs::create_dir_all(&merged_path).unwrap();
+        fs::write(merged_path.join("2.mp3"), "earlier").unwrap();
+
+        insert_stats(&db, 1, false, 2).await;
+        insert_stats(&db, 2, true, 3).await;
+
+        for name in ["Both", "Duplicate only"] {
+            playlists::ActiveModel {
+                name: ActiveValue::Set(name.to_string()),
+                group: ActiveValue::Set(String::new()),
+                created_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+                updated_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        insert_playlist_item(&db, 1, 1).await;
+        insert_playlist_item(&db, 1, 2).await;
+        insert_playlist_item(&db, 2, 2).await;
+
+        playback_queue::ActiveModel {
+            media_file_id: ActiveValue::Set(2),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        play_events::ActiveModel {
+            media_file_id: ActiveValue::Set(2),
+            started_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+            listened_duration: ActiveValue::Set(Decimal::from(90)),
+            completion_ratio: ActiveValue::Set(Decimal::from_f64(0.9).unwrap()),
+            source: ActiveValue::Set(None),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        merge_duplicate_files(&db, lib_path.path(), 1, &[1, 2, 3])
+            .await
+            .unwrap();
+
+        let ids: Vec<i32> = media_files::Entity::find()
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        // CUE tracks are never merged
+        assert_eq!(ids, vec![1, 3]);
+
+        let stats = media_file_stats::Entity::find().all(&db).await.unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].media_file_id, 1);
+        assert!(stats[0].liked);
+        assert_eq!(stats[0].skipped, 2);
+        assert_eq!(stats[0].played_through, 5);
+
+        let mut items: Vec<(i32, i32)> = media_file_playlists::Entity::find()
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.playlist_id, x.media_file_id))
+            .collect();
+        items.sort();
+        assert_eq!(items, vec![(1, 1), (2, 1)]);
+
+        let queue = playback_queue::Entity::find().all(&db).await.unwrap();
+        assert_eq!(queue[0].media_file_id, 1);
+
+        let events = play_events::Entity::find().all(&db).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].media_file_id, 1);
+
+        // The duplicate is set aside, next to the one merged earlier
+        assert!(album_path.join("1.flac").exists());
+        assert!(album_path.join("3.flac").exists());
+        assert!(!album_path.join("2.mp3").exists());
+        assert_eq!(
+            fs::read_to_string(merged_path.join("2 (2).mp3")).unwrap(),
+            "hash-2"
+        );
+        assert_eq!(
+            fs::read_to_string(merged_path.join("2.mp3")).unwrap(),
+            "earlier"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_move_merges_nothing() {
+        let db = connect_test_main_db().await;
+
+        let files = [
+            file(1, "flac", 100.0, 44100),
+            file(2, "mp3", 100.0, 44100),
+            file(3, "mp3", 100.0, 44100),
+        ];
+        for file in files.iter() {
+            insert_file(&db, file).await;
+        }
+        let lib_path = create_library(&[&files[0], &files[1], &files[2]]);
+        // The directory of the merged files can not be created
+        fs::write(lib_path.path().join(".rune"), "").unwrap();
+
+        assert!(merge_duplicate_files(&db, lib_path.path(), 1, &[2, 3])
+            .await
+            .is_err());
+        assert_eq!(media_files::Entity::find().all(&db).await.unwrap().len(), 3);
+        for file in files.iter() {
+            assert!(lib_path
+                .path()
+                .join(&file.directory)
+                .join(&file.file_name)
+                .exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merge_into_cue_track_fails() {
+        let db = connect_test_main_db().await;
+
+        let mut cue_track = file(1, "flac", 100.0, 44100);
+        cue_track.cue_track = Some(1);
+        insert_file(&db, &cue_track).await;
+        insert_file(&db, &file(2, "mp3", 100.0, 44100)).await;
+
+        let lib_path = tempfile::tempdir().unwrap();
+        assert!(merge_duplicate_files(&db, lib_path.path(), 1, &[2])
+            .await
+            .is_err());
+        assert_eq!(media_files::Entity::find().all(&db).await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveValue, QueryOrder, TransactionTrait};
+
+use playback::dsp::EqualizerBand;
+
+use crate::entities::{equalizer_preset_bands, equalizer_presets};
+
+/// A stored equalizer preset with its bands in order.
+#[derive(Debug, Clone)]
+pub struct EqualizerPreset {
+    pub id: i32,
+    pub name: String,
+    pub preamp: f32,
+    pub bands: Vec<EqualizerBand>,
+}
+
+fn to_decimal(x: f32) -> Decimal {
+    Decimal::from_f32(x).unwrap_or_default()
+}
+
+fn from_decimal(x: Decimal) -> f32 {
+    x.to_f32().unwrap_or_default()
+}
+
+fn band_from_model(model: &equalizer_preset_bands::Model) -> EqualizerBand {
+    EqualizerBand {
+        filter_type: (model.filter_type as u32).into(),
+        frequency: from_decimal(model.frequency),
+        gain: from_decimal(model.gain),
+        q: from_decimal(model.q),
+    }
+}
+
+async fn get_bands_by_preset_id<C>(db: &C, preset_id: i32) -> Result<Vec<EqualizerBand>>
+where
+    C: ConnectionTrait,
+{
+    Ok(equalizer_preset_bands::Entity::find()
+        .filter(equalizer_preset_bands::Column::PresetId.eq(preset_id))
+        .order_by_asc(equalizer_preset_bands::Column::Position)
+        .all(db)
+        .await?
+        .iter()
+        .map(band_from_model)
+        .collect())
+}
+
+async fn replace_bands<C>(db: &C, preset_id: i32, bands: &[EqualizerBand]) -> Result<()>
+where
+    C: ConnectionTrait,
+{
+    equalizer_preset_bands::Entity::delete_many()
+        .filter(equalizer_preset_bands::Column::PresetId.eq(preset_id))
+        .exec(db)
+        .await?;
+
+    if bands.is_empty() {
+        return Ok(());
+    }
+
+    let models =
+        bands
+            .iter()
+            .enumerate()
+            .map(|(position, band)| equalizer_preset_bands::ActiveModel {
+                preset_id: ActiveValue::Set(preset_id),
+                position: ActiveValue::Set(position as i32),
+                filter_type: ActiveValue::Set(u32::from(band.filter_type) as i32),
+                frequency: ActiveValue::Set(to_decimal(band.frequency)),
+                gain: ActiveValue::Set(to_decimal(band.gain)),
+                q: ActiveValue::Set(to_decimal(band.q)),
+                ..Default::default()
+            });
+
+    equalizer_preset_bands::Entity::insert_many(models)
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_all_equalizer_presets(
+    main_db: &DatabaseConnection,
+) -> Result<Vec<EqualizerPreset>> {
+    let presets = equalizer_presets::Entity::find()
+        .order_by_asc(equalizer_presets::Column::Id)
+        .all(main_db)
+        .await?;
+
+    let mut result = Vec::with_capacity(presets.len());
+    for preset in presets {
+        result.push(EqualizerPreset {
+            id: preset.id,
+            name: preset.name,
+            preamp: from_decimal(preset.preamp),
+            bands: get_bands_by_preset_id(main_db, preset.id).await?,
+        });
+    }
+
+    Ok(result)
+}
+
+pub async fn create_equalizer_preset(
+    main_db: &DatabaseConnection,
+    name: String,
+    preamp: f32,
+    bands: Vec<EqualizerBand>,
+) -> Result<EqualizerPreset> {
+    let txn = main_db.begin().await?;
+
+    let new_preset = equalizer_presets::ActiveModel {
+        name: ActiveValue::Set(name),
+        preamp: ActiveValue::Set(to_decimal(preamp)),
+        created_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+        updated_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+        ..Default::default()
+    };
+
+    let preset = new_preset
+        .insert(&txn)
+        .await
+        .with_context(|| "Failed to insert equalizer preset")?;
+    replace_bands(&txn, preset.id, &bands)
+        .await
+        .with_context(|| "Failed to insert equalizer bands")?;
+
+    txn.commit().await?;
+
+    Ok(EqualizerPreset {
+        id: preset.id,
+        name: preset.name,
+        preamp,
+        bands,
+    })
+}
+
+pub async fn update_equalizer_preset(
+    main_db: &DatabaseConnection,
+    id: i32,
+    name: String,
+    preamp: f32,
+    bands: Vec<EqualizerBand>,
+) -> Result<EqualizerPreset> {
+    let txn = main_db.begin().await?;
+
+    let preset = equalizer_presets::Entity::find_by_id(id).one(&txn).await?;
+    let Some(preset) = preset else {
+        bail!("Equalizer preset not found");
+    };
+
+    let mut active_model: equalizer_presets::ActiveModel = preset.into();
+    active_model.name = ActiveValue::Set(name);
+    active_model.preamp = ActiveValue::Set(to_decimal(preamp));
+    active_model.updated_at = ActiveValue::Set(Utc::now().to_rfc3339());
+
+    let preset = active_model
+        .update(&txn)
+        .await
+        .with_context(|| "Failed to update equalizer preset")?;
+    replace_bands(&txn, preset.id, &bands)
+        .await
+        .with_context(|| "Failed to replace equalizer bands")?;
+
+    txn.commit().await?;
+
+    Ok(EqualizerPreset {
+        id: preset.id,
+        name: preset.name,
+        preamp,
+        bands,
+    })
+}
+
+pub async fn remove_equalizer_preset(main_db: &DatabaseConnection, id: i32) -> Result<()> {
+    let preset = equalizer_presets::Entity::find_by_id(id)
+        .one(main_db)
+        .await?;
+
+    if let Some(preset) = preset {
+        preset.delete(main_db).await?;
+        Ok(())
+    } else {
+        bail!("Equalizer preset not found")
+    }
+}
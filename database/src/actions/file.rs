@@ -82,6 +82,41 @@ pub async fn get_file_by_path(
     Ok(file)
 }
 
+/// Finds the row of a track of a CUE sheet by the path of its audio file.
+pub async fn get_cue_track_by_path(
+    db: &DatabaseConnection,
+    relative_path: &Path,
+    track: i32,
+) -> Result<Option<media_files::Model>, sea_orm::DbErr> {
+    let directory = relative_path
+        .parent()
+        .and_then(|x| x.to_str())
+        .unwrap_or("")
+        .to_string();
+    let file_name = relative_path
+        .file_name()
+        .and_then(|x| x.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    media_files::Entity::find()
+        .filter(media_files::Column::Directory.eq(directory))
+        .filter(media_files::Column::FileName.eq(file_name))
+        .filter(media_files::Column::CueTrack.eq(track))
+        .one(db)
+        .await
+}
+
+/// Returns the start and end offsets of a track in seconds, `None` for
+/// tracks that span their whole file. A track without an end lasts until
+/// the end of the file.
+pub fn get_track_range(file: &media_files::Model) -> Option<(f64, f64)> {
+    let start = file.start_offset?.to_f64()?;
+    let end = file.end_offset.and_then(|x| x.to_f64()).unwrap_or(f64::MAX);
+
+    Some((start, end))
+}
+
 pub async fn get_file_id_from_path(
     db: &DatabaseConnection,
     root_path: &Path,
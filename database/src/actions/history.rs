@@ -0,0 +1,255 @@
+use std::num::NonZeroU32;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::{Alias, Condition, Expr, SimpleExpr};
+use sea_orm::{ActiveValue, JoinType, Order, QueryOrder, QuerySelect};
+
+use crate::actions::collection::CollectionQueryType;
+use crate::entities::{media_file_albums, media_file_artists, media_file_genres, play_events};
+
+/// A finished play of a library track.
+#[derive(Debug, Clone)]
+pub struct PlayEvent {
+    pub media_file_id: i32,
+    pub started_at: DateTime<Utc>,
+    /// Seconds actually listened, skipped parts do not count
+    pub listened_duration: f64,
+    /// `listened_duration` relative to the track duration, from 0 to 1
+    pub completion_ratio: f64,
+    /// The mix queries the track was queued from
+    pub source: Option<String>,
+}
+
+impl From<play_events::Model> for PlayEvent {
+    fn from(model: play_events::Model) -> Self {
+        PlayEvent {
+            media_file_id: model.media_file_id,
+            started_at: DateTime::parse_from_rfc3339(&model.started_at)
+                .map(|x| x.with_timezone(&Utc))
+                .unwrap_or_default(),
+            listened_duration: model.listened_duration.to_f64().unwrap_or_default(),
+            completion_ratio: model.completion_ratio.to_f64().unwrap_or_default(),
+            source: model.source,
+        }
+    }
+}
+
+/// A time window over the history, either end may be left open.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HistoryWindow {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl HistoryWindow {
+    /// The window covering the last `days` days.
+    pub fn last_days(days: i64) -> Self {
+        HistoryWindow {
+            since: Some(Utc::now() - Duration::days(days)),
+            until: None,
+        }
+    }
+}
+
+/// A track, artist, album or genre ranked by how often it was played.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopPlayed {
+    pub id: i32,
+    pub plays: i64,
+    pub listened_duration: f64,
+}
+
+pub async fn insert_play_event(
+    main_db: &DatabaseConnection,
+    event: PlayEvent,
+) -> Result<play_events::Model> {
+    let model = play_events::ActiveModel {
+        media_file_id: ActiveValue::Set(event.media_file_id),
+        started_at: ActiveValue::Set(event.started_at.to_rfc3339()),
+        listened_duration: ActiveValue::Set(
+            Decimal::from_f64(event.listened_duration).unwrap_or_default(),
+        ),
+        completion_ratio: ActiveValue::Set(
+            Decimal::from_f64(event.completion_ratio.clamp(0.0, 1.0)).unwrap_or_default(),
+        ),
+        source: ActiveValue::Set(event.source),
+        ..Default::default()
+    };
+
+    Ok(model.insert(main_db).await?)
+}
+
+/// Lists the play events in a window, newest first.
+pub async fn list_play_events(
+    main_db: &DatabaseConnection,
+    window: HistoryWindow,
+    cursor: u64,
+    page_size: u64,
+) -> Result<Vec<PlayEvent>> {
+    let events = play_events::Entity::find()
+        .filter(window_condition(window))
+        .order_by_desc(play_events::Column::StartedAt)
+        .order_by_desc(play_events::Column::Id)
+        .offset(cursor)
+        .limit(page_size)
+        .all(main_db)
+        .await?;
+
+    Ok(events.into_iter().map(PlayEvent::from).collect())
+}
+
+fn window_condition(window: HistoryWindow) -> Condition {
+    let mut condition = Condition::all();
+
+    if let Some(since) = window.since {
+        condition = condition.add(play_events::Column::StartedAt.gte(since.to_rfc3339()));
+    }
+
+    if let Some(until) = window.until {
+        condition = condition.add(play_events::Column::StartedAt.lt(until.to_rfc3339()));
+    }
+
+    condition
+}
+
+/// Ranks the tracks, artists, albums or genres played most in a window.
+///
+/// A play of a track with several artists counts for each of them.
+pub async fn get_top_played(
+    main_db: &DatabaseConnection,
+    collection_type: CollectionQueryType,
+    window: HistoryWindow,
+    limit: u64,
+) -> Result<Vec<TopPlayed>> {
+    let mut query = play_events::Entity::find().select_only();
+
+    let id_column: SimpleExpr = match collection_type {
+        CollectionQueryType::Track => {
+            Expr::col((play_events::Entity, play_events::Column::MediaFileId)).into()
+        }
+        CollectionQueryType::Artist => {
+            query = query.join(
+                JoinType::InnerJoin,
+                play_events::Entity::belongs_to(media_file_artists::Entity)
+                    .from(play_events::Column::MediaFileId)
+                    .to(media_file_artists::Column::MediaFileId)
+                    .into(),
+            );
+            Expr::col((
+                media_file_artists::Entity,
+                media_file_artists::Column::ArtistId,
+            ))
+            .into()
+        }
+        CollectionQueryType::Album => {
+            query = query.join(
+                JoinType::InnerJoin,
+                play_events::Entity::belongs_to(media_file_albums::Entity)
+                    .from(play_events::Column::MediaFileId)
+                    .to(media_file_albums::Column::MediaFileId)
+                    .into(),
+            );
+            Expr::col((
+                media_file_albums::Entity,
+                media_file_albums::Column::AlbumId,
+            ))
+            .into()
+        }
+        CollectionQueryType::Genre => {
+            query = query.join(
+                JoinType::InnerJoin,
+                play_events::Entity::belongs_to(media_file_genres::Entity)
+                    .from(play_events::Column::MediaFileId)
+                    .to(media_file_genres::Column::MediaFileId)
+                    .into(),
+            );
+            Expr::col((
+                media_file_genres::Entity,
+                media_file_genres::Column::GenreId,
+            ))
+            .into()
+        }
+        _ => bail!("Play history can not be ranked by {:?}", collection_type),
+    };
+
+    let rows: Vec<(i32, i64, Option<f64>)> = query
+        .column_as(id_column.clone(), "id")
+        .column_as(play_events::Column::Id.count(), "plays")
+        .column_as(play_events::Column::ListenedDuration.sum(), "listened")
+        .filter(window_condition(window))
+        .group_by(id_column)
+        .order_by(Expr::col(Alias::new("plays")), Order::Desc)
+        .order_by(Expr::col(Alias::new("listened")), Order::Desc)
+        .limit(limit)
+        .into_tuple()
+        .all(main_db)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, plays, listened)| TopPlayed {
+            id,
+            plays,
+            listened_duration: listened.unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// Mix sort operators backed by the play history.
+///
+/// * `sort::last_played(true)` orders by the time of the last play, tracks
+///   that were never played come first when ascending
+/// * `sort::recent_plays(30)` puts the tracks played most in the last 30
+///   days first
+/// * `sort::decayed_plays(14)` puts favorites first, a play counts fully
+///   right away and half as much after 14 days
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HistorySort {
+    LastPlayed(bool),
+    RecentPlays(NonZeroU32),
+    DecayedPlays(NonZeroU32),
+}
+
+const EVENTS_OF_FILE: &str =
+    "FROM \"play_events\" WHERE \"play_events\".\"media_file_id\" = \"media_files\".\"id\"";
+
+impl HistorySort {
+    pub(crate) fn order(&self) -> (SimpleExpr, Order) {
+        match self {
+            HistorySort::LastPlayed(asc) => (
+                Expr::cust(format!(
+                    "(SELECT MAX(\"play_events\".\"started_at\") {})",
+                    EVENTS_OF_FILE
+                )),
+                if *asc { Order::Asc } else { Order::Desc },
+            ),
+            HistorySort::RecentPlays(days) => (
+                Expr::cust_with_values(
+                    format!(
+                        "(SELECT COUNT(*) {} AND \"play_events\".\"started_at\" >= ?)",
+                        EVENTS_OF_FILE
+                    ),
+                    [(Utc::now() - Duration::days(days.get() as i64)).to_rfc3339()],
+                ),
+                Order::Desc,
+            ),
+            HistorySort::DecayedPlays(days) => {
+                let half_life = days.get() as f64;
+                (
+                    Expr::cust_with_values(
+                        format!(
+                            "(SELECT COALESCE(SUM(? / (? + julianday('now') \
+                             - julianday(\"play_events\".\"started_at\"))), 0) {})",
+                            EVENTS_OF_FILE
+                        ),
+                        [half_life, half_life],
+                    ),
+                    Order::Desc,
+                )
+            }
+        }
+    }
+}
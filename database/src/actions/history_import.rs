@@ -0,0 +1,644 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use rust_decimal::prelude::FromPrimitive;
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveValue, TransactionTrait};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::entities::{media_file_stats, play_events};
+
+use super::track_matching::{normalize_name, LibraryIndex, TrackQuery};
+
+/// Rows inserted per statement, kept well below the SQLite variable limit.
+const INSERT_CHUNK_SIZE: usize = 500;
+
+/// Plays reaching this share of the track count as played through.
+const PLAYED_THROUGH_RATIO: f64 = 0.5;
+
+/// Seconds an imported play may be apart from a play in the history and
+/// still be the same play, for tracks shorter than this.
+const MIN_DUPLICATE_WINDOW: f64 = 30.0;
+
+/// The services whose exports can be imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistorySource {
+    /// CSV of `artist,album,title,date` rows, as written by the common
+    /// Last.fm export tools
+    LastFm,
+    /// The JSON or JSON lines listens export
+    ListenBrainz,
+    /// `StreamingHistory*.json` or the extended `Streaming_History_Audio_*.json`
+    Spotify,
+}
+
+impl HistorySource {
+    /// The play event source recorded for imported plays.
+    fn event_source(&self) -> &'static str {
+        match self {
+            HistorySource::LastFm => "import::lastfm",
+            HistorySource::ListenBrainz => "import::listenbrainz",
+            HistorySource::Spotify => "import::spotify",
+        }
+    }
+}
+
+impl FromStr for HistorySource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "lastfm" | "last.fm" => Ok(HistorySource::LastFm),
+            "listenbrainz" => Ok(HistorySource::ListenBrainz),
+            "spotify" => Ok(HistorySource::Spotify),
+            _ => bail!("Unknown history source: {}", s),
+        }
+    }
+}
+
+/// A play read from an export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedListen {
+    /// The track artist, `None` for exports that only name the album artist
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub title: String,
+    pub album: Option<String>,
+    pub played_at: DateTime<Utc>,
+    /// Seconds listened, `None` if the export only records full plays
+    pub listened_duration: Option<f64>,
+    pub skipped: bool,
+}
+
+/// Plays of a track that is not in the library.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnmatchedListen {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub plays: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Plays read from the export
+    pub total: usize,
+    /// Plays added to the history
+    pub imported: usize,
+    /// Plays matched to a track but already in the history
+    pub duplicates: usize,
+    /// Tracks without a match, the most played first
+    pub unmatched: Vec<UnmatchedListen>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListenBrainzListen {
+    listened_at: i64,
+    track_metadata: ListenBrainzMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListenBrainzMetadata {
+    artist_name: String,
+    track_name: String,
+    release_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpotifyStream {
+    end_time: String,
+    artist_name: String,
+    track_name: String,
+    ms_played: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpotifyExtendedStream {
+    ts: String,
+    ms_played: u64,
+    master_metadata_track_name: Option<String>,
+    master_metadata_album_artist_name: Option<String>,
+    master_metadata_album_album_name: Option<String>,
+    reason_end: Option<String>,
+    skipped: Option<bool>,
+}
+
+fn parse_lastfm_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+
+    if let Ok(timestamp) = date.parse::<i64>() {
+        return Utc.timestamp_opt(timestamp, 0).single();
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    ["%d %b %Y %H:%M", "%d %b %Y, %H:%M", "%Y-%m-%d %H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .map(|date| date.and_utc())
+}
+
+fn parse_lastfm(content: &str) -> Result<Vec<ImportedListen>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+
+    let mut listens = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record?;
+        if record.len() < 4 {
+            bail!(
+                "Line {} has {} columns, expected 4",
+                index + 1,
+                record.len()
+            );
+        }
+
+        let Some(played_at) = parse_lastfm_date(&record[3]) else {
+            // The header of exports that have one
+            if index == 0 {
+                continue;
+            }
+            bail!("Line {} has an invalid date: {}", index + 1, &record[3]);
+        };
+
+        listens.push(ImportedListen {
+            artist: Some(record[0].to_string()),
+            album_artist: None,
+            title: record[2].to_string(),
+            album: Some(record[1].to_string()).filter(|x| !x.is_empty()),
+            played_at,
+            listened_duration: None,
+            skipped: false,
+        });
+    }
+
+    Ok(listens)
+}
+
+fn parse_listenbrainz(content: &str) -> Result<Vec<ImportedListen>> {
+    let listens: Vec<ListenBrainzListen> = if content.trim_start().starts_with('[') {
+        serde_json::from_str(content)?
+    } else {
+        content
+            .lines()
+            .filter(|x| !x.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?
+    };
+
+    listens
+        .into_iter()
+        .map(|x| {
+            Ok(ImportedListen {
+                artist: Some(x.track_metadata.artist_name),
+                album_artist: None,
+                title: x.track_metadata.track_name,
+                album: x.track_metadata.release_name,
+                played_at: Utc
+                    .timestamp_opt(x.listened_at, 0)
+                    .single()
+                    .with_context(|| format!("Invalid listen time: {}", x.listened_at))?,
+                listened_duration: None,
+                skipped: false,
+            })
+        })
+        .collect()
+}
+
+fn parse_spotify(content: &str) -> Result<Vec<ImportedListen>> {
+    let items: Vec<Value> = serde_json::from_str(content)?;
+    let mut listens = Vec::new();
+
+    for item in items {
+        // The extended history has `ts`, the account data export `endTime`
+        let (ended_at, listen) = if item.get("ts").is_some() {
+            // The extended history only names the album artist, which
+            // differs from the track artist on compilations and features
+            let stream: SpotifyExtendedStream = serde_json::from_value(item)?;
+            let (Some(title), Some(album_artist)) = (
+                stream.master_metadata_track_name,
+                stream.master_metadata_album_artist_name,
+            ) else {
+                // Podcast episodes and audiobooks
+                continue;
+            };
+
+            let ended_at = DateTime::parse_from_rfc3339(&stream.ts)
+                .with_context(|| format!("Invalid stream time: {}", stream.ts))?
+                .with_timezone(&Utc);
+
+            (
+                ended_at,
+                ImportedListen {
+                    artist: None,
+                    album_artist: Some(album_artist),
+                    title,
+                    album: stream.master_metadata_album_album_name,
+                    played_at: ended_at,
+                    listened_duration: Some(stream.ms_played as f64 / 1000.0),
+                    skipped: stream.skipped.unwrap_or(false)
+                        || stream.reason_end.as_deref() == Some("fwdbtn"),
+                },
+            )
+        } else {
+            let stream: SpotifyStream = serde_json::from_value(item)?;
+            let ended_at = NaiveDateTime::parse_from_str(&stream.end_time, "%Y-%m-%d %H:%M")
+                .with_context(|| format!("Invalid stream time: {}", stream.end_time))?
+                .and_utc();
+
+            (
+                ended_at,
+                ImportedListen {
+                    artist: Some(stream.artist_name),
+                    album_artist: None,
+                    title: stream.track_name,
+                    album: None,
+                    played_at: ended_at,
+                    listened_duration: Some(stream.ms_played as f64 / 1000.0),
+                    skipped: false,
+                },
+            )
+        };
+
+        // Spotify records when a stream ended
+        let listened = listen.listened_duration.unwrap_or_default();
+        listens.push(ImportedListen {
+            played_at: ended_at - Duration::milliseconds((listened * 1000.0) as i64),
+            ..listen
+        });
+    }
+
+    Ok(listens)
+}
+
+/// Reads the plays from the content of an export file.
+pub fn parse_history(source: HistorySource, content: &str) -> Result<Vec<ImportedListen>> {
+    let content = content.trim_start_matches('\u{feff}');
+
+    match source {
+        HistorySource::LastFm => parse_lastfm(content),
+        HistorySource::ListenBrainz => parse_listenbrainz(content),
+        HistorySource::Spotify => parse_spotify(content),
+    }
+    .with_context(|| format!("Failed to parse {:?} history", source))
+}
+
+/// Takes the play of `known` closest to `played_at`, if it is less than
+/// `window` seconds apart. Every play in the history stands for a single
+/// imported play, so repeated plays of a track are still imported.
+fn take_known_play(known: &mut Vec<DateTime<Utc>>, played_at: DateTime<Utc>, window: f64) -> bool {
+    let closest = known
+        .iter()
+        .enumerate()
+        .map(|(i, started_at)| (i, (*started_at - played_at).num_milliseconds().abs()))
+        .filter(|(_, distance)| *distance as f64 <= window * 1000.0)
+        .min_by_key(|(_, distance)| *distance);
+
+    match closest {
+        Some((i, _)) => {
+            known.swap_remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+#[derive(Debug, Default)]
+struct StatsDelta {
+    played_through: i32,
+    skipped: i32,
+}
+
+/// Matches imported plays to library tracks and adds them to the play
+/// history and play counts. Plays already in the history are left out, so
+/// importing an export twice is harmless. Services record the start of a
+/// play a little differently, so a play of the same track less than its
+/// duration apart counts as already known.
+pub async fn import_play_history(
+    main_db: &DatabaseConnection,
+    source: HistorySource,
+    listens: Vec<ImportedListen>,
+) -> Result<ImportReport> {
+    let index = LibraryIndex::load(main_db).await?;
+
+    let mut report = ImportReport {
+        total: listens.len(),
+        ..Default::default()
+    };
+    let mut matched = Vec::new();
+    let mut unmatched: HashMap<(String, String), UnmatchedListen> = HashMap::new();
+
+    for listen in listens {
+        let query = TrackQuery {
+            title: &listen.title,
+            artist: listen.artist.as_deref(),
+            album_artist: listen.album_artist.as_deref(),
+            album: listen.album.as_deref(),
+            duration: None,
+        };
+
+        match index.find(&query) {
+            Some(track) => matched.push((track.id, track.duration, listen)),
+            None => {
+                let artist = listen
+                    .artist
+                    .as_ref()
+                    .or(listen.album_artist.as_ref())
+                    .cloned()
+                    .unwrap_or_default();
+
+                unmatched
+                    .entry((normalize_name(&artist), normalize_name(&listen.title)))
+                    .or_insert_with(|| UnmatchedListen {
+                        artist,
+                        title: listen.title.clone(),
+                        album: listen.album.clone(),
+                        plays: 0,
+                    })
+                    .plays += 1;
+            }
+        }
+    }
+
+    let matched_ids: HashSet<i32> = matched.iter().map(|(id, _, _)| *id).collect();
+    let matched_ids: Vec<i32> = matched_ids.into_iter().collect();
+    let mut known: HashMap<i32, Vec<DateTime<Utc>>> = HashMap::new();
+    for ids in matched_ids.chunks(INSERT_CHUNK_SIZE) {
+        for event in play_events::Entity::find()
+            .filter(play_events::Column::MediaFileId.is_in(ids.to_vec()))
+            .all(main_db)
+            .await?
+        {
+            if let Ok(started_at) = DateTime::parse_from_rfc3339(&event.started_at) {
+                known
+                    .entry(event.media_file_id)
+                    .or_default()
+                    .push(started_at.with_timezone(&Utc));
+            }
+        }
+    }
+
+    let mut seen: HashSet<(i32, DateTime<Utc>)> = HashSet::new();
+    let mut events = Vec::new();
+    let mut deltas: HashMap<i32, StatsDelta> = HashMap::new();
+    for (id, duration, listen) in matched {
+        // Copies of a play within the export carry the exact same time
+        let is_copy = !seen.insert((id, listen.played_at));
+        let window = duration.max(MIN_DUPLICATE_WINDOW);
+        if is_copy
+            || known
+                .get_mut(&id)
+                .is_some_and(|known| take_known_play(known, listen.played_at, window))
+        {
+            report.duplicates += 1;
+            continue;
+        }
+
+        let started_at = listen.played_at.to_rfc3339();
+
+        let listened = listen.listened_duration.unwrap_or(duration);
+        let completion = if duration > 0.0 {
+            (listened / duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        let delta = deltas.entry(id).or_default();
+        if listen.skipped {
+            delta.skipped += 1;
+        } else if completion >= PLAYED_THROUGH_RATIO {
+            delta.played_through += 1;
+        }
+
+        events.push(play_events::ActiveModel {
+            media_file_id: ActiveValue::Set(id),
+            started_at: ActiveValue::Set(started_at),
+            listened_duration: ActiveValue::Set(Decimal::from_f64(listened).unwrap_or_default()),
+            completion_ratio: ActiveValue::Set(Decimal::from_f64(completion).unwrap_or_default()),
+            source: ActiveValue::Set(Some(source.event_source().to_string())),
+            ..Default::default()
+        });
+    }
+
+    report.imported = events.len();
+
+    let txn = main_db.begin().await?;
+
+    let mut events = events.into_iter().peekable();
+    while events.peek().is_some() {
+        let chunk: Vec<_> = events.by_ref().take(INSERT_CHUNK_SIZE).collect();
+        play_events::Entity::insert_many(chunk)
+            .exec_without_returning(&txn)
+            .await?;
+    }
+
+    let now = Utc::now().to_rfc3339();
+    for (id, delta) in deltas {
+        if delta.played_through == 0 && delta.skipped == 0 {
+            continue;
+        }
+
+        let stats = media_file_stats::Entity::find()
+            .filter(media_file_stats::Column::MediaFileId.eq(id))
+            .one(&txn)
+            .await?;
+
+        match stats {
+            Some(stats) => {
+                let mut active_model: media_file_stats::ActiveModel = stats.clone().into();
+                active_model.played_through =
+                    ActiveValue::Set(stats.played_through + delta.played_through);
+                active_model.skipped = ActiveValue::Set(stats.skipped + delta.skipped);
+                active_model.updated_at = ActiveValue::Set(now.clone());
+                active_model.update(&txn).await?;
+            }
+            None => {
+                media_file_stats::ActiveModel {
+                    media_file_id: ActiveValue::Set(id),
+                    liked: ActiveValue::Set(false),
+                    skipped: ActiveValue::Set(delta.skipped),
+                    played_through: ActiveValue::Set(delta.played_through),
+                    updated_at: ActiveValue::Set(now.clone()),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?;
+            }
+        }
+    }
+
+    txn.commit().await?;
+
+    report.unmatched = unmatched.into_values().collect();
+    report
+        .unmatched
+        .sort_by(|a, b| b.plays.cmp(&a.plays).then_with(|| a.artist.cmp(&b.artist)));
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::connect_test_main_db;
+    use crate::entities::media_files;
+
+    use super::*;
+
+    fn date(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_lastfm() {
+        let content = "\u{feff}artist,album,title,date\n\
+                       Sigur Rós,Takk...,Hoppípolla,31 Jan 2024 20:15\n\
+                       Björk,,Jóga,1706732100\n";
+        let listens = parse_history(HistorySource::LastFm, content).unwrap();
+
+        assert_eq!(listens.len(), 2);
+        assert_eq!(listens[0].artist.as_deref(), Some("Sigur Rós"));
+        assert_eq!(listens[0].album.as_deref(), Some("Takk..."));
+        assert_eq!(listens[0].title, "Hoppípolla");
+        assert_eq!(listens[0].played_at, date("2024-01-31T20:15:00Z"));
+        assert_eq!(listens[1].album, None);
+        assert_eq!(listens[1].played_at, date("2024-01-31T20:15:00Z"));
+
+        assert!(parse_history(HistorySource::LastFm, "a,b,c\n").is_err());
+        assert!(parse_history(HistorySource::LastFm, "a,b,c,now\na,b,c,later\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_listenbrainz() {
+        let listen = r#"{"listened_at": 1706732100, "track_metadata": {"artist_name": "Björk", "track_name": "Jóga", "release_name": "Homogenic"}}"#;
+
+        for content in [
+            format!("[{}]", listen),
+            format!("{}\n\n{}\n", listen, listen),
+        ] {
+            let listens = parse_history(HistorySource::ListenBrainz, &content).unwrap();
+            assert!(!listens.is_empty());
+            assert_eq!(listens[0].artist.as_deref(), Some("Björk"));
+            assert_eq!(listens[0].album.as_deref(), Some("Homogenic"));
+            assert_eq!(listens[0].played_at, date("2024-01-31T20:15:00Z"));
+        }
+    }
+
+    #[test]
+    fn test_parse_spotify() {
+        let content = r#"[{"endTime": "2024-01-31 20:15", "artistName": "Björk", "trackName": "Jóga", "msPlayed": 60000}]"#;
+        let listens = parse_history(HistorySource::Spotify, content).unwrap();
+
+        assert_eq!(listens.len(), 1);
+        assert_eq!(listens[0].artist.as_deref(), Some("Björk"));
+        assert_eq!(listens[0].album_artist, None);
+        assert_eq!(listens[0].listened_duration, Some(60.0));
+        // The export records when the stream ended
+        assert_eq!(listens[0].played_at, date("2024-01-31T20:14:00Z"));
+    }
+
+    #[test]
+    fn test_parse_spotify_extended() {
+        let content = r#"[
+            {"ts": "2024-01-31T20:15:00Z", "ms_played": 30000,
+             "master_metadata_track_name": "Song", "master_metadata_album_artist_name": "Various Artists",
+             "master_metadata_album_album_name": "Compilation", "reason_end": "fwdbtn", "skipped": null},
+            {"ts": "2024-01-31T21:00:00Z", "ms_played": 1200000,
+             "master_metadata_track_name": null, "master_metadata_album_artist_name": null,
+             "master_metadata_album_album_name": null, "episode_name": "Podcast", "reason_end": "trackdone"}
+        ]"#;
+        let listens = parse_history(HistorySource::Spotify, content).unwrap();
+
+        // Podcast episodes are left out
+        assert_eq!(listens.len(), 1);
+        assert_eq!(listens[0].artist, None);
+        assert_eq!(listens[0].album_artist.as_deref(), Some("Various Artists"));
+        assert_eq!(listens[0].album.as_deref(), Some("Compilation"));
+        assert_eq!(listens[0].played_at, date("2024-01-31T20:14:30Z"));
+        assert!(listens[0].skipped);
+    }
+
+    fn listen(title: &str, played_at: &str) -> ImportedListen {
+        ImportedListen {
+            artist: None,
+            album_artist: None,
+            title: title.to_string(),
+            album: None,
+            played_at: date(played_at),
+            listened_duration: None,
+            skipped: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_skips_known_plays() {
+        let db = connect_test_main_db().await;
+
+        media_files::ActiveModel {
+            id: ActiveValue::Set(1),
+            file_name: ActiveValue::Set("Song".to_string()),
+            directory: ActiveValue::Set("Artist".to_string()),
+            extension: ActiveValue::Set("ogg".to_string()),
+            file_hash: ActiveValue::Set("hash".to_string()),
+            last_modified: ActiveValue::Set("2024-01-01T00:00:00+00:00".to_string()),
+            cover_art_id: ActiveValue::Set(None),
+            sample_rate: ActiveValue::Set(44100),
+            duration: ActiveValue::Set(Decimal::new(200, 0)),
+            cue_track: ActiveValue::Set(None),
+            start_offset: ActiveValue::Set(None),
+            end_offset: ActiveValue::Set(None),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        // Recorded by Rune while the play was scrobbled
+        play_events::ActiveModel {
+            media_file_id: ActiveValue::Set(1),
+            started_at: ActiveValue::Set("2024-01-31T20:15:00.123+00:00".to_string()),
+            listened_duration: ActiveValue::Set(Decimal::new(200, 0)),
+            completion_ratio: ActiveValue::Set(Decimal::ONE),
+            source: ActiveValue::Set(None),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let listens = vec![
+            listen("Song", "2024-01-31T20:15:30Z"),
+            listen("Song", "2024-01-31T21:00:00Z"),
+            listen("Song", "2024-01-31T21:00:00Z"),
+            listen("Song", "2024-01-31T21:03:20Z"),
+        ];
+
+        let report = import_play_history(&db, HistorySource::LastFm, listens.clone())
+            .await
+            .unwrap();
+        // The play Rune recorded is known, the one repeated right after it
+        // is not
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.duplicates, 2);
+        assert_eq!(play_events::Entity::find().all(&db).await.unwrap().len(), 3);
+
+        let stats = media_file_stats::Entity::find().all(&db).await.unwrap();
+        assert_eq!(stats[0].played_through, 2);
+
+        // Importing the export again adds nothing
+        let report = import_play_history(&db, HistorySource::LastFm, listens)
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 0);
+        assert_eq!(report.duplicates, 4);
+        assert_eq!(play_events::Entity::find().all(&db).await.unwrap().len(), 3);
+    }
+}
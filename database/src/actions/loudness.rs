@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::prelude::*;
+
+use analysis::utils::loudness::{aggregate_loudness, loudness_to_replay_gain};
+use metadata::replay_gain::{parse_replay_gain, ReplayGainTags};
+use playback::crossfade::SilenceProfile;
+use playback::normalization::ReplayGainInfo;
+
+use crate::entities::{media_file_albums, media_files, media_loudness, media_metadata};
+
+const REPLAY_GAIN_KEYS: [&str; 6] = [
+    "replaygain_track_gain",
+    "replaygain_track_peak",
+    "replaygain_album_gain",
+    "replaygain_album_peak",
+    "r128_track_gain",
+    "r128_album_gain",
+];
+
+#[derive(Debug, Clone, Copy)]
+struct MeasuredLoudness {
+    loudness: Option<f64>,
+    peak: Option<f64>,
+}
+
+/// Merges ReplayGain tags with measured values, tags take precedence.
+pub fn merge_replay_gain(
+    tags: ReplayGainTags,
+    track: Option<(Option<f64>, Option<f64>)>,
+    album: Option<(Option<f64>, Option<f64>)>,
+) -> ReplayGainInfo {
+    let (track_loudness, track_peak) = track.unwrap_or_default();
+    let (album_loudness, album_peak) = album.unwrap_or_default();
+
+    ReplayGainInfo {
+        track_gain: tags
+            .track_gain
+            .or(track_loudness.map(loudness_to_replay_gain))
+            .map(|x| x as f32),
+        track_peak: tags.track_peak.or(track_peak).map(|x| x as f32),
+        album_gain: tags
+            .album_gain
+            .or(album_loudness.map(loudness_to_replay_gain))
+            .map(|x| x as f32),
+        album_peak: tags.album_peak.or(album_peak).map(|x| x as f32),
+    }
+}
+
+async fn get_measured_loudness(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, MeasuredLoudness>> {
+    Ok(media_loudness::Entity::find()
+        .filter(media_loudness::Column::FileId.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| {
+            (
+                x.file_id,
+                MeasuredLoudness {
+                    loudness: x.integrated_loudness.and_then(|x| x.to_f64()),
+                    peak: x.true_peak.and_then(|x| x.to_f64()),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Computes album loudness and peak from the measured loudness of every
+/// track in the albums the given files belong to.
+async fn get_album_loudness(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, (Option<f64>, Option<f64>)>> {
+    let file_albums: HashMap<i32, i32> = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::MediaFileId.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.media_file_id, x.album_id))
+        .collect();
+
+    let album_ids: HashSet<i32> = file_albums.values().copied().collect();
+    if album_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let album_tracks = media_file_albums::Entity::find()
+        .filter(media_file_albums::Column::AlbumId.is_in(album_ids))
+        .all(main_db)
+        .await?;
+
+    let album_track_ids: Vec<i32> = album_tracks.iter().map(|x| x.media_file_id).collect();
+
+    let measured = get_measured_loudness(main_db, &album_track_ids).await?;
+    let durations: HashMap<i32, f64> = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(album_track_ids))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.duration.to_f64().unwrap_or_default()))
+        .collect();
+
+    let mut albums: HashMap<i32, (Vec<(f64, f64)>, Option<f64>)> = HashMap::new();
+    for track in album_tracks {
+        let entry = albums.entry(track.album_id).or_default();
+
+        if let Some(measured) = measured.get(&track.media_file_id) {
+            if let (Some(loudness), Some(duration)) =
+                (measured.loudness, durations.get(&track.media_file_id))
+            {
+                entry.0.push((loudness, *duration));
+            }
+            if let Some(peak) = measured.peak {
+                entry.1 = Some(entry.1.map_or(peak, |x| x.max(peak)));
+            }
+        }
+    }
+
+    Ok(file_albums
+        .into_iter()
+        .filter_map(|(file_id, album_id)| {
+            albums
+                .get(&album_id)
+                .map(|(tracks, peak)| (file_id, (aggregate_loudness(tracks), *peak)))
+        })
+        .collect())
+}
+
+/// Retrieve the silent head and tail measured during analysis.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files to resolve.
+///
+/// # Returns
+/// * `Result<HashMap<i32, SilenceProfile>>` - Silence profiles keyed by file ID,
+///   files that were not measured yet are omitted.
+pub async fn get_silence_profile_by_file_ids(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, SilenceProfile>> {
+    if file_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    Ok(media_loudness::Entity::find()
+        .filter(media_loudness::Column::FileId.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .filter_map(|x| {
+            let leading_silence = x.leading_silence.and_then(|x| x.to_f32())?;
+            let trailing_silence = x.trailing_silence.and_then(|x| x.to_f32())?;
+
+            Some((
+                x.file_id,
+                SilenceProfile {
+                    leading_silence,
+                    trailing_silence,
+                },
+            ))
+        })
+        .collect())
+}
+
+/// Resolve ReplayGain information for library files, using REPLAYGAIN_* or
+/// R128_* tags when present and falling back to the loudness measured during
+/// analysis.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files to resolve.
+///
+/// # Returns
+/// * `Result<HashMap<i32, ReplayGainInfo>>` - Gain information keyed by file ID,
+///   files without any information are omitted.
+pub async fn get_replay_gain_by_file_ids(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, ReplayGainInfo>> {
+    if file_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut tags: HashMap<i32, Vec<(String, String)>> = HashMap::new();
+    for entry in media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.is_in(file_ids.to_vec()))
+        .filter(media_metadata::Column::MetaKey.is_in(REPLAY_GAIN_KEYS))
+        .all(main_db)
+        .await?
+    {
+        tags.entry(entry.file_id)
+            .or_default()
+            .push((entry.meta_key, entry.meta_value));
+    }
+
+    let measured = get_measured_loudness(main_db, file_ids).await?;
+    let albums = get_album_loudness(main_db, file_ids).await?;
+
+    Ok(file_ids
+        .iter()
+        .filter_map(|file_id| {
+            let tags = tags
+                .get(file_id)
+                .map(|x| parse_replay_gain(x))
+                .unwrap_or_default();
+            let track = measured.get(file_id).map(|x| (x.loudness, x.peak));
+            let album = albums.get(file_id).copied();
+
+            if tags.is_empty() && track.is_none() && album.is_none() {
+                return None;
+            }
+
+            Some((*file_id, merge_replay_gain(tags, track, album)))
+        })
+        .collect())
+}
@@ -1,23 +1,25 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
 use anyhow::{bail, Context, Result};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
 use sea_orm::entity::prelude::*;
-use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::{ActiveValue, ColumnTrait, Condition, EntityTrait, QueryFilter};
 use sea_orm::{DatabaseConnection, TransactionTrait};
 use tokio_util::sync::CancellationToken;
 
+use metadata::cue::{CueSheet, CueSheetCache};
 use metadata::describe::{describe_file, FileDescription};
 use metadata::reader::get_metadata;
 use metadata::scanner::AudioScanner;
 
 use crate::actions::collection::CollectionQueryType;
 use crate::actions::cover_art::remove_cover_art_by_file_id;
-use crate::actions::file::get_file_ids_by_descriptions;
+use crate::actions::file::{get_file_ids_by_descriptions, get_track_range};
 use crate::actions::index::{index_media_files, perform_library_maintenance};
 use crate::actions::logging::{insert_log, LogLevel};
 use crate::actions::search::{add_term, remove_term};
@@ -56,6 +58,7 @@ pub fn read_metadata(description: &FileDescription) -> Result<FileMetadata> {
 pub async fn sync_file_descriptions(
     main_db: &DatabaseConnection,
     descriptions: &mut [Option<FileDescription>],
+    cue_sheets: &mut CueSheetCache,
     force: bool,
 ) -> Result<()> {
     debug!("Starting to process multiple files");
@@ -82,6 +85,42 @@ pub async fn sync_file_descriptions(
             Some(description) => {
                 debug!("Processing file: {}", description.file_name.clone());
 
+                if let Some((cue_path, cue_sheet)) = cue_sheets.find(&description.full_path) {
+                    if let Err(e) = sync_cue_tracks(&txn, description, &cue_path, &cue_sheet, force)
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "Failed to sync CUE tracks: {}",
+                                description.file_name.clone()
+                            )
+                        })
+                    {
+                        error!("{:?}", e);
+                        insert_log(
+                            &txn,
+                            LogLevel::Error,
+                            "actions::metadata::sync_file_descriptions".to_string(),
+                            format!("{:#?}", e),
+                        )
+                        .await?;
+                    }
+                    continue;
+                }
+
+                if let Err(e) = remove_cue_tracks(&txn, description)
+                    .await
+                    .with_context(|| "Unable to remove CUE tracks")
+                {
+                    insert_log(
+                        &txn,
+                        LogLevel::Error,
+                        "actions::metadata::sync_file_descriptions".to_string(),
+                        format!("{:#?}", e),
+                    )
+                    .await?;
+                    continue;
+                }
+
                 let existing_file = match media_files::Entity::find()
                     .filter(media_files::Column::Directory.eq(description.directory.clone()))
                     .filter(media_files::Column::FileName.eq(description.file_name.clone()))
@@ -775,6 +814,607 @@ where
     Ok(())
 }
 
+/// Indexes an audio file described by a CUE sheet as one track per sheet
+/// entry. The tracks share the audio file and only differ in their offsets.
+///
+/// Rows of tracks that are still listed in the sheet are updated in place, so
+/// statistics and playlists referencing them survive a rescan.
+pub async fn sync_cue_tracks<E>(
+    db: &E,
+    description: &mut FileDescription,
+    cue_path: &Path,
+    cue_sheet: &CueSheet,
+    force: bool,
+) -> Result<()>
+where
+    E: DatabaseExecutor + sea_orm::ConnectionTrait,
+{
+    // Editing the sheet has to refresh the tracks, so the newest modification
+    // time of both files is stored
+    let cue_modified = cue_path
+        .metadata()?
+        .modified()?
+        .duration_since(UNIX_EPOCH)?
+        .as_secs();
+    let file_modified = description.last_modified.parse::<u64>().unwrap_or_default();
+    let last_modified = format!("{}", file_modified.max(cue_modified));
+
+    let existing_files = media_files::Entity::find()
+        .filter(media_files::Column::Directory.eq(description.directory.clone()))
+        .filter(media_files::Column::FileName.eq(description.file_name.clone()))
+        .all(db)
+        .await?;
+
+    if !force
+        && !existing_files.is_empty()
+        && existing_files
+            .iter()
+            .all(|x| x.cue_track.is_some() && x.last_modified == last_modified)
+    {
+        debug!(
+            "CUE tracks haven't changed, skipping: {}",
+            description.file_name.clone()
+        );
+        return Ok(());
+    }
+
+    let file_metadata = read_metadata(description)?;
+    let (sample_rate, duration_in_seconds) = description.get_codec_information()?;
+    let file_hash = description
+        .get_crc()
+        .with_context(|| format!("Failed to get CRC: {}", description.file_name))?;
+
+    let segments = cue_sheet.segments(
+        &description.file_name,
+        duration_in_seconds,
+        &file_metadata.metadata,
+    );
+    if segments.is_empty() {
+        bail!("No CUE track found for: {}", description.file_name);
+    }
+
+    let mut existing_tracks: HashMap<i32, media_files::Model> = HashMap::new();
+    for existing_file in existing_files {
+        match existing_file.cue_track {
+            Some(track) if !existing_tracks.contains_key(&track) => {
+                existing_tracks.insert(track, existing_file);
+            }
+            _ => {
+                // The whole file was indexed as a single track before
+                media_files::Entity::delete_by_id(existing_file.id)
+                    .exec(db)
+                    .await?;
+                remove_term(db, CollectionQueryType::Track, existing_file.id).await?;
+            }
+        }
+    }
+
+    for segment in segments {
+        let track = segment.number as i32;
+        let start_offset = Decimal::from_f64(segment.start).unwrap_or_default();
+        let end_offset = Decimal::from_f64(segment.end).unwrap_or_default();
+        let duration = Decimal::from_f64(segment.end - segment.start).unwrap_or_default();
+
+        let file_id = match existing_tracks.remove(&track) {
+            Some(existing_file) => {
+                let file_id = existing_file.id;
+                let mut active_model: media_files::ActiveModel = existing_file.into();
+                active_model.extension = ActiveValue::Set(description.extension.clone());
+                active_model.file_hash = ActiveValue::Set(file_hash.clone());
+                active_model.last_modified = ActiveValue::Set(last_modified.clone());
+                active_model.sample_rate = ActiveValue::Set(sample_rate.try_into()?);
+                active_model.duration = ActiveValue::Set(duration);
+                active_model.start_offset = ActiveValue::Set(Some(start_offset));
+                active_model.end_offset = ActiveValue::Set(Some(end_offset));
+                active_model.update(db).await?;
+
+                media_metadata::Entity::delete_many()
+                    .filter(media_metadata::Column::FileId.eq(file_id))
+                    .exec(db)
+                    .await?;
+
+                file_id
+            }
+            None => {
+                let new_file = media_files::ActiveModel {
+                    file_name: ActiveValue::Set(description.file_name.to_string()),
+                    directory: ActiveValue::Set(description.directory.clone()),
+                    extension: ActiveValue::Set(description.extension.clone()),
+                    file_hash: ActiveValue::Set(file_hash.clone()),
+                    sample_rate: ActiveValue::Set(sample_rate.try_into()?),
+                    duration: ActiveValue::Set(duration),
+                    last_modified: ActiveValue::Set(last_modified.clone()),
+                    cue_track: ActiveValue::Set(Some(track)),
+                    start_offset: ActiveValue::Set(Some(start_offset)),
+                    end_offset: ActiveValue::Set(Some(end_offset)),
+                    ..Default::default()
+                };
+
+                media_files::Entity::insert(new_file)
+                    .exec(db)
+                    .await?
+                    .last_insert_id
+            }
+        };
+
+        let title = segment
+            .metadata
+            .iter()
+            .find(|(key, _)| key == "track_title")
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| format!("{} - {:02}", description.file_name, track));
+        add_term(db, CollectionQueryType::Track, file_id, &title).await?;
+
+        let new_metadata: Vec<media_metadata::ActiveModel> = segment
+            .metadata
+            .into_iter()
+            .map(|(key, value)| media_metadata::ActiveModel {
+                file_id: ActiveValue::Set(file_id),
+                meta_key: ActiveValue::Set(key),
+                meta_value: ActiveValue::Set(value),
+                ..Default::default()
+            })
+            .collect();
+
+        if !new_metadata.is_empty() {
+            media_metadata::Entity::insert_many(new_metadata)
+                .exec(db)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to insert CUE track metadata: {} #{}",
+                        description.file_name, track
+                    )
+                })?;
+        }
+    }
+
+    // Tracks that disappeared from the sheet
+    for (_, existing_file) in existing_tracks {
+        media_files::Entity::delete_by_id(existing_file.id)
+            .exec(db)
+            .await?;
+        remove_term(db, CollectionQueryType::Track, existing_file.id).await?;
+    }
+
+    Ok(())
+}
+
+/// Drops the CUE tracks of a file whose sheet was removed, so the file is
+/// indexed as a single track again.
+pub async fn remove_cue_tracks<E>(db: &E, description: &FileDescription) -> Result<()>
+where
+    E: DatabaseExecutor + sea_orm::ConnectionTrait,
+{
+    let cue_tracks = media_files::Entity::find()
+        .filter(media_files::Column::Directory.eq(description.directory.clone()))
+        .filter(media_files::Column::FileName.eq(description.file_name.clone()))
+        .filter(media_files::Column::CueTrack.is_not_null())
+        .all(db)
+        .await?;
+
+    for cue_track in cue_tracks {
+        media_files::Entity::delete_by_id(cue_track.id)
+            .exec(db)
+            .await?;
+        remove_term(db, CollectionQueryType::Track, cue_track.id).await?;
+    }
+
+    Ok(())
+}
+
+/// Seconds two durations may differ by before a file counts as a different
+/// recording when matching moved files by their tags.
+const RELOCATE_DURATION_TOLERANCE: f64 = 1.0;
+
+/// Tags compared when matching moved files, the title is required.
+const RELOCATE_TAGS: [&str; 3] = ["track_title", "artist", "album"];
+
+fn is_missing_file(lib_path: &Path, file: &media_files::Model) -> bool {
+    !lib_path
+        .join(&file.directory)
+        .join(&file.file_name)
+        .exists()
+}
+
+fn normalize_tag(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+/// Title, artist and album of a file, normalized for comparison.
+type RelocateKey = [String; 3];
+
+fn relocate_key<'a>(tags: impl Iterator<Item = (&'a str, &'a str)>) -> Option<RelocateKey> {
+    let mut key: RelocateKey = Default::default();
+    for (meta_key, value) in tags {
+        if let Some(index) = RELOCATE_TAGS.iter().position(|x| *x == meta_key) {
+            key[index] = normalize_tag(value);
+        }
+    }
+
+    // Without a title there is nothing meaningful to compare
+    (!key[0].is_empty()).then_some(key)
+}
+
+/// The files of the library that disappeared from disk, indexed by hash and
+/// by tags so new files can be matched against them.
+///
+/// The index is loaded on the first lookup and kept for the whole scan,
+/// scans that find no missing file never read the new files again.
+#[derive(Debug, Default)]
+pub struct MovedFileIndex {
+    loaded: bool,
+    by_hash: HashMap<String, Vec<media_files::Model>>,
+    by_tags: HashMap<RelocateKey, Vec<(f64, media_files::Model)>>,
+}
+
+impl MovedFileIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn load<E>(&mut self, db: &E, lib_path: &Path) -> Result<()>
+    where
+        E: DatabaseExecutor + sea_orm::ConnectionTrait,
+    {
+        if self.loaded {
+            return Ok(());
+        }
+        self.loaded = true;
+
+        let missing_files: Vec<media_files::Model> = media_files::Entity::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|file| is_missing_file(lib_path, file))
+            .collect();
+
+        // CUE tracks share their file and tags with the other tracks of the
+        // sheet, they are only matched by hash
+        let file_ids: Vec<i32> = missing_files
+            .iter()
+            .filter(|file| file.cue_track.is_none())
+            .map(|file| file.id)
+            .collect();
+
+        let mut tags: HashMap<i32, Vec<(String, String)>> = HashMap::new();
+        for chunk in file_ids.chunks(500) {
+            let rows = media_metadata::Entity::find()
+                .filter(media_metadata::Column::FileId.is_in(chunk.to_vec()))
+                .filter(media_metadata::Column::MetaKey.is_in(RELOCATE_TAGS))
+                .all(db)
+                .await?;
+            for row in rows {
+                tags.entry(row.file_id)
+                    .or_default()
+                    .push((row.meta_key, row.meta_value));
+            }
+        }
+
+        for file in missing_files {
+            if let Some(key) = tags
+                .get(&file.id)
+                .and_then(|tags| relocate_key(tags.iter().map(|(k, v)| (k.as_str(), v.as_str()))))
+            {
+                let duration = file.duration.to_f64().unwrap_or_default();
+                self.by_tags
+                    .entry(key)
+                    .or_default()
+                    .push((duration, file.clone()));
+            }
+
+            self.by_hash
+                .entry(file.file_hash.clone())
+                .or_default()
+                .push(file);
+        }
+
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.by_hash.is_empty()
+    }
+
+    /// Finds a missing file with the same content as `description`.
+    fn find_by_hash(
+        &self,
+        description: &mut FileDescription,
+    ) -> Result<Option<media_files::Model>> {
+        let file_hash = description.get_crc()?;
+
+        Ok(self
+            .by_hash
+            .get(&file_hash)
+            .and_then(|files| files.first())
+            .cloned())
+    }
+
+    /// Finds a missing file with the same duration, title, artist and album
+    /// as `description`. Rewriting tags changes the hash, so moves done by
+    /// tag editors are only found this way. Ambiguous matches are ignored.
+    fn find_by_tags(
+        &self,
+        description: &mut FileDescription,
+    ) -> Result<Option<media_files::Model>> {
+        let (_, duration) = description.get_codec_information()?;
+
+        let metadata = read_metadata(description)?;
+        let Some(key) = relocate_key(
+            metadata
+                .metadata
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str())),
+        ) else {
+            return Ok(None);
+        };
+
+        let mut matches = self
+            .by_tags
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .filter(|(x, _)| (x - duration).abs() <= RELOCATE_DURATION_TOLERANCE);
+
+        match (matches.next(), matches.next()) {
+            (Some((_, file)), None) => Ok(Some(file.clone())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Drops the rows of a file that was found again.
+    fn remove(&mut self, directory: &str, file_name: &str) {
+        let is_other =
+            |file: &media_files::Model| file.directory != directory || file.file_name != file_name;
+
+        self.by_hash.retain(|_, files| {
+            files.retain(is_other);
+            !files.is_empty()
+        });
+        self.by_tags.retain(|_, files| {
+            files.retain(|(_, file)| is_other(file));
+            !files.is_empty()
+        });
+    }
+}
+
+/// Points the rows of a file that disappeared from the library at the file
+/// described by `description`, so stats, playlists, likes and analysis
+/// follow a moved or renamed file instead of being deleted with its row.
+///
+/// Missing files are matched by their hash first, then by duration and tags.
+/// Returns the ids of the relocated rows, which is empty if nothing moved.
+pub async fn relocate_moved_file<E>(
+    db: &E,
+    moved_files: &mut MovedFileIndex,
+    lib_path: &Path,
+    description: &mut FileDescription,
+) -> Result<Vec<i32>>
+where
+    E: DatabaseExecutor + sea_orm::ConnectionTrait,
+{
+    let existing_file = media_files::Entity::find()
+        .filter(media_files::Column::Directory.eq(description.directory.clone()))
+        .filter(media_files::Column::FileName.eq(description.file_name.clone()))
+        .one(db)
+        .await?;
+
+    if existing_file.is_some() {
+        return Ok(Vec::new());
+    }
+
+    moved_files.load(db, lib_path).await?;
+    if moved_files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let source = match moved_files.find_by_hash(description)? {
+        Some(file) => file,
+        None => match moved_files.find_by_tags(description)? {
+            Some(file) => file,
+            None => return Ok(Vec::new()),
+        },
+    };
+    moved_files.remove(&source.directory, &source.file_name);
+
+    info!(
+        "Relocating {}/{} to {}",
+        source.directory,
+        source.file_name,
+        description.rel_path.display()
+    );
+
+    // CUE tracks share one file, all of them move together
+    let files = media_files::Entity::find()
+        .filter(media_files::Column::Directory.eq(source.directory.clone()))
+        .filter(media_files::Column::FileName.eq(source.file_name.clone()))
+        .all(db)
+        .await?;
+
+    let mut relocated = Vec::new();
+    for file in files {
+        let file_id = file.id;
+        let mut active_model: media_files::ActiveModel = file.into();
+        active_model.directory = ActiveValue::Set(description.directory.clone());
+        active_model.file_name = ActiveValue::Set(description.file_name.clone());
+        active_model.extension = ActiveValue::Set(description.extension.clone());
+        active_model.update(db).await?;
+
+        relocated.push(file_id);
+    }
+
+    Ok(relocated)
+}
+
+/// Removes the rows of `path` that no longer exist on disk. `path` may point
+/// to a file or to a directory, in which case every file below it is removed.
+///
+/// Returns the number of removed rows.
+pub async fn remove_missing_files(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    path: &Path,
+) -> Result<usize> {
+    let rel_path = path.strip_prefix(lib_path)?;
+    let rel_path = rel_path.to_string_lossy().replace('\\', "/");
+
+    let directory = Path::new(&rel_path)
+        .parent()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = Path::new(&rel_path)
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut condition = Condition::any().add(
+        Condition::all()
+            .add(media_files::Column::Directory.eq(directory))
+            .add(media_files::Column::FileName.eq(file_name)),
+    );
+    if !rel_path.is_empty() {
+        condition = condition
+            .add(media_files::Column::Directory.eq(rel_path.clone()))
+            .add(media_files::Column::Directory.starts_with(format!("{}/", rel_path)));
+    }
+
+    let db_files = media_files::Entity::find()
+        .filter(condition)
+        .all(main_db)
+        .await?;
+
+    let mut removed = 0;
+    for db_file in db_files {
+        let full_path = lib_path
+            .join(PathBuf::from(&db_file.directory))
+            .join(PathBuf::from(&db_file.file_name));
+        if full_path.exists() {
+            continue;
+        }
+
+        info!("Cleaning {}", full_path.to_str().unwrap_or_default());
+        media_files::Entity::delete_by_id(db_file.id)
+            .exec(main_db)
+            .await?;
+        remove_term(main_db, CollectionQueryType::Track, db_file.id).await?;
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PathSyncSummary {
+    pub updated: usize,
+    pub relocated: usize,
+    pub removed: usize,
+}
+
+fn is_cue_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.eq_ignore_ascii_case("cue"))
+        .unwrap_or(false)
+}
+
+/// Syncs only the given paths instead of walking the whole library.
+///
+/// Existing paths are described and synced, directories are scanned
+/// recursively and a touched CUE sheet resyncs the files next to it. Paths
+/// that no longer exist drop their rows, unless the file showed up again at
+/// one of the other paths, in which case the rows are relocated.
+pub async fn sync_library_paths(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    paths: &[PathBuf],
+    cancel_token: Option<&CancellationToken>,
+) -> Result<PathSyncSummary> {
+    let mut summary = PathSyncSummary::default();
+
+    let mut existing_paths: Vec<PathBuf> = Vec::new();
+    let mut missing_paths: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if !path.starts_with(lib_path) {
+            continue;
+        }
+
+        if !path.exists() {
+            missing_paths.push(path.clone());
+        } else if is_cue_path(path) {
+            if let Some(parent) = path.parent() {
+                existing_paths.push(parent.to_path_buf());
+            }
+        } else {
+            existing_paths.push(path.clone());
+        }
+    }
+    existing_paths.sort();
+    existing_paths.dedup();
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path in existing_paths.iter() {
+        let mut scanner = AudioScanner::new(path);
+        while !scanner.has_ended() {
+            files.extend(scanner.read_files(12).into_iter().map(|x| x.into_path()));
+        }
+
+        for unsupported in scanner.take_unsupported() {
+            warn!(
+                "Skipping unsupported file {:?}: {}",
+                unsupported.path, unsupported.reason
+            );
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    let mut cue_sheets = CueSheetCache::new();
+    let mut moved_files = MovedFileIndex::new();
+    for chunk in files.chunks(12) {
+        if let Some(token) = cancel_token {
+            if token.is_cancelled() {
+                info!("Path sync cancelled.");
+                return Ok(summary);
+            }
+        }
+
+        let mut descriptions: Vec<Option<FileDescription>> = chunk
+            .iter()
+            .map(|file| describe_file(file, &Some(lib_path.to_path_buf())))
+            .map(|result| result.ok())
+            .collect();
+
+        for description in descriptions.iter_mut().flatten() {
+            match relocate_moved_file(main_db, &mut moved_files, lib_path, description)
+                .await
+                .with_context(|| format!("Unable to relocate file: {}", description.file_name))
+            {
+                Ok(relocated) => summary.relocated += relocated.len(),
+                Err(e) => error!("{:?}", e),
+            }
+        }
+
+        sync_file_descriptions(main_db, &mut descriptions, &mut cue_sheets, false)
+            .await
+            .with_context(|| "Unable to describe files")?;
+
+        let file_ids = get_file_ids_by_descriptions(main_db, &descriptions).await?;
+        summary.updated += file_ids.len();
+
+        index_media_files(main_db, file_ids, cancel_token)
+            .await
+            .with_context(|| "Unable to index files")?;
+    }
+
+    for path in missing_paths {
+        summary.removed += remove_missing_files(main_db, lib_path, &path)
+            .await
+            .with_context(|| format!("Unable to remove missing files: {}", path.display()))?;
+    }
+
+    Ok(summary)
+}
+
 async fn clean_up_database(main_db: &DatabaseConnection, root_path: &Path) -> Result<()> {
     let db_files = media_files::Entity::find().all(main_db).await?;
 
@@ -816,6 +1456,7 @@ where
 
     // Get the total number of files to scan (assuming AudioScanner has this method)
     let mut processed_files = 0;
+    let mut cue_sheets = CueSheetCache::new();
 
     // Read audio files at a time until no more files are available.
     while !scanner.has_ended() {
@@ -829,6 +1470,27 @@ where
 
         debug!("Reading metadata for the next 12 files");
         let files = scanner.read_files(12);
+
+        for unsupported in scanner.take_unsupported() {
+            warn!(
+                "Skipping unsupported file {:?}: {}",
+                unsupported.path, unsupported.reason
+            );
+            if let Err(e) = insert_log(
+                main_db,
+                LogLevel::Warning,
+                "actions::metadata::scan_audio_library".to_string(),
+                format!(
+                    "Unsupported file {}: {}",
+                    unsupported.path.display(),
+                    unsupported.reason
+                ),
+            )
+            .await
+            {
+                error!("{:?}", e);
+            }
+        }
         let mut descriptions: Vec<Option<FileDescription>> = files
             .clone()
             .into_iter()
@@ -836,7 +1498,18 @@ where
             .map(|result| result.ok())
             .collect();
 
-        match sync_file_descriptions(main_db, &mut descriptions, force)
+        // Reconcile moved files before they are inserted as new ones, the
+        // cleanup would delete their old rows otherwise
+        for description in descriptions.iter_mut().flatten() {
+            if let Err(e) = relocate_moved_file(main_db, &mut moved_files, lib_path, description)
+                .await
+                .with_context(|| format!("Unable to relocate file: {}", description.file_name))
+            {
+                error!("{:?}", e);
+            }
+        }
+
+        match sync_file_descriptions(main_db, &mut descriptions, &mut cue_sheets, force)
             .await
             .with_context(|| "Unable to describe files")
         {
@@ -904,6 +1577,8 @@ pub struct MetadataSummary {
     pub track_number: i32,
     pub duration: f64,
     pub cover_art_id: Option<i32>,
+    /// Offsets of a CUE track in its file, in seconds
+    pub range: Option<(f64, f64)>,
 }
 
 pub async fn get_metadata_summary_by_files(
@@ -947,6 +1622,7 @@ pub async fn get_metadata_summary_by_files(
         let duration = file.duration;
 
         let cover_art_id = file.cover_art_id;
+        let range = get_track_range(&file);
 
         let parsed_disk_number = metadata
             .get("disc_number")
@@ -982,6 +1658,7 @@ pub async fn get_metadata_summary_by_files(
             } else {
                 cover_art_id
             },
+            range,
         };
 
         results.push(summary);
@@ -0,0 +1,509 @@
+use migration::{ExprTrait, IntoCondition};
+use sea_orm::sea_query::{Condition, Expr, Func, LikeExpr, SelectStatement, SimpleExpr};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QuerySelect, QueryTrait, Value};
+
+use analysis::utils::key::MusicalKey;
+
+use crate::actions::mixes::MixQueryError;
+use crate::actions::utils::LOSSLESS_EXTENSIONS;
+use crate::entities::{media_files, media_metadata, media_rhythm};
+
+/// Columns of `media_analysis` that can be filtered.
+const ANALYSIS_FEATURES: [&str; 12] = [
+    "rms",
+    "zcr",
+    "energy",
+    "spectral_centroid",
+    "spectral_flatness",
+    "spectral_slope",
+    "spectral_rolloff",
+    "spectral_spread",
+    "spectral_skewness",
+    "spectral_kurtosis",
+    "perceptual_spread",
+    "perceptual_sharpness",
+];
+
+/// One end of a range. Percentiles are resolved against the whole library
+/// when the query runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Bound {
+    Value(f64),
+    Percentile(f64),
+}
+
+/// An inclusive range written as `min..max`, either end may be left out.
+/// A single value matches exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Range<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+/// Filters over file columns, metadata and analysis results. All of them
+/// compile to SQL conditions on `media_files`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum FieldFilter {
+    Year(Range<i32>),
+    Date(Range<String>),
+    Duration(Range<f64>),
+    SampleRate(Range<i32>),
+    Extension(Vec<String>),
+    Lossless(bool),
+    Metadata {
+        key: String,
+        value: String,
+    },
+    Analysis {
+        column: &'static str,
+        range: Range<Bound>,
+    },
+    Loudness(Range<Bound>),
+    Bpm(Range<Bound>),
+    /// Camelot wheel positions, see `MusicalKey::camelot_index`
+    Key(Vec<i32>),
+}
+
+fn invalid_parameter(operator: &str, parameter: &str) -> MixQueryError {
+    MixQueryError::InvalidParameter {
+        operator: operator.to_string(),
+        parameter: parameter.to_string(),
+    }
+}
+
+fn parse_range<T, F>(operator: &str, parameter: &str, parse: F) -> Result<Range<T>, MixQueryError>
+where
+    T: Clone,
+    F: Fn(&str) -> Option<T>,
+{
+    let parse_end = |x: &str| -> Result<Option<T>, MixQueryError> {
+        let x = x.trim();
+        if x.is_empty() {
+            return Ok(None);
+        }
+
+        parse(x)
+            .map(Some)
+            .ok_or_else(|| invalid_parameter(operator, parameter))
+    };
+
+    let range = match parameter.split_once("..") {
+        Some((min, max)) => Range {
+            min: parse_end(min)?,
+            max: parse_end(max)?,
+        },
+        None => {
+            let value = parse_end(parameter)?;
+            Range {
+                min: value.clone(),
+                max: value,
+            }
+        }
+    };
+
+    if range.min.is_none() && range.max.is_none() {
+        return Err(invalid_parameter(operator, parameter));
+    }
+
+    Ok(range)
+}
+
+fn parse_bound(x: &str) -> Option<Bound> {
+    match x.strip_suffix('%') {
+        Some(percentile) => {
+            let percentile = percentile.trim().parse::<f64>().ok()?;
+            (0.0..=100.0)
+                .contains(&percentile)
+                .then_some(Bound::Percentile(percentile / 100.0))
+        }
+        None => x.parse::<f64>().ok().map(Bound::Value),
+    }
+}
+
+fn parse_list(operator: &str, parameter: &str) -> Result<Vec<String>, MixQueryError> {
+    let items: Vec<String> = parameter
+        .split(',')
+        .map(|x| x.trim().trim_start_matches('.').to_lowercase())
+        .filter(|x| !x.is_empty())
+        .collect();
+
+    if items.is_empty() {
+        return Err(invalid_parameter(operator, parameter));
+    }
+
+    Ok(items)
+}
+
+fn parse_keys(
+    operator: &str,
+    parameter: &str,
+    compatible: bool,
+) -> Result<Vec<i32>, MixQueryError> {
+    let items = parameter
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty());
+
+    let mut keys: Vec<i32> = Vec::new();
+    for item in items {
+        let key: MusicalKey = item
+            .parse()
+            .map_err(|_| invalid_parameter(operator, parameter))?;
+
+        let matching = if compatible {
+            key.compatible_keys().to_vec()
+        } else {
+            vec![key]
+        };
+        keys.extend(matching.iter().map(|x| x.camelot_index() as i32));
+    }
+
+    if keys.is_empty() {
+        return Err(invalid_parameter(operator, parameter));
+    }
+
+    keys.sort_unstable();
+    keys.dedup();
+    Ok(keys)
+}
+
+fn parse_text(operator: &str, parameter: &str) -> Result<String, MixQueryError> {
+    let value = parameter.trim();
+    if value.is_empty() {
+        return Err(invalid_parameter(operator, parameter));
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parses a field filter, returns `None` if `operator` is not one.
+///
+/// * `filter::year(1990..1999)`, `filter::date(1995-03..)`
+/// * `filter::duration(..240)` in seconds, `filter::sample_rate(44100..)`
+/// * `filter::extension(flac,ape)`, `filter::lossless(true)`
+/// * `filter::composer(Bach)`, `filter::label(ECM)`, `filter::metadata.<key>(value)`
+/// * `filter::analysis.<feature>(25%..75%)`, `filter::loudness(-14..)`
+/// * `filter::bpm(120..130)`, `filter::key(8A,Am)`, `filter::key_compatible(8A)`
+///   to keep the keys that mix harmonically with any of the given ones
+///
+/// Ranges are inclusive, and bounds of analysis features, loudness and
+/// tempo may be percentiles of the library.
+pub(crate) fn parse_field_filter(
+    operator: &str,
+    parameter: &str,
+) -> Result<Option<FieldFilter>, MixQueryError> {
+    let filter = match operator {
+        "filter::year" => FieldFilter::Year(parse_range(operator, parameter, |x| x.parse().ok())?),
+        "filter::date" => {
+            FieldFilter::Date(parse_range(operator, parameter, |x| Some(x.to_string()))?)
+        }
+        "filter::duration" => {
+            FieldFilter::Duration(parse_range(operator, parameter, |x| x.parse().ok())?)
+        }
+        "filter::sample_rate" => {
+            FieldFilter::SampleRate(parse_range(operator, parameter, |x| x.parse().ok())?)
+        }
+        "filter::extension" => FieldFilter::Extension(parse_list(operator, parameter)?),
+        "filter::lossless" => FieldFilter::Lossless(
+            parameter
+                .parse()
+                .map_err(|_| invalid_parameter(operator, parameter))?,
+        ),
+        "filter::composer" => FieldFilter::Metadata {
+            key: "composer".to_string(),
+            value: parse_text(operator, parameter)?,
+        },
+        "filter::label" => FieldFilter::Metadata {
+            key: "label".to_string(),
+            value: parse_text(operator, parameter)?,
+        },
+        "filter::loudness" => FieldFilter::Loudness(parse_range(operator, parameter, parse_bound)?),
+        "filter::bpm" => FieldFilter::Bpm(parse_range(operator, parameter, parse_bound)?),
+        "filter::key" => FieldFilter::Key(parse_keys(operator, parameter, false)?),
+        "filter::key_compatible" => FieldFilter::Key(parse_keys(operator, parameter, true)?),
+        _ => {
+            if let Some(key) = operator.strip_prefix("filter::metadata.") {
+                if key.is_empty() {
+                    return Err(MixQueryError::UnknownOperator(operator.to_string()));
+                }
+
+                FieldFilter::Metadata {
+                    key: key.to_string(),
+                    value: parse_text(operator, parameter)?,
+                }
+            } else if let Some(feature) = operator.strip_prefix("filter::analysis.") {
+                let Some(column) = ANALYSIS_FEATURES.iter().find(|x| **x == feature) else {
+                    return Err(MixQueryError::UnknownOperator(operator.to_string()));
+                };
+
+                FieldFilter::Analysis {
+                    column,
+                    range: parse_range(operator, parameter, parse_bound)?,
+                }
+            } else {
+                return Ok(None);
+            }
+        }
+    };
+
+    Ok(Some(filter))
+}
+
+fn file_id_in(subquery: SelectStatement) -> SimpleExpr {
+    Expr::cust("\"media_files\".\"id\"").in_subquery(subquery)
+}
+
+fn metadata_subquery<F>(key: &str, condition: F) -> SimpleExpr
+where
+    F: IntoCondition,
+{
+    file_id_in(
+        media_metadata::Entity::find()
+            .select_only()
+            .column(media_metadata::Column::FileId)
+            .filter(media_metadata::Column::MetaKey.eq(key))
+            .filter(condition)
+            .into_query(),
+    )
+}
+
+/// A `LIKE` pattern matching `value` anywhere, with the wildcards in
+/// `value` matched literally.
+fn contains_pattern(value: &str) -> LikeExpr {
+    let mut pattern = String::with_capacity(value.len() + 2);
+    pattern.push('%');
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+
+    LikeExpr::new(pattern).escape('\\')
+}
+
+/// SQL for one end of a range over `table.column`. Percentiles pick the
+/// value at that rank among the files that have one.
+fn bound_sql(table: &str, column: &str, bound: Bound) -> (String, Value) {
+    match bound {
+        Bound::Value(x) => ("?".to_string(), x.into()),
+        Bound::Percentile(p) => (
+            format!(
+                "(SELECT \"p\".\"{column}\" FROM \"{table}\" AS \"p\" \
+                 WHERE \"p\".\"{column}\" IS NOT NULL ORDER BY \"p\".\"{column}\" LIMIT 1 \
+                 OFFSET (SELECT CAST((COUNT(\"q\".\"{column}\") - 1) * ? AS INTEGER) \
+                 FROM \"{table}\" AS \"q\"))"
+            ),
+            p.into(),
+        ),
+    }
+}
+
+fn bound_range_condition(table: &str, column: &str, range: &Range<Bound>) -> SimpleExpr {
+    let mut predicates = vec![format!("\"{table}\".\"{column}\" IS NOT NULL")];
+    let mut values: Vec<Value> = Vec::new();
+
+    if let Some(min) = range.min {
+        let (sql, value) = bound_sql(table, column, min);
+        predicates.push(format!("\"{table}\".\"{column}\" >= {sql}"));
+        values.push(value);
+    }
+
+    if let Some(max) = range.max {
+        let (sql, value) = bound_sql(table, column, max);
+        predicates.push(format!("\"{table}\".\"{column}\" <= {sql}"));
+        values.push(value);
+    }
+
+    Expr::cust_with_values(
+        format!(
+            "\"media_files\".\"id\" IN (SELECT \"{table}\".\"file_id\" FROM \"{table}\" WHERE {})",
+            predicates.join(" AND ")
+        ),
+        values,
+    )
+}
+
+fn range_condition<C, T>(column: C, range: &Range<T>) -> Condition
+where
+    C: ColumnTrait,
+    T: Into<Value> + Clone,
+{
+    let mut condition = Condition::all();
+
+    if let Some(min) = &range.min {
+        condition = condition.add(column.gte(min.clone()));
+    }
+
+    if let Some(max) = &range.max {
+        condition = condition.add(column.lte(max.clone()));
+    }
+
+    condition
+}
+
+impl FieldFilter {
+    pub(crate) fn condition(&self) -> Condition {
+        match self {
+            FieldFilter::Year(range) => {
+                // Dates are stored as written in the tags, the year leads
+                let year =
+                    Expr::cust("CAST(SUBSTR(\"media_metadata\".\"meta_value\", 1, 4) AS INTEGER)");
+                let mut condition = Condition::all();
+                if let Some(min) = range.min {
+                    condition = condition.add(year.clone().gte(min));
+                }
+                if let Some(max) = range.max {
+                    condition = condition.add(year.lte(max));
+                }
+
+                Condition::all().add(metadata_subquery("date", condition))
+            }
+            FieldFilter::Date(range) => {
+                let date = Expr::col(media_metadata::Column::MetaValue);
+                let mut condition = Condition::all();
+                if let Some(min) = &range.min {
+                    condition = condition.add(date.clone().gte(min.clone()));
+                }
+                if let Some(max) = &range.max {
+                    // `1995-06` should include every day of June
+                    condition = condition.add(date.lte(format!("{}\u{10FFFF}", max)));
+                }
+
+                Condition::all().add(metadata_subquery("date", condition))
+            }
+            FieldFilter::Duration(range) => range_condition(media_files::Column::Duration, range),
+            FieldFilter::SampleRate(range) => {
+                range_condition(media_files::Column::SampleRate, range)
+            }
+            FieldFilter::Extension(extensions) => Condition::all().add(
+                Func::lower(Expr::col(media_files::Column::Extension)).is_in(extensions.clone()),
+            ),
+            FieldFilter::Lossless(lossless) => {
+                let expr = Func::lower(Expr::col(media_files::Column::Extension));
+                Condition::all().add(if *lossless {
+                    expr.is_in(LOSSLESS_EXTENSIONS)
+                } else {
+                    expr.is_not_in(LOSSLESS_EXTENSIONS)
+                })
+            }
+            FieldFilter::Metadata { key, value } => Condition::all().add(metadata_subquery(
+                key,
+                media_metadata::Column::MetaValue.like(contains_pattern(value)),
+            )),
+            FieldFilter::Analysis { column, range } => {
+                Condition::all().add(bound_range_condition("media_analysis", column, range))
+            }
+            FieldFilter::Loudness(range) => Condition::all().add(bound_range_condition(
+                "media_loudness",
+                "integrated_loudness",
+                range,
+            )),
+            FieldFilter::Bpm(range) => {
+                Condition::all().add(bound_range_condition("media_rhythm", "bpm", range))
+            }
+            FieldFilter::Key(keys) => Condition::all().add(file_id_in(
+                media_rhythm::Entity::find()
+                    .select_only()
+                    .column(media_rhythm::Column::FileId)
+                    .filter(media_rhythm::Column::MusicalKey.is_in(keys.clone()))
+                    .into_query(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::sea_query::SqliteQueryBuilder;
+
+    use super::*;
+
+    fn parse_i32(x: &str) -> Option<i32> {
+        x.parse().ok()
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range("filter::year", "1990..1999", parse_i32).unwrap(),
+            Range {
+                min: Some(1990),
+                max: Some(1999)
+            }
+        );
+        assert_eq!(
+            parse_range("filter::year", " 1990 .. ", parse_i32).unwrap(),
+            Range {
+                min: Some(1990),
+                max: None
+            }
+        );
+        assert_eq!(
+            parse_range("filter::year", "..1999", parse_i32).unwrap(),
+            Range {
+                min: None,
+                max: Some(1999)
+            }
+        );
+        assert_eq!(
+            parse_range("filter::year", "1995", parse_i32).unwrap(),
+            Range {
+                min: Some(1995),
+                max: Some(1995)
+            }
+        );
+
+        for parameter in ["", "..", " .. ", "abc", "1990..abc", "abc..1999"] {
+            assert!(
+                matches!(
+                    parse_range("filter::year", parameter, parse_i32),
+                    Err(MixQueryError::InvalidParameter { .. })
+                ),
+                "{:?} should be rejected",
+                parameter
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_bound() {
+        assert_eq!(parse_bound("120"), Some(Bound::Value(120.0)));
+        assert_eq!(parse_bound("-14.5"), Some(Bound::Value(-14.5)));
+        assert_eq!(parse_bound("25%"), Some(Bound::Percentile(0.25)));
+        assert_eq!(parse_bound("0%"), Some(Bound::Percentile(0.0)));
+        assert_eq!(parse_bound("100 %"), Some(Bound::Percentile(1.0)));
+        assert_eq!(parse_bound("101%"), None);
+        assert_eq!(parse_bound("-1%"), None);
+        assert_eq!(parse_bound("%"), None);
+        assert_eq!(parse_bound("fast"), None);
+
+        assert_eq!(
+            parse_range("filter::bpm", "25%..140", parse_bound).unwrap(),
+            Range {
+                min: Some(Bound::Percentile(0.25)),
+                max: Some(Bound::Value(140.0))
+            }
+        );
+    }
+
+    #[test]
+    fn test_metadata_wildcards_are_escaped() {
+        let filter = parse_field_filter("filter::label", "100%_Records\\").unwrap();
+        let Some(filter) = filter else {
+            panic!("filter::label should be a field filter");
+        };
+
+        let sql = media_files::Entity::find()
+            .filter(filter.condition())
+            .into_query()
+            .to_string(SqliteQueryBuilder);
+
+        assert!(
+            sql.contains("LIKE '%100\\%\\_Records\\\\%' ESCAPE '\\'"),
+            "{}",
+            sql
+        );
+    }
+}
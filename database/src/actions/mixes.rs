@@ -1,9 +1,9 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::num::NonZeroU32;
 
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use chrono::Utc;
-use log::warn;
 use migration::ExprTrait;
 use migration::Func;
 use migration::IntoCondition;
@@ -14,11 +14,17 @@ use sea_orm::{
     ActiveValue, ColumnTrait, EntityTrait, JoinType, Order, QueryFilter, QueryOrder, QuerySelect,
     QueryTrait, TransactionTrait,
 };
+use thiserror::Error;
 
 use crate::actions::analysis::get_analyze_count;
 use crate::actions::analysis::get_percentile_analysis_result;
 use crate::actions::cover_art::get_magic_cover_art_id;
+use crate::actions::history::HistorySort;
+use crate::actions::mix_filters::{parse_field_filter, FieldFilter};
 use crate::actions::playback_queue::list_playback_queue;
+use crate::actions::rhythm::RhythmSort;
+use crate::actions::scriptlet::{compile_scriptlet, run_scriptlet_paged, SCRIPTLET_OPERATOR};
+use crate::actions::search::{add_term, remove_term};
 use crate::connection::{MainDbConnection, RecommendationDbConnection};
 use crate::entities::media_file_genres;
 use crate::entities::{
@@ -83,11 +89,11 @@ impl CollectionQuery for mixes::Model {
         CollectionQueryType::Mix
     }
 
-    async fn query_builder(main_db: &MainDbConnection, id: i32) -> Result<Vec<(String, String)>> {
+    async fn query_builder(main_db: &MainDbConnection, id: i32) -> Result<Vec<GroupedQuery>> {
         Ok(get_mix_queries_by_mix_id(main_db, id)
             .await?
             .into_iter()
-            .map(|x| (x.operator, x.parameter))
+            .map(GroupedQuery::from)
             .collect())
     }
 
@@ -204,6 +210,14 @@ pub async fn create_mix(
     };
 
     let inserted_mix = new_mix.insert(db).await?;
+    add_term(
+        db,
+        CollectionQueryType::Mix,
+        inserted_mix.id,
+        &inserted_mix.name,
+    )
+    .await?;
+
     Ok(inserted_mix)
 }
 
@@ -241,6 +255,7 @@ pub async fn update_mix(
         let mut active_model: mixes::ActiveModel = mix.into();
 
         if let Some(name) = name {
+            add_term(db, CollectionQueryType::Mix, id, &name).await?;
             active_model.name = ActiveValue::Set(name);
         }
         if let Some(group) = group {
@@ -271,28 +286,61 @@ pub async fn remove_mix(main_db: &DatabaseConnection, id: i32) -> Result<()> {
     let mix = MixEntity::find_by_id(id).one(main_db).await?;
     if let Some(m) = mix {
         m.delete(main_db).await?;
+        remove_term(main_db, CollectionQueryType::Mix, id).await?;
         Ok(())
     } else {
         bail!("Mix not found")
     }
 }
 
+/// Checks the queries of a mix before they are stored. A scriptlet mix
+/// holds a single `script::source` query whose script has to compile.
+pub fn validate_mix_queries(
+    scriptlet_mode: bool,
+    queries: &[GroupedQuery],
+) -> Result<(), MixQueryError> {
+    if scriptlet_mode {
+        return match queries {
+            [query] if query.operator == SCRIPTLET_OPERATOR => compile_scriptlet(&query.parameter),
+            _ => Err(MixQueryError::Scriptlet(format!(
+                "A scriptlet mix must have exactly one {} query",
+                SCRIPTLET_OPERATOR
+            ))),
+        };
+    }
+
+    for query in queries {
+        parse_query(query)?;
+    }
+
+    Ok(())
+}
+
 pub async fn replace_mix_queries(
     main_db: &DatabaseConnection,
     mix_id: i32,
-    operator_parameters: Vec<(String, String)>,
-    group: Option<i32>,
+    queries: Vec<GroupedQuery>,
 ) -> Result<()> {
     use mix_queries::Entity as MixQueryEntity;
 
+    // Refuse to store a mix that can not be queried later
+    let mix = get_mix_by_id(main_db, mix_id).await?;
+    validate_mix_queries(mix.scriptlet_mode, &queries)?;
+
     let txn = main_db.begin().await?;
     let mut existing_ids = Vec::new();
 
-    for (operator, parameter) in &operator_parameters {
+    for GroupedQuery {
+        operator,
+        parameter,
+        group,
+    } in &queries
+    {
         let mix_query = MixQueryEntity::find()
             .filter(mix_queries::Column::MixId.eq(mix_id))
             .filter(mix_queries::Column::Operator.eq(operator))
             .filter(mix_queries::Column::Parameter.eq(parameter))
+            .filter(mix_queries::Column::Group.eq(*group))
             .one(&txn)
             .await
             .with_context(|| {
@@ -309,7 +357,7 @@ pub async fn replace_mix_queries(
                 mix_id: ActiveValue::Set(mix_id),
                 operator: ActiveValue::Set(operator.clone()),
                 parameter: ActiveValue::Set(parameter.clone()),
-                group: ActiveValue::Set(group.unwrap_or_default()),
+                group: ActiveValue::Set(*group),
                 created_at: ActiveValue::Set(Utc::now().to_rfc3339()),
                 updated_at: ActiveValue::Set(Utc::now().to_rfc3339()),
                 ..Default::default()
@@ -328,11 +376,12 @@ pub async fn replace_mix_queries(
     }
 
     let mut operator_parameter_conditions = Condition::any();
-    for (operator, parameter) in &operator_parameters {
+    for query in &queries {
         operator_parameter_conditions = operator_parameter_conditions.add(
             Condition::all()
-                .add(mix_queries::Column::Operator.eq(operator.clone()))
-                .add(mix_queries::Column::Parameter.eq(parameter.clone())),
+                .add(mix_queries::Column::Operator.eq(query.operator.clone()))
+                .add(mix_queries::Column::Parameter.eq(query.parameter.clone()))
+                .add(mix_queries::Column::Group.eq(query.group)),
         );
     }
 
@@ -373,6 +422,57 @@ pub async fn remove_mix_query(main_db: &DatabaseConnection, id: i32) -> Result<(
     }
 }
 
+/// A mix query together with the group it belongs to.
+///
+/// Group `0` holds the base selection, a file is picked if it matches any of
+/// its `lib::*` operators. Every positive group narrows the selection down to
+/// files that also match one of the `lib::*` operators of that group, and
+/// every negative group drops the files matching any of its `lib::*`
+/// operators. `sort::*`, `filter::*` and `pipe::*` operators apply to the
+/// whole mix and always belong to group `0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupedQuery {
+    pub operator: String,
+    pub parameter: String,
+    pub group: i32,
+}
+
+impl GroupedQuery {
+    pub fn new(operator: impl Into<String>, parameter: impl Into<String>, group: i32) -> Self {
+        GroupedQuery {
+            operator: operator.into(),
+            parameter: parameter.into(),
+            group,
+        }
+    }
+}
+
+impl From<(String, String)> for GroupedQuery {
+    fn from((operator, parameter): (String, String)) -> Self {
+        GroupedQuery::new(operator, parameter, 0)
+    }
+}
+
+impl From<mix_queries::Model> for GroupedQuery {
+    fn from(model: mix_queries::Model) -> Self {
+        GroupedQuery::new(model.operator, model.parameter, model.group)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MixQueryError {
+    #[error("Unknown operator: {0}")]
+    UnknownOperator(String),
+    #[error("Unable to parse the parameter of operator: {operator}({parameter})")]
+    InvalidParameter { operator: String, parameter: String },
+    #[error("Operator {operator} applies to the whole mix and can not be placed in group {group}")]
+    UngroupableOperator { operator: String, group: i32 },
+    #[error("lib::all can not be excluded by group {0}")]
+    ExcludedAll(i32),
+    #[error("Scriptlet failed: {0}")]
+    Scriptlet(String),
+}
+
 #[derive(Debug)]
 enum QueryOperator {
     LibAll(bool),
@@ -390,29 +490,45 @@ enum QueryOperator {
     SortDuration(bool),
     SortPlayedthrough(bool),
     SortSkipped(bool),
+    SortHistory(HistorySort),
+    SortRhythm(RhythmSort),
     FilterLiked(bool),
     FilterWithCoverArt(bool),
     FilterAnalyzed(bool),
     PipeLimit(u64),
     PipeRecommend(i32),
-    Unknown(String),
+    FilterField(FieldFilter),
 }
 
-fn parse_parameter<T>(parameter: &str, operator: &str) -> Option<T>
+impl QueryOperator {
+    /// Whether the operator selects files and can be placed in any group.
+    fn is_selector(&self) -> bool {
+        matches!(
+            self,
+            QueryOperator::LibAll(_)
+                | QueryOperator::LibArtist(_)
+                | QueryOperator::LibAlbum(_)
+                | QueryOperator::LibGenre(_)
+                | QueryOperator::LibPlaylist(_)
+                | QueryOperator::LibTrack(_)
+                | QueryOperator::LibRandom(_)
+                | QueryOperator::LibQueue(_)
+                | QueryOperator::LibDirectoryDeep(_)
+                | QueryOperator::LibDirectoryShallow(_)
+        )
+    }
+}
+
+fn parse_parameter<T>(parameter: &str, operator: &str) -> Result<T, MixQueryError>
 where
     T: std::str::FromStr,
-    T::Err: std::fmt::Debug,
 {
-    match parameter.parse::<T>() {
-        Ok(x) => Some(x),
-        Err(_) => {
-            warn!(
-                "Unable to parse the parameter of operator: {}({})",
-                operator, parameter
-            );
-            None
-        }
-    }
+    parameter
+        .parse::<T>()
+        .map_err(|_| MixQueryError::InvalidParameter {
+            operator: operator.to_string(),
+            parameter: parameter.to_string(),
+        })
 }
 
 pub async fn add_item_to_mix(
@@ -503,67 +619,90 @@ pub async fn initialize_mix_queries(main_db: &DatabaseConnection) -> Result<()>
     Ok(())
 }
 
-fn parse_query(query: &(String, String)) -> QueryOperator {
-    let (operator, parameter) = query;
-    match operator.as_str() {
-        "lib::all" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::LibAll)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "lib::artist" => parse_parameter::<i32>(parameter, operator)
-            .map(QueryOperator::LibArtist)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "lib::album" => parse_parameter::<i32>(parameter, operator)
-            .map(QueryOperator::LibAlbum)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "lib::genre" => parse_parameter::<i32>(parameter, operator)
-            .map(QueryOperator::LibGenre)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "lib::playlist" => parse_parameter::<i32>(parameter, operator)
-            .map(QueryOperator::LibPlaylist)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "lib::track" => parse_parameter::<i32>(parameter, operator)
-            .map(QueryOperator::LibTrack)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "lib::random" => parse_parameter::<i32>(parameter, operator)
-            .map(QueryOperator::LibRandom)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "lib::queue" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::LibQueue)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
+fn parse_query(query: &GroupedQuery) -> Result<QueryOperator, MixQueryError> {
+    let GroupedQuery {
+        operator,
+        parameter,
+        group,
+    } = query;
+
+    let parsed = match operator.as_str() {
+        "lib::all" => QueryOperator::LibAll(parse_parameter(parameter, operator)?),
+        "lib::artist" => QueryOperator::LibArtist(parse_parameter(parameter, operator)?),
+        "lib::album" => QueryOperator::LibAlbum(parse_parameter(parameter, operator)?),
+        "lib::genre" => QueryOperator::LibGenre(parse_parameter(parameter, operator)?),
+        "lib::playlist" => QueryOperator::LibPlaylist(parse_parameter(parameter, operator)?),
+        "lib::track" => QueryOperator::LibTrack(parse_parameter(parameter, operator)?),
+        "lib::random" => QueryOperator::LibRandom(parse_parameter(parameter, operator)?),
+        "lib::queue" => QueryOperator::LibQueue(parse_parameter(parameter, operator)?),
         "lib::directory.deep" => QueryOperator::LibDirectoryDeep(parameter.clone()),
         "lib::directory.shallow" => QueryOperator::LibDirectoryShallow(parameter.clone()),
-        "sort::track_number" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::SortTrackNumber)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "sort::last_modified" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::SortLastModified)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "sort::duration" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::SortDuration)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "sort::playedthrough" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::SortPlayedthrough)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "sort::skipped" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::SortSkipped)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "filter::liked" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::FilterLiked)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "filter::analyzed" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::FilterAnalyzed)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "filter::with_cover_art" => parse_parameter::<bool>(parameter, operator)
-            .map(QueryOperator::FilterWithCoverArt)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "pipe::limit" => parse_parameter::<u64>(parameter, operator)
-            .map(QueryOperator::PipeLimit)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        "pipe::recommend" => parse_parameter::<i32>(parameter, operator)
-            .map(QueryOperator::PipeRecommend)
-            .unwrap_or(QueryOperator::Unknown(operator.clone())),
-        _ => QueryOperator::Unknown(operator.clone()),
+        "sort::track_number" => {
+            QueryOperator::SortTrackNumber(parse_parameter(parameter, operator)?)
+        }
+        "sort::last_modified" => {
+            QueryOperator::SortLastModified(parse_parameter(parameter, operator)?)
+        }
+        "sort::duration" => QueryOperator::SortDuration(parse_parameter(parameter, operator)?),
+        "sort::playedthrough" => {
+            QueryOperator::SortPlayedthrough(parse_parameter(parameter, operator)?)
+        }
+        "sort::skipped" => QueryOperator::SortSkipped(parse_parameter(parameter, operator)?),
+        "sort::last_played" => QueryOperator::SortHistory(HistorySort::LastPlayed(
+            parse_parameter(parameter, operator)?,
+        )),
+        "sort::recent_plays" => QueryOperator::SortHistory(HistorySort::RecentPlays(
+            parse_parameter::<NonZeroU32>(parameter, operator)?,
+        )),
+        "sort::decayed_plays" => QueryOperator::SortHistory(HistorySort::DecayedPlays(
+            parse_parameter::<NonZeroU32>(parameter, operator)?,
+        )),
+        "sort::bpm" => {
+            QueryOperator::SortRhythm(RhythmSort::Bpm(parse_parameter(parameter, operator)?))
+        }
+        "sort::key" => {
+            QueryOperator::SortRhythm(RhythmSort::Key(parse_parameter(parameter, operator)?))
+        }
+        "filter::liked" => QueryOperator::FilterLiked(parse_parameter(parameter, operator)?),
+        "filter::analyzed" => QueryOperator::FilterAnalyzed(parse_parameter(parameter, operator)?),
+        "filter::with_cover_art" => {
+            QueryOperator::FilterWithCoverArt(parse_parameter(parameter, operator)?)
+        }
+        "pipe::limit" => QueryOperator::PipeLimit(parse_parameter(parameter, operator)?),
+        "pipe::recommend" => QueryOperator::PipeRecommend(parse_parameter(parameter, operator)?),
+        _ => match parse_field_filter(operator, parameter)? {
+            Some(filter) => QueryOperator::FilterField(filter),
+            None => return Err(MixQueryError::UnknownOperator(operator.clone())),
+        },
+    };
+
+    if *group != 0 && !parsed.is_selector() {
+        return Err(MixQueryError::UngroupableOperator {
+            operator: operator.clone(),
+            group: *group,
+        });
     }
+
+    if *group < 0 && matches!(parsed, QueryOperator::LibAll(true)) {
+        return Err(MixQueryError::ExcludedAll(*group));
+    }
+
+    Ok(parsed)
+}
+
+/// The `lib::*` operators of one group.
+#[derive(Debug, Default)]
+struct LibSelectors {
+    all: bool,
+    artist_ids: Vec<i32>,
+    album_ids: Vec<i32>,
+    genre_ids: Vec<i32>,
+    playlist_ids: Vec<i32>,
+    track_ids: Vec<i32>,
+    random_count: Vec<i32>,
+    directories_deep: Vec<String>,
+    directories_shallow: Vec<String>,
+    playback_queue: Option<bool>,
 }
 
 fn apply_join_filter(
@@ -657,92 +796,23 @@ fn sort_media_files(
     media_files
 }
 
-pub async fn query_mix_media_files(
+/// Builds the condition matching any of the `lib::*` operators of a group.
+async fn build_selector_condition(
     main_db: &DatabaseConnection,
-    recommend_db: &RecommendationDbConnection,
-    queries: Vec<(String, String)>,
-    cursor: usize,
-    page_size: usize,
-) -> Result<Vec<media_files::Model>> {
-    let mut all: bool = false;
-
-    let mut artist_ids: Vec<i32> = vec![];
-    let mut album_ids: Vec<i32> = vec![];
-    let mut genre_ids: Vec<i32> = vec![];
-    let mut playlist_ids: Vec<i32> = vec![];
-    let mut track_ids: Vec<i32> = vec![];
-    let mut random_count: Vec<i32> = vec![];
-    let mut directories_deep: Vec<String> = vec![];
-    let mut directories_shallow: Vec<String> = vec![];
-    let mut playback_queue: Option<bool> = None;
-
-    let mut sort_track_number_asc: Option<bool> = None;
-    let mut sort_last_modified_asc: Option<bool> = None;
-    let mut sort_duration_asc: Option<bool> = None;
-    let mut sort_playedthrough_asc: Option<bool> = None;
-    let mut sort_skipped_asc: Option<bool> = None;
-
-    let mut filter_liked: Option<bool> = None;
-    let mut filter_cover_art: Option<bool> = None;
-    let mut filter_analyzed: Option<bool> = None;
-    let mut pipe_limit: Option<u64> = None;
-    let mut pipe_recommend: Option<i32> = None;
-
-    for query in queries {
-        match parse_query(&query) {
-            QueryOperator::LibAll(is_all) => all = is_all,
-            QueryOperator::LibArtist(id) => artist_ids.push(id),
-            QueryOperator::LibAlbum(id) => album_ids.push(id),
-            QueryOperator::LibGenre(id) => genre_ids.push(id),
-            QueryOperator::LibPlaylist(id) => playlist_ids.push(id),
-            QueryOperator::LibTrack(id) => track_ids.push(id),
-            QueryOperator::LibRandom(count) => random_count.push(count),
-            QueryOperator::LibQueue(enabled) => playback_queue = Some(enabled),
-            QueryOperator::LibDirectoryDeep(dir) => directories_deep.push(dir),
-            QueryOperator::LibDirectoryShallow(dir) => directories_shallow.push(dir),
-            QueryOperator::SortTrackNumber(asc) => sort_track_number_asc = Some(asc),
-            QueryOperator::SortLastModified(asc) => sort_last_modified_asc = Some(asc),
-            QueryOperator::SortDuration(asc) => sort_duration_asc = Some(asc),
-            QueryOperator::SortPlayedthrough(asc) => sort_playedthrough_asc = Some(asc),
-            QueryOperator::SortSkipped(asc) => sort_skipped_asc = Some(asc),
-            QueryOperator::FilterLiked(liked) => filter_liked = Some(liked),
-            QueryOperator::FilterWithCoverArt(cover_art) => filter_cover_art = Some(cover_art),
-            QueryOperator::FilterAnalyzed(analyzed) => filter_analyzed = Some(analyzed),
-            QueryOperator::PipeLimit(limit) => pipe_limit = Some(limit),
-            QueryOperator::PipeRecommend(recommend) => pipe_recommend = Some(recommend),
-            QueryOperator::Unknown(op) => warn!("Unknown operator: {}", op),
-        }
-    }
-
-    if pipe_recommend.is_some() && cursor > 0 {
-        return Ok([].to_vec());
-    }
-
-    if pipe_recommend.is_some() && get_analyze_count(main_db).await? < 1 {
-        return Ok([].to_vec());
-    }
-
-    if pipe_recommend.is_some() {
-        filter_analyzed = Some(true);
-    }
-
-    let only_one_playlist = artist_ids.is_empty()
-        && album_ids.is_empty()
-        && track_ids.is_empty()
-        && genre_ids.is_empty()
-        && random_count.is_empty()
-        && directories_deep.is_empty()
-        && directories_shallow.is_empty()
-        && playlist_ids.len() == 1;
-
-    let only_playlist = if only_one_playlist {
-        playlist_ids[0]
-    } else {
-        -1
-    };
-
-    // Base query for media_files
-    let mut query = media_files::Entity::find();
+    selectors: LibSelectors,
+) -> Result<Condition> {
+    let LibSelectors {
+        artist_ids,
+        album_ids,
+        genre_ids,
+        playlist_ids,
+        track_ids,
+        random_count,
+        directories_deep,
+        directories_shallow,
+        playback_queue,
+        ..
+    } = selectors;
 
     // Create an OR condition to hold all the subconditions
     let mut or_condition = Condition::any();
@@ -777,7 +847,7 @@ pub async fn query_mix_media_files(
     // Filter by playlist_ids if provided
     add_subquery_filter!(
         or_condition,
-        playlist_ids.clone(),
+        playlist_ids,
         media_file_playlists::Entity,
         media_file_playlists::Column::PlaylistId,
         media_file_playlists::Column::MediaFileId
@@ -787,7 +857,7 @@ pub async fn query_mix_media_files(
     if !track_ids.is_empty() {
         let subquery = media_files::Entity::find()
             .select_only()
-            .filter(media_files::Column::Id.is_in(track_ids.clone()))
+            .filter(media_files::Column::Id.is_in(track_ids))
             .column(media_files::Column::Id)
             .into_query();
 
@@ -847,17 +917,198 @@ pub async fn query_mix_media_files(
         }
     }
 
+    Ok(or_condition)
+}
+
+/// Runs a scriptlet and returns one page of the tracks it picked.
+///
+/// The script runs for the first page, later pages are taken from the
+/// result of that run.
+async fn query_scriptlet_media_files(
+    main_db: &DatabaseConnection,
+    recommend_db: &RecommendationDbConnection,
+    source: &str,
+    cursor: usize,
+    page_size: usize,
+) -> Result<Vec<media_files::Model>> {
+    let file_ids: Vec<i32> = run_scriptlet_paged(main_db, recommend_db, source, cursor)
+        .await?
+        .iter()
+        .skip(cursor)
+        .take(page_size)
+        .copied()
+        .collect();
+
+    let file_map: HashMap<i32, media_files::Model> = get_files_by_ids(main_db, &file_ids)
+        .await?
+        .into_iter()
+        .map(|file| (file.id, file))
+        .collect();
+
+    Ok(file_ids
+        .into_iter()
+        .filter_map(|id| file_map.get(&id).cloned())
+        .collect())
+}
+
+/// Queries the files selected by a mix, see `GroupedQuery` for how groups
+/// combine. Malformed queries are rejected with a `MixQueryError`.
+pub async fn query_mix_media_files<Q>(
+    main_db: &DatabaseConnection,
+    recommend_db: &RecommendationDbConnection,
+    queries: Vec<Q>,
+    cursor: usize,
+    page_size: usize,
+) -> Result<Vec<media_files::Model>>
+where
+    Q: Into<GroupedQuery>,
+{
+    let queries: Vec<GroupedQuery> = queries.into_iter().map(Into::into).collect();
+
+    if let Some(scriptlet) = queries.iter().find(|x| x.operator == SCRIPTLET_OPERATOR) {
+        if queries.len() > 1 {
+            bail!(MixQueryError::Scriptlet(format!(
+                "{} can not be combined with other queries",
+                SCRIPTLET_OPERATOR
+            )));
+        }
+
+        return query_scriptlet_media_files(
+            main_db,
+            recommend_db,
+            &scriptlet.parameter,
+            cursor,
+            page_size,
+        )
+        .await;
+    }
+
+    let mut groups: BTreeMap<i32, LibSelectors> = BTreeMap::new();
+
+    let mut sort_track_number_asc: Option<bool> = None;
+    let mut sort_last_modified_asc: Option<bool> = None;
+    let mut sort_duration_asc: Option<bool> = None;
+    let mut sort_playedthrough_asc: Option<bool> = None;
+    let mut sort_skipped_asc: Option<bool> = None;
+    let mut sort_history: Vec<HistorySort> = vec![];
+    let mut sort_rhythm: Vec<RhythmSort> = vec![];
+
+    let mut filter_liked: Option<bool> = None;
+    let mut filter_cover_art: Option<bool> = None;
+    let mut filter_analyzed: Option<bool> = None;
+    let mut pipe_limit: Option<u64> = None;
+    let mut pipe_recommend: Option<i32> = None;
+    let mut field_filters: Vec<FieldFilter> = vec![];
+
+    for query in queries {
+        let operator = parse_query(&query)?;
+        let selectors = groups.entry(query.group).or_default();
+
+        match operator {
+            QueryOperator::LibAll(is_all) => selectors.all = is_all,
+            QueryOperator::LibArtist(id) => selectors.artist_ids.push(id),
+            QueryOperator::LibAlbum(id) => selectors.album_ids.push(id),
+            QueryOperator::LibGenre(id) => selectors.genre_ids.push(id),
+            QueryOperator::LibPlaylist(id) => selectors.playlist_ids.push(id),
+            QueryOperator::LibTrack(id) => selectors.track_ids.push(id),
+            QueryOperator::LibRandom(count) => selectors.random_count.push(count),
+            QueryOperator::LibQueue(enabled) => selectors.playback_queue = Some(enabled),
+            QueryOperator::LibDirectoryDeep(dir) => selectors.directories_deep.push(dir),
+            QueryOperator::LibDirectoryShallow(dir) => selectors.directories_shallow.push(dir),
+            QueryOperator::SortTrackNumber(asc) => sort_track_number_asc = Some(asc),
+            QueryOperator::SortLastModified(asc) => sort_last_modified_asc = Some(asc),
+            QueryOperator::SortDuration(asc) => sort_duration_asc = Some(asc),
+            QueryOperator::SortPlayedthrough(asc) => sort_playedthrough_asc = Some(asc),
+            QueryOperator::SortSkipped(asc) => sort_skipped_asc = Some(asc),
+            QueryOperator::SortHistory(sort) => sort_history.push(sort),
+            QueryOperator::SortRhythm(sort) => sort_rhythm.push(sort),
+            QueryOperator::FilterLiked(liked) => filter_liked = Some(liked),
+            QueryOperator::FilterWithCoverArt(cover_art) => filter_cover_art = Some(cover_art),
+            QueryOperator::FilterAnalyzed(analyzed) => filter_analyzed = Some(analyzed),
+            QueryOperator::PipeLimit(limit) => pipe_limit = Some(limit),
+            QueryOperator::PipeRecommend(recommend) => pipe_recommend = Some(recommend),
+            QueryOperator::FilterField(filter) => field_filters.push(filter),
+        }
+    }
+
+    if pipe_recommend.is_some() && cursor > 0 {
+        return Ok([].to_vec());
+    }
+
+    if pipe_recommend.is_some() && get_analyze_count(main_db).await? < 1 {
+        return Ok([].to_vec());
+    }
+
+    if pipe_recommend.is_some() {
+        filter_analyzed = Some(true);
+    }
+
+    let base = groups.remove(&0).unwrap_or_default();
+    let all = base.all;
+    let track_ids = base.track_ids.clone();
+
+    let only_one_playlist = base.artist_ids.is_empty()
+        && base.album_ids.is_empty()
+        && base.track_ids.is_empty()
+        && base.genre_ids.is_empty()
+        && base.random_count.is_empty()
+        && base.directories_deep.is_empty()
+        && base.directories_shallow.is_empty()
+        && base.playlist_ids.len() == 1
+        && groups.is_empty();
+
+    let only_playlist = if only_one_playlist {
+        base.playlist_ids[0]
+    } else {
+        -1
+    };
+
+    // Base query for media_files
+    let mut query = media_files::Entity::find();
+
+    let or_condition = build_selector_condition(main_db, base).await?;
+
+    // Every other group narrows down or excludes from the base selection
+    let mut group_condition = Condition::all();
+    for (group, selectors) in groups {
+        if group > 0 && selectors.all {
+            continue;
+        }
+
+        let condition = build_selector_condition(main_db, selectors).await?;
+        if condition.is_empty() {
+            continue;
+        }
+
+        group_condition = if group > 0 {
+            group_condition.add(condition)
+        } else {
+            group_condition.add(condition.not())
+        };
+    }
+
     let has_liked = filter_liked.is_some();
     let has_cover_art = filter_cover_art.is_some();
     let has_analyzed = filter_analyzed.is_some();
 
-    if has_liked || has_cover_art || has_analyzed {
+    let has_groups = !group_condition.is_empty();
+    let has_fields = !field_filters.is_empty();
+
+    if has_liked || has_cover_art || has_analyzed || has_groups || has_fields {
         let mut filter = Condition::all();
 
         if !all {
             filter = filter.add(or_condition);
         }
 
+        if has_groups {
+            filter = filter.add(group_condition);
+        }
+
+        for field_filter in &field_filters {
+            filter = filter.add(field_filter.condition());
+        }
+
         if let Some(liked) = filter_liked {
             filter = filter.add(media_file_stats::Column::Liked.eq(liked));
         }
@@ -954,6 +1205,16 @@ pub async fn query_mix_media_files(
             );
         }
 
+        for sort in &sort_history {
+            let (expr, order) = sort.order();
+            query = query.order_by(expr, order);
+        }
+
+        for sort in &sort_rhythm {
+            let (expr, order) = sort.order();
+            query = query.order_by(expr, order);
+        }
+
         if let Some(query_limit) = pipe_limit {
             query = query.limit(query_limit);
         }
@@ -1057,6 +1318,16 @@ pub async fn query_mix_media_files(
         );
     }
 
+    for sort in &sort_history {
+        let (expr, order) = sort.order();
+        query = query.order_by(expr, order);
+    }
+
+    for sort in &sort_rhythm {
+        let (expr, order) = sort.order();
+        query = query.order_by(expr, order);
+    }
+
     if let Some(limit) = pipe_limit {
         if cursor as u64 >= limit {
             return Ok(vec![]);
@@ -1080,3 +1351,225 @@ pub async fn query_mix_media_files(
 
     Ok(sorted_files)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::connection::{connect_recommendation_db, connect_test_main_db};
+
+    fn query(operator: &str, parameter: &str, group: i32) -> GroupedQuery {
+        GroupedQuery::new(operator, parameter, group)
+    }
+
+    async fn insert_file(db: &DatabaseConnection, id: i32, directory: &str) {
+        media_files::ActiveModel {
+            id: ActiveValue::Set(id),
+            file_name: ActiveValue::Set(format!("{}.ogg", id)),
+            directory: ActiveValue::Set(directory.to_string()),
+            extension: ActiveValue::Set("ogg".to_string()),
+            file_hash: ActiveValue::Set(format!("hash-{}", id)),
+            last_modified: ActiveValue::Set("2024-01-01T00:00:00+00:00".to_string()),
+            cover_art_id: ActiveValue::Set(None),
+            sample_rate: ActiveValue::Set(44100),
+            duration: ActiveValue::Set(Decimal::new(180, 0)),
+            cue_track: ActiveValue::Set(None),
+            start_offset: ActiveValue::Set(None),
+            end_offset: ActiveValue::Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    async fn query_ids(
+        main_db: &DatabaseConnection,
+        recommend_db: &RecommendationDbConnection,
+        queries: Vec<GroupedQuery>,
+    ) -> Vec<i32> {
+        let mut ids: Vec<i32> = query_mix_media_files(main_db, recommend_db, queries, 0, 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_validate_mix_queries() {
+        assert!(validate_mix_queries(
+            false,
+            &[
+                query("lib::directory.deep", "A", 0),
+                query("lib::track", "1", -1),
+                query("lib::artist", "2", 1),
+                query("sort::duration", "true", 0),
+            ]
+        )
+        .is_ok());
+
+        assert!(matches!(
+            validate_mix_queries(false, &[query("lib::nothing", "1", 0)]),
+            Err(MixQueryError::UnknownOperator(operator)) if operator == "lib::nothing"
+        ));
+        assert!(matches!(
+            validate_mix_queries(false, &[query("lib::track", "abc", 0)]),
+            Err(MixQueryError::InvalidParameter { operator, parameter })
+                if operator == "lib::track" && parameter == "abc"
+        ));
+        assert!(matches!(
+            validate_mix_queries(false, &[query("sort::duration", "true", 1)]),
+            Err(MixQueryError::UngroupableOperator { operator, group: 1 })
+                if operator == "sort::duration"
+        ));
+        assert!(matches!(
+            validate_mix_queries(false, &[query("lib::all", "true", -1)]),
+            Err(MixQueryError::ExcludedAll(-1))
+        ));
+        assert!(matches!(
+            validate_mix_queries(true, &[query("lib::all", "true", 0)]),
+            Err(MixQueryError::Scriptlet(_))
+        ));
+        assert!(matches!(
+            validate_mix_queries(
+                true,
+                &[
+                    query(SCRIPTLET_OPERATOR, "", 0),
+                    query(SCRIPTLET_OPERATOR, "", 0)
+                ]
+            ),
+            Err(MixQueryError::Scriptlet(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_query_groups() {
+        let main_db = connect_test_main_db().await;
+        let lib_dir = TempDir::new().unwrap();
+        let recommend_db =
+            connect_recommendation_db(lib_dir.path().to_str().unwrap(), None).unwrap();
+
+        insert_file(&main_db, 1, "A").await;
+        insert_file(&main_db, 2, "A/Disc 2").await;
+        insert_file(&main_db, 3, "B").await;
+        insert_file(&main_db, 4, "AB").await;
+
+        // Group 0 is the union of its selectors
+        assert_eq!(
+            query_ids(
+                &main_db,
+                &recommend_db,
+                vec![query("lib::directory.deep", "A", 0)]
+            )
+            .await,
+            vec![1, 2]
+        );
+        assert_eq!(
+            query_ids(
+                &main_db,
+                &recommend_db,
+                vec![
+                    query("lib::directory.deep", "A", 0),
+                    query("lib::track", "3", 0)
+                ]
+            )
+            .await,
+            vec![1, 2, 3]
+        );
+
+        // Negative groups exclude from the base selection
+        assert_eq!(
+            query_ids(
+                &main_db,
+                &recommend_db,
+                vec![
+                    query("lib::directory.deep", "A", 0),
+                    query("lib::track", "2", -1)
+                ]
+            )
+            .await,
+            vec![1]
+        );
+
+        // Positive groups narrow the base selection down
+        assert_eq!(
+            query_ids(
+                &main_db,
+                &recommend_db,
+                vec![
+                    query("lib::all", "true", 0),
+                    query("lib::track", "2", 1),
+                    query("lib::track", "3", 1)
+                ]
+            )
+            .await,
+            vec![2, 3]
+        );
+        assert_eq!(
+            query_ids(
+                &main_db,
+                &recommend_db,
+                vec![
+                    query("lib::directory.deep", "A", 0),
+                    query("lib::track", "2", 1),
+                    query("lib::track", "3", 1)
+                ]
+            )
+            .await,
+            vec![2]
+        );
+
+        // lib::all in a narrowing group keeps the base selection
+        assert_eq!(
+            query_ids(
+                &main_db,
+                &recommend_db,
+                vec![
+                    query("lib::directory.deep", "B", 0),
+                    query("lib::all", "true", 1)
+                ]
+            )
+            .await,
+            vec![3]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_stored_queries_are_rejected() {
+        let main_db = connect_test_main_db().await;
+        let lib_dir = TempDir::new().unwrap();
+        let recommend_db =
+            connect_recommendation_db(lib_dir.path().to_str().unwrap(), None).unwrap();
+
+        insert_file(&main_db, 1, "A").await;
+        insert_file(&main_db, 2, "B").await;
+
+        for malformed in [
+            query("lib::track", "abc", 0),
+            query("lib::removed", "1", 0),
+            query("sort::duration", "true", 2),
+            query("lib::all", "true", -1),
+        ] {
+            let error = query_mix_media_files(
+                &main_db,
+                &recommend_db,
+                vec![query("lib::directory.deep", "A", 0), malformed.clone()],
+                0,
+                100,
+            )
+            .await
+            .unwrap_err();
+
+            assert!(
+                error.downcast_ref::<MixQueryError>().is_some(),
+                "{:?}: {:?}",
+                malformed,
+                error
+            );
+        }
+    }
+}
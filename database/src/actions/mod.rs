@@ -1,19 +1,33 @@
 pub mod albums;
 pub mod analysis;
 pub mod artists;
+pub mod catalog;
 pub mod collection;
 pub mod cover_art;
 pub mod directory;
+pub mod duplicates;
+pub mod equalizer;
 pub mod file;
+pub mod history;
+pub mod history_import;
 pub mod genres;
 pub mod index;
 pub mod library;
 pub mod logging;
+pub mod loudness;
 pub mod metadata;
+pub mod mix_filters;
 pub mod mixes;
 pub mod playback_queue;
+pub mod playlist_formats;
 pub mod playlists;
 pub mod recommendation;
+pub mod rhythm;
+pub mod scriptlet;
+pub mod scrobble_queue;
 pub mod search;
+pub mod search_query;
 pub mod stats;
+pub mod tag_edits;
+pub mod track_matching;
 pub mod utils;
@@ -0,0 +1,880 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Error, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{Deserialize, Serialize};
+use xml::reader::{EventReader, XmlEvent};
+
+const XSPF_NAMESPACE: &str = "http://xspf.org/ns/0/";
+
+/// Characters escaped in the paths of `file://` URIs.
+const PATH_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u8,
+    Pls,
+    Xspf,
+    Jspf,
+    /// Cue sheet listing one file per track, only exported
+    Cue,
+}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Pls => "pls",
+            PlaylistFormat::Xspf => "xspf",
+            PlaylistFormat::Jspf => "jspf",
+            PlaylistFormat::Cue => "cue",
+        }
+    }
+
+    /// Whether the locations of the format are URIs rather than paths.
+    fn uses_uris(&self) -> bool {
+        matches!(self, PlaylistFormat::Xspf | PlaylistFormat::Jspf)
+    }
+}
+
+impl FromStr for PlaylistFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "m3u" | "m3u8" => Ok(PlaylistFormat::M3u8),
+            "pls" => Ok(PlaylistFormat::Pls),
+            "xspf" => Ok(PlaylistFormat::Xspf),
+            "jspf" => Ok(PlaylistFormat::Jspf),
+            "cue" => Ok(PlaylistFormat::Cue),
+            _ => bail!("Unsupported playlist format: {}", s),
+        }
+    }
+}
+
+/// A track of a playlist file. Players write very different amounts of
+/// information, so every field is optional.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    /// Path or URI of the file, as written in the playlist
+    pub location: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Length in seconds
+    pub duration: Option<f64>,
+}
+
+impl PlaylistEntry {
+    /// A short description of the entry for error reports.
+    pub fn describe(&self) -> String {
+        if let Some(location) = &self.location {
+            return location.clone();
+        }
+
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title.clone(),
+            _ => "Unknown entry".to_owned(),
+        }
+    }
+
+    /// The local path of the entry, `None` for remote locations.
+    pub fn path(&self) -> Option<String> {
+        location_to_path(self.location.as_deref()?)
+    }
+}
+
+/// Converts a playlist location to a local path, decoding `file://` URIs.
+fn location_to_path(location: &str) -> Option<String> {
+    let location = location.trim();
+
+    let Some((scheme, rest)) = location.split_once("://") else {
+        return Some(location.to_owned());
+    };
+
+    // Windows drive letters such as `C:\` are not URI schemes
+    if scheme.len() == 1 {
+        return Some(location.to_owned());
+    }
+
+    if !scheme.eq_ignore_ascii_case("file") {
+        return None;
+    }
+
+    // Skip the host, usually empty or `localhost`
+    let path = &rest[rest.find('/')?..];
+    let path = percent_decode_str(path).decode_utf8_lossy().into_owned();
+
+    // `file:///C:/Music` names a Windows path
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[2] == b':' && bytes[1].is_ascii_alphabetic() {
+        return Some(path[1..].to_owned());
+    }
+
+    Some(path)
+}
+
+/// Decodes relative URI references of XSPF and JSPF playlists to paths,
+/// absolute URIs are kept as written.
+fn decode_uri_reference(location: &str) -> Option<String> {
+    let location = non_empty(location)?;
+    if location.contains("://") {
+        return Some(location);
+    }
+
+    Some(
+        percent_decode_str(&location)
+            .decode_utf8_lossy()
+            .into_owned(),
+    )
+}
+
+/// Converts a path to a playlist location of the format.
+fn path_to_location(format: PlaylistFormat, path: &str) -> String {
+    if !format.uses_uris() {
+        return path.to_owned();
+    }
+
+    let path = path.replace('\\', "/");
+    let encoded = utf8_percent_encode(&path, PATH_SET).to_string();
+
+    if path.starts_with('/') {
+        format!("file://{}", encoded)
+    } else if path.as_bytes().get(1) == Some(&b':') {
+        format!("file:///{}", encoded)
+    } else {
+        encoded
+    }
+}
+
+/// Parses the content of a playlist file.
+pub fn parse_playlist(format: PlaylistFormat, content: &str) -> Result<Vec<PlaylistEntry>> {
+    // Some editors prepend a byte order mark
+    let content = content.trim_start_matches('\u{feff}');
+
+    match format {
+        PlaylistFormat::M3u8 => Ok(parse_m3u(content)),
+        PlaylistFormat::Pls => parse_pls(content),
+        PlaylistFormat::Xspf => parse_xspf(content),
+        PlaylistFormat::Jspf => parse_jspf(content),
+        PlaylistFormat::Cue => bail!("Cue sheets can only be exported"),
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+fn parse_duration(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|x| *x > 0.0)
+}
+
+/// Splits the `Artist - Title` display names of M3U and PLS entries.
+fn split_display_name(name: &str) -> (Option<String>, Option<String>) {
+    match name.split_once(" - ") {
+        Some((artist, title)) => (non_empty(artist), non_empty(title)),
+        None => (None, non_empty(name)),
+    }
+}
+
+fn parse_m3u(content: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut pending = PlaylistEntry::default();
+
+    for line in content.lines().map(str::trim).filter(|x| !x.is_empty()) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // `#EXTINF:<seconds> <attributes>,<artist> - <title>`
+            let (head, name) = info.split_once(',').unwrap_or((info, ""));
+            let duration = head.split_whitespace().next().unwrap_or_default();
+            let (artist, title) = split_display_name(name);
+
+            pending.duration = parse_duration(duration);
+            pending.artist = artist;
+            pending.title = title;
+        } else if let Some(album) = line.strip_prefix("#EXTALB:") {
+            pending.album = non_empty(album);
+        } else if !line.starts_with('#') {
+            pending.location = Some(line.to_owned());
+            entries.push(std::mem::take(&mut pending));
+        }
+    }
+
+    entries
+}
+
+fn parse_pls(content: &str) -> Result<Vec<PlaylistEntry>> {
+    let mut entries: Vec<(usize, PlaylistEntry)> = Vec::new();
+
+    for line in content.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let key = key.trim().to_lowercase();
+        let (field, index) = match key.find(|c: char| c.is_ascii_digit()) {
+            Some(position) => key.split_at(position),
+            None => continue,
+        };
+        let Ok(index) = index.parse::<usize>() else {
+            continue;
+        };
+
+        let entry = match entries.iter().position(|(x, _)| *x == index) {
+            Some(position) => &mut entries[position].1,
+            None => {
+                entries.push((index, PlaylistEntry::default()));
+                &mut entries.last_mut().unwrap().1
+            }
+        };
+
+        match field {
+            "file" => entry.location = non_empty(value),
+            "title" => {
+                let (artist, title) = split_display_name(value);
+                entry.artist = artist;
+                entry.title = title;
+            }
+            // -1 marks streams of unknown length
+            "length" => entry.duration = parse_duration(value),
+            _ => {}
+        }
+    }
+
+    if entries.is_empty() && !content.to_lowercase().contains("[playlist]") {
+        bail!("Not a PLS playlist");
+    }
+
+    entries.sort_by_key(|(index, _)| *index);
+    Ok(entries
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .filter(|x| x.location.is_some())
+        .collect())
+}
+
+fn parse_xspf(content: &str) -> Result<Vec<PlaylistEntry>> {
+    let mut entries = Vec::new();
+    let mut track: Option<PlaylistEntry> = None;
+    // Elements below the current track, the innermost last
+    let mut elements: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    for event in EventReader::from_str(content) {
+        match event.context("Failed to parse XSPF playlist")? {
+            XmlEvent::StartElement { name, .. } => {
+                if track.is_some() {
+                    elements.push(name.local_name);
+                } else if name.local_name == "track" {
+                    track = Some(PlaylistEntry::default());
+                }
+                text.clear();
+            }
+            XmlEvent::Characters(x) | XmlEvent::CData(x) => text.push_str(&x),
+            XmlEvent::EndElement { .. } => {
+                let Some(entry) = track.as_mut() else {
+                    continue;
+                };
+
+                let Some(element) = elements.pop() else {
+                    entries.push(track.take().unwrap());
+                    continue;
+                };
+
+                // Only direct children of the track, so the titles of
+                // extensions do not override the track title
+                if elements.is_empty() {
+                    match element.as_str() {
+                        // The first location is the preferred one
+                        "location" if entry.location.is_none() => {
+                            entry.location = decode_uri_reference(&text)
+                        }
+                        "title" => entry.title = non_empty(&text),
+                        "creator" => entry.artist = non_empty(&text),
+                        "album" => entry.album = non_empty(&text),
+                        // Durations are in milliseconds
+                        "duration" => entry.duration = parse_duration(&text).map(|x| x / 1000.0),
+                        _ => {}
+                    }
+                }
+                text.clear();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Jspf {
+    playlist: JspfPlaylist,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JspfPlaylist {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default)]
+    track: Vec<JspfTrack>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JspfTrack {
+    /// A single string in some exports, a list in the specification
+    #[serde(
+        default,
+        deserialize_with = "deserialize_locations",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    location: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    album: Option<String>,
+    /// Milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+}
+
+fn deserialize_locations<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Locations {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Option::<Locations>::deserialize(deserializer)? {
+        Some(Locations::One(x)) => vec![x],
+        Some(Locations::Many(x)) => x,
+        None => Vec::new(),
+    })
+}
+
+fn parse_jspf(content: &str) -> Result<Vec<PlaylistEntry>> {
+    let jspf: Jspf = serde_json::from_str(content).context("Failed to parse JSPF playlist")?;
+
+    Ok(jspf
+        .playlist
+        .track
+        .into_iter()
+        .map(|x| PlaylistEntry {
+            location: x.location.iter().find_map(|x| decode_uri_reference(x)),
+            title: x.title.as_deref().and_then(non_empty),
+            artist: x.creator.as_deref().and_then(non_empty),
+            album: x.album.as_deref().and_then(non_empty),
+            duration: x.duration.filter(|x| *x > 0.0).map(|x| x / 1000.0),
+        })
+        .collect())
+}
+
+/// Writes a playlist. Locations of the entries are paths, they are
+/// converted to URIs for the formats that expect them.
+pub fn write_playlist(format: PlaylistFormat, title: &str, entries: &[PlaylistEntry]) -> String {
+    let entries: Vec<PlaylistEntry> = entries
+        .iter()
+        .map(|x| PlaylistEntry {
+            location: x.location.as_deref().map(|x| path_to_location(format, x)),
+            ..x.clone()
+        })
+        .collect();
+
+    match format {
+        PlaylistFormat::M3u8 => write_m3u(title, &entries),
+        PlaylistFormat::Pls => write_pls(&entries),
+        PlaylistFormat::Xspf => write_xspf(title, &entries),
+        PlaylistFormat::Jspf => write_jspf(title, &entries),
+        PlaylistFormat::Cue => write_cue(title, &entries),
+    }
+}
+
+fn display_name(entry: &PlaylistEntry) -> Option<String> {
+    match (&entry.artist, &entry.title) {
+        (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+        (None, Some(title)) => Some(title.clone()),
+        _ => None,
+    }
+}
+
+fn write_m3u(title: &str, entries: &[PlaylistEntry]) -> String {
+    let mut output = String::from("#EXTM3U\n");
+    if !title.is_empty() {
+        let _ = writeln!(output, "#PLAYLIST:{}", title);
+    }
+
+    for entry in entries {
+        let Some(location) = &entry.location else {
+            continue;
+        };
+
+        if let Some(name) = display_name(entry) {
+            let duration = entry.duration.map_or(-1, |x| x.round() as i64);
+            let _ = writeln!(output, "#EXTINF:{},{}", duration, name);
+        }
+        if let Some(album) = &entry.album {
+            let _ = writeln!(output, "#EXTALB:{}", album);
+        }
+        let _ = writeln!(output, "{}", location);
+    }
+
+    output
+}
+
+fn write_pls(entries: &[PlaylistEntry]) -> String {
+    let mut output = String::from("[playlist]\n");
+    let entries: Vec<&PlaylistEntry> = entries.iter().filter(|x| x.location.is_some()).collect();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let number = index + 1;
+        let _ = writeln!(
+            output,
+            "File{}={}",
+            number,
+            entry.location.as_ref().unwrap()
+        );
+        if let Some(name) = display_name(entry) {
+            let _ = writeln!(output, "Title{}={}", number, name);
+        }
+        let duration = entry.duration.map_or(-1, |x| x.round() as i64);
+        let _ = writeln!(output, "Length{}={}", number, duration);
+    }
+
+    let _ = writeln!(output, "NumberOfEntries={}", entries.len());
+    output.push_str("Version=2\n");
+    output
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_xspf(title: &str, entries: &[PlaylistEntry]) -> String {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        output,
+        "<playlist version=\"1\" xmlns=\"{}\">",
+        XSPF_NAMESPACE
+    );
+    if !title.is_empty() {
+        let _ = writeln!(output, "  <title>{}</title>", escape_xml(title));
+    }
+    output.push_str("  <trackList>\n");
+
+    for entry in entries {
+        output.push_str("    <track>\n");
+        let fields = [
+            ("location", entry.location.as_ref()),
+            ("title", entry.title.as_ref()),
+            ("creator", entry.artist.as_ref()),
+            ("album", entry.album.as_ref()),
+        ];
+        for (element, value) in fields {
+            if let Some(value) = value {
+                let _ = writeln!(output, "      <{element}>{}</{element}>", escape_xml(value));
+            }
+        }
+        if let Some(duration) = entry.duration {
+            let _ = writeln!(
+                output,
+                "      <duration>{}</duration>",
+                (duration * 1000.0).round() as i64
+            );
+        }
+        output.push_str("    </track>\n");
+    }
+
+    output.push_str("  </trackList>\n</playlist>\n");
+    output
+}
+
+fn write_jspf(title: &str, entries: &[PlaylistEntry]) -> String {
+    let jspf = Jspf {
+        playlist: JspfPlaylist {
+            title: non_empty(title),
+            track: entries
+                .iter()
+                .map(|x| JspfTrack {
+                    location: x.location.iter().cloned().collect(),
+                    title: x.title.clone(),
+                    creator: x.artist.clone(),
+                    album: x.album.clone(),
+                    duration: x.duration.map(|x| (x * 1000.0).round()),
+                })
+                .collect(),
+        },
+    };
+
+    // Serializing plain strings and numbers does not fail
+    serde_json::to_string_pretty(&jspf).unwrap_or_default()
+}
+
+fn escape_cue(value: &str) -> String {
+    value.replace('"', "'")
+}
+
+/// Writes a cue sheet with one `FILE` per track, so players and burning
+/// tools that read cue sheets play the tracks in order.
+fn write_cue(title: &str, entries: &[PlaylistEntry]) -> String {
+    let mut output = String::new();
+    if !title.is_empty() {
+        let _ = writeln!(output, "TITLE \"{}\"", escape_cue(title));
+    }
+
+    let entries = entries.iter().filter(|x| x.location.is_some());
+    for (index, entry) in entries.enumerate() {
+        let location = entry.location.as_ref().unwrap();
+        let file_type = match Path::new(location)
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_lowercase())
+            .as_deref()
+        {
+            Some("mp3") => "MP3",
+            Some("aif") | Some("aiff") => "AIFF",
+            _ => "WAVE",
+        };
+
+        let _ = writeln!(output, "FILE \"{}\" {}", escape_cue(location), file_type);
+        let _ = writeln!(output, "  TRACK {:02} AUDIO", index + 1);
+        if let Some(title) = &entry.title {
+            let _ = writeln!(output, "    TITLE \"{}\"", escape_cue(title));
+        }
+        if let Some(artist) = &entry.artist {
+            let _ = writeln!(output, "    PERFORMER \"{}\"", escape_cue(artist));
+        }
+        output.push_str("    INDEX 01 00:00:00\n");
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(location: &str) -> PlaylistEntry {
+        PlaylistEntry {
+            location: Some(location.to_owned()),
+            title: Some("Hoppípolla".to_owned()),
+            artist: Some("Sigur Rós".to_owned()),
+            album: Some("Takk...".to_owned()),
+            duration: Some(268.0),
+        }
+    }
+
+    fn round_trip(format: PlaylistFormat, entries: &[PlaylistEntry]) -> Vec<PlaylistEntry> {
+        parse_playlist(format, &write_playlist(format, "Mix & <Match>", entries)).unwrap()
+    }
+
+    fn paths(entries: &[PlaylistEntry]) -> Vec<Option<String>> {
+        entries.iter().map(|x| x.path()).collect()
+    }
+
+    const LOCATIONS: [&str; 4] = [
+        "Sigur Rós/Takk/01 Glósóli #1 [50%].flac",
+        "../Other Library/a?b.mp3",
+        "/home/user/Music/Track 1.ogg",
+        "C:\\Music\\Track 2.flac",
+    ];
+
+    #[test]
+    fn test_m3u_round_trip() {
+        let entries: Vec<PlaylistEntry> = LOCATIONS.iter().map(|x| entry(x)).collect();
+        let parsed = round_trip(PlaylistFormat::M3u8, &entries);
+
+        assert_eq!(parsed, entries);
+    }
+
+    #[test]
+    fn test_pls_round_trip() {
+        let mut entries: Vec<PlaylistEntry> = LOCATIONS.iter().map(|x| entry(x)).collect();
+        // Albums are not part of the format
+        for entry in entries.iter_mut() {
+            entry.album = None;
+        }
+        let parsed = round_trip(PlaylistFormat::Pls, &entries);
+
+        assert_eq!(parsed, entries);
+    }
+
+    #[test]
+    fn test_xspf_round_trip() {
+        let entries: Vec<PlaylistEntry> = LOCATIONS.iter().map(|x| entry(x)).collect();
+        let parsed = round_trip(PlaylistFormat::Xspf, &entries);
+
+        // Relative paths stay relative, absolute ones become file URIs
+        assert_eq!(
+            parsed[0].location.as_deref(),
+            Some("Sigur Rós/Takk/01 Glósóli #1 [50%].flac")
+        );
+        assert_eq!(
+            parsed[1].location.as_deref(),
+            Some("../Other Library/a?b.mp3")
+        );
+        assert_eq!(
+            parsed[2].location.as_deref(),
+            Some("file:///home/user/Music/Track%201.ogg")
+        );
+        assert_eq!(
+            parsed[3].location.as_deref(),
+            Some("file:///C:/Music/Track%202.flac")
+        );
+        assert_eq!(
+            paths(&parsed),
+            vec![
+                Some("Sigur Rós/Takk/01 Glósóli #1 [50%].flac".to_owned()),
+                Some("../Other Library/a?b.mp3".to_owned()),
+                Some("/home/user/Music/Track 1.ogg".to_owned()),
+                Some("C:/Music/Track 2.flac".to_owned()),
+            ]
+        );
+
+        for (parsed, entry) in parsed.iter().zip(&entries) {
+            assert_eq!(parsed.title, entry.title);
+            assert_eq!(parsed.artist, entry.artist);
+            assert_eq!(parsed.album, entry.album);
+            assert_eq!(parsed.duration, entry.duration);
+        }
+    }
+
+    #[test]
+    fn test_jspf_round_trip() {
+        let entries: Vec<PlaylistEntry> = LOCATIONS.iter().map(|x| entry(x)).collect();
+        let parsed = round_trip(PlaylistFormat::Jspf, &entries);
+
+        assert_eq!(
+            paths(&parsed),
+            vec![
+                Some("Sigur Rós/Takk/01 Glósóli #1 [50%].flac".to_owned()),
+                Some("../Other Library/a?b.mp3".to_owned()),
+                Some("/home/user/Music/Track 1.ogg".to_owned()),
+                Some("C:/Music/Track 2.flac".to_owned()),
+            ]
+        );
+
+        for (parsed, entry) in parsed.iter().zip(&entries) {
+            assert_eq!(parsed.title, entry.title);
+            assert_eq!(parsed.artist, entry.artist);
+            assert_eq!(parsed.album, entry.album);
+            assert_eq!(parsed.duration, entry.duration);
+        }
+    }
+
+    #[test]
+    fn test_entries_without_location() {
+        let entries = vec![
+            PlaylistEntry {
+                title: Some("Lost".to_owned()),
+                ..Default::default()
+            },
+            entry("a.flac"),
+        ];
+
+        // Only XSPF and JSPF keep entries that are matched by title alone
+        assert_eq!(round_trip(PlaylistFormat::M3u8, &entries).len(), 1);
+        assert_eq!(round_trip(PlaylistFormat::Pls, &entries).len(), 1);
+        assert_eq!(round_trip(PlaylistFormat::Xspf, &entries).len(), 2);
+        assert_eq!(round_trip(PlaylistFormat::Jspf, &entries).len(), 2);
+    }
+
+    #[test]
+    fn test_write_cue() {
+        let entries = vec![
+            PlaylistEntry {
+                title: Some("Say \"Hi\"".to_owned()),
+                ..entry("Artist/01 Track.MP3")
+            },
+            PlaylistEntry::default(),
+            entry("../02 Track.flac"),
+            entry("03 Track.aiff"),
+        ];
+        let output = write_playlist(PlaylistFormat::Cue, "Mix", &entries);
+
+        assert_eq!(
+            output,
+            "TITLE \"Mix\"\n\
+             FILE \"Artist/01 Track.MP3\" MP3\n  TRACK 01 AUDIO\n    TITLE \"Say 'Hi'\"\n    PERFORMER \"Sigur Rós\"\n    INDEX 01 00:00:00\n\
+             FILE \"../02 Track.flac\" WAVE\n  TRACK 02 AUDIO\n    TITLE \"Hoppípolla\"\n    PERFORMER \"Sigur Rós\"\n    INDEX 01 00:00:00\n\
+             FILE \"03 Track.aiff\" AIFF\n  TRACK 03 AUDIO\n    TITLE \"Hoppípolla\"\n    PERFORMER \"Sigur Rós\"\n    INDEX 01 00:00:00\n"
+        );
+        assert!(parse_playlist(PlaylistFormat::Cue, &output).is_err());
+    }
+
+    #[test]
+    fn test_parse_m3u() {
+        let content = "\u{feff}#EXTM3U\n\
+                       #EXTINF:-1 tvg-id=\"x\",Just a Title\n\
+                       http://example.com/stream\n\
+                       \n\
+                       #EXTINF:120,Artist - Title - Live\n\
+                       #EXTALB:Album\n\
+                       music/track.mp3\n";
+        let entries = parse_playlist(PlaylistFormat::M3u8, content).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title.as_deref(), Some("Just a Title"));
+        assert_eq!(entries[0].duration, None);
+        assert_eq!(entries[0].path(), None);
+        assert_eq!(entries[1].artist.as_deref(), Some("Artist"));
+        assert_eq!(entries[1].title.as_deref(), Some("Title - Live"));
+        assert_eq!(entries[1].album.as_deref(), Some("Album"));
+        assert_eq!(entries[1].duration, Some(120.0));
+    }
+
+    #[test]
+    fn test_parse_pls() {
+        let content = "[playlist]\n\
+                       File2=b.mp3\n\
+                       Title2=Second\n\
+                       file1=a.mp3\n\
+                       Length1=-1\n\
+                       Title3=No file\n\
+                       NumberOfEntries=3\n";
+        let entries = parse_playlist(PlaylistFormat::Pls, content).unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|x| x.location.as_deref())
+                .collect::<Vec<_>>(),
+            vec![Some("a.mp3"), Some("b.mp3")]
+        );
+        assert_eq!(entries[0].duration, None);
+        assert_eq!(entries[1].title.as_deref(), Some("Second"));
+
+        assert!(parse_playlist(PlaylistFormat::Pls, "[playlist]\n")
+            .unwrap()
+            .is_empty());
+        assert!(parse_playlist(PlaylistFormat::Pls, "hello\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_xspf() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <title>Playlist title</title>
+              <trackList>
+                <track>
+                  <location>http://example.com/a.mp3</location>
+                  <location>file://localhost/music/a%20b.mp3</location>
+                  <title><![CDATA[A & B]]></title>
+                  <extension application="http://example.com">
+                    <title>Not the title</title>
+                  </extension>
+                  <duration>1500</duration>
+                </track>
+              </trackList>
+            </playlist>"#;
+        let entries = parse_playlist(PlaylistFormat::Xspf, content).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        // The first location is kept even if it is remote
+        assert_eq!(
+            entries[0].location.as_deref(),
+            Some("http://example.com/a.mp3")
+        );
+        assert_eq!(entries[0].path(), None);
+        assert_eq!(entries[0].title.as_deref(), Some("A & B"));
+        assert_eq!(entries[0].duration, Some(1.5));
+
+        assert!(parse_playlist(PlaylistFormat::Xspf, "<playlist>").is_err());
+    }
+
+    #[test]
+    fn test_parse_jspf() {
+        let content = r#"{"playlist": {"track": [
+            {"location": "file:///music/a%20b.mp3", "title": " ", "duration": 0},
+            {"location": ["", "b.mp3"], "creator": "Artist"},
+            {"title": "No location"}
+        ]}}"#;
+        let entries = parse_playlist(PlaylistFormat::Jspf, content).unwrap();
+
+        assert_eq!(
+            paths(&entries),
+            vec![
+                Some("/music/a b.mp3".to_owned()),
+                Some("b.mp3".to_owned()),
+                None
+            ]
+        );
+        assert_eq!(entries[0].title, None);
+        assert_eq!(entries[0].duration, None);
+        assert_eq!(entries[1].artist.as_deref(), Some("Artist"));
+
+        assert!(parse_playlist(PlaylistFormat::Jspf, "[]").is_err());
+    }
+
+    #[test]
+    fn test_location_to_path() {
+        assert_eq!(location_to_path(" a/b.mp3 ").as_deref(), Some("a/b.mp3"));
+        assert_eq!(
+            location_to_path("C:\\Music\\a.mp3").as_deref(),
+            Some("C:\\Music\\a.mp3")
+        );
+        assert_eq!(
+            location_to_path("FILE:///music/%C3%A9t%C3%A9.mp3").as_deref(),
+            Some("/music/été.mp3")
+        );
+        assert_eq!(
+            location_to_path("file://localhost/music/a.mp3").as_deref(),
+            Some("/music/a.mp3")
+        );
+        assert_eq!(
+            location_to_path("file:///D:/Music/a.mp3").as_deref(),
+            Some("D:/Music/a.mp3")
+        );
+        assert_eq!(location_to_path("https://example.com/a.mp3"), None);
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            PlaylistFormat::from_path(Path::new("a/List.M3U")),
+            Some(PlaylistFormat::M3u8)
+        );
+        assert_eq!(
+            PlaylistFormat::from_path(Path::new("list.jspf")),
+            Some(PlaylistFormat::Jspf)
+        );
+        assert_eq!(PlaylistFormat::from_path(Path::new("list.txt")), None);
+        assert_eq!(PlaylistFormat::from_path(Path::new("list")), None);
+    }
+}
@@ -1,5 +1,5 @@
-use std::collections::HashSet;
-use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
 use anyhow::{bail, Result};
 use async_trait::async_trait;
@@ -8,15 +8,20 @@ use log::info;
 use sea_orm::ActiveValue;
 use sea_orm::QueryOrder;
 use sea_orm::{prelude::*, TransactionTrait};
-use tokio::fs::read_to_string;
+use tokio::fs::{read_to_string, write};
 
 use crate::actions::collection::CollectionQuery;
+use crate::actions::metadata::get_metadata_summary_by_files;
+use crate::actions::playlist_formats::{
+    parse_playlist, write_playlist, PlaylistEntry, PlaylistFormat,
+};
 use crate::actions::search::{add_term, remove_term};
 use crate::connection::MainDbConnection;
 use crate::entities::{media_file_playlists, media_files, playlists};
 use crate::{collection_query, get_by_id};
 
 use super::collection::CollectionQueryType;
+use super::track_matching::{LibraryIndex, TrackQuery};
 use super::utils::{CollectionDefinition, DatabaseExecutor};
 
 impl CollectionDefinition for playlists::Entity {
@@ -282,138 +287,267 @@ pub async fn reorder_playlist_item_position(
     }
 }
 
+/// Get the items of a playlist.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `playlist_id` - The ID of the playlist.
+///
+/// # Returns
+/// * `Result<Vec<Model>>` - The items ordered by position or an error.
+pub async fn get_playlist_items(
+    main_db: &DatabaseConnection,
+    playlist_id: i32,
+) -> Result<Vec<media_file_playlists::Model>> {
+    use media_file_playlists::Entity as MediaFilePlaylistEntity;
+
+    let items = MediaFilePlaylistEntity::find()
+        .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
+        .order_by_asc(media_file_playlists::Column::Position)
+        .all(main_db)
+        .await?;
+
+    Ok(items)
+}
+
+/// Replace all items of a playlist.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `playlist_id` - The ID of the playlist.
+/// * `media_file_ids` - The media files the playlist will contain, in order.
+///
+/// # Returns
+/// * `Result<()>` - An empty result or an error.
+pub async fn replace_playlist_items(
+    main_db: &DatabaseConnection,
+    playlist_id: i32,
+    media_file_ids: &[i32],
+) -> Result<()> {
+    use media_file_playlists::Entity as MediaFilePlaylistEntity;
+    use playlists::Entity as PlaylistEntity;
+
+    let txn = main_db.begin().await?;
+
+    let Some(playlist) = PlaylistEntity::find_by_id(playlist_id).one(&txn).await? else {
+        bail!("Playlist not found");
+    };
+
+    MediaFilePlaylistEntity::delete_many()
+        .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
+        .exec(&txn)
+        .await?;
+
+    if !media_file_ids.is_empty() {
+        let items = media_file_ids
+            .iter()
+            .enumerate()
+            .map(
+                |(position, media_file_id)| media_file_playlists::ActiveModel {
+                    playlist_id: ActiveValue::Set(playlist_id),
+                    media_file_id: ActiveValue::Set(*media_file_id),
+                    position: ActiveValue::Set(position as i32),
+                    ..Default::default()
+                },
+            );
+
+        MediaFilePlaylistEntity::insert_many(items)
+            .exec(&txn)
+            .await?;
+    }
+
+    let mut active_model: playlists::ActiveModel = playlist.into();
+    active_model.updated_at = ActiveValue::Set(Utc::now().to_rfc3339());
+    active_model.update(&txn).await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct PlaylistImportResult {
     pub matched_ids: Vec<i32>,
+    /// Entries without a library file, described by their location or,
+    /// for entries without one, by their artist and title
     pub unmatched_paths: Vec<String>,
 }
 
-pub async fn parse_m3u8_playlist<E>(
+/// Splits a path written on any platform into its components, resolving
+/// `.` and `..` where possible.
+fn path_components(path: &str) -> Vec<String> {
+    let mut components: Vec<String> = Vec::new();
+
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." if components.last().is_some_and(|x| x != "..") => {
+                components.pop();
+            }
+            _ => components.push(component.to_owned()),
+        }
+    }
+
+    components
+}
+
+fn is_absolute_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    path.starts_with(['/', '\\'])
+        || (bytes.len() >= 2 && bytes[1] == b':' && bytes[0].is_ascii_alphabetic())
+}
+
+/// Number of trailing components two directories share.
+fn common_suffix_len(a: &[String], b: &[String]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+enum PathMatch {
+    /// No library file has the name
+    Missing,
+    /// Several files have the name and their directories do not tell them
+    /// apart, the most recently indexed one is the best guess
+    Ambiguous(i32),
+    Found(i32),
+}
+
+/// Finds the library file a playlist path refers to.
+///
+/// Files are looked up by name, then the one sharing the longest trailing
+/// part of its directory with the path wins, so playlists written against
+/// another copy of the library still match. Relative paths are resolved
+/// against the directory of the playlist first.
+async fn match_playlist_path<E>(
     main_db: &E,
-    playlist_path: &Path,
+    base_directory: Option<&str>,
+    path: &str,
+) -> Result<PathMatch>
+where
+    E: DatabaseExecutor + sea_orm::ConnectionTrait,
+{
+    let mut components = match base_directory {
+        Some(base_directory) if !is_absolute_path(path) => {
+            path_components(&format!("{}/{}", base_directory, path))
+        }
+        _ => path_components(path),
+    };
+
+    let Some(file_name) = components.pop() else {
+        return Ok(PathMatch::Missing);
+    };
+
+    let candidates = media_files::Entity::find()
+        .filter(media_files::Column::FileName.eq(file_name))
+        .all(main_db)
+        .await?;
+
+    if let [file] = candidates.as_slice() {
+        return Ok(PathMatch::Found(file.id));
+    }
+
+    let scored: Vec<(i32, usize)> = candidates
+        .iter()
+        .map(|file| {
+            let directory = path_components(&file.directory);
+            (file.id, common_suffix_len(&directory, &components))
+        })
+        .collect();
+
+    // Ties go to the most recently indexed file
+    let best = scored.into_iter().max_by_key(|(id, score)| (*score, *id));
+
+    Ok(match best {
+        None => PathMatch::Missing,
+        Some((id, 0)) => PathMatch::Ambiguous(id),
+        Some((id, _)) => PathMatch::Found(id),
+    })
+}
+
+/// Matches the entries of a playlist to library files.
+///
+/// Entries are matched by path first. Entries whose path no longer
+/// resolves, or that have no local path at all, are looked up by title,
+/// artist and duration instead.
+pub async fn match_playlist_entries<E>(
+    main_db: &E,
+    base_directory: Option<&Path>,
+    entries: &[PlaylistEntry],
 ) -> Result<PlaylistImportResult>
 where
     E: DatabaseExecutor + sea_orm::ConnectionTrait,
 {
-    // Read the content of the M3U8 file asynchronously into a string
-    let content = read_to_string(playlist_path).await?;
-    // Initialize vectors to store matched file IDs and unmatched paths
+    let base_directory = base_directory.map(|x| x.to_string_lossy().into_owned());
+    // Loading the whole library is only worth it once a path fails
+    let mut index: Option<LibraryIndex> = None;
+
     let mut matched_ids = Vec::new();
     let mut unmatched_paths = Vec::new();
 
-    // Iterate over each line in the content, filtering out empty lines
-    for line in content.lines().filter_map(|l| {
-        // Trim whitespace from the line
-        let trimmed = l.trim();
-        // If the line is empty after trimming, return None; otherwise, return the trimmed line
-        if trimmed.is_empty() || trimmed.starts_with("#") {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        // Convert the line into a PathBuf object
-        let path = PathBuf::from(line);
-        // Extract the file name from the path, if possible
-        let file_name = path.file_name().and_then(|n| n.to_str()).map(String::from);
-
-        // If a file name was successfully extracted
-        if let Some(file_name) = file_name {
-            // Query the database for files with the same file name
-            let matching_files = media_files::Entity::find()
-                .filter(media_files::Column::FileName.eq(file_name.clone()))
-                .all(main_db)
-                .await?;
-
-            // Handle different cases based on the number of matching files found
-            match matching_files.len() {
-                0 => {
-                    // No matching files found, add the path to unmatched_paths
-                    unmatched_paths.push(line.to_string());
-                }
-                1 => {
-                    // Exactly one matching file found, add its ID to matched_ids
-                    matched_ids.push(matching_files[0].id);
-                }
-                _ => {
-                    // More than one matching file found, need to disambiguate using directory paths
-                    let mut path_components: Vec<String> = path
-                        .parent()
-                        .map(|p| {
-                            // Split the path into components and reverse them for comparison
-                            p.components()
-                                .map(|c| c.as_os_str().to_string_lossy().into_owned())
-                                .collect()
-                        })
-                        .unwrap_or_default();
-                    path_components.reverse();
-
-                    // Prepare a vector of tuples containing each file and its directory components
-                    let mut matches_with_paths: Vec<_> = matching_files
-                        .into_iter()
-                        .map(|file| {
-                            let file_path = PathBuf::from(&file.directory);
-                            let mut components: Vec<String> = file_path
-                                .components()
-                                .map(|c| c.as_os_str().to_string_lossy().into_owned())
-                                .collect();
-                            components.reverse();
-                            (file, components)
-                        })
-                        .collect();
-
-                    let mut matched = false;
-                    // Iterate over the components of the path to find the best match
-                    for (i, component) in path_components.iter().enumerate() {
-                        // Retain only the files whose directory components match the current component
-                        matches_with_paths.retain(|(_, file_components)| {
-                            file_components
-                                .get(i)
-                                .map(|c| c == component)
-                                .unwrap_or(false)
-                        });
-
-                        // If no matches are left, add the path to unmatched_paths and break
-                        if matches_with_paths.is_empty() {
-                            unmatched_paths.push(line.to_string());
-                            matched = true;
-                            break;
-                        }
-
-                        // If only one match is left, add its ID to matched_ids and break
-                        if matches_with_paths.len() == 1 {
-                            matched_ids.push(matches_with_paths[0].0.id);
-                            matched = true;
-                            break;
-                        }
-                    }
-
-                    // If still multiple matches exist, select the one with the highest ID
-                    if !matched {
-                        if let Some((file, _)) =
-                            matches_with_paths.into_iter().max_by_key(|(f, _)| f.id)
-                        {
-                            matched_ids.push(file.id);
-                        } else {
-                            unmatched_paths.push(line.to_string());
-                        }
-                    }
-                }
+    for entry in entries {
+        let path_match = match entry.path() {
+            Some(path) => match_playlist_path(main_db, base_directory.as_deref(), &path).await?,
+            None => PathMatch::Missing,
+        };
+
+        let guess = match path_match {
+            PathMatch::Found(id) => {
+                matched_ids.push(id);
+                continue;
+            }
+            PathMatch::Ambiguous(id) => Some(id),
+            PathMatch::Missing => None,
+        };
+
+        let mut found = None;
+        if let Some(title) = &entry.title {
+            if index.is_none() {
+                index = Some(LibraryIndex::load(main_db).await?);
             }
-        } else {
-            // If no file name could be extracted, add the path to unmatched_paths
-            unmatched_paths.push(line.to_string());
+
+            let query = TrackQuery {
+                title,
+                artist: entry.artist.as_deref(),
+                album_artist: None,
+                album: entry.album.as_deref(),
+                duration: entry.duration,
+            };
+            found = index.as_ref().and_then(|x| x.find(&query)).map(|x| x.id);
+        }
+
+        match found.or(guess) {
+            Some(id) => matched_ids.push(id),
+            None => unmatched_paths.push(entry.describe()),
         }
     }
 
-    // Return the result containing matched file IDs and unmatched paths
     Ok(PlaylistImportResult {
         matched_ids,
         unmatched_paths,
     })
 }
 
-pub async fn import_m3u8_to_playlist<E>(
+/// Reads a playlist file and matches its entries to library files. The
+/// format is picked by the extension, files with an unknown extension are
+/// read as M3U.
+pub async fn parse_playlist_file<E>(
+    main_db: &E,
+    playlist_path: &Path,
+) -> Result<PlaylistImportResult>
+where
+    E: DatabaseExecutor + sea_orm::ConnectionTrait,
+{
+    let format = PlaylistFormat::from_path(playlist_path).unwrap_or(PlaylistFormat::M3u8);
+    let content = read_to_string(playlist_path).await?;
+    let entries = parse_playlist(format, &content)?;
+
+    match_playlist_entries(main_db, playlist_path.parent(), &entries).await
+}
+
+pub async fn import_playlist_file<E>(
     main_db: &E,
     playlist_id: i32,
     playlist_path: &Path,
@@ -421,7 +555,7 @@ pub async fn import_m3u8_to_playlist<E>(
 where
     E: DatabaseExecutor + sea_orm::ConnectionTrait,
 {
-    let import_result = parse_m3u8_playlist(main_db, playlist_path).await?;
+    let import_result = parse_playlist_file(main_db, playlist_path).await?;
 
     let models: Vec<media_file_playlists::ActiveModel> = import_result
         .matched_ids
@@ -446,19 +580,20 @@ where
     Ok(import_result)
 }
 
-pub async fn create_m3u8_playlist(
+/// Create a playlist from an M3U, PLS, XSPF or JSPF file.
+pub async fn create_playlist_from_file(
     main_db: &MainDbConnection,
     name: String,
     group: String,
-    m3u8_path: &Path,
+    playlist_path: &Path,
 ) -> Result<(playlists::Model, PlaylistImportResult)> {
     let txn = main_db.begin().await?;
 
     // Create the playlist
     let playlist: playlists::Model = create_playlist(&txn, name.clone(), group.clone()).await?;
 
-    // Import the M3U8 file contents into the playlist
-    let import_result = import_m3u8_to_playlist(&txn, playlist.id, m3u8_path).await;
+    // Import the playlist file contents into the playlist
+    let import_result = import_playlist_file(&txn, playlist.id, playlist_path).await;
 
     // Check if the import was successful
     match import_result {
@@ -475,6 +610,109 @@ pub async fn create_m3u8_playlist(
     }
 }
 
+/// Describe library files as playlist entries, in the given order.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root of the library.
+/// * `files` - The files to describe, repeated files are kept.
+/// * `relative_to` - Write paths relative to this directory instead of
+///   absolute ones, usually the directory of the playlist file.
+///
+/// # Returns
+/// * `Result<Vec<PlaylistEntry>>` - The entries or an error.
+pub async fn playlist_entries_for_files(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    files: Vec<media_files::Model>,
+    relative_to: Option<&Path>,
+) -> Result<Vec<PlaylistEntry>> {
+    let summaries = get_metadata_summary_by_files(main_db, files).await?;
+
+    Ok(summaries
+        .into_iter()
+        .map(|summary| {
+            let path = lib_path.join(&summary.directory).join(&summary.file_name);
+            let path = relative_to
+                .and_then(|base| pathdiff::diff_paths(&path, base))
+                .unwrap_or(path);
+
+            PlaylistEntry {
+                location: Some(path.to_string_lossy().into_owned()),
+                title: Some(summary.title),
+                artist: Some(summary.artist).filter(|x| !x.is_empty()),
+                album: Some(summary.album).filter(|x| !x.is_empty()),
+                duration: Some(summary.duration).filter(|x| *x > 0.0),
+            }
+        })
+        .collect())
+}
+
+/// Export a playlist to a file.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `lib_path` - The root of the library.
+/// * `playlist_id` - The ID of the playlist to export.
+/// * `format` - The format of the file.
+/// * `output_path` - Where to write the file.
+/// * `relative_paths` - Whether to write paths relative to the file, so the
+///   playlist keeps working when moved along with the library.
+///
+/// # Returns
+/// * `Result<usize>` - The number of exported tracks or an error.
+pub async fn export_playlist(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    playlist_id: i32,
+    format: PlaylistFormat,
+    output_path: &Path,
+    relative_paths: bool,
+) -> Result<usize> {
+    let playlist = match playlists::Entity::find_by_id(playlist_id)
+        .one(main_db)
+        .await?
+    {
+        Some(playlist) => playlist,
+        None => bail!("Playlist not found"),
+    };
+
+    let items = media_file_playlists::Entity::find()
+        .filter(media_file_playlists::Column::PlaylistId.eq(playlist_id))
+        .order_by_asc(media_file_playlists::Column::Position)
+        .all(main_db)
+        .await?;
+
+    let ids: HashSet<i32> = items.iter().map(|x| x.media_file_id).collect();
+    let files: HashMap<i32, media_files::Model> = media_files::Entity::find()
+        .filter(media_files::Column::Id.is_in(ids))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    let files: Vec<media_files::Model> = items
+        .iter()
+        .filter_map(|x| files.get(&x.media_file_id).cloned())
+        .collect();
+
+    let relative_to = if relative_paths {
+        output_path.parent()
+    } else {
+        None
+    };
+    let entries = playlist_entries_for_files(main_db, lib_path, files, relative_to).await?;
+
+    write(
+        output_path,
+        write_playlist(format, &playlist.name, &entries),
+    )
+    .await?;
+
+    Ok(entries.len())
+}
+
 /// Remove a specific item from a playlist by position.
 ///
 /// # Arguments
@@ -534,3 +772,162 @@ pub async fn remove_item_from_playlist(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::connect_test_main_db;
+    use crate::entities::media_metadata;
+
+    use super::*;
+
+    async fn insert_file(db: &DatabaseConnection, id: i32, directory: &str, file_name: &str) {
+        media_files::ActiveModel {
+            id: ActiveValue::Set(id),
+            file_name: ActiveValue::Set(file_name.to_owned()),
+            directory: ActiveValue::Set(directory.to_owned()),
+            extension: ActiveValue::Set("flac".to_owned()),
+            file_hash: ActiveValue::Set(format!("hash-{}", id)),
+            last_modified: ActiveValue::Set("2024-01-01T00:00:00+00:00".to_owned()),
+            cover_art_id: ActiveValue::Set(None),
+            sample_rate: ActiveValue::Set(44100),
+            duration: ActiveValue::Set(Decimal::new(200, 0)),
+            cue_track: ActiveValue::Set(None),
+            start_offset: ActiveValue::Set(None),
+            end_offset: ActiveValue::Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_metadata(db: &DatabaseConnection, file_id: i32, key: &str, value: &str) {
+        media_metadata::ActiveModel {
+            file_id: ActiveValue::Set(file_id),
+            meta_key: ActiveValue::Set(key.to_owned()),
+            meta_value: ActiveValue::Set(value.to_owned()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    async fn library() -> DatabaseConnection {
+        let db = connect_test_main_db().await;
+
+        insert_file(&db, 1, "Artist/Album", "01 Song.flac").await;
+        insert_file(&db, 2, "Copy/Album", "01 Song.flac").await;
+        insert_file(&db, 3, "Other", "02 Other.flac").await;
+        insert_metadata(&db, 3, "track_title", "Other Song").await;
+        insert_metadata(&db, 3, "artist", "Other Artist").await;
+
+        db
+    }
+
+    fn located(location: &str) -> PlaylistEntry {
+        PlaylistEntry {
+            location: Some(location.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_match_relative_paths() {
+        let db = library().await;
+        let entries = vec![
+            // Resolved against the playlist directory
+            located("../Album/01 Song.flac"),
+            located("/elsewhere/Copy/Album/01 Song.flac"),
+            located("C:\\Copy\\Album\\01 Song.flac"),
+            // The directory does not tell the files apart
+            located("Unknown/01 Song.flac"),
+            // Moved files are found by title and artist
+            PlaylistEntry {
+                title: Some("Other Song".to_owned()),
+                artist: Some("Other Artist".to_owned()),
+                ..located("Moved/03 Other.flac")
+            },
+            located("Missing/04 Gone.flac"),
+        ];
+
+        for format in [
+            PlaylistFormat::M3u8,
+            PlaylistFormat::Pls,
+            PlaylistFormat::Xspf,
+            PlaylistFormat::Jspf,
+        ] {
+            let entries =
+                parse_playlist(format, &write_playlist(format, "Playlist", &entries)).unwrap();
+            let result =
+                match_playlist_entries(&db, Some(Path::new("/music/Artist/Playlists")), &entries)
+                    .await
+                    .unwrap();
+
+            assert_eq!(result.matched_ids, vec![1, 2, 2, 2, 3], "{:?}", format);
+            assert_eq!(
+                result.unmatched_paths,
+                vec!["Missing/04 Gone.flac".to_owned()],
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_relative_paths() {
+        let db = library().await;
+        let files = media_files::Entity::find()
+            .filter(media_files::Column::Id.is_in([3, 1]))
+            .order_by_asc(media_files::Column::Id)
+            .all(&db)
+            .await
+            .unwrap();
+
+        let playlist_directory = Path::new("/music/Playlists");
+        let entries = playlist_entries_for_files(
+            &db,
+            Path::new("/music"),
+            files.clone(),
+            Some(playlist_directory),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|x| x.location.as_deref())
+                .collect::<Vec<_>>(),
+            vec![
+                Some("../Artist/Album/01 Song.flac"),
+                Some("../Other/02 Other.flac")
+            ]
+        );
+        assert_eq!(entries[1].title.as_deref(), Some("Other Song"));
+        assert_eq!(entries[1].artist.as_deref(), Some("Other Artist"));
+
+        let absolute = playlist_entries_for_files(&db, Path::new("/music"), files, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            absolute[0].location.as_deref(),
+            Some("/music/Artist/Album/01 Song.flac")
+        );
+
+        // Exported playlists match the same files when read back
+        for format in [
+            PlaylistFormat::M3u8,
+            PlaylistFormat::Pls,
+            PlaylistFormat::Xspf,
+            PlaylistFormat::Jspf,
+        ] {
+            let entries =
+                parse_playlist(format, &write_playlist(format, "Playlist", &entries)).unwrap();
+            let result = match_playlist_entries(&db, Some(playlist_directory), &entries)
+                .await
+                .unwrap();
+
+            assert_eq!(result.matched_ids, vec![1, 3], "{:?}", format);
+            assert!(result.unmatched_paths.is_empty());
+        }
+    }
+}
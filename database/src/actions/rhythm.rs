@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use sea_orm::prelude::*;
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{ActiveValue, Order};
+
+use analysis::utils::key::MusicalKey;
+use analysis::utils::rhythm::RhythmResult;
+
+use crate::entities::{media_metadata, media_rhythm};
+
+const BPM_TAG: &str = "bpm";
+const KEY_TAG: &str = "initial_key";
+
+/// Tagged tempos outside of this range are typos or placeholders.
+const TAGGED_BPM_RANGE: std::ops::RangeInclusive<f64> = 20.0..=400.0;
+
+/// Tempo and key of a library file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackRhythm {
+    pub bpm: Option<f64>,
+    pub key: Option<MusicalKey>,
+}
+
+impl From<media_rhythm::Model> for TrackRhythm {
+    fn from(model: media_rhythm::Model) -> Self {
+        TrackRhythm {
+            bpm: model.bpm.and_then(|x| x.to_f64()),
+            key: model
+                .musical_key
+                .and_then(|x| MusicalKey::from_camelot_index(x as u8)),
+        }
+    }
+}
+
+fn parse_tagged_bpm(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|x| TAGGED_BPM_RANGE.contains(x))
+}
+
+/// Stores the tempo and key estimated during analysis, replacing any
+/// previous result for the same file. Values the analysis could not
+/// estimate fall back to the `bpm` and `initial_key` tags of the file.
+pub(crate) async fn insert_rhythm_result(
+    main_db: &DatabaseConnection,
+    file_id: i32,
+    rhythm: Option<RhythmResult>,
+) -> Result<()> {
+    let tags: HashMap<String, String> = media_metadata::Entity::find()
+        .filter(media_metadata::Column::FileId.eq(file_id))
+        .filter(media_metadata::Column::MetaKey.is_in([BPM_TAG, KEY_TAG]))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.meta_key, x.meta_value))
+        .collect();
+
+    let detected_key = rhythm.and_then(|x| x.key);
+
+    let bpm = rhythm
+        .and_then(|x| x.bpm)
+        .or_else(|| tags.get(BPM_TAG).and_then(|x| parse_tagged_bpm(x)));
+    let key = detected_key
+        .map(|x| x.key)
+        .or_else(|| tags.get(KEY_TAG).and_then(|x| x.parse::<MusicalKey>().ok()));
+
+    let new_rhythm = media_rhythm::ActiveModel {
+        file_id: ActiveValue::Set(file_id),
+        bpm: ActiveValue::Set(bpm.and_then(Decimal::from_f64)),
+        musical_key: ActiveValue::Set(key.map(|x| x.camelot_index() as i32)),
+        key_strength: ActiveValue::Set(detected_key.and_then(|x| Decimal::from_f64(x.strength))),
+        ..Default::default()
+    };
+
+    media_rhythm::Entity::delete_many()
+        .filter(media_rhythm::Column::FileId.eq(file_id))
+        .exec(main_db)
+        .await?;
+
+    media_rhythm::Entity::insert(new_rhythm)
+        .exec(main_db)
+        .await?;
+
+    Ok(())
+}
+
+/// Retrieve the tempo and key of library files.
+///
+/// # Arguments
+/// * `main_db` - A reference to the database connection.
+/// * `file_ids` - The IDs of the files to resolve.
+///
+/// # Returns
+/// * `Result<HashMap<i32, TrackRhythm>>` - Tempo and key keyed by file ID,
+///   files that were not analyzed yet are omitted.
+pub async fn get_rhythm_by_file_ids(
+    main_db: &DatabaseConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, TrackRhythm>> {
+    if file_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    Ok(media_rhythm::Entity::find()
+        .filter(media_rhythm::Column::FileId.is_in(file_ids.to_vec()))
+        .all(main_db)
+        .await?
+        .into_iter()
+        .map(|x| (x.file_id, x.into()))
+        .collect())
+}
+
+/// Mix sort operators backed by the tempo and key of tracks.
+///
+/// * `sort::bpm(true)` orders by tempo, slowest first when ascending
+/// * `sort::key(true)` orders along the Camelot wheel, from 1A to 12B, so
+///   neighbouring tracks mix harmonically
+///
+/// Tracks without a tempo or key come first when ascending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RhythmSort {
+    Bpm(bool),
+    Key(bool),
+}
+
+impl RhythmSort {
+    pub(crate) fn order(&self) -> (SimpleExpr, Order) {
+        let (column, asc) = match self {
+            RhythmSort::Bpm(asc) => ("bpm", asc),
+            RhythmSort::Key(asc) => ("musical_key", asc),
+        };
+
+        (
+            Expr::cust(format!(
+                "(SELECT \"media_rhythm\".\"{column}\" FROM \"media_rhythm\" \
+                 WHERE \"media_rhythm\".\"file_id\" = \"media_files\".\"id\")"
+            )),
+            if *asc { Order::Asc } else { Order::Desc },
+        )
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{debug, info};
+use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Position, Scope, INT};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tokio::runtime::Handle;
+use tokio::task;
+
+use crate::actions::metadata::get_metadata_summary_by_file_id;
+use crate::actions::mixes::{query_mix_media_files, GroupedQuery, MixQueryError};
+use crate::actions::recommendation::get_recommendation_by_file_id;
+use crate::actions::rhythm::get_rhythm_by_file_ids;
+use crate::connection::RecommendationDbConnection;
+use crate::entities::media_file_stats;
+
+/// Operator holding the source of a scriptlet mix. A scriptlet mix has this
+/// single query and nothing else.
+pub const SCRIPTLET_OPERATOR: &str = "script::source";
+
+/// Wall clock time a scriptlet may run, including the library calls it makes.
+const TIME_LIMIT: Duration = Duration::from_secs(5);
+
+/// Engine operations a scriptlet may perform.
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+/// Size limits of the values a scriptlet creates. rhai has no heap limit, so
+/// memory use is bounded through the size of strings, arrays and maps.
+const MAX_STRING_SIZE: usize = 64 * 1024;
+const MAX_ARRAY_SIZE: usize = 20_000;
+const MAX_MAP_SIZE: usize = 1_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+
+/// Tracks a single `query` call returns at most.
+const MAX_QUERY_RESULTS: usize = 10_000;
+
+/// Scriptlet results kept for paging, oldest first.
+const MAX_CACHED_RESULTS: usize = 16;
+
+static RESULT_CACHE: Lazy<Mutex<VecDeque<(String, Arc<Vec<i32>>)>>> =
+    Lazy::new(|| Mutex::new(VecDeque::new()));
+
+fn script_error(message: impl Into<String>) -> Box<EvalAltResult> {
+    Box::new(EvalAltResult::ErrorRuntime(
+        Dynamic::from(message.into()),
+        Position::NONE,
+    ))
+}
+
+fn parse_script_query(item: Dynamic) -> Result<GroupedQuery, Box<EvalAltResult>> {
+    let query = if item.is_array() {
+        let parts = item.cast::<Array>();
+        let operator = parts.first().map(|x| x.to_string()).unwrap_or_default();
+        let parameter = parts.get(1).map(|x| x.to_string()).unwrap_or_default();
+        let group = parts
+            .get(2)
+            .map(|x| {
+                x.as_int()
+                    .map_err(|_| script_error("Query group must be an integer"))
+            })
+            .transpose()?
+            .unwrap_or(0);
+        GroupedQuery::new(operator, parameter, group as i32)
+    } else if item.is_map() {
+        let map = item.cast::<Map>();
+        let field = |key: &str| map.get(key).map(|x| x.to_string()).unwrap_or_default();
+        let group = map
+            .get("group")
+            .map(|x| {
+                x.as_int()
+                    .map_err(|_| script_error("Query group must be an integer"))
+            })
+            .transpose()?
+            .unwrap_or(0);
+        GroupedQuery::new(field("operator"), field("parameter"), group as i32)
+    } else {
+        return Err(script_error(
+            "A query must be [operator, parameter, group] or #{operator, parameter, group}",
+        ));
+    };
+
+    if query.operator == SCRIPTLET_OPERATOR {
+        return Err(script_error("Scriptlets can not run other scriptlets"));
+    }
+
+    Ok(query)
+}
+
+fn ids_to_array(ids: impl IntoIterator<Item = i32>) -> Array {
+    ids.into_iter().map(|x| Dynamic::from(x as INT)).collect()
+}
+
+/// Creates an engine with the sandbox limits and no library access. Scripts
+/// can not import modules or evaluate code.
+fn sandboxed_engine(time_limit: Duration) -> Engine {
+    let mut engine = Engine::new();
+
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+    engine.set_max_map_size(MAX_MAP_SIZE);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+    engine.set_max_modules(0);
+    engine.disable_symbol("eval");
+
+    let started_at = Instant::now();
+    engine.on_progress(move |_| {
+        if started_at.elapsed() > time_limit {
+            Some(Dynamic::from(format!(
+                "Time limit of {} seconds exceeded",
+                time_limit.as_secs_f64()
+            )))
+        } else {
+            None
+        }
+    });
+
+    engine.on_print(|x| info!("Scriptlet: {}", x));
+    engine.on_debug(|x, _, position| debug!("Scriptlet {}: {}", position, x));
+
+    engine
+}
+
+/// Creates the engine scriptlets run in. They can only reach the library
+/// through the functions registered here, none of which write.
+fn create_engine(
+    main_db: DatabaseConnection,
+    recommend_db: Arc<RecommendationDbConnection>,
+    runtime: Handle,
+) -> Engine {
+    let mut engine = sandboxed_engine(TIME_LIMIT);
+
+    // query([["lib::genre", "3"], ["filter::year", "1990..1999"]]) -> [id]
+    let (db, recommend, handle) = (main_db.clone(), Arc::clone(&recommend_db), runtime.clone());
+    engine.register_fn(
+        "query",
+        move |queries: Array| -> Result<Array, Box<EvalAltResult>> {
+            let queries = queries
+                .into_iter()
+                .map(parse_script_query)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let files = handle
+                .block_on(query_mix_media_files(
+                    &db,
+                    &recommend,
+                    queries,
+                    0,
+                    MAX_QUERY_RESULTS,
+                ))
+                .map_err(|e| script_error(format!("{:#}", e)))?;
+
+            Ok(ids_to_array(files.into_iter().map(|x| x.id)))
+        },
+    );
+
+    // recommend(id, n) -> [id]
+    let recommend = Arc::clone(&recommend_db);
+    engine.register_fn(
+        "recommend",
+        move |id: INT, n: INT| -> Result<Array, Box<EvalAltResult>> {
+            let n = n.clamp(1, MAX_QUERY_RESULTS as INT) as usize;
+            let results = get_recommendation_by_file_id(&recommend, id as i32, n)
+                .map_err(|e| script_error(format!("{:#}", e)))?;
+
+            Ok(ids_to_array(results.into_iter().map(|x| x.0 as i32)))
+        },
+    );
+
+    // stats(id) -> #{liked, skipped, played_through}
+    let (db, handle) = (main_db.clone(), runtime.clone());
+    engine.register_fn("stats", move |id: INT| -> Result<Map, Box<EvalAltResult>> {
+        let stats = handle
+            .block_on(
+                media_file_stats::Entity::find()
+                    .filter(media_file_stats::Column::MediaFileId.eq(id as i32))
+                    .one(&db),
+            )
+            .map_err(|e| script_error(format!("{:#}", e)))?;
+
+        let mut map = Map::new();
+        map.insert(
+            "liked".into(),
+            Dynamic::from(stats.as_ref().map(|x| x.liked).unwrap_or(false)),
+        );
+        map.insert(
+            "skipped".into(),
+            Dynamic::from(stats.as_ref().map(|x| x.skipped).unwrap_or(0) as INT),
+        );
+        map.insert(
+            "played_through".into(),
+            Dynamic::from(stats.as_ref().map(|x| x.played_through).unwrap_or(0) as INT),
+        );
+
+        Ok(map)
+    });
+
+    // rhythm(id) -> #{bpm, key, camelot}, unknown values are ()
+    let (db, handle) = (main_db.clone(), runtime.clone());
+    engine.register_fn(
+        "rhythm",
+        move |id: INT| -> Result<Map, Box<EvalAltResult>> {
+            let rhythm = handle
+                .block_on(get_rhythm_by_file_ids(&db, &[id as i32]))
+                .map_err(|e| script_error(format!("{:#}", e)))?
+                .remove(&(id as i32));
+
+            let bpm = rhythm.and_then(|x| x.bpm);
+            let key = rhythm.and_then(|x| x.key);
+
+            let mut map = Map::new();
+            map.insert(
+                "bpm".into(),
+                bpm.map(Dynamic::from).unwrap_or(Dynamic::UNIT),
+            );
+            map.insert(
+                "key".into(),
+                key.map(|x| Dynamic::from(x.to_string()))
+                    .unwrap_or(Dynamic::UNIT),
+            );
+            map.insert(
+                "camelot".into(),
+                key.map(|x| Dynamic::from(x.camelot()))
+                    .unwrap_or(Dynamic::UNIT),
+            );
+
+            Ok(map)
+        },
+    );
+
+    // track(id) -> #{id, title, artist, album, genre, track_number, duration}
+    let (db, handle) = (main_db, runtime);
+    engine.register_fn("track", move |id: INT| -> Result<Map, Box<EvalAltResult>> {
+        let summary = handle
+            .block_on(get_metadata_summary_by_file_id(&db, id as i32))
+            .map_err(|e| script_error(format!("{:#}", e)))?;
+
+        let mut map = Map::new();
+        map.insert("id".into(), Dynamic::from(summary.id as INT));
+        map.insert("title".into(), Dynamic::from(summary.title));
+        map.insert("artist".into(), Dynamic::from(summary.artist));
+        map.insert("album".into(), Dynamic::from(summary.album));
+        map.insert("genre".into(), Dynamic::from(summary.genre));
+        map.insert(
+            "track_number".into(),
+            Dynamic::from(summary.track_number as INT),
+        );
+        map.insert("duration".into(), Dynamic::from(summary.duration));
+
+        Ok(map)
+    });
+
+    engine.register_fn("shuffle", |mut items: Array| -> Array {
+        items.shuffle(&mut rand::thread_rng());
+        items
+    });
+
+    engine
+}
+
+/// Checks that a scriptlet compiles in the sandbox, so broken mixes are
+/// refused on save.
+pub fn compile_scriptlet(source: &str) -> Result<(), MixQueryError> {
+    sandboxed_engine(TIME_LIMIT)
+        .compile(source)
+        .map(|_| ())
+        .map_err(|e| MixQueryError::Scriptlet(e.to_string()))
+}
+
+/// Runs a scriptlet and returns the track ids it produced, in order.
+///
+/// The script runs on a blocking thread, its last expression must evaluate
+/// to an array of track ids.
+pub async fn run_scriptlet(
+    main_db: &DatabaseConnection,
+    recommend_db: &RecommendationDbConnection,
+    source: &str,
+) -> Result<Vec<i32>, MixQueryError> {
+    let main_db = main_db.clone();
+    let recommend_db = Arc::new(recommend_db.clone());
+    let source = source.to_string();
+    let runtime = Handle::current();
+
+    let result = task::spawn_blocking(move || {
+        let engine = create_engine(main_db, recommend_db, runtime);
+        let ast = engine.compile(&source).map_err(|e| e.to_string())?;
+
+        let value = engine
+            .eval_ast_with_scope::<Dynamic>(&mut Scope::new(), &ast)
+            .map_err(|e| e.to_string())?;
+
+        if !value.is_array() {
+            return Err(format!(
+                "A scriptlet must return an array of track ids, got {}",
+                value.type_name()
+            ));
+        }
+
+        value
+            .cast::<Array>()
+            .into_iter()
+            .map(|x| {
+                x.as_int()
+                    .map(|x| x as i32)
+                    .map_err(|t| format!("Track ids must be integers, got {}", t))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .map_err(|e| MixQueryError::Scriptlet(e.to_string()))?;
+
+    result.map_err(MixQueryError::Scriptlet)
+}
+
+/// Runs a scriptlet for the first page of a query and keeps the result, so
+/// later pages come from the same run even if the script shuffles.
+pub async fn run_scriptlet_paged(
+    main_db: &DatabaseConnection,
+    recommend_db: &RecommendationDbConnection,
+    source: &str,
+    cursor: usize,
+) -> Result<Arc<Vec<i32>>, MixQueryError> {
+    if cursor > 0 {
+        let cached = RESULT_CACHE
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(cached_source, _)| cached_source == source)
+            .map(|(_, ids)| Arc::clone(ids));
+
+        if let Some(ids) = cached {
+            return Ok(ids);
+        }
+    }
+
+    let ids = Arc::new(run_scriptlet(main_db, recommend_db, source).await?);
+
+    let mut cache = RESULT_CACHE.lock().unwrap();
+    cache.retain(|(cached_source, _)| cached_source != source);
+    if cache.len() >= MAX_CACHED_RESULTS {
+        cache.pop_front();
+    }
+    cache.push_back((source.to_string(), Arc::clone(&ids)));
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(engine: &Engine, source: &str) -> Box<EvalAltResult> {
+        engine
+            .eval::<Dynamic>(source)
+            .expect_err("the script should be stopped")
+    }
+
+    #[test]
+    fn test_compile_scriptlet() {
+        assert!(
+            compile_scriptlet("let ids = query([[\"lib::all\", \"true\"]]); shuffle(ids)").is_ok()
+        );
+        assert!(matches!(
+            compile_scriptlet("let ids = ;"),
+            Err(MixQueryError::Scriptlet(_))
+        ));
+
+        // The checks on save use the same sandbox the scriptlet runs in
+        assert!(compile_scriptlet("eval(\"[1, 2, 3]\")").is_err());
+        assert!(compile_scriptlet(&format!("{}1{}", "(".repeat(200), ")".repeat(200))).is_err());
+    }
+
+    #[test]
+    fn test_operation_limit() {
+        let engine = sandboxed_engine(Duration::from_secs(3600));
+
+        assert!(matches!(
+            *eval(&engine, "loop {}"),
+            EvalAltResult::ErrorTooManyOperations(_)
+        ));
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let engine = sandboxed_engine(Duration::from_millis(50));
+
+        let started_at = Instant::now();
+        assert!(matches!(
+            *eval(&engine, "loop {}"),
+            EvalAltResult::ErrorTerminated(..)
+        ));
+        assert!(started_at.elapsed() < TIME_LIMIT);
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let engine = sandboxed_engine(TIME_LIMIT);
+
+        assert!(matches!(
+            *eval(&engine, "let s = \"x\"; loop { s += s; }"),
+            EvalAltResult::ErrorDataTooLarge(..)
+        ));
+        assert!(matches!(
+            *eval(
+                &engine,
+                &format!("let a = []; a.pad({}, 0);", MAX_ARRAY_SIZE + 1)
+            ),
+            EvalAltResult::ErrorDataTooLarge(..)
+        ));
+        assert!(matches!(
+            *eval(&engine, "let m = #{}; for i in 0..2000 { m[`k${i}`] = i; }"),
+            EvalAltResult::ErrorDataTooLarge(..)
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use migration::OnConflict;
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ActiveValue, QueryOrder, QuerySelect};
+
+use crate::entities::scrobble_queue;
+
+/// Delivered scrobbles are kept this long to recognize duplicates.
+const DELIVERED_RETENTION_DAYS: i64 = 30;
+
+/// A played track waiting to be scrobbled.
+#[derive(Debug, Clone)]
+pub struct NewScrobble {
+    pub artist: String,
+    pub track: String,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub duration: Option<i32>,
+    /// Unix time the track was played at, in seconds
+    pub timestamp: i64,
+}
+
+/// Queues a scrobble for each of the services. A scrobble of the same track
+/// at the same time already queued for a service is left as it is.
+pub async fn enqueue_scrobbles(
+    main_db: &DatabaseConnection,
+    services: &[String],
+    scrobble: NewScrobble,
+) -> Result<()> {
+    if services.is_empty() {
+        return Ok(());
+    }
+
+    let created_at = Utc::now().to_rfc3339();
+    let models = services.iter().map(|service| scrobble_queue::ActiveModel {
+        service: ActiveValue::Set(service.clone()),
+        artist: ActiveValue::Set(scrobble.artist.clone()),
+        track: ActiveValue::Set(scrobble.track.clone()),
+        album: ActiveValue::Set(scrobble.album.clone()),
+        album_artist: ActiveValue::Set(scrobble.album_artist.clone()),
+        duration: ActiveValue::Set(scrobble.duration),
+        timestamp: ActiveValue::Set(scrobble.timestamp),
+        attempts: ActiveValue::Set(0),
+        created_at: ActiveValue::Set(created_at.clone()),
+        ..Default::default()
+    });
+
+    scrobble_queue::Entity::insert_many(models)
+        .on_conflict(
+            OnConflict::columns([
+                scrobble_queue::Column::Service,
+                scrobble_queue::Column::Artist,
+                scrobble_queue::Column::Track,
+                scrobble_queue::Column::Timestamp,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(main_db)
+        .await?;
+
+    Ok(())
+}
+
+/// Lists the oldest undelivered scrobbles of a service that were rejected
+/// fewer than `max_attempts` times.
+pub async fn list_pending_scrobbles(
+    main_db: &DatabaseConnection,
+    service: &str,
+    max_attempts: i32,
+    limit: u64,
+) -> Result<Vec<scrobble_queue::Model>> {
+    Ok(scrobble_queue::Entity::find()
+        .filter(scrobble_queue::Column::Service.eq(service))
+        .filter(scrobble_queue::Column::DeliveredAt.is_null())
+        .filter(scrobble_queue::Column::Attempts.lt(max_attempts))
+        .order_by_asc(scrobble_queue::Column::Timestamp)
+        .order_by_asc(scrobble_queue::Column::Id)
+        .limit(limit)
+        .all(main_db)
+        .await?)
+}
+
+/// Marks scrobbles as delivered and forgets those delivered long ago.
+pub async fn mark_scrobbles_delivered(main_db: &DatabaseConnection, ids: &[i32]) -> Result<()> {
+    let now = Utc::now();
+
+    scrobble_queue::Entity::update_many()
+        .col_expr(
+            scrobble_queue::Column::DeliveredAt,
+            Expr::value(now.to_rfc3339()),
+        )
+        .col_expr(
+            scrobble_queue::Column::LastError,
+            Expr::value(Option::<String>::None),
+        )
+        .filter(scrobble_queue::Column::Id.is_in(ids.to_vec()))
+        .exec(main_db)
+        .await?;
+
+    scrobble_queue::Entity::delete_many()
+        .filter(scrobble_queue::Column::DeliveredAt.is_not_null())
+        .filter(
+            scrobble_queue::Column::DeliveredAt
+                .lt((now - Duration::days(DELIVERED_RETENTION_DAYS)).to_rfc3339()),
+        )
+        .exec(main_db)
+        .await?;
+
+    Ok(())
+}
+
+/// Records a rejection of the scrobbles.
+pub async fn mark_scrobbles_failed(
+    main_db: &DatabaseConnection,
+    ids: &[i32],
+    error: &str,
+) -> Result<()> {
+    scrobble_queue::Entity::update_many()
+        .col_expr(
+            scrobble_queue::Column::Attempts,
+            Expr::col(scrobble_queue::Column::Attempts).add(1),
+        )
+        .col_expr(scrobble_queue::Column::LastError, Expr::value(error))
+        .filter(scrobble_queue::Column::Id.is_in(ids.to_vec()))
+        .exec(main_db)
+        .await?;
+
+    Ok(())
+}
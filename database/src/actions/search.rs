@@ -1,17 +1,52 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Result;
 use deunicode::deunicode;
 use log::warn;
+use sea_orm::sea_query::Condition;
 use sea_orm::{
     ColumnTrait, DatabaseConnection, DbBackend, EntityTrait, FromQueryResult, QueryFilter,
-    Statement,
+    QuerySelect, QueryTrait, Select, Statement,
 };
 
-use crate::entities::search_index;
+use crate::actions::mix_filters::{FieldFilter, Range};
+use crate::actions::search_query::{SearchField, SearchQuery, SearchTerm};
+use crate::entities::{
+    media_file_albums, media_file_artists, media_file_genres, media_files, search_index,
+};
 
 use super::{collection::CollectionQueryType, utils::DatabaseExecutor};
 
+/// Collections searched when the caller does not narrow them down.
+const SEARCHABLE_TYPES: [CollectionQueryType; 7] = [
+    CollectionQueryType::Track,
+    CollectionQueryType::Artist,
+    CollectionQueryType::Album,
+    CollectionQueryType::Genre,
+    CollectionQueryType::Playlist,
+    CollectionQueryType::Mix,
+    CollectionQueryType::Directory,
+];
+
+/// Scoped fields that can narrow down tracks and albums.
+const NARROWING_FIELDS: [SearchField; 3] =
+    [SearchField::Artist, SearchField::Album, SearchField::Genre];
+
+/// Documents sharing trigrams with the query that are checked for typos.
+const FUZZY_CANDIDATES: i64 = 200;
+
+/// Typo tolerant matches rank below every exact or prefix match.
+const FUZZY_WEIGHT: f64 = 0.6;
+
+/// Names a scoped term may stand for, like the artists `artist:floyd` matches.
+const SCOPE_MATCHES: usize = 50;
+
+/// Text matches checked against the scopes for each result wanted.
+const NARROWED_CANDIDATES_FACTOR: usize = 10;
+
+/// Score of items found through scopes alone, like the tracks of `artist:floyd`.
+const SCOPED_SCORE: f64 = 0.5;
+
 pub fn convert_to_collection_types(input: Vec<String>) -> Vec<CollectionQueryType> {
     input
         .into_iter()
@@ -60,6 +95,14 @@ where
         .exec(main_db)
         .await?;
 
+    main_db
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            r#"DELETE FROM search_trigram WHERE key = ? AND entry_type = ?;"#,
+            [id.to_string().into(), entry_type.to_string().into()],
+        ))
+        .await?;
+
     Ok(())
 }
 
@@ -87,6 +130,18 @@ where
         ],
     )).all(main_db).await?;
 
+    main_db
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            r#"INSERT INTO search_trigram (key, entry_type, doc) VALUES (?, ?, ?);"#,
+            [
+                id.to_string().into(),
+                entry_type.to_string().into(),
+                deunicode(name).into(),
+            ],
+        ))
+        .await?;
+
     Ok(())
 }
 
@@ -97,47 +152,460 @@ pub struct SearchResult {
     pub doc: String,
 }
 
-pub async fn search_for(
+/// An item found by a search. Scores of different collections are
+/// comparable, higher is better.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub collection_type: CollectionQueryType,
+    pub id: i64,
+    pub score: f64,
+}
+
+/// Folds case, accents and punctuation like the search index does.
+fn normalize_words(text: &str) -> Vec<String> {
+    deunicode(text)
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect()
+}
+
+/// Builds the FTS5 query: phrases match as written, words as prefixes.
+fn fts_query(terms: &[SearchTerm]) -> Option<String> {
+    let parts: Vec<String> = terms
+        .iter()
+        .flat_map(|term| {
+            let words = normalize_words(&term.text);
+            if term.phrase {
+                if words.is_empty() {
+                    vec![]
+                } else {
+                    vec![format!("\"{}\"", words.join(" "))]
+                }
+            } else {
+                words.into_iter().map(|x| format!("\"{}\"*", x)).collect()
+            }
+        })
+        .collect();
+
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+/// Builds the query for the trigram index, any shared trigram makes a
+/// document a candidate.
+fn trigram_query(words: &[String]) -> Option<String> {
+    let mut trigrams: Vec<String> = words
+        .iter()
+        .flat_map(|word| {
+            let chars: Vec<char> = word.chars().collect();
+            chars
+                .windows(3)
+                .map(|x| format!("\"{}\"", x.iter().collect::<String>()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    trigrams.sort();
+    trigrams.dedup();
+
+    (!trigrams.is_empty()).then(|| trigrams.join(" OR "))
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Edit distance between a query word and a document word, or the start
+/// of it, so words still being typed match.
+fn word_distance(word: &[char], doc_word: &[char]) -> usize {
+    let full = levenshtein(word, doc_word);
+    if doc_word.len() > word.len() {
+        full.min(levenshtein(word, &doc_word[..word.len()]))
+    } else {
+        full
+    }
+}
+
+/// Typos allowed in a word, none in short words.
+fn allowed_typos(length: usize) -> usize {
+    match length {
+        0..=3 => 0,
+        4..=6 => 1,
+        _ => 2,
+    }
+}
+
+fn text_score(query_words: &[String], doc: &str) -> f64 {
+    let doc_words = normalize_words(doc);
+    if doc_words == query_words {
+        1.0
+    } else if doc_words.starts_with(query_words) {
+        0.9
+    } else {
+        0.8
+    }
+}
+
+fn fuzzy_score(words: &[String], phrases: &[String], doc: &str) -> Option<f64> {
+    let doc_words = normalize_words(doc);
+    let doc_text = format!(" {} ", doc_words.join(" "));
+    if phrases
+        .iter()
+        .any(|phrase| !doc_text.contains(&format!(" {} ", phrase)))
+    {
+        return None;
+    }
+
+    let doc_words: Vec<Vec<char>> = doc_words.iter().map(|x| x.chars().collect()).collect();
+    let mut distance = 0;
+    let mut length = 0;
+    for word in words {
+        let word: Vec<char> = word.chars().collect();
+        let best = doc_words.iter().map(|x| word_distance(&word, x)).min()?;
+        if best > allowed_typos(word.len()) {
+            return None;
+        }
+
+        distance += best;
+        length += word.len();
+    }
+
+    Some(1.0 - distance as f64 / length.max(1) as f64)
+}
+
+fn collect_hits<F>(
+    rows: Vec<SearchResult>,
+    hits: &mut Vec<(i64, f64)>,
+    seen: &mut HashSet<i64>,
+    score: F,
+) where
+    F: Fn(&str) -> Option<f64>,
+{
+    for row in rows {
+        let Ok(id) = row.key.parse::<i64>() else {
+            warn!("Invalid document ID found!");
+            continue;
+        };
+
+        if seen.contains(&id) {
+            continue;
+        }
+
+        if let Some(score) = score(&row.doc) {
+            seen.insert(id);
+            hits.push((id, score));
+        }
+    }
+}
+
+/// Matches terms against the names of one collection, best first. Exact and
+/// prefix matches come from the full text index, typo tolerant ones from
+/// the trigram index when there are too few of them.
+async fn match_text(
     main_db: &DatabaseConnection,
-    query_str: &str,
-    search_fields: Option<Vec<CollectionQueryType>>,
+    collection_type: &CollectionQueryType,
+    terms: &[SearchTerm],
+    limit: usize,
+) -> Result<Vec<(i64, f64)>> {
+    let mut hits: Vec<(i64, f64)> = Vec::new();
+    let mut seen: HashSet<i64> = HashSet::new();
+
+    if let Some(query) = fts_query(terms) {
+        let query_words: Vec<String> = terms
+            .iter()
+            .flat_map(|x| normalize_words(&x.text))
+            .collect();
+
+        // Every name is indexed as written and transliterated
+        let rows = SearchResult::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            r#"SELECT key, entry_type, doc FROM search_index WHERE doc MATCH ? AND entry_type = ? ORDER BY rank LIMIT ?;"#,
+            [query.into(), collection_type.to_string().into(), ((limit * 2) as i64).into()],
+        )).all(main_db).await?;
+
+        collect_hits(rows, &mut hits, &mut seen, |doc| {
+            Some(text_score(&query_words, doc))
+        });
+    }
+
+    let words: Vec<String> = terms
+        .iter()
+        .filter(|x| !x.phrase)
+        .flat_map(|x| normalize_words(&x.text))
+        .collect();
+
+    if hits.len() < limit {
+        if let Some(query) = trigram_query(&words) {
+            let phrases: Vec<String> = terms
+                .iter()
+                .filter(|x| x.phrase)
+                .map(|x| normalize_words(&x.text).join(" "))
+                .collect();
+
+            let rows = SearchResult::find_by_statement(Statement::from_sql_and_values(
+                DbBackend::Sqlite,
+                r#"SELECT key, entry_type, doc FROM search_trigram WHERE doc MATCH ? AND entry_type = ? ORDER BY rank LIMIT ?;"#,
+                [query.into(), collection_type.to_string().into(), FUZZY_CANDIDATES.into()],
+            )).all(main_db).await?;
+
+            collect_hits(rows, &mut hits, &mut seen, |doc| {
+                fuzzy_score(&words, &phrases, doc).map(|x| x * FUZZY_WEIGHT)
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+    hits.truncate(limit);
+
+    Ok(hits)
+}
+
+/// Builds the condition on `media_files` that scoped terms of other
+/// collections and the year range put on tracks.
+async fn scope_condition(
+    main_db: &DatabaseConnection,
+    query: &SearchQuery,
+    collection_type: &CollectionQueryType,
+) -> Result<Condition> {
+    let mut condition = Condition::all();
+
+    for (field, term) in query.foreign_scopes(collection_type) {
+        let ids: Vec<i32> = match_text(
+            main_db,
+            &field.collection_type(),
+            std::slice::from_ref(term),
+            SCOPE_MATCHES,
+        )
+        .await?
+        .into_iter()
+        .map(|(id, _)| id as i32)
+        .collect();
+
+        let subquery = match field {
+            SearchField::Artist => media_file_artists::Entity::find()
+                .select_only()
+                .column(media_file_artists::Column::MediaFileId)
+                .filter(media_file_artists::Column::ArtistId.is_in(ids))
+                .into_query(),
+            SearchField::Album => media_file_albums::Entity::find()
+                .select_only()
+                .column(media_file_albums::Column::MediaFileId)
+                .filter(media_file_albums::Column::AlbumId.is_in(ids))
+                .into_query(),
+            SearchField::Genre => media_file_genres::Entity::find()
+                .select_only()
+                .column(media_file_genres::Column::MediaFileId)
+                .filter(media_file_genres::Column::GenreId.is_in(ids))
+                .into_query(),
+            _ => continue,
+        };
+
+        condition = condition.add(media_files::Column::Id.in_subquery(subquery));
+    }
+
+    if let Some(year) = query.year {
+        condition = condition.add(
+            FieldFilter::Year(Range {
+                min: year.min,
+                max: year.max,
+            })
+            .condition(),
+        );
+    }
+
+    Ok(condition)
+}
+
+/// Keeps the candidates `select` returns, or takes the first items it
+/// returns when nothing was matched by name.
+async fn narrow<E>(
+    main_db: &DatabaseConnection,
+    select: Select<E>,
+    id_column: E::Column,
+    candidates: Option<Vec<(i64, f64)>>,
     n: usize,
-) -> Result<HashMap<CollectionQueryType, Vec<i64>>> {
-    let mut results: HashMap<CollectionQueryType, Vec<i64>> = HashMap::new();
+) -> Result<Vec<(i64, f64)>>
+where
+    E: EntityTrait,
+{
+    let Some(candidates) = candidates else {
+        let ids: Vec<i32> = select.limit(n as u64).into_tuple().all(main_db).await?;
+        return Ok(ids
+            .into_iter()
+            .map(|id| (id as i64, SCOPED_SCORE))
+            .collect());
+    };
 
-    if query_str.is_empty() {
-        return Ok(results);
+    let ids: Vec<i32> = candidates.iter().map(|(id, _)| *id as i32).collect();
+    let allowed: HashSet<i32> = select
+        .filter(id_column.is_in(ids))
+        .into_tuple::<i32>()
+        .all(main_db)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(candidates
+        .into_iter()
+        .filter(|(id, _)| allowed.contains(&(*id as i32)))
+        .take(n)
+        .collect())
+}
+
+/// Searches a collection for items matching terms and the scopes.
+async fn search_narrowed(
+    main_db: &DatabaseConnection,
+    query: &SearchQuery,
+    collection_type: &CollectionQueryType,
+    terms: &[SearchTerm],
+    n: usize,
+) -> Result<Vec<(i64, f64)>> {
+    let candidates = if terms.is_empty() {
+        None
+    } else {
+        Some(
+            match_text(
+                main_db,
+                collection_type,
+                terms,
+                n * NARROWED_CANDIDATES_FACTOR,
+            )
+            .await?,
+        )
+    };
+
+    let tracks = media_files::Entity::find()
+        .select_only()
+        .column(media_files::Column::Id)
+        .filter(scope_condition(main_db, query, collection_type).await?);
+
+    match collection_type {
+        CollectionQueryType::Track => {
+            narrow(main_db, tracks, media_files::Column::Id, candidates, n).await
+        }
+        CollectionQueryType::Album => {
+            let albums = media_file_albums::Entity::find()
+                .select_only()
+                .column(media_file_albums::Column::AlbumId)
+                .distinct()
+                .filter(media_file_albums::Column::MediaFileId.in_subquery(tracks.into_query()));
+
+            narrow(
+                main_db,
+                albums,
+                media_file_albums::Column::AlbumId,
+                candidates,
+                n,
+            )
+            .await
+        }
+        _ => Ok(vec![]),
     }
+}
+
+/// Whether the scopes of a query apply to a collection. Tracks and albums
+/// can be narrowed by artist, album, genre and year, the rest only match
+/// their own names.
+fn accepts_scopes(query: &SearchQuery, collection_type: &CollectionQueryType) -> bool {
+    match collection_type {
+        CollectionQueryType::Track | CollectionQueryType::Album => query
+            .foreign_scopes(collection_type)
+            .all(|(field, _)| NARROWING_FIELDS.contains(field)),
+        _ => query.foreign_scopes(collection_type).next().is_none() && query.year.is_none(),
+    }
+}
+
+fn is_narrowed(query: &SearchQuery, collection_type: &CollectionQueryType) -> bool {
+    query.foreign_scopes(collection_type).next().is_some() || query.year.is_some()
+}
+
+/// Collections rank slightly above tracks of the same name, as searching
+/// for a name usually means the artist or album rather than a track.
+fn collection_weight(collection_type: &CollectionQueryType) -> f64 {
+    match collection_type {
+        CollectionQueryType::Track => 0.95,
+        CollectionQueryType::Directory => 0.9,
+        _ => 1.0,
+    }
+}
 
-    for collection_type in [
-        CollectionQueryType::Track,
-        CollectionQueryType::Artist,
-        CollectionQueryType::Album,
-        CollectionQueryType::Directory,
-        CollectionQueryType::Playlist,
-    ] {
-        if let Some(ref search_fields) = search_fields {
+/// Searches the library, returning at most `n` items of each collection,
+/// ranked together across collections.
+pub async fn search(
+    main_db: &DatabaseConnection,
+    query: &SearchQuery,
+    search_fields: Option<&[CollectionQueryType]>,
+    n: usize,
+) -> Result<Vec<SearchHit>> {
+    let mut hits: Vec<SearchHit> = Vec::new();
+
+    if query.is_empty() || n == 0 {
+        return Ok(hits);
+    }
+
+    for collection_type in SEARCHABLE_TYPES {
+        if let Some(search_fields) = search_fields {
             if !search_fields.contains(&collection_type) {
                 continue;
             }
         }
 
-        let query_str = deunicode(query_str);
+        if !accepts_scopes(query, &collection_type) {
+            continue;
+        }
 
-        let top_docs = SearchResult::find_by_statement(Statement::from_sql_and_values(
-            DbBackend::Sqlite,
-            r#"SELECT * FROM search_index WHERE doc MATCH ? AND entry_type = ? ORDER BY rank LIMIT ?;"#,
-            [ format!("\"{}\"", query_str.replace("\"", "\"\"")).into(), collection_type.to_string().into(), (n * 2).to_string().into() ],
-        )).all(main_db).await?;
+        let terms = query.terms_for(&collection_type);
+        let narrowed = is_narrowed(query, &collection_type);
 
-        for item in top_docs {
-            let id = item.key.parse::<i64>();
-            if let Ok(id) = id {
-                results.entry(collection_type.clone()).or_default().push(id);
-            } else {
-                warn!("Invalid document ID found!");
-            }
-        }
+        let found = if narrowed {
+            search_narrowed(main_db, query, &collection_type, &terms, n).await?
+        } else if !terms.is_empty() {
+            match_text(main_db, &collection_type, &terms, n).await?
+        } else {
+            continue;
+        };
+
+        let weight = collection_weight(&collection_type);
+        hits.extend(found.into_iter().map(|(id, score)| SearchHit {
+            collection_type: collection_type.clone(),
+            id,
+            score: score * weight,
+        }));
+    }
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    Ok(hits)
+}
+
+/// Searches the library with a query in the syntax `SearchQuery::parse`
+/// accepts, grouping the results by collection.
+pub async fn search_for(
+    main_db: &DatabaseConnection,
+    query_str: &str,
+    search_fields: Option<Vec<CollectionQueryType>>,
+    n: usize,
+) -> Result<HashMap<CollectionQueryType, Vec<i64>>> {
+    let query = SearchQuery::parse(query_str);
+    let hits = search(main_db, &query, search_fields.as_deref(), n).await?;
+
+    let mut results: HashMap<CollectionQueryType, Vec<i64>> = HashMap::new();
+    for hit in hits {
+        results.entry(hit.collection_type).or_default().push(hit.id);
     }
 
     Ok(results)
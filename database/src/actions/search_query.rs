@@ -0,0 +1,393 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::actions::collection::CollectionQueryType;
+
+/// Fields a search term can be scoped to, written as `field:value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchField {
+    Artist,
+    Album,
+    Genre,
+    Title,
+    Playlist,
+    Mix,
+    Directory,
+}
+
+impl SearchField {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "artist" => Some(SearchField::Artist),
+            "album" => Some(SearchField::Album),
+            "genre" => Some(SearchField::Genre),
+            "title" | "track" => Some(SearchField::Title),
+            "playlist" => Some(SearchField::Playlist),
+            "mix" => Some(SearchField::Mix),
+            "dir" | "directory" => Some(SearchField::Directory),
+            _ => None,
+        }
+    }
+
+    /// The collection whose names the field matches.
+    pub fn collection_type(&self) -> CollectionQueryType {
+        match self {
+            SearchField::Artist => CollectionQueryType::Artist,
+            SearchField::Album => CollectionQueryType::Album,
+            SearchField::Genre => CollectionQueryType::Genre,
+            SearchField::Title => CollectionQueryType::Track,
+            SearchField::Playlist => CollectionQueryType::Playlist,
+            SearchField::Mix => CollectionQueryType::Mix,
+            SearchField::Directory => CollectionQueryType::Directory,
+        }
+    }
+}
+
+/// A word or a quoted phrase. Words match as prefixes and tolerate typos,
+/// phrases only match as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchTerm {
+    pub text: String,
+    pub phrase: bool,
+}
+
+/// An inclusive range of release years, either end may be open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct YearRange {
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+}
+
+impl YearRange {
+    fn intersect(self, other: YearRange) -> YearRange {
+        YearRange {
+            min: self.min.max(other.min),
+            max: match (self.max, other.max) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+/// A parsed search query.
+///
+/// `"dark side" artist:floyd year:>1970` matches the phrase `dark side`
+/// among tracks, albums and the rest, keeps tracks and albums by an artist
+/// matching `floyd` released after 1970, and looks for artists matching
+/// both `dark side` and `floyd`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Terms matched against every collection
+    pub terms: Vec<SearchTerm>,
+    /// Terms matched against the names of one collection
+    pub scoped: Vec<(SearchField, SearchTerm)>,
+    pub year: Option<YearRange>,
+}
+
+impl SearchQuery {
+    /// Parses a query. Parsing never fails, so partially typed queries
+    /// still search: unknown fields and invalid years are searched as
+    /// plain text, and an unclosed quote runs to the end.
+    pub fn parse(input: &str) -> Self {
+        let mut query = SearchQuery::default();
+        let mut chars = input.chars().peekable();
+
+        while let Some(token) = next_token(&mut chars) {
+            let Some((name, value, phrase)) = token.field() else {
+                query.terms.push(token.term());
+                continue;
+            };
+
+            if value.trim().is_empty() {
+                continue;
+            }
+
+            if name.eq_ignore_ascii_case("year") {
+                match parse_year(value) {
+                    Some(range) => {
+                        query.year = Some(match query.year {
+                            Some(year) => year.intersect(range),
+                            None => range,
+                        });
+                    }
+                    None => query.terms.push(token.term()),
+                }
+                continue;
+            }
+
+            match SearchField::from_name(name) {
+                Some(field) => query.scoped.push((
+                    field,
+                    SearchTerm {
+                        text: value.to_string(),
+                        phrase,
+                    },
+                )),
+                None => query.terms.push(token.term()),
+            }
+        }
+
+        query
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.scoped.is_empty() && self.year.is_none()
+    }
+
+    /// Terms matched against the names of a collection.
+    pub fn terms_for(&self, collection_type: &CollectionQueryType) -> Vec<SearchTerm> {
+        self.terms
+            .iter()
+            .cloned()
+            .chain(
+                self.scoped
+                    .iter()
+                    .filter(|(field, _)| field.collection_type() == *collection_type)
+                    .map(|(_, term)| term.clone()),
+            )
+            .collect()
+    }
+
+    /// Scoped terms of other collections than `collection_type`.
+    pub fn foreign_scopes(
+        &self,
+        collection_type: &CollectionQueryType,
+    ) -> impl Iterator<Item = &(SearchField, SearchTerm)> {
+        let collection_type = collection_type.clone();
+        self.scoped
+            .iter()
+            .filter(move |(field, _)| field.collection_type() != collection_type)
+    }
+}
+
+#[derive(Debug)]
+struct Token {
+    text: String,
+    /// Byte offset of the `:` ending a field name
+    colon: Option<usize>,
+    /// Whether the text after the field name was quoted
+    quoted: bool,
+}
+
+impl Token {
+    fn field(&self) -> Option<(&str, &str, bool)> {
+        let colon = self.colon?;
+        Some((&self.text[..colon], &self.text[colon + 1..], self.quoted))
+    }
+
+    fn term(&self) -> SearchTerm {
+        SearchTerm {
+            text: self.text.clone(),
+            phrase: self.quoted && self.colon.is_none(),
+        }
+    }
+}
+
+fn next_token(chars: &mut Peekable<Chars>) -> Option<Token> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    chars.peek()?;
+
+    let mut token = Token {
+        text: String::new(),
+        colon: None,
+        quoted: false,
+    };
+
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+        match c {
+            '"' => {
+                token.quoted = true;
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    token.text.push(c);
+                }
+            }
+            ':' if token.colon.is_none() && !token.quoted && !token.text.is_empty() => {
+                token.colon = Some(token.text.len());
+                token.text.push(c);
+            }
+            _ => token.text.push(c),
+        }
+    }
+
+    Some(token)
+}
+
+/// Parses `2000`, `>2000`, `>=2000`, `<2000`, `<=2000` and `1990..1999`.
+fn parse_year(value: &str) -> Option<YearRange> {
+    let value = value.trim();
+
+    if let Some((min, max)) = value.split_once("..") {
+        let parse_end = |x: &str| -> Option<Option<i32>> {
+            let x = x.trim();
+            if x.is_empty() {
+                Some(None)
+            } else {
+                x.parse().ok().map(Some)
+            }
+        };
+
+        let range = YearRange {
+            min: parse_end(min)?,
+            max: parse_end(max)?,
+        };
+        return (range.min.is_some() || range.max.is_some()).then_some(range);
+    }
+
+    let (operator, year) = match value.find(|c: char| c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => return None,
+    };
+    let year: i32 = year.parse().ok()?;
+
+    match operator.trim() {
+        "" | "=" => Some(YearRange {
+            min: Some(year),
+            max: Some(year),
+        }),
+        ">" => Some(YearRange {
+            min: Some(year + 1),
+            max: None,
+        }),
+        ">=" => Some(YearRange {
+            min: Some(year),
+            max: None,
+        }),
+        "<" => Some(YearRange {
+            min: None,
+            max: Some(year - 1),
+        }),
+        "<=" => Some(YearRange {
+            min: None,
+            max: Some(year),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> SearchTerm {
+        SearchTerm {
+            text: text.to_string(),
+            phrase: false,
+        }
+    }
+
+    fn phrase(text: &str) -> SearchTerm {
+        SearchTerm {
+            text: text.to_string(),
+            phrase: true,
+        }
+    }
+
+    fn years(min: Option<i32>, max: Option<i32>) -> YearRange {
+        YearRange { min, max }
+    }
+
+    #[test]
+    fn test_parse_terms() {
+        let query = SearchQuery::parse("  dark \"side of\"   moon ");
+        assert_eq!(
+            query.terms,
+            vec![word("dark"), phrase("side of"), word("moon")]
+        );
+        assert!(query.scoped.is_empty());
+        assert_eq!(query.year, None);
+
+        // An unclosed quote runs to the end
+        let query = SearchQuery::parse("\"the wall");
+        assert_eq!(query.terms, vec![phrase("the wall")]);
+
+        // A colon inside a phrase does not start a field
+        let query = SearchQuery::parse("\"artist:floyd\"");
+        assert_eq!(query.terms, vec![phrase("artist:floyd")]);
+
+        assert!(SearchQuery::parse("   ").is_empty());
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let query = SearchQuery::parse("Artist:floyd album:\"the wall\" track:mother dir:rock");
+        assert!(query.terms.is_empty());
+        assert_eq!(
+            query.scoped,
+            vec![
+                (SearchField::Artist, word("floyd")),
+                (SearchField::Album, phrase("the wall")),
+                (SearchField::Title, word("mother")),
+                (SearchField::Directory, word("rock")),
+            ]
+        );
+
+        // Unknown fields are searched as text, empty fields are ignored
+        let query = SearchQuery::parse("label:harvest artist: genre:\"\"");
+        assert_eq!(query.terms, vec![word("label:harvest")]);
+        assert!(query.scoped.is_empty());
+
+        // A leading colon is plain text
+        let query = SearchQuery::parse(":floyd");
+        assert_eq!(query.terms, vec![word(":floyd")]);
+    }
+
+    #[test]
+    fn test_parse_year_field() {
+        let query = SearchQuery::parse("year:>1970 year:<=1979 floyd");
+        assert_eq!(query.year, Some(years(Some(1971), Some(1979))));
+        assert_eq!(query.terms, vec![word("floyd")]);
+
+        // Invalid years are searched as text
+        let query = SearchQuery::parse("year:seventies");
+        assert_eq!(query.year, None);
+        assert_eq!(query.terms, vec![word("year:seventies")]);
+    }
+
+    #[test]
+    fn test_parse_year() {
+        assert_eq!(parse_year("1973"), Some(years(Some(1973), Some(1973))));
+        assert_eq!(parse_year("=1973"), Some(years(Some(1973), Some(1973))));
+        assert_eq!(parse_year(">1973"), Some(years(Some(1974), None)));
+        assert_eq!(parse_year(">=1973"), Some(years(Some(1973), None)));
+        assert_eq!(parse_year("<1973"), Some(years(None, Some(1972))));
+        assert_eq!(parse_year("<=1973"), Some(years(None, Some(1973))));
+        assert_eq!(
+            parse_year("1990..1999"),
+            Some(years(Some(1990), Some(1999)))
+        );
+        assert_eq!(parse_year("1990.."), Some(years(Some(1990), None)));
+        assert_eq!(parse_year("..1999"), Some(years(None, Some(1999))));
+
+        assert_eq!(parse_year(".."), None);
+        assert_eq!(parse_year("19x0..1999"), None);
+        assert_eq!(parse_year("~1973"), None);
+        assert_eq!(parse_year("1973s"), None);
+        assert_eq!(parse_year("now"), None);
+        assert_eq!(parse_year(""), None);
+    }
+
+    #[test]
+    fn test_terms_for() {
+        let query = SearchQuery::parse("wall artist:floyd album:animals");
+
+        assert_eq!(
+            query.terms_for(&CollectionQueryType::Artist),
+            vec![word("wall"), word("floyd")]
+        );
+        assert_eq!(
+            query.terms_for(&CollectionQueryType::Genre),
+            vec![word("wall")]
+        );
+        assert_eq!(
+            query
+                .foreign_scopes(&CollectionQueryType::Artist)
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![(SearchField::Album, word("animals"))]
+        );
+    }
+}
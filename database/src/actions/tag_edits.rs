@@ -0,0 +1,587 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use log::{error, info};
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveValue, DatabaseConnection, QueryOrder, TransactionTrait};
+use thiserror::Error;
+
+use metadata::describe::describe_file;
+use tag_editor::writer::{read_cover_art, read_tags, write_tags};
+
+pub use tag_editor::writer::{CoverArtEdit, TagEdit};
+
+use crate::actions::collection::CollectionQueryType;
+use crate::actions::cover_art::{
+    ensure_magic_cover_art_id, extract_cover_art_by_file_id, insert_extract_result,
+    remove_cover_art_by_file_id,
+};
+use crate::actions::index::index_media_files;
+use crate::actions::logging::{insert_log, LogLevel};
+use crate::actions::metadata::{read_metadata, update_file_metadata};
+use crate::actions::search::add_term;
+use crate::entities::{media_files, tag_edit_batches, tag_edit_entries};
+
+/// Key of the entry recording the previous front cover of a file.
+const COVER_ART_KEY: &str = "cover_art";
+
+/// The changes to apply to one file.
+#[derive(Debug, Clone)]
+pub struct FileTagEdit {
+    pub file_id: i32,
+    pub tags: Vec<TagEdit>,
+    pub cover_art: CoverArtEdit,
+}
+
+fn file_path(lib_path: &Path, file: &media_files::Model) -> PathBuf {
+    lib_path.join(&file.directory).join(&file.file_name)
+}
+
+async fn get_editable_file(
+    main_db: &DatabaseConnection,
+    file_id: i32,
+) -> Result<media_files::Model> {
+    let file = media_files::Entity::find_by_id(file_id)
+        .one(main_db)
+        .await?
+        .with_context(|| format!("File not found: {}", file_id))?;
+
+    // Tracks of a CUE sheet take their metadata from the sheet, not the file
+    if file.cue_track.is_some() {
+        bail!(
+            "Tags of CUE sheet tracks can not be edited: {}",
+            file.file_name
+        );
+    }
+
+    Ok(file)
+}
+
+/// Reads the file again after its tags changed and refreshes the stored
+/// metadata, hash, search term, cover art and index.
+async fn refresh_edited_file(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    file: &media_files::Model,
+    cover_art_changed: bool,
+) -> Result<()> {
+    let mut description = describe_file(&file_path(lib_path, file), &Some(lib_path.to_path_buf()))?;
+    let metadata = read_metadata(&description)?;
+
+    let txn = main_db.begin().await?;
+    update_file_metadata(&txn, file, &mut description, &metadata).await?;
+
+    let title = metadata
+        .metadata
+        .iter()
+        .find(|(key, _)| key == "track_title")
+        .map(|(_, value)| value.clone())
+        .unwrap_or_else(|| file.file_name.clone());
+    add_term(&txn, CollectionQueryType::Track, file.id, &title).await?;
+
+    if cover_art_changed {
+        remove_cover_art_by_file_id(&txn, file.id).await?;
+    }
+    txn.commit().await?;
+
+    if cover_art_changed {
+        let file = media_files::Entity::find_by_id(file.id)
+            .one(main_db)
+            .await?
+            .with_context(|| format!("File not found: {}", file.id))?;
+        let magic_cover_art_id = ensure_magic_cover_art_id(main_db).await?;
+        let result = extract_cover_art_by_file_id(&file, lib_path);
+        insert_extract_result(main_db, &file, magic_cover_art_id, result).await?;
+    }
+
+    index_media_files(main_db, vec![file.id], None).await?;
+
+    Ok(())
+}
+
+async fn log_refresh_error(
+    main_db: &DatabaseConnection,
+    file_id: i32,
+    e: anyhow::Error,
+) -> Result<()> {
+    error!("Failed to refresh edited file {}: {:?}", file_id, e);
+    insert_log(
+        main_db,
+        LogLevel::Error,
+        "actions::tag_edits::refresh_edited_file".to_string(),
+        format!("{:#?}", e),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// A batch that stopped at a file that could not be written. The files
+/// written before it stay changed and are recorded in the batch, so it can
+/// still be reverted.
+#[derive(Debug, Error)]
+#[error("Tag edit batch {batch_id} stopped at file {file_id}: {source:#}")]
+pub struct PartialTagEditError {
+    pub batch_id: i32,
+    pub file_id: i32,
+    #[source]
+    pub source: anyhow::Error,
+}
+
+/// Values of `keys` that are set, empty values are left out since writing
+/// them removes them.
+fn current_values(path: &Path, keys: &[String]) -> Result<Vec<TagEdit>> {
+    Ok(read_tags(path, keys)?
+        .into_iter()
+        .map(|x| TagEdit {
+            key: x.key,
+            values: x.values.into_iter().filter(|x| !x.is_empty()).collect(),
+        })
+        .collect())
+}
+
+/// Entries store the values of a field as a JSON array, fields without
+/// values as `NULL`.
+fn encode_values(values: Vec<String>) -> Result<Option<String>> {
+    if values.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::to_string(&values)?))
+}
+
+fn decode_values(value: Option<String>) -> Result<Vec<String>> {
+    match value {
+        Some(value) => serde_json::from_str(&value)
+            .with_context(|| format!("Malformed tag edit entry: {}", value)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Writes the edits of one file and records the previous values in the
+/// batch once the write succeeded. `old_tags` were read before the batch
+/// started.
+async fn write_file_edit(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    batch_id: i32,
+    file: &media_files::Model,
+    edit: &FileTagEdit,
+    old_tags: Vec<TagEdit>,
+) -> Result<()> {
+    let path = file_path(lib_path, file);
+    let cover_art_changed = edit.cover_art != CoverArtEdit::Keep;
+    let old_picture = match cover_art_changed {
+        true => read_cover_art(&path)?,
+        false => None,
+    };
+
+    write_tags(&path, &edit.tags, &edit.cover_art)?;
+
+    // Formats may store a value differently than it was given, the value
+    // read back is what undoing compares against
+    let keys: Vec<String> = edit.tags.iter().map(|x| x.key.clone()).collect();
+    let new_tags = current_values(&path, &keys)?;
+
+    let mut entries: Vec<tag_edit_entries::ActiveModel> = old_tags
+        .into_iter()
+        .zip(new_tags)
+        .map(|(old, new)| {
+            Ok(tag_edit_entries::ActiveModel {
+                batch_id: ActiveValue::Set(batch_id),
+                file_id: ActiveValue::Set(file.id),
+                meta_key: ActiveValue::Set(new.key),
+                old_value: ActiveValue::Set(encode_values(old.values)?),
+                new_value: ActiveValue::Set(encode_values(new.values)?),
+                old_picture: ActiveValue::Set(None),
+                ..Default::default()
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    if cover_art_changed {
+        entries.push(tag_edit_entries::ActiveModel {
+            batch_id: ActiveValue::Set(batch_id),
+            file_id: ActiveValue::Set(file.id),
+            meta_key: ActiveValue::Set(COVER_ART_KEY.to_string()),
+            old_value: ActiveValue::Set(None),
+            new_value: ActiveValue::Set(None),
+            old_picture: ActiveValue::Set(old_picture),
+            ..Default::default()
+        });
+    }
+
+    if !entries.is_empty() {
+        tag_edit_entries::Entity::insert_many(entries)
+            .exec(main_db)
+            .await?;
+    }
+
+    if let Err(e) = refresh_edited_file(main_db, lib_path, file, cover_art_changed).await {
+        log_refresh_error(main_db, file.id, e).await?;
+    }
+
+    Ok(())
+}
+
+/// Writes the tags of several files as one batch and returns the batch id.
+///
+/// Every file and field is checked before anything is written. Files are
+/// then written one after another and recorded in the batch as their write
+/// succeeds, so the batch can be reverted with `undo_tag_edit_batch`. If a
+/// write fails, the batch stops with a `PartialTagEditError` holding its
+/// id, unless nothing was written yet.
+pub async fn edit_file_tags(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    edits: Vec<FileTagEdit>,
+) -> Result<i32> {
+    let mut files = Vec::new();
+    for edit in &edits {
+        let file = get_editable_file(main_db, edit.file_id).await?;
+
+        let keys: Vec<String> = edit.tags.iter().map(|x| x.key.clone()).collect();
+        let old_tags = current_values(&file_path(lib_path, &file), &keys)?;
+
+        files.push((file, old_tags));
+    }
+
+    let batch = tag_edit_batches::ActiveModel {
+        created_at: ActiveValue::Set(Utc::now().to_rfc3339()),
+        reverted_at: ActiveValue::Set(None),
+        ..Default::default()
+    }
+    .insert(main_db)
+    .await?;
+
+    let mut written = 0;
+    for (edit, (file, old_tags)) in edits.iter().zip(files) {
+        if edit.tags.is_empty() && edit.cover_art == CoverArtEdit::Keep {
+            continue;
+        }
+
+        if let Err(e) = write_file_edit(main_db, lib_path, batch.id, &file, edit, old_tags).await {
+            if written == 0 {
+                tag_edit_batches::Entity::delete_by_id(batch.id)
+                    .exec(main_db)
+                    .await?;
+                return Err(e);
+            }
+
+            return Err(PartialTagEditError {
+                batch_id: batch.id,
+                file_id: file.id,
+                source: e,
+            }
+            .into());
+        }
+        written += 1;
+    }
+
+    info!("Tag edit batch {} written", batch.id);
+
+    Ok(batch.id)
+}
+
+/// Writes the values recorded in a batch back to the files.
+///
+/// Fields that were changed again after the batch are not overwritten, the
+/// batch is refused before anything is written. The previous cover art is
+/// restored as it was recorded.
+pub async fn undo_tag_edit_batch(
+    main_db: &DatabaseConnection,
+    lib_path: &Path,
+    batch_id: i32,
+) -> Result<()> {
+    let batch = tag_edit_batches::Entity::find_by_id(batch_id)
+        .one(main_db)
+        .await?
+        .with_context(|| format!("Tag edit batch not found: {}", batch_id))?;
+
+    if batch.reverted_at.is_some() {
+        bail!("Tag edit batch has already been reverted: {}", batch_id);
+    }
+
+    let entries = tag_edit_entries::Entity::find()
+        .filter(tag_edit_entries::Column::BatchId.eq(batch_id))
+        .order_by_asc(tag_edit_entries::Column::Id)
+        .all(main_db)
+        .await?;
+
+    let mut edits: BTreeMap<i32, (Vec<TagEdit>, Vec<TagEdit>, CoverArtEdit)> = BTreeMap::new();
+    for entry in entries {
+        let (old_tags, new_tags, cover_art) = edits.entry(entry.file_id).or_default();
+
+        if entry.meta_key == COVER_ART_KEY {
+            *cover_art = match entry.old_picture {
+                Some(data) => CoverArtEdit::Replace(data),
+                None => CoverArtEdit::Remove,
+            };
+        } else {
+            old_tags.push(TagEdit {
+                key: entry.meta_key.clone(),
+                values: decode_values(entry.old_value)?,
+            });
+            new_tags.push(TagEdit {
+                key: entry.meta_key,
+                values: decode_values(entry.new_value)?,
+            });
+        }
+    }
+
+    let mut files = Vec::new();
+    for (file_id, (old_tags, new_tags, cover_art)) in edits {
+        let file = get_editable_file(main_db, file_id).await?;
+
+        let keys: Vec<String> = new_tags.iter().map(|x| x.key.clone()).collect();
+        let current_tags = current_values(&file_path(lib_path, &file), &keys)?;
+        let changed: Vec<&str> = current_tags
+            .iter()
+            .zip(new_tags.iter())
+            .filter(|(current, new)| current.values != new.values)
+            .map(|(current, _)| current.key.as_str())
+            .collect();
+        if !changed.is_empty() {
+            bail!(
+                "Tags of {} changed after batch {}: {}",
+                file.file_name,
+                batch_id,
+                changed.join(", ")
+            );
+        }
+
+        files.push((file, old_tags, cover_art));
+    }
+
+    for (file, old_tags, cover_art) in files {
+        let cover_art_changed = cover_art != CoverArtEdit::Keep;
+
+        write_tags(&file_path(lib_path, &file), &old_tags, &cover_art)?;
+
+        if let Err(e) = refresh_edited_file(main_db, lib_path, &file, cover_art_changed).await {
+            log_refresh_error(main_db, file.id, e).await?;
+        }
+    }
+
+    let mut active_model: tag_edit_batches::ActiveModel = batch.into();
+    active_model.reverted_at = ActiveValue::Set(Some(Utc::now().to_rfc3339()));
+    active_model.update(main_db).await?;
+
+    info!("Tag edit batch {} reverted", batch_id);
+
+    Ok(())
+}
+
+/// Lists the batches that can still be reverted, newest first.
+pub async fn list_tag_edit_batches(
+    main_db: &DatabaseConnection,
+) -> Result<Vec<tag_edit_batches::Model>> {
+    Ok(tag_edit_batches::Entity::find()
+        .filter(tag_edit_batches::Column::RevertedAt.is_null())
+        .order_by_desc(tag_edit_batches::Column::Id)
+        .all(main_db)
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    use crate::connection::connect_test_main_db;
+
+    use super::*;
+
+    /// Copies an Ogg Vorbis file into a new library and indexes it.
+    async fn setup() -> (DatabaseConnection, TempDir, i32) {
+        let db = connect_test_main_db().await;
+        let lib_dir = TempDir::new().unwrap();
+
+        fs::create_dir(lib_dir.path().join("Artist")).unwrap();
+        fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/startup_0.ogg"),
+            lib_dir.path().join("Artist/track.ogg"),
+        )
+        .unwrap();
+
+        let file = media_files::ActiveModel {
+            file_name: ActiveValue::Set("track.ogg".to_string()),
+            directory: ActiveValue::Set("Artist".to_string()),
+            extension: ActiveValue::Set("ogg".to_string()),
+            file_hash: ActiveValue::Set(String::new()),
+            last_modified: ActiveValue::Set(String::new()),
+            cover_art_id: ActiveValue::Set(None),
+            sample_rate: ActiveValue::Set(44100),
+            duration: ActiveValue::Set(Decimal::ONE),
+            cue_track: ActiveValue::Set(None),
+            start_offset: ActiveValue::Set(None),
+            end_offset: ActiveValue::Set(None),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        (db, lib_dir, file.id)
+    }
+
+    fn edit(key: &str, value: &str) -> TagEdit {
+        TagEdit {
+            key: key.to_string(),
+            values: vec![value.to_string()],
+        }
+    }
+
+    fn title_and_album(lib_dir: &TempDir) -> Vec<TagEdit> {
+        current_values(
+            &lib_dir.path().join("Artist/track.ogg"),
+            &["track_title".to_string(), "album".to_string()],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_edit_and_undo() {
+        let (db, lib_dir, file_id) = setup().await;
+        let original = title_and_album(&lib_dir);
+
+        let batch_id = edit_file_tags(
+            &db,
+            lib_dir.path(),
+            vec![FileTagEdit {
+                file_id,
+                tags: vec![edit("track_title", "New Title"), edit("album", "New Album")],
+                cover_art: CoverArtEdit::Keep,
+            }],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            title_and_album(&lib_dir),
+            vec![edit("track_title", "New Title"), edit("album", "New Album")]
+        );
+
+        let entries = tag_edit_entries::Entity::find()
+            .filter(tag_edit_entries::Column::BatchId.eq(batch_id))
+            .all(&db)
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].old_value,
+            encode_values(original[0].values.clone()).unwrap()
+        );
+        assert_eq!(entries[0].new_value.as_deref(), Some(r#"["New Title"]"#));
+
+        undo_tag_edit_batch(&db, lib_dir.path(), batch_id)
+            .await
+            .unwrap();
+        assert_eq!(title_and_album(&lib_dir), original);
+
+        // A batch is reverted once
+        assert!(list_tag_edit_batches(&db).await.unwrap().is_empty());
+        assert!(undo_tag_edit_batch(&db, lib_dir.path(), batch_id)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_undo_restores_every_value() {
+        let (db, lib_dir, file_id) = setup().await;
+
+        let path = lib_dir.path().join("Artist/track.ogg");
+        let artists = TagEdit {
+            key: "artist".to_string(),
+            values: vec!["First Artist".to_string(), "Second Artist".to_string()],
+        };
+        write_tags(&path, &[artists.clone()], &CoverArtEdit::Keep).unwrap();
+
+        let batch_id = edit_file_tags(
+            &db,
+            lib_dir.path(),
+            vec![FileTagEdit {
+                file_id,
+                tags: vec![edit("artist", "Solo")],
+                cover_art: CoverArtEdit::Keep,
+            }],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            current_values(&path, &["artist".to_string()]).unwrap(),
+            vec![edit("artist", "Solo")]
+        );
+
+        undo_tag_edit_batch(&db, lib_dir.path(), batch_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            current_values(&path, &["artist".to_string()]).unwrap(),
+            vec![artists]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undo_keeps_later_changes() {
+        let (db, lib_dir, file_id) = setup().await;
+
+        let batch_id = edit_file_tags(
+            &db,
+            lib_dir.path(),
+            vec![FileTagEdit {
+                file_id,
+                tags: vec![edit("track_title", "New Title")],
+                cover_art: CoverArtEdit::Keep,
+            }],
+        )
+        .await
+        .unwrap();
+
+        let path = lib_dir.path().join("Artist/track.ogg");
+        write_tags(
+            &path,
+            &[edit("track_title", "Changed Again")],
+            &CoverArtEdit::Keep,
+        )
+        .unwrap();
+
+        assert!(undo_tag_edit_batch(&db, lib_dir.path(), batch_id)
+            .await
+            .is_err());
+        assert_eq!(
+            title_and_album(&lib_dir)[0],
+            edit("track_title", "Changed Again")
+        );
+        assert_eq!(list_tag_edit_batches(&db).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_edits_write_nothing() {
+        let (db, lib_dir, file_id) = setup().await;
+        let original = title_and_album(&lib_dir);
+
+        let result = edit_file_tags(
+            &db,
+            lib_dir.path(),
+            vec![FileTagEdit {
+                file_id,
+                tags: vec![edit("track_title", "New Title"), edit("unknown", "Value")],
+                cover_art: CoverArtEdit::Keep,
+            }],
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(title_and_album(&lib_dir), original);
+        assert!(tag_edit_batches::Entity::find()
+            .all(&db)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use deunicode::deunicode;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::entity::prelude::*;
+
+use crate::entities::{artists, media_file_artists, media_files, media_metadata};
+
+/// Tracks whose durations differ by more than this many seconds are
+/// different recordings.
+const DURATION_TOLERANCE: f64 = 5.0;
+
+/// Folds case, accents and punctuation, so `Sigur Rós` matches `sigur ros`.
+pub(crate) fn normalize_name(name: &str) -> String {
+    deunicode(name)
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug)]
+pub(crate) struct LibraryTrack {
+    pub id: i32,
+    pub artists: HashSet<String>,
+    pub album_artist: String,
+    pub album: String,
+    pub duration: f64,
+}
+
+/// What is known about a track outside of the library, such as a play in
+/// an exported history or an entry of a playlist.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TrackQuery<'a> {
+    pub title: &'a str,
+    pub artist: Option<&'a str>,
+    pub album_artist: Option<&'a str>,
+    pub album: Option<&'a str>,
+    /// Length in seconds
+    pub duration: Option<f64>,
+}
+
+/// Library tracks by normalized title.
+pub(crate) struct LibraryIndex {
+    tracks: HashMap<String, Vec<LibraryTrack>>,
+}
+
+impl LibraryIndex {
+    pub(crate) async fn load<E>(main_db: &E) -> Result<Self>
+    where
+        E: ConnectionTrait,
+    {
+        let files = media_files::Entity::find().all(main_db).await?;
+
+        let mut metadata: HashMap<i32, HashMap<String, String>> = HashMap::new();
+        for entry in media_metadata::Entity::find()
+            .filter(media_metadata::Column::MetaKey.is_in([
+                "artist",
+                "album_artist",
+                "album",
+                "track_title",
+            ]))
+            .all(main_db)
+            .await?
+        {
+            metadata
+                .entry(entry.file_id)
+                .or_default()
+                .insert(entry.meta_key, entry.meta_value);
+        }
+
+        let artist_names: HashMap<i32, String> = artists::Entity::find()
+            .all(main_db)
+            .await?
+            .into_iter()
+            .map(|x| (x.id, normalize_name(&x.name)))
+            .collect();
+
+        let mut file_artists: HashMap<i32, HashSet<String>> = HashMap::new();
+        for link in media_file_artists::Entity::find().all(main_db).await? {
+            if let Some(name) = artist_names.get(&link.artist_id) {
+                file_artists
+                    .entry(link.media_file_id)
+                    .or_default()
+                    .insert(name.clone());
+            }
+        }
+
+        let mut tracks: HashMap<String, Vec<LibraryTrack>> = HashMap::new();
+        for file in files {
+            let empty = HashMap::new();
+            let metadata = metadata.get(&file.id).unwrap_or(&empty);
+
+            // The full artist tag also matches, for exports that keep
+            // collaborations as a single name
+            let mut artists = file_artists.remove(&file.id).unwrap_or_default();
+            if let Some(artist) = metadata.get("artist") {
+                artists.insert(normalize_name(artist));
+            }
+
+            let title = metadata
+                .get("track_title")
+                .cloned()
+                .unwrap_or(file.file_name.clone());
+
+            tracks
+                .entry(normalize_name(&title))
+                .or_default()
+                .push(LibraryTrack {
+                    id: file.id,
+                    artists,
+                    album_artist: normalize_name(
+                        metadata.get("album_artist").map_or("", |x| x.as_str()),
+                    ),
+                    album: normalize_name(metadata.get("album").map_or("", |x| x.as_str())),
+                    duration: file.duration.to_f64().unwrap_or_default(),
+                });
+        }
+
+        Ok(LibraryIndex { tracks })
+    }
+
+    /// Finds a track by title, narrowed down by artist, album artist and
+    /// duration when they are known. Among several matches the track on the
+    /// same album wins, then the one closest in duration. A bare title only
+    /// matches if a single track has it.
+    ///
+    /// Tracks without an album artist tag match an album artist that is one
+    /// of their artists.
+    pub(crate) fn find(&self, query: &TrackQuery) -> Option<&LibraryTrack> {
+        let candidates = self.tracks.get(&normalize_name(query.title))?;
+        let artist = query.artist.map(normalize_name);
+        let album_artist = query.album_artist.map(normalize_name);
+        let album = query.album.map(normalize_name);
+
+        let matches: Vec<&LibraryTrack> = candidates
+            .iter()
+            .filter(|x| {
+                artist
+                    .as_ref()
+                    .is_none_or(|artist| x.artists.contains(artist))
+            })
+            .filter(|x| {
+                album_artist.as_ref().is_none_or(|album_artist| {
+                    if x.album_artist.is_empty() {
+                        x.artists.contains(album_artist)
+                    } else {
+                        x.album_artist == *album_artist
+                    }
+                })
+            })
+            .filter(|x| {
+                query.duration.is_none_or(|duration| {
+                    x.duration <= 0.0 || (x.duration - duration).abs() <= DURATION_TOLERANCE
+                })
+            })
+            .collect();
+
+        if artist.is_none()
+            && album_artist.is_none()
+            && query.duration.is_none()
+            && matches.len() != 1
+        {
+            return None;
+        }
+
+        let same_album: Vec<&LibraryTrack> = match album {
+            Some(album) => matches
+                .iter()
+                .copied()
+                .filter(|x| x.album == album)
+                .collect(),
+            None => Vec::new(),
+        };
+        let matches = if same_album.is_empty() {
+            matches
+        } else {
+            same_album
+        };
+
+        match query.duration {
+            Some(duration) => matches.into_iter().min_by(|a, b| {
+                (a.duration - duration)
+                    .abs()
+                    .total_cmp(&(b.duration - duration).abs())
+            }),
+            None => matches.into_iter().next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(
+        id: i32,
+        artists: &[&str],
+        album_artist: &str,
+        album: &str,
+        duration: f64,
+    ) -> LibraryTrack {
+        LibraryTrack {
+            id,
+            artists: artists.iter().map(|x| normalize_name(x)).collect(),
+            album_artist: normalize_name(album_artist),
+            album: normalize_name(album),
+            duration,
+        }
+    }
+
+    fn index(tracks: Vec<(&str, LibraryTrack)>) -> LibraryIndex {
+        let mut index = LibraryIndex {
+            tracks: HashMap::new(),
+        };
+        for (title, track) in tracks {
+            index
+                .tracks
+                .entry(normalize_name(title))
+                .or_default()
+                .push(track);
+        }
+        index
+    }
+
+    fn find(index: &LibraryIndex, query: TrackQuery) -> Option<i32> {
+        index.find(&query).map(|x| x.id)
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("Sigur Rós"), "sigur ros");
+        assert_eq!(normalize_name("  AC/DC  "), "ac dc");
+        assert_eq!(normalize_name("Hoppípolla!"), "hoppipolla");
+    }
+
+    #[test]
+    fn test_find() {
+        let index = index(vec![
+            ("Song", track(1, &["Artist A"], "", "Album A", 200.0)),
+            ("Song", track(2, &["Artist B"], "", "Album B", 300.0)),
+            ("Song", track(3, &["Artist B"], "", "Live", 320.0)),
+            ("Unique", track(4, &["Artist A"], "", "Album A", 100.0)),
+        ]);
+
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "song",
+                    artist: Some("artist a"),
+                    ..Default::default()
+                }
+            ),
+            Some(1)
+        );
+        // The album, then the duration picks among several matches
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Song",
+                    artist: Some("Artist B"),
+                    album: Some("Live"),
+                    ..Default::default()
+                }
+            ),
+            Some(3)
+        );
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Song",
+                    duration: Some(302.0),
+                    ..Default::default()
+                }
+            ),
+            Some(2)
+        );
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Song",
+                    duration: Some(250.0),
+                    ..Default::default()
+                }
+            ),
+            None
+        );
+        // A bare title needs to be unique
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Song",
+                    ..Default::default()
+                }
+            ),
+            None
+        );
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Unique",
+                    ..Default::default()
+                }
+            ),
+            Some(4)
+        );
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Missing",
+                    ..Default::default()
+                }
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_by_album_artist() {
+        let index = index(vec![
+            (
+                "Song",
+                track(1, &["Artist A"], "Various Artists", "Compilation", 200.0),
+            ),
+            ("Song", track(2, &["Artist A"], "", "Album", 200.0)),
+            (
+                "Other",
+                track(3, &["Artist B", "Artist C"], "", "Album", 200.0),
+            ),
+        ]);
+
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Song",
+                    album_artist: Some("Various Artists"),
+                    ..Default::default()
+                }
+            ),
+            Some(1)
+        );
+        // Without an album artist tag the track artists stand in for it
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Song",
+                    album_artist: Some("Artist A"),
+                    ..Default::default()
+                }
+            ),
+            Some(2)
+        );
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Other",
+                    album_artist: Some("Artist C"),
+                    ..Default::default()
+                }
+            ),
+            Some(3)
+        );
+        assert_eq!(
+            find(
+                &index,
+                TrackQuery {
+                    title: "Other",
+                    album_artist: Some("Various Artists"),
+                    ..Default::default()
+                }
+            ),
+            None
+        );
+    }
+}
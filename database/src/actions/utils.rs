@@ -3,6 +3,9 @@ use deunicode::deunicode;
 use sea_orm::prelude::*;
 use sea_orm::{DatabaseConnection, DatabaseTransaction, EntityTrait};
 
+/// Extensions of the formats that store audio without loss.
+pub const LOSSLESS_EXTENSIONS: [&str; 7] = ["flac", "wav", "aiff", "aif", "alac", "ape", "wv"];
+
 pub trait DatabaseExecutor: Send + Sync {}
 
 impl DatabaseExecutor for DatabaseConnection {}
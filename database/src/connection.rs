@@ -183,6 +183,19 @@ pub async fn connect_fake_main_db() -> Result<MainDbConnection> {
     Ok(db)
 }
 
+/// Connects to an empty main database in memory with every migration applied.
+#[cfg(test)]
+pub async fn connect_test_main_db() -> MainDbConnection {
+    let db = Database::connect("sqlite::memory:")
+        .await
+        .expect("Failed to open the test database");
+    initialize_db(&db)
+        .await
+        .expect("Failed to initialize the test database");
+
+    db
+}
+
 const DB_SIZE: usize = 2 * 1024 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
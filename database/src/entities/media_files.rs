@@ -20,6 +20,9 @@ pub struct Model {
     pub cover_art_id: Option<i32>,
     pub sample_rate: i32,
     pub duration: Decimal,
+    pub cue_track: Option<i32>,
+    pub start_offset: Option<Decimal>,
+    pub end_offset: Option<Decimal>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -42,8 +45,12 @@ pub enum Relation {
     MediaFilePlaylists,
     #[sea_orm(has_many = "super::media_file_stats::Entity")]
     MediaFileStats,
+    #[sea_orm(has_many = "super::media_loudness::Entity")]
+    MediaLoudness,
     #[sea_orm(has_many = "super::media_metadata::Entity")]
     MediaMetadata,
+    #[sea_orm(has_many = "super::media_rhythm::Entity")]
+    MediaRhythm,
 }
 
 impl Related<super::media_analysis::Entity> for Entity {
@@ -82,10 +89,22 @@ impl Related<super::media_file_stats::Entity> for Entity {
     }
 }
 
+impl Related<super::media_loudness::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MediaLoudness.def()
+    }
+}
+
 impl Related<super::media_metadata::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::MediaMetadata.def()
     }
 }
 
+impl Related<super::media_rhythm::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MediaRhythm.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
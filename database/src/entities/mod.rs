@@ -4,6 +4,8 @@ pub mod prelude;
 
 pub mod albums;
 pub mod artists;
+pub mod equalizer_preset_bands;
+pub mod equalizer_presets;
 pub mod genres;
 pub mod log;
 pub mod media_analysis;
@@ -14,9 +16,15 @@ pub mod media_file_genres;
 pub mod media_file_playlists;
 pub mod media_file_stats;
 pub mod media_files;
+pub mod media_loudness;
 pub mod media_metadata;
+pub mod media_rhythm;
 pub mod mix_queries;
 pub mod mixes;
+pub mod play_events;
 pub mod playback_queue;
 pub mod playlists;
+pub mod scrobble_queue;
 pub mod search_index;
+pub mod tag_edit_batches;
+pub mod tag_edit_entries;
@@ -2,6 +2,8 @@
 
 pub use super::albums::Entity as Albums;
 pub use super::artists::Entity as Artists;
+pub use super::equalizer_preset_bands::Entity as EqualizerPresetBands;
+pub use super::equalizer_presets::Entity as EqualizerPresets;
 pub use super::genres::Entity as Genres;
 pub use super::log::Entity as Log;
 pub use super::media_analysis::Entity as MediaAnalysis;
@@ -12,9 +14,15 @@ pub use super::media_file_genres::Entity as MediaFileGenres;
 pub use super::media_file_playlists::Entity as MediaFilePlaylists;
 pub use super::media_file_stats::Entity as MediaFileStats;
 pub use super::media_files::Entity as MediaFiles;
+pub use super::media_loudness::Entity as MediaLoudness;
 pub use super::media_metadata::Entity as MediaMetadata;
+pub use super::media_rhythm::Entity as MediaRhythm;
 pub use super::mix_queries::Entity as MixQueries;
 pub use super::mixes::Entity as Mixes;
+pub use super::play_events::Entity as PlayEvents;
 pub use super::playback_queue::Entity as PlaybackQueue;
 pub use super::playlists::Entity as Playlists;
+pub use super::scrobble_queue::Entity as ScrobbleQueue;
 pub use super::search_index::Entity as SearchIndex;
+pub use super::tag_edit_batches::Entity as TagEditBatches;
+pub use super::tag_edit_entries::Entity as TagEditEntries;
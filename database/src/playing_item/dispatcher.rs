@@ -7,7 +7,7 @@ use std::{
 use anyhow::Result;
 use sea_orm::DatabaseConnection;
 
-use playback::player::PlayingItem;
+use playback::{crossfade::SilenceProfile, normalization::ReplayGainInfo, player::PlayingItem};
 
 use super::{
     independent_file::IndependentFileProcessor, library_item::LibraryItemProcessor,
@@ -158,6 +158,48 @@ impl PlayingItemActionDispatcher {
 
         processor.get_cover_art_primary_color(main_db, item).await
     }
+
+    pub async fn get_replay_gain(
+        &self,
+        main_db: &DatabaseConnection,
+        items: &[PlayingItem],
+    ) -> Result<HashMap<PlayingItem, ReplayGainInfo>> {
+        let in_library_results = self
+            .in_library_processor
+            .get_replay_gain(main_db, items)
+            .await?;
+        let independent_results = self
+            .independent_file_processor
+            .get_replay_gain(main_db, items)
+            .await?;
+
+        let mut result_map = HashMap::new();
+        result_map.extend(in_library_results);
+        result_map.extend(independent_results);
+
+        Ok(result_map)
+    }
+
+    pub async fn get_silence_profile(
+        &self,
+        main_db: &DatabaseConnection,
+        items: &[PlayingItem],
+    ) -> Result<HashMap<PlayingItem, SilenceProfile>> {
+        let in_library_results = self
+            .in_library_processor
+            .get_silence_profile(main_db, items)
+            .await?;
+        let independent_results = self
+            .independent_file_processor
+            .get_silence_profile(main_db, items)
+            .await?;
+
+        let mut result_map = HashMap::new();
+        result_map.extend(in_library_results);
+        result_map.extend(independent_results);
+
+        Ok(result_map)
+    }
 }
 
 pub trait HasPlayingItem {
@@ -13,10 +13,13 @@ use metadata::{
     crc::media_crc32,
     describe::{describe_file, get_codec_information_from_path},
     reader::get_metadata,
+    replay_gain::parse_replay_gain,
 };
-use playback::player::PlayingItem;
+use playback::{crossfade::SilenceProfile, normalization::ReplayGainInfo, player::PlayingItem};
 
-use crate::actions::{cover_art::COVER_TEMP_DIR, metadata::extract_number};
+use crate::actions::{
+    cover_art::COVER_TEMP_DIR, loudness::merge_replay_gain, metadata::extract_number,
+};
 
 use super::{MediaFileHandle, PlayingFileMetadataProvider, PlayingItemMetadataSummary};
 
@@ -210,4 +213,39 @@ impl PlayingFileMetadataProvider for IndependentFileProcessor {
             _ => None,
         }
     }
+
+    async fn get_replay_gain(
+        &self,
+        _main_db: &DatabaseConnection,
+        items: &[PlayingItem],
+    ) -> Result<HashMap<PlayingItem, ReplayGainInfo>> {
+        let independent_paths = extract_independent_file_paths(items.to_vec());
+
+        let mut result = HashMap::new();
+
+        for path in independent_paths {
+            let tags = match get_metadata(&path, None) {
+                Ok(metadata) => parse_replay_gain(&metadata),
+                Err(_) => continue,
+            };
+
+            if !tags.is_empty() {
+                result.insert(
+                    PlayingItem::IndependentFile(path),
+                    merge_replay_gain(tags, None, None),
+                );
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn get_silence_profile(
+        &self,
+        _main_db: &DatabaseConnection,
+        _items: &[PlayingItem],
+    ) -> Result<HashMap<PlayingItem, SilenceProfile>> {
+        // Independent files are never analyzed
+        Ok(HashMap::new())
+    }
 }
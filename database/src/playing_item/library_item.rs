@@ -8,13 +8,14 @@ use async_trait::async_trait;
 use dunce::canonicalize;
 use sea_orm::DatabaseConnection;
 
-use playback::player::PlayingItem;
+use playback::{crossfade::SilenceProfile, normalization::ReplayGainInfo, player::PlayingItem};
 
 use crate::actions::{
     cover_art::{
         bake_cover_art_by_file_ids, get_cover_art_id_by_track_id, get_primary_color_by_cover_art_id,
     },
     file::get_files_by_ids,
+    loudness::{get_replay_gain_by_file_ids, get_silence_profile_by_file_ids},
     metadata::get_metadata_summary_by_file_ids,
 };
 
@@ -131,4 +132,32 @@ impl PlayingFileMetadataProvider for LibraryItemProcessor {
             PlayingItem::Unknown => None,
         }
     }
+
+    async fn get_replay_gain(
+        &self,
+        main_db: &DatabaseConnection,
+        items: &[PlayingItem],
+    ) -> Result<HashMap<PlayingItem, ReplayGainInfo>> {
+        let in_library_ids = extract_in_library_ids(items.to_vec());
+
+        Ok(get_replay_gain_by_file_ids(main_db, &in_library_ids)
+            .await?
+            .into_iter()
+            .map(|(id, gain)| (PlayingItem::InLibrary(id), gain))
+            .collect())
+    }
+
+    async fn get_silence_profile(
+        &self,
+        main_db: &DatabaseConnection,
+        items: &[PlayingItem],
+    ) -> Result<HashMap<PlayingItem, SilenceProfile>> {
+        let in_library_ids = extract_in_library_ids(items.to_vec());
+
+        Ok(get_silence_profile_by_file_ids(main_db, &in_library_ids)
+            .await?
+            .into_iter()
+            .map(|(id, profile)| (PlayingItem::InLibrary(id), profile))
+            .collect())
+    }
 }
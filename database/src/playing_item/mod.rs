@@ -6,14 +6,19 @@ use std::{
     collections::HashMap,
     fmt,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use anyhow::Result;
 use async_trait::async_trait;
+use rust_decimal::prelude::ToPrimitive;
 use sea_orm::DatabaseConnection;
 
 use metadata::describe::FileDescription;
-use playback::player::PlayingItem;
+use playback::{
+    crossfade::SilenceProfile, normalization::ReplayGainInfo, player::PlayingItem,
+    range::TrackRange,
+};
 
 use crate::{actions::metadata::MetadataSummary, entities::media_files};
 
@@ -24,16 +29,28 @@ pub struct MediaFileHandle {
     pub directory: String,
     pub extension: String,
     pub last_modified: String,
+    /// Set for tracks that only cover a part of the file, like CUE tracks
+    pub range: Option<TrackRange>,
 }
 
 impl From<media_files::Model> for MediaFileHandle {
     fn from(x: media_files::Model) -> Self {
+        let range = x.start_offset.map(|start| {
+            TrackRange::new(
+                Duration::from_secs_f64(start.to_f64().unwrap_or_default().max(0.0)),
+                x.end_offset
+                    .and_then(|end| end.to_f64())
+                    .map(|end| Duration::from_secs_f64(end.max(0.0))),
+            )
+        });
+
         MediaFileHandle {
             item: PlayingItem::InLibrary(x.id),
             file_name: x.file_name,
             directory: x.directory,
             extension: x.extension,
             last_modified: x.last_modified,
+            range,
         }
     }
 }
@@ -46,6 +63,7 @@ impl From<FileDescription> for MediaFileHandle {
             directory: x.directory,
             extension: x.extension,
             last_modified: x.last_modified,
+            range: None,
         }
     }
 }
@@ -128,4 +146,16 @@ pub trait PlayingFileMetadataProvider {
         main_db: &DatabaseConnection,
         item: &PlayingItem,
     ) -> Option<i32>;
+
+    async fn get_replay_gain(
+        &self,
+        main_db: &DatabaseConnection,
+        items: &[PlayingItem],
+    ) -> Result<HashMap<PlayingItem, ReplayGainInfo>>;
+
+    async fn get_silence_profile(
+        &self,
+        main_db: &DatabaseConnection,
+        items: &[PlayingItem],
+    ) -> Result<HashMap<PlayingItem, SilenceProfile>>;
 }
@@ -25,6 +25,59 @@ pub enum UserStatus {
     Blocked,
 }
 
+/// Defines what a remote client may ask the server to do.
+///
+/// Every protocol request requires one capability, a client may send it if
+/// the role of its user grants that capability.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Reading the library, playlists, lyrics and other data.
+    Browse,
+    /// Changing what the server plays, including its queue.
+    ControlPlayback,
+    /// Changing the library, such as scanning, editing tags or playlists.
+    EditLibrary,
+    /// Managing the server itself, its clients and accounts.
+    Admin,
+}
+
+/// Defines the role of an approved user.
+///
+/// Each role grants the capabilities of the roles before it, so a
+/// `Controller` can browse the library and control playback but can not
+/// change the library.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UserRole {
+    /// Can only browse the library.
+    Viewer,
+    /// Can browse the library and control playback.
+    Controller,
+    /// Can also change the library.
+    Editor,
+    /// Has full access.
+    Admin,
+}
+
+impl UserRole {
+    /// Checks whether the role grants a capability.
+    pub fn allows(&self, capability: Capability) -> bool {
+        let required = match capability {
+            Capability::Browse => UserRole::Viewer,
+            Capability::ControlPlayback => UserRole::Controller,
+            Capability::EditLibrary => UserRole::Editor,
+            Capability::Admin => UserRole::Admin,
+        };
+
+        *self >= required
+    }
+}
+
+// Users stored before roles existed get the role of new users, admins
+// promote them as needed
+fn get_legacy_role() -> UserRole {
+    UserRole::Controller
+}
+
 // Helper function to get current time, used as default for add_time during deserialization
 fn get_current_time() -> SystemTime {
     SystemTime::now()
@@ -49,6 +102,12 @@ pub struct User {
     device_type: DeviceType,
     /// Current status of the user in the permission system (`Approved`, `Pending`, `Blocked`).
     pub status: UserStatus,
+    /// Role of the user, deciding which requests an approved user may send.
+    #[serde(default = "get_legacy_role")]
+    pub role: UserRole,
+    /// Highest bitrate in kbps the user may stream transcoded audio at, `None` for no limit.
+    #[serde(default)]
+    pub max_bitrate: Option<u32>,
     /// The timestamp of the time when user added.
     #[serde(default = "get_current_time")]
     pub add_time: SystemTime,
@@ -70,6 +129,10 @@ pub struct UserSummary {
     pub device_type: DeviceType,
     /// Current status of the user.
     pub status: UserStatus,
+    /// Role of the user.
+    pub role: UserRole,
+    /// Highest bitrate in kbps the user may stream at.
+    pub max_bitrate: Option<u32>,
     /// User adding time
     pub add_time: SystemTime,
 }
@@ -172,6 +235,8 @@ impl PermissionManager {
                 device_model: user.device_model.clone(),
                 device_type: user.device_type,
                 status: user.status.clone(),
+                role: user.role,
+                max_bitrate: user.max_bitrate,
                 add_time: user.add_time,
             })
             .collect() // Collect UserSummary into a Vec
@@ -239,6 +304,8 @@ impl PermissionManager {
                         device_model,
                         device_type,
                         status: UserStatus::Pending, // Default status is Pending for new users
+                        role: UserRole::Controller, // New users can not change the library until promoted
+                        max_bitrate: None,
                         add_time: SystemTime::now(), // Set current time when adding user
                     },
                 );
@@ -318,6 +385,87 @@ impl PermissionManager {
             .await
     }
 
+    /// Changes the role of a user in the permission system.
+    ///
+    /// This method updates the `UserRole` of a user identified by their fingerprint.
+    /// The new role applies to the next request the user sends, including on open connections.
+    ///
+    /// # Arguments
+    /// * `fingerprint` - The fingerprint of the user whose role is to be changed.
+    /// * `new_role` - The new `UserRole` to set for the user.
+    ///
+    /// # Returns
+    /// `Result<(), PermissionError>` - A `Result` indicating success or failure.
+    ///
+    /// # Errors
+    /// Returns `PermissionError::UserNotFound` if no user with the given fingerprint is found.
+    /// Returns `PermissionError::Persistence` if there is an issue updating the persistent storage.
+    pub async fn change_user_role(
+        &self,
+        fingerprint: &str,
+        new_role: UserRole,
+    ) -> Result<(), PermissionError> {
+        self.storage
+            .update(|mut permissions| async move {
+                let user = permissions
+                    .users
+                    .get_mut(fingerprint)
+                    .ok_or(PermissionError::UserNotFound)?;
+                user.role = new_role;
+                Ok((permissions, ()))
+            })
+            .await
+    }
+
+    /// Changes the highest bitrate a user may stream transcoded audio at.
+    ///
+    /// # Arguments
+    /// * `fingerprint` - The fingerprint of the user whose limit is to be changed.
+    /// * `max_bitrate` - The limit in kbps, or `None` to remove it.
+    ///
+    /// # Returns
+    /// `Result<(), PermissionError>` - A `Result` indicating success or failure.
+    ///
+    /// # Errors
+    /// Returns `PermissionError::UserNotFound` if no user with the given fingerprint is found.
+    /// Returns `PermissionError::Persistence` if there is an issue updating the persistent storage.
+    pub async fn change_user_max_bitrate(
+        &self,
+        fingerprint: &str,
+        max_bitrate: Option<u32>,
+    ) -> Result<(), PermissionError> {
+        self.storage
+            .update(|mut permissions| async move {
+                let user = permissions
+                    .users
+                    .get_mut(fingerprint)
+                    .ok_or(PermissionError::UserNotFound)?;
+                user.max_bitrate = max_bitrate;
+                Ok((permissions, ()))
+            })
+            .await
+    }
+
+    /// Checks whether a user may use a capability.
+    ///
+    /// Only approved users are granted capabilities, so users blocked or removed while
+    /// connected lose access with their next request.
+    ///
+    /// # Arguments
+    /// * `fingerprint` - The fingerprint of the user sending a request.
+    /// * `capability` - The `Capability` the request requires.
+    ///
+    /// # Returns
+    /// `bool` - `true` if the user is approved and its role grants the capability.
+    pub async fn is_allowed(&self, fingerprint: &str, capability: Capability) -> bool {
+        self.storage
+            .read()
+            .await
+            .users
+            .get(fingerprint)
+            .is_some_and(|user| user.status == UserStatus::Approved && user.role.allows(capability))
+    }
+
     /// Removes a user from the permission system.
     ///
     /// This method deletes a user from the permission list based on their fingerprint.
@@ -368,6 +516,8 @@ impl PermissionManager {
                 device_model: user.device_model.clone(),
                 device_type: user.device_type,
                 status: user.status.clone(),
+                role: user.role,
+                max_bitrate: user.max_bitrate,
                 add_time: user.add_time,
             })
             .collect() // Collect UserSummary into a Vec
@@ -404,3 +554,57 @@ impl PermissionManager {
         self.request_sender.subscribe()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_allows() {
+        let capabilities = [
+            Capability::Browse,
+            Capability::ControlPlayback,
+            Capability::EditLibrary,
+            Capability::Admin,
+        ];
+        let roles = [
+            (UserRole::Viewer, 1),
+            (UserRole::Controller, 2),
+            (UserRole::Editor, 3),
+            (UserRole::Admin, 4),
+        ];
+
+        for (role, granted) in roles {
+            for (index, capability) in capabilities.iter().enumerate() {
+                assert_eq!(
+                    role.allows(*capability),
+                    index < granted,
+                    "{:?} {:?}",
+                    role,
+                    capability
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_legacy_users_are_controllers() {
+        let permissions: PermissionList = toml::from_str(
+            r#"
+            [users.abc]
+            public_key = "key"
+            fingerprint = "abc"
+            alias = "Phone"
+            device_model = "Pixel"
+            device_type = "mobile"
+            status = "Approved"
+            "#,
+        )
+        .unwrap();
+
+        let user = &permissions.users["abc"];
+        assert_eq!(user.role, UserRole::Controller);
+        assert_eq!(user.max_bitrate, None);
+        assert!(!user.role.allows(Capability::EditLibrary));
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use lazy_static::lazy_static;
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::{Descriptor, Hint, ProbeResult, QueryDescriptor};
+use symphonia::default::formats::*;
+
+/// Extensions registered by containers that mostly carry video, they are not
+/// treated as music.
+const VIDEO_EXTENSIONS: [&str; 7] = ["mp4", "m4v", "mov", "webm", "mkv", "ogv", "ogm"];
+
+/// Extensions of containers symphonia reads, but does not register.
+const EXTENSION_ALIASES: [&str; 3] = ["mka", "alac", "vorbis"];
+
+/// Containers that always hold a codec symphonia decodes, files using them
+/// are trusted by their extension instead of being probed.
+const SINGLE_CODEC_EXTENSIONS: [&str; 9] = [
+    "aac", "aif", "aifc", "aiff", "flac", "mp1", "mp2", "mp3", "wav",
+];
+
+/// Audio formats symphonia can not decode. Files using them are reported
+/// instead of being skipped silently.
+const UNDECODABLE_EXTENSIONS: [&str; 9] =
+    ["ape", "dff", "dsf", "mpc", "ofr", "tak", "tta", "wma", "wv"];
+
+lazy_static! {
+    static ref REGISTRY: FormatRegistry = FormatRegistry::new();
+}
+
+/// Describes whether a file can be scanned, analyzed and played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatSupport {
+    Supported,
+    /// The file is audio, but can not be decoded, the reason is attached
+    Unsupported(String),
+    /// The file is not audio at all
+    NotAudio,
+}
+
+impl fmt::Display for FormatSupport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatSupport::Supported => write!(f, "Supported"),
+            FormatSupport::Unsupported(reason) => write!(f, "Unsupported: {}", reason),
+            FormatSupport::NotAudio => write!(f, "Not audio"),
+        }
+    }
+}
+
+/// The audio formats known to the library, built from the format readers
+/// registered in symphonia.
+pub struct FormatRegistry {
+    extensions: BTreeSet<String>,
+    undecodable_extensions: BTreeSet<String>,
+}
+
+fn descriptor_extensions(descriptors: &[Descriptor]) -> impl Iterator<Item = &'static str> + '_ {
+    descriptors
+        .iter()
+        .flat_map(|descriptor| descriptor.extensions.iter().copied())
+}
+
+fn extension_of(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+}
+
+impl FormatRegistry {
+    fn new() -> Self {
+        let mut extensions: BTreeSet<String> = BTreeSet::new();
+
+        for descriptors in [
+            AdtsReader::query(),
+            CafReader::query(),
+            FlacReader::query(),
+            IsoMp4Reader::query(),
+            MpaReader::query(),
+            AiffReader::query(),
+            WavReader::query(),
+            OggReader::query(),
+            MkvReader::query(),
+        ] {
+            extensions.extend(descriptor_extensions(descriptors).map(str::to_lowercase));
+        }
+
+        for ext in VIDEO_EXTENSIONS {
+            extensions.remove(ext);
+        }
+        extensions.extend(EXTENSION_ALIASES.iter().map(|ext| ext.to_string()));
+
+        FormatRegistry {
+            extensions,
+            undecodable_extensions: UNDECODABLE_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+        }
+    }
+
+    /// Extensions of the formats that can be decoded, in lower case.
+    pub fn extensions(&self) -> impl Iterator<Item = &str> {
+        self.extensions.iter().map(String::as_str)
+    }
+
+    /// Returns true for files that look like audio by their extension,
+    /// including the ones that can not be decoded.
+    pub fn is_audio_path(&self, path: &Path) -> bool {
+        match extension_of(path) {
+            Some(ext) => {
+                self.extensions.contains(&ext) || self.undecodable_extensions.contains(&ext)
+            }
+            None => false,
+        }
+    }
+
+    /// Opens the file and probes its container, the extension is only a hint.
+    pub fn probe(&self, path: &Path) -> Result<ProbeResult> {
+        let src = File::open(path).with_context(|| format!("Failed to open media: {:?}", path))?;
+        let mss = MediaSourceStream::new(Box::new(src), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(ext) = extension_of(path) {
+            hint.with_extension(&ext);
+        }
+
+        let fmt_opts: FormatOptions = Default::default();
+        let meta_opts: MetadataOptions = Default::default();
+
+        symphonia::default::get_probe()
+            .format(&hint, mss, &fmt_opts, &meta_opts)
+            .map_err(|e| anyhow!("Unsupported format: {}", e))
+    }
+
+    /// Checks whether the file holds an audio track that can be decoded.
+    ///
+    /// Only containers that can carry several codecs are probed, the others
+    /// are told apart by their extension, so scans don't open every file.
+    pub fn check(&self, path: &Path) -> FormatSupport {
+        let Some(ext) = extension_of(path) else {
+            return FormatSupport::NotAudio;
+        };
+
+        if self.undecodable_extensions.contains(&ext) {
+            return FormatSupport::Unsupported(format!("No decoder for .{} files", ext));
+        }
+
+        if !self.extensions.contains(&ext) {
+            return FormatSupport::NotAudio;
+        }
+
+        if SINGLE_CODEC_EXTENSIONS.contains(&ext.as_str()) {
+            return FormatSupport::Supported;
+        }
+
+        match self.decodable_track(path) {
+            Ok(_) => FormatSupport::Supported,
+            Err(e) => FormatSupport::Unsupported(format!("{:#}", e)),
+        }
+    }
+
+    fn decodable_track(&self, path: &Path) -> Result<()> {
+        let probed = self.probe(path)?;
+
+        let track = probed
+            .format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .with_context(|| "No audio track found")?;
+
+        if symphonia::default::get_codecs()
+            .get_codec(track.codec_params.codec)
+            .is_none()
+        {
+            bail!("No decoder for codec {}", track.codec_params.codec);
+        }
+
+        Ok(())
+    }
+}
+
+/// The registry shared by the scanner, the analysis and the player.
+pub fn registry() -> &'static FormatRegistry {
+    &REGISTRY
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_extensions() {
+        let extensions: Vec<&str> = registry().extensions().collect();
+
+        for ext in ["caf", "mka", "flac", "m4a", "ogg"] {
+            assert!(extensions.contains(&ext), "missing .{}", ext);
+        }
+        for ext in VIDEO_EXTENSIONS {
+            assert!(!extensions.contains(&ext), "video .{} listed", ext);
+        }
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn test_check_probes_containers() {
+        assert_eq!(
+            registry().check(&fixture("sine.caf")),
+            FormatSupport::Supported
+        );
+        assert_eq!(
+            registry().check(&fixture("sine.mka")),
+            FormatSupport::Supported
+        );
+        assert!(matches!(
+            registry().check(Path::new("missing.mka")),
+            FormatSupport::Unsupported(_)
+        ));
+        assert!(matches!(
+            registry().check(Path::new("missing.wv")),
+            FormatSupport::Unsupported(_)
+        ));
+        assert_eq!(
+            registry().check(Path::new("cover.jpg")),
+            FormatSupport::NotAudio
+        );
+        assert_eq!(
+            registry().check(Path::new("video.mkv")),
+            FormatSupport::NotAudio
+        );
+    }
+
+    #[test]
+    fn test_check_mislabeled_files() {
+        let directory = tempfile::tempdir().unwrap();
+
+        // Containers are told apart by their content, not their extension
+        let caf_as_mka = directory.path().join("sine.mka");
+        fs::copy(fixture("sine.caf"), &caf_as_mka).unwrap();
+        assert_eq!(registry().check(&caf_as_mka), FormatSupport::Supported);
+
+        let mka_as_ogg = directory.path().join("sine.ogg");
+        fs::copy(fixture("sine.mka"), &mka_as_ogg).unwrap();
+        assert_eq!(registry().check(&mka_as_ogg), FormatSupport::Supported);
+
+        let text_as_caf = directory.path().join("notes.caf");
+        fs::write(&text_as_caf, "Not audio at all").unwrap();
+        assert!(matches!(
+            registry().check(&text_as_caf),
+            FormatSupport::Unsupported(_)
+        ));
+
+        // Single codec containers are trusted by their extension
+        let text_as_flac = directory.path().join("notes.flac");
+        fs::write(&text_as_flac, "Not audio at all").unwrap();
+        assert_eq!(registry().check(&text_as_flac), FormatSupport::Supported);
+    }
+}
@@ -48,6 +48,28 @@ const kMiddleClickActionKey = 'middle_click_action';
 /// under specific conditions.
 const kAdaptiveSwitchingKey = 'adaptive_switching';
 
+/// This key stores the loudness normalization mode used by the player.
+/// 0 disables normalization, 1 applies track gain and 2 applies album gain.
+const kNormalizationModeKey = 'normalization_mode';
+
+/// This key stores the crossfade duration in milliseconds, 0 disables
+/// crossfading and keeps the gapless transition between tracks.
+const kCrossfadeDurationKey = 'crossfade_duration';
+
+/// This key stores the crossfade curve: 0 for linear, 1 for equal power and
+/// 2 for S-curve.
+const kCrossfadeCurveKey = 'crossfade_curve';
+
+/// This key stores whether the equalizer is applied to the output stream.
+const kEqualizerEnabledKey = 'equalizer_enabled';
+
+/// This key stores the equalizer preamp in dB.
+const kEqualizerPreampKey = 'equalizer_preamp';
+
+/// This key stores the equalizer bands as a JSON list of objects with the
+/// `filterType`, `frequency`, `gain` and `q` fields.
+const kEqualizerBandsKey = 'equalizer_bands';
+
 /// This key is used to store the user's preference for the color mode of the
 /// application. This can include options such as "system", "dark", or "light".
 const kColorModeKey = 'color_mode';
@@ -28,6 +28,9 @@ import 'utils/update_color_mode.dart';
 import 'utils/theme_color_manager.dart';
 import 'utils/storage_key_manager.dart';
 import 'utils/api/set_adaptive_switching_enabled.dart';
+import 'utils/api/set_normalization_mode.dart';
+import 'utils/api/set_crossfade.dart';
+import 'utils/api/set_equalizer.dart';
 import 'utils/api/operate_playback_with_mix_query.dart';
 import 'utils/file_storage/mac_secure_manager.dart';
 import 'utils/macos_window_control_button_manager.dart';
@@ -217,6 +220,9 @@ void main(List<String> arguments) async {
   }
 
   setAdaptiveSwitchingEnabled();
+  setNormalizationMode();
+  setCrossfade();
+  setEqualizer();
 
   mainLoop(licenseProvider);
   if (isDesktop && !Platform.isMacOS) {
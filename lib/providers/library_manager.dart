@@ -57,6 +57,7 @@ class LibraryManagerProvider with ChangeNotifier {
   final Map<String, ScanTaskProgress> _scanTasks = {};
   StreamSubscription? _scanProgressSubscription;
   StreamSubscription? _scanResultSubscription;
+  StreamSubscription? _libraryFilesChangedSubscription;
   StreamSubscription? _analyzeProgressSubscription;
   StreamSubscription? _analyzeResultSubscription;
   StreamSubscription? _cancelTaskSubscription;
@@ -107,6 +108,12 @@ class LibraryManagerProvider with ChangeNotifier {
       CollectionCache().clearAll();
     });
 
+    _libraryFilesChangedSubscription =
+        LibraryFilesChanged.rustSignalStream.listen((event) {
+      CollectionCache().clearAll();
+      notifyListeners();
+    });
+
     _analyzeProgressSubscription =
         AnalyzeAudioLibraryProgress.rustSignalStream.listen((event) {
       final analyzeProgress = event.message;
@@ -298,6 +305,7 @@ class LibraryManagerProvider with ChangeNotifier {
   void dispose() {
     _scanProgressSubscription?.cancel();
     _scanResultSubscription?.cancel();
+    _libraryFilesChangedSubscription?.cancel();
     _analyzeProgressSubscription?.cancel();
     _analyzeResultSubscription?.cancel();
     _cancelTaskSubscription?.cancel();
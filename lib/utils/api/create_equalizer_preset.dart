@@ -0,0 +1,16 @@
+import '../../messages/all.dart';
+
+Future<EqualizerPreset> createEqualizerPreset(
+  String name,
+  double preamp,
+  List<EqualizerBand> bands,
+) async {
+  final createRequest =
+      CreateEqualizerPresetRequest(name: name, preamp: preamp, bands: bands);
+  createRequest.sendSignalToRust(); // GENERATED
+
+  final rustSignal = await CreateEqualizerPresetResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  return response.preset;
+}
@@ -20,5 +20,9 @@ Future<Mix> createMix(
   final rustSignal = await CreateMixResponse.rustSignalStream.first;
   final response = rustSignal.message;
 
+  if (response.hasError()) {
+    throw response.error;
+  }
+
   return response.mix;
 }
@@ -0,0 +1,22 @@
+import '../../messages/all.dart';
+
+Future<int> exportPlaylist(
+  int playlistId,
+  String path, {
+  bool relativePaths = false,
+}) async {
+  ExportPlaylistRequest(
+    playlistId: playlistId,
+    path: path,
+    relativePaths: relativePaths,
+  ).sendSignalToRust();
+
+  final rustSignal = await ExportPlaylistResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  if (!response.success) {
+    throw response.error;
+  }
+
+  return response.exportedCount;
+}
@@ -0,0 +1,14 @@
+import '../../messages/all.dart';
+
+Future<List<DuplicateFileGroup>> findDuplicateFiles() async {
+  FindDuplicateFilesRequest().sendSignalToRust(); // GENERATED
+
+  final rustSignal = await FindDuplicateFilesResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  if (!response.success) {
+    throw response.error;
+  }
+
+  return response.groups;
+}
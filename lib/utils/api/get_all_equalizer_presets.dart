@@ -0,0 +1,12 @@
+import '../../messages/all.dart';
+
+Future<List<EqualizerPreset>> getAllEqualizerPresets() async {
+  final fetchRequest = FetchAllEqualizerPresetsRequest();
+  fetchRequest.sendSignalToRust(); // GENERATED
+
+  final rustSignal =
+      await FetchAllEqualizerPresetsResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  return response.presets;
+}
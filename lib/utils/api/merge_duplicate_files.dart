@@ -0,0 +1,15 @@
+import '../../messages/all.dart';
+
+Future<void> mergeDuplicateFiles(int keeperId, List<int> duplicateIds) async {
+  MergeDuplicateFilesRequest(
+    keeperId: keeperId,
+    duplicateIds: duplicateIds,
+  ).sendSignalToRust(); // GENERATED
+
+  final rustSignal = await MergeDuplicateFilesResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  if (!response.success) {
+    throw response.error;
+  }
+}
@@ -17,6 +17,10 @@ Future<List<InternalMediaFile>> queryMixTracks(
 
   final response = (await MixQueryResponse.rustSignalStream.first).message;
 
+  if (response.hasError()) {
+    throw response.error;
+  }
+
   return response.files
       .map(
         (x) => InternalMediaFile(
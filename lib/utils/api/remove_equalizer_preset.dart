@@ -0,0 +1,11 @@
+import '../../messages/all.dart';
+
+Future<bool> removeEqualizerPreset(int presetId) async {
+  final removeRequest = RemoveEqualizerPresetRequest(presetId: presetId);
+  removeRequest.sendSignalToRust(); // GENERATED
+
+  final rustSignal = await RemoveEqualizerPresetResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  return response.success;
+}
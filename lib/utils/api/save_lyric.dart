@@ -0,0 +1,25 @@
+import '../../messages/all.dart';
+import '../playing_item.dart';
+
+Future<String> saveLyric(
+  PlayingItem item, {
+  List<LyricContentLine> lines = const [],
+  String format = '',
+  int offset = 0,
+}) async {
+  SaveLyricRequest(
+    item: item.toRequest(),
+    lines: lines,
+    format: format,
+    offset: offset,
+  ).sendSignalToRust();
+
+  final rustSignal = await SaveLyricResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  if (!response.success) {
+    throw response.error;
+  }
+
+  return response.path;
+}
@@ -0,0 +1,12 @@
+import '../../messages/all.dart';
+import '../../constants/configurations.dart';
+
+import '../settings_manager.dart';
+
+void setCrossfade() async {
+  final durationMs =
+      await SettingsManager().getValue<int?>(kCrossfadeDurationKey) ?? 0;
+  final curve = await SettingsManager().getValue<int?>(kCrossfadeCurveKey) ?? 1;
+
+  SetCrossfadeRequest(durationMs: durationMs, curve: curve).sendSignalToRust();
+}
@@ -0,0 +1,29 @@
+import 'dart:convert';
+
+import '../../messages/all.dart';
+import '../../constants/configurations.dart';
+
+import '../settings_manager.dart';
+
+void setEqualizer() async {
+  final enabled =
+      await SettingsManager().getValue<bool?>(kEqualizerEnabledKey) ?? false;
+  final preamp =
+      await SettingsManager().getValue<double?>(kEqualizerPreampKey) ?? 0.0;
+  final bandsJson = await SettingsManager().getValue<String?>(kEqualizerBandsKey);
+
+  final List<EqualizerBand> bands = [];
+  if (bandsJson != null) {
+    for (final band in jsonDecode(bandsJson) as List<dynamic>) {
+      bands.add(EqualizerBand(
+        filterType: band['filterType'] as int,
+        frequency: (band['frequency'] as num).toDouble(),
+        gain: (band['gain'] as num).toDouble(),
+        q: (band['q'] as num).toDouble(),
+      ));
+    }
+  }
+
+  SetEqualizerRequest(enabled: enabled, preamp: preamp, bands: bands)
+      .sendSignalToRust();
+}
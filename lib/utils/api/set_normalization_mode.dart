@@ -0,0 +1,11 @@
+import '../../messages/all.dart';
+import '../../constants/configurations.dart';
+
+import '../settings_manager.dart';
+
+void setNormalizationMode() async {
+  final mode =
+      await SettingsManager().getValue<int?>(kNormalizationModeKey) ?? 0;
+
+  SetNormalizationModeRequest(mode: mode).sendSignalToRust();
+}
@@ -0,0 +1,21 @@
+import '../../messages/all.dart';
+
+Future<bool> updateClientMaxBitrate(
+  String fingerprint,
+  int? maxBitrate,
+) async {
+  UpdateClientMaxBitrateRequest(
+    fingerprint: fingerprint,
+    maxBitrate: maxBitrate,
+  ).sendSignalToRust();
+
+  final rustSignal =
+      await UpdateClientMaxBitrateResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  if (!response.success) {
+    throw response.error;
+  }
+
+  return response.success;
+}
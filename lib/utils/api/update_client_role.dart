@@ -0,0 +1,20 @@
+import '../../messages/all.dart';
+
+Future<bool> updateClientRole(
+  String fingerprint,
+  ClientRole role,
+) async {
+  UpdateClientRoleRequest(
+    fingerprint: fingerprint,
+    role: role,
+  ).sendSignalToRust();
+
+  final rustSignal = await UpdateClientRoleResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  if (!response.success) {
+    throw response.error;
+  }
+
+  return response.success;
+}
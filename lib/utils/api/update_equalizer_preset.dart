@@ -0,0 +1,21 @@
+import '../../messages/all.dart';
+
+Future<EqualizerPreset> updateEqualizerPreset(
+  int presetId,
+  String name,
+  double preamp,
+  List<EqualizerBand> bands,
+) async {
+  final updateRequest = UpdateEqualizerPresetRequest(
+    presetId: presetId,
+    name: name,
+    preamp: preamp,
+    bands: bands,
+  );
+  updateRequest.sendSignalToRust(); // GENERATED
+
+  final rustSignal = await UpdateEqualizerPresetResponse.rustSignalStream.first;
+  final response = rustSignal.message;
+
+  return response.preset;
+}
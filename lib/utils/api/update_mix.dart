@@ -22,5 +22,9 @@ Future<Mix> updateMix(
   final rustSignal = await UpdateMixResponse.rustSignalStream.first;
   final response = rustSignal.message;
 
+  if (response.hasError()) {
+    throw response.error;
+  }
+
   return response.mix;
 }
@@ -8,6 +8,7 @@ import 'package:material_symbols_icons/symbols.dart';
 
 import '../../utils/api/get_all_mixes.dart';
 import '../../utils/api/add_item_to_mix.dart';
+import '../../utils/api/export_playlist.dart';
 import '../../utils/dialogs/mix/mix_studio.dart';
 import '../../utils/dialogs/mix/create_edit_mix.dart';
 import '../../utils/dialogs/mix/remove_mix_dialog.dart';
@@ -282,16 +283,23 @@ MenuFlyout buildLargeScreenCollectionItemContextMenu(
               final FileSaveLocation? path = await getSaveLocation(
                 suggestedName: '$title.m3u8',
                 initialDirectory: appDocumentsDir.path,
-                acceptedTypeGroups: const [
+                acceptedTypeGroups: [
                   XTypeGroup(
                     label: 'playlist',
-                    extensions: <String>['m3u8'],
+                    extensions: type == CollectionType.Playlist
+                        ? <String>['m3u8', 'pls', 'xspf', 'jspf', 'cue']
+                        : <String>['m3u8'],
                   )
                 ],
               );
 
               if (path == null) return;
 
+              if (type == CollectionType.Playlist) {
+                await exportPlaylist(id, path.path);
+                return;
+              }
+
               final playlist = await buildM3u8(type, id);
 
               final file = File(path.path);
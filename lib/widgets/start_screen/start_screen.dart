@@ -198,7 +198,7 @@ class StartScreenImplementationState extends State<StartScreenImplementation>
                 onPressed: () async {
                   const XTypeGroup typeGroup = XTypeGroup(
                     label: 'playlist',
-                    extensions: <String>['m3u', 'm3u8'],
+                    extensions: <String>['m3u', 'm3u8', 'pls', 'xspf', 'jspf'],
                   );
                   final XFile? file = await openFile(
                     acceptedTypeGroups: <XTypeGroup>[typeGroup],
@@ -5,3 +5,4 @@ pub mod ttml;
 pub mod types;
 pub mod utils;
 pub mod vtt;
+pub mod writer;
@@ -65,24 +65,12 @@ pub fn parse_lrc(content: &str) -> Result<LyricFile> {
                 let word_time_tags = if remaining_content.contains('<') {
                     parse_enhanced_lrc(remaining_content)?
                 } else {
-                    vec![(
-                        start_time.clone(),
-                        TimeTag {
-                            minutes: 9999,
-                            seconds: 0,
-                            milliseconds: 0,
-                        },
-                        text.to_string(),
-                    )]
+                    vec![(start_time.clone(), TimeTag::unbounded(), text.to_string())]
                 };
 
                 lrc.lyrics.push(LyricLine {
                     start_time: start_time.clone(),
-                    end_time: TimeTag {
-                        minutes: 9999,
-                        seconds: 0,
-                        milliseconds: 0,
-                    }, // Temporary, will be updated in next iteration
+                    end_time: TimeTag::unbounded(), // Temporary, will be updated in next iteration
                     voice_type,
                     text,
                     word_time_tags,
@@ -105,11 +93,7 @@ fn parse_enhanced_lrc(content: &str) -> Result<Vec<(TimeTag, TimeTag, String)>>
             let start_time = TimeTag::from_str(time_tag_str)?;
 
             // Determine end_time using the previous start_time
-            let end_time = TimeTag {
-                minutes: 9999,
-                seconds: 0,
-                milliseconds: 0,
-            };
+            let end_time = TimeTag::unbounded();
 
             // Update previous line's end_time
             if let Some(last_line) = word_time_tags.last_mut() {
@@ -136,6 +120,76 @@ fn parse_enhanced_lrc(content: &str) -> Result<Vec<(TimeTag, TimeTag, String)>>
     Ok(word_time_tags)
 }
 
+/// Formats a time as `[mm:ss.xx]`, or `<mm:ss.xx>` for word time tags.
+fn format_time_tag(time: &TimeTag, word: bool) -> String {
+    // Normalized, as some parsers keep more than a second of milliseconds
+    let time = TimeTag::from_milliseconds(time.total_milliseconds()).to_string();
+
+    if word {
+        format!("<{}>", &time[1..time.len() - 1])
+    } else {
+        time
+    }
+}
+
+/// Whether a line has word timings beyond the single word `parse_lrc`
+/// gives plain lines.
+fn has_word_timings(line: &LyricLine) -> bool {
+    match line.word_time_tags.as_slice() {
+        [] => false,
+        [(_, _, word)] => *word != line.text,
+        _ => true,
+    }
+}
+
+/// Writes lyrics as LRC, with enhanced word time tags for lines that have
+/// word timings and `M:`, `F:` and `D:` prefixes for voices.
+///
+/// LRC lines end where the next one starts, so a gap before the next line
+/// or after the last one is written as an empty line.
+pub fn write_lrc(lrc: &LyricFile) -> String {
+    let mut output = String::new();
+
+    let mut keys: Vec<&String> = lrc.metadata.keys().collect();
+    keys.sort();
+    for key in keys {
+        output.push_str(&format!("[{}:{}]\n", key, lrc.metadata[key]));
+    }
+
+    for (index, line) in lrc.lyrics.iter().enumerate() {
+        output.push_str(&format_time_tag(&line.start_time, false));
+
+        match line.voice_type {
+            VoiceType::Male => output.push_str("M: "),
+            VoiceType::Female => output.push_str("F: "),
+            VoiceType::Duet => output.push_str("D: "),
+            VoiceType::Default => {}
+        }
+
+        if has_word_timings(line) {
+            for (start_time, _, word) in &line.word_time_tags {
+                output.push_str(&format_time_tag(start_time, true));
+                output.push_str(word);
+            }
+        } else {
+            output.push_str(&line.text);
+        }
+        output.push('\n');
+
+        if line.end_time.is_unbounded() {
+            continue;
+        }
+
+        let next_start = lrc.lyrics.get(index + 1).map(|x| &x.start_time);
+        if next_start != Some(&line.end_time) {
+            output.push_str(&format_time_tag(&line.end_time, false));
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
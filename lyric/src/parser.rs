@@ -6,32 +6,48 @@ use std::{
 
 use anyhow::Result;
 
-use crate::{lrc::parse_lrc, srt::parse_srt, ttml::parse_ttml, types::LyricFile, vtt::parse_vtt};
-
-pub fn parse_audio_lyrics(path: PathBuf) -> Option<Result<LyricFile>> {
-    // Try to find and parse the .ttml file
-    if let Some(lyric) = parse_lyrics_with_extension(&path, "ttml", parse_ttml) {
-        return Some(lyric);
-    }
+use crate::{
+    lrc::parse_lrc,
+    srt::parse_srt,
+    ttml::parse_ttml,
+    types::{LyricFile, LyricFormat},
+    vtt::parse_vtt,
+};
 
-    // Try to find and parse the .lrc file
-    if let Some(lyric) = parse_lyrics_with_extension(&path, "lrc", parse_lrc) {
-        return Some(lyric);
-    }
+/// Sidecar lyric files, in the order they are looked up.
+pub const LYRIC_EXTENSIONS: [(&str, LyricFormat); 5] = [
+    ("ttml", LyricFormat::Ttml),
+    ("lrc", LyricFormat::Lrc),
+    ("lrcx", LyricFormat::Lrc),
+    ("vtt", LyricFormat::Vtt),
+    ("srt", LyricFormat::Srt),
+];
 
-    // Try to find and parse the .lrc file
-    if let Some(lyric) = parse_lyrics_with_extension(&path, "lrcx", parse_lrc) {
-        return Some(lyric);
+pub fn parse_lyrics(format: LyricFormat, content: &str) -> Result<LyricFile> {
+    match format {
+        LyricFormat::Lrc => parse_lrc(content),
+        LyricFormat::Ttml => parse_ttml(content),
+        LyricFormat::Vtt => parse_vtt(content),
+        LyricFormat::Srt => parse_srt(content),
     }
+}
 
-    // Try to find and parse the .vtt file
-    if let Some(lyric) = parse_lyrics_with_extension(&path, "vtt", parse_vtt) {
-        return Some(lyric);
-    }
+/// Finds the sidecar lyric file of an audio file.
+pub fn find_audio_lyrics(path: &Path) -> Option<(PathBuf, LyricFormat)> {
+    LYRIC_EXTENSIONS.iter().find_map(|(extension, format)| {
+        let mut file_path = path.to_path_buf();
+        file_path.set_extension(extension);
+        file_path.exists().then_some((file_path, *format))
+    })
+}
 
-    // Try to find and parse the .srt file
-    if let Some(lyric) = parse_lyrics_with_extension(&path, "srt", parse_srt) {
-        return Some(lyric);
+pub fn parse_audio_lyrics(path: PathBuf) -> Option<Result<LyricFile>> {
+    for (extension, format) in LYRIC_EXTENSIONS {
+        if let Some(lyric) =
+            parse_lyrics_with_extension(&path, extension, |x| parse_lyrics(format, x))
+        {
+            return Some(lyric);
+        }
     }
 
     None
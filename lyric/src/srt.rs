@@ -3,7 +3,10 @@ use subtp::srt::{SrtTimestamp, SubRip};
 
 use crate::{
     types::{LyricFile, LyricLine, TimeTag, VoiceType},
-    utils::parse_word_time_tags,
+    utils::{
+        format_clock_time, join_word_time_tags, parse_word_time_tags, resolved_end_time,
+        TIME_TAG_RE,
+    },
 };
 
 impl From<SrtTimestamp> for TimeTag {
@@ -56,6 +59,41 @@ pub fn parse_srt(content: &str) -> Result<LyricFile> {
     Ok(srt)
 }
 
+/// The text of a cue, with inline time tags between words that have their
+/// own timings.
+pub(crate) fn cue_text(line: &LyricLine, separator: char) -> String {
+    if line.word_time_tags.len() > 1 && !TIME_TAG_RE.is_match(&line.text) {
+        join_word_time_tags(&line.word_time_tags, separator)
+    } else {
+        line.text.clone()
+    }
+}
+
+/// Writes lyrics as SRT. Empty lines are left out, as SRT has no empty
+/// cues.
+pub fn write_srt(srt: &LyricFile) -> String {
+    let mut output = String::new();
+    let mut number = 0;
+
+    for (index, line) in srt.lyrics.iter().enumerate() {
+        let text = cue_text(line, ',');
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        number += 1;
+        output.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            number,
+            format_clock_time(&line.start_time, ','),
+            format_clock_time(&resolved_end_time(&srt.lyrics, index), ','),
+            text.trim()
+        ));
+    }
+
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
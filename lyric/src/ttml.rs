@@ -1,7 +1,38 @@
+use std::collections::{BTreeMap, HashMap};
+
 use anyhow::Result;
 use xmltree::Element;
 
 use crate::types::{LyricFile, LyricLine, TimeTag, VoiceType};
+use crate::utils::resolved_end_time;
+
+/// Metadata key of the title, shared with the `ti` tag of LRC.
+const TITLE_KEY: &str = "ti";
+
+/// Types of `ttm:agent` elements, agents are kept in the metadata as their
+/// ID mapped to their type.
+const AGENT_TYPES: [&str; 5] = ["person", "character", "group", "organization", "other"];
+
+/// Agents written for voices other than the default one.
+fn voice_agent(voice_type: VoiceType) -> Option<(&'static str, &'static str)> {
+    match voice_type {
+        VoiceType::Male => Some(("male", "person")),
+        VoiceType::Female => Some(("female", "person")),
+        VoiceType::Duet => Some(("duet", "group")),
+        VoiceType::Default => None,
+    }
+}
+
+/// Agents written by other tools only tell groups from single singers.
+fn voice_type_of_agent(agent: &str, metadata: &HashMap<String, String>) -> VoiceType {
+    match agent {
+        "male" => VoiceType::Male,
+        "female" => VoiceType::Female,
+        "duet" => VoiceType::Duet,
+        _ if metadata.get(agent).map(|x| x.as_str()) == Some("group") => VoiceType::Duet,
+        _ => VoiceType::Default,
+    }
+}
 
 fn parse_timestamp(s: &str) -> TimeTag {
     let parts: Vec<&str> = s.split(':').collect();
@@ -86,14 +117,19 @@ pub fn parse_ttml(content: &str) -> Result<LyricFile> {
         if let Some(metadata) = head.get_child("metadata") {
             for child in &metadata.children {
                 if let Some(element) = child.as_element() {
+                    // Attributes are keyed by their local name, `xml:id` is `id`
                     if element.name == "agent" {
-                        if let Some(id) = element.attributes.get("xml:id") {
+                        if let Some(id) = element.attributes.get("id") {
                             if let Some(agent_type) = element.attributes.get("type") {
                                 lyric_file
                                     .metadata
                                     .insert(id.to_string(), agent_type.to_string());
                             }
                         }
+                    } else if element.name == "title" {
+                        lyric_file
+                            .metadata
+                            .insert(TITLE_KEY.to_string(), extract_text(element));
                     }
                 }
             }
@@ -120,7 +156,11 @@ pub fn parse_ttml(content: &str) -> Result<LyricFile> {
                     None => continue,
                 };
 
-                let voice_type = VoiceType::Default;
+                let voice_type = p
+                    .attributes
+                    .get("agent")
+                    .map(|agent| voice_type_of_agent(agent, &lyric_file.metadata))
+                    .unwrap_or(VoiceType::Default);
 
                 let mut text = String::new();
                 let mut word_time_tags = Vec::new();
@@ -166,6 +206,113 @@ pub fn parse_ttml(content: &str) -> Result<LyricFile> {
     Ok(lyric_file)
 }
 
+fn format_timestamp(time: &TimeTag) -> String {
+    let total = time.total_milliseconds();
+
+    // Centiseconds, the precision `parse_timestamp` reads
+    format!(
+        "{:02}:{:02}:{:02}.{:02}",
+        total / 3_600_000,
+        total / 60_000 % 60,
+        total / 1000 % 60,
+        total % 1000 / 10
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes lyrics as TTML. Words with timings become spans and voices become
+/// `ttm:agent` references.
+pub fn write_ttml(lyric_file: &LyricFile) -> String {
+    let mut agents: BTreeMap<&str, &str> = lyric_file
+        .metadata
+        .iter()
+        .filter(|(_, agent_type)| AGENT_TYPES.contains(&agent_type.as_str()))
+        .map(|(id, agent_type)| (id.as_str(), agent_type.as_str()))
+        .collect();
+    // Duets keep the group agent of the file they were read from
+    let group = agents
+        .iter()
+        .find(|(_, agent_type)| **agent_type == "group")
+        .map(|(id, _)| *id);
+    let line_agent = |voice_type| match (voice_type, group) {
+        (VoiceType::Duet, Some(group)) => Some((group, "group")),
+        _ => voice_agent(voice_type),
+    };
+    for line in &lyric_file.lyrics {
+        if let Some((id, agent_type)) = line_agent(line.voice_type) {
+            agents.insert(id, agent_type);
+        }
+    }
+
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str(
+        "<tt xmlns=\"http://www.w3.org/ns/ttml\" xmlns:ttm=\"http://www.w3.org/ns/ttml#metadata\">\n",
+    );
+
+    let title = lyric_file.metadata.get(TITLE_KEY);
+    if title.is_some() || !agents.is_empty() {
+        output.push_str("  <head>\n    <metadata>\n");
+        if let Some(title) = title {
+            output.push_str(&format!(
+                "      <ttm:title>{}</ttm:title>\n",
+                escape_xml(title)
+            ));
+        }
+        for (id, agent_type) in &agents {
+            output.push_str(&format!(
+                "      <ttm:agent type=\"{}\" xml:id=\"{}\"/>\n",
+                escape_xml(agent_type),
+                escape_xml(id)
+            ));
+        }
+        output.push_str("    </metadata>\n  </head>\n");
+    }
+
+    output.push_str("  <body>\n    <div>\n");
+    for (index, line) in lyric_file.lyrics.iter().enumerate() {
+        let end_time = resolved_end_time(&lyric_file.lyrics, index);
+
+        output.push_str(&format!(
+            "      <p begin=\"{}\" end=\"{}\"",
+            format_timestamp(&line.start_time),
+            format_timestamp(&end_time)
+        ));
+        if let Some((id, _)) = line_agent(line.voice_type) {
+            output.push_str(&format!(" ttm:agent=\"{}\"", escape_xml(id)));
+        }
+        output.push('>');
+
+        if line.word_time_tags.is_empty() {
+            output.push_str(&escape_xml(&line.text));
+        } else {
+            for (start_time, word_end_time, word) in &line.word_time_tags {
+                let word_end_time = if word_end_time.is_unbounded() {
+                    &end_time
+                } else {
+                    word_end_time
+                };
+
+                output.push_str(&format!(
+                    "<span begin=\"{}\" end=\"{}\">{}</span>",
+                    format_timestamp(start_time),
+                    format_timestamp(word_end_time),
+                    escape_xml(word)
+                ));
+            }
+        }
+        output.push_str("</p>\n");
+    }
+    output.push_str("    </div>\n  </body>\n</tt>\n");
+
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -9,6 +9,46 @@ pub struct TimeTag {
     pub milliseconds: u32,
 }
 
+impl TimeTag {
+    /// Placeholder end of lines whose end is not known yet, such as LRC
+    /// lines, which end where the next line starts.
+    pub fn unbounded() -> Self {
+        TimeTag {
+            minutes: 9999,
+            seconds: 0,
+            milliseconds: 0,
+        }
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.minutes >= 9999
+    }
+
+    pub fn from_milliseconds(milliseconds: i64) -> Self {
+        let milliseconds = milliseconds.max(0) as u64;
+
+        TimeTag {
+            minutes: (milliseconds / 60_000) as u32,
+            seconds: (milliseconds / 1000 % 60) as u32,
+            milliseconds: (milliseconds % 1000) as u32,
+        }
+    }
+
+    pub fn total_milliseconds(&self) -> i64 {
+        self.minutes as i64 * 60_000 + self.seconds as i64 * 1000 + self.milliseconds as i64
+    }
+
+    /// Moves the time by `offset` milliseconds, stopping at zero. Unbounded
+    /// times stay unbounded.
+    pub fn shifted(&self, offset: i64) -> Self {
+        if self.is_unbounded() {
+            return self.clone();
+        }
+
+        TimeTag::from_milliseconds(self.total_milliseconds() + offset)
+    }
+}
+
 impl From<TimeTag> for i32 {
     fn from(val: TimeTag) -> Self {
         (val.minutes * 60 * 1000 + val.seconds * 1000 + val.milliseconds) as i32
@@ -58,7 +98,7 @@ impl FromStr for TimeTag {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VoiceType {
     Male,
     Female,
@@ -75,7 +115,7 @@ pub struct LyricLine {
     pub word_time_tags: Vec<(TimeTag, TimeTag, String)>, // Start and end time tags for each word
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct LyricFile {
     // ID tags
     pub metadata: HashMap<String, String>,
@@ -90,4 +130,51 @@ impl LyricFile {
             lyrics: Vec::new(),
         }
     }
+
+    /// Moves every line and word by `offset` milliseconds, negative offsets
+    /// show the lyrics earlier.
+    pub fn shift(&mut self, offset: i64) {
+        for line in &mut self.lyrics {
+            line.start_time = line.start_time.shifted(offset);
+            line.end_time = line.end_time.shifted(offset);
+
+            for (start_time, end_time, _) in &mut line.word_time_tags {
+                *start_time = start_time.shifted(offset);
+                *end_time = end_time.shifted(offset);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricFormat {
+    Lrc,
+    Ttml,
+    Vtt,
+    Srt,
+}
+
+impl LyricFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LyricFormat::Lrc => "lrc",
+            LyricFormat::Ttml => "ttml",
+            LyricFormat::Vtt => "vtt",
+            LyricFormat::Srt => "srt",
+        }
+    }
+}
+
+impl FromStr for LyricFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "lrc" | "lrcx" => Ok(LyricFormat::Lrc),
+            "ttml" => Ok(LyricFormat::Ttml),
+            "vtt" => Ok(LyricFormat::Vtt),
+            "srt" => Ok(LyricFormat::Srt),
+            _ => bail!("Unsupported lyric format: {}", s),
+        }
+    }
 }
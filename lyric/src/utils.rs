@@ -1,7 +1,10 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 
-use crate::types::TimeTag;
+use crate::types::{LyricLine, TimeTag};
+
+/// Length given to the last line of lyrics that do not say when it ends.
+const LAST_LINE_DURATION: i64 = 5000;
 
 static STYLE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"</?([a-zA-Z][^>]*)>").unwrap());
 pub static TIME_TAG_RE: Lazy<Regex> =
@@ -93,3 +96,50 @@ pub fn parse_word_time_tags(
 
     (cleaned_text.trim().to_string(), word_time_tags)
 }
+
+/// Formats a time as `HH:MM:SS.mmm`, with `separator` before the
+/// milliseconds.
+pub fn format_clock_time(time: &TimeTag, separator: char) -> String {
+    let total = time.total_milliseconds();
+
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        total / 3_600_000,
+        total / 60_000 % 60,
+        total / 1000 % 60,
+        separator,
+        total % 1000
+    )
+}
+
+/// The end of a line, for formats where every line needs one. Unbounded
+/// lines end where the next one starts.
+pub fn resolved_end_time(lines: &[LyricLine], index: usize) -> TimeTag {
+    let line = &lines[index];
+    if !line.end_time.is_unbounded() {
+        return line.end_time.clone();
+    }
+
+    match lines.get(index + 1) {
+        Some(next) => next.start_time.clone(),
+        None => line.start_time.shifted(LAST_LINE_DURATION),
+    }
+}
+
+/// Joins the words of a line with inline time tags in the syntax of SRT
+/// and VTT, so `parse_word_time_tags` reads the same words back.
+pub fn join_word_time_tags(words: &[(TimeTag, TimeTag, String)], separator: char) -> String {
+    let mut text = String::new();
+
+    for (index, (start_time, _, word)) in words.iter().enumerate() {
+        if index > 0 {
+            text.push(' ');
+            text.push('<');
+            text.push_str(&format_clock_time(start_time, separator));
+            text.push('>');
+        }
+        text.push_str(word.trim());
+    }
+
+    text
+}
@@ -1,11 +1,40 @@
 use anyhow::Result;
-use subtp::vtt::{VttBlock, VttCue, WebVtt};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use subtp::vtt::{VttBlock, VttCue, VttTimestamp, WebVtt};
 
 use crate::{
+    srt::cue_text,
     types::{LyricFile, LyricLine, TimeTag, VoiceType},
-    utils::parse_word_time_tags,
+    utils::{format_clock_time, parse_word_time_tags, resolved_end_time},
 };
 
+/// The voice span opening a cue, `<v Female>` or `<v.loud Female>`.
+static VOICE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<v(?:\.[^\s>]*)?\s+([^>]+)>").unwrap());
+
+impl From<VttTimestamp> for TimeTag {
+    fn from(timestamp: VttTimestamp) -> Self {
+        TimeTag {
+            minutes: timestamp.hours as u32 * 60 + timestamp.minutes as u32,
+            seconds: timestamp.seconds as u32,
+            milliseconds: timestamp.milliseconds as u32,
+        }
+    }
+}
+
+fn parse_voice(payload: &str) -> VoiceType {
+    let voice = VOICE_RE
+        .captures(payload)
+        .map(|x| x[1].trim().to_lowercase());
+
+    match voice.as_deref() {
+        Some("male") => VoiceType::Male,
+        Some("female") => VoiceType::Female,
+        Some("duet") => VoiceType::Duet,
+        _ => VoiceType::Default,
+    }
+}
+
 pub fn parse_vtt(content: &str) -> Result<LyricFile> {
     let mut vtt = LyricFile::new();
     let webvtt = WebVtt::parse(content.trim_start())?;
@@ -15,25 +44,17 @@ pub fn parse_vtt(content: &str) -> Result<LyricFile> {
             timings, payload, ..
         }) = block
         {
-            let start_time = TimeTag {
-                minutes: (timings.start.seconds / 60) as u32,
-                seconds: (timings.start.seconds % 60) as u32,
-                milliseconds: timings.start.milliseconds as u32,
-            };
+            let start_time: TimeTag = timings.start.into();
+            let end_time: TimeTag = timings.end.into();
 
-            let end_time = TimeTag {
-                minutes: (timings.end.seconds / 60) as u32,
-                seconds: (timings.end.seconds % 60) as u32,
-                milliseconds: timings.end.milliseconds as u32,
-            };
-
-            let (text, word_time_tags) =
-                parse_word_time_tags(&payload.join("\n"), &start_time, &end_time);
+            let payload = payload.join("\n");
+            let voice_type = parse_voice(&payload);
+            let (text, word_time_tags) = parse_word_time_tags(&payload, &start_time, &end_time);
 
             let lyric_line = LyricLine {
                 start_time,
                 end_time,
-                voice_type: VoiceType::Default,
+                voice_type,
                 text,
                 word_time_tags,
             };
@@ -45,6 +66,35 @@ pub fn parse_vtt(content: &str) -> Result<LyricFile> {
     Ok(vtt)
 }
 
+/// Writes lyrics as WebVTT, voices become `<v>` spans.
+pub fn write_vtt(vtt: &LyricFile) -> String {
+    let mut output = String::from("WEBVTT\n\n");
+
+    for (index, line) in vtt.lyrics.iter().enumerate() {
+        let text = cue_text(line, '.');
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let voice = match line.voice_type {
+            VoiceType::Male => "<v Male>",
+            VoiceType::Female => "<v Female>",
+            VoiceType::Duet => "<v Duet>",
+            VoiceType::Default => "",
+        };
+
+        output.push_str(&format!(
+            "{} --> {}\n{}{}\n\n",
+            format_clock_time(&line.start_time, '.'),
+            format_clock_time(&resolved_end_time(&vtt.lyrics, index), '.'),
+            voice,
+            text.trim()
+        ));
+    }
+
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
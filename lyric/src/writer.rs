@@ -0,0 +1,280 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    lrc::write_lrc,
+    parser::LYRIC_EXTENSIONS,
+    srt::write_srt,
+    ttml::write_ttml,
+    types::{LyricFile, LyricFormat},
+    vtt::write_vtt,
+};
+
+pub fn write_lyrics(lyric_file: &LyricFile, format: LyricFormat) -> String {
+    match format {
+        LyricFormat::Lrc => write_lrc(lyric_file),
+        LyricFormat::Ttml => write_ttml(lyric_file),
+        LyricFormat::Vtt => write_vtt(lyric_file),
+        LyricFormat::Srt => write_srt(lyric_file),
+    }
+}
+
+/// Saves lyrics next to an audio file, replacing the sidecar file of the
+/// same format. Returns the path of the written file.
+///
+/// Sidecar files of other formats would still be read before the saved
+/// one, they are renamed to `<name>.<extension>.bak` once it is written.
+pub fn save_audio_lyrics(
+    audio_path: &Path,
+    lyric_file: &LyricFile,
+    format: LyricFormat,
+) -> Result<PathBuf> {
+    let mut file_path = audio_path.to_path_buf();
+    file_path.set_extension(format.extension());
+
+    fs::write(&file_path, write_lyrics(lyric_file, format))?;
+
+    for (extension, _) in LYRIC_EXTENSIONS {
+        let mut other_path = audio_path.to_path_buf();
+        other_path.set_extension(extension);
+        if other_path == file_path || !other_path.exists() {
+            continue;
+        }
+
+        let mut backup_path = other_path.clone().into_os_string();
+        backup_path.push(".bak");
+        fs::rename(&other_path, &backup_path)
+            .with_context(|| format!("Failed to set aside lyrics: {}", other_path.display()))?;
+    }
+
+    Ok(file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{find_audio_lyrics, parse_audio_lyrics, parse_lyrics};
+    use crate::types::{TimeTag, VoiceType};
+
+    const LRC: &str = r#"[ar:Chubby Checker]
+[ti:Let's Twist Again]
+[00:12.00]First line
+[00:15.30]F: Female line
+[00:21.10]M: <00:21.10>Male <00:23.10>line
+[00:24.00]D: <00:24.00>Both <00:24.50>sing
+[00:25.00]<00:25.00>Word <00:25.50>by <00:26.00>word
+[01:02.50]  Indented line"#;
+
+    const TTML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata">
+  <head>
+    <metadata>
+      <ttm:title>Song &amp; Dance</ttm:title>
+      <ttm:agent type="person" xml:id="female"/>
+      <ttm:agent type="group" xml:id="v1000"/>
+    </metadata>
+  </head>
+  <body>
+    <div>
+      <p begin="00:00:01.00" end="00:00:03.50" ttm:agent="female"><span begin="00:00:01.00" end="00:00:02.00">Hello </span><span begin="00:00:02.00" end="00:00:03.50">world</span></p>
+      <p begin="00:00:04.00" end="00:00:06.00">Plain &lt;line&gt;</p>
+      <p begin="00:01:30.25" end="01:02:03.40" ttm:agent="v1000">Everyone</p>
+    </div>
+  </body>
+</tt>"#;
+
+    const SRT: &str = r#"1
+00:00:01,000 --> 00:00:05,000
+Hello world <00:00:02,500>this is <00:00:03,500>a test
+
+2
+00:00:06,120 --> 00:00:10,003
+Line one
+Line two
+
+3
+01:30:00,000 --> 01:30:05,000
+Text spanning over an hour
+"#;
+
+    const VTT: &str = r#"WEBVTT
+
+00:00:01.000 --> 00:00:02.000
+<v Male>Hello world
+
+00:01:03.000 --> 00:01:04.500
+Another <00:01:03.750>line
+
+01:00:00.000 --> 01:00:01.000
+<v Duet>An hour in
+"#;
+
+    fn time(minutes: u32, seconds: u32, milliseconds: u32) -> TimeTag {
+        TimeTag {
+            minutes,
+            seconds,
+            milliseconds,
+        }
+    }
+
+    /// Parsing what was written gives back the parsed file, and writing it
+    /// again gives the same text.
+    fn assert_round_trip(format: LyricFormat, content: &str) -> LyricFile {
+        let parsed = parse_lyrics(format, content).unwrap();
+        assert!(!parsed.lyrics.is_empty());
+
+        let written = write_lyrics(&parsed, format);
+        let reparsed = parse_lyrics(format, &written).unwrap();
+        assert_eq!(reparsed, parsed, "{:?} round trip of:\n{}", format, written);
+        assert_eq!(write_lyrics(&reparsed, format), written);
+
+        parsed
+    }
+
+    #[test]
+    fn test_lrc_round_trip() {
+        let lrc = assert_round_trip(LyricFormat::Lrc, LRC);
+
+        assert_eq!(lrc.metadata.get("ti").unwrap(), "Let's Twist Again");
+        assert_eq!(lrc.lyrics[2].voice_type, VoiceType::Male);
+        assert_eq!(lrc.lyrics[3].voice_type, VoiceType::Duet);
+        assert_eq!(lrc.lyrics[4].word_time_tags.len(), 3);
+        assert_eq!(lrc.lyrics[5].text, "  Indented line");
+    }
+
+    #[test]
+    fn test_ttml_round_trip() {
+        let ttml = assert_round_trip(LyricFormat::Ttml, TTML);
+
+        assert_eq!(ttml.metadata.get("ti").unwrap(), "Song & Dance");
+        assert_eq!(ttml.metadata.get("v1000").unwrap(), "group");
+        assert_eq!(ttml.lyrics[0].voice_type, VoiceType::Female);
+        assert_eq!(ttml.lyrics[0].text, "Hello world");
+        assert_eq!(ttml.lyrics[1].text, "Plain <line>");
+        assert_eq!(ttml.lyrics[1].voice_type, VoiceType::Default);
+        assert_eq!(ttml.lyrics[2].voice_type, VoiceType::Duet);
+        assert_eq!(ttml.lyrics[2].end_time, time(62, 3, 400));
+    }
+
+    #[test]
+    fn test_srt_round_trip() {
+        let srt = assert_round_trip(LyricFormat::Srt, SRT);
+
+        assert_eq!(srt.lyrics[0].word_time_tags.len(), 3);
+        assert_eq!(srt.lyrics[1].text, "Line one\nLine two");
+        assert_eq!(srt.lyrics[1].end_time, time(0, 10, 3));
+        assert_eq!(srt.lyrics[2].start_time, time(90, 0, 0));
+    }
+
+    #[test]
+    fn test_vtt_round_trip() {
+        let vtt = assert_round_trip(LyricFormat::Vtt, VTT);
+
+        assert_eq!(vtt.lyrics[0].voice_type, VoiceType::Male);
+        assert_eq!(vtt.lyrics[0].text, "Hello world");
+        assert_eq!(vtt.lyrics[1].start_time, time(1, 3, 0));
+        assert_eq!(vtt.lyrics[1].word_time_tags.len(), 2);
+        assert_eq!(vtt.lyrics[2].voice_type, VoiceType::Duet);
+        assert_eq!(vtt.lyrics[2].start_time, time(60, 0, 0));
+    }
+
+    #[test]
+    fn test_cross_format_conversion() {
+        let lrc = parse_lyrics(LyricFormat::Lrc, LRC).unwrap();
+
+        for format in [LyricFormat::Ttml, LyricFormat::Vtt, LyricFormat::Srt] {
+            let converted = parse_lyrics(format, &write_lyrics(&lrc, format)).unwrap();
+            assert_eq!(converted.lyrics.len(), lrc.lyrics.len(), "{:?}", format);
+
+            for (converted, original) in converted.lyrics.iter().zip(&lrc.lyrics) {
+                assert_eq!(converted.start_time, original.start_time, "{:?}", format);
+            }
+
+            // Lines end where the next one starts, the last one gets a length
+            assert_eq!(converted.lyrics[0].end_time, time(0, 15, 300));
+            assert_eq!(converted.lyrics[5].end_time, time(1, 7, 500));
+
+            assert_eq!(converted.lyrics[0].text, "First line");
+            assert_eq!(converted.lyrics[4].word_time_tags.len(), 3);
+            assert_eq!(
+                converted.lyrics[4].word_time_tags[1].0,
+                time(0, 25, 500),
+                "{:?}",
+                format
+            );
+
+            if format != LyricFormat::Srt {
+                assert_eq!(converted.lyrics[1].voice_type, VoiceType::Female);
+                assert_eq!(converted.lyrics[2].voice_type, VoiceType::Male);
+            }
+        }
+
+        // Voices and word timings survive the way back to LRC
+        let mut ttml = parse_lyrics(LyricFormat::Ttml, TTML).unwrap();
+        // LRC has no way to escape `<`
+        ttml.lyrics.remove(1);
+        let lrc = parse_lyrics(LyricFormat::Lrc, &write_lyrics(&ttml, LyricFormat::Lrc)).unwrap();
+        assert_eq!(lrc.metadata.get("ti").unwrap(), "Song & Dance");
+        assert_eq!(lrc.lyrics[0].voice_type, VoiceType::Female);
+        assert_eq!(lrc.lyrics[0].word_time_tags[1].2, "world");
+        assert_eq!(lrc.lyrics[1].text, "");
+        assert_eq!(lrc.lyrics[2].voice_type, VoiceType::Duet);
+    }
+
+    #[test]
+    fn test_lrc_gaps() {
+        let srt = parse_lyrics(LyricFormat::Srt, SRT).unwrap();
+        let lrc = write_lyrics(&srt, LyricFormat::Lrc);
+
+        // Silences between lines become empty lines
+        assert!(lrc.contains("[00:05.00]\n[00:06.12]"));
+        assert!(lrc.ends_with("[90:05.00]\n"));
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut lrc = parse_lyrics(LyricFormat::Lrc, LRC).unwrap();
+
+        lrc.shift(1500);
+        assert_eq!(lrc.lyrics[0].start_time, time(0, 13, 500));
+        assert_eq!(lrc.lyrics[0].end_time, time(0, 16, 800));
+        assert_eq!(lrc.lyrics[4].word_time_tags[1].0, time(0, 27, 0));
+        assert!(lrc.lyrics[5].end_time.is_unbounded());
+
+        lrc.shift(-20_000);
+        assert_eq!(lrc.lyrics[0].start_time, time(0, 0, 0));
+        assert_eq!(lrc.lyrics[5].start_time, time(0, 44, 0));
+
+        // Shifted lyrics still round trip
+        let written = write_lyrics(&lrc, LyricFormat::Lrc);
+        assert!(written.contains("[00:44.00]  Indented line"));
+    }
+
+    #[test]
+    fn test_save_sets_aside_other_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        let audio_path = dir.path().join("track.flac");
+        fs::write(dir.path().join("track.ttml"), TTML).unwrap();
+        fs::write(dir.path().join("track.srt"), SRT).unwrap();
+
+        let lrc = parse_lyrics(LyricFormat::Lrc, LRC).unwrap();
+        let path = save_audio_lyrics(&audio_path, &lrc, LyricFormat::Lrc).unwrap();
+        assert_eq!(path, dir.path().join("track.lrc"));
+
+        // The saved file is the one read back
+        assert_eq!(
+            find_audio_lyrics(&audio_path),
+            Some((path, LyricFormat::Lrc))
+        );
+        assert_eq!(parse_audio_lyrics(audio_path).unwrap().unwrap(), lrc);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("track.ttml.bak")).unwrap(),
+            TTML
+        );
+        assert!(dir.path().join("track.srt.bak").exists());
+    }
+}
@@ -0,0 +1,492 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// CUE sheets address positions in frames, 75 frames per second.
+const FRAMES_PER_SECOND: f64 = 75.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub isrc: Option<String>,
+    /// Position of `INDEX 01` in seconds
+    pub start: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CueFile {
+    pub name: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+    pub disc_number: Option<String>,
+    pub files: Vec<CueFile>,
+}
+
+/// A track of a CUE sheet resolved against the audio file it lives in.
+#[derive(Debug, Clone)]
+pub struct CueSegment {
+    pub number: u32,
+    /// Start offset in seconds
+    pub start: f64,
+    /// End offset in seconds
+    pub end: f64,
+    pub metadata: Vec<(String, String)>,
+}
+
+/// Splits a CUE command line into its arguments, honouring double quotes.
+fn split_arguments(line: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_argument = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_argument = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    has_argument = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_argument = true;
+            }
+        }
+    }
+
+    if has_argument {
+        arguments.push(current);
+    }
+
+    arguments
+}
+
+/// Parses a `mm:ss:ff` timestamp into seconds.
+fn parse_timestamp(timestamp: &str) -> Result<f64> {
+    let parts: Vec<&str> = timestamp.split(':').collect();
+    if parts.len() != 3 {
+        bail!("Invalid CUE timestamp: {}", timestamp);
+    }
+
+    let minutes: u32 = parts[0]
+        .parse()
+        .with_context(|| format!("Invalid minutes in CUE timestamp: {}", timestamp))?;
+    let seconds: u32 = parts[1]
+        .parse()
+        .with_context(|| format!("Invalid seconds in CUE timestamp: {}", timestamp))?;
+    let frames: u32 = parts[2]
+        .parse()
+        .with_context(|| format!("Invalid frames in CUE timestamp: {}", timestamp))?;
+
+    Ok(minutes as f64 * 60.0 + seconds as f64 + frames as f64 / FRAMES_PER_SECOND)
+}
+
+pub fn parse_cue_sheet(content: &str) -> Result<CueSheet> {
+    let mut sheet = CueSheet::default();
+
+    for (line_number, line) in content.trim_start_matches('\u{feff}').lines().enumerate() {
+        let arguments = split_arguments(line);
+        let Some(command) = arguments.first() else {
+            continue;
+        };
+
+        let value = arguments.get(1).cloned();
+        let current_track = sheet
+            .files
+            .last_mut()
+            .and_then(|file| file.tracks.last_mut());
+
+        match command.to_uppercase().as_str() {
+            "FILE" => {
+                let Some(name) = value else {
+                    bail!("FILE without a file name on line {}", line_number + 1);
+                };
+                sheet.files.push(CueFile {
+                    name,
+                    tracks: Vec::new(),
+                });
+            }
+            "TRACK" => {
+                let Some(file) = sheet.files.last_mut() else {
+                    bail!("TRACK before any FILE on line {}", line_number + 1);
+                };
+                let number = value
+                    .as_deref()
+                    .unwrap_or_default()
+                    .parse()
+                    .with_context(|| format!("Invalid track number on line {}", line_number + 1))?;
+                file.tracks.push(CueTrack {
+                    number,
+                    ..Default::default()
+                });
+            }
+            "INDEX" => {
+                let (Some(index), Some(timestamp)) = (value, arguments.get(2)) else {
+                    bail!("Incomplete INDEX on line {}", line_number + 1);
+                };
+                if index.parse::<u32>().ok() == Some(1) {
+                    let Some(track) = current_track else {
+                        bail!("INDEX outside of a TRACK on line {}", line_number + 1);
+                    };
+                    track.start = parse_timestamp(timestamp)?;
+                }
+            }
+            "TITLE" => match current_track {
+                Some(track) => track.title = value,
+                None => sheet.title = value,
+            },
+            "PERFORMER" => match current_track {
+                Some(track) => track.performer = value,
+                None => sheet.performer = value,
+            },
+            "SONGWRITER" => match current_track {
+                Some(track) => track.songwriter = value,
+                None => sheet.songwriter = value,
+            },
+            "ISRC" => {
+                if let Some(track) = current_track {
+                    track.isrc = value;
+                }
+            }
+            "REM" => {
+                let remark = arguments.get(2).cloned();
+                match value.as_deref().map(str::to_uppercase).as_deref() {
+                    Some("GENRE") => sheet.genre = remark,
+                    Some("DATE") => sheet.date = remark,
+                    Some("DISCNUMBER") => sheet.disc_number = remark,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if sheet.files.iter().all(|file| file.tracks.is_empty()) {
+        bail!("CUE sheet contains no tracks");
+    }
+
+    Ok(sheet)
+}
+
+pub fn read_cue_sheet(path: &Path) -> Result<CueSheet> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read CUE sheet: {:?}", path))?;
+    let content = String::from_utf8_lossy(&bytes);
+
+    parse_cue_sheet(&content).with_context(|| format!("Failed to parse CUE sheet: {:?}", path))
+}
+
+fn is_cue_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map(|ext| ext.eq_ignore_ascii_case("cue"))
+        .unwrap_or(false)
+}
+
+fn file_stem(name: &str) -> Option<String> {
+    Path::new(name)
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase)
+}
+
+impl CueSheet {
+    /// Finds the `FILE` entry describing `file_name`.
+    ///
+    /// Rips are often re-encoded after the sheet was written, so an entry
+    /// whose name only matches without the extension is accepted as well.
+    pub fn file(&self, file_name: &str) -> Option<&CueFile> {
+        let exact = self.files.iter().find(|file| {
+            Path::new(&file.name)
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map(|name| name.eq_ignore_ascii_case(file_name))
+                .unwrap_or(false)
+        });
+
+        exact.or_else(|| {
+            let stem = file_stem(file_name)?;
+            self.files
+                .iter()
+                .find(|file| file_stem(&file.name).as_deref() == Some(stem.as_str()))
+        })
+    }
+
+    /// Resolves the tracks stored in `file_name` into segments, the last track
+    /// ends with the file.
+    ///
+    /// `file_metadata` holds the tags of the audio file, the values from the
+    /// sheet take precedence.
+    pub fn segments(
+        &self,
+        file_name: &str,
+        duration: f64,
+        file_metadata: &[(String, String)],
+    ) -> Vec<CueSegment> {
+        let Some(file) = self.file(file_name) else {
+            return Vec::new();
+        };
+
+        let track_total = file.tracks.len().to_string();
+
+        file.tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, track)| {
+                let end = file
+                    .tracks
+                    .get(index + 1)
+                    .map(|next| next.start)
+                    .unwrap_or(duration)
+                    .min(duration);
+
+                if end <= track.start {
+                    return None;
+                }
+
+                let mut metadata: Vec<(String, String)> = file_metadata
+                    .iter()
+                    .filter(|(key, _)| {
+                        !matches!(
+                            key.as_str(),
+                            "track_title"
+                                | "artist"
+                                | "track_number"
+                                | "track_total"
+                                | "ident_isrc"
+                                | "composer"
+                        )
+                    })
+                    .cloned()
+                    .collect();
+
+                let mut set = |key: &str, value: Option<&String>| {
+                    if let Some(value) = value {
+                        metadata.retain(|(k, _)| k != key);
+                        metadata.push((key.to_string(), value.clone()));
+                    }
+                };
+
+                set("album", self.title.as_ref());
+                set("album_artist", self.performer.as_ref());
+                set("genre", self.genre.as_ref());
+                set("date", self.date.as_ref());
+                set("disc_number", self.disc_number.as_ref());
+                set("track_title", track.title.as_ref());
+                set(
+                    "artist",
+                    track.performer.as_ref().or(self.performer.as_ref()),
+                );
+                set(
+                    "composer",
+                    track.songwriter.as_ref().or(self.songwriter.as_ref()),
+                );
+                set("ident_isrc", track.isrc.as_ref());
+                set("track_number", Some(&track.number.to_string()));
+                set("track_total", Some(&track_total));
+
+                Some(CueSegment {
+                    number: track.number,
+                    start: track.start,
+                    end,
+                    metadata,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Finds the CUE sheets describing audio files, reading each directory once.
+///
+/// Scans visit the files of a directory one after another, so only the
+/// sheets of the last directory are kept.
+#[derive(Debug, Default)]
+pub struct CueSheetCache {
+    directory: Option<PathBuf>,
+    sheets: Vec<(PathBuf, CueSheet)>,
+}
+
+impl CueSheetCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks for a CUE sheet next to `audio_path` that describes it.
+    pub fn find(&mut self, audio_path: &Path) -> Option<(PathBuf, CueSheet)> {
+        let directory = audio_path.parent()?;
+        let file_name = audio_path.file_name()?.to_str()?;
+
+        if self.directory.as_deref() != Some(directory) {
+            self.sheets = read_cue_sheets(directory);
+            self.directory = Some(directory.to_path_buf());
+        }
+
+        self.sheets
+            .iter()
+            .find(|(_, sheet)| sheet.file(file_name).is_some())
+            .cloned()
+    }
+}
+
+/// Reads the valid CUE sheets of a directory, sorted by path.
+fn read_cue_sheets(directory: &Path) -> Vec<(PathBuf, CueSheet)> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_cue_file(path))
+        .collect();
+    candidates.sort();
+
+    candidates
+        .into_iter()
+        .filter_map(|path| {
+            let sheet = read_cue_sheet(&path).ok()?;
+            Some((path, sheet))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = "\u{feff}REM GENRE Rock
+REM DATE 1999
+PERFORMER \"The Band\"
+TITLE \"Live Album\"
+FILE \"Live Album.wav\" WAVE
+  TRACK 01 AUDIO
+    TITLE \"Opening\"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE \"Second Song\"
+    PERFORMER \"Guest\"
+    ISRC USABC9900001
+    INDEX 00 03:58:00
+    INDEX 01 04:00:37
+  TRACK 03 AUDIO
+    TITLE \"Closing\"
+    INDEX 01 09:30:00
+";
+
+    fn value<'a>(metadata: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_split_arguments() {
+        assert_eq!(
+            split_arguments("  FILE \"My Album.flac\" WAVE"),
+            vec!["FILE", "My Album.flac", "WAVE"]
+        );
+        assert_eq!(split_arguments("TITLE \"\""), vec!["TITLE", ""]);
+        assert!(split_arguments("   ").is_empty());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("00:00:00").unwrap(), 0.0);
+        assert_eq!(parse_timestamp("01:02:75").unwrap(), 63.0);
+        assert!((parse_timestamp("04:00:37").unwrap() - (240.0 + 37.0 / 75.0)).abs() < 1e-9);
+        assert!(parse_timestamp("01:02").is_err());
+        assert!(parse_timestamp("aa:00:00").is_err());
+    }
+
+    #[test]
+    fn test_parse_cue_sheet() {
+        let sheet = parse_cue_sheet(SHEET).unwrap();
+
+        assert_eq!(sheet.title.as_deref(), Some("Live Album"));
+        assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+        assert_eq!(sheet.genre.as_deref(), Some("Rock"));
+        assert_eq!(sheet.date.as_deref(), Some("1999"));
+        assert_eq!(sheet.files.len(), 1);
+
+        let tracks = &sheet.files[0].tracks;
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[1].number, 2);
+        assert_eq!(tracks[1].performer.as_deref(), Some("Guest"));
+        assert_eq!(tracks[1].isrc.as_deref(), Some("USABC9900001"));
+        // INDEX 00 is the pregap, tracks start at INDEX 01
+        assert!((tracks[1].start - (240.0 + 37.0 / 75.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_cue_sheet_errors() {
+        assert!(parse_cue_sheet("TITLE \"Nothing\"").is_err());
+        assert!(parse_cue_sheet("TRACK 01 AUDIO\nINDEX 01 00:00:00").is_err());
+        assert!(parse_cue_sheet("FILE \"a.wav\" WAVE\nTRACK xx AUDIO").is_err());
+        assert!(parse_cue_sheet("FILE \"a.wav\" WAVE\nTRACK 01 AUDIO\nINDEX 01").is_err());
+    }
+
+    #[test]
+    fn test_file_matches_stem() {
+        let sheet = parse_cue_sheet(SHEET).unwrap();
+
+        assert!(sheet.file("live album.WAV").is_some());
+        assert!(sheet.file("Live Album.flac").is_some());
+        assert!(sheet.file("Other.flac").is_none());
+    }
+
+    #[test]
+    fn test_segments() {
+        let sheet = parse_cue_sheet(SHEET).unwrap();
+        let file_metadata = vec![
+            ("album".to_string(), "Tagged Album".to_string()),
+            ("track_title".to_string(), "Whole File".to_string()),
+            ("label".to_string(), "Label".to_string()),
+        ];
+
+        let segments = sheet.segments("Live Album.flac", 600.0, &file_metadata);
+        assert_eq!(segments.len(), 3);
+
+        assert_eq!(segments[0].start, 0.0);
+        assert_eq!(segments[0].end, segments[1].start);
+        assert_eq!(segments[2].start, 570.0);
+        assert_eq!(segments[2].end, 600.0);
+
+        // The sheet takes precedence, other tags of the file are kept
+        let metadata = &segments[1].metadata;
+        assert_eq!(value(metadata, "album"), Some("Live Album"));
+        assert_eq!(value(metadata, "track_title"), Some("Second Song"));
+        assert_eq!(value(metadata, "artist"), Some("Guest"));
+        assert_eq!(value(metadata, "album_artist"), Some("The Band"));
+        assert_eq!(value(metadata, "track_number"), Some("2"));
+        assert_eq!(value(metadata, "track_total"), Some("3"));
+        assert_eq!(value(metadata, "label"), Some("Label"));
+        assert_eq!(value(&segments[0].metadata, "artist"), Some("The Band"));
+    }
+
+    #[test]
+    fn test_segments_past_the_end_are_dropped() {
+        let sheet = parse_cue_sheet(SHEET).unwrap();
+
+        let segments = sheet.segments("Live Album.wav", 300.0, &[]);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].end, 300.0);
+    }
+}
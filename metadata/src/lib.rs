@@ -1,7 +1,9 @@
 pub mod artist;
 pub mod cover_art;
 pub mod crc;
+pub mod cue;
 pub mod describe;
 pub mod reader;
+pub mod replay_gain;
 pub mod scanner;
 pub mod genre;
\ No newline at end of file
@@ -1,12 +1,13 @@
 use std::collections::HashMap;
-use std::fs::File;
 use std::path::Path;
 
 use anyhow::{bail, Result};
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, Value};
-use symphonia::core::probe::{Hint, ProbeResult};
+use symphonia::core::meta::{MetadataRevision, StandardTagKey, Value};
+use symphonia::core::probe::ProbeResult;
+
+use formats::registry;
+
+use crate::replay_gain::normalize_replay_gain_key;
 
 fn create_standard_tag_key_maps() -> (
     HashMap<StandardTagKey, &'static str>,
@@ -183,6 +184,22 @@ pub fn string_to_standard_tag_key(s: &str) -> Option<StandardTagKey> {
     STRING_TO_STANDARD_TAG_KEY.get(s).cloned()
 }
 
+/// Symphonia has no standard key for the musical key of a track, it is
+/// written as `TKEY` in ID3v2 and `INITIALKEY` or `KEY` in Vorbis comments
+/// and MP4 freeform atoms.
+fn normalize_initial_key_key(raw_key: &str) -> Option<&'static str> {
+    let key = raw_key
+        .rsplit(':')
+        .next()
+        .unwrap_or(raw_key)
+        .to_ascii_lowercase();
+
+    match key.as_str() {
+        "tkey" | "initialkey" | "initial_key" | "key" => Some("initial_key"),
+        _ => None,
+    }
+}
+
 fn push_tags(
     revision: &MetadataRevision,
     metadata_list: &mut Vec<(String, String)>,
@@ -191,7 +208,10 @@ fn push_tags(
     for tag in revision.tags() {
         let std_key = match tag.std_key {
             Some(standard_key) => standard_tag_key_to_string(standard_key),
-            None => String::from(""),
+            None => normalize_replay_gain_key(&tag.key)
+                .or_else(|| normalize_initial_key_key(&tag.key))
+                .map(String::from)
+                .unwrap_or_default(),
         };
 
         if field_blacklist.contains(&std_key.as_str()) {
@@ -216,25 +236,9 @@ fn probe_audio_file<P: AsRef<Path>>(file_path: P) -> Result<ProbeResult> {
         bail!("File not found");
     }
 
-    // Create a probe hint using the file's extension.
-    let mut hint = Hint::new();
-    let file_path_str = file_path.as_ref().to_string_lossy();
-
-    // Open the media source.
-    let src = File::open(&file_path)?;
-
-    // Create the media source stream.
-    let mss = MediaSourceStream::new(Box::new(src), Default::default());
-    let ext = file_path_str.split('.').last().unwrap_or_default();
-    hint.with_extension(ext);
-
-    // Use the default options for metadata and format readers.
-    let fmt_opts: FormatOptions = Default::default();
-    let meta_opts: MetadataOptions = Default::default();
-
     // Probe the media source.
-    let probed = symphonia::default::get_probe()
-        .format(&hint, mss, &fmt_opts, &meta_opts)
+    let probed = registry()
+        .probe(file_path.as_ref())
         .map_err(|e| anyhow::anyhow!("Failed to probe file: {}", e))?;
 
     Ok(probed)
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+/// Loudness of the R128 reference level relative to ReplayGain 2.0 (-23 vs -18 LUFS).
+const R128_TO_REPLAY_GAIN_OFFSET: f64 = 5.0;
+
+/// ReplayGain values read from file tags. Gains are in dB, peaks are linear.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayGainTags {
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+impl ReplayGainTags {
+    pub fn is_empty(&self) -> bool {
+        self.track_gain.is_none() && self.album_gain.is_none()
+    }
+}
+
+/// Maps non-standard tag keys (ID3v2 `TXXX`, MP4 freeform atoms, Opus R128
+/// headers, ...) onto the key names used by `get_metadata`.
+pub fn normalize_replay_gain_key(raw_key: &str) -> Option<&'static str> {
+    // MP4 freeform atoms are stored as `----:com.apple.iTunes:replaygain_track_gain`,
+    // ID3v2 user text frames as `TXXX:REPLAYGAIN_TRACK_GAIN`
+    let key = raw_key
+        .rsplit(':')
+        .next()
+        .unwrap_or(raw_key)
+        .to_ascii_lowercase();
+
+    match key.as_str() {
+        "replaygain_track_gain" => Some("replaygain_track_gain"),
+        "replaygain_track_peak" => Some("replaygain_track_peak"),
+        "replaygain_album_gain" => Some("replaygain_album_gain"),
+        "replaygain_album_peak" => Some("replaygain_album_peak"),
+        "r128_track_gain" => Some("r128_track_gain"),
+        "r128_album_gain" => Some("r128_album_gain"),
+        _ => None,
+    }
+}
+
+/// Parses a gain value such as `-6.54 dB` or `+1.2dB`.
+fn parse_gain(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .or_else(|| value.strip_suffix("DB"))
+        .unwrap_or(value);
+
+    value.trim().trim_start_matches('+').parse::<f64>().ok()
+}
+
+fn parse_peak(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|x| x.is_finite() && *x >= 0.0)
+}
+
+/// R128 gains are Q7.8 fixed point integers relative to -23 LUFS.
+fn parse_r128_gain(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<i32>()
+        .ok()
+        .map(|x| x as f64 / 256.0 + R128_TO_REPLAY_GAIN_OFFSET)
+}
+
+/// Extracts ReplayGain information from the key/value pairs returned by
+/// `get_metadata`. ReplayGain tags take precedence over R128 tags.
+pub fn parse_replay_gain(metadata: &[(String, String)]) -> ReplayGainTags {
+    let metadata: HashMap<&str, &str> = metadata
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+
+    let get = |key: &str| metadata.get(key).copied();
+
+    ReplayGainTags {
+        track_gain: get("replaygain_track_gain")
+            .and_then(parse_gain)
+            .or_else(|| get("r128_track_gain").and_then(parse_r128_gain)),
+        track_peak: get("replaygain_track_peak").and_then(parse_peak),
+        album_gain: get("replaygain_album_gain")
+            .and_then(parse_gain)
+            .or_else(|| get("r128_album_gain").and_then(parse_r128_gain)),
+        album_peak: get("replaygain_album_peak").and_then(parse_peak),
+    }
+}
@@ -1,27 +1,37 @@
 use std::path::{Path, PathBuf};
 use walkdir::{DirEntry, WalkDir};
 
+use formats::{registry, FormatSupport};
+
 fn is_audio_file(entry: &DirEntry) -> bool {
-    if let Some(ext) = entry.path().extension() {
-        matches!(
-            ext.to_str().unwrap_or("").to_lowercase().as_str(),
-            "mp3" | "flac" | "wav" | "aac" | "ogg" | "m4a" | "opus" | "vorbis"
-        )
-    } else {
-        false
-    }
+    registry().is_audio_path(entry.path())
+}
+
+/// The `.rune` directory below the library root holds the database and the
+/// duplicates set aside by merges, it is not part of the library.
+fn is_rune_dir(entry: &DirEntry) -> bool {
+    entry.depth() == 1 && entry.file_type().is_dir() && entry.file_name() == ".rune"
 }
 
 fn scan_audio_files<P: AsRef<Path>>(path: &P) -> impl Iterator<Item = DirEntry> + Send {
     WalkDir::new(path)
         .into_iter()
+        .filter_entry(|entry| !is_rune_dir(entry))
         .filter_map(Result::ok)
         .filter(|entry| entry.file_type().is_file() && is_audio_file(entry))
 }
 
+/// An audio file that was found while scanning but can not be decoded.
+#[derive(Debug, Clone)]
+pub struct UnsupportedFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
 pub struct AudioScanner<'a> {
     root_path: PathBuf,
     iterator: Box<dyn Iterator<Item = DirEntry> + Send + 'a>,
+    unsupported: Vec<UnsupportedFile>,
     ended: bool,
 }
 
@@ -30,15 +40,25 @@ impl<'a> AudioScanner<'a> {
         AudioScanner {
             root_path: path.as_ref().to_path_buf(),
             iterator: Box::new(scan_audio_files(path)),
+            unsupported: Vec::new(),
             ended: false,
         }
     }
 
+    /// Reads up to `count` decodable files, files that can not be decoded are
+    /// kept aside and can be collected with `take_unsupported`.
     pub fn read_files(&mut self, count: usize) -> Vec<DirEntry> {
         let mut files = Vec::new();
-        for _ in 0..count {
+        while files.len() < count {
             if let Some(file) = self.iterator.next() {
-                files.push(file);
+                match registry().check(file.path()) {
+                    FormatSupport::Supported => files.push(file),
+                    FormatSupport::Unsupported(reason) => self.unsupported.push(UnsupportedFile {
+                        path: file.path().to_path_buf(),
+                        reason,
+                    }),
+                    FormatSupport::NotAudio => {}
+                }
             } else {
                 self.ended = true;
                 break;
@@ -47,6 +67,11 @@ impl<'a> AudioScanner<'a> {
         files
     }
 
+    /// Returns the files that were skipped since the last call.
+    pub fn take_unsupported(&mut self) -> Vec<UnsupportedFile> {
+        std::mem::take(&mut self.unsupported)
+    }
+
     pub fn has_ended(&self) -> bool {
         self.ended
     }
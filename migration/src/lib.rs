@@ -20,6 +20,17 @@ mod m20231110_000019_create_playback_queue_table;
 mod m20231117_000020_create_log_table;
 mod m20250311_000021_create_genres_table;
 mod m20250311_000021_create_media_file_genres_table;
+mod m20250401_000022_create_media_loudness_table;
+mod m20250402_000023_add_columns_silence;
+mod m20250403_000024_create_equalizer_preset_bands_table;
+mod m20250403_000024_create_equalizer_presets_table;
+mod m20250404_000025_add_columns_cue_track;
+mod m20250405_000026_create_tag_edit_batches_table;
+mod m20250405_000026_create_tag_edit_entries_table;
+mod m20250406_000027_create_play_events_table;
+mod m20250407_000028_create_scrobble_queue_table;
+mod m20250408_000029_create_search_trigram_index;
+mod m20250409_000030_create_media_rhythm_table;
 
 pub struct Migrator;
 
@@ -47,6 +58,17 @@ impl MigratorTrait for Migrator {
             Box::new(m20231117_000020_create_log_table::Migration),
             Box::new(m20250311_000021_create_genres_table::Migration),
             Box::new(m20250311_000021_create_media_file_genres_table::Migration),
+            Box::new(m20250401_000022_create_media_loudness_table::Migration),
+            Box::new(m20250402_000023_add_columns_silence::Migration),
+            Box::new(m20250403_000024_create_equalizer_presets_table::Migration),
+            Box::new(m20250403_000024_create_equalizer_preset_bands_table::Migration),
+            Box::new(m20250404_000025_add_columns_cue_track::Migration),
+            Box::new(m20250405_000026_create_tag_edit_batches_table::Migration),
+            Box::new(m20250405_000026_create_tag_edit_entries_table::Migration),
+            Box::new(m20250406_000027_create_play_events_table::Migration),
+            Box::new(m20250407_000028_create_scrobble_queue_table::Migration),
+            Box::new(m20250408_000029_create_search_trigram_index::Migration),
+            Box::new(m20250409_000030_create_media_rhythm_table::Migration),
         ]
     }
 }
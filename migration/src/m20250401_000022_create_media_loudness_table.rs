@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250401_000022_create_media_loudness_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MediaLoudness::Table)
+                    .col(
+                        ColumnDef::new(MediaLoudness::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MediaLoudness::FileId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(MediaLoudness::IntegratedLoudness).double())
+                    .col(ColumnDef::new(MediaLoudness::TruePeak).double())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-media_loudness-file_id")
+                            .from(MediaLoudness::Table, MediaLoudness::FileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MediaLoudness::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum MediaLoudness {
+    Table,
+    Id,
+    FileId,
+    IntegratedLoudness,
+    TruePeak,
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250402_000023_add_columns_silence"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaLoudness::Table)
+                    .add_column(
+                        ColumnDef::new(MediaLoudness::LeadingSilence)
+                            .double()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaLoudness::Table)
+                    .add_column(
+                        ColumnDef::new(MediaLoudness::TrailingSilence)
+                            .double()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaLoudness::Table)
+                    .drop_column(MediaLoudness::LeadingSilence)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaLoudness::Table)
+                    .drop_column(MediaLoudness::TrailingSilence)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum MediaLoudness {
+    Table,
+    LeadingSilence,
+    TrailingSilence,
+}
@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20250403_000024_create_equalizer_presets_table::EqualizerPresets;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250403_000024_create_equalizer_preset_bands_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EqualizerPresetBands::Table)
+                    .col(
+                        ColumnDef::new(EqualizerPresetBands::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(EqualizerPresetBands::PresetId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EqualizerPresetBands::Position)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EqualizerPresetBands::FilterType)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EqualizerPresetBands::Frequency)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EqualizerPresetBands::Gain)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(EqualizerPresetBands::Q).double().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-equalizer_preset_bands-preset_id")
+                            .from(EqualizerPresetBands::Table, EqualizerPresetBands::PresetId)
+                            .to(EqualizerPresets::Table, EqualizerPresets::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EqualizerPresetBands::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum EqualizerPresetBands {
+    Table,
+    Id,
+    PresetId,
+    Position,
+    FilterType,
+    Frequency,
+    Gain,
+    Q,
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250403_000024_create_equalizer_presets_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EqualizerPresets::Table)
+                    .col(
+                        ColumnDef::new(EqualizerPresets::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(EqualizerPresets::Name).string().not_null())
+                    .col(ColumnDef::new(EqualizerPresets::Preamp).double().not_null())
+                    .col(
+                        ColumnDef::new(EqualizerPresets::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EqualizerPresets::UpdatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EqualizerPresets::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum EqualizerPresets {
+    Table,
+    Id,
+    Name,
+    Preamp,
+    CreatedAt,
+    UpdatedAt,
+}
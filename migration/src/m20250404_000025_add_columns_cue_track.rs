@@ -0,0 +1,78 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250404_000025_add_columns_cue_track"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFiles::Table)
+                    .add_column(ColumnDef::new(MediaFiles::CueTrack).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFiles::Table)
+                    .add_column(ColumnDef::new(MediaFiles::StartOffset).double().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFiles::Table)
+                    .add_column(ColumnDef::new(MediaFiles::EndOffset).double().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFiles::Table)
+                    .drop_column(MediaFiles::CueTrack)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFiles::Table)
+                    .drop_column(MediaFiles::StartOffset)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFiles::Table)
+                    .drop_column(MediaFiles::EndOffset)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum MediaFiles {
+    Table,
+    CueTrack,
+    StartOffset,
+    EndOffset,
+}
@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230701_000001_create_media_files_table::MediaFiles;
+use crate::m20250405_000026_create_tag_edit_batches_table::TagEditBatches;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250405_000026_create_tag_edit_entries_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TagEditEntries::Table)
+                    .col(
+                        ColumnDef::new(TagEditEntries::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TagEditEntries::BatchId).integer().not_null())
+                    .col(ColumnDef::new(TagEditEntries::FileId).integer().not_null())
+                    .col(ColumnDef::new(TagEditEntries::MetaKey).text().not_null())
+                    .col(ColumnDef::new(TagEditEntries::OldValue).text().null())
+                    .col(ColumnDef::new(TagEditEntries::NewValue).text().null())
+                    .col(
+                        ColumnDef::new(TagEditEntries::OldPicture)
+                            .var_binary(16777216)
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-tag_edit_entries-batch_id")
+                            .from(TagEditEntries::Table, TagEditEntries::BatchId)
+                            .to(TagEditBatches::Table, TagEditBatches::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-tag_edit_entries-file_id")
+                            .from(TagEditEntries::Table, TagEditEntries::FileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TagEditEntries::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TagEditEntries {
+    Table,
+    Id,
+    BatchId,
+    FileId,
+    MetaKey,
+    OldValue,
+    NewValue,
+    OldPicture,
+}
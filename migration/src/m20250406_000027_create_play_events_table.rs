@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250406_000027_create_play_events_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PlayEvents::Table)
+                    .col(
+                        ColumnDef::new(PlayEvents::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PlayEvents::MediaFileId).integer().not_null())
+                    .col(ColumnDef::new(PlayEvents::StartedAt).timestamp().not_null())
+                    .col(
+                        ColumnDef::new(PlayEvents::ListenedDuration)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlayEvents::CompletionRatio)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PlayEvents::Source).text().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-play_events-media_file_id")
+                            .from(PlayEvents::Table, PlayEvents::MediaFileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-play_events-started_at")
+                    .table(PlayEvents::Table)
+                    .col(PlayEvents::StartedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-play_events-media_file_id")
+                    .table(PlayEvents::Table)
+                    .col(PlayEvents::MediaFileId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PlayEvents::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PlayEvents {
+    Table,
+    Id,
+    MediaFileId,
+    StartedAt,
+    ListenedDuration,
+    CompletionRatio,
+    Source,
+}
@@ -0,0 +1,94 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250407_000028_create_scrobble_queue_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScrobbleQueue::Table)
+                    .col(
+                        ColumnDef::new(ScrobbleQueue::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ScrobbleQueue::Service).text().not_null())
+                    .col(ColumnDef::new(ScrobbleQueue::Artist).text().not_null())
+                    .col(ColumnDef::new(ScrobbleQueue::Track).text().not_null())
+                    .col(ColumnDef::new(ScrobbleQueue::Album).text().null())
+                    .col(ColumnDef::new(ScrobbleQueue::AlbumArtist).text().null())
+                    .col(ColumnDef::new(ScrobbleQueue::Duration).integer().null())
+                    .col(
+                        ColumnDef::new(ScrobbleQueue::Timestamp)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ScrobbleQueue::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(ScrobbleQueue::LastError).text().null())
+                    .col(
+                        ColumnDef::new(ScrobbleQueue::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ScrobbleQueue::DeliveredAt)
+                            .timestamp()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-scrobble_queue-service-artist-track-timestamp")
+                    .table(ScrobbleQueue::Table)
+                    .col(ScrobbleQueue::Service)
+                    .col(ScrobbleQueue::Artist)
+                    .col(ScrobbleQueue::Track)
+                    .col(ScrobbleQueue::Timestamp)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ScrobbleQueue::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum ScrobbleQueue {
+    Table,
+    Id,
+    Service,
+    Artist,
+    Track,
+    Album,
+    AlbumArtist,
+    Duration,
+    Timestamp,
+    Attempts,
+    LastError,
+    CreatedAt,
+    DeliveredAt,
+}
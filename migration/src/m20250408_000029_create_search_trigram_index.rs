@@ -0,0 +1,117 @@
+use std::collections::BTreeSet;
+
+use async_trait::async_trait;
+use deunicode::deunicode;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DbBackend, Statement, Value};
+
+/// Rows inserted per statement, kept below the SQLite variable limit.
+const INSERT_CHUNK_SIZE: usize = 300;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250408_000029_create_search_trigram_index"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Candidates for typo tolerant search, names are compared by trigrams
+        db.execute_unprepared(
+            "CREATE VIRTUAL TABLE search_trigram USING fts5(key UNINDEXED, entry_type UNINDEXED, doc, tokenize = 'trigram');",
+        )
+        .await?;
+
+        // Mixes were never indexed, names are indexed as written and
+        // transliterated, the way `add_term` does it
+        let mixes = db
+            .query_all(Statement::from_string(
+                DbBackend::Sqlite,
+                "SELECT id, name FROM mixes;",
+            ))
+            .await?;
+
+        let mut rows: Vec<[Value; 3]> = Vec::new();
+        for mix in mixes {
+            let id: i32 = mix.try_get("", "id")?;
+            let name: String = mix.try_get("", "name")?;
+            rows.push([id.to_string().into(), "mix".into(), deunicode(&name).into()]);
+            rows.push([id.to_string().into(), "mix".into(), name.into()]);
+        }
+        insert_rows(
+            db,
+            "search_index (id, key, entry_type, doc)",
+            "('', ?, ?, ?)",
+            rows,
+        )
+        .await?;
+
+        // The trigram index only holds the transliterated names
+        let docs: BTreeSet<(String, String, String)> = db
+            .query_all(Statement::from_string(
+                DbBackend::Sqlite,
+                "SELECT key, entry_type, doc FROM search_index;",
+            ))
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok((
+                    row.try_get("", "key")?,
+                    row.try_get("", "entry_type")?,
+                    deunicode(&row.try_get::<String>("", "doc")?),
+                ))
+            })
+            .collect::<Result<_, DbErr>>()?;
+
+        let rows = docs
+            .into_iter()
+            .map(|(key, entry_type, doc)| [key.into(), entry_type.into(), doc.into()])
+            .collect();
+        insert_rows(
+            db,
+            "search_trigram (key, entry_type, doc)",
+            "(?, ?, ?)",
+            rows,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DELETE FROM search_index WHERE entry_type = 'mix'")
+            .await?;
+        db.execute_unprepared("DROP TABLE `search_trigram`").await?;
+
+        Ok(())
+    }
+}
+
+async fn insert_rows<C>(
+    db: &C,
+    table: &str,
+    placeholders: &str,
+    rows: Vec<[Value; 3]>,
+) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    for chunk in rows.chunks(INSERT_CHUNK_SIZE) {
+        let values = vec![placeholders; chunk.len()].join(", ");
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            format!("INSERT INTO {} VALUES {};", table, values),
+            chunk.iter().flatten().cloned(),
+        ))
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230701_000001_create_media_files_table::MediaFiles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250409_000030_create_media_rhythm_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MediaRhythm::Table)
+                    .col(
+                        ColumnDef::new(MediaRhythm::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MediaRhythm::FileId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(MediaRhythm::Bpm).double())
+                    // Position on the Camelot wheel, from 0 (1A) to 23 (12B)
+                    .col(ColumnDef::new(MediaRhythm::MusicalKey).integer())
+                    .col(ColumnDef::new(MediaRhythm::KeyStrength).double())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-media_rhythm-file_id")
+                            .from(MediaRhythm::Table, MediaRhythm::FileId)
+                            .to(MediaFiles::Table, MediaFiles::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-media_rhythm-bpm")
+                    .table(MediaRhythm::Table)
+                    .col(MediaRhythm::Bpm)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MediaRhythm::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum MediaRhythm {
+    Table,
+    Id,
+    FileId,
+    Bpm,
+    MusicalKey,
+    KeyStrength,
+}
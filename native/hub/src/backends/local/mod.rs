@@ -1,6 +1,7 @@
 #[macro_use]
 mod gui_request;
 
+use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 use std::sync::OnceLock;
 
@@ -21,7 +22,10 @@ use ::scrobbling::manager::ScrobblingManager;
 use crate::listen_local_gui_event;
 use crate::messages::*;
 use crate::server::ServerManager;
+use crate::utils::history::PlayHistoryTracker;
 use crate::utils::player::initialize_local_player;
+use crate::utils::scrobble_store::DatabaseScrobbleStore;
+use crate::utils::watcher::initialize_library_watcher;
 use crate::utils::Broadcaster;
 use crate::utils::DatabaseConnections;
 use crate::utils::GlobalParams;
@@ -59,6 +63,12 @@ pub async fn local_player_loop(
         let sfx_player: Arc<Mutex<SfxPlayer>> = Arc::new(Mutex::new(sfx_player));
 
         let main_cancel_token = Arc::new(main_cancel_token);
+        let play_history = Arc::new(Mutex::new(PlayHistoryTracker::default()));
+
+        scrobbler
+            .lock()
+            .await
+            .set_store(Arc::new(DatabaseScrobbleStore::new(main_db.clone())));
 
         let device_scanner = Arc::new(DiscoveryService::without_store());
         let permission_manager =
@@ -72,12 +82,37 @@ pub async fn local_player_loop(
             lib_path.clone(),
             main_db.clone(),
             player.clone(),
+            play_history.clone(),
             scrobbler.clone(),
             broadcaster.clone(),
             cert_validator.clone(),
             permission_manager.clone(),
         ));
 
+        let library_version = Arc::new(AtomicU32::new(1));
+
+        info!("Initializing library watcher");
+        let watcher_lib_path = lib_path.clone();
+        let watcher_main_db = main_db.clone();
+        let watcher_task_tokens = task_tokens.clone();
+        let watcher_library_version = library_version.clone();
+        let watcher_broadcaster = broadcaster.clone();
+        let watcher_cancel_token = main_cancel_token.clone();
+        tokio::spawn(async move {
+            if let Err(e) = initialize_library_watcher(
+                watcher_lib_path,
+                watcher_main_db,
+                watcher_task_tokens,
+                watcher_library_version,
+                watcher_broadcaster,
+                watcher_cancel_token,
+            )
+            .await
+            {
+                error!("Library watcher failed: {:?}", e);
+            }
+        });
+
         info!("Initializing UI events");
         let global_params = GlobalParams {
             lib_path,
@@ -86,7 +121,9 @@ pub async fn local_player_loop(
             recommend_db,
             main_token: Arc::clone(&main_cancel_token),
             task_tokens,
+            library_version,
             player,
+            play_history,
             sfx_player,
             scrobbler,
             broadcaster,
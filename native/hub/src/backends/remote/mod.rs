@@ -3,7 +3,7 @@ mod remote_request;
 
 use std::{
     collections::HashMap,
-    sync::{Arc, OnceLock},
+    sync::{atomic::AtomicU32, Arc, OnceLock},
 };
 
 use anyhow::{bail, Context, Result};
@@ -35,7 +35,8 @@ use crate::{
     register_remote_handlers,
     server::{api::check_fingerprint, generate_or_load_certificates},
     utils::{
-        GlobalParams, LocalGuiBroadcaster, ParamsExtractor, RinfRustSignal, RunningMode, TaskTokens,
+        history::PlayHistoryTracker, GlobalParams, LocalGuiBroadcaster, ParamsExtractor,
+        RinfRustSignal, RunningMode, TaskTokens,
     },
     Signal,
 };
@@ -274,7 +275,9 @@ impl WebSocketDartBridge {
                         scan_token: None,
                         analyze_token: None,
                     })),
+                    library_version: Arc::new(AtomicU32::new(1)),
                     player: Arc::new(Mutex::new(MockPlayer {})),
+                    play_history: Arc::new(Mutex::new(PlayHistoryTracker::default())),
                     sfx_player,
                     scrobbler: Arc::new(Mutex::new(MockScrobblingManager::new())),
                     broadcaster: Arc::new(LocalGuiBroadcaster),
@@ -347,6 +350,7 @@ pub async fn server_player_loop(url: &str, config_path: &str, alias: &str) -> Re
             bridge,
             ScanAudioLibraryProgress,
             ScanAudioLibraryResponse,
+            LibraryFilesChanged,
             SetMediaLibraryPathResponse,
             AnalyzeAudioLibraryProgress,
             AnalyzeAudioLibraryResponse,
@@ -5,6 +5,7 @@ use anyhow::Result;
 use futures::future::join_all;
 
 use database::actions::collection::{CollectionQuery, CollectionQueryListMode, UnifiedCollection};
+use database::actions::mixes::GroupedQuery;
 use database::connection::{MainDbConnection, RecommendationDbConnection};
 use database::entities::{albums, artists, genres, mix_queries, mixes, playlists};
 
@@ -163,10 +164,27 @@ impl From<mix_queries::Model> for MixQuery {
         MixQuery {
             operator: model.operator,
             parameter: model.parameter,
+            group: model.group,
         }
     }
 }
 
+impl From<GroupedQuery> for MixQuery {
+    fn from(query: GroupedQuery) -> Self {
+        MixQuery {
+            operator: query.operator,
+            parameter: query.parameter,
+            group: query.group,
+        }
+    }
+}
+
+impl From<MixQuery> for GroupedQuery {
+    fn from(query: MixQuery) -> Self {
+        GroupedQuery::new(query.operator, query.parameter, query.group)
+    }
+}
+
 impl Collection {
     pub async fn from_model<T: CollectionQuery>(
         main_db: &MainDbConnection,
@@ -178,10 +196,7 @@ impl Collection {
             queries: T::query_builder(main_db, model.id())
                 .await?
                 .into_iter()
-                .map(|x| MixQuery {
-                    operator: x.0,
-                    parameter: x.1,
-                })
+                .map(MixQuery::from)
                 .collect(),
             collection_type: T::collection_type().into(),
             cover_art_map: HashMap::new(),
@@ -195,14 +210,7 @@ impl Collection {
         Collection {
             id: x.id,
             name: x.name,
-            queries: x
-                .queries
-                .into_iter()
-                .map(|x| MixQuery {
-                    operator: x.0,
-                    parameter: x.1,
-                })
-                .collect(),
+            queries: x.queries.into_iter().map(MixQuery::from).collect(),
             collection_type: x.collection_type.into(),
             cover_art_map: HashMap::new(),
             readonly: x.readonly,
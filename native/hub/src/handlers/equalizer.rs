@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::sync::Mutex;
+
+use ::playback::dsp::{DspSettings, EqualizerBand as DspEqualizerBand};
+use ::playback::player::Playable;
+use database::actions::equalizer::{
+    create_equalizer_preset, get_all_equalizer_presets, remove_equalizer_preset,
+    update_equalizer_preset, EqualizerPreset as EqualizerPresetModel,
+};
+use database::connection::MainDbConnection;
+
+use crate::utils::{GlobalParams, ParamsExtractor};
+use crate::{messages::*, Session, Signal};
+
+impl From<&EqualizerBand> for DspEqualizerBand {
+    fn from(x: &EqualizerBand) -> Self {
+        DspEqualizerBand {
+            filter_type: x.filter_type.into(),
+            frequency: x.frequency,
+            gain: x.gain,
+            q: x.q,
+        }
+    }
+}
+
+impl From<&DspEqualizerBand> for EqualizerBand {
+    fn from(x: &DspEqualizerBand) -> Self {
+        EqualizerBand {
+            filter_type: x.filter_type.into(),
+            frequency: x.frequency,
+            gain: x.gain,
+            q: x.q,
+        }
+    }
+}
+
+impl From<EqualizerPresetModel> for EqualizerPreset {
+    fn from(x: EqualizerPresetModel) -> Self {
+        EqualizerPreset {
+            id: x.id,
+            name: x.name,
+            preamp: x.preamp,
+            bands: x.bands.iter().map(|band| band.into()).collect(),
+        }
+    }
+}
+
+impl ParamsExtractor for SetEqualizerRequest {
+    type Params = (Arc<Mutex<dyn Playable>>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.player),)
+    }
+}
+
+impl Signal for SetEqualizerRequest {
+    type Params = (Arc<Mutex<dyn Playable>>,);
+    type Response = ();
+
+    async fn handle(
+        &self,
+        (player,): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        player.lock().await.set_dsp(DspSettings {
+            enabled: dart_signal.enabled,
+            preamp: dart_signal.preamp,
+            bands: dart_signal.bands.iter().map(|band| band.into()).collect(),
+        });
+
+        Ok(Some(()))
+    }
+}
+
+impl ParamsExtractor for FetchAllEqualizerPresetsRequest {
+    type Params = (Arc<MainDbConnection>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.main_db),)
+    }
+}
+
+impl Signal for FetchAllEqualizerPresetsRequest {
+    type Params = (Arc<MainDbConnection>,);
+    type Response = FetchAllEqualizerPresetsResponse;
+
+    async fn handle(
+        &self,
+        (main_db,): Self::Params,
+        _session: Option<Session>,
+        _dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let presets = get_all_equalizer_presets(&main_db)
+            .await
+            .with_context(|| "Failed to fetch all equalizer presets")?;
+
+        Ok(Some(FetchAllEqualizerPresetsResponse {
+            presets: presets.into_iter().map(|x| x.into()).collect(),
+        }))
+    }
+}
+
+impl ParamsExtractor for CreateEqualizerPresetRequest {
+    type Params = (Arc<MainDbConnection>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.main_db),)
+    }
+}
+
+impl Signal for CreateEqualizerPresetRequest {
+    type Params = (Arc<MainDbConnection>,);
+    type Response = CreateEqualizerPresetResponse;
+
+    async fn handle(
+        &self,
+        (main_db,): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+
+        let preset = create_equalizer_preset(
+            &main_db,
+            request.name.clone(),
+            request.preamp,
+            request.bands.iter().map(|band| band.into()).collect(),
+        )
+        .await
+        .with_context(|| "Failed to create equalizer preset")?;
+
+        Ok(Some(CreateEqualizerPresetResponse {
+            preset: Some(preset.into()),
+        }))
+    }
+}
+
+impl ParamsExtractor for UpdateEqualizerPresetRequest {
+    type Params = (Arc<MainDbConnection>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.main_db),)
+    }
+}
+
+impl Signal for UpdateEqualizerPresetRequest {
+    type Params = (Arc<MainDbConnection>,);
+    type Response = UpdateEqualizerPresetResponse;
+
+    async fn handle(
+        &self,
+        (main_db,): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+
+        let preset = update_equalizer_preset(
+            &main_db,
+            request.preset_id,
+            request.name.clone(),
+            request.preamp,
+            request.bands.iter().map(|band| band.into()).collect(),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to update equalizer preset with id: {}",
+                request.preset_id
+            )
+        })?;
+
+        Ok(Some(UpdateEqualizerPresetResponse {
+            preset: Some(preset.into()),
+        }))
+    }
+}
+
+impl ParamsExtractor for RemoveEqualizerPresetRequest {
+    type Params = (Arc<MainDbConnection>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.main_db),)
+    }
+}
+
+impl Signal for RemoveEqualizerPresetRequest {
+    type Params = (Arc<MainDbConnection>,);
+    type Response = RemoveEqualizerPresetResponse;
+
+    async fn handle(
+        &self,
+        (main_db,): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+
+        remove_equalizer_preset(&main_db, request.preset_id)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to remove equalizer preset with id: {}",
+                    request.preset_id
+                )
+            })?;
+
+        Ok(Some(RemoveEqualizerPresetResponse {
+            preset_id: request.preset_id,
+            success: true,
+        }))
+    }
+}
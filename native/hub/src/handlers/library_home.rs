@@ -12,7 +12,7 @@ use database::actions::collection::{
 };
 use database::actions::file::{get_media_files, get_random_files, get_reverse_listed_media_files};
 use database::actions::metadata::{get_metadata_summary_by_files, MetadataSummary};
-use database::actions::mixes::query_mix_media_files;
+use database::actions::mixes::{query_mix_media_files, GroupedQuery};
 use database::connection::MainDbConnection;
 use database::connection::RecommendationDbConnection;
 use database::entities::{albums, artists, genres, media_files, mixes, playlists};
@@ -107,10 +107,10 @@ fn create_track_collection(
 ) -> UnifiedCollection {
     let mut queries = Vec::new();
     for item in all_ids.iter().skip(idx) {
-        queries.push(("lib::track".to_string(), item.clone()));
+        queries.push(GroupedQuery::new("lib::track", item.clone(), 0));
     }
     for item in all_ids.iter().take(idx) {
-        queries.push(("lib::track".to_string(), item.clone()));
+        queries.push(GroupedQuery::new("lib::track", item.clone(), 0));
     }
 
     UnifiedCollection {
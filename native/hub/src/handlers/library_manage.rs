@@ -1,19 +1,22 @@
 use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use log::{debug, info, warn};
-use tokio::{sync::Mutex, task};
+use tokio::{runtime::Handle, sync::Mutex, task};
 use tokio_util::sync::CancellationToken;
 
 use database::actions::analysis::analysis_audio_library;
 use database::actions::cover_art::scan_cover_arts;
+use database::actions::duplicates::{find_duplicate_files, merge_duplicate_files, DuplicateMatch};
 use database::actions::metadata::scan_audio_library;
 use database::actions::recommendation::sync_recommendation;
 use database::connection::MainDbConnection;
 use database::connection::RecommendationDbConnection;
 
 use crate::utils::determine_batch_size;
+use crate::utils::history::{flush_play_history, PlayHistoryTracker};
 use crate::utils::Broadcaster;
 use crate::utils::GlobalParams;
 use crate::utils::ParamsExtractor;
@@ -22,11 +25,19 @@ use crate::TaskTokens;
 use crate::{messages::*, Signal};
 
 impl ParamsExtractor for CloseLibraryRequest {
-    type Params = (Arc<String>, Arc<CancellationToken>, Arc<Mutex<TaskTokens>>);
+    type Params = (
+        Arc<String>,
+        Arc<MainDbConnection>,
+        Arc<Mutex<PlayHistoryTracker>>,
+        Arc<CancellationToken>,
+        Arc<Mutex<TaskTokens>>,
+    );
 
     fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
         (
             Arc::clone(&all_params.lib_path),
+            Arc::clone(&all_params.main_db),
+            Arc::clone(&all_params.play_history),
             Arc::clone(&all_params.main_token),
             Arc::clone(&all_params.task_tokens),
         )
@@ -34,12 +45,18 @@ impl ParamsExtractor for CloseLibraryRequest {
 }
 
 impl Signal for CloseLibraryRequest {
-    type Params = (Arc<String>, Arc<CancellationToken>, Arc<Mutex<TaskTokens>>);
+    type Params = (
+        Arc<String>,
+        Arc<MainDbConnection>,
+        Arc<Mutex<PlayHistoryTracker>>,
+        Arc<CancellationToken>,
+        Arc<Mutex<TaskTokens>>,
+    );
     type Response = CloseLibraryResponse;
 
     async fn handle(
         &self,
-        (lib_path, main_token, task_tokens): Self::Params,
+        (lib_path, main_db, play_history, main_token, task_tokens): Self::Params,
         _session: Option<Session>,
         dart_signal: &Self,
     ) -> Result<Option<Self::Response>> {
@@ -63,6 +80,7 @@ impl Signal for CloseLibraryRequest {
             token.cancel();
         }
 
+        flush_play_history(&main_db, &play_history).await;
         main_token.cancel();
 
         Ok(Some(CloseLibraryResponse {
@@ -75,6 +93,7 @@ impl ParamsExtractor for ScanAudioLibraryRequest {
     type Params = (
         Arc<MainDbConnection>,
         Arc<Mutex<TaskTokens>>,
+        Arc<AtomicU32>,
         Arc<dyn Broadcaster>,
     );
 
@@ -82,6 +101,7 @@ impl ParamsExtractor for ScanAudioLibraryRequest {
         (
             Arc::clone(&all_params.main_db),
             Arc::clone(&all_params.task_tokens),
+            Arc::clone(&all_params.library_version),
             Arc::clone(&all_params.broadcaster),
         )
     }
@@ -91,13 +111,14 @@ impl Signal for ScanAudioLibraryRequest {
     type Params = (
         Arc<MainDbConnection>,
         Arc<Mutex<TaskTokens>>,
+        Arc<AtomicU32>,
         Arc<dyn Broadcaster>,
     );
     type Response = ();
 
     async fn handle(
         &self,
-        (main_db, task_tokens, broadcaster): Self::Params,
+        (main_db, task_tokens, library_version, broadcaster): Self::Params,
         _session: Option<Session>,
         dart_signal: &Self,
     ) -> Result<Option<()>> {
@@ -137,6 +158,9 @@ impl Signal for ScanAudioLibraryRequest {
                     )
                     .await?;
 
+                    // Even a cancelled scan may have changed some files
+                    library_version.fetch_add(1, Ordering::Relaxed);
+
                     if new_token.is_cancelled() {
                         info!("Operation cancelled during artist processing.");
 
@@ -176,6 +200,10 @@ impl Signal for ScanAudioLibraryRequest {
                 }
                 .await;
 
+                // Mark the scan as finished whether it succeeded or not, the
+                // library watcher waits for running scans
+                new_token.cancel();
+
                 result?;
                 Ok::<(), anyhow::Error>(())
             })
@@ -329,3 +357,104 @@ impl Signal for CancelTaskRequest {
         }))
     }
 }
+
+impl ParamsExtractor for FindDuplicateFilesRequest {
+    type Params = (Arc<String>, Arc<MainDbConnection>, Arc<CancellationToken>);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (
+            Arc::clone(&all_params.lib_path),
+            Arc::clone(&all_params.main_db),
+            Arc::clone(&all_params.main_token),
+        )
+    }
+}
+
+impl Signal for FindDuplicateFilesRequest {
+    type Params = (Arc<String>, Arc<MainDbConnection>, Arc<CancellationToken>);
+    type Response = FindDuplicateFilesResponse;
+
+    async fn handle(
+        &self,
+        (lib_path, main_db, main_token): Self::Params,
+        _session: Option<Session>,
+        _dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        // Fingerprinting reads every candidate file, so the search runs on a
+        // blocking thread and stops when the library is closed
+        let handle = Handle::current();
+        let result = task::spawn_blocking(move || {
+            handle.block_on(async move {
+                find_duplicate_files(&main_db, Path::new(&*lib_path), Some(&*main_token)).await
+            })
+        })
+        .await?;
+
+        match result {
+            Ok(groups) => Ok(Some(FindDuplicateFilesResponse {
+                groups: groups
+                    .into_iter()
+                    .map(|group| DuplicateFileGroup {
+                        matched_by: match group.matched_by {
+                            DuplicateMatch::FileHash => DuplicateMatchType::FileHash,
+                            DuplicateMatch::Fingerprint => DuplicateMatchType::Fingerprint,
+                            DuplicateMatch::Analysis => DuplicateMatchType::Analysis,
+                        } as i32,
+                        keeper_id: group.keeper_id,
+                        file_ids: group.file_ids,
+                    })
+                    .collect(),
+                success: true,
+                error: String::new(),
+            })),
+            Err(e) => Ok(Some(FindDuplicateFilesResponse {
+                groups: vec![],
+                success: false,
+                error: format!("{:#?}", e),
+            })),
+        }
+    }
+}
+
+impl ParamsExtractor for MergeDuplicateFilesRequest {
+    type Params = (Arc<String>, Arc<MainDbConnection>);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (
+            Arc::clone(&all_params.lib_path),
+            Arc::clone(&all_params.main_db),
+        )
+    }
+}
+
+impl Signal for MergeDuplicateFilesRequest {
+    type Params = (Arc<String>, Arc<MainDbConnection>);
+    type Response = MergeDuplicateFilesResponse;
+
+    async fn handle(
+        &self,
+        (lib_path, main_db): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+
+        match merge_duplicate_files(
+            &main_db,
+            Path::new(&*lib_path),
+            request.keeper_id,
+            &request.duplicate_ids,
+        )
+        .await
+        {
+            Ok(_) => Ok(Some(MergeDuplicateFilesResponse {
+                success: true,
+                error: String::new(),
+            })),
+            Err(e) => Ok(Some(MergeDuplicateFilesResponse {
+                success: false,
+                error: format!("{:#?}", e),
+            })),
+        }
+    }
+}
@@ -1,11 +1,19 @@
-use std::{path::Path, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 
 use ::database::{
     connection::MainDbConnection, playing_item::dispatcher::PlayingItemActionDispatcher,
 };
-use ::lyric::{lrc::parse_lrc, parser::parse_audio_lyrics};
+use ::lyric::{
+    lrc::parse_lrc,
+    parser::{find_audio_lyrics, parse_audio_lyrics},
+    types::{LyricFile, LyricFormat, LyricLine, TimeTag, VoiceType},
+    writer::save_audio_lyrics,
+};
 use ::metadata::reader::get_lyrics;
 use ::playback::player::PlayingItem;
 
@@ -15,6 +23,97 @@ use crate::{
     Session, Signal,
 };
 
+/// Independent files name any path on the host, so remote clients only
+/// reach the lyrics of library files.
+fn check_item_access(item: &PlayingItem, session: &Option<Session>) -> Result<()> {
+    if session.is_some() && matches!(item, PlayingItem::IndependentFile(_)) {
+        bail!("Remote clients can only access the lyrics of library files");
+    }
+
+    Ok(())
+}
+
+async fn item_file_path(
+    lib_path: &str,
+    main_db: &MainDbConnection,
+    item: &PlayingItemRequest,
+    session: &Option<Session>,
+) -> Result<Option<PathBuf>> {
+    let parsed_item: PlayingItem = item.clone().into();
+    check_item_access(&parsed_item, session)?;
+
+    let dispatcher = PlayingItemActionDispatcher::new();
+
+    let paths = dispatcher
+        .get_file_path(Path::new(lib_path), main_db, [parsed_item.clone()].as_ref())
+        .await?;
+
+    Ok(paths.get(&parsed_item).cloned())
+}
+
+/// Sidecar files come first, so lyrics saved next to the audio file win
+/// over the ones embedded in its tags.
+fn load_lyrics(path: &Path) -> Option<Result<LyricFile>> {
+    parse_audio_lyrics(path.to_path_buf())
+        .or_else(|| get_lyrics(path).unwrap_or_default().map(|x| parse_lrc(&x)))
+}
+
+fn voice_type_to_proto(voice_type: VoiceType) -> LyricVoiceType {
+    match voice_type {
+        VoiceType::Male => LyricVoiceType::Male,
+        VoiceType::Female => LyricVoiceType::Female,
+        VoiceType::Duet => LyricVoiceType::Duet,
+        VoiceType::Default => LyricVoiceType::Default,
+    }
+}
+
+fn voice_type_from_proto(voice_type: LyricVoiceType) -> VoiceType {
+    match voice_type {
+        LyricVoiceType::Male => VoiceType::Male,
+        LyricVoiceType::Female => VoiceType::Female,
+        LyricVoiceType::Duet => VoiceType::Duet,
+        LyricVoiceType::Default => VoiceType::Default,
+    }
+}
+
+fn line_to_proto(line: LyricLine) -> LyricContentLine {
+    LyricContentLine {
+        start_time: line.start_time.into(),
+        end_time: line.end_time.into(),
+        sections: line
+            .word_time_tags
+            .into_iter()
+            .map(|tag| LyricContentLineSection {
+                start_time: tag.0.into(),
+                end_time: tag.1.into(),
+                content: tag.2,
+            })
+            .collect(),
+        text: line.text,
+        voice_type: voice_type_to_proto(line.voice_type) as i32,
+    }
+}
+
+fn line_from_proto(line: &LyricContentLine) -> Result<LyricLine> {
+    Ok(LyricLine {
+        start_time: TimeTag::from_milliseconds(line.start_time.into()),
+        end_time: TimeTag::from_milliseconds(line.end_time.into()),
+        voice_type: voice_type_from_proto(LyricVoiceType::try_from(line.voice_type)?),
+        text: line.text.clone(),
+        word_time_tags: line
+            .sections
+            .iter()
+            .map(|section| {
+                (
+                    TimeTag::from_milliseconds(section.start_time.into()),
+                    TimeTag::from_milliseconds(section.end_time.into()),
+                    section.content.clone(),
+                )
+            })
+            .collect(),
+    })
+}
+
 impl ParamsExtractor for GetLyricByTrackIdRequest {
     type Params = (Arc<String>, Arc<MainDbConnection>);
 
@@ -33,66 +132,30 @@ impl Signal for GetLyricByTrackIdRequest {
     async fn handle(
         &self,
         (lib_path, main_db): Self::Params,
-        _session: Option<Session>,
+        session: Option<Session>,
         dart_signal: &Self,
     ) -> Result<Option<Self::Response>> {
         let item = &dart_signal.item;
 
         if let Some(item) = item {
-            let parsed_item: PlayingItem = item.clone().into();
-            let dispatcher = PlayingItemActionDispatcher::new();
-
-            let path = dispatcher
-                .get_file_path(
-                    Path::new(lib_path.as_ref()),
-                    &main_db,
-                    [parsed_item.clone()].as_ref(),
-                )
-                .await?;
-
-            let path = path.get(&parsed_item);
+            let path = item_file_path(&lib_path, &main_db, item, &session).await?;
 
             match path {
-                Some(path) => {
-                    let build_in_lyric = get_lyrics(path).unwrap_or_default();
-
-                    let lyrics = match build_in_lyric {
-                        Some(x) => Some(parse_lrc(&x)),
-                        None => parse_audio_lyrics(path.to_path_buf()),
-                    };
-
-                    match lyrics {
-                        Some(lyric) => match lyric {
-                            Ok(lyric) => Ok(Some(GetLyricByTrackIdResponse {
-                                item: Some(item.clone()),
-                                lines: lyric
-                                    .lyrics
-                                    .into_iter()
-                                    .map(|x| LyricContentLine {
-                                        start_time: x.start_time.into(),
-                                        end_time: x.end_time.into(),
-                                        sections: x
-                                            .word_time_tags
-                                            .into_iter()
-                                            .map(|tag| LyricContentLineSection {
-                                                start_time: tag.0.into(),
-                                                end_time: tag.1.into(),
-                                                content: tag.2,
-                                            })
-                                            .collect(),
-                                    })
-                                    .collect(),
-                            })),
-                            Err(err) => {
-                                Err(err.context(format!("Unable to parse lyric: item={:#?}", item)))
-                            }
-                        },
-                        None => Ok(Some(GetLyricByTrackIdResponse {
+                Some(path) => match load_lyrics(&path) {
+                    Some(lyric) => match lyric {
+                        Ok(lyric) => Ok(Some(GetLyricByTrackIdResponse {
                             item: Some(item.clone()),
-                            lines: [].to_vec(),
+                            lines: lyric.lyrics.into_iter().map(line_to_proto).collect(),
                         })),
-                    }
-                }
+                        Err(err) => {
+                            Err(err.context(format!("Unable to parse lyric: item={:#?}", item)))
+                        }
+                    },
+                    None => Ok(Some(GetLyricByTrackIdResponse {
+                        item: Some(item.clone()),
+                        lines: [].to_vec(),
+                    })),
+                },
                 None => Ok(Some(GetLyricByTrackIdResponse {
                     item: Some(item.clone()),
                     lines: [].to_vec(),
@@ -103,3 +166,113 @@ impl Signal for GetLyricByTrackIdRequest {
         }
     }
 }
+
+/// Saves the edited lines, or the current lyrics when there are none,
+/// shifted by the offset of the request.
+async fn save_lyric(
+    lib_path: &str,
+    main_db: &MainDbConnection,
+    request: &SaveLyricRequest,
+    item: &PlayingItemRequest,
+    session: &Option<Session>,
+) -> Result<PathBuf> {
+    let path = item_file_path(lib_path, main_db, item, session)
+        .await?
+        .ok_or_else(|| anyhow!("Unable to find the file of item: {:#?}", item))?;
+
+    // Edits replace the current lyric file, so it keeps its format
+    let format = if request.format.is_empty() {
+        find_audio_lyrics(&path).map_or(LyricFormat::Lrc, |(_, format)| format)
+    } else {
+        request.format.parse::<LyricFormat>()?
+    };
+
+    let current = load_lyrics(&path).transpose();
+
+    let mut lyric_file = if request.lines.is_empty() {
+        match current? {
+            Some(lyric_file) => lyric_file,
+            None => bail!("No lyrics to save: item={:#?}", item),
+        }
+    } else {
+        LyricFile {
+            // Titles and agents are not edited, take them from the current file
+            metadata: current
+                .ok()
+                .flatten()
+                .map(|x| x.metadata)
+                .unwrap_or_default(),
+            lyrics: request
+                .lines
+                .iter()
+                .map(line_from_proto)
+                .collect::<Result<_>>()?,
+        }
+    };
+
+    lyric_file.shift(request.offset.into());
+
+    save_audio_lyrics(&path, &lyric_file, format)
+}
+
+impl ParamsExtractor for SaveLyricRequest {
+    type Params = (Arc<String>, Arc<MainDbConnection>);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (
+            Arc::clone(&all_params.lib_path),
+            Arc::clone(&all_params.main_db),
+        )
+    }
+}
+
+impl Signal for SaveLyricRequest {
+    type Params = (Arc<String>, Arc<MainDbConnection>);
+    type Response = SaveLyricResponse;
+
+    async fn handle(
+        &self,
+        (lib_path, main_db): Self::Params,
+        session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let Some(item) = &dart_signal.item else {
+            return Ok(None);
+        };
+
+        match save_lyric(&lib_path, &main_db, dart_signal, item, &session).await {
+            Ok(path) => Ok(Some(SaveLyricResponse {
+                item: Some(item.clone()),
+                path: path.to_string_lossy().to_string(),
+                success: true,
+                error: String::new(),
+            })),
+            Err(e) => Ok(Some(SaveLyricResponse {
+                item: Some(item.clone()),
+                path: String::new(),
+                success: false,
+                error: e.to_string(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_item_access() {
+        let independent = PlayingItem::IndependentFile(PathBuf::from("/etc/passwd"));
+        let in_library = PlayingItem::InLibrary(1);
+        let remote = Some(Session {
+            fingerprint: "fingerprint".to_owned(),
+            host: "192.168.1.2".to_owned(),
+        });
+
+        assert!(check_item_access(&independent, &None).is_ok());
+        assert!(check_item_access(&in_library, &None).is_ok());
+        assert!(check_item_access(&in_library, &remote).is_ok());
+        assert!(check_item_access(&independent, &remote).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Context, Result};
@@ -7,6 +8,10 @@ use sea_orm::DatabaseConnection;
 use ::database::actions::cover_art::{bake_cover_art_by_file_ids, bake_cover_art_by_media_files};
 use ::database::actions::file::{get_files_by_ids, get_media_files, list_files};
 use ::database::actions::metadata::{get_metadata_summary_by_files, get_parsed_file_by_id};
+use ::database::actions::tag_edits::{
+    edit_file_tags, undo_tag_edit_batch, CoverArtEdit, FileTagEdit as TagEditRequest,
+    PartialTagEditError, TagEdit,
+};
 use ::database::connection::MainDbConnection;
 
 use crate::utils::{parse_media_files, GlobalParams, ParamsExtractor};
@@ -211,3 +216,105 @@ impl Signal for SearchMediaFileSummaryRequest {
         }))
     }
 }
+
+impl ParamsExtractor for EditFileTagsRequest {
+    type Params = (Arc<MainDbConnection>, Arc<String>);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (
+            Arc::clone(&all_params.main_db),
+            Arc::clone(&all_params.lib_path),
+        )
+    }
+}
+
+impl Signal for EditFileTagsRequest {
+    type Params = (Arc<MainDbConnection>, Arc<String>);
+    type Response = EditFileTagsResponse;
+
+    async fn handle(
+        &self,
+        (main_db, lib_path): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+
+        let edits = request
+            .edits
+            .iter()
+            .map(|edit| {
+                let cover_art = match CoverArtEditKind::try_from(edit.cover_art_kind)? {
+                    CoverArtEditKind::Keep => CoverArtEdit::Keep,
+                    CoverArtEditKind::Remove => CoverArtEdit::Remove,
+                    CoverArtEditKind::Replace => CoverArtEdit::Replace(edit.cover_art.clone()),
+                };
+
+                Ok(TagEditRequest {
+                    file_id: edit.file_id,
+                    tags: edit
+                        .fields
+                        .iter()
+                        .map(|field| TagEdit {
+                            key: field.key.clone(),
+                            values: field.values.clone(),
+                        })
+                        .collect(),
+                    cover_art,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let response = match edit_file_tags(&main_db, Path::new(&*lib_path), edits).await {
+            Ok(batch_id) => EditFileTagsResponse {
+                batch_id,
+                success: true,
+                error: None,
+            },
+            // Files written before a failure are still part of the batch
+            Err(e) => EditFileTagsResponse {
+                batch_id: e
+                    .downcast_ref::<PartialTagEditError>()
+                    .map(|x| x.batch_id)
+                    .unwrap_or(-1),
+                success: false,
+                error: Some(format!("{:#?}", e)),
+            },
+        };
+
+        Ok(Some(response))
+    }
+}
+
+impl ParamsExtractor for UndoTagEditRequest {
+    type Params = (Arc<MainDbConnection>, Arc<String>);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (
+            Arc::clone(&all_params.main_db),
+            Arc::clone(&all_params.lib_path),
+        )
+    }
+}
+
+impl Signal for UndoTagEditRequest {
+    type Params = (Arc<MainDbConnection>, Arc<String>);
+    type Response = UndoTagEditResponse;
+
+    async fn handle(
+        &self,
+        (main_db, lib_path): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+
+        let result = undo_tag_edit_batch(&main_db, Path::new(&*lib_path), request.batch_id).await;
+
+        Ok(Some(UndoTagEditResponse {
+            batch_id: request.batch_id,
+            success: result.is_ok(),
+            error: result.err().map(|e| format!("{:#?}", e)),
+        }))
+    }
+}
@@ -7,7 +7,8 @@ use database::actions::cover_art::bake_cover_art_by_media_files;
 use database::actions::metadata::get_metadata_summary_by_files;
 use database::actions::mixes::{
     add_item_to_mix, create_mix, get_all_mixes, get_mix_by_id, get_mix_queries_by_mix_id,
-    query_mix_media_files, remove_mix, replace_mix_queries, update_mix,
+    query_mix_media_files, remove_mix, replace_mix_queries, update_mix, validate_mix_queries,
+    GroupedQuery,
 };
 use database::connection::{MainDbConnection, RecommendationDbConnection};
 
@@ -71,6 +72,20 @@ impl Signal for CreateMixRequest {
     ) -> Result<Option<Self::Response>> {
         let request = dart_signal;
 
+        let queries: Vec<GroupedQuery> = request
+            .queries
+            .clone()
+            .into_iter()
+            .map(GroupedQuery::from)
+            .collect();
+
+        if let Err(e) = validate_mix_queries(request.scriptlet_mode, &queries) {
+            return Ok(Some(CreateMixResponse {
+                mix: None,
+                error: Some(e.to_string()),
+            }));
+        }
+
         let mix = create_mix(
             &main_db,
             request.name.clone(),
@@ -82,19 +97,9 @@ impl Signal for CreateMixRequest {
         .await
         .with_context(|| "Failed to create mix")?;
 
-        replace_mix_queries(
-            &main_db,
-            mix.id,
-            request
-                .queries
-                .clone()
-                .into_iter()
-                .map(|x| (x.operator, x.parameter))
-                .collect(),
-            None,
-        )
-        .await
-        .with_context(|| "Failed to update replace mix queries while creating")?;
+        replace_mix_queries(&main_db, mix.id, queries)
+            .await
+            .with_context(|| "Failed to update replace mix queries while creating")?;
 
         Ok(Some(CreateMixResponse {
             mix: Some(Mix {
@@ -104,6 +109,7 @@ impl Signal for CreateMixRequest {
                 locked: mix.locked,
                 mode: mix.mode.expect("Mix mode not exists"),
             }),
+            error: None,
         }))
     }
 }
@@ -128,6 +134,20 @@ impl Signal for UpdateMixRequest {
     ) -> Result<Option<Self::Response>> {
         let request = dart_signal;
 
+        let queries: Vec<GroupedQuery> = request
+            .queries
+            .clone()
+            .into_iter()
+            .map(GroupedQuery::from)
+            .collect();
+
+        if let Err(e) = validate_mix_queries(request.scriptlet_mode, &queries) {
+            return Ok(Some(UpdateMixResponse {
+                mix: None,
+                error: Some(e.to_string()),
+            }));
+        }
+
         let mix = update_mix(
             &main_db,
             request.mix_id,
@@ -140,19 +160,9 @@ impl Signal for UpdateMixRequest {
         .await
         .with_context(|| "Failed to update mix metadata")?;
 
-        replace_mix_queries(
-            &main_db,
-            request.mix_id,
-            request
-                .queries
-                .clone()
-                .into_iter()
-                .map(|x| (x.operator, x.parameter))
-                .collect(),
-            None,
-        )
-        .await
-        .with_context(|| "Failed to update replace mix queries while updating")?;
+        replace_mix_queries(&main_db, request.mix_id, queries)
+            .await
+            .with_context(|| "Failed to update replace mix queries while updating")?;
 
         Ok(Some(UpdateMixResponse {
             mix: Some(Mix {
@@ -162,6 +172,7 @@ impl Signal for UpdateMixRequest {
                 locked: mix.locked,
                 mode: mix.mode.expect("Mix mode not exists"),
             }),
+            error: None,
         }))
     }
 }
@@ -302,14 +313,11 @@ impl Signal for MixQueryRequest {
     ) -> Result<Option<Self::Response>> {
         let request = dart_signal;
 
-        let queries = request
-            .queries
-            .clone()
-            .into_iter()
-            .map(|x| (x.operator, x.parameter))
-            .collect();
+        let queries = request.queries.clone();
 
-        let media_entries = query_mix_media_files(
+        // Broken queries and failing scriptlets are reported to the user
+        // instead of failing the request
+        let media_entries = match query_mix_media_files(
             &main_db,
             &recommend_db,
             queries,
@@ -317,7 +325,16 @@ impl Signal for MixQueryRequest {
             request.page_size as usize,
         )
         .await
-        .with_context(|| "Unable to query mix media files")?;
+        {
+            Ok(x) => x,
+            Err(e) => {
+                return Ok(Some(MixQueryResponse {
+                    files: vec![],
+                    cover_art_map: HashMap::new(),
+                    error: Some(format!("{:#}", e)),
+                }))
+            }
+        };
 
         let media_summaries = get_metadata_summary_by_files(&main_db, media_entries.clone())
             .await
@@ -333,6 +350,7 @@ impl Signal for MixQueryRequest {
         Ok(Some(MixQueryResponse {
             files,
             cover_art_map,
+            error: None,
         }))
     }
 }
@@ -362,13 +380,7 @@ impl Signal for FetchMixQueriesRequest {
             .with_context(|| "Unable to get mix queries files")?;
 
         Ok(Some(FetchMixQueriesResponse {
-            result: queries
-                .into_iter()
-                .map(|x| MixQuery {
-                    operator: x.operator,
-                    parameter: x.parameter,
-                })
-                .collect(),
+            result: queries.into_iter().map(MixQuery::from).collect(),
         }))
     }
 }
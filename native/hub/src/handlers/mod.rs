@@ -3,6 +3,7 @@ mod collection;
 mod connection;
 mod cover_art;
 mod directory;
+mod equalizer;
 mod library_home;
 mod library_manage;
 mod license;
@@ -15,7 +15,7 @@ use ::database::actions::cover_art::COVER_TEMP_DIR;
 use ::discovery::client::{fetch_server_certificate, select_best_host, try_connect, CertValidator};
 use ::discovery::protocol::DiscoveryService;
 use ::discovery::request::{create_https_client, send_http_request};
-use ::discovery::server::{PermissionManager, UserStatus};
+use ::discovery::server::{PermissionManager, UserRole, UserStatus};
 use ::discovery::url::decode_rnsrv_url;
 use ::discovery::utils::{DeviceInfo, DeviceType};
 use ::discovery::DiscoveryParams;
@@ -292,6 +292,28 @@ impl Signal for StopServerRequest {
     }
 }
 
+impl From<UserRole> for ClientRole {
+    fn from(role: UserRole) -> Self {
+        match role {
+            UserRole::Viewer => ClientRole::Viewer,
+            UserRole::Controller => ClientRole::Controller,
+            UserRole::Editor => ClientRole::Editor,
+            UserRole::Admin => ClientRole::Admin,
+        }
+    }
+}
+
+impl From<ClientRole> for UserRole {
+    fn from(role: ClientRole) -> Self {
+        match role {
+            ClientRole::Viewer => UserRole::Viewer,
+            ClientRole::Controller => UserRole::Controller,
+            ClientRole::Editor => UserRole::Editor,
+            ClientRole::Admin => UserRole::Admin,
+        }
+    }
+}
+
 impl ParamsExtractor for ListClientsRequest {
     type Params = Arc<RwLock<PermissionManager>>;
 
@@ -323,6 +345,8 @@ impl Signal for ListClientsRequest {
                     UserStatus::Pending => ClientStatus::Pending.into(),
                     UserStatus::Blocked => ClientStatus::Blocked.into(),
                 },
+                role: ClientRole::from(u.role).into(),
+                max_bitrate: u.max_bitrate,
             })
             .collect();
 
@@ -486,6 +510,80 @@ impl Signal for UpdateClientStatusRequest {
     }
 }
 
+impl ParamsExtractor for UpdateClientRoleRequest {
+    type Params = Arc<RwLock<PermissionManager>>;
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        Arc::clone(&all_params.permission_manager)
+    }
+}
+
+impl Signal for UpdateClientRoleRequest {
+    type Params = Arc<RwLock<PermissionManager>>;
+    type Response = UpdateClientRoleResponse;
+
+    async fn handle(
+        &self,
+        permission_manager: Self::Params,
+        _session: Option<Session>,
+        message: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let role: UserRole = ClientRole::try_from(message.role)?.into();
+
+        match permission_manager
+            .write()
+            .await
+            .change_user_role(&message.fingerprint, role)
+            .await
+        {
+            Ok(_) => Ok(Some(UpdateClientRoleResponse {
+                success: true,
+                error: String::new(),
+            })),
+            Err(e) => Ok(Some(UpdateClientRoleResponse {
+                success: false,
+                error: format!("{:#?}", e),
+            })),
+        }
+    }
+}
+
+impl ParamsExtractor for UpdateClientMaxBitrateRequest {
+    type Params = Arc<RwLock<PermissionManager>>;
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        Arc::clone(&all_params.permission_manager)
+    }
+}
+
+impl Signal for UpdateClientMaxBitrateRequest {
+    type Params = Arc<RwLock<PermissionManager>>;
+    type Response = UpdateClientMaxBitrateResponse;
+
+    async fn handle(
+        &self,
+        permission_manager: Self::Params,
+        _session: Option<Session>,
+        message: &Self,
+    ) -> Result<Option<Self::Response>> {
+        match permission_manager
+            .write()
+            .await
+            .change_user_max_bitrate(&message.fingerprint, message.max_bitrate)
+            .await
+        {
+            Ok(_) => Ok(Some(UpdateClientMaxBitrateResponse {
+                success: true,
+                error: String::new(),
+            })),
+            Err(e) => Ok(Some(UpdateClientMaxBitrateResponse {
+                success: false,
+                error: format!("{:#?}", e),
+            })),
+        }
+    }
+}
+
 impl ParamsExtractor for EditHostsRequest {
     type Params = Arc<RwLock<CertValidator>>;
 
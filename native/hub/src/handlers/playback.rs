@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use ::playback::player::Playable;
 use anyhow::{Context, Result};
@@ -10,11 +11,14 @@ use ::database::actions::stats::increase_skipped;
 use ::database::connection::MainDbConnection;
 use ::database::connection::RecommendationDbConnection;
 use ::database::playing_item::dispatcher::PlayingItemActionDispatcher;
+use ::database::playing_item::library_item::extract_in_library_ids;
+use ::playback::crossfade::CrossfadeSettings;
 use ::playback::player::PlayingItem;
 use ::playback::strategies::AddMode;
 
 use crate::utils::files_to_playback_request;
 use crate::utils::find_nearest_index;
+use crate::utils::history::PlayHistoryTracker;
 use crate::utils::GlobalParams;
 use crate::utils::ParamsExtractor;
 use crate::Session;
@@ -408,12 +412,63 @@ impl Signal for SetAdaptiveSwitchingEnabledRequest {
     }
 }
 
+impl ParamsExtractor for SetNormalizationModeRequest {
+    type Params = (Arc<Mutex<dyn Playable>>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.player),)
+    }
+}
+
+impl Signal for SetNormalizationModeRequest {
+    type Params = (Arc<Mutex<dyn Playable>>,);
+    type Response = ();
+
+    async fn handle(
+        &self,
+        (player,): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let mode = dart_signal.mode;
+        player.lock().await.set_normalization_mode(mode.into());
+        Ok(Some(()))
+    }
+}
+
+impl ParamsExtractor for SetCrossfadeRequest {
+    type Params = (Arc<Mutex<dyn Playable>>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.player),)
+    }
+}
+
+impl Signal for SetCrossfadeRequest {
+    type Params = (Arc<Mutex<dyn Playable>>,);
+    type Response = ();
+
+    async fn handle(
+        &self,
+        (player,): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        player.lock().await.set_crossfade(CrossfadeSettings {
+            duration: Duration::from_millis(dart_signal.duration_ms.into()),
+            curve: dart_signal.curve.into(),
+        });
+        Ok(Some(()))
+    }
+}
+
 impl ParamsExtractor for OperatePlaybackWithMixQueryRequest {
     type Params = (
         Arc<MainDbConnection>,
         Arc<RecommendationDbConnection>,
         Arc<String>,
         Arc<Mutex<dyn Playable>>,
+        Arc<Mutex<PlayHistoryTracker>>,
     );
 
     fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
@@ -422,6 +477,7 @@ impl ParamsExtractor for OperatePlaybackWithMixQueryRequest {
             Arc::clone(&all_params.recommend_db),
             Arc::clone(&all_params.lib_path),
             Arc::clone(&all_params.player),
+            Arc::clone(&all_params.play_history),
         )
     }
 }
@@ -432,12 +488,13 @@ impl Signal for OperatePlaybackWithMixQueryRequest {
         Arc<RecommendationDbConnection>,
         Arc<String>,
         Arc<Mutex<dyn Playable>>,
+        Arc<Mutex<PlayHistoryTracker>>,
     );
     type Response = OperatePlaybackWithMixQueryResponse;
 
     async fn handle(
         &self,
-        (main_db, recommend_db, lib_path, player): Self::Params,
+        (main_db, recommend_db, lib_path, player, play_history): Self::Params,
         _session: Option<Session>,
         dart_signal: &Self,
     ) -> Result<Option<Self::Response>> {
@@ -456,22 +513,12 @@ impl Signal for OperatePlaybackWithMixQueryRequest {
                 .get_file_handle(&main_db, &items)
                 .await?
         } else {
-            query_mix_media_files(
-                &main_db,
-                &recommend_db,
-                request
-                    .queries
-                    .iter()
-                    .map(|x| (x.operator.clone(), x.parameter.clone()))
-                    .collect(),
-                0,
-                4096,
-            )
-            .await
-            .with_context(|| format!("Failed to query tracks: {:?}", request.queries))?
-            .into_iter()
-            .map(|x| x.into())
-            .collect()
+            query_mix_media_files(&main_db, &recommend_db, request.queries.clone(), 0, 4096)
+                .await
+                .with_context(|| format!("Failed to query tracks: {:?}", request.queries))?
+                .into_iter()
+                .map(|x| x.into())
+                .collect()
         };
 
         let mut player = player.lock().await;
@@ -482,6 +529,21 @@ impl Signal for OperatePlaybackWithMixQueryRequest {
             player.clear_playlist();
         }
 
+        // Remember which mix the tracks came from for the play history
+        if !request.queries.is_empty() {
+            let source = request
+                .queries
+                .iter()
+                .map(|x| format!("{}({})", x.operator, x.parameter))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            play_history.lock().await.set_source(
+                extract_in_library_ids(tracks.iter().map(|x| x.item.clone()).collect()),
+                &source,
+            );
+        }
+
         let add_mode = if operate_mode == PlaylistOperateMode::PlayNext {
             AddMode::PlayNext
         } else {
@@ -5,9 +5,11 @@ use anyhow::{anyhow, Context, Result};
 use database::actions::playlists::remove_item_from_playlist;
 use sea_orm::TransactionTrait;
 
+use ::database::actions::playlist_formats::PlaylistFormat;
 use ::database::actions::playlists::{
-    add_item_to_playlist, create_m3u8_playlist, create_playlist, get_all_playlists,
-    get_playlist_by_id, remove_playlist, reorder_playlist_item_position, update_playlist,
+    add_item_to_playlist, create_playlist, create_playlist_from_file, export_playlist,
+    get_all_playlists, get_playlist_by_id, remove_playlist, reorder_playlist_item_position,
+    update_playlist,
 };
 use ::database::connection::MainDbConnection;
 
@@ -298,7 +300,9 @@ impl Signal for CreateM3u8PlaylistRequest {
         let group = &request.group;
         let path = &request.path;
 
-        match create_m3u8_playlist(&main_db, name.clone(), group.clone(), Path::new(&path)).await {
+        match create_playlist_from_file(&main_db, name.clone(), group.clone(), Path::new(&path))
+            .await
+        {
             Ok((playlist, import_result)) => Ok(Some(CreateM3u8PlaylistResponse {
                 playlist: Some(Playlist {
                     id: playlist.id,
@@ -360,3 +364,64 @@ impl Signal for RemoveItemFromPlaylistRequest {
         }
     }
 }
+
+impl ParamsExtractor for ExportPlaylistRequest {
+    type Params = (Arc<String>, Arc<MainDbConnection>);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (
+            Arc::clone(&all_params.lib_path),
+            Arc::clone(&all_params.main_db),
+        )
+    }
+}
+
+impl Signal for ExportPlaylistRequest {
+    type Params = (Arc<String>, Arc<MainDbConnection>);
+    type Response = ExportPlaylistResponse;
+
+    async fn handle(
+        &self,
+        (lib_path, main_db): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+        let path = Path::new(&request.path);
+
+        let format = if request.format.is_empty() {
+            PlaylistFormat::from_path(path)
+                .ok_or_else(|| anyhow!("Unknown playlist format: {}", request.path))
+        } else {
+            request.format.parse::<PlaylistFormat>()
+        };
+
+        let result = match format {
+            Ok(format) => {
+                export_playlist(
+                    &main_db,
+                    Path::new(lib_path.as_ref()),
+                    request.playlist_id,
+                    format,
+                    path,
+                    request.relative_paths,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(count) => Ok(Some(ExportPlaylistResponse {
+                exported_count: count as i32,
+                success: true,
+                error: String::new(),
+            })),
+            Err(e) => Ok(Some(ExportPlaylistResponse {
+                exported_count: 0,
+                success: false,
+                error: e.to_string(),
+            })),
+        }
+    }
+}
@@ -4,7 +4,8 @@ use anyhow::{Context, Result};
 
 use ::database::actions::collection::CollectionQueryType;
 use ::database::actions::search::convert_to_collection_types;
-use ::database::actions::search::search_for;
+use ::database::actions::search::search;
+use ::database::actions::search_query::SearchQuery;
 use ::database::connection::MainDbConnection;
 
 use crate::{
@@ -36,13 +37,15 @@ impl Signal for SearchForRequest {
         let search_fields = convert_to_collection_types(request.fields.clone());
         let n = request.n as usize;
 
-        let results = search_for(
+        let query = SearchQuery::parse(query_str);
+
+        let results = search(
             &main_db,
-            query_str,
+            &query,
             if search_fields.is_empty() {
                 None
             } else {
-                Some(search_fields)
+                Some(&search_fields)
             },
             n,
         )
@@ -53,16 +56,27 @@ impl Signal for SearchForRequest {
         let mut albums: Vec<i32> = Vec::new();
         let mut playlists: Vec<i32> = Vec::new();
         let mut tracks: Vec<i32> = Vec::new();
+        let mut mixes: Vec<i32> = Vec::new();
+        let mut genres: Vec<i32> = Vec::new();
+        let mut hits: Vec<SearchHit> = Vec::new();
 
-        for (collection_type, ids) in results {
-            let ids: Vec<i32> = ids.iter().map(|&x| x as i32).collect();
-            match collection_type {
-                CollectionQueryType::Artist => artists.extend(ids),
-                CollectionQueryType::Album => albums.extend(ids),
-                CollectionQueryType::Playlist => playlists.extend(ids),
-                CollectionQueryType::Track => tracks.extend(ids),
+        for hit in results {
+            let id = hit.id as i32;
+            match hit.collection_type {
+                CollectionQueryType::Artist => artists.push(id),
+                CollectionQueryType::Album => albums.push(id),
+                CollectionQueryType::Playlist => playlists.push(id),
+                CollectionQueryType::Track => tracks.push(id),
+                CollectionQueryType::Mix => mixes.push(id),
+                CollectionQueryType::Genre => genres.push(id),
                 _ => {}
             }
+
+            hits.push(SearchHit {
+                collection_type: hit.collection_type.to_string(),
+                id,
+                score: hit.score,
+            });
         }
 
         Ok(Some(SearchForResponse {
@@ -70,6 +84,9 @@ impl Signal for SearchForRequest {
             albums,
             playlists,
             tracks,
+            mixes,
+            genres,
+            hits,
         }))
     }
 }
@@ -1,9 +1,14 @@
 use std::sync::Arc;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
 
 use ::database::{
-    actions::stats::{get_liked, set_liked},
+    actions::{
+        collection::CollectionQueryType,
+        history::{get_top_played, list_play_events, HistoryWindow},
+        stats::{get_liked, set_liked},
+    },
     connection::MainDbConnection,
 };
 use ::playback::player::PlayingItem;
@@ -123,3 +128,105 @@ impl Signal for GetLikedRequest {
         Ok(None)
     }
 }
+
+fn parse_window(since: Option<i64>, until: Option<i64>) -> HistoryWindow {
+    HistoryWindow {
+        since: since.and_then(|x| DateTime::<Utc>::from_timestamp(x, 0)),
+        until: until.and_then(|x| DateTime::<Utc>::from_timestamp(x, 0)),
+    }
+}
+
+impl ParamsExtractor for FetchPlayHistoryRequest {
+    type Params = (Arc<MainDbConnection>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.main_db),)
+    }
+}
+
+impl Signal for FetchPlayHistoryRequest {
+    type Params = (Arc<MainDbConnection>,);
+    type Response = FetchPlayHistoryResponse;
+
+    async fn handle(
+        &self,
+        (main_db,): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+
+        let events = list_play_events(
+            &main_db,
+            parse_window(request.since, request.until),
+            request.cursor.max(0) as u64,
+            request.page_size.max(0) as u64,
+        )
+        .await
+        .with_context(|| "Failed to fetch play history")?;
+
+        Ok(Some(FetchPlayHistoryResponse {
+            events: events
+                .into_iter()
+                .map(|x| PlayEvent {
+                    file_id: x.media_file_id,
+                    started_at: x.started_at.to_rfc3339(),
+                    listened_duration: x.listened_duration,
+                    completion_ratio: x.completion_ratio,
+                    source: x.source,
+                })
+                .collect(),
+        }))
+    }
+}
+
+impl ParamsExtractor for FetchTopPlayedRequest {
+    type Params = (Arc<MainDbConnection>,);
+
+    fn extract_params(&self, all_params: &GlobalParams) -> Self::Params {
+        (Arc::clone(&all_params.main_db),)
+    }
+}
+
+impl Signal for FetchTopPlayedRequest {
+    type Params = (Arc<MainDbConnection>,);
+    type Response = FetchTopPlayedResponse;
+
+    async fn handle(
+        &self,
+        (main_db,): Self::Params,
+        _session: Option<Session>,
+        dart_signal: &Self,
+    ) -> Result<Option<Self::Response>> {
+        let request = dart_signal;
+
+        let collection_type = match CollectionType::try_from(request.collection_type)? {
+            CollectionType::Track => CollectionQueryType::Track,
+            CollectionType::Artist => CollectionQueryType::Artist,
+            CollectionType::Album => CollectionQueryType::Album,
+            CollectionType::Genre => CollectionQueryType::Genre,
+            x => bail!("Play history can not be ranked by {:?}", x),
+        };
+
+        let items = get_top_played(
+            &main_db,
+            collection_type,
+            parse_window(request.since, request.until),
+            request.limit.max(0) as u64,
+        )
+        .await
+        .with_context(|| "Failed to fetch top played items")?;
+
+        Ok(Some(FetchTopPlayedResponse {
+            collection_type: request.collection_type,
+            items: items
+                .into_iter()
+                .map(|x| TopPlayedItem {
+                    id: x.id,
+                    plays: x.plays,
+                    listened_duration: x.listened_duration,
+                })
+                .collect(),
+        }))
+    }
+}
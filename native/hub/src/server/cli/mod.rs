@@ -1,4 +1,5 @@
 pub mod server;
 pub mod broadcast;
 pub mod permission;
-pub mod chpwd;
\ No newline at end of file
+pub mod chpwd;
+pub mod subsonic;
\ No newline at end of file
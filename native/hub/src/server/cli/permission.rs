@@ -3,7 +3,7 @@ use log::info;
 
 use hub::server::utils::{
     path::get_config_dir,
-    permission::{parse_status, print_permission_table, validate_index},
+    permission::{parse_bitrate, parse_role, parse_status, print_permission_table, validate_index},
 };
 
 use crate::PermissionAction;
@@ -27,6 +27,23 @@ pub async fn handle_permission(action: PermissionAction) -> Result<()> {
             pm.change_user_status(&user.fingerprint, status).await?;
             info!("User status updated successfully");
         }
+        PermissionAction::Role { index, role } => {
+            let users = pm.list_users().await;
+            validate_index(index, users.len())?;
+            let user = &users[index - 1];
+            let role = parse_role(&role)?;
+            pm.change_user_role(&user.fingerprint, role).await?;
+            info!("User role updated successfully");
+        }
+        PermissionAction::Bitrate { index, bitrate } => {
+            let users = pm.list_users().await;
+            validate_index(index, users.len())?;
+            let user = &users[index - 1];
+            let bitrate = parse_bitrate(&bitrate)?;
+            pm.change_user_max_bitrate(&user.fingerprint, bitrate)
+                .await?;
+            info!("User bitrate limit updated successfully");
+        }
         PermissionAction::Delete { index } => {
             let users = pm.list_users().await;
             validate_index(index, users.len())?;
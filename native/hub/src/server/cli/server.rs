@@ -1,25 +1,54 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use anyhow::Result;
+use log::error;
 use tokio::signal::ctrl_c;
 
 use hub::server::{
+    mpd, upnp,
     utils::{device::load_device_info, path::get_config_dir},
     ServerManager,
 };
+use hub::utils::history::flush_play_history;
 
 use crate::initialize_global_params;
 
 use ::discovery::DiscoveryParams;
 
-pub async fn handle_server(addr: String, lib_path: String) -> Result<()> {
+pub async fn handle_server(
+    addr: String,
+    mpd_addr: Option<String>,
+    upnp_addr: Option<String>,
+    lib_path: String,
+) -> Result<()> {
     let config_path = get_config_dir()?;
     let device_info = load_device_info(&config_path).await?;
     let global_params = initialize_global_params(&lib_path, config_path.to_str().unwrap()).await?;
 
-    let server_manager = Arc::new(ServerManager::new(global_params).await?);
+    let server_manager = Arc::new(ServerManager::new(global_params.clone()).await?);
     let socket_addr: SocketAddr = addr.parse()?;
 
+    if let Some(mpd_addr) = mpd_addr {
+        let mpd_addr: SocketAddr = mpd_addr.parse()?;
+        let global_params = global_params.clone();
+        tokio::spawn(async move {
+            if let Err(e) = mpd::serve(mpd_addr, global_params).await {
+                error!("MPD server failed: {:#?}", e);
+            }
+        });
+    }
+
+    if let Some(upnp_addr) = upnp_addr {
+        let upnp_addr: SocketAddr = upnp_addr.parse()?;
+        let global_params = global_params.clone();
+        let device_info = device_info.clone();
+        tokio::spawn(async move {
+            if let Err(e) = upnp::serve(upnp_addr, global_params, device_info).await {
+                error!("UPnP media server failed: {:#?}", e);
+            }
+        });
+    }
+
     server_manager
         .clone()
         .start(socket_addr, DiscoveryParams { device_info })
@@ -27,5 +56,6 @@ pub async fn handle_server(addr: String, lib_path: String) -> Result<()> {
 
     ctrl_c().await?;
     server_manager.stop().await?;
+    flush_play_history(&global_params.main_db, &global_params.play_history).await;
     Ok(())
 }
@@ -0,0 +1,61 @@
+use anyhow::Result;
+use colored::*;
+use log::{error, info};
+use rpassword::prompt_password;
+
+use hub::server::{
+    subsonic::accounts::SubsonicAccountManager,
+    utils::{path::get_config_dir, permission::parse_role},
+};
+
+use crate::SubsonicAction;
+
+fn prompt_new_password() -> Result<String> {
+    loop {
+        let pwd = prompt_password("Enter password: ")?;
+        let confirm = prompt_password("Confirm password: ")?;
+
+        if pwd == confirm {
+            return Ok(pwd);
+        }
+        error!("Passwords do not match, please try again");
+    }
+}
+
+pub async fn handle_subsonic(action: SubsonicAction) -> Result<()> {
+    let config_path = get_config_dir()?;
+    let accounts = SubsonicAccountManager::new(config_path)?;
+
+    match action {
+        SubsonicAction::Ls => {
+            for account in accounts.list_accounts().await {
+                println!(
+                    "{} {}",
+                    account.username.cyan().bold(),
+                    format!("{:?}", account.role).white()
+                );
+            }
+        }
+        SubsonicAction::Add { username, role } => {
+            let role = parse_role(&role)?;
+            let password = prompt_new_password()?;
+            accounts.add_account(&username, &password, role).await?;
+            info!("Account added successfully");
+        }
+        SubsonicAction::Passwd { username } => {
+            let password = prompt_new_password()?;
+            accounts.change_password(&username, &password).await?;
+            info!("Password updated successfully");
+        }
+        SubsonicAction::Role { username, role } => {
+            let role = parse_role(&role)?;
+            accounts.change_role(&username, role).await?;
+            info!("Account role updated successfully");
+        }
+        SubsonicAction::Delete { username } => {
+            accounts.remove_account(&username).await?;
+            info!("Account deleted successfully");
+        }
+    }
+    Ok(())
+}
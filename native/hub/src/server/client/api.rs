@@ -108,6 +108,7 @@ pub async fn operate_playback_with_mix_query_request(
             .map(|(operator, parameter)| MixQuery {
                 operator,
                 parameter,
+                group: 0,
             })
             .collect(),
         playback_mode: playback_mode.into(),
@@ -133,6 +134,7 @@ pub async fn send_mix_query_request(
             .map(|(operator, parameter)| MixQuery {
                 operator,
                 parameter,
+                group: 0,
             })
             .collect(),
         cursor: 0,
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use axum::{
     body::Body,
     extract::{Path, State},
-    http::{Request, StatusCode},
+    http::{HeaderMap, Request, StatusCode},
     response::{IntoResponse, Response},
 };
 use dunce::canonicalize;
@@ -15,10 +15,26 @@ use crate::server::ServerState;
 pub async fn file_handler(
     Path(file_path): Path<String>,
     State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    let lib_path = &state.app_state.lib_path;
-    let cover_temp_dir = &state.app_state.cover_temp_dir;
+    serve_file(
+        &state.app_state.lib_path,
+        &state.app_state.cover_temp_dir,
+        &file_path,
+        &headers,
+    )
+    .await
+}
 
+/// Serves `library/<path>` from the library or `cache/<path>` from the
+/// cover art cache, refusing paths leading out of them. The request headers
+/// are passed on, so clients can seek with range requests.
+pub async fn serve_file(
+    lib_path: &std::path::Path,
+    cover_temp_dir: &std::path::Path,
+    file_path: &str,
+    headers: &HeaderMap,
+) -> Response {
     // Parse the request path, splitting it into prefix and actual file path
     let path_parts: Vec<&str> = file_path.splitn(2, '/').collect();
     if path_parts.len() != 2 {
@@ -52,12 +68,20 @@ pub async fn file_handler(
         Err(_) => return StatusCode::FORBIDDEN.into_response(),
     };
 
-    // Serve the file using ServeDir
+    // Serve the file using ServeDir, which decodes the path again
     let service = ServeDir::new(root_dir);
-    let request = Request::builder()
-        .uri(format!("/{}", relative_path.to_string_lossy()))
+    let uri_path: Vec<String> = relative_path
+        .components()
+        .map(|x| urlencoding::encode(&x.as_os_str().to_string_lossy()).into_owned())
+        .collect();
+    let mut request = match Request::builder()
+        .uri(format!("/{}", uri_path.join("/")))
         .body(axum::body::Body::empty())
-        .unwrap();
+    {
+        Ok(request) => request,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    *request.headers_mut() = headers.clone();
 
     match service.oneshot(request).await {
         Ok(response) => {
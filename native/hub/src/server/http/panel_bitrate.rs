@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::server::ServerState;
+
+use super::register::AppError;
+
+#[derive(Deserialize)]
+pub struct BitrateUpdate {
+    /// Highest bitrate in kbps, `null` removes the limit
+    max_bitrate: Option<u32>,
+}
+
+pub async fn update_user_bitrate_handler(
+    Path(fingerprint): Path<String>,
+    State(state): State<Arc<ServerState>>,
+    Json(payload): Json<BitrateUpdate>,
+) -> Result<StatusCode, AppError> {
+    state
+        .permission_manager
+        .write()
+        .await
+        .change_user_max_bitrate(&fingerprint, payload.max_bitrate)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
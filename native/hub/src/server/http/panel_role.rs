@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use discovery::server::UserRole;
+
+use crate::server::ServerState;
+
+use super::register::AppError;
+
+#[derive(Deserialize)]
+pub struct RoleUpdate {
+    role: UserRole,
+}
+
+pub async fn update_user_role_handler(
+    Path(fingerprint): Path<String>,
+    State(state): State<Arc<ServerState>>,
+    Json(payload): Json<RoleUpdate>,
+) -> Result<StatusCode, AppError> {
+    state
+        .permission_manager
+        .write()
+        .await
+        .change_user_role(&fingerprint, payload.role)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
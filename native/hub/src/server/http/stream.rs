@@ -0,0 +1,367 @@
+use std::{io, path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+};
+use dunce::canonicalize;
+use futures::stream;
+use log::error;
+use serde::Deserialize;
+use tokio::{sync::mpsc, task};
+
+use ::database::actions::file::{get_cue_track_by_path, get_track_range};
+use ::discovery::server::{Capability, User, UserStatus};
+use ::transcoding::{SegmentCache, TranscodeFormat, TranscodeOptions, Transcoder};
+
+use crate::server::ServerState;
+
+const X_CONTENT_DURATION: HeaderName = HeaderName::from_static("x-content-duration");
+
+/// First byte, last byte and content of a range.
+type RangeData = (u64, u64, Vec<u8>);
+
+#[derive(Deserialize)]
+pub struct StreamParams {
+    /// Public key or fingerprint of the client, as for the WebSocket
+    auth: String,
+    /// `opus`, `aac` or `mp3`, defaults to `opus`
+    format: Option<String>,
+    /// Bitrate in kbps, capped by the limit set for the client in the panel
+    bitrate: Option<u32>,
+    sample_rate: Option<u32>,
+    channels: Option<u16>,
+    /// Time to start at, in seconds
+    #[serde(default)]
+    start: f64,
+    /// Number of the CUE sheet track to stream, the whole file otherwise
+    track: Option<i32>,
+}
+
+/// Streams a library file transcoded on the fly.
+///
+/// To test this API, use:
+/// curl -k -o out.opus \
+///  "https://localhost:7863/stream/Artist/Album/01.flac?auth=01:23:45:67:89:AB:CD:EF&format=opus&bitrate=64"
+pub async fn stream_handler(
+    Path(file_path): Path<String>,
+    Query(params): Query<StreamParams>,
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Response {
+    let user = match authorize(&state, &params.auth).await {
+        Ok(user) => user,
+        Err(code) => return code.into_response(),
+    };
+
+    let format = match params.format.as_deref().map(str::parse).transpose() {
+        Ok(format) => format.unwrap_or(TranscodeFormat::Opus),
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    let options = TranscodeOptions {
+        format,
+        bitrate: params.bitrate,
+        sample_rate: params.sample_rate,
+        channels: params.channels,
+    }
+    .cap_bitrate(user.max_bitrate);
+
+    // Security check: Ensure the accessed path does not go beyond the library
+    let lib_path = &state.app_state.lib_path;
+    let path = match canonicalize(lib_path.join(&file_path)) {
+        Ok(path) if path.starts_with(lib_path) && path.is_file() => path,
+        Ok(_) => return StatusCode::FORBIDDEN.into_response(),
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    let range = match params.track {
+        Some(track) => {
+            let relative_path = path.strip_prefix(lib_path).unwrap_or(&path);
+            match get_cue_track_by_path(&state.app_state.main_db, relative_path, track).await {
+                Ok(Some(file)) => get_track_range(&file),
+                Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+                Err(e) => {
+                    error!("Failed to look up CUE track: {:#?}", e);
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            }
+        }
+        None => None,
+    };
+
+    transcode_response(
+        path,
+        range,
+        options,
+        state.app_state.transcode_cache.clone(),
+        params.start,
+        &headers,
+    )
+    .await
+}
+
+async fn authorize(state: &ServerState, auth_key: &str) -> Result<User, StatusCode> {
+    let permission_manager = state.permission_manager.read().await;
+
+    let user = match permission_manager.verify_by_public_key(auth_key).await {
+        Some(user) => user,
+        None => permission_manager
+            .verify_by_fingerprint(auth_key)
+            .await
+            .ok_or(StatusCode::UNAUTHORIZED)?,
+    };
+
+    match user.status {
+        UserStatus::Approved if user.role.allows(Capability::Browse) => Ok(user),
+        UserStatus::Pending => Err(StatusCode::UNAUTHORIZED),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// Answers a request for a file, or the `range` of it taken by a CUE track,
+/// transcoded with `options`.
+///
+/// Range requests address the stream from the start of the file, they are
+/// answered from the cache once the whole stream is cached. Until then the
+/// range is ignored, and like other requests the stream is sent while the
+/// file is transcoded, starting at `start` seconds.
+pub async fn transcode_response(
+    path: PathBuf,
+    range: Option<(f64, f64)>,
+    options: TranscodeOptions,
+    cache: Arc<SegmentCache>,
+    start: f64,
+    headers: &HeaderMap,
+) -> Response {
+    let display_path = path.display().to_string();
+    let transcoder =
+        match task::spawn_blocking(move || Transcoder::new(&path, range, &options, cache)).await {
+            Ok(Ok(transcoder)) => Arc::new(transcoder),
+            Ok(Err(e)) => {
+                error!("Failed to open {} for transcoding: {:#?}", display_path, e);
+                return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+            }
+            Err(e) => {
+                error!("Transcoding task failed: {:#?}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(parse_range);
+
+    let result = match (range, transcoder.cached_segments()) {
+        (Some(range), Some(sizes)) => range_response(transcoder, sizes, range).await,
+        _ => Ok(stream_response(transcoder, start.max(0.0))),
+    };
+
+    result.unwrap_or_else(|e| {
+        error!("Failed to transcode {}: {:#?}", display_path, e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })
+}
+
+fn stream_response(transcoder: Arc<Transcoder>, start: f64) -> Response {
+    let (tx, rx) = mpsc::channel::<Result<Bytes, io::Error>>(16);
+    let content_type = transcoder.options().format.mime_type();
+    let duration = (transcoder.duration() - start).max(0.0);
+    let cached_segments = if start <= 0.0 {
+        transcoder.cached_segments()
+    } else {
+        None
+    };
+
+    // Ranges are only served from a complete stream
+    let accept_ranges = match cached_segments {
+        Some(_) => "bytes",
+        None => "none",
+    };
+
+    let mut response_headers = vec![
+        (header::CONTENT_TYPE, content_type.to_owned()),
+        (header::ACCEPT_RANGES, accept_ranges.to_owned()),
+    ];
+    if duration > 0.0 {
+        response_headers.push((X_CONTENT_DURATION, format!("{:.3}", duration)));
+    }
+
+    match cached_segments {
+        Some(sizes) => {
+            response_headers.push((
+                header::CONTENT_LENGTH,
+                sizes.iter().sum::<u64>().to_string(),
+            ));
+
+            task::spawn_blocking(move || {
+                for index in 0..sizes.len() {
+                    let segment = transcoder
+                        .read_segment(index)
+                        .map(Bytes::from)
+                        .map_err(io::Error::other);
+                    if tx.blocking_send(segment).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        None => {
+            task::spawn_blocking(move || {
+                let result = transcoder.run(start, |bytes| {
+                    bytes.is_empty() || tx.blocking_send(Ok(Bytes::copy_from_slice(bytes))).is_ok()
+                });
+
+                if let Err(e) = result {
+                    error!("Failed to transcode: {:#?}", e);
+                    let _ = tx.blocking_send(Err(io::Error::other(e.to_string())));
+                }
+            });
+        }
+    }
+
+    let body = Body::from_stream(stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|x| (x, rx))
+    }));
+
+    let mut response = body.into_response();
+    for (name, value) in response_headers {
+        if let Ok(value) = value.parse() {
+            response.headers_mut().insert(name, value);
+        }
+    }
+
+    response
+}
+
+/// Answers a range of a stream whose segments, of the given `sizes`, are
+/// all cached.
+async fn range_response(
+    transcoder: Arc<Transcoder>,
+    sizes: Vec<u64>,
+    range: (Option<u64>, Option<u64>),
+) -> Result<Response> {
+    let content_type = transcoder.options().format.mime_type();
+
+    let (total, data) = task::spawn_blocking(move || -> Result<(u64, Option<RangeData>)> {
+        let total: u64 = sizes.iter().sum();
+        let Some((first, last)) = resolve_range(range, total) else {
+            return Ok((total, None));
+        };
+
+        let mut data = Vec::with_capacity((last - first + 1) as usize);
+        let mut offset = 0;
+        for (index, size) in sizes.into_iter().enumerate() {
+            let end = offset + size;
+            if end > first && offset <= last {
+                let segment = transcoder.read_segment(index)?;
+                let from = first.saturating_sub(offset) as usize;
+                let to = ((last + 1).min(end) - offset) as usize;
+                data.extend_from_slice(&segment[from..to]);
+            }
+            offset = end;
+        }
+
+        Ok((total, Some((first, last, data))))
+    })
+    .await??;
+
+    Ok(match data {
+        Some((first, last, data)) => (
+            StatusCode::PARTIAL_CONTENT,
+            [
+                (header::CONTENT_TYPE, content_type.to_owned()),
+                (header::ACCEPT_RANGES, "bytes".to_owned()),
+                (
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", first, last, total),
+                ),
+            ],
+            data,
+        )
+            .into_response(),
+        None => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(header::CONTENT_RANGE, format!("bytes */{}", total))],
+        )
+            .into_response(),
+    })
+}
+
+/// Parses a single byte range as `(first, last)`, a suffix range has no
+/// first byte. Multiple ranges are not supported, they are ignored.
+fn parse_range(value: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let (first, last) = value.trim().strip_prefix("bytes=")?.split_once('-')?;
+    if last.contains(',') {
+        return None;
+    }
+
+    let parse = |x: &str| -> Option<Option<u64>> {
+        let x = x.trim();
+        if x.is_empty() {
+            Some(None)
+        } else {
+            x.parse().ok().map(Some)
+        }
+    };
+
+    match (parse(first)?, parse(last)?) {
+        (None, None) => None,
+        range => Some(range),
+    }
+}
+
+fn resolve_range(range: (Option<u64>, Option<u64>), total: u64) -> Option<(u64, u64)> {
+    if total == 0 {
+        return None;
+    }
+
+    match range {
+        (Some(first), last) => {
+            let last = last.unwrap_or(total - 1).min(total - 1);
+            (first <= last).then_some((first, last))
+        }
+        (None, Some(suffix)) if suffix > 0 => Some((total.saturating_sub(suffix), total - 1)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99"), Some((Some(0), Some(99))));
+        assert_eq!(parse_range(" bytes=100- "), Some((Some(100), None)));
+        assert_eq!(parse_range("bytes=-500"), Some((None, Some(500))));
+
+        assert_eq!(parse_range("bytes=-"), None);
+        assert_eq!(parse_range("bytes=0-99,200-299"), None);
+        assert_eq!(parse_range("bytes=a-b"), None);
+        assert_eq!(parse_range("items=0-99"), None);
+        assert_eq!(parse_range("bytes=100"), None);
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        assert_eq!(resolve_range((Some(0), Some(99)), 1000), Some((0, 99)));
+        assert_eq!(resolve_range((Some(900), None), 1000), Some((900, 999)));
+        // The last byte is capped at the end of the stream
+        assert_eq!(
+            resolve_range((Some(900), Some(5000)), 1000),
+            Some((900, 999))
+        );
+        assert_eq!(resolve_range((None, Some(100)), 1000), Some((900, 999)));
+        assert_eq!(resolve_range((None, Some(5000)), 1000), Some((0, 999)));
+
+        assert_eq!(resolve_range((Some(1000), None), 1000), None);
+        assert_eq!(resolve_range((Some(100), Some(99)), 1000), None);
+        assert_eq!(resolve_range((None, Some(0)), 1000), None);
+        assert_eq!(resolve_range((Some(0), None), 0), None);
+    }
+}
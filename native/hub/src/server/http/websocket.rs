@@ -130,6 +130,7 @@ pub async fn handle_socket(socket: WebSocket, state: Arc<ServerState>, user: Use
                                 fingerprint: fingerprint.to_owned(),
                                 host: host.to_owned(),
                             }),
+                            &state.permission_manager,
                         )
                         .await
                     {
@@ -1,13 +1,13 @@
 mod cli;
 
 use std::{
-    sync::{Arc, OnceLock},
+    sync::{atomic::AtomicU32, Arc, OnceLock},
     time::Duration,
 };
 
 use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
-use log::info;
+use log::{error, info};
 use rustls::crypto::aws_lc_rs::default_provider;
 use tokio::sync::{Mutex, RwLock};
 use tokio_util::sync::CancellationToken;
@@ -15,13 +15,14 @@ use tracing_subscriber::EnvFilter;
 
 use cli::{
     broadcast::handle_broadcast, chpwd::handle_chpwd, permission::handle_permission,
-    server::handle_server,
+    server::handle_server, subsonic::handle_subsonic,
 };
 use hub::{
     server::{ServerManager, WebSocketService},
     utils::{
-        initialize_databases, player::initialize_local_player, GlobalParams, RunningMode,
-        TaskTokens,
+        history::PlayHistoryTracker, initialize_databases, player::initialize_local_player,
+        scrobble_store::DatabaseScrobbleStore, watcher::initialize_library_watcher, GlobalParams,
+        RunningMode, TaskTokens,
     },
 };
 
@@ -43,6 +44,12 @@ enum Commands {
     Server {
         #[arg(short, long, default_value = "127.0.0.1:7863")]
         addr: String,
+        /// Also serve MPD clients on this address, such as 127.0.0.1:6600
+        #[arg(long, value_name = "ADDR")]
+        mpd: Option<String>,
+        /// Also serve the library to UPnP/DLNA players on this address, such as 0.0.0.0:8200
+        #[arg(long, value_name = "ADDR")]
+        upnp: Option<String>,
         #[arg(required = true, index = 1)]
         lib_path: String,
     },
@@ -55,6 +62,11 @@ enum Commands {
         #[command(subcommand)]
         action: PermissionAction,
     },
+    /// Manage Subsonic API accounts
+    Subsonic {
+        #[command(subcommand)]
+        action: SubsonicAction,
+    },
 }
 
 #[derive(Subcommand)]
@@ -70,6 +82,24 @@ enum PermissionAction {
         #[arg(value_name = "STATUS")]
         status: String,
     },
+    /// Modify user role
+    Role {
+        /// User index number
+        #[arg(value_name = "INDEX")]
+        index: usize,
+        /// New role (viewer/controller/editor/admin)
+        #[arg(value_name = "ROLE")]
+        role: String,
+    },
+    /// Limit the bitrate of transcoded streams
+    Bitrate {
+        /// User index number
+        #[arg(value_name = "INDEX")]
+        index: usize,
+        /// Highest bitrate in kbps, or "none" to remove the limit
+        #[arg(value_name = "KBPS")]
+        bitrate: String,
+    },
     /// Delete user permission
     Delete {
         /// User index number
@@ -78,6 +108,42 @@ enum PermissionAction {
     },
 }
 
+#[derive(Subcommand)]
+enum SubsonicAction {
+    /// List all accounts and roles
+    Ls,
+    /// Add an account, prompting for its password
+    Add {
+        /// Account username
+        #[arg(value_name = "USERNAME")]
+        username: String,
+        /// Role (viewer/controller/editor/admin)
+        #[arg(value_name = "ROLE", default_value = "viewer")]
+        role: String,
+    },
+    /// Change the password of an account
+    Passwd {
+        /// Account username
+        #[arg(value_name = "USERNAME")]
+        username: String,
+    },
+    /// Modify account role
+    Role {
+        /// Account username
+        #[arg(value_name = "USERNAME")]
+        username: String,
+        /// New role (viewer/controller/editor/admin)
+        #[arg(value_name = "ROLE")]
+        role: String,
+    },
+    /// Delete an account
+    Delete {
+        /// Account username
+        #[arg(value_name = "USERNAME")]
+        username: String,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     setup_logging();
@@ -89,10 +155,16 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Server { addr, lib_path } => handle_server(addr, lib_path).await?,
+        Commands::Server {
+            addr,
+            mpd,
+            upnp,
+            lib_path,
+        } => handle_server(addr, mpd, upnp, lib_path).await?,
         Commands::Chpwd => handle_chpwd().await?,
         Commands::Broadcast => handle_broadcast().await?,
         Commands::Permission { action } => handle_permission(action).await?,
+        Commands::Subsonic { action } => handle_subsonic(action).await?,
     }
 
     Ok(())
@@ -126,8 +198,10 @@ async fn initialize_global_params(lib_path: &str, config_path: &str) -> Result<A
     let sfx_player: Arc<Mutex<SfxPlayer>> = Arc::new(Mutex::new(sfx_player));
 
     let main_cancel_token = Arc::new(main_cancel_token);
+    let play_history = Arc::new(Mutex::new(PlayHistoryTracker::default()));
 
-    let scrobbler = ScrobblingManager::new(10, Duration::new(5, 0));
+    let mut scrobbler = ScrobblingManager::new(10, Duration::new(5, 0));
+    scrobbler.set_store(Arc::new(DatabaseScrobbleStore::new(main_db.clone())));
     let scrobbler = Arc::new(Mutex::new(scrobbler));
 
     let broadcaster = Arc::new(WebSocketService::new());
@@ -141,12 +215,37 @@ async fn initialize_global_params(lib_path: &str, config_path: &str) -> Result<A
         lib_path.clone(),
         main_db.clone(),
         player.clone(),
+        play_history.clone(),
         scrobbler.clone(),
         broadcaster.clone(),
         cert_validator.clone(),
         permission_manager.clone(),
     ));
 
+    let library_version = Arc::new(AtomicU32::new(1));
+
+    info!("Initializing library watcher");
+    let watcher_lib_path = lib_path.clone();
+    let watcher_main_db = main_db.clone();
+    let watcher_task_tokens = task_tokens.clone();
+    let watcher_library_version = library_version.clone();
+    let watcher_broadcaster = broadcaster.clone();
+    let watcher_cancel_token = main_cancel_token.clone();
+    tokio::spawn(async move {
+        if let Err(e) = initialize_library_watcher(
+            watcher_lib_path,
+            watcher_main_db,
+            watcher_task_tokens,
+            watcher_library_version,
+            watcher_broadcaster,
+            watcher_cancel_token,
+        )
+        .await
+        {
+            error!("Library watcher failed: {:?}", e);
+        }
+    });
+
     let global_params = Arc::new(GlobalParams {
         lib_path,
         config_path,
@@ -154,7 +253,9 @@ async fn initialize_global_params(lib_path: &str, config_path: &str) -> Result<A
         recommend_db,
         main_token: main_cancel_token,
         task_tokens,
+        library_version,
         player,
+        play_history,
         sfx_player,
         scrobbler,
         broadcaster,
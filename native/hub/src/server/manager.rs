@@ -35,6 +35,7 @@ use tower_governor::{
 
 use ::database::actions::cover_art::COVER_TEMP_DIR;
 use ::discovery::{client::parse_certificate, ssl::generate_self_signed_cert, DiscoveryParams};
+use ::transcoding::{SegmentCache, TRANSCODE_CACHE_DIR};
 
 use crate::{
     messages::*,
@@ -42,18 +43,23 @@ use crate::{
         http::{
             check_fingerprint::check_fingerprint_handler, device_info::device_info_handler,
             file::file_handler, list::list_users_handler, panel_alias::update_alias_handler,
-            panel_auth_middleware::auth_middleware, panel_broadcast::toggle_broadcast_handler,
-            panel_delete_user::delete_user_handler, panel_login::login_handler,
-            panel_refresh::refresh_handler, panel_self::self_handler,
+            panel_auth_middleware::auth_middleware, panel_bitrate::update_user_bitrate_handler,
+            panel_broadcast::toggle_broadcast_handler, panel_delete_user::delete_user_handler,
+            panel_login::login_handler, panel_refresh::refresh_handler,
+            panel_role::update_user_role_handler, panel_self::self_handler,
             panel_status::update_user_status_handler, ping::ping_handler,
-            register::register_handler, websocket::websocket_handler,
+            register::register_handler, stream::stream_handler, websocket::websocket_handler,
         },
+        subsonic::{self, accounts::SubsonicAccountManager, SubsonicState},
         AppState, ServerState, WebSocketService,
     },
     utils::{GlobalParams, ParamsExtractor, RinfRustSignal},
     Signal,
 };
 
+/// Size in bytes the transcoded streams may take on disk.
+pub(crate) const TRANSCODE_CACHE_SIZE: u64 = 1024 * 1024 * 1024;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JwtClaims {
     /// Expiration time (UNIX timestamp)
@@ -135,9 +141,16 @@ impl ServerManager {
             self.global_params.clone()
         );
 
+        let transcode_cache = Arc::new(SegmentCache::new(
+            TRANSCODE_CACHE_DIR.clone(),
+            TRANSCODE_CACHE_SIZE,
+        ));
+
         let app_state = Arc::new(AppState {
             lib_path: PathBuf::from(&*self.global_params.lib_path),
             cover_temp_dir: COVER_TEMP_DIR.clone(),
+            transcode_cache: transcode_cache.clone(),
+            main_db: self.global_params.main_db.clone(),
         });
 
         let server_state = Arc::new(ServerState {
@@ -148,6 +161,16 @@ impl ServerManager {
             device_scanner: self.global_params.device_scanner.clone(),
         });
 
+        let subsonic_state = Arc::new(SubsonicState {
+            lib_path: app_state.lib_path.clone(),
+            main_db: self.global_params.main_db.clone(),
+            transcode_cache,
+            accounts: Arc::new(
+                SubsonicAccountManager::new(&*self.global_params.config_path)
+                    .context("Failed to load Subsonic accounts")?,
+            ),
+        });
+
         let governor_conf = GovernorConfigBuilder::default()
             .per_second(60)
             .burst_size(5)
@@ -155,6 +178,16 @@ impl ServerManager {
             .finish()
             .unwrap();
 
+        // Subsonic clients sign in with every request, so the API is limited
+        // per address to slow down password guessing. The limit leaves room
+        // for clients loading a page of cover art at once.
+        let subsonic_governor_conf = GovernorConfigBuilder::default()
+            .per_millisecond(50)
+            .burst_size(100)
+            .key_extractor(PeerIpKeyExtractor)
+            .finish()
+            .unwrap();
+
         let auth_routes: Router<Arc<ServerState>> = Router::new()
             .route("/panel/auth/login", post(login_handler))
             .layer(Extension(self.clone()));
@@ -170,6 +203,14 @@ impl ServerManager {
                 "/panel/users/{fingerprint}/status",
                 put(update_user_status_handler),
             )
+            .route(
+                "/panel/users/{fingerprint}/role",
+                put(update_user_role_handler),
+            )
+            .route(
+                "/panel/users/{fingerprint}/bitrate",
+                put(update_user_bitrate_handler),
+            )
             .layer(middleware::from_fn(auth_middleware))
             .layer(Extension(self.clone()))
             .with_state(server_state.clone());
@@ -184,10 +225,14 @@ impl ServerManager {
             .merge(register_route)
             .merge(auth_routes)
             .merge(protected_routes)
+            .merge(subsonic::router(subsonic_state).layer(GovernorLayer {
+                config: subsonic_governor_conf.into(),
+            }))
             .route("/ping", get(ping_handler))
             .route("/ws", get(websocket_handler))
             .route("/check-fingerprint", get(check_fingerprint_handler))
             .route("/files/{*file_path}", get(file_handler))
+            .route("/stream/{*file_path}", get(stream_handler))
             .route("/device-info", get(device_info_handler))
             .with_state(server_state);
 
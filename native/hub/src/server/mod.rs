@@ -3,6 +3,9 @@ mod server_request;
 pub mod api;
 pub mod http;
 mod manager;
+pub mod mpd;
+pub mod subsonic;
+pub mod upnp;
 pub mod utils;
 
 use discovery::protocol::DiscoveryService;
@@ -13,13 +16,20 @@ pub use manager::ServerManager;
 
 use std::{collections::HashMap, future::Future, path::PathBuf, pin::Pin, sync::Arc};
 
-use log::error;
+use log::{error, warn};
+use prost::Message;
 use tokio::sync::{broadcast, Mutex, RwLock};
 
-use ::discovery::{server::PermissionManager, utils::DeviceInfo};
+use ::database::connection::MainDbConnection;
+use ::discovery::{
+    server::{Capability, PermissionManager},
+    utils::DeviceInfo,
+};
+use ::transcoding::SegmentCache;
 
 use crate::{
     backends::remote::encode_message,
+    messages::CrashResponse,
     utils::{Broadcaster, RinfRustSignal},
     Session,
 };
@@ -34,6 +44,8 @@ pub type BroadcastTx = broadcast::Sender<Vec<u8>>;
 pub struct AppState {
     pub lib_path: PathBuf,
     pub cover_temp_dir: PathBuf,
+    pub transcode_cache: Arc<SegmentCache>,
+    pub main_db: Arc<MainDbConnection>,
 }
 
 #[derive(Clone)]
@@ -45,6 +57,21 @@ pub struct ServerState {
     pub device_scanner: Arc<DiscoveryService>,
 }
 
+macro_rules! define_required_capability {
+    ($($request:ident => $capability:ident),*) => {
+        /// The capability a client needs to send a request, requests unknown
+        /// to the protocol are only allowed to admins.
+        pub fn required_capability(msg_type: &str) -> Capability {
+            match msg_type {
+                $(stringify!($request) => Capability::$capability,)*
+                _ => Capability::Admin,
+            }
+        }
+    };
+}
+
+for_all_request_capabilities!(define_required_capability);
+
 pub struct WebSocketService {
     pub handlers: HandlerMap,
     pub broadcast_tx: BroadcastTx,
@@ -81,9 +108,44 @@ impl WebSocketService {
         msg_type: &str,
         payload: Vec<u8>,
         session: Option<Session>,
+        permission_manager: &RwLock<PermissionManager>,
     ) -> Option<(String, Vec<u8>)> {
         let handlers = self.handlers.lock().await;
         let handler = handlers.get(msg_type)?;
+
+        // Checked on every request, so role changes apply to open connections
+        let capability = required_capability(msg_type);
+        let allowed = match &session {
+            Some(session) => {
+                permission_manager
+                    .read()
+                    .await
+                    .is_allowed(&session.fingerprint, capability)
+                    .await
+            }
+            None => false,
+        };
+
+        if !allowed {
+            warn!(
+                "Rejected {} from {}: {:?} capability required",
+                msg_type,
+                session.as_ref().map_or("", |x| x.fingerprint.as_str()),
+                capability
+            );
+
+            return Some((
+                "CrashResponse".to_owned(),
+                CrashResponse {
+                    detail: format!(
+                        "Permission denied: {} requires the {:?} capability",
+                        msg_type, capability
+                    ),
+                }
+                .encode_to_vec(),
+            ));
+        }
+
         Some(handler(payload, session).await)
     }
 }
@@ -108,3 +170,154 @@ impl Clone for WebSocketService {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use discovery::server::{UserRole, UserStatus};
+    use discovery::utils::DeviceType;
+
+    use super::*;
+
+    fn session(fingerprint: &str) -> Option<Session> {
+        Some(Session {
+            fingerprint: fingerprint.to_owned(),
+            host: "192.168.1.2".to_owned(),
+        })
+    }
+
+    async fn add_user(
+        permission_manager: &RwLock<PermissionManager>,
+        fingerprint: &str,
+        status: UserStatus,
+        role: UserRole,
+    ) {
+        let permission_manager = permission_manager.read().await;
+        permission_manager
+            .add_user(
+                format!("key-{}", fingerprint),
+                fingerprint.to_owned(),
+                fingerprint.to_owned(),
+                "Test".to_owned(),
+                DeviceType::Mobile,
+                format!("ip-{}", fingerprint),
+            )
+            .await
+            .unwrap();
+        permission_manager
+            .change_user_status(fingerprint, status)
+            .await
+            .unwrap();
+        permission_manager
+            .change_user_role(fingerprint, role)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_required_capability() {
+        assert_eq!(
+            required_capability("ScanAudioLibraryRequest"),
+            Capability::EditLibrary
+        );
+        assert_eq!(
+            required_capability("VolumeRequest"),
+            Capability::ControlPlayback
+        );
+        assert_eq!(required_capability("UnknownRequest"), Capability::Admin);
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_checks_capabilities() {
+        let directory = tempfile::tempdir().unwrap();
+        let permission_manager = RwLock::new(PermissionManager::new(directory.path()).unwrap());
+        add_user(
+            &permission_manager,
+            "controller",
+            UserStatus::Approved,
+            UserRole::Controller,
+        )
+        .await;
+        add_user(
+            &permission_manager,
+            "editor",
+            UserStatus::Approved,
+            UserRole::Editor,
+        )
+        .await;
+        add_user(
+            &permission_manager,
+            "pending",
+            UserStatus::Pending,
+            UserRole::Admin,
+        )
+        .await;
+
+        let service = WebSocketService::new();
+        for msg_type in ["VolumeRequest", "ScanAudioLibraryRequest"] {
+            service
+                .register_handler(msg_type, move |payload, _session| async move {
+                    (format!("{}Handled", msg_type), payload)
+                })
+                .await;
+        }
+
+        let send = |msg_type: &'static str, fingerprint: Option<&str>| {
+            let session = fingerprint.and_then(session);
+            let service = &service;
+            let permission_manager = &permission_manager;
+            async move {
+                service
+                    .handle_message(msg_type, vec![1, 2, 3], session, permission_manager)
+                    .await
+                    .map(|(response, _)| response)
+            }
+        };
+
+        assert_eq!(
+            send("VolumeRequest", Some("controller")).await.as_deref(),
+            Some("VolumeRequestHandled")
+        );
+        // Scanning needs a higher role than controlling playback
+        assert_eq!(
+            send("ScanAudioLibraryRequest", Some("controller"))
+                .await
+                .as_deref(),
+            Some("CrashResponse")
+        );
+        assert_eq!(
+            send("ScanAudioLibraryRequest", Some("editor"))
+                .await
+                .as_deref(),
+            Some("ScanAudioLibraryRequestHandled")
+        );
+        // Roles only apply to approved users
+        assert_eq!(
+            send("VolumeRequest", Some("pending")).await.as_deref(),
+            Some("CrashResponse")
+        );
+        assert_eq!(
+            send("VolumeRequest", Some("unknown")).await.as_deref(),
+            Some("CrashResponse")
+        );
+        assert_eq!(
+            send("VolumeRequest", None).await.as_deref(),
+            Some("CrashResponse")
+        );
+        // Requests without a handler are not answered
+        assert_eq!(send("PlayRequest", Some("editor")).await, None);
+
+        // Role changes apply to the next request
+        permission_manager
+            .read()
+            .await
+            .change_user_role("controller", UserRole::Editor)
+            .await
+            .unwrap();
+        assert_eq!(
+            send("ScanAudioLibraryRequest", Some("controller"))
+                .await
+                .as_deref(),
+            Some("ScanAudioLibraryRequestHandled")
+        );
+    }
+}
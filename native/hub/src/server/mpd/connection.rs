@@ -0,0 +1,557 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::Result;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{tcp::OwnedReadHalf, TcpStream},
+    sync::broadcast::{
+        self,
+        error::{RecvError, TryRecvError},
+    },
+};
+
+use ::discovery::server::{Capability, UserRole};
+
+use super::{
+    database::{self, TAG_TYPES},
+    idle::Subsystem,
+    playback,
+    protocol::{push, tokenize, AckCode, Args, MpdError, MpdResult, PROTOCOL_VERSION},
+    queue, MpdState,
+};
+
+const ANYONE: Option<Capability> = None;
+const BROWSE: Option<Capability> = Some(Capability::Browse);
+const CONTROL: Option<Capability> = Some(Capability::ControlPlayback);
+
+/// The supported commands, with the capability each requires. Commands
+/// anyone may send work before signing in.
+const COMMANDS: [(&str, Option<Capability>); 45] = [
+    ("add", CONTROL),
+    ("addid", CONTROL),
+    ("clear", CONTROL),
+    ("close", ANYONE),
+    ("commands", ANYONE),
+    ("consume", CONTROL),
+    ("currentsong", BROWSE),
+    ("decoders", ANYONE),
+    ("delete", CONTROL),
+    ("deleteid", CONTROL),
+    ("find", BROWSE),
+    ("findadd", CONTROL),
+    ("idle", BROWSE),
+    ("list", BROWSE),
+    ("move", CONTROL),
+    ("moveid", CONTROL),
+    ("next", CONTROL),
+    ("noidle", ANYONE),
+    ("notcommands", ANYONE),
+    ("outputs", BROWSE),
+    ("password", ANYONE),
+    ("pause", CONTROL),
+    ("ping", ANYONE),
+    ("play", CONTROL),
+    ("playid", CONTROL),
+    ("playlistid", BROWSE),
+    ("playlistinfo", BROWSE),
+    ("plchanges", BROWSE),
+    ("plchangesposid", BROWSE),
+    ("previous", CONTROL),
+    ("random", CONTROL),
+    ("repeat", CONTROL),
+    ("search", BROWSE),
+    ("searchadd", CONTROL),
+    ("seek", CONTROL),
+    ("seekcur", CONTROL),
+    ("seekid", CONTROL),
+    ("setvol", CONTROL),
+    ("single", CONTROL),
+    ("stats", BROWSE),
+    ("status", BROWSE),
+    ("stop", CONTROL),
+    ("tagtypes", ANYONE),
+    ("urlhandlers", ANYONE),
+    ("volume", CONTROL),
+];
+
+/// Subsystems of MPD that Rune never reports, clients may still wait for them.
+const SILENT_SUBSYSTEMS: [&str; 10] = [
+    "database",
+    "update",
+    "stored_playlist",
+    "output",
+    "partition",
+    "sticker",
+    "subscription",
+    "message",
+    "neighbor",
+    "mount",
+];
+
+struct Client {
+    state: Arc<MpdState>,
+    role: Option<UserRole>,
+    changes: broadcast::Receiver<Subsystem>,
+    /// Subsystems that changed since the client last waited for them
+    pending: HashSet<Subsystem>,
+}
+
+pub async fn handle_connection(state: Arc<MpdState>, stream: TcpStream) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut client = Client {
+        changes: state.changes.subscribe(),
+        state,
+        role: None,
+        pending: HashSet::new(),
+    };
+
+    writer
+        .write_all(format!("OK MPD {}\n", PROTOCOL_VERSION).as_bytes())
+        .await?;
+
+    // Commands of a list run together once the list ends, `list_OK` is sent
+    // after each of them if the flag is set
+    let mut command_list: Option<(bool, Vec<Vec<String>>)> = None;
+
+    while let Some(line) = lines.next_line().await? {
+        let words = match tokenize(&line) {
+            Ok(words) if !words.is_empty() => words,
+            Ok(_) => {
+                let error = MpdError::new(AckCode::Unknown, "No command given");
+                writer.write_all(error.ack(0, "").as_bytes()).await?;
+                continue;
+            }
+            Err(e) => {
+                command_list = None;
+                writer.write_all(e.ack(0, "").as_bytes()).await?;
+                continue;
+            }
+        };
+
+        if let Some((list_ok, mut commands)) = command_list.take() {
+            if words[0] == "command_list_end" {
+                let response = client.run(&commands, list_ok).await;
+                writer.write_all(response.as_bytes()).await?;
+            } else {
+                commands.push(words);
+                command_list = Some((list_ok, commands));
+            }
+            continue;
+        }
+
+        let response = match words[0].as_str() {
+            "command_list_begin" => {
+                command_list = Some((false, Vec::new()));
+                continue;
+            }
+            "command_list_ok_begin" => {
+                command_list = Some((true, Vec::new()));
+                continue;
+            }
+            "close" => break,
+            // Only answered while idle
+            "noidle" => continue,
+            "idle" => match client.idle(&words[1..], &mut lines).await? {
+                Some(response) => response,
+                None => break,
+            },
+            _ => client.run(&[words], false).await,
+        };
+
+        writer.write_all(response.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+impl Client {
+    /// Runs commands until one fails, and returns the whole response.
+    async fn run(&mut self, commands: &[Vec<String>], list_ok: bool) -> String {
+        self.collect_changes();
+
+        let mut out = String::new();
+        for (index, words) in commands.iter().enumerate() {
+            match self.execute(&words[0], &Args(&words[1..])).await {
+                Ok(body) => {
+                    out.push_str(&body);
+                    if list_ok {
+                        out.push_str("list_OK\n");
+                    }
+                }
+                Err(e) => {
+                    out.push_str(&e.ack(index, &words[0]));
+                    return out;
+                }
+            }
+        }
+
+        out.push_str("OK\n");
+        out
+    }
+
+    async fn execute(&mut self, command: &str, args: &Args<'_>) -> MpdResult {
+        self.authorize(command)?;
+        let state = self.state.clone();
+
+        match command {
+            "ping" => Ok(String::new()),
+            "password" => self.sign_in(args).await,
+            "commands" => Ok(self.list_commands(true)),
+            "notcommands" => Ok(self.list_commands(false)),
+            "tagtypes" => Ok(tag_types(args)),
+            "urlhandlers" | "decoders" => Ok(String::new()),
+            "outputs" => Ok(outputs()),
+            "stats" => database::stats(&state).await,
+            "status" => playback::status(&state).await,
+            "currentsong" => playback::current_song(&state).await,
+            "play" => playback::play(&state, args, false).await,
+            "playid" => playback::play(&state, args, true).await,
+            "pause" => playback::pause(&state, args).await,
+            "stop" => playback::stop(&state).await,
+            "next" => playback::skip(&state, true).await,
+            "previous" => playback::skip(&state, false).await,
+            "seek" => playback::seek(&state, args, false).await,
+            "seekid" => playback::seek(&state, args, true).await,
+            "seekcur" => playback::seek_current(&state, args).await,
+            "setvol" => playback::set_volume(&state, args).await,
+            "volume" => playback::change_volume(&state, args).await,
+            "repeat" | "random" | "single" => playback::set_option(&state, command, args).await,
+            "consume" => playback::consume(args),
+            "playlistinfo" => queue::playlist_info(&state, args).await,
+            "playlistid" => queue::playlist_id(&state, args).await,
+            "plchanges" => queue::playlist_changes(&state, args, false).await,
+            "plchangesposid" => queue::playlist_changes(&state, args, true).await,
+            "add" => queue::add(&state, args, false).await,
+            "addid" => queue::add(&state, args, true).await,
+            "delete" => queue::delete(&state, args).await,
+            "deleteid" => queue::delete_id(&state, args).await,
+            "move" => queue::move_items(&state, args).await,
+            "moveid" => queue::move_id(&state, args).await,
+            "clear" => queue::clear(&state).await,
+            "find" => database::find(&state, args, false).await,
+            "search" => database::find(&state, args, true).await,
+            "findadd" => database::find_add(&state, args, false).await,
+            "searchadd" => database::find_add(&state, args, true).await,
+            "list" => database::list(&state, args).await,
+            "idle" | "noidle" | "close" => Err(MpdError::arg(format!(
+                "\"{}\" is not allowed in command lists",
+                command
+            ))),
+            _ => Err(MpdError::unknown(command)),
+        }
+    }
+
+    fn authorize(&self, command: &str) -> Result<(), MpdError> {
+        let (_, capability) = COMMANDS
+            .iter()
+            .find(|(name, _)| *name == command)
+            .ok_or_else(|| MpdError::unknown(command))?;
+
+        match capability {
+            Some(capability) if !self.role.is_some_and(|x| x.allows(*capability)) => {
+                Err(MpdError::new(
+                    AckCode::Permission,
+                    format!("you don't have permission for \"{}\"", command),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Signs in with the credentials of a Subsonic account, given as
+    /// `username:password`.
+    async fn sign_in(&mut self, args: &Args<'_>) -> MpdResult {
+        let account = match args.required(0)?.split_once(':') {
+            Some((username, password)) => self
+                .state
+                .accounts
+                .get_account(username)
+                .await
+                .filter(|x| x.password == password),
+            None => None,
+        };
+
+        let account =
+            account.ok_or_else(|| MpdError::new(AckCode::Password, "incorrect password"))?;
+        self.role = Some(account.role);
+
+        Ok(String::new())
+    }
+
+    fn list_commands(&self, allowed: bool) -> String {
+        let mut out = String::new();
+        for (name, _) in COMMANDS {
+            if self.authorize(name).is_ok() == allowed {
+                push(&mut out, "command", name);
+            }
+        }
+
+        out
+    }
+
+    fn collect_changes(&mut self) {
+        loop {
+            match self.changes.try_recv() {
+                Ok(subsystem) => {
+                    self.pending.insert(subsystem);
+                }
+                Err(TryRecvError::Lagged(_)) => self.pending.extend(Subsystem::ALL),
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Waits until one of the subsystems, or any if none are given, changed
+    /// or the client sends `noidle`. Returns `None` if the connection should
+    /// be closed.
+    async fn idle(
+        &mut self,
+        names: &[String],
+        lines: &mut Lines<BufReader<OwnedReadHalf>>,
+    ) -> Result<Option<String>> {
+        if let Err(e) = self.authorize("idle") {
+            return Ok(Some(e.ack(0, "idle")));
+        }
+
+        let mut wanted = Vec::new();
+        for name in names {
+            let name = name.to_lowercase();
+            match Subsystem::from_name(&name) {
+                Some(subsystem) => wanted.push(subsystem),
+                None if SILENT_SUBSYSTEMS.contains(&name.as_str()) => {}
+                None => {
+                    let error = MpdError::arg(format!("Unrecognized idle event: {}", name));
+                    return Ok(Some(error.ack(0, "idle")));
+                }
+            }
+        }
+        if names.is_empty() {
+            wanted.extend(Subsystem::ALL);
+        }
+
+        loop {
+            self.collect_changes();
+
+            let changed: Vec<Subsystem> = wanted
+                .iter()
+                .copied()
+                .filter(|x| self.pending.remove(x))
+                .collect();
+            if !changed.is_empty() {
+                let mut out = String::new();
+                for subsystem in changed {
+                    push(&mut out, "changed", subsystem.name());
+                }
+                out.push_str("OK\n");
+                return Ok(Some(out));
+            }
+
+            tokio::select! {
+                line = lines.next_line() => {
+                    return match line? {
+                        Some(line) if line.trim() == "noidle" => Ok(Some("OK\n".to_owned())),
+                        // Other commands are not allowed while idle
+                        _ => Ok(None),
+                    };
+                }
+                changed = self.changes.recv() => match changed {
+                    Ok(subsystem) => {
+                        self.pending.insert(subsystem);
+                    }
+                    Err(RecvError::Lagged(_)) => self.pending.extend(Subsystem::ALL),
+                    Err(RecvError::Closed) => return Ok(None),
+                },
+            }
+        }
+    }
+}
+
+/// Lists the tags songs may have. Clients can not choose which tags they
+/// get, so the subcommands doing so are accepted and ignored.
+fn tag_types(args: &Args<'_>) -> String {
+    let mut out = String::new();
+    if args.get(0).is_none() {
+        for (name, _) in TAG_TYPES {
+            push(&mut out, "tagtype", name);
+        }
+    }
+
+    out
+}
+
+/// Rune plays through a single output, which can not be turned off.
+fn outputs() -> String {
+    let mut out = String::new();
+    push(&mut out, "outputid", 0);
+    push(&mut out, "outputname", "Rune");
+    push(&mut out, "plugin", "rune");
+    push(&mut out, "outputenabled", 1);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use tokio::{
+        net::{tcp::OwnedWriteHalf, TcpListener},
+        sync::Mutex,
+    };
+
+    use ::database::connection::connect_fake_main_db;
+    use ::playback::player::MockPlayer;
+
+    use crate::server::{
+        mpd::{idle::ChangeTracker, ids::QueueIds},
+        subsonic::accounts::SubsonicAccountManager,
+    };
+
+    use super::*;
+
+    /// A client talking to a connection, one command at a time.
+    struct Session {
+        lines: Lines<BufReader<OwnedReadHalf>>,
+        writer: OwnedWriteHalf,
+    }
+
+    impl Session {
+        async fn connect(state: Arc<MpdState>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                handle_connection(state, stream).await.unwrap();
+            });
+
+            let (reader, writer) = TcpStream::connect(addr).await.unwrap().into_split();
+            Self {
+                lines: BufReader::new(reader).lines(),
+                writer,
+            }
+        }
+
+        /// Sends lines and reads the response, up to its `OK` or `ACK`.
+        async fn send(&mut self, lines: &str) -> String {
+            self.writer
+                .write_all(format!("{}\n", lines).as_bytes())
+                .await
+                .unwrap();
+            self.response().await
+        }
+
+        async fn response(&mut self) -> String {
+            let mut out = String::new();
+            while let Some(line) = self.lines.next_line().await.unwrap() {
+                out.push_str(&line);
+                out.push('\n');
+                if line.starts_with("OK") || line.starts_with("ACK ") {
+                    break;
+                }
+            }
+
+            out
+        }
+    }
+
+    async fn test_state(directory: &tempfile::TempDir) -> Arc<MpdState> {
+        let accounts = SubsonicAccountManager::new(directory.path()).unwrap();
+        accounts
+            .add_account("alice", "secret", UserRole::Admin)
+            .await
+            .unwrap();
+        accounts
+            .add_account("bob", "hunter2", UserRole::Viewer)
+            .await
+            .unwrap();
+
+        Arc::new(MpdState {
+            lib_path: Arc::new(directory.path().to_string_lossy().into_owned()),
+            main_db: Arc::new(connect_fake_main_db().await.unwrap()),
+            player: Arc::new(Mutex::new(MockPlayer)),
+            accounts,
+            changes: ChangeTracker::new(),
+            ids: QueueIds::new(),
+            started_at: Instant::now(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_session() {
+        let directory = tempfile::tempdir().unwrap();
+        let state = test_state(&directory).await;
+
+        let mut session = Session::connect(state.clone()).await;
+        assert_eq!(
+            session.response().await,
+            format!("OK MPD {}\n", PROTOCOL_VERSION)
+        );
+
+        assert_eq!(
+            session.send("status").await,
+            "ACK [4@0] {status} you don't have permission for \"status\"\n"
+        );
+        assert_eq!(session.send("ping").await, "OK\n");
+        assert_eq!(
+            session.send("password alice:wrong").await,
+            "ACK [3@0] {password} incorrect password\n"
+        );
+        assert_eq!(session.send("password alice:secret").await, "OK\n");
+
+        let status = session.send("status").await;
+        assert!(status.contains("playlistlength: 0\n"), "{}", status);
+        assert!(status.contains("state: stop\n"), "{}", status);
+        assert!(status.ends_with("OK\n"), "{}", status);
+
+        assert_eq!(
+            session
+                .send("command_list_ok_begin\nping\nping\ncommand_list_end")
+                .await,
+            "list_OK\nlist_OK\nOK\n"
+        );
+        // Commands after a failed one do not run
+        assert_eq!(
+            session
+                .send("command_list_begin\nping\nfoo\nping\ncommand_list_end")
+                .await,
+            "ACK [5@1] {foo} unknown command \"foo\"\n"
+        );
+
+        assert_eq!(
+            session.send("find \"album").await,
+            "ACK [2@0] {} Unterminated quoted string\n"
+        );
+        assert_eq!(session.send("").await, "ACK [5@0] {} No command given\n");
+        assert_eq!(
+            session.send("deleteid 7").await,
+            "ACK [50@0] {deleteid} No such song\n"
+        );
+        assert_eq!(
+            session.send("playid 7").await,
+            "ACK [50@0] {playid} No such song\n"
+        );
+        assert_eq!(
+            session.send("idle mood").await,
+            "ACK [2@0] {idle} Unrecognized idle event: mood\n"
+        );
+        assert_eq!(session.send("idle playlist database\nnoidle").await, "OK\n");
+
+        // The connection closes without a response
+        assert_eq!(session.send("close").await, "");
+
+        // Viewers may browse but not control playback
+        let mut session = Session::connect(state).await;
+        session.response().await;
+        assert_eq!(session.send("password bob:hunter2").await, "OK\n");
+        assert!(session.send("currentsong").await.ends_with("OK\n"));
+        assert_eq!(
+            session.send("play").await,
+            "ACK [4@0] {play} you don't have permission for \"play\"\n"
+        );
+
+        let commands = session.send("notcommands").await;
+        assert!(commands.contains("command: play\n"), "{}", commands);
+        assert!(!commands.contains("command: status\n"), "{}", commands);
+    }
+}
@@ -0,0 +1,415 @@
+use std::{iter::Peekable, str::Chars};
+
+use ::database::actions::catalog::{find_track_ids, get_metadata_values, TrackField, TrackFilter};
+
+use super::{
+    protocol::{push, Args, MpdError, MpdResult},
+    queue::append_tracks,
+    song::{load_summaries, write_song},
+    MpdState,
+};
+
+/// The tags clients can ask for, with the metadata key each is stored as.
+pub const TAG_TYPES: [(&str, &str); 9] = [
+    ("Artist", "artist"),
+    ("AlbumArtist", "album_artist"),
+    ("Album", "album"),
+    ("Title", "track_title"),
+    ("Track", "track_number"),
+    ("Disc", "disc_number"),
+    ("Genre", "genre"),
+    ("Date", "date"),
+    ("Composer", "composer"),
+];
+
+/// Finds the name and the metadata key of a tag, ignoring case.
+fn tag_type(name: &str) -> Option<(&'static str, &'static str)> {
+    TAG_TYPES
+        .into_iter()
+        .find(|(tag, _)| tag.eq_ignore_ascii_case(name))
+}
+
+fn track_field(name: &str) -> Result<TrackField, MpdError> {
+    match name.to_lowercase().as_str() {
+        "any" => Ok(TrackField::Any),
+        "file" => Ok(TrackField::Path),
+        "base" => Ok(TrackField::Base),
+        _ => tag_type(name)
+            .map(|(_, key)| TrackField::Metadata(key.to_owned()))
+            .ok_or_else(|| MpdError::arg(format!("Unknown tag type: {}", name))),
+    }
+}
+
+/// A request for songs: filters followed by `sort`, `window` or `group`
+/// options. Songs are always sorted by path and never grouped.
+struct Query {
+    filters: Vec<TrackFilter>,
+    window: Option<(usize, usize)>,
+}
+
+impl Query {
+    /// Parses either a filter expression such as `((artist == 'x') AND
+    /// (album contains 'y'))` or `TYPE VALUE` pairs. Pairs match whole
+    /// values in `find` and parts of values, ignoring case, in `search`.
+    fn parse(words: &[String], partial: bool) -> Result<Self, MpdError> {
+        let mut filters = Vec::new();
+        let mut index = 0;
+
+        if words.first().is_some_and(|x| x.starts_with('(')) {
+            filters = parse_expression(&words[0])?;
+            index = 1;
+        } else {
+            while index < words.len() && !is_option(&words[index]) {
+                let value = words
+                    .get(index + 1)
+                    .ok_or_else(|| MpdError::arg("Missing filter value"))?;
+                filters.push(TrackFilter {
+                    field: track_field(&words[index])?,
+                    value: value.clone(),
+                    partial,
+                    negated: false,
+                });
+                index += 2;
+            }
+        }
+
+        if filters.is_empty() {
+            return Err(MpdError::arg("too few arguments"));
+        }
+
+        let mut window = None;
+        while index < words.len() {
+            let option = words[index].to_lowercase();
+            if !is_option(&option) {
+                return Err(MpdError::arg(format!("Unknown argument: {}", words[index])));
+            }
+
+            let args = Args(&words[index + 1..]);
+            args.required(0)?;
+            if option == "window" {
+                window = args.range(0, usize::MAX)?;
+            }
+            index += 2;
+        }
+
+        Ok(Self { filters, window })
+    }
+
+    async fn find(&self, state: &MpdState) -> Result<Vec<i32>, MpdError> {
+        let mut file_ids = find_track_ids(&state.main_db, &self.filters).await?;
+
+        if let Some((start, end)) = self.window {
+            file_ids = file_ids.into_iter().skip(start).take(end - start).collect();
+        }
+
+        Ok(file_ids)
+    }
+}
+
+fn is_option(word: &str) -> bool {
+    ["sort", "window", "group"]
+        .iter()
+        .any(|x| x.eq_ignore_ascii_case(word))
+}
+
+/// Parses a filter expression. Expressions may be combined with `AND` and
+/// single comparisons negated with `!`, other forms are not supported.
+fn parse_expression(expression: &str) -> Result<Vec<TrackFilter>, MpdError> {
+    let mut chars = expression.chars().peekable();
+    let filters = parse_group(&mut chars)?;
+
+    skip_spaces(&mut chars);
+    if chars.next().is_some() {
+        return Err(MpdError::arg("Unexpected text after the filter"));
+    }
+
+    Ok(filters)
+}
+
+fn parse_group(chars: &mut Peekable<Chars>) -> Result<Vec<TrackFilter>, MpdError> {
+    skip_spaces(chars);
+    expect(chars, '(')?;
+    skip_spaces(chars);
+
+    let filters = match chars.peek() {
+        Some('(') => {
+            let mut filters = parse_group(chars)?;
+            loop {
+                skip_spaces(chars);
+                if chars.peek() == Some(&')') {
+                    break;
+                }
+
+                let word = read_word(chars);
+                if word != "AND" {
+                    return Err(MpdError::arg(format!(
+                        "Unsupported filter operator: {}",
+                        word
+                    )));
+                }
+                filters.extend(parse_group(chars)?);
+            }
+            filters
+        }
+        Some('!') => {
+            chars.next();
+            let mut filters = parse_group(chars)?;
+            match filters.as_mut_slice() {
+                [filter] => filter.negated = !filter.negated,
+                _ => return Err(MpdError::arg("Only single comparisons can be negated")),
+            }
+            filters
+        }
+        _ => vec![parse_comparison(chars)?],
+    };
+
+    skip_spaces(chars);
+    expect(chars, ')')?;
+
+    Ok(filters)
+}
+
+fn parse_comparison(chars: &mut Peekable<Chars>) -> Result<TrackFilter, MpdError> {
+    let tag = read_word(chars);
+    let field = track_field(&tag)?;
+    skip_spaces(chars);
+
+    // `base` takes a directory without an operator
+    let operator = if field == TrackField::Base {
+        String::from("==")
+    } else {
+        let operator = read_word(chars);
+        skip_spaces(chars);
+        operator
+    };
+
+    let (partial, negated) = match operator.as_str() {
+        "==" => (false, false),
+        "!=" => (false, true),
+        "contains" => (true, false),
+        _ => {
+            return Err(MpdError::arg(format!(
+                "Unsupported filter operator: {}",
+                operator
+            )))
+        }
+    };
+
+    Ok(TrackFilter {
+        field,
+        value: read_quoted(chars)?,
+        partial,
+        negated,
+    })
+}
+
+fn skip_spaces(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|x| x.is_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), MpdError> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        _ => Err(MpdError::arg(format!("'{}' expected in filter", expected))),
+    }
+}
+
+fn read_word(chars: &mut Peekable<Chars>) -> String {
+    skip_spaces(chars);
+
+    let mut word = String::new();
+    while let Some(c) = chars.next_if(|x| !x.is_whitespace() && *x != '(' && *x != ')') {
+        word.push(c);
+    }
+
+    word
+}
+
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String, MpdError> {
+    let quote = match chars.next() {
+        Some(c @ ('\'' | '"')) => c,
+        _ => return Err(MpdError::arg("Quoted value expected in filter")),
+    };
+
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => value.extend(chars.next()),
+            Some(c) if c == quote => return Ok(value),
+            Some(c) => value.push(c),
+            None => return Err(MpdError::arg("Unterminated value in filter")),
+        }
+    }
+}
+
+/// Lists the songs matching a query. `find` matches whole values, `search`
+/// parts of values.
+pub async fn find(state: &MpdState, args: &Args<'_>, partial: bool) -> MpdResult {
+    let file_ids = Query::parse(args.0, partial)?.find(state).await?;
+    let summaries = load_summaries(&state.main_db, &file_ids).await?;
+
+    let mut out = String::new();
+    for file_id in file_ids {
+        if let Some(summary) = summaries.get(&file_id) {
+            write_song(&mut out, summary);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Adds the songs matching a query to the queue.
+pub async fn find_add(state: &MpdState, args: &Args<'_>, partial: bool) -> MpdResult {
+    let file_ids = Query::parse(args.0, partial)?.find(state).await?;
+    if !file_ids.is_empty() {
+        append_tracks(state, &file_ids, None).await?;
+    }
+
+    Ok(String::new())
+}
+
+/// Lists the values of a tag, among the songs matching a query if given.
+/// The legacy form `list album ARTIST` is accepted too.
+pub async fn list(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let tag = args.required(0)?;
+    let (name, meta_key) =
+        tag_type(tag).ok_or_else(|| MpdError::arg(format!("Unknown tag type: {}", tag)))?;
+
+    let rest = &args.0[1..];
+    let filters = match rest {
+        [] => vec![],
+        [artist] if name == "Album" && !artist.starts_with('(') => vec![TrackFilter {
+            field: TrackField::Metadata(String::from("artist")),
+            value: artist.clone(),
+            partial: false,
+            negated: false,
+        }],
+        _ if is_option(&rest[0]) => vec![],
+        _ => Query::parse(rest, false)?.filters,
+    };
+
+    let mut out = String::new();
+    for value in get_metadata_values(&state.main_db, meta_key, &filters).await? {
+        push(&mut out, name, value);
+    }
+
+    Ok(out)
+}
+
+pub async fn stats(state: &MpdState) -> MpdResult {
+    let artists = get_metadata_values(&state.main_db, "artist", &[]).await?;
+    let albums = get_metadata_values(&state.main_db, "album", &[]).await?;
+    let songs = find_track_ids(&state.main_db, &[]).await?;
+
+    let mut out = String::new();
+    push(&mut out, "artists", artists.len());
+    push(&mut out, "albums", albums.len());
+    push(&mut out, "songs", songs.len());
+    push(&mut out, "uptime", state.started_at.elapsed().as_secs());
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// Describes filters as `(field, value, partial, negated)`.
+    fn describe(filters: &[TrackFilter]) -> Vec<(TrackField, &str, bool, bool)> {
+        filters
+            .iter()
+            .map(|x| (x.field.clone(), x.value.as_str(), x.partial, x.negated))
+            .collect()
+    }
+
+    fn metadata(key: &str) -> TrackField {
+        TrackField::Metadata(key.to_owned())
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let filters = parse_expression("(artist == 'Queen')").unwrap();
+        assert_eq!(
+            describe(&filters),
+            vec![(metadata("artist"), "Queen", false, false)]
+        );
+
+        let filters = parse_expression(
+            r#"((Album contains "night") AND (!(genre != 'Rock')) AND (base 'Queen/A Night'))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            describe(&filters),
+            vec![
+                (metadata("album"), "night", true, false),
+                (metadata("genre"), "Rock", false, false),
+                (TrackField::Base, "Queen/A Night", false, false),
+            ]
+        );
+
+        let filters = parse_expression(r"(file == 'It\'s/a.flac')").unwrap();
+        assert_eq!(
+            describe(&filters),
+            vec![(TrackField::Path, "It's/a.flac", false, false)]
+        );
+
+        for expression in [
+            "(artist == 'Queen'",
+            "(artist == Queen)",
+            "(artist =~ 'Queen')",
+            "(mood == 'happy')",
+            "((artist == 'a') OR (artist == 'b'))",
+            "(!((artist == 'a') AND (album == 'b')))",
+            "(artist == 'Queen') x",
+            "(artist == 'Queen)",
+        ] {
+            assert!(parse_expression(expression).is_err(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = Query::parse(&words(&["artist", "Queen", "album", "Jazz"]), false).unwrap();
+        assert_eq!(
+            describe(&query.filters),
+            vec![
+                (metadata("artist"), "Queen", false, false),
+                (metadata("album"), "Jazz", false, false),
+            ]
+        );
+        assert_eq!(query.window, None);
+
+        let query = Query::parse(&words(&["any", "jazz", "window", "2:5"]), true).unwrap();
+        assert_eq!(
+            describe(&query.filters),
+            vec![(TrackField::Any, "jazz", true, false)]
+        );
+        assert_eq!(query.window, Some((2, 5)));
+
+        let query = Query::parse(
+            &words(&["(title contains 'love')", "sort", "Artist", "WINDOW", "0:1"]),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            describe(&query.filters),
+            vec![(metadata("track_title"), "love", true, false)]
+        );
+        assert_eq!(query.window, Some((0, 1)));
+
+        for query in [
+            &["artist"][..],
+            &[][..],
+            &["window", "0:1"][..],
+            &["mood", "happy"][..],
+            &["artist", "Queen", "sort"][..],
+            &["(artist == 'Queen')", "extra", "x"][..],
+        ] {
+            assert!(Query::parse(&words(query), false).is_err(), "{:?}", query);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    sync::{broadcast, Mutex},
+    time::interval,
+};
+use tokio_util::sync::CancellationToken;
+
+use ::playback::player::{Playable, PlaybackState, PlayerStatus};
+
+/// How often the player is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A position further than this from where playback should be was seeked to.
+const SEEK_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// The parts of the player clients can wait for changes of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    /// Playback started, stopped, seeked or moved to another track
+    Player,
+    /// The volume changed
+    Mixer,
+    /// The playback mode changed
+    Options,
+    /// The queue changed
+    Playlist,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] = [
+        Subsystem::Player,
+        Subsystem::Mixer,
+        Subsystem::Options,
+        Subsystem::Playlist,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subsystem::Player => "player",
+            Subsystem::Mixer => "mixer",
+            Subsystem::Options => "options",
+            Subsystem::Playlist => "playlist",
+        }
+    }
+
+    /// Parses a subsystem name. Subsystems Rune never reports, such as
+    /// `database`, are accepted and never fire.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Subsystem::ALL.into_iter().find(|x| x.name() == name)
+    }
+}
+
+/// Watches the player and tells every connection what changed.
+///
+/// The player only has one subscriber for each kind of event, which the
+/// hub already uses, so the tracker polls its status instead.
+pub struct ChangeTracker {
+    sender: broadcast::Sender<Subsystem>,
+    playlist_version: AtomicU32,
+}
+
+impl Default for ChangeTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangeTracker {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(64);
+
+        Self {
+            sender,
+            playlist_version: AtomicU32::new(1),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Subsystem> {
+        self.sender.subscribe()
+    }
+
+    /// A number that grows every time the queue changes.
+    pub fn playlist_version(&self) -> u32 {
+        self.playlist_version.load(Ordering::Relaxed)
+    }
+
+    /// Polls the player until `cancel_token` is cancelled.
+    pub async fn run(
+        &self,
+        player: Arc<Mutex<dyn Playable>>,
+        cancel_token: Arc<CancellationToken>,
+    ) {
+        let mut ticker = interval(POLL_INTERVAL);
+        let mut previous = player.lock().await.get_status();
+        let mut polled_at = Instant::now();
+
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => break,
+                _ = ticker.tick() => {}
+            }
+
+            let status = player.lock().await.get_status();
+            let elapsed = polled_at.elapsed();
+            polled_at = Instant::now();
+
+            for subsystem in changes(&previous, &status, elapsed) {
+                if subsystem == Subsystem::Playlist {
+                    self.playlist_version.fetch_add(1, Ordering::Relaxed);
+                }
+
+                // Nobody may be waiting, which is fine
+                let _ = self.sender.send(subsystem);
+            }
+
+            previous = status;
+        }
+    }
+}
+
+fn changes(previous: &PlayerStatus, current: &PlayerStatus, elapsed: Duration) -> Vec<Subsystem> {
+    let mut changes = Vec::new();
+
+    // The position only moves while playing
+    let expected_position =
+        if previous.state == current.state && current.state == PlaybackState::Playing {
+            previous.position + elapsed
+        } else {
+            previous.position
+        };
+    let seeked = current.position.abs_diff(expected_position) > SEEK_TOLERANCE;
+
+    if previous.state != current.state
+        || previous.index != current.index
+        || previous.item != current.item
+        || seeked
+    {
+        changes.push(Subsystem::Player);
+    }
+    if previous.volume != current.volume {
+        changes.push(Subsystem::Mixer);
+    }
+    if previous.playback_mode != current.playback_mode {
+        changes.push(Subsystem::Options);
+    }
+    if previous.playlist != current.playlist {
+        changes.push(Subsystem::Playlist);
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use ::playback::player::PlayingItem;
+
+    use super::*;
+
+    fn status() -> PlayerStatus {
+        PlayerStatus {
+            item: Some(PlayingItem::InLibrary(1)),
+            index: Some(0),
+            path: None,
+            position: Duration::from_secs(10),
+            state: PlaybackState::Playing,
+            playlist: vec![PlayingItem::InLibrary(1), PlayingItem::InLibrary(2)],
+            playback_mode: 0u32.into(),
+            ready: true,
+            volume: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_changes() {
+        let previous = status();
+
+        // Playback going on as expected is no change
+        let mut current = status();
+        current.position = Duration::from_secs(11);
+        assert!(changes(&previous, &current, Duration::from_secs(1)).is_empty());
+        assert!(changes(&previous, &previous, Duration::ZERO).is_empty());
+
+        current.position = Duration::from_secs(60);
+        assert_eq!(
+            changes(&previous, &current, Duration::from_secs(1)),
+            vec![Subsystem::Player]
+        );
+
+        // The position does not move while paused
+        let mut current = status();
+        current.state = PlaybackState::Paused;
+        assert_eq!(
+            changes(&previous, &current, Duration::from_secs(5)),
+            vec![Subsystem::Player]
+        );
+        let paused = current.clone();
+        assert!(changes(&paused, &current, Duration::from_secs(5)).is_empty());
+
+        let mut current = status();
+        current.index = Some(1);
+        current.item = Some(PlayingItem::InLibrary(2));
+        current.position = Duration::ZERO;
+        assert_eq!(
+            changes(&previous, &current, Duration::ZERO),
+            vec![Subsystem::Player]
+        );
+
+        let mut current = status();
+        current.volume = 0.5;
+        current.playback_mode = 3u32.into();
+        current.playlist.push(PlayingItem::InLibrary(3));
+        assert_eq!(
+            changes(&previous, &current, Duration::ZERO),
+            vec![Subsystem::Mixer, Subsystem::Options, Subsystem::Playlist]
+        );
+    }
+}
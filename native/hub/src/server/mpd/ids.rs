@@ -0,0 +1,157 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use ::playback::player::PlayingItem;
+
+/// Ids reserved for entries the player has not added yet, beyond which the
+/// oldest are dropped.
+const MAX_RESERVED: usize = 64;
+
+/// Gives the entries of the queue ids that stay the same while entries
+/// move and others are added or removed, as clients expect.
+///
+/// The player only knows the items of its queue, so ids are matched to
+/// items: when the queue changes, each entry keeps the id of an entry with
+/// the same item, in order, and new entries get the next id.
+pub struct QueueIds {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    entries: Vec<(PlayingItem, u32)>,
+    /// Ids given out for entries being added, the player adds them later
+    reserved: VecDeque<(PlayingItem, u32)>,
+    next_id: u32,
+}
+
+impl Default for QueueIds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueueIds {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                entries: Vec::new(),
+                reserved: VecDeque::new(),
+                next_id: 1,
+            }),
+        }
+    }
+
+    /// Returns the ids of the entries of `playlist`, the current queue.
+    pub fn sync(&self, playlist: &[PlayingItem]) -> Vec<u32> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let mut available: HashMap<&PlayingItem, VecDeque<u32>> = HashMap::new();
+        for (item, id) in &inner.entries {
+            available.entry(item).or_default().push_back(*id);
+        }
+
+        let mut ids = Vec::with_capacity(playlist.len());
+        let mut reserved = inner.reserved.clone();
+        let mut next_id = inner.next_id;
+        for item in playlist {
+            let id = match available.get_mut(item).and_then(|x| x.pop_front()) {
+                Some(id) => id,
+                None => match reserved.iter().position(|(x, _)| x == item) {
+                    Some(index) => reserved.remove(index).unwrap().1,
+                    None => {
+                        let id = next_id;
+                        next_id += 1;
+                        id
+                    }
+                },
+            };
+            ids.push(id);
+        }
+
+        inner.entries = playlist.iter().cloned().zip(ids.iter().copied()).collect();
+        inner.reserved = reserved;
+        inner.next_id = next_id;
+
+        ids
+    }
+
+    /// Returns the position of the entry with `id` in `playlist`.
+    pub fn position(&self, playlist: &[PlayingItem], id: u32) -> Option<usize> {
+        self.sync(playlist).iter().position(|x| *x == id)
+    }
+
+    /// Gives out the id of an entry of `item` about to be added, which it
+    /// gets once the player added it.
+    pub fn reserve(&self, item: PlayingItem) -> u32 {
+        let mut inner = self.inner.lock().unwrap();
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        // Entries that failed to be added would otherwise be kept forever
+        if inner.reserved.len() >= MAX_RESERVED {
+            inner.reserved.pop_front();
+        }
+        inner.reserved.push_back((item, id));
+
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(file_ids: &[i32]) -> Vec<PlayingItem> {
+        file_ids
+            .iter()
+            .map(|x| PlayingItem::InLibrary(*x))
+            .collect()
+    }
+
+    #[test]
+    fn test_sync() {
+        let ids = QueueIds::new();
+        assert_eq!(ids.sync(&items(&[10, 20, 30])), vec![1, 2, 3]);
+
+        // Entries keep their ids when moved or when others are removed
+        assert_eq!(ids.sync(&items(&[30, 10, 20])), vec![3, 1, 2]);
+        assert_eq!(ids.sync(&items(&[30, 20])), vec![3, 2]);
+
+        // Removed ids are not given again
+        assert_eq!(ids.sync(&items(&[30, 20, 10])), vec![3, 2, 4]);
+
+        // Entries of the same item keep their ids in order
+        assert_eq!(ids.sync(&items(&[20, 30, 20, 10])), vec![2, 3, 5, 4]);
+        assert_eq!(ids.sync(&items(&[20, 20])), vec![2, 5]);
+
+        assert_eq!(ids.sync(&[]), Vec::<u32>::new());
+        assert_eq!(ids.sync(&items(&[20])), vec![6]);
+        assert_eq!(ids.position(&items(&[10, 20]), 6), Some(1));
+        assert_eq!(ids.position(&items(&[10, 20]), 2), None);
+    }
+
+    #[test]
+    fn test_reserve() {
+        let ids = QueueIds::new();
+        assert_eq!(ids.sync(&items(&[10])), vec![1]);
+
+        let id = ids.reserve(PlayingItem::InLibrary(20));
+        assert_eq!(id, 2);
+
+        // The player has not added the entry yet
+        assert_eq!(ids.sync(&items(&[10])), vec![1]);
+        assert_eq!(ids.sync(&items(&[20, 10])), vec![2, 1]);
+
+        // The reserved id is only given once
+        assert_eq!(ids.sync(&items(&[20, 10, 20])), vec![2, 1, 3]);
+
+        for file_id in 0..MAX_RESERVED as i32 + 1 {
+            ids.reserve(PlayingItem::InLibrary(100 + file_id));
+        }
+        // The oldest reservation was dropped
+        assert_eq!(ids.sync(&items(&[100, 101])), vec![69, 5]);
+    }
+}
@@ -0,0 +1,92 @@
+mod connection;
+mod database;
+mod idle;
+mod ids;
+mod playback;
+mod protocol;
+mod queue;
+mod song;
+
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+use tokio::{net::TcpListener, sync::Mutex};
+
+use ::database::connection::MainDbConnection;
+use ::playback::player::Playable;
+
+use crate::{server::subsonic::accounts::SubsonicAccountManager, utils::GlobalParams};
+
+use idle::ChangeTracker;
+use ids::QueueIds;
+
+pub struct MpdState {
+    pub lib_path: Arc<String>,
+    pub main_db: Arc<MainDbConnection>,
+    pub player: Arc<Mutex<dyn Playable>>,
+    pub accounts: SubsonicAccountManager,
+    changes: ChangeTracker,
+    ids: QueueIds,
+    started_at: Instant,
+}
+
+/// Serves clients of the Music Player Daemon protocol on `addr` until the
+/// main token is cancelled.
+///
+/// Clients sign in with a Subsonic account, sending `username:password` as
+/// the password, and may send the commands the role of the account allows.
+/// To test the server, use:
+///
+/// mpc -h 'alice:secret@127.0.0.1' -p 6600 status
+pub async fn serve(addr: SocketAddr, global_params: Arc<GlobalParams>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen for MPD clients on {}", addr))?;
+
+    let state = Arc::new(MpdState {
+        lib_path: global_params.lib_path.clone(),
+        main_db: global_params.main_db.clone(),
+        player: global_params.player.clone(),
+        accounts: SubsonicAccountManager::new(&*global_params.config_path)
+            .context("Failed to load Subsonic accounts")?,
+        changes: ChangeTracker::new(),
+        ids: QueueIds::new(),
+        started_at: Instant::now(),
+    });
+
+    let tracker_state = state.clone();
+    let cancel_token = global_params.main_token.clone();
+    tokio::spawn(async move {
+        tracker_state
+            .changes
+            .run(tracker_state.player.clone(), cancel_token)
+            .await
+    });
+
+    info!("MPD server listening on {}", addr);
+
+    loop {
+        let accepted = tokio::select! {
+            _ = global_params.main_token.cancelled() => break,
+            accepted = listener.accept() => accepted,
+        };
+
+        let (stream, peer) = match accepted {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Failed to accept MPD client: {}", e);
+                continue;
+            }
+        };
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = connection::handle_connection(state, stream).await {
+                warn!("MPD connection from {} closed: {:#?}", peer, e);
+            }
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,265 @@
+use anyhow::Context;
+
+use ::database::actions::{file::get_duration_by_file_id, stats::increase_skipped};
+use ::playback::player::{PlaybackState, PlayingItem};
+
+use super::{
+    protocol::{push, Args, MpdError, MpdResult},
+    queue::find_id,
+    song::{load_summaries, write_item},
+    MpdState,
+};
+
+// Playback modes, as the player numbers them
+const SEQUENTIAL: u32 = 0;
+const REPEAT_ONE: u32 = 1;
+const REPEAT_ALL: u32 = 2;
+const SHUFFLE: u32 = 3;
+
+pub async fn status(state: &MpdState) -> MpdResult {
+    let status = state.player.lock().await.get_status();
+    let mode: u32 = status.playback_mode.into();
+
+    let mut out = String::new();
+    push(&mut out, "volume", (status.volume * 100.0).round() as i32);
+    push(
+        &mut out,
+        "repeat",
+        (mode == REPEAT_ONE || mode == REPEAT_ALL) as u8,
+    );
+    push(&mut out, "random", (mode == SHUFFLE) as u8);
+    push(&mut out, "single", (mode == REPEAT_ONE) as u8);
+    push(&mut out, "consume", 0);
+    push(&mut out, "playlist", state.changes.playlist_version());
+    push(&mut out, "playlistlength", status.playlist.len());
+    push(
+        &mut out,
+        "state",
+        match status.state {
+            PlaybackState::Playing => "play",
+            PlaybackState::Paused => "pause",
+            PlaybackState::Stopped => "stop",
+        },
+    );
+
+    if let Some(index) = status.index.filter(|x| *x < status.playlist.len()) {
+        let ids = state.ids.sync(&status.playlist);
+        push(&mut out, "song", index);
+        push(&mut out, "songid", ids[index]);
+        if index + 1 < status.playlist.len() {
+            push(&mut out, "nextsong", index + 1);
+            push(&mut out, "nextsongid", ids[index + 1]);
+        }
+
+        let elapsed = status.position.as_secs_f64();
+        let duration = match status.item {
+            Some(PlayingItem::InLibrary(file_id)) => {
+                get_duration_by_file_id(&state.main_db, file_id)
+                    .await
+                    .unwrap_or_default()
+            }
+            _ => 0.0,
+        };
+
+        push(
+            &mut out,
+            "time",
+            format!("{}:{}", elapsed.round(), duration.round()),
+        );
+        push(&mut out, "elapsed", format!("{:.3}", elapsed));
+        if duration > 0.0 {
+            push(&mut out, "duration", format!("{:.3}", duration));
+        }
+    }
+
+    Ok(out)
+}
+
+pub async fn current_song(state: &MpdState) -> MpdResult {
+    let status = state.player.lock().await.get_status();
+
+    let (Some(index), Some(item)) = (status.index, status.item) else {
+        return Ok(String::new());
+    };
+    let Some(id) = state.ids.sync(&status.playlist).get(index).copied() else {
+        return Ok(String::new());
+    };
+
+    let file_ids = match item {
+        PlayingItem::InLibrary(file_id) => vec![file_id],
+        _ => vec![],
+    };
+    let summaries = load_summaries(&state.main_db, &file_ids).await?;
+
+    let mut out = String::new();
+    write_item(&mut out, &item, &summaries, index, id);
+
+    Ok(out)
+}
+
+/// Starts playing, at the song at `POS` (or with id `ID` if `by_id`) if
+/// given.
+pub async fn play(state: &MpdState, args: &Args<'_>, by_id: bool) -> MpdResult {
+    let player = state.player.lock().await;
+
+    if let Some(value) = args.parsed::<i64>(0)? {
+        // Clients send -1 to resume the current song
+        if value >= 0 {
+            let playlist = player.get_playlist();
+            let index = if by_id {
+                let id = u32::try_from(value).map_err(|_| MpdError::no_exist("No such song"))?;
+                find_id(state, &playlist, id)?
+            } else if (value as usize) < playlist.len() {
+                value as usize
+            } else {
+                return Err(MpdError::arg("Bad song index"));
+            };
+            player.switch(index);
+        }
+    }
+
+    player.play();
+    Ok(String::new())
+}
+
+/// Pauses or resumes, toggling if no state is given.
+pub async fn pause(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let player = state.player.lock().await;
+
+    let pause = match args.get(0) {
+        Some(_) => args.flag(0)?,
+        None => player.get_status().state == PlaybackState::Playing,
+    };
+
+    if pause {
+        player.pause();
+    } else {
+        player.play();
+    }
+
+    Ok(String::new())
+}
+
+pub async fn stop(state: &MpdState) -> MpdResult {
+    state.player.lock().await.stop();
+    Ok(String::new())
+}
+
+/// Moves to the next or the previous song. Like skipping in the app, this
+/// counts as a skip of the current song.
+pub async fn skip(state: &MpdState, forward: bool) -> MpdResult {
+    let player = state.player.lock().await;
+
+    if let Some(PlayingItem::InLibrary(file_id)) = player.get_status().item {
+        increase_skipped(&state.main_db, file_id)
+            .await
+            .context("Unable to increase skipped count")?;
+    }
+
+    if forward {
+        player.next();
+    } else {
+        player.previous();
+    }
+
+    Ok(String::new())
+}
+
+/// Seeks within the song at `POS` (or with id `ID` if `by_id`), switching
+/// to it first if another song is playing.
+pub async fn seek(state: &MpdState, args: &Args<'_>, by_id: bool) -> MpdResult {
+    let position: f64 = args.required_parsed(1)?;
+
+    let player = state.player.lock().await;
+    let status = player.get_status();
+    let index = if by_id {
+        find_id(state, &status.playlist, args.required_parsed(0)?)?
+    } else {
+        let index: usize = args.required_parsed(0)?;
+        if index >= status.playlist.len() {
+            return Err(MpdError::arg("Bad song index"));
+        }
+        index
+    };
+
+    if status.index != Some(index) {
+        player.switch(index);
+    }
+    player.seek(position.max(0.0));
+
+    Ok(String::new())
+}
+
+/// Seeks within the current song, relatively if the time starts with a sign.
+pub async fn seek_current(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let time = args.required(0)?;
+    let offset: f64 = args.required_parsed(0)?;
+
+    let player = state.player.lock().await;
+    let status = player.get_status();
+    if status.index.is_none() {
+        return Err(MpdError::no_exist("Not playing"));
+    }
+
+    let position = if time.starts_with('+') || time.starts_with('-') {
+        status.position.as_secs_f64() + offset
+    } else {
+        offset
+    };
+    player.seek(position.max(0.0));
+
+    Ok(String::new())
+}
+
+pub async fn set_volume(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let volume: i32 = args.required_parsed(0)?;
+    if !(0..=100).contains(&volume) {
+        return Err(MpdError::arg("Invalid volume value"));
+    }
+
+    state.player.lock().await.set_volume(volume as f32 / 100.0);
+
+    Ok(String::new())
+}
+
+/// Changes the volume by a number of percents.
+pub async fn change_volume(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let change: i32 = args.required_parsed(0)?;
+
+    let mut player = state.player.lock().await;
+    let volume = (player.get_status().volume * 100.0).round() as i32 + change;
+    player.set_volume(volume.clamp(0, 100) as f32 / 100.0);
+
+    Ok(String::new())
+}
+
+/// Turns one of the `repeat`, `random` or `single` options on or off. The
+/// player has a single playback mode, so they replace each other.
+pub async fn set_option(state: &MpdState, option: &str, args: &Args<'_>) -> MpdResult {
+    let enabled = args.flag(0)?;
+
+    let mut player = state.player.lock().await;
+    let mode: u32 = player.get_status().playback_mode.into();
+
+    let mode = match (option, enabled) {
+        ("repeat", true) if mode == REPEAT_ONE => REPEAT_ONE,
+        ("repeat", true) => REPEAT_ALL,
+        ("random", true) => SHUFFLE,
+        ("single", true) => REPEAT_ONE,
+        ("repeat", false) if mode == REPEAT_ONE || mode == REPEAT_ALL => SEQUENTIAL,
+        ("random", false) if mode == SHUFFLE => SEQUENTIAL,
+        ("single", false) if mode == REPEAT_ONE => REPEAT_ALL,
+        _ => mode,
+    };
+    player.set_playback_mode(mode.into());
+
+    Ok(String::new())
+}
+
+/// Songs are never removed once played, so consume mode can only be off.
+pub fn consume(args: &Args<'_>) -> MpdResult {
+    match args.flag(0)? {
+        false => Ok(String::new()),
+        true => Err(MpdError::arg("Consume mode is not supported")),
+    }
+}
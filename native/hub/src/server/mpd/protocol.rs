@@ -0,0 +1,218 @@
+use std::{fmt, fmt::Write, str::FromStr};
+
+/// The protocol version announced to clients.
+pub const PROTOCOL_VERSION: &str = "0.23.5";
+
+/// Error codes of the MPD protocol, sent back in `ACK` lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckCode {
+    Arg = 2,
+    Password = 3,
+    Permission = 4,
+    Unknown = 5,
+    NoExist = 50,
+    System = 52,
+}
+
+#[derive(Debug)]
+pub struct MpdError {
+    code: AckCode,
+    message: String,
+}
+
+impl MpdError {
+    pub fn new(code: AckCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn arg(message: impl Into<String>) -> Self {
+        Self::new(AckCode::Arg, message)
+    }
+
+    pub fn no_exist(message: impl Into<String>) -> Self {
+        Self::new(AckCode::NoExist, message)
+    }
+
+    pub fn unknown(command: &str) -> Self {
+        Self::new(AckCode::Unknown, format!("unknown command \"{}\"", command))
+    }
+
+    /// Formats the error as the `ACK` line ending a response. `index` is
+    /// the position of the failed command in its command list.
+    pub fn ack(&self, index: usize, command: &str) -> String {
+        format!(
+            "ACK [{}@{}] {{{}}} {}\n",
+            self.code as u8, index, command, self.message
+        )
+    }
+}
+
+impl From<anyhow::Error> for MpdError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(AckCode::System, format!("{:#}", e))
+    }
+}
+
+/// The body of a successful response, without the final `OK`.
+pub type MpdResult = Result<String, MpdError>;
+
+/// Appends a `key: value` line to a response.
+pub fn push(out: &mut String, key: &str, value: impl fmt::Display) {
+    let _ = writeln!(out, "{}: {}", key, value);
+}
+
+/// Splits a command line into words. Words containing spaces are quoted,
+/// with quotes and backslashes inside escaped by a backslash.
+pub fn tokenize(line: &str) -> Result<Vec<String>, MpdError> {
+    let mut words = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut word = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(escaped) => word.push(escaped),
+                        None => return Err(MpdError::arg("Unterminated quoted string")),
+                    },
+                    Some(c) => word.push(c),
+                    None => return Err(MpdError::arg("Unterminated quoted string")),
+                }
+            }
+        } else {
+            word.push(c);
+            while let Some(c) = chars.next_if(|x| !x.is_whitespace()) {
+                word.push(c);
+            }
+        }
+
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// The arguments of a command, after its name.
+pub struct Args<'a>(pub &'a [String]);
+
+impl Args<'_> {
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.0.get(index).map(String::as_str)
+    }
+
+    pub fn required(&self, index: usize) -> Result<&str, MpdError> {
+        self.get(index)
+            .ok_or_else(|| MpdError::arg("too few arguments"))
+    }
+
+    pub fn parsed<T: FromStr>(&self, index: usize) -> Result<Option<T>, MpdError> {
+        self.get(index).map(parse).transpose()
+    }
+
+    pub fn required_parsed<T: FromStr>(&self, index: usize) -> Result<T, MpdError> {
+        parse(self.required(index)?)
+    }
+
+    /// Reads a `0` or `1` switch.
+    pub fn flag(&self, index: usize) -> Result<bool, MpdError> {
+        match self.required(index)? {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            x => Err(MpdError::arg(format!("Boolean (0/1) expected: {}", x))),
+        }
+    }
+
+    /// Reads a position or a `START:END` range of positions, an open range
+    /// ends at `len`.
+    pub fn range(&self, index: usize, len: usize) -> Result<Option<(usize, usize)>, MpdError> {
+        let Some(value) = self.get(index) else {
+            return Ok(None);
+        };
+
+        let (start, end) = match value.split_once(':') {
+            Some((start, "")) => (parse(start)?, len),
+            Some((start, end)) => (parse(start)?, parse::<usize>(end)?.min(len)),
+            None => {
+                let position: usize = parse(value)?;
+                (position, position + 1)
+            }
+        };
+
+        if start > end {
+            return Err(MpdError::arg(format!("Bad range: {}", value)));
+        }
+
+        Ok(Some((start, end)))
+    }
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, MpdError> {
+    value
+        .parse()
+        .map_err(|_| MpdError::arg(format!("Invalid argument: {}", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        tokenize(line).unwrap()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(words("status"), vec!["status"]);
+        assert_eq!(words("  play   3 "), vec!["play", "3"]);
+        assert_eq!(words(""), Vec::<String>::new());
+        assert_eq!(
+            words(r#"find "album" "The Dark Side""#),
+            vec!["find", "album", "The Dark Side"]
+        );
+        assert_eq!(
+            words(r#"find "(artist == \"Guns N' Roses\")""#),
+            vec!["find", r#"(artist == "Guns N' Roses")"#]
+        );
+        assert_eq!(words(r#"add "a\\b" """#), vec!["add", r"a\b", ""]);
+        assert_eq!(words("add Artist/\"x"), vec!["add", "Artist/\"x"]);
+
+        for line in [r#"find "album"#, r#"find "album\"#] {
+            assert_eq!(tokenize(line).unwrap_err().code, AckCode::Arg, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        let words: Vec<String> = ["3", "2:5", "4:", "1:100", "5:2", "x"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let args = Args(&words);
+
+        assert_eq!(args.range(0, 10).unwrap(), Some((3, 4)));
+        assert_eq!(args.range(1, 10).unwrap(), Some((2, 5)));
+        assert_eq!(args.range(2, 10).unwrap(), Some((4, 10)));
+        assert_eq!(args.range(3, 10).unwrap(), Some((1, 10)));
+        assert!(args.range(4, 10).is_err());
+        assert!(args.range(5, 10).is_err());
+        assert_eq!(args.range(6, 10).unwrap(), None);
+    }
+
+    #[test]
+    fn test_ack() {
+        let error = MpdError::no_exist("No such song");
+        assert_eq!(
+            error.ack(2, "deleteid"),
+            "ACK [50@2] {deleteid} No such song\n"
+        );
+    }
+}
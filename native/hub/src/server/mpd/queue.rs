@@ -0,0 +1,259 @@
+use ::database::{
+    actions::{
+        catalog::{find_track_ids, TrackField, TrackFilter},
+        file::get_ordered_files_by_ids,
+    },
+    playing_item::{library_item::extract_in_library_ids, MediaFileHandle},
+};
+use ::playback::{player::PlayingItem, strategies::AddMode};
+
+use crate::utils::files_to_playback_request;
+
+use super::{
+    protocol::{push, Args, MpdError, MpdResult},
+    song::{load_summaries, write_item},
+    MpdState,
+};
+
+/// Tracks added to the queue at once.
+const CHUNK_SIZE: usize = 1000;
+
+/// Lists the queue, or the items in a range of it.
+pub async fn playlist_info(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let playlist = state.player.lock().await.get_status().playlist;
+    let (start, end) = match args.range(0, playlist.len())? {
+        Some((start, end)) if start >= playlist.len() && end > start => {
+            return Err(MpdError::arg("Bad song index"))
+        }
+        Some(range) => range,
+        None => (0, playlist.len()),
+    };
+
+    write_items(state, &playlist, start, end).await
+}
+
+/// Lists the queue, or the song with an id.
+pub async fn playlist_id(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let playlist = state.player.lock().await.get_status().playlist;
+    match args.parsed::<u32>(0)? {
+        Some(id) => {
+            let position = find_id(state, &playlist, id)?;
+            write_items(state, &playlist, position, position + 1).await
+        }
+        None => write_items(state, &playlist, 0, playlist.len()).await,
+    }
+}
+
+/// Finds the position of the song with an id.
+pub fn find_id(state: &MpdState, playlist: &[PlayingItem], id: u32) -> Result<usize, MpdError> {
+    state
+        .ids
+        .position(playlist, id)
+        .ok_or_else(|| MpdError::no_exist("No such song"))
+}
+
+/// Lists the queue if it changed since `version`. The changes themselves
+/// are not tracked, so the whole queue is sent.
+pub async fn playlist_changes(
+    state: &MpdState,
+    args: &Args<'_>,
+    positions_only: bool,
+) -> MpdResult {
+    let version: u32 = args.required_parsed(0)?;
+    if version >= state.changes.playlist_version() {
+        return Ok(String::new());
+    }
+
+    let playlist = state.player.lock().await.get_status().playlist;
+    let (start, end) = args
+        .range(1, playlist.len())?
+        .unwrap_or((0, playlist.len()));
+
+    if positions_only {
+        let ids = state.ids.sync(&playlist);
+        let mut out = String::new();
+        for position in start..end.min(playlist.len()) {
+            push(&mut out, "cpos", position);
+            push(&mut out, "Id", ids[position]);
+        }
+        return Ok(out);
+    }
+
+    write_items(state, &playlist, start, end).await
+}
+
+async fn write_items(
+    state: &MpdState,
+    playlist: &[PlayingItem],
+    start: usize,
+    end: usize,
+) -> MpdResult {
+    let ids = state.ids.sync(playlist);
+    let items = playlist
+        .get(start..end.min(playlist.len()))
+        .unwrap_or_default();
+    let summaries = load_summaries(&state.main_db, &extract_in_library_ids(items.to_vec())).await?;
+
+    let mut out = String::new();
+    for (offset, item) in items.iter().enumerate() {
+        let position = start + offset;
+        write_item(&mut out, item, &summaries, position, ids[position]);
+    }
+
+    Ok(out)
+}
+
+/// Adds a file or a directory of the library to the queue, at `POS` if
+/// given. With `id_only`, only a file can be added and its id is returned.
+pub async fn add(state: &MpdState, args: &Args<'_>, id_only: bool) -> MpdResult {
+    let uri = args.required(0)?.trim_matches('/');
+    let position: Option<usize> = args.parsed(1)?;
+
+    let mut file_ids = find_track_ids(
+        &state.main_db,
+        &[TrackFilter {
+            field: TrackField::Path,
+            value: uri.to_owned(),
+            partial: false,
+            negated: false,
+        }],
+    )
+    .await?;
+
+    if file_ids.is_empty() && !id_only {
+        file_ids = find_track_ids(
+            &state.main_db,
+            &[TrackFilter {
+                field: TrackField::Base,
+                value: uri.to_owned(),
+                partial: false,
+                negated: false,
+            }],
+        )
+        .await?;
+    }
+
+    if file_ids.is_empty() {
+        return Err(MpdError::no_exist("No such song"));
+    }
+
+    // The player adds the track later, so its id is given out beforehand
+    let id = id_only.then(|| state.ids.reserve(PlayingItem::InLibrary(file_ids[0])));
+    append_tracks(state, &file_ids, position).await?;
+
+    let mut out = String::new();
+    if let Some(id) = id {
+        push(&mut out, "Id", id);
+    }
+
+    Ok(out)
+}
+
+/// Appends library tracks to the queue, then moves them to `position` if
+/// given.
+pub async fn append_tracks(
+    state: &MpdState,
+    file_ids: &[i32],
+    position: Option<usize>,
+) -> Result<(), MpdError> {
+    let player = state.player.lock().await;
+    let playlist_len = player.get_playlist().len();
+    if position.is_some_and(|x| x > playlist_len) {
+        return Err(MpdError::arg("Bad song index"));
+    }
+
+    let mut added = 0;
+    for chunk in file_ids.chunks(CHUNK_SIZE) {
+        let handles: Vec<MediaFileHandle> = get_ordered_files_by_ids(&state.main_db, chunk)
+            .await?
+            .into_iter()
+            .map(|x| x.into())
+            .collect();
+
+        let tracks = files_to_playback_request(&state.lib_path, &handles);
+        added += tracks.len();
+        player.add_to_playlist(tracks, AddMode::AppendToEnd);
+    }
+
+    if let Some(position) = position {
+        for offset in 0..added {
+            player.move_playlist_item(playlist_len + offset, position + offset);
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes a song or a range of songs from the queue.
+pub async fn delete(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let player = state.player.lock().await;
+    let playlist_len = player.get_playlist().len();
+    let (start, end) = args
+        .range(0, playlist_len)?
+        .ok_or_else(|| MpdError::arg("too few arguments"))?;
+    if end > playlist_len || start >= end {
+        return Err(MpdError::arg("Bad song index"));
+    }
+
+    // From the end, so the positions left to remove do not shift
+    for index in (start..end).rev() {
+        player.remove_from_playlist(index);
+    }
+
+    Ok(String::new())
+}
+
+pub async fn delete_id(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let id: u32 = args.required_parsed(0)?;
+
+    let player = state.player.lock().await;
+    let position = find_id(state, &player.get_playlist(), id)?;
+    player.remove_from_playlist(position);
+
+    Ok(String::new())
+}
+
+/// Moves a song or a range of songs so it starts at `TO`.
+pub async fn move_items(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let player = state.player.lock().await;
+    let playlist_len = player.get_playlist().len();
+    let (start, end) = args
+        .range(0, playlist_len)?
+        .ok_or_else(|| MpdError::arg("too few arguments"))?;
+    let to: usize = args.required_parsed(1)?;
+
+    let count = end.saturating_sub(start);
+    if end > playlist_len || count == 0 || to + count > playlist_len {
+        return Err(MpdError::arg("Bad song index"));
+    }
+
+    for offset in 0..count {
+        if to <= start {
+            player.move_playlist_item(start + offset, to + offset);
+        } else {
+            player.move_playlist_item(start, to + count - 1);
+        }
+    }
+
+    Ok(String::new())
+}
+
+pub async fn move_id(state: &MpdState, args: &Args<'_>) -> MpdResult {
+    let id: u32 = args.required_parsed(0)?;
+    let to: usize = args.required_parsed(1)?;
+
+    let player = state.player.lock().await;
+    let playlist = player.get_playlist();
+    let position = find_id(state, &playlist, id)?;
+    if to >= playlist.len() {
+        return Err(MpdError::arg("Bad song index"));
+    }
+    player.move_playlist_item(position, to);
+
+    Ok(String::new())
+}
+
+pub async fn clear(state: &MpdState) -> MpdResult {
+    state.player.lock().await.clear_playlist();
+    Ok(String::new())
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use ::database::{
+    actions::metadata::{get_metadata_summary_by_file_ids, MetadataSummary},
+    connection::MainDbConnection,
+};
+use ::playback::player::PlayingItem;
+
+use super::protocol::push;
+
+/// Tracks looked up at once, to keep queries within the limits of SQLite.
+const CHUNK_SIZE: usize = 1000;
+
+/// Loads the metadata of library tracks.
+pub async fn load_summaries(
+    main_db: &MainDbConnection,
+    file_ids: &[i32],
+) -> Result<HashMap<i32, MetadataSummary>> {
+    let mut summaries = HashMap::with_capacity(file_ids.len());
+    for chunk in file_ids.chunks(CHUNK_SIZE) {
+        for summary in get_metadata_summary_by_file_ids(main_db, chunk.to_vec()).await? {
+            summaries.insert(summary.id, summary);
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// The URI of a library track, its path relative to the library.
+pub fn song_uri(summary: &MetadataSummary) -> String {
+    if summary.directory.is_empty() {
+        summary.file_name.clone()
+    } else {
+        format!("{}/{}", summary.directory, summary.file_name)
+    }
+}
+
+pub fn write_song(out: &mut String, summary: &MetadataSummary) {
+    push(out, "file", song_uri(summary));
+    push(out, "Title", &summary.title);
+    if !summary.artist.is_empty() {
+        push(out, "Artist", &summary.artist);
+    }
+    if !summary.album.is_empty() {
+        push(out, "Album", &summary.album);
+    }
+    if !summary.genre.is_empty() {
+        push(out, "Genre", &summary.genre);
+    }
+    if summary.track_number % 1000 > 0 {
+        push(out, "Track", summary.track_number % 1000);
+    }
+    if summary.track_number >= 1000 {
+        push(out, "Disc", summary.track_number / 1000);
+    }
+    push(out, "Time", summary.duration.round() as i64);
+    push(out, "duration", format!("{:.3}", summary.duration));
+}
+
+/// Describes an item of the queue. Files outside of the library are only
+/// known by their path.
+pub fn write_item(
+    out: &mut String,
+    item: &PlayingItem,
+    summaries: &HashMap<i32, MetadataSummary>,
+    position: usize,
+    id: u32,
+) {
+    match item {
+        PlayingItem::InLibrary(file_id) => match summaries.get(file_id) {
+            Some(summary) => write_song(out, summary),
+            None => push(out, "file", format!("rune://track/{}", file_id)),
+        },
+        PlayingItem::IndependentFile(path) => {
+            push(out, "file", path.to_string_lossy());
+            if let Some(name) = path.file_stem() {
+                push(out, "Title", name.to_string_lossy());
+            }
+        }
+        PlayingItem::Unknown => push(out, "file", "rune://unknown"),
+    }
+
+    push(out, "Pos", position);
+    push(out, "Id", id);
+}
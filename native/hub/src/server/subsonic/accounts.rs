@@ -0,0 +1,122 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use ::discovery::{persistent::PersistentDataManager, server::UserRole};
+
+/// An account Subsonic clients sign in with.
+///
+/// Subsonic token authentication hashes the password with a salt chosen by
+/// the client, so the password is kept as it is rather than hashed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubsonicAccount {
+    pub username: String,
+    pub password: String,
+    pub role: UserRole,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SubsonicAccountList {
+    pub accounts: HashMap<String, SubsonicAccount>, // Username -> Account
+}
+
+/// Manages the accounts of the Subsonic API, which are separate from the
+/// devices known to the server.
+#[derive(Debug)]
+pub struct SubsonicAccountManager {
+    storage: PersistentDataManager<SubsonicAccountList>,
+}
+
+impl SubsonicAccountManager {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let storage_path = path.as_ref().join(".subsonic-accounts");
+        let storage = PersistentDataManager::new(storage_path)?;
+
+        Ok(Self { storage })
+    }
+
+    /// Lists the accounts by username.
+    pub async fn list_accounts(&self) -> Vec<SubsonicAccount> {
+        let mut accounts: Vec<SubsonicAccount> = self
+            .storage
+            .read()
+            .await
+            .accounts
+            .values()
+            .cloned()
+            .collect();
+        accounts.sort_by(|a, b| a.username.cmp(&b.username));
+
+        accounts
+    }
+
+    pub async fn get_account(&self, username: &str) -> Option<SubsonicAccount> {
+        self.storage.read().await.accounts.get(username).cloned()
+    }
+
+    pub async fn add_account(&self, username: &str, password: &str, role: UserRole) -> Result<()> {
+        if username.is_empty() || password.is_empty() {
+            bail!("Username and password must not be empty");
+        }
+
+        self.storage
+            .update(|mut list| async move {
+                if list.accounts.contains_key(username) {
+                    bail!("Account already exists: {}", username);
+                }
+
+                list.accounts.insert(
+                    username.to_owned(),
+                    SubsonicAccount {
+                        username: username.to_owned(),
+                        password: password.to_owned(),
+                        role,
+                    },
+                );
+                Ok((list, ()))
+            })
+            .await
+    }
+
+    pub async fn change_password(&self, username: &str, password: &str) -> Result<()> {
+        if password.is_empty() {
+            bail!("Password must not be empty");
+        }
+
+        self.update_account(username, |account| account.password = password.to_owned())
+            .await
+    }
+
+    pub async fn change_role(&self, username: &str, role: UserRole) -> Result<()> {
+        self.update_account(username, |account| account.role = role)
+            .await
+    }
+
+    pub async fn remove_account(&self, username: &str) -> Result<()> {
+        self.storage
+            .update(|mut list| async move {
+                list.accounts
+                    .remove(username)
+                    .ok_or_else(|| anyhow!("Account not found: {}", username))?;
+                Ok((list, ()))
+            })
+            .await
+    }
+
+    async fn update_account<F>(&self, username: &str, updater: F) -> Result<()>
+    where
+        F: FnOnce(&mut SubsonicAccount),
+    {
+        self.storage
+            .update(|mut list| async move {
+                let account = list
+                    .accounts
+                    .get_mut(username)
+                    .ok_or_else(|| anyhow!("Account not found: {}", username))?;
+                updater(account);
+                Ok((list, ()))
+            })
+            .await
+    }
+}
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use ::database::actions::{
+    catalog::get_liked_track_ids,
+    file::get_duration_by_file_id,
+    history::{insert_play_event, PlayEvent},
+    stats::{increase_played_through, set_liked},
+};
+
+use super::{
+    browsing::songs_json,
+    response::{SubsonicError, SubsonicReply, SubsonicResult},
+    SubsonicParams, SubsonicState,
+};
+
+/// Source of the plays reported by Subsonic clients in the play history.
+const PLAY_SOURCE: &str = "subsonic";
+
+/// Stars or unstars songs. Rune likes tracks only, so starring albums or
+/// artists is refused rather than silently ignored.
+pub async fn star(state: &SubsonicState, params: &SubsonicParams, liked: bool) -> SubsonicResult {
+    if params.get("albumId").is_some() || params.get("artistId").is_some() {
+        return Err(SubsonicError::generic("Only songs can be starred"));
+    }
+
+    let ids = params.ids("id")?;
+    if ids.is_empty() {
+        return Err(SubsonicError::missing_parameter("id"));
+    }
+
+    for id in ids {
+        set_liked(&state.main_db, id, liked)
+            .await?
+            .ok_or_else(|| SubsonicError::not_found(&format!("Song {}", id)))?;
+    }
+
+    Ok(SubsonicReply::empty())
+}
+
+pub async fn get_starred2(state: &SubsonicState) -> SubsonicResult {
+    let liked_ids = get_liked_track_ids(&state.main_db).await?;
+    let songs = songs_json(state, liked_ids).await?;
+
+    Ok(SubsonicReply::element(
+        "starred2",
+        json!({ "artist": [], "album": [], "song": songs }),
+    ))
+}
+
+/// Records finished plays in the play history. Clients also call this when
+/// a track starts playing, without `submission`, which is acknowledged only.
+pub async fn scrobble(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    let ids = params.ids("id")?;
+    if ids.is_empty() {
+        return Err(SubsonicError::missing_parameter("id"));
+    }
+
+    if !params.parsed("submission", true)? {
+        return Ok(SubsonicReply::empty());
+    }
+
+    let times = params.all("time");
+    for (index, id) in ids.into_iter().enumerate() {
+        let started_at = match times.get(index) {
+            Some(time) => time
+                .parse::<i64>()
+                .ok()
+                .and_then(DateTime::<Utc>::from_timestamp_millis)
+                .ok_or_else(|| SubsonicError::generic(format!("Invalid time: {}", time)))?,
+            None => Utc::now(),
+        };
+
+        let duration = get_duration_by_file_id(&state.main_db, id)
+            .await
+            .map_err(|_| SubsonicError::not_found(&format!("Song {}", id)))?;
+
+        insert_play_event(
+            &state.main_db,
+            PlayEvent {
+                media_file_id: id,
+                started_at,
+                listened_duration: duration,
+                completion_ratio: 1.0,
+                source: Some(PLAY_SOURCE.to_owned()),
+            },
+        )
+        .await?;
+        increase_played_through(&state.main_db, id).await?;
+    }
+
+    Ok(SubsonicReply::empty())
+}
@@ -0,0 +1,50 @@
+use super::{
+    accounts::{SubsonicAccount, SubsonicAccountManager},
+    response::SubsonicError,
+    SubsonicParams,
+};
+
+/// Signs in the account of a request. Clients either send the password,
+/// as it is or hex encoded after `enc:`, or a token made of the MD5 hash
+/// of the password followed by a random salt.
+pub async fn authenticate(
+    params: &SubsonicParams,
+    accounts: &SubsonicAccountManager,
+) -> Result<SubsonicAccount, SubsonicError> {
+    let username = params.required("u")?;
+
+    let verified = match (params.get("p"), params.get("t"), params.get("s")) {
+        (Some(password), _, _) => {
+            let password =
+                decode_password(password).ok_or_else(SubsonicError::wrong_credentials)?;
+            accounts
+                .get_account(username)
+                .await
+                .filter(|account| account.password == password)
+        }
+        (None, Some(token), Some(salt)) => accounts.get_account(username).await.filter(|account| {
+            let expected = md5::compute(format!("{}{}", account.password, salt));
+            format!("{:x}", expected).eq_ignore_ascii_case(token)
+        }),
+        _ => return Err(SubsonicError::missing_parameter("p")),
+    };
+
+    verified.ok_or_else(SubsonicError::wrong_credentials)
+}
+
+fn decode_password(password: &str) -> Option<String> {
+    let Some(hex) = password.strip_prefix("enc:") else {
+        return Some(password.to_owned());
+    };
+
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    String::from_utf8(bytes).ok()
+}
@@ -0,0 +1,260 @@
+use std::{collections::BTreeMap, path::Path};
+
+use rand::seq::SliceRandom;
+use serde_json::{json, Value};
+
+use ::database::actions::{
+    catalog::{
+        get_album_ids_by_artist, get_album_summaries, get_artist_summaries, get_track_ids_by_album,
+        get_track_links, AlbumSummary, ArtistSummary,
+    },
+    metadata::get_metadata_summary_by_file_ids,
+    utils::generate_group_name,
+};
+
+use super::{
+    response::{SubsonicError, SubsonicReply, SubsonicResult},
+    SubsonicParams, SubsonicState,
+};
+
+/// The library is the only music folder.
+const MUSIC_FOLDER_ID: i32 = 1;
+
+pub fn get_license() -> SubsonicResult {
+    Ok(SubsonicReply::element("license", json!({ "valid": true })))
+}
+
+pub fn get_music_folders() -> SubsonicResult {
+    Ok(SubsonicReply::element(
+        "musicFolders",
+        json!({ "musicFolder": [{ "id": MUSIC_FOLDER_ID, "name": "Library" }] }),
+    ))
+}
+
+pub fn get_open_subsonic_extensions() -> SubsonicResult {
+    Ok(SubsonicReply::element(
+        "openSubsonicExtensions",
+        json!([{ "name": "formPost", "versions": [1] }]),
+    ))
+}
+
+pub async fn get_artists(state: &SubsonicState) -> SubsonicResult {
+    let artists = get_artist_summaries(&state.main_db).await?;
+
+    let mut index: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    for artist in &artists {
+        index
+            .entry(generate_group_name(&artist.name))
+            .or_default()
+            .push(artist_json(artist));
+    }
+
+    let index: Vec<Value> = index
+        .into_iter()
+        .map(|(name, artists)| json!({ "name": name, "artist": artists }))
+        .collect();
+
+    Ok(SubsonicReply::element(
+        "artists",
+        json!({ "ignoredArticles": "", "index": index }),
+    ))
+}
+
+pub async fn get_artist(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    let artist_id = params.id("id")?;
+
+    let artist = get_artist_summaries(&state.main_db)
+        .await?
+        .into_iter()
+        .find(|x| x.id == artist_id)
+        .ok_or_else(|| SubsonicError::not_found("Artist"))?;
+
+    let album_ids = get_album_ids_by_artist(&state.main_db, artist_id).await?;
+    let albums = get_album_summaries(&state.main_db, Some(album_ids)).await?;
+
+    let mut value = artist_json(&artist);
+    value["album"] = albums.iter().map(album_json).collect();
+
+    Ok(SubsonicReply::element("artist", value))
+}
+
+pub async fn get_album(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    let album_id = params.id("id")?;
+
+    let album = get_album_summaries(&state.main_db, Some(vec![album_id]))
+        .await?
+        .pop()
+        .ok_or_else(|| SubsonicError::not_found("Album"))?;
+
+    let track_ids = get_track_ids_by_album(&state.main_db, album_id).await?;
+    let mut songs = songs_json(state, track_ids).await?;
+    songs.sort_by_key(|x| {
+        (
+            x["discNumber"].as_i64().unwrap_or_default(),
+            x["track"].as_i64().unwrap_or_default(),
+        )
+    });
+
+    let mut value = album_json(&album);
+    value["song"] = Value::Array(songs);
+
+    Ok(SubsonicReply::element("album", value))
+}
+
+pub async fn get_song(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    let song_id = params.id("id")?;
+
+    let song = songs_json(state, vec![song_id])
+        .await?
+        .pop()
+        .ok_or_else(|| SubsonicError::not_found("Song"))?;
+
+    Ok(SubsonicReply::element("song", song))
+}
+
+pub async fn get_album_list2(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    let list_type = params.required("type")?;
+    let size: usize = params.parsed("size", 10)?;
+    let offset: usize = params.parsed("offset", 0)?;
+
+    let mut albums = get_album_summaries(&state.main_db, None).await?;
+
+    match list_type {
+        "random" => albums.shuffle(&mut rand::thread_rng()),
+        "newest" => albums.sort_by_key(|x| -x.id),
+        "alphabeticalByName" => {}
+        "alphabeticalByArtist" => albums.sort_by(|a, b| a.artist.cmp(&b.artist)),
+        "frequent" => {
+            albums.retain(|x| x.play_count > 0);
+            albums.sort_by_key(|x| -x.play_count);
+        }
+        "recent" => {
+            albums.retain(|x| x.last_played.is_some());
+            albums.sort_by(|a, b| b.last_played.cmp(&a.last_played));
+        }
+        "starred" => albums.retain(|x| x.liked),
+        _ => {
+            return Err(SubsonicError::generic(format!(
+                "Unsupported album list type: {}",
+                list_type
+            )))
+        }
+    }
+
+    let albums: Vec<Value> = albums
+        .iter()
+        .skip(offset)
+        .take(size.min(500))
+        .map(album_json)
+        .collect();
+
+    Ok(SubsonicReply::element(
+        "albumList2",
+        json!({ "album": albums }),
+    ))
+}
+
+pub fn artist_json(artist: &ArtistSummary) -> Value {
+    json!({
+        "id": artist.id.to_string(),
+        "name": artist.name,
+        "albumCount": artist.album_count,
+    })
+}
+
+pub fn album_json(album: &AlbumSummary) -> Value {
+    let mut value = json!({
+        "id": album.id.to_string(),
+        "name": album.name,
+        "artist": album.artist,
+        "songCount": album.song_count,
+        "duration": album.duration.round() as i64,
+        "playCount": album.play_count,
+    });
+
+    if let Some(artist_id) = album.artist_id {
+        value["artistId"] = json!(artist_id.to_string());
+    }
+    if let Some(cover_art_id) = album.cover_art_id {
+        value["coverArt"] = json!(cover_art_id.to_string());
+    }
+    if let Some(last_played) = &album.last_played {
+        value["played"] = json!(last_played);
+    }
+
+    value
+}
+
+/// Describes tracks as Subsonic songs, in the given order. Unknown tracks
+/// are left out.
+pub async fn songs_json(state: &SubsonicState, file_ids: Vec<i32>) -> anyhow::Result<Vec<Value>> {
+    let links = get_track_links(&state.main_db, &file_ids).await?;
+    let summaries = get_metadata_summary_by_file_ids(&state.main_db, file_ids).await?;
+
+    Ok(summaries
+        .into_iter()
+        .map(|summary| {
+            let links = links.get(&summary.id).cloned().unwrap_or_default();
+            let path = Path::new(&summary.directory).join(&summary.file_name);
+            let suffix = path
+                .extension()
+                .map(|x| x.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+
+            let mut value = json!({
+                "id": summary.id.to_string(),
+                "parent": links.album_id.map(|x| x.to_string()).unwrap_or_default(),
+                "isDir": false,
+                "title": summary.title,
+                "album": summary.album,
+                "artist": summary.artist,
+                "track": summary.track_number % 1000,
+                "duration": summary.duration.round() as i64,
+                "suffix": suffix,
+                "contentType": content_type(&suffix),
+                "path": path.to_string_lossy(),
+                "type": "music",
+                "playCount": links.play_count,
+            });
+
+            if summary.track_number >= 1000 {
+                value["discNumber"] = json!(summary.track_number / 1000);
+            }
+            if !summary.genre.is_empty() {
+                value["genre"] = json!(summary.genre);
+            }
+            if let Some(cover_art_id) = summary.cover_art_id {
+                value["coverArt"] = json!(cover_art_id.to_string());
+            }
+            if let Some(album_id) = links.album_id {
+                value["albumId"] = json!(album_id.to_string());
+            }
+            if let Some(artist_id) = links.artist_ids.first() {
+                value["artistId"] = json!(artist_id.to_string());
+            }
+            if let Ok(metadata) = std::fs::metadata(state.lib_path.join(&path)) {
+                value["size"] = json!(metadata.len());
+            }
+            if let Some(liked_at) = links.liked_at {
+                value["starred"] = json!(liked_at);
+            }
+
+            value
+        })
+        .collect())
+}
+
+pub fn content_type(suffix: &str) -> &'static str {
+    match suffix {
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "m4a" | "m4b" | "mp4" | "aac" | "alac" => "audio/mp4",
+        "wav" => "audio/wav",
+        "aiff" | "aif" => "audio/aiff",
+        "wv" => "audio/x-wavpack",
+        "ape" => "audio/x-ape",
+        "wma" => "audio/x-ms-wma",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,176 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request},
+    response::{IntoResponse, Response},
+};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use ::database::actions::{
+    cover_art::get_cover_art_by_id,
+    file::{get_duration_by_file_id, get_file_by_id, get_track_range},
+};
+use ::database::entities::media_files;
+use ::transcoding::{TranscodeFormat, TranscodeOptions};
+
+use crate::server::http::stream::transcode_response;
+
+use super::{
+    browsing::content_type,
+    response::{SubsonicError, SubsonicReply, SubsonicResult},
+    SubsonicParams, SubsonicState,
+};
+
+/// Serves a track, transcoded if the client asks for another format or a
+/// lower bitrate and `allow_transcoding` is set. Range requests are passed
+/// on, so clients can seek without downloading the whole file.
+///
+/// Tracks of a CUE sheet share their file with the other tracks of the
+/// sheet, so they are always transcoded to cut their part out of it.
+pub async fn stream(
+    state: &SubsonicState,
+    params: &SubsonicParams,
+    headers: &HeaderMap,
+    allow_transcoding: bool,
+) -> SubsonicResult {
+    let file = get_file_by_id(&state.main_db, params.id("id")?)
+        .await
+        .map_err(anyhow::Error::from)?
+        .ok_or_else(|| SubsonicError::not_found("Song"))?;
+
+    let path = state.lib_path.join(&file.directory).join(&file.file_name);
+    if !path.is_file() {
+        return Err(SubsonicError::not_found("Song file"));
+    }
+
+    let range = get_track_range(&file);
+    let mut options = match allow_transcoding {
+        true => transcode_options(state, params, &file).await?,
+        false => None,
+    };
+    if options.is_none() && range.is_some() {
+        options = Some(TranscodeOptions::new(TranscodeFormat::Mp3));
+    }
+
+    if let Some(options) = options {
+        let start = params.parsed("timeOffset", 0.0)?;
+        return Ok(SubsonicReply::Media(
+            transcode_response(
+                path,
+                range,
+                options,
+                state.transcode_cache.clone(),
+                start,
+                headers,
+            )
+            .await,
+        ));
+    }
+
+    serve_raw(&path, &file, headers).await
+}
+
+/// Serves a file as it is stored.
+async fn serve_raw(
+    path: &std::path::Path,
+    file: &media_files::Model,
+    headers: &HeaderMap,
+) -> SubsonicResult {
+    let mut request = Request::builder();
+    for name in [header::RANGE, header::IF_RANGE, header::IF_MODIFIED_SINCE] {
+        if let Some(value) = headers.get(&name) {
+            request = request.header(name, value);
+        }
+    }
+    let request = request
+        .body(Body::empty())
+        .map_err(|e| SubsonicError::generic(e.to_string()))?;
+
+    let service = ServeFile::new_with_mime(
+        path,
+        &content_type(&file.extension.to_lowercase())
+            .parse()
+            .map_err(|_| SubsonicError::generic("Invalid content type"))?,
+    );
+
+    match service.oneshot(request).await {
+        Ok(response) => {
+            let (parts, body) = response.into_parts();
+            Ok(SubsonicReply::Media(Response::from_parts(
+                parts,
+                Body::new(body),
+            )))
+        }
+        Err(e) => Err(SubsonicError::generic(e.to_string())),
+    }
+}
+
+/// Decides whether a track is transcoded. Clients ask for a `format`, or
+/// only for a `maxBitRate`, in which case tracks above it are transcoded to
+/// MP3.
+async fn transcode_options(
+    state: &SubsonicState,
+    params: &SubsonicParams,
+    file: &media_files::Model,
+) -> Result<Option<TranscodeOptions>, SubsonicError> {
+    let max_bitrate = match params.parsed("maxBitRate", 0u32)? {
+        0 => None,
+        x => Some(x),
+    };
+
+    let format = match params.get("format") {
+        Some("raw") => return Ok(None),
+        Some(format) => format
+            .parse()
+            .map_err(|_| SubsonicError::generic(format!("Unsupported format: {}", format)))?,
+        None => {
+            let Some(max_bitrate) = max_bitrate else {
+                return Ok(None);
+            };
+
+            let duration = get_duration_by_file_id(&state.main_db, file.id)
+                .await
+                .map_err(anyhow::Error::from)?;
+            let size =
+                tokio::fs::metadata(state.lib_path.join(&file.directory).join(&file.file_name))
+                    .await
+                    .map_err(anyhow::Error::from)?
+                    .len();
+
+            // Average bitrate of the file in kbps
+            if duration <= 0.0 || size as f64 * 8.0 / duration / 1000.0 <= max_bitrate as f64 {
+                return Ok(None);
+            }
+
+            TranscodeFormat::Mp3
+        }
+    };
+
+    Ok(Some(TranscodeOptions {
+        bitrate: max_bitrate,
+        ..TranscodeOptions::new(format)
+    }))
+}
+
+pub async fn get_cover_art(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    let cover_art = get_cover_art_by_id(&state.main_db, params.id("id")?)
+        .await?
+        .ok_or_else(|| SubsonicError::not_found("Cover art"))?;
+
+    let mime = if cover_art.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    };
+
+    Ok(SubsonicReply::Media(
+        (
+            [
+                (header::CONTENT_TYPE, mime),
+                (header::CACHE_CONTROL, "public, max-age=86400"),
+            ],
+            cover_art,
+        )
+            .into_response(),
+    ))
+}
@@ -0,0 +1,348 @@
+pub mod accounts;
+mod annotation;
+mod auth;
+mod browsing;
+mod media;
+mod playlists;
+pub mod response;
+mod search;
+
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, RawQuery, State},
+    http::HeaderMap,
+    response::Response,
+    routing::get,
+    Router,
+};
+
+use ::database::connection::MainDbConnection;
+use ::discovery::server::Capability;
+use ::transcoding::SegmentCache;
+
+use accounts::SubsonicAccountManager;
+use auth::authenticate;
+use response::{render, ResponseFormat, SubsonicError, SubsonicReply, SubsonicResult};
+
+pub use browsing::content_type;
+
+pub struct SubsonicState {
+    pub lib_path: PathBuf,
+    pub main_db: Arc<MainDbConnection>,
+    pub transcode_cache: Arc<SegmentCache>,
+    pub accounts: Arc<SubsonicAccountManager>,
+}
+
+/// The parameters of a request, from its query string and, for clients
+/// posting forms, from its body. Parameters like `id` may be repeated.
+pub struct SubsonicParams(Vec<(String, String)>);
+
+impl SubsonicParams {
+    fn parse(query: Option<&str>, body: &[u8]) -> Self {
+        let query = url::form_urlencoded::parse(query.unwrap_or_default().as_bytes());
+        let form = url::form_urlencoded::parse(body);
+
+        Self(
+            query
+                .chain(form)
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn all(&self, name: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    pub fn required(&self, name: &str) -> Result<&str, SubsonicError> {
+        self.get(name)
+            .ok_or_else(|| SubsonicError::missing_parameter(name))
+    }
+
+    /// Parses an optional parameter, falling back to `default` if it is
+    /// missing.
+    pub fn parsed<T: FromStr>(&self, name: &str, default: T) -> Result<T, SubsonicError> {
+        match self.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| SubsonicError::generic(format!("Invalid {}: {}", name, value))),
+            None => Ok(default),
+        }
+    }
+
+    pub fn id(&self, name: &str) -> Result<i32, SubsonicError> {
+        parse_id(self.required(name)?)
+    }
+
+    pub fn ids(&self, name: &str) -> Result<Vec<i32>, SubsonicError> {
+        self.all(name).into_iter().map(parse_id).collect()
+    }
+}
+
+fn parse_id(id: &str) -> Result<i32, SubsonicError> {
+    id.parse()
+        .map_err(|_| SubsonicError::not_found(&format!("Item {}", id)))
+}
+
+/// Routes the Subsonic API under `/rest`. Requests are signed in with a
+/// Subsonic account rather than a device certificate, for example:
+///
+/// curl -k "https://localhost:7863/rest/ping?u=alice&p=secret&f=json"
+pub fn router<S>(state: Arc<SubsonicState>) -> Router<S> {
+    Router::new()
+        .route("/rest/{method}", get(rest_handler).post(rest_handler))
+        .with_state(state)
+}
+
+async fn rest_handler(
+    State(state): State<Arc<SubsonicState>>,
+    Path(method): Path<String>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let params = SubsonicParams::parse(query.as_deref(), &body);
+    let format = ResponseFormat::from_param(params.get("f"));
+    let method = method.strip_suffix(".view").unwrap_or(&method);
+
+    render(format, handle(&state, method, &params, &headers).await)
+}
+
+/// The capability an account needs to call a method.
+fn required_capability(method: &str) -> Option<Capability> {
+    match method {
+        "ping"
+        | "getLicense"
+        | "getMusicFolders"
+        | "getOpenSubsonicExtensions"
+        | "getArtists"
+        | "getArtist"
+        | "getAlbum"
+        | "getSong"
+        | "getAlbumList2"
+        | "getStarred2"
+        | "search3"
+        | "getPlaylists"
+        | "getPlaylist"
+        | "stream"
+        | "download"
+        | "getCoverArt" => Some(Capability::Browse),
+        "scrobble" => Some(Capability::ControlPlayback),
+        "star" | "unstar" | "createPlaylist" | "updatePlaylist" | "deletePlaylist" => {
+            Some(Capability::EditLibrary)
+        }
+        _ => None,
+    }
+}
+
+async fn handle(
+    state: &SubsonicState,
+    method: &str,
+    params: &SubsonicParams,
+    headers: &HeaderMap,
+) -> SubsonicResult {
+    let account = authenticate(params, &state.accounts).await?;
+
+    let capability = required_capability(method)
+        .ok_or_else(|| SubsonicError::not_found(&format!("Method {}", method)))?;
+    if !account.role.allows(capability) {
+        return Err(SubsonicError::not_authorized(method));
+    }
+
+    match method {
+        "ping" => Ok(SubsonicReply::empty()),
+        "getLicense" => browsing::get_license(),
+        "getMusicFolders" => browsing::get_music_folders(),
+        "getOpenSubsonicExtensions" => browsing::get_open_subsonic_extensions(),
+        "getArtists" => browsing::get_artists(state).await,
+        "getArtist" => browsing::get_artist(state, params).await,
+        "getAlbum" => browsing::get_album(state, params).await,
+        "getSong" => browsing::get_song(state, params).await,
+        "getAlbumList2" => browsing::get_album_list2(state, params).await,
+        "search3" => search::search3(state, params).await,
+        "stream" => media::stream(state, params, headers, true).await,
+        "download" => media::stream(state, params, headers, false).await,
+        "getCoverArt" => media::get_cover_art(state, params).await,
+        "scrobble" => annotation::scrobble(state, params).await,
+        "star" => annotation::star(state, params, true).await,
+        "unstar" => annotation::star(state, params, false).await,
+        "getStarred2" => annotation::get_starred2(state).await,
+        "getPlaylists" => playlists::get_playlists(state, &account).await,
+        "getPlaylist" => playlists::get_playlist(state, params, &account).await,
+        "createPlaylist" => playlists::create_playlist(state, params, &account).await,
+        "updatePlaylist" => playlists::update_playlist(state, params).await,
+        "deletePlaylist" => playlists::delete_playlist(state, params).await,
+        _ => Err(SubsonicError::not_found(&format!("Method {}", method))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+    };
+    use sea_orm::{ActiveModelTrait, ActiveValue};
+    use serde_json::Value;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    use ::database::connection::{connect_fake_main_db, initialize_db};
+    use ::database::entities::media_files;
+    use ::discovery::server::UserRole;
+
+    use super::*;
+
+    async fn test_router(directory: &TempDir) -> Router {
+        let lib_path = directory.path().join("library");
+        std::fs::create_dir_all(lib_path.join("Artist")).unwrap();
+        std::fs::write(
+            lib_path.join("Artist/song.mp3"),
+            (0..100).collect::<Vec<u8>>(),
+        )
+        .unwrap();
+
+        let main_db = connect_fake_main_db().await.unwrap();
+        initialize_db(&main_db).await.unwrap();
+        media_files::ActiveModel {
+            id: ActiveValue::Set(1),
+            file_name: ActiveValue::Set("song.mp3".to_owned()),
+            directory: ActiveValue::Set("Artist".to_owned()),
+            extension: ActiveValue::Set("mp3".to_owned()),
+            file_hash: ActiveValue::Set("hash".to_owned()),
+            last_modified: ActiveValue::Set("2024-01-01T00:00:00+00:00".to_owned()),
+            cover_art_id: ActiveValue::Set(None),
+            sample_rate: ActiveValue::Set(44100),
+            duration: ActiveValue::Set(Default::default()),
+            cue_track: ActiveValue::Set(None),
+            start_offset: ActiveValue::Set(None),
+            end_offset: ActiveValue::Set(None),
+        }
+        .insert(&main_db)
+        .await
+        .unwrap();
+
+        let accounts = SubsonicAccountManager::new(directory.path()).unwrap();
+        accounts
+            .add_account("alice", "secret", UserRole::Admin)
+            .await
+            .unwrap();
+        accounts
+            .add_account("bob", "hunter2", UserRole::Viewer)
+            .await
+            .unwrap();
+
+        router(Arc::new(SubsonicState {
+            lib_path,
+            main_db: Arc::new(main_db),
+            transcode_cache: Arc::new(SegmentCache::new(directory.path().join("cache"), 0)),
+            accounts: Arc::new(accounts),
+        }))
+    }
+
+    async fn call(router: &Router, uri: &str) -> Value {
+        let response = router
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<Value>(&body).unwrap()["subsonic-response"].clone()
+    }
+
+    fn error_code(response: &Value) -> Option<u64> {
+        response["error"]["code"].as_u64()
+    }
+
+    #[tokio::test]
+    async fn test_authentication() {
+        let directory = tempfile::tempdir().unwrap();
+        let router = test_router(&directory).await;
+
+        let response = call(&router, "/rest/ping.view?u=alice&p=secret&f=json").await;
+        assert_eq!(response["status"], "ok");
+        assert_eq!(response["version"], response::API_VERSION);
+
+        // `secret` encoded as hex
+        let response = call(&router, "/rest/ping?u=alice&p=enc:736563726574&f=json").await;
+        assert_eq!(response["status"], "ok");
+
+        // The token is the MD5 hash of the password followed by the salt
+        let token = format!("{:x}", md5::compute("secretc19b2d"));
+        let uri = format!("/rest/ping?u=alice&t={}&s=c19b2d&f=json", token);
+        assert_eq!(call(&router, &uri).await["status"], "ok");
+
+        let uri = format!("/rest/ping?u=alice&t={}&s=other&f=json", token);
+        assert_eq!(error_code(&call(&router, &uri).await), Some(40));
+
+        for uri in [
+            "/rest/ping?u=alice&p=wrong&f=json",
+            "/rest/ping?u=nobody&p=secret&f=json",
+            "/rest/ping?u=alice&p=enc:7&f=json",
+        ] {
+            let response = call(&router, uri).await;
+            assert_eq!(response["status"], "failed");
+            assert_eq!(error_code(&response), Some(40), "{}", uri);
+        }
+
+        let response = call(&router, "/rest/ping?u=alice&f=json").await;
+        assert_eq!(error_code(&response), Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_roles() {
+        let directory = tempfile::tempdir().unwrap();
+        let router = test_router(&directory).await;
+
+        let response = call(&router, "/rest/getLicense?u=bob&p=hunter2&f=json").await;
+        assert_eq!(response["status"], "ok");
+
+        // Viewers can not change the library
+        let response = call(&router, "/rest/star?u=bob&p=hunter2&id=1&f=json").await;
+        assert_eq!(error_code(&response), Some(50));
+
+        let response = call(&router, "/rest/unknownMethod?u=alice&p=secret&f=json").await;
+        assert_eq!(error_code(&response), Some(70));
+    }
+
+    #[tokio::test]
+    async fn test_stream_range() {
+        let directory = tempfile::tempdir().unwrap();
+        let router = test_router(&directory).await;
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::get("/rest/stream?u=bob&p=hunter2&id=1")
+                    .header(header::RANGE, "bytes=10-19")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 10-19/100");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "audio/mpeg");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], (10..20).collect::<Vec<u8>>().as_slice());
+
+        let response = call(&router, "/rest/stream?u=bob&p=hunter2&id=2&f=json").await;
+        assert_eq!(error_code(&response), Some(70));
+    }
+}
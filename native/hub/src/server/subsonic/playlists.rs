@@ -0,0 +1,158 @@
+use std::collections::BTreeSet;
+
+use serde_json::{json, Value};
+
+use ::database::actions::{
+    catalog::{get_playlist_summaries, PlaylistSummary},
+    playlists::{
+        add_item_to_playlist, create_playlist as create_library_playlist, get_playlist_items,
+        remove_item_from_playlist, remove_playlist, replace_playlist_items,
+        update_playlist as update_library_playlist,
+    },
+};
+
+use super::{
+    accounts::SubsonicAccount,
+    browsing::songs_json,
+    response::{SubsonicError, SubsonicReply, SubsonicResult},
+    SubsonicParams, SubsonicState,
+};
+
+/// The group playlists created by Subsonic clients are put in.
+const PLAYLIST_GROUP: &str = "Subsonic";
+
+/// Library playlists have no owner, they are shown as owned by the account
+/// asking for them.
+fn playlist_json(playlist: &PlaylistSummary, account: &SubsonicAccount) -> Value {
+    let mut value = json!({
+        "id": playlist.id.to_string(),
+        "name": playlist.name,
+        "owner": account.username,
+        "public": false,
+        "songCount": playlist.song_count,
+        "duration": playlist.duration.round() as i64,
+        "created": playlist.created_at,
+        "changed": playlist.updated_at,
+    });
+
+    if let Some(cover_art_id) = playlist.cover_art_id {
+        value["coverArt"] = json!(cover_art_id.to_string());
+    }
+
+    value
+}
+
+async fn playlist_with_entries(
+    state: &SubsonicState,
+    playlist_id: i32,
+    account: &SubsonicAccount,
+) -> SubsonicResult {
+    let playlist = get_playlist_summaries(&state.main_db, Some(vec![playlist_id]))
+        .await?
+        .pop()
+        .ok_or_else(|| SubsonicError::not_found("Playlist"))?;
+
+    let file_ids = get_playlist_items(&state.main_db, playlist_id)
+        .await?
+        .into_iter()
+        .map(|x| x.media_file_id)
+        .collect();
+
+    let mut value = playlist_json(&playlist, account);
+    value["entry"] = Value::Array(songs_json(state, file_ids).await?);
+
+    Ok(SubsonicReply::element("playlist", value))
+}
+
+pub async fn get_playlists(state: &SubsonicState, account: &SubsonicAccount) -> SubsonicResult {
+    let playlists: Vec<Value> = get_playlist_summaries(&state.main_db, None)
+        .await?
+        .iter()
+        .map(|x| playlist_json(x, account))
+        .collect();
+
+    Ok(SubsonicReply::element(
+        "playlists",
+        json!({ "playlist": playlists }),
+    ))
+}
+
+pub async fn get_playlist(
+    state: &SubsonicState,
+    params: &SubsonicParams,
+    account: &SubsonicAccount,
+) -> SubsonicResult {
+    playlist_with_entries(state, params.id("id")?, account).await
+}
+
+/// Creates a playlist, or replaces the songs of the one given by
+/// `playlistId`.
+pub async fn create_playlist(
+    state: &SubsonicState,
+    params: &SubsonicParams,
+    account: &SubsonicAccount,
+) -> SubsonicResult {
+    let song_ids = params.ids("songId")?;
+
+    let playlist_id = match params.get("playlistId") {
+        Some(_) => params.id("playlistId")?,
+        None => {
+            let name = params.required("name")?;
+            create_library_playlist(&*state.main_db, name.to_owned(), PLAYLIST_GROUP.to_owned())
+                .await?
+                .id
+        }
+    };
+
+    replace_playlist_items(&state.main_db, playlist_id, &song_ids).await?;
+
+    playlist_with_entries(state, playlist_id, account).await
+}
+
+pub async fn update_playlist(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    let playlist_id = params.id("playlistId")?;
+
+    if let Some(name) = params.get("name") {
+        update_library_playlist(&state.main_db, playlist_id, Some(name.to_owned()), None)
+            .await
+            .map_err(|_| SubsonicError::not_found("Playlist"))?;
+    }
+
+    // Remove from the end, so the indexes of the other items stay valid
+    let indexes: BTreeSet<usize> = params
+        .all("songIndexToRemove")
+        .into_iter()
+        .map(|x| {
+            x.parse()
+                .map_err(|_| SubsonicError::generic(format!("Invalid songIndexToRemove: {}", x)))
+        })
+        .collect::<Result<_, _>>()?;
+    if !indexes.is_empty() {
+        let items = get_playlist_items(&state.main_db, playlist_id).await?;
+        for index in indexes.into_iter().rev() {
+            if let Some(item) = items.get(index) {
+                remove_item_from_playlist(
+                    &state.main_db,
+                    playlist_id,
+                    item.media_file_id,
+                    item.position,
+                )
+                .await?;
+            }
+        }
+    }
+
+    for song_id in params.ids("songIdToAdd")? {
+        add_item_to_playlist(&state.main_db, playlist_id, song_id, None).await?;
+    }
+
+    Ok(SubsonicReply::empty())
+}
+
+pub async fn delete_playlist(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    remove_playlist(&state.main_db, params.id("id")?)
+        .await
+        .map_err(|_| SubsonicError::not_found("Playlist"))?;
+
+    Ok(SubsonicReply::empty())
+}
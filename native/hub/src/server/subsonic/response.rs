@@ -0,0 +1,209 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Map, Value};
+
+/// The Subsonic API version the server implements.
+pub const API_VERSION: &str = "1.16.1";
+
+const XML_NAMESPACE: &str = "http://subsonic.org/restapi";
+
+/// An error reported to the client in the response body, Subsonic clients
+/// expect errors to be answered with a successful HTTP status.
+#[derive(Debug)]
+pub struct SubsonicError {
+    pub code: u32,
+    pub message: String,
+}
+
+impl SubsonicError {
+    pub fn generic(message: impl Into<String>) -> Self {
+        Self {
+            code: 0,
+            message: message.into(),
+        }
+    }
+
+    pub fn missing_parameter(name: &str) -> Self {
+        Self {
+            code: 10,
+            message: format!("Required parameter is missing: {}", name),
+        }
+    }
+
+    pub fn wrong_credentials() -> Self {
+        Self {
+            code: 40,
+            message: "Wrong username or password".to_owned(),
+        }
+    }
+
+    pub fn not_authorized(method: &str) -> Self {
+        Self {
+            code: 50,
+            message: format!("User is not authorized for {}", method),
+        }
+    }
+
+    pub fn not_found(what: &str) -> Self {
+        Self {
+            code: 70,
+            message: format!("{} not found", what),
+        }
+    }
+}
+
+impl From<anyhow::Error> for SubsonicError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::generic(format!("{:#}", e))
+    }
+}
+
+/// The body of a successful response, or a complete response for methods
+/// returning media.
+pub enum SubsonicReply {
+    Body(Map<String, Value>),
+    Media(Response),
+}
+
+impl SubsonicReply {
+    pub fn empty() -> Self {
+        Self::Body(Map::new())
+    }
+
+    /// A body with a single element.
+    pub fn element(name: &str, value: Value) -> Self {
+        let mut body = Map::new();
+        body.insert(name.to_owned(), value);
+        Self::Body(body)
+    }
+}
+
+pub type SubsonicResult = Result<SubsonicReply, SubsonicError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseFormat {
+    Xml,
+    Json,
+}
+
+impl ResponseFormat {
+    pub fn from_param(format: Option<&str>) -> Self {
+        match format {
+            Some("json") => Self::Json,
+            _ => Self::Xml,
+        }
+    }
+}
+
+pub fn render(format: ResponseFormat, result: SubsonicResult) -> Response {
+    let mut body = Map::new();
+
+    match result {
+        Ok(SubsonicReply::Media(response)) => return response,
+        Ok(SubsonicReply::Body(content)) => {
+            body.insert("status".to_owned(), json!("ok"));
+            insert_server_info(&mut body);
+            body.extend(content);
+        }
+        Err(e) => {
+            body.insert("status".to_owned(), json!("failed"));
+            insert_server_info(&mut body);
+            body.insert(
+                "error".to_owned(),
+                json!({ "code": e.code, "message": e.message }),
+            );
+        }
+    }
+
+    match format {
+        ResponseFormat::Json => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/json")],
+            json!({ "subsonic-response": body }).to_string(),
+        )
+            .into_response(),
+        ResponseFormat::Xml => {
+            body.insert("xmlns".to_owned(), json!(XML_NAMESPACE));
+
+            let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+            write_element(&mut xml, "subsonic-response", &body);
+
+            (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "application/xml")],
+                xml,
+            )
+                .into_response()
+        }
+    }
+}
+
+fn insert_server_info(body: &mut Map<String, Value>) {
+    body.insert("version".to_owned(), json!(API_VERSION));
+    body.insert("type".to_owned(), json!("rune"));
+    body.insert("serverVersion".to_owned(), json!(env!("CARGO_PKG_VERSION")));
+    body.insert("openSubsonic".to_owned(), json!(true));
+}
+
+/// Writes an object the way the Subsonic XML schema lays it out: scalars
+/// become attributes, objects become child elements, arrays become
+/// repeated child elements and a `value` field becomes the text content.
+fn write_element(xml: &mut String, name: &str, object: &Map<String, Value>) {
+    xml.push('<');
+    xml.push_str(name);
+
+    for (key, value) in object {
+        let text = match value {
+            Value::String(x) => x.clone(),
+            Value::Number(x) => x.to_string(),
+            Value::Bool(x) => x.to_string(),
+            _ => continue,
+        };
+
+        if key != "value" {
+            xml.push_str(&format!(r#" {}="{}""#, key, escape_xml(&text)));
+        }
+    }
+
+    let mut content = String::new();
+    if let Some(Value::String(text)) = object.get("value") {
+        content.push_str(&escape_xml(text));
+    }
+
+    for (key, value) in object {
+        match value {
+            Value::Object(child) => write_element(&mut content, key, child),
+            Value::Array(items) => {
+                for item in items {
+                    match item {
+                        Value::Object(child) => write_element(&mut content, key, child),
+                        Value::Null => {}
+                        scalar => {
+                            let text = scalar.as_str().map_or(scalar.to_string(), str::to_owned);
+                            content.push_str(&format!("<{0}>{1}</{0}>", key, escape_xml(&text)));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if content.is_empty() {
+        xml.push_str("/>");
+    } else {
+        xml.push('>');
+        xml.push_str(&content);
+        xml.push_str(&format!("</{}>", name));
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
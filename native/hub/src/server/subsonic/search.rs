@@ -0,0 +1,109 @@
+use serde_json::{json, Value};
+
+use ::database::actions::{
+    catalog::{get_album_summaries, get_artist_summaries},
+    collection::CollectionQueryType,
+    file::get_reverse_listed_media_files,
+    search::search_for,
+};
+
+use super::{
+    browsing::{album_json, artist_json, songs_json},
+    response::{SubsonicError, SubsonicReply, SubsonicResult},
+    SubsonicParams, SubsonicState,
+};
+
+struct Page {
+    count: usize,
+    offset: usize,
+}
+
+impl Page {
+    fn from_params(params: &SubsonicParams, name: &str) -> Result<Self, SubsonicError> {
+        Ok(Self {
+            count: params.parsed(&format!("{}Count", name), 20)?,
+            offset: params.parsed(&format!("{}Offset", name), 0)?,
+        })
+    }
+
+    fn apply<T: Clone>(&self, items: &[T]) -> Vec<T> {
+        items
+            .iter()
+            .skip(self.offset)
+            .take(self.count)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Searches artists, albums and songs. An empty query lists the whole
+/// library, which clients use to sync it page by page.
+pub async fn search3(state: &SubsonicState, params: &SubsonicParams) -> SubsonicResult {
+    let query = params.required("query")?.trim().trim_matches('"').trim();
+
+    let artist_page = Page::from_params(params, "artist")?;
+    let album_page = Page::from_params(params, "album")?;
+    let song_page = Page::from_params(params, "song")?;
+
+    let (artists, albums, songs) = if query.is_empty() {
+        let artists = get_artist_summaries(&state.main_db).await?;
+        let albums = get_album_summaries(&state.main_db, None).await?;
+        let files =
+            get_reverse_listed_media_files(&state.main_db, song_page.offset, song_page.count)
+                .await
+                .map_err(anyhow::Error::from)?;
+
+        (
+            artist_page.apply(&artists),
+            album_page.apply(&albums),
+            files.into_iter().map(|x| x.id).collect(),
+        )
+    } else {
+        let n = [&artist_page, &album_page, &song_page]
+            .iter()
+            .map(|x| x.offset + x.count)
+            .max()
+            .unwrap_or_default();
+
+        let mut results = search_for(
+            &state.main_db,
+            query,
+            Some(vec![
+                CollectionQueryType::Artist,
+                CollectionQueryType::Album,
+                CollectionQueryType::Track,
+            ]),
+            n,
+        )
+        .await?;
+
+        let mut ids = |collection_type: CollectionQueryType, page: &Page| -> Vec<i32> {
+            page.apply(&results.remove(&collection_type).unwrap_or_default())
+                .into_iter()
+                .map(|x| x as i32)
+                .collect()
+        };
+        let artist_ids = ids(CollectionQueryType::Artist, &artist_page);
+        let album_ids = ids(CollectionQueryType::Album, &album_page);
+        let song_ids = ids(CollectionQueryType::Track, &song_page);
+
+        // Keep the ranking of the search
+        let mut artists = get_artist_summaries(&state.main_db).await?;
+        artists.retain(|x| artist_ids.contains(&x.id));
+        artists.sort_by_key(|x| artist_ids.iter().position(|id| *id == x.id));
+
+        let mut albums = get_album_summaries(&state.main_db, Some(album_ids.clone())).await?;
+        albums.sort_by_key(|x| album_ids.iter().position(|id| *id == x.id));
+
+        (artists, albums, song_ids)
+    };
+
+    let artists: Vec<Value> = artists.iter().map(artist_json).collect();
+    let albums: Vec<Value> = albums.iter().map(album_json).collect();
+    let songs = songs_json(state, songs).await?;
+
+    Ok(SubsonicReply::element(
+        "searchResult3",
+        json!({ "artist": artists, "album": albums, "song": songs }),
+    ))
+}
@@ -0,0 +1,738 @@
+use std::{fmt::Write, path::Path, str::FromStr};
+
+use ::database::{
+    actions::{
+        collection::{CollectionQuery, CollectionQueryListMode, CollectionQueryType},
+        directory::{get_directory_tree, DirectoryTree},
+        metadata::{
+            get_metadata_summary_by_file_ids, get_metadata_summary_by_files, MetadataSummary,
+        },
+        mixes::{query_mix_media_files, GroupedQuery},
+    },
+    entities::{albums, artists, genres, media_files, playlists},
+};
+use ::transcoding::TranscodeFormat;
+
+use crate::server::subsonic::content_type;
+
+use super::{soap::escape, soap::UpnpError, UpnpState};
+
+/// Tracks listed at most in a container, as many as the app plays at once.
+const MAX_TRACKS: usize = 4096;
+
+/// The collections browsable from the root, with the title of each.
+const ROOT_COLLECTIONS: [(CollectionQueryType, &str); 5] = [
+    (CollectionQueryType::Artist, "Artists"),
+    (CollectionQueryType::Album, "Albums"),
+    (CollectionQueryType::Genre, "Genres"),
+    (CollectionQueryType::Playlist, "Playlists"),
+    (CollectionQueryType::Directory, "Folders"),
+];
+
+/// An object of the content directory. Ids are `0` for the root, the
+/// collection type for the list of its collections and the type followed
+/// by `/<id>` for a collection, such as `album/12`. Folders use their path
+/// as id, like `directory/Rock/Live`, and tracks are `track/<file id>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Object {
+    Root,
+    Collections(CollectionQueryType),
+    Collection(CollectionQueryType, i32),
+    Directory(String),
+    Track(i32),
+}
+
+impl Object {
+    fn parse(id: &str) -> Option<Self> {
+        if id == "0" {
+            return Some(Object::Root);
+        }
+
+        let (collection_type, rest) = match id.split_once('/') {
+            Some((collection_type, rest)) => (collection_type, Some(rest)),
+            None => (id, None),
+        };
+
+        match (CollectionQueryType::from_str(collection_type).ok()?, rest) {
+            (CollectionQueryType::Directory, path) => {
+                Some(Object::directory(path.unwrap_or_default()))
+            }
+            (CollectionQueryType::Track, Some(id)) => id.parse().ok().map(Object::Track),
+            (CollectionQueryType::Mix | CollectionQueryType::Track, _) => None,
+            (collection_type, None) => Some(Object::Collections(collection_type)),
+            (collection_type, Some(id)) => id
+                .parse()
+                .ok()
+                .map(|id| Object::Collection(collection_type, id)),
+        }
+    }
+
+    /// The folder at a path of the library, which is an empty path for the
+    /// library itself and starts with a slash otherwise, as in the
+    /// directory tree.
+    fn directory(path: &str) -> Self {
+        match path.trim_matches('/') {
+            "" => Object::Directory(String::new()),
+            path => Object::Directory(format!("/{}", path)),
+        }
+    }
+
+    fn id(&self) -> String {
+        match self {
+            Object::Root => String::from("0"),
+            Object::Collections(collection_type) => collection_type.to_string(),
+            Object::Collection(collection_type, id) => format!("{}/{}", collection_type, id),
+            // Paths of the directory tree start with a slash
+            Object::Directory(path) => format!("{}{}", CollectionQueryType::Directory, path),
+            Object::Track(id) => format!("{}/{}", CollectionQueryType::Track, id),
+        }
+    }
+}
+
+enum Entry {
+    Container {
+        object: Object,
+        parent: String,
+        title: String,
+        child_count: Option<usize>,
+    },
+    Track {
+        parent: String,
+        summary: MetadataSummary,
+    },
+}
+
+/// What `Browse` asks for, the object itself or a page of its children.
+pub struct BrowseRequest {
+    pub object_id: String,
+    pub children: bool,
+    pub start: usize,
+    /// Zero asks for all children
+    pub count: usize,
+}
+
+pub struct BrowseResult {
+    pub didl: String,
+    pub returned: usize,
+    pub total: usize,
+}
+
+pub async fn browse(
+    state: &UpnpState,
+    request: &BrowseRequest,
+    base_url: &str,
+) -> Result<BrowseResult, UpnpError> {
+    let object = Object::parse(&request.object_id).ok_or(UpnpError::NoSuchObject)?;
+
+    let (entries, total) = if request.children {
+        children(state, &object, request.start, request.count).await?
+    } else {
+        (vec![metadata(state, &object).await?], 1)
+    };
+
+    Ok(BrowseResult {
+        didl: didl(&entries, base_url, &state.lib_path),
+        returned: entries.len(),
+        total,
+    })
+}
+
+async fn metadata(state: &UpnpState, object: &Object) -> Result<Entry, UpnpError> {
+    let entry = match object {
+        Object::Root => Entry::Container {
+            object: Object::Root,
+            parent: String::from("-1"),
+            title: state.device.friendly_name.clone(),
+            child_count: Some(ROOT_COLLECTIONS.len()),
+        },
+        Object::Collections(collection_type) => Entry::Container {
+            object: object.clone(),
+            parent: Object::Root.id(),
+            title: ROOT_COLLECTIONS
+                .iter()
+                .find(|(x, _)| x == collection_type)
+                .map(|(_, title)| title.to_string())
+                .ok_or(UpnpError::NoSuchObject)?,
+            child_count: None,
+        },
+        Object::Collection(collection_type, id) => Entry::Container {
+            object: object.clone(),
+            parent: Object::Collections(collection_type.clone()).id(),
+            title: collection_name(state, collection_type, *id)
+                .await?
+                .ok_or(UpnpError::NoSuchObject)?,
+            child_count: None,
+        },
+        Object::Directory(path) => {
+            let tree = get_directory_tree(&state.main_db).await?;
+            let node = find_directory(&tree, path).ok_or(UpnpError::NoSuchObject)?;
+
+            Entry::Container {
+                object: object.clone(),
+                parent: match path.rsplit_once('/') {
+                    Some((parent, _)) => Object::directory(parent).id(),
+                    None => Object::Root.id(),
+                },
+                title: if path.is_empty() {
+                    String::from("Folders")
+                } else {
+                    node.name.clone()
+                },
+                child_count: None,
+            }
+        }
+        Object::Track(id) => {
+            let summary = get_metadata_summary_by_file_ids(&state.main_db, vec![*id])
+                .await?
+                .pop()
+                .ok_or(UpnpError::NoSuchObject)?;
+
+            Entry::Track {
+                parent: Object::directory(&summary.directory).id(),
+                summary,
+            }
+        }
+    };
+
+    Ok(entry)
+}
+
+async fn children(
+    state: &UpnpState,
+    object: &Object,
+    start: usize,
+    count: usize,
+) -> Result<(Vec<Entry>, usize), UpnpError> {
+    let end = |total: usize| match count {
+        0 => total,
+        count => total.min(start.saturating_add(count)),
+    };
+    let parent = object.id();
+
+    match object {
+        Object::Root => {
+            let entries: Vec<Entry> = ROOT_COLLECTIONS
+                .iter()
+                .map(|(collection_type, title)| Entry::Container {
+                    object: Object::Collections(collection_type.clone()),
+                    parent: parent.clone(),
+                    title: title.to_string(),
+                    child_count: None,
+                })
+                .collect();
+            let total = entries.len();
+
+            Ok((page(entries, start, end(total)), total))
+        }
+        Object::Collections(collection_type) => {
+            let total = collection_count(state, collection_type).await?;
+            let collections = list_collections(state, collection_type, end(total)).await?;
+
+            let entries = collections
+                .into_iter()
+                .skip(start)
+                .map(|(id, name)| Entry::Container {
+                    object: Object::Collection(collection_type.clone(), id),
+                    parent: parent.clone(),
+                    title: name,
+                    child_count: None,
+                })
+                .collect();
+
+            Ok((entries, total))
+        }
+        Object::Collection(collection_type, id) => {
+            let queries = collection_queries(state, collection_type, *id).await?;
+            let files =
+                query_mix_media_files(&state.main_db, &state.recommend_db, queries, 0, MAX_TRACKS)
+                    .await?;
+
+            track_page(state, files, &parent, start, end).await
+        }
+        Object::Directory(path) => {
+            let tree = get_directory_tree(&state.main_db).await?;
+            let node = find_directory(&tree, path).ok_or(UpnpError::NoSuchObject)?;
+
+            let mut directories: Vec<&DirectoryTree> = node.children.iter().collect();
+            directories.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let files = query_mix_media_files(
+                &state.main_db,
+                &state.recommend_db,
+                vec![GroupedQuery::new(
+                    "lib::directory.shallow",
+                    path.as_str(),
+                    0,
+                )],
+                0,
+                MAX_TRACKS,
+            )
+            .await?;
+
+            // Folders come first, then the tracks right in the folder
+            let total = directories.len() + files.len();
+            let end = end(total);
+            let mut entries: Vec<Entry> = directories
+                .iter()
+                .skip(start)
+                .take(end.saturating_sub(start))
+                .map(|x| Entry::Container {
+                    object: Object::Directory(x.path.clone()),
+                    parent: parent.clone(),
+                    title: x.name.clone(),
+                    child_count: Some(x.children.len()),
+                })
+                .collect();
+
+            let (tracks, _) = track_page(
+                state,
+                files,
+                &parent,
+                start.saturating_sub(directories.len()),
+                |_| end.saturating_sub(directories.len()),
+            )
+            .await?;
+            entries.extend(tracks);
+
+            Ok((entries, total))
+        }
+        Object::Track(_) => Err(UpnpError::NoSuchContainer),
+    }
+}
+
+fn page<T>(items: Vec<T>, start: usize, end: usize) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(start)
+        .take(end.saturating_sub(start))
+        .collect()
+}
+
+async fn track_page(
+    state: &UpnpState,
+    files: Vec<media_files::Model>,
+    parent: &str,
+    start: usize,
+    end: impl Fn(usize) -> usize,
+) -> Result<(Vec<Entry>, usize), UpnpError> {
+    let total = files.len();
+    let files = page(files, start, end(total));
+
+    let entries = get_metadata_summary_by_files(&state.main_db, files)
+        .await?
+        .into_iter()
+        .map(|summary| Entry::Track {
+            parent: parent.to_owned(),
+            summary,
+        })
+        .collect();
+
+    Ok((entries, total))
+}
+
+fn find_directory<'a>(tree: &'a DirectoryTree, path: &str) -> Option<&'a DirectoryTree> {
+    path.split('/')
+        .filter(|x| !x.is_empty())
+        .try_fold(tree, |node, name| {
+            node.children.iter().find(|x| x.name == name)
+        })
+}
+
+/// Runs an operation on the collection model of a collection type. Only
+/// the types browsable from the root have collections.
+macro_rules! with_collection_model {
+    ($collection_type:expr, $model:ident => $body:expr) => {
+        match $collection_type {
+            CollectionQueryType::Artist => {
+                type $model = artists::Model;
+                $body
+            }
+            CollectionQueryType::Album => {
+                type $model = albums::Model;
+                $body
+            }
+            CollectionQueryType::Genre => {
+                type $model = genres::Model;
+                $body
+            }
+            CollectionQueryType::Playlist => {
+                type $model = playlists::Model;
+                $body
+            }
+            _ => Err(UpnpError::NoSuchObject),
+        }
+    };
+}
+
+async fn collection_count(
+    state: &UpnpState,
+    collection_type: &CollectionQueryType,
+) -> Result<usize, UpnpError> {
+    with_collection_model!(collection_type, Model => {
+        let groups = Model::count_by_first_letter(&state.main_db).await?;
+        Ok(groups.iter().map(|(_, count)| *count as usize).sum())
+    })
+}
+
+/// Lists the first `limit` collections of a type by name.
+async fn list_collections(
+    state: &UpnpState,
+    collection_type: &CollectionQueryType,
+    limit: usize,
+) -> Result<Vec<(i32, String)>, UpnpError> {
+    with_collection_model!(collection_type, Model => {
+        let models =
+            Model::list(&state.main_db, limit as u64, CollectionQueryListMode::Name).await?;
+        Ok(models
+            .iter()
+            .map(|x| (x.id(), x.name().to_owned()))
+            .collect())
+    })
+}
+
+async fn collection_name(
+    state: &UpnpState,
+    collection_type: &CollectionQueryType,
+    id: i32,
+) -> Result<Option<String>, UpnpError> {
+    with_collection_model!(collection_type, Model => {
+        let models = Model::get_by_ids(&state.main_db, &[id]).await?;
+        Ok(models.first().map(|x| x.name().to_owned()))
+    })
+}
+
+async fn collection_queries(
+    state: &UpnpState,
+    collection_type: &CollectionQueryType,
+    id: i32,
+) -> Result<Vec<GroupedQuery>, UpnpError> {
+    with_collection_model!(collection_type, Model => {
+        Ok(Model::query_builder(&state.main_db, id).await?)
+    })
+}
+
+/// Describes entries as a DIDL-Lite document, the format `Browse` returns.
+fn didl(entries: &[Entry], base_url: &str, lib_path: &Path) -> String {
+    let mut out = String::from(concat!(
+        r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" "#,
+        r#"xmlns:dc="http://purl.org/dc/elements/1.1/" "#,
+        r#"xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">"#,
+    ));
+
+    for entry in entries {
+        match entry {
+            Entry::Container {
+                object,
+                parent,
+                title,
+                child_count,
+            } => {
+                let _ = write!(
+                    out,
+                    r#"<container id="{}" parentID="{}" restricted="1" searchable="0""#,
+                    escape(&object.id()),
+                    escape(parent)
+                );
+                if let Some(child_count) = child_count {
+                    let _ = write!(out, r#" childCount="{}""#, child_count);
+                }
+                let _ = write!(
+                    out,
+                    "><dc:title>{}</dc:title><upnp:class>{}</upnp:class></container>",
+                    escape(title),
+                    container_class(object)
+                );
+            }
+            Entry::Track { parent, summary } => {
+                write_track(&mut out, parent, summary, base_url, lib_path)
+            }
+        }
+    }
+
+    out.push_str("</DIDL-Lite>");
+    out
+}
+
+fn container_class(object: &Object) -> &'static str {
+    match object {
+        Object::Collection(CollectionQueryType::Artist, _) => "object.container.person.musicArtist",
+        Object::Collection(CollectionQueryType::Album, _) => "object.container.album.musicAlbum",
+        Object::Collection(CollectionQueryType::Genre, _) => "object.container.genre.musicGenre",
+        Object::Collection(CollectionQueryType::Playlist, _) => {
+            "object.container.playlistContainer"
+        }
+        Object::Directory(_) => "object.container.storageFolder",
+        _ => "object.container",
+    }
+}
+
+fn write_track(
+    out: &mut String,
+    parent: &str,
+    summary: &MetadataSummary,
+    base_url: &str,
+    lib_path: &Path,
+) {
+    let path = Path::new(&summary.directory).join(&summary.file_name);
+    let suffix = path
+        .extension()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    // The library is served as it is stored, through the files route, but
+    // tracks of a CUE sheet are cut out of their file by the tracks route
+    let url = match summary.range {
+        Some(_) => format!("{}/upnp/tracks/{}", base_url, summary.id),
+        None => format!(
+            "{}/files/library/{}",
+            base_url,
+            path.iter()
+                .map(|x| urlencoding::encode(&x.to_string_lossy()).into_owned())
+                .collect::<Vec<_>>()
+                .join("/")
+        ),
+    };
+    let content_type = match summary.range {
+        Some(_) => TranscodeFormat::Mp3.mime_type(),
+        None => content_type(&suffix),
+    };
+
+    let _ = write!(
+        out,
+        r#"<item id="{}" parentID="{}" restricted="1"><dc:title>{}</dc:title>"#,
+        escape(&Object::Track(summary.id).id()),
+        escape(parent),
+        escape(&summary.title)
+    );
+    if !summary.artist.is_empty() {
+        let _ = write!(
+            out,
+            "<dc:creator>{0}</dc:creator><upnp:artist>{0}</upnp:artist>",
+            escape(&summary.artist)
+        );
+    }
+    if !summary.album.is_empty() {
+        let _ = write!(out, "<upnp:album>{}</upnp:album>", escape(&summary.album));
+    }
+    if !summary.genre.is_empty() {
+        let _ = write!(out, "<upnp:genre>{}</upnp:genre>", escape(&summary.genre));
+    }
+    if summary.track_number % 1000 > 0 {
+        let _ = write!(
+            out,
+            "<upnp:originalTrackNumber>{}</upnp:originalTrackNumber>",
+            summary.track_number % 1000
+        );
+    }
+
+    let mut res = format!(
+        r#"<res protocolInfo="http-get:*:{}:{}" duration="{}""#,
+        content_type,
+        super::DLNA_FEATURES,
+        format_duration(summary.duration)
+    );
+    if summary.range.is_none() {
+        if let Ok(metadata) = std::fs::metadata(lib_path.join(&path)) {
+            let _ = write!(res, r#" size="{}""#, metadata.len());
+        }
+    }
+
+    let _ = write!(
+        out,
+        "<upnp:class>object.item.audioItem.musicTrack</upnp:class>{}>{}</res></item>",
+        res,
+        escape(&url)
+    );
+}
+
+/// Formats seconds as `H:MM:SS.mmm`.
+fn format_duration(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicU32, Arc};
+
+    use sea_orm::{ActiveModelTrait, ActiveValue};
+    use tempfile::TempDir;
+
+    use ::database::connection::{
+        connect_fake_main_db, connect_fake_recommendation_db, initialize_db,
+    };
+    use ::transcoding::SegmentCache;
+
+    use crate::server::upnp::description::Device;
+
+    use super::*;
+
+    /// A library with tracks 1, 3 and 5 at its root, 2 in `Rock` and 4 in
+    /// `Jazz/Live`.
+    async fn test_state(directory: &TempDir) -> UpnpState {
+        let main_db = connect_fake_main_db().await.unwrap();
+        initialize_db(&main_db).await.unwrap();
+
+        for (id, directory) in [(1, ""), (2, "Rock"), (3, ""), (4, "Jazz/Live"), (5, "")] {
+            media_files::ActiveModel {
+                id: ActiveValue::Set(id),
+                file_name: ActiveValue::Set(format!("{}.mp3", id)),
+                directory: ActiveValue::Set(directory.to_owned()),
+                extension: ActiveValue::Set("mp3".to_owned()),
+                file_hash: ActiveValue::Set(format!("hash{}", id)),
+                last_modified: ActiveValue::Set("2024-01-01T00:00:00+00:00".to_owned()),
+                cover_art_id: ActiveValue::Set(None),
+                sample_rate: ActiveValue::Set(44100),
+                duration: ActiveValue::Set(Default::default()),
+                cue_track: ActiveValue::Set(None),
+                start_offset: ActiveValue::Set(None),
+                end_offset: ActiveValue::Set(None),
+            }
+            .insert(&main_db)
+            .await
+            .unwrap();
+        }
+
+        UpnpState {
+            lib_path: directory.path().to_path_buf(),
+            main_db: Arc::new(main_db),
+            recommend_db: Arc::new(connect_fake_recommendation_db().unwrap()),
+            cover_temp_dir: directory.path().join("covers"),
+            transcode_cache: Arc::new(SegmentCache::new(directory.path().join("cache"), 0)),
+            device: Device::new("fingerprint", "Rune".to_owned(), "1.0.0".to_owned()),
+            system_update_id: Arc::new(AtomicU32::new(1)),
+            addr: "127.0.0.1:8200".parse().unwrap(),
+        }
+    }
+
+    fn ids(entries: &[Entry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|x| match x {
+                Entry::Container { object, .. } => object.id(),
+                Entry::Track { summary, .. } => Object::Track(summary.id).id(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_object_ids() {
+        for object in [
+            Object::Root,
+            Object::Collections(CollectionQueryType::Album),
+            Object::Collection(CollectionQueryType::Artist, 12),
+            Object::directory(""),
+            Object::directory("/Rock/Live/"),
+            Object::Track(7),
+        ] {
+            assert_eq!(Object::parse(&object.id()), Some(object));
+        }
+
+        assert_eq!(
+            Object::directory("/Rock/Live/"),
+            Object::Directory(String::from("/Rock/Live"))
+        );
+        for id in ["", "album/x", "track", "mix/1", "unknown/1"] {
+            assert_eq!(Object::parse(id), None, "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0.0), "0:00:00.000");
+        assert_eq!(format_duration(3725.5), "1:02:05.500");
+        assert_eq!(format_duration(-1.0), "0:00:00.000");
+    }
+
+    #[tokio::test]
+    async fn test_children_paging() {
+        let directory = tempfile::tempdir().unwrap();
+        let state = test_state(&directory).await;
+
+        let (entries, total) = children(&state, &Object::Root, 1, 2).await.unwrap();
+        assert_eq!(
+            ids(&entries),
+            [CollectionQueryType::Album, CollectionQueryType::Genre]
+                .map(|x| Object::Collections(x).id())
+        );
+        assert_eq!(total, ROOT_COLLECTIONS.len());
+
+        let (entries, _) = children(&state, &Object::Root, 4, 10).await.unwrap();
+        assert_eq!(
+            ids(&entries),
+            [Object::Collections(CollectionQueryType::Directory).id()]
+        );
+        let (entries, total) = children(&state, &Object::Root, 9, 0).await.unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(total, ROOT_COLLECTIONS.len());
+
+        // Folders come first, then the tracks right in the folder
+        let folder = Object::directory("");
+        let (entries, total) = children(&state, &folder, 0, 0).await.unwrap();
+        let all = ids(&entries);
+        assert_eq!(total, 5);
+        assert_eq!(
+            all[..2],
+            [
+                Object::directory("Jazz").id(),
+                Object::directory("Rock").id()
+            ]
+        );
+        let mut tracks = all[2..].to_vec();
+        tracks.sort();
+        assert_eq!(tracks, [1, 3, 5].map(|x| Object::Track(x).id()));
+
+        // Pages may start among the folders and end among the tracks
+        for (start, count) in [
+            (0, 1),
+            (1, 2),
+            (2, 2),
+            (1, 4),
+            (3, 0),
+            (4, 10),
+            (5, 1),
+            (8, 2),
+        ] {
+            let (entries, total) = children(&state, &folder, start, count).await.unwrap();
+            let start = start.min(total);
+            let end = match count {
+                0 => total,
+                count => (start + count).min(total),
+            };
+            assert_eq!(
+                ids(&entries),
+                all[start..end].to_vec(),
+                "{}+{}",
+                start,
+                count
+            );
+            assert_eq!(total, 5);
+        }
+
+        let (entries, total) = children(&state, &Object::directory("Jazz"), 0, 0)
+            .await
+            .unwrap();
+        assert_eq!(ids(&entries), [Object::directory("Jazz/Live").id()]);
+        assert_eq!(total, 1);
+        let (entries, _) = children(&state, &Object::directory("Jazz/Live"), 0, 0)
+            .await
+            .unwrap();
+        assert_eq!(ids(&entries), [Object::Track(4).id()]);
+
+        assert!(matches!(
+            children(&state, &Object::directory("Pop"), 0, 0).await,
+            Err(UpnpError::NoSuchObject)
+        ));
+        assert!(matches!(
+            children(&state, &Object::Track(1), 0, 0).await,
+            Err(UpnpError::NoSuchContainer)
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+use std::{str::FromStr, sync::atomic::Ordering};
+
+use crate::server::subsonic::content_type;
+
+use super::{
+    content::{browse, BrowseRequest},
+    description::{CONNECTION_MANAGER, CONTENT_DIRECTORY},
+    soap::{action_response, argument, required_argument, ActionResult, UpnpError},
+    UpnpState, DLNA_FEATURES,
+};
+
+/// Suffixes of the formats the library may hold, to list their types.
+const SUFFIXES: [&str; 9] = [
+    "mp3", "flac", "ogg", "m4a", "wav", "aiff", "wv", "ape", "wma",
+];
+
+pub async fn content_directory(
+    state: &UpnpState,
+    action: &str,
+    body: &str,
+    base_url: &str,
+) -> ActionResult {
+    let system_update_id = state.system_update_id.load(Ordering::Relaxed).to_string();
+    let arguments = match action {
+        "Browse" => {
+            let request = BrowseRequest {
+                object_id: required_argument(body, "ObjectID")?,
+                children: match required_argument(body, "BrowseFlag")?.as_str() {
+                    "BrowseMetadata" => false,
+                    "BrowseDirectChildren" => true,
+                    _ => return Err(UpnpError::InvalidArgs),
+                },
+                start: parsed_argument(body, "StartingIndex")?.unwrap_or(0),
+                count: parsed_argument(body, "RequestedCount")?.unwrap_or(0),
+            };
+            let result = browse(state, &request, base_url).await?;
+
+            vec![
+                ("Result", result.didl),
+                ("NumberReturned", result.returned.to_string()),
+                ("TotalMatches", result.total.to_string()),
+                ("UpdateID", system_update_id),
+            ]
+        }
+        "GetSearchCapabilities" => vec![("SearchCaps", String::new())],
+        "GetSortCapabilities" => vec![("SortCaps", String::new())],
+        "GetSystemUpdateID" => vec![("Id", system_update_id)],
+        _ => return Err(UpnpError::InvalidAction),
+    };
+
+    Ok(action_response(CONTENT_DIRECTORY, action, &arguments))
+}
+
+/// Files are only served over HTTP, which needs no connection to be set
+/// up, so the only connection is the implicit one with id 0.
+pub fn connection_manager(action: &str, body: &str) -> ActionResult {
+    let arguments = match action {
+        "GetProtocolInfo" => {
+            let source: Vec<String> = SUFFIXES
+                .iter()
+                .map(|x| format!("http-get:*:{}:{}", content_type(x), DLNA_FEATURES))
+                .collect();
+
+            vec![("Source", source.join(",")), ("Sink", String::new())]
+        }
+        "GetCurrentConnectionIDs" => vec![("ConnectionIDs", String::from("0"))],
+        "GetCurrentConnectionInfo" => {
+            if parsed_argument::<i32>(body, "ConnectionID")? != Some(0) {
+                return Err(UpnpError::InvalidConnection);
+            }
+
+            vec![
+                ("RcsID", String::from("-1")),
+                ("AVTransportID", String::from("-1")),
+                ("ProtocolInfo", String::new()),
+                ("PeerConnectionManager", String::new()),
+                ("PeerConnectionID", String::from("-1")),
+                ("Direction", String::from("Output")),
+                ("Status", String::from("OK")),
+            ]
+        }
+        _ => return Err(UpnpError::InvalidAction),
+    };
+
+    Ok(action_response(CONNECTION_MANAGER, action, &arguments))
+}
+
+fn parsed_argument<T: FromStr>(body: &str, name: &str) -> Result<Option<T>, UpnpError> {
+    argument(body, name)
+        .map(|x| x.trim().parse().map_err(|_| UpnpError::InvalidArgs))
+        .transpose()
+}
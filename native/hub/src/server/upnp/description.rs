@@ -0,0 +1,81 @@
+use sha2::{Digest, Sha256};
+use uuid::{Builder, Uuid};
+
+use super::soap::escape;
+
+pub const MEDIA_SERVER: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+pub const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+pub const CONNECTION_MANAGER: &str = "urn:schemas-upnp-org:service:ConnectionManager:1";
+
+pub const CONTENT_DIRECTORY_SCPD: &str = include_str!("ContentDirectory.xml");
+pub const CONNECTION_MANAGER_SCPD: &str = include_str!("ConnectionManager.xml");
+
+/// How the media server presents itself to control points.
+pub struct Device {
+    pub uuid: Uuid,
+    pub friendly_name: String,
+    pub version: String,
+}
+
+impl Device {
+    /// Derives the id of the device from the fingerprint of the server, so
+    /// control points recognize it across restarts.
+    pub fn new(fingerprint: &str, friendly_name: String, version: String) -> Self {
+        let digest = Sha256::digest(fingerprint.as_bytes());
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+
+        Self {
+            uuid: Builder::from_random_bytes(bytes).into_uuid(),
+            friendly_name,
+            version,
+        }
+    }
+
+    /// The `SERVER` header of SSDP messages.
+    pub fn server_header(&self) -> String {
+        format!(
+            "{}/1.0 UPnP/1.0 Rune/{}",
+            std::env::consts::OS,
+            self.version
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let service = |service_type: &str, name: &str| {
+            format!(
+                concat!(
+                    "<service><serviceType>{0}</serviceType>",
+                    "<serviceId>urn:upnp-org:serviceId:{1}</serviceId>",
+                    "<SCPDURL>/upnp/{1}.xml</SCPDURL>",
+                    "<controlURL>/upnp/control/{1}</controlURL>",
+                    "<eventSubURL>/upnp/event/{1}</eventSubURL></service>",
+                ),
+                service_type, name
+            )
+        };
+
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="utf-8"?>"#,
+                r#"<root xmlns="urn:schemas-upnp-org:device-1-0" "#,
+                r#"xmlns:dlna="urn:schemas-dlna-org:device-1-0">"#,
+                "<specVersion><major>1</major><minor>0</minor></specVersion>",
+                "<device><deviceType>{}</deviceType>",
+                "<friendlyName>{}</friendlyName>",
+                "<manufacturer>Rune</manufacturer>",
+                "<modelName>Rune</modelName>",
+                "<modelNumber>{}</modelNumber>",
+                "<UDN>uuid:{}</UDN>",
+                "<dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>",
+                "<serviceList>{}{}</serviceList></device></root>",
+            ),
+            MEDIA_SERVER,
+            escape(&self.friendly_name),
+            escape(&self.version),
+            self.uuid,
+            service(CONTENT_DIRECTORY, "ContentDirectory"),
+            service(CONNECTION_MANAGER, "ConnectionManager"),
+        )
+    }
+}
@@ -0,0 +1,251 @@
+mod content;
+mod control;
+mod description;
+mod soap;
+mod ssdp;
+
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{atomic::AtomicU32, Arc},
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{any, get, post},
+    Router,
+};
+use log::{error, info};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+use ::database::{
+    actions::{
+        cover_art::COVER_TEMP_DIR,
+        file::{get_file_by_id, get_track_range},
+    },
+    connection::{MainDbConnection, RecommendationDbConnection},
+};
+use ::discovery::utils::DeviceInfo;
+use ::transcoding::{SegmentCache, TranscodeFormat, TranscodeOptions, TRANSCODE_CACHE_DIR};
+
+use crate::{
+    server::{
+        http::{file::serve_file, stream::transcode_response},
+        manager::TRANSCODE_CACHE_SIZE,
+    },
+    utils::GlobalParams,
+};
+
+use description::{Device, CONNECTION_MANAGER_SCPD, CONTENT_DIRECTORY_SCPD};
+use soap::{action_name, xml_headers, UpnpError};
+use ssdp::Advertiser;
+
+/// Flags of the `contentFeatures.dlna.org` header: byte seeking is
+/// supported, files are sent as they are and played as streams.
+pub const DLNA_FEATURES: &str =
+    "DLNA.ORG_OP=01;DLNA.ORG_CI=0;DLNA.ORG_FLAGS=01700000000000000000000000000000";
+
+pub struct UpnpState {
+    lib_path: PathBuf,
+    main_db: Arc<MainDbConnection>,
+    recommend_db: Arc<RecommendationDbConnection>,
+    cover_temp_dir: PathBuf,
+    transcode_cache: Arc<SegmentCache>,
+    device: Device,
+    /// Grows every time the library changes, so control points know to
+    /// browse it again
+    system_update_id: Arc<AtomicU32>,
+    addr: SocketAddr,
+}
+
+/// Serves the library as a UPnP media server on `addr` until the main token
+/// is cancelled, and announces it to the network with SSDP.
+///
+/// DLNA renderers and control points can't speak TLS, so the media server
+/// listens on plain HTTP and only serves the library read only. To test the
+/// server, send a search to it and browse the root container:
+///
+/// printf 'M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: "ssdp:discover"\r\nST: ssdp:all\r\n\r\n' | nc -u -w1 127.0.0.1 1900
+///
+/// curl -H 'SOAPACTION: "urn:schemas-upnp-org:service:ContentDirectory:1#Browse"' -d '<ObjectID>0</ObjectID><BrowseFlag>BrowseDirectChildren</BrowseFlag>' http://127.0.0.1:8200/upnp/control/ContentDirectory
+pub async fn serve(
+    addr: SocketAddr,
+    global_params: Arc<GlobalParams>,
+    device_info: DeviceInfo,
+) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen for UPnP clients on {}", addr))?;
+    let addr = listener.local_addr()?;
+
+    let state = Arc::new(UpnpState {
+        lib_path: PathBuf::from(global_params.lib_path.as_str()),
+        main_db: global_params.main_db.clone(),
+        recommend_db: global_params.recommend_db.clone(),
+        cover_temp_dir: COVER_TEMP_DIR.clone(),
+        transcode_cache: Arc::new(SegmentCache::new(
+            TRANSCODE_CACHE_DIR.clone(),
+            TRANSCODE_CACHE_SIZE,
+        )),
+        device: Device::new(
+            &device_info.fingerprint,
+            device_info.alias,
+            device_info.version,
+        ),
+        system_update_id: global_params.library_version.clone(),
+        addr,
+    });
+
+    let app = Router::new()
+        .route("/upnp/description.xml", get(description_handler))
+        .route(
+            "/upnp/ContentDirectory.xml",
+            get(|| async { (xml_headers(), CONTENT_DIRECTORY_SCPD) }),
+        )
+        .route(
+            "/upnp/ConnectionManager.xml",
+            get(|| async { (xml_headers(), CONNECTION_MANAGER_SCPD) }),
+        )
+        .route("/upnp/control/{service}", post(control_handler))
+        .route("/upnp/event/{service}", any(event_handler))
+        .route("/files/{*file_path}", get(file_handler))
+        .route("/upnp/tracks/{id}", get(track_handler))
+        .with_state(state.clone());
+
+    let advertiser = Advertiser::new(&state.device, addr);
+    let cancel_token = global_params.main_token.clone();
+    tokio::spawn(async move {
+        if let Err(e) = advertiser.run(cancel_token).await {
+            error!("Failed to advertise the UPnP media server: {:#?}", e);
+        }
+    });
+
+    info!("UPnP media server listening on {}", addr);
+
+    let cancel_token = global_params.main_token.clone();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { cancel_token.cancelled().await })
+        .await
+        .context("UPnP media server failed")
+}
+
+async fn description_handler(State(state): State<Arc<UpnpState>>) -> impl IntoResponse {
+    (xml_headers(), state.device.description())
+}
+
+async fn control_handler(
+    Path(service): Path<String>,
+    State(state): State<Arc<UpnpState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let Some(action) = headers
+        .get("soapaction")
+        .and_then(|x| x.to_str().ok())
+        .and_then(action_name)
+    else {
+        return UpnpError::InvalidAction.into_response();
+    };
+
+    // Links to files must use the address the control point reached us at
+    let host = headers
+        .get(header::HOST)
+        .and_then(|x| x.to_str().ok())
+        .map(str::to_owned)
+        .unwrap_or_else(|| state.addr.to_string());
+    let base_url = format!("http://{}", host);
+
+    let result = match service.as_str() {
+        "ContentDirectory" => control::content_directory(&state, action, &body, &base_url).await,
+        "ConnectionManager" => control::connection_manager(action, &body),
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    result.unwrap_or_else(IntoResponse::into_response)
+}
+
+/// Nothing is evented, control points see changes of the library through
+/// `GetSystemUpdateID`, but they still expect subscriptions to succeed.
+async fn event_handler(method: Method, headers: HeaderMap) -> Response {
+    match method.as_str() {
+        "SUBSCRIBE" => {
+            let sid = headers
+                .get("sid")
+                .and_then(|x| x.to_str().ok())
+                .map(str::to_owned)
+                .unwrap_or_else(|| format!("uuid:{}", Uuid::new_v4()));
+
+            (
+                StatusCode::OK,
+                [("sid", sid), ("timeout", String::from("Second-1800"))],
+            )
+                .into_response()
+        }
+        "UNSUBSCRIBE" => StatusCode::OK.into_response(),
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+async fn file_handler(
+    Path(file_path): Path<String>,
+    State(state): State<Arc<UpnpState>>,
+    headers: HeaderMap,
+) -> Response {
+    let response = serve_file(&state.lib_path, &state.cover_temp_dir, &file_path, &headers).await;
+
+    with_dlna_headers(response)
+}
+
+/// Serves a track of a CUE sheet, transcoded to MP3 since it only takes a
+/// part of its file.
+async fn track_handler(
+    Path(id): Path<i32>,
+    State(state): State<Arc<UpnpState>>,
+    headers: HeaderMap,
+) -> Response {
+    let file = match get_file_by_id(&state.main_db, id).await {
+        Ok(Some(file)) => file,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            error!("Failed to get track {}: {:#?}", id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let Some(range) = get_track_range(&file) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let path = state.lib_path.join(&file.directory).join(&file.file_name);
+    let response = transcode_response(
+        path,
+        Some(range),
+        TranscodeOptions::new(TranscodeFormat::Mp3),
+        state.transcode_cache.clone(),
+        0.0,
+        &headers,
+    )
+    .await;
+
+    with_dlna_headers(response)
+}
+
+fn with_dlna_headers(mut response: Response) -> Response {
+    if response.status().is_success() {
+        let headers = response.headers_mut();
+        headers.insert(
+            "transfermode.dlna.org",
+            header::HeaderValue::from_static("Streaming"),
+        );
+        headers.insert(
+            "contentfeatures.dlna.org",
+            header::HeaderValue::from_static(DLNA_FEATURES),
+        );
+    }
+
+    response
+}
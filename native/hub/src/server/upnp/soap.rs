@@ -0,0 +1,265 @@
+use std::fmt::Write;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+/// Errors of the UPnP control protocol, sent back in SOAP faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpnpError {
+    InvalidAction = 401,
+    InvalidArgs = 402,
+    ActionFailed = 501,
+    NoSuchObject = 701,
+    InvalidConnection = 706,
+    NoSuchContainer = 710,
+}
+
+impl UpnpError {
+    fn description(&self) -> &'static str {
+        match self {
+            UpnpError::InvalidAction => "Invalid Action",
+            UpnpError::InvalidArgs => "Invalid Args",
+            UpnpError::ActionFailed => "Action Failed",
+            UpnpError::NoSuchObject => "No such object",
+            UpnpError::InvalidConnection => "Invalid connection reference",
+            UpnpError::NoSuchContainer => "No such container",
+        }
+    }
+}
+
+impl From<anyhow::Error> for UpnpError {
+    fn from(e: anyhow::Error) -> Self {
+        log::error!("UPnP action failed: {:#?}", e);
+        UpnpError::ActionFailed
+    }
+}
+
+impl IntoResponse for UpnpError {
+    fn into_response(self) -> Response {
+        let body = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="utf-8"?>"#,
+                r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" "#,
+                r#"s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body>"#,
+                "<s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring>",
+                r#"<detail><UPnPError xmlns="urn:schemas-upnp-org:control-1-0">"#,
+                "<errorCode>{}</errorCode><errorDescription>{}</errorDescription>",
+                "</UPnPError></detail></s:Fault></s:Body></s:Envelope>",
+            ),
+            self as u16,
+            self.description()
+        );
+
+        (StatusCode::INTERNAL_SERVER_ERROR, xml_headers(), body).into_response()
+    }
+}
+
+pub type ActionResult = Result<Response, UpnpError>;
+
+pub fn xml_headers() -> [(header::HeaderName, &'static str); 1] {
+    [(header::CONTENT_TYPE, r#"text/xml; charset="utf-8""#)]
+}
+
+/// Builds the response of an action, its output arguments are escaped here.
+pub fn action_response(service_type: &str, action: &str, arguments: &[(&str, String)]) -> Response {
+    let mut body = String::from(concat!(
+        r#"<?xml version="1.0" encoding="utf-8"?>"#,
+        r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" "#,
+        r#"s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body>"#,
+    ));
+
+    let _ = write!(body, r#"<u:{}Response xmlns:u="{}">"#, action, service_type);
+    for (name, value) in arguments {
+        let _ = write!(body, "<{0}>{1}</{0}>", name, escape(value));
+    }
+    let _ = write!(body, "</u:{}Response></s:Body></s:Envelope>", action);
+
+    (xml_headers(), body).into_response()
+}
+
+/// Reads the action name from a `SOAPACTION` header, such as
+/// `"urn:schemas-upnp-org:service:ContentDirectory:1#Browse"`.
+pub fn action_name(soap_action: &str) -> Option<&str> {
+    soap_action
+        .trim()
+        .trim_matches('"')
+        .rsplit_once('#')
+        .map(|(_, action)| action)
+}
+
+/// Reads an input argument of an action from the request body. Arguments
+/// are plain elements of the action element, so no full XML parser is
+/// needed to find them.
+pub fn argument(body: &str, name: &str) -> Option<String> {
+    let open = format!("<{}", name);
+    let mut from = 0;
+
+    while let Some(found) = body[from..].find(&open) {
+        let start = from + found + open.len();
+        from = start;
+
+        let tag_end = start + body[start..].find('>')?;
+        let attributes = &body[start..tag_end];
+
+        // Skip longer names sharing the prefix, like `<ObjectIDs>`
+        if !(attributes.is_empty()
+            || attributes == "/"
+            || attributes.starts_with(char::is_whitespace))
+        {
+            continue;
+        }
+        if attributes.ends_with('/') {
+            return Some(String::new());
+        }
+
+        let close = format!("</{}>", name);
+        let value_end = tag_end + 1 + body[tag_end + 1..].find(&close)?;
+        return Some(unescape(&body[tag_end + 1..value_end]));
+    }
+
+    None
+}
+
+pub fn required_argument(body: &str, name: &str) -> Result<String, UpnpError> {
+    argument(body, name).ok_or(UpnpError::InvalidArgs)
+}
+
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|x| u32::from_str_radix(x, 16))
+                .or_else(|| entity.strip_prefix('#').map(|x| x.parse()))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+
+        match decoded {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argument() {
+        let body = concat!(
+            r#"<?xml version="1.0"?><s:Envelope><s:Body>"#,
+            r#"<u:Browse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1">"#,
+            "<ObjectIDs>wrong</ObjectIDs>",
+            r#"<ObjectID xmlns:dt="urn:schemas-microsoft-com:datatypes">album/3</ObjectID>"#,
+            "<BrowseFlag>BrowseDirectChildren</BrowseFlag>",
+            "<Filter>dc:title,res@size</Filter>",
+            "<SortCriteria/>",
+            "<SearchCriteria />",
+            "<Title>Rock &amp; Roll &lt;Live&gt;</Title>",
+            "<StartingIndex>0</StartingIndex>",
+            "</u:Browse></s:Body></s:Envelope>",
+        );
+
+        assert_eq!(argument(body, "ObjectID").as_deref(), Some("album/3"));
+        assert_eq!(argument(body, "ObjectIDs").as_deref(), Some("wrong"));
+        assert_eq!(
+            argument(body, "BrowseFlag").as_deref(),
+            Some("BrowseDirectChildren")
+        );
+        assert_eq!(
+            argument(body, "Filter").as_deref(),
+            Some("dc:title,res@size")
+        );
+        assert_eq!(argument(body, "SortCriteria").as_deref(), Some(""));
+        assert_eq!(argument(body, "SearchCriteria").as_deref(), Some(""));
+        assert_eq!(
+            argument(body, "Title").as_deref(),
+            Some("Rock & Roll <Live>")
+        );
+        assert_eq!(argument(body, "RequestedCount"), None);
+        assert_eq!(argument("<Object>x", "Object"), None);
+        assert_eq!(argument("<Object", "Object"), None);
+
+        assert_eq!(required_argument(body, "StartingIndex").as_deref(), Ok("0"));
+        assert_eq!(
+            required_argument(body, "Missing"),
+            Err(UpnpError::InvalidArgs)
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("plain"), "plain");
+        assert_eq!(
+            unescape("&lt;a href=&quot;x&quot;&gt; &amp;&apos;"),
+            "<a href=\"x\"> &'"
+        );
+        assert_eq!(unescape("&#233;t&#xE9; &#x1F3B5;"), "été 🎵");
+
+        // Unknown or broken entities are kept as they are
+        assert_eq!(unescape("AT&T"), "AT&T");
+        assert_eq!(unescape("a &unknown; b"), "a &unknown; b");
+        assert_eq!(unescape("&#xZZ; &#; &#1114112;"), "&#xZZ; &#; &#1114112;");
+        assert_eq!(unescape("&&amp;"), "&&");
+
+        let text = "<Rock & \"Roll\" 'n' more>";
+        assert_eq!(unescape(&escape(text)), text);
+    }
+
+    #[test]
+    fn test_action_name() {
+        assert_eq!(
+            action_name("\"urn:schemas-upnp-org:service:ContentDirectory:1#Browse\""),
+            Some("Browse")
+        );
+        assert_eq!(
+            action_name(" urn:schemas-upnp-org:service:ConnectionManager:1#GetProtocolInfo "),
+            Some("GetProtocolInfo")
+        );
+        assert_eq!(action_name("\"Browse\""), None);
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use rand::Rng;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{net::UdpSocket, sync::Semaphore, time::interval};
+use tokio_util::sync::CancellationToken;
+
+use super::description::{Device, CONNECTION_MANAGER, CONTENT_DIRECTORY, MEDIA_SERVER};
+
+/// Multicast group and port SSDP messages are sent to.
+const SSDP_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+
+/// How long control points may remember the server without hearing from it.
+const MAX_AGE: u64 = 1800;
+/// Announcements are repeated well before control points forget them.
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(MAX_AGE / 3);
+
+/// Control points asking for more time to collect answers get at most this.
+const MAX_SEARCH_DELAY: u64 = 5;
+
+/// Searches answered at once. Answers wait for up to `MAX_SEARCH_DELAY`,
+/// so searches coming in faster than that are dropped past this.
+const MAX_PENDING_ANSWERS: usize = 32;
+
+/// Announces the media server on the network and answers searches for it.
+pub struct Advertiser {
+    /// Notification types, with the unique service name of each
+    targets: Vec<(String, String)>,
+    server: String,
+    /// The address of the HTTP server
+    http_addr: SocketAddr,
+}
+
+impl Advertiser {
+    pub fn new(device: &Device, http_addr: SocketAddr) -> Self {
+        let udn = format!("uuid:{}", device.uuid);
+        let targets = vec![
+            (
+                String::from("upnp:rootdevice"),
+                format!("{}::upnp:rootdevice", udn),
+            ),
+            (udn.clone(), udn.clone()),
+            (
+                MEDIA_SERVER.to_owned(),
+                format!("{}::{}", udn, MEDIA_SERVER),
+            ),
+            (
+                CONTENT_DIRECTORY.to_owned(),
+                format!("{}::{}", udn, CONTENT_DIRECTORY),
+            ),
+            (
+                CONNECTION_MANAGER.to_owned(),
+                format!("{}::{}", udn, CONNECTION_MANAGER),
+            ),
+        ];
+
+        Self {
+            targets,
+            server: device.server_header(),
+            http_addr,
+        }
+    }
+
+    /// Announces the server until `cancel_token` is cancelled, then says
+    /// goodbye so control points drop it right away.
+    pub async fn run(self, cancel_token: Arc<CancellationToken>) -> Result<()> {
+        let socket = Arc::new(self.bind().await?);
+        let advertiser = Arc::new(self);
+        let group = SocketAddr::new(IpAddr::V4(SSDP_GROUP), SSDP_PORT);
+
+        let mut ticker = interval(NOTIFY_INTERVAL);
+        let mut buffer = [0; 2048];
+        let answers = Arc::new(Semaphore::new(MAX_PENDING_ANSWERS));
+
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => break,
+                _ = ticker.tick() => {
+                    let location = advertiser.location(group).await;
+                    for (nt, usn) in &advertiser.targets {
+                        let message = advertiser.notify_message(nt, usn, Some(&location));
+                        if let Err(e) = socket.send_to(message.as_bytes(), group).await {
+                            warn!("Failed to announce the UPnP media server: {}", e);
+                            break;
+                        }
+                    }
+                }
+                received = socket.recv_from(&mut buffer) => {
+                    let (length, peer) = match received {
+                        Ok(x) => x,
+                        Err(e) => {
+                            debug!("Failed to receive SSDP message: {}", e);
+                            continue;
+                        }
+                    };
+
+                    let Ok(permit) = answers.clone().try_acquire_owned() else {
+                        debug!("Dropped SSDP message from {}, too many searches pending", peer);
+                        continue;
+                    };
+
+                    let message = String::from_utf8_lossy(&buffer[..length]).into_owned();
+                    let socket = socket.clone();
+                    let advertiser = advertiser.clone();
+                    tokio::spawn(async move {
+                        advertiser.answer(&socket, &message, peer).await;
+                        drop(permit);
+                    });
+                }
+            }
+        }
+
+        for (nt, usn) in &advertiser.targets {
+            let message = advertiser.notify_message(nt, usn, None);
+            let _ = socket.send_to(message.as_bytes(), group).await;
+        }
+
+        Ok(())
+    }
+
+    /// Binds the SSDP port, shared with other UPnP software on the host,
+    /// and joins the multicast group. Searches sent right to the server
+    /// are still answered if joining fails.
+    async fn bind(&self) -> Result<UdpSocket> {
+        let interface = match self.http_addr.ip() {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+        };
+
+        let socket = tokio::task::spawn_blocking(move || {
+            let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+            socket.set_nonblocking(true)?;
+            socket.set_reuse_address(true)?;
+            #[cfg(not(target_os = "windows"))]
+            socket.set_reuse_port(true)?;
+            socket.bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), SSDP_PORT).into())?;
+
+            socket.set_multicast_ttl_v4(4)?;
+            if !interface.is_unspecified() {
+                socket.set_multicast_if_v4(&interface)?;
+            }
+            if let Err(e) = socket.join_multicast_v4(&SSDP_GROUP, &interface) {
+                warn!("Failed to join the SSDP multicast group: {}", e);
+            }
+
+            Ok::<_, anyhow::Error>(socket)
+        })
+        .await??;
+
+        UdpSocket::from_std(socket.into()).context("Failed to convert to tokio socket")
+    }
+
+    async fn answer(&self, socket: &UdpSocket, message: &str, peer: SocketAddr) {
+        if !message.starts_with("M-SEARCH ") || header(message, "MAN") != Some("\"ssdp:discover\"")
+        {
+            return;
+        }
+        let Some(search_target) = header(message, "ST") else {
+            return;
+        };
+
+        let targets: Vec<&(String, String)> = self
+            .targets
+            .iter()
+            .filter(|(nt, _)| search_target == "ssdp:all" || nt == search_target)
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+
+        // Answers are spread over the time the control point waits for
+        // them, searches sent right to the server have no wait time
+        let max_delay = header(message, "MX")
+            .and_then(|x| x.parse::<u64>().ok())
+            .unwrap_or(0)
+            .min(MAX_SEARCH_DELAY);
+        if max_delay > 0 {
+            let delay = rand::thread_rng().gen_range(0..max_delay * 1000);
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+
+        let location = self.location(peer).await;
+        for (st, usn) in targets {
+            let message = format!(
+                concat!(
+                    "HTTP/1.1 200 OK\r\n",
+                    "CACHE-CONTROL: max-age={}\r\n",
+                    "EXT:\r\n",
+                    "LOCATION: {}\r\n",
+                    "SERVER: {}\r\n",
+                    "ST: {}\r\n",
+                    "USN: {}\r\n",
+                    "\r\n",
+                ),
+                MAX_AGE, location, self.server, st, usn
+            );
+
+            if let Err(e) = socket.send_to(message.as_bytes(), peer).await {
+                debug!("Failed to answer SSDP search from {}: {}", peer, e);
+                return;
+            }
+        }
+    }
+
+    /// Builds an `ssdp:alive` notification, or `ssdp:byebye` without a
+    /// location.
+    fn notify_message(&self, nt: &str, usn: &str, location: Option<&str>) -> String {
+        let mut message = format!(
+            "NOTIFY * HTTP/1.1\r\nHOST: {}:{}\r\nNT: {}\r\nUSN: {}\r\n",
+            SSDP_GROUP, SSDP_PORT, nt, usn
+        );
+
+        match location {
+            Some(location) => message.push_str(&format!(
+                "NTS: ssdp:alive\r\nCACHE-CONTROL: max-age={}\r\nLOCATION: {}\r\nSERVER: {}\r\n",
+                MAX_AGE, location, self.server
+            )),
+            None => message.push_str("NTS: ssdp:byebye\r\n"),
+        }
+
+        message.push_str("\r\n");
+        message
+    }
+
+    /// The URL of the device description, on the address `peer` reaches
+    /// the server at if it listens on every interface.
+    async fn location(&self, peer: SocketAddr) -> String {
+        let ip = match self.http_addr.ip() {
+            ip if ip.is_unspecified() => local_ip(peer).await.unwrap_or(ip),
+            ip => ip,
+        };
+
+        format!(
+            "http://{}/upnp/description.xml",
+            SocketAddr::new(ip, self.http_addr.port())
+        )
+    }
+}
+
+/// Finds the local address packets to `peer` leave from. Connecting a UDP
+/// socket sends nothing, it only picks the route.
+async fn local_ip(peer: SocketAddr) -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.ok()?;
+    socket.connect(peer).await.ok()?;
+
+    socket.local_addr().ok().map(|x| x.ip())
+}
+
+/// Reads a header of an SSDP message, ignoring the case of its name.
+fn header<'a>(message: &'a str, name: &str) -> Option<&'a str> {
+    message.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::timeout;
+
+    use super::*;
+
+    fn advertiser() -> Advertiser {
+        let device = Device::new("fingerprint", "Rune".to_owned(), "1.0.0".to_owned());
+        Advertiser::new(&device, "127.0.0.1:8200".parse().unwrap())
+    }
+
+    fn search(search_target: &str) -> String {
+        format!(
+            concat!(
+                "M-SEARCH * HTTP/1.1\r\n",
+                "HOST: 239.255.255.250:1900\r\n",
+                "MAN: \"ssdp:discover\"\r\n",
+                "ST: {}\r\n",
+                "\r\n",
+            ),
+            search_target
+        )
+    }
+
+    /// Answers a message and collects what the peer received.
+    async fn collect_answers(advertiser: &Advertiser, message: &str) -> Vec<String> {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let peer = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        advertiser
+            .answer(&socket, message, peer.local_addr().unwrap())
+            .await;
+
+        let mut answers = Vec::new();
+        let mut buffer = [0; 2048];
+        while let Ok(Ok(length)) = timeout(Duration::from_millis(100), peer.recv(&mut buffer)).await
+        {
+            answers.push(String::from_utf8_lossy(&buffer[..length]).into_owned());
+        }
+
+        answers
+    }
+
+    #[test]
+    fn test_header() {
+        let message = concat!(
+            "M-SEARCH * HTTP/1.1\r\n",
+            "Host: 239.255.255.250:1900\r\n",
+            "man: \"ssdp:discover\"\r\n",
+            "MX:  3 \r\n",
+            "ST: urn:schemas-upnp-org:device:MediaServer:1\r\n",
+            "\r\n",
+        );
+
+        assert_eq!(header(message, "HOST"), Some("239.255.255.250:1900"));
+        assert_eq!(header(message, "MAN"), Some("\"ssdp:discover\""));
+        assert_eq!(header(message, "mx"), Some("3"));
+        assert_eq!(header(message, "ST"), Some(MEDIA_SERVER));
+        assert_eq!(header(message, "USN"), None);
+        // The first line is the request line, not a header
+        assert_eq!(header("ST: ssdp:all\r\n\r\n", "ST"), None);
+    }
+
+    #[tokio::test]
+    async fn test_answer() {
+        let advertiser = advertiser();
+        let usn = format!(
+            "uuid:{}::{}",
+            Device::new("fingerprint", String::new(), String::new()).uuid,
+            MEDIA_SERVER
+        );
+
+        let answers = collect_answers(&advertiser, &search("ssdp:all")).await;
+        assert_eq!(answers.len(), advertiser.targets.len());
+        for (answer, (st, usn)) in answers.iter().zip(&advertiser.targets) {
+            assert!(answer.starts_with("HTTP/1.1 200 OK\r\n"), "{}", answer);
+            assert!(answer.ends_with("\r\n\r\n"), "{}", answer);
+            assert_eq!(header(answer, "ST"), Some(st.as_str()));
+            assert_eq!(header(answer, "USN"), Some(usn.as_str()));
+            assert_eq!(
+                header(answer, "LOCATION"),
+                Some("http://127.0.0.1:8200/upnp/description.xml")
+            );
+            assert_eq!(header(answer, "CACHE-CONTROL"), Some("max-age=1800"));
+        }
+
+        let answers = collect_answers(&advertiser, &search(MEDIA_SERVER)).await;
+        assert_eq!(answers.len(), 1);
+        assert_eq!(header(&answers[0], "ST"), Some(MEDIA_SERVER));
+        assert_eq!(header(&answers[0], "USN"), Some(usn.as_str()));
+
+        // Searches for other devices and other messages are not answered
+        for message in [
+            search("urn:schemas-upnp-org:device:MediaRenderer:1"),
+            search("ssdp:all").replace("ssdp:discover", "ssdp:other"),
+            search("ssdp:all").replace("M-SEARCH", "NOTIFY"),
+            String::from("M-SEARCH * HTTP/1.1\r\nMAN: \"ssdp:discover\"\r\n\r\n"),
+        ] {
+            assert!(
+                collect_answers(&advertiser, &message).await.is_empty(),
+                "{}",
+                message
+            );
+        }
+    }
+}
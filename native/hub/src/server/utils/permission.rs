@@ -1,7 +1,7 @@
 use anyhow::Result;
 use colored::*;
 
-use discovery::server::{UserStatus, UserSummary};
+use discovery::server::{UserRole, UserStatus, UserSummary};
 
 pub fn print_permission_table(users: &[UserSummary]) {
     for (i, user) in users.iter().enumerate() {
@@ -16,10 +16,16 @@ pub fn print_permission_table(users: &[UserSummary]) {
             UserStatus::Pending => "Pending".yellow(),
             UserStatus::Blocked => "Blocked".red(),
         };
+        let role = format!("{:?}", user.role).white();
+        let max_bitrate = match user.max_bitrate {
+            Some(bitrate) => format!("{} kbps", bitrate),
+            None => "Unlimited".to_owned(),
+        }
+        .white();
 
         println!(
-            "{} {} {} {} {}",
-            index_str, alias, device_info, fingerprint, status
+            "{} {} {} {} {} {} {}",
+            index_str, alias, device_info, fingerprint, status, role, max_bitrate
         );
     }
 }
@@ -39,3 +45,23 @@ pub fn parse_status(input: &str) -> Result<UserStatus> {
         _ => anyhow::bail!("Invalid status: {}", input),
     }
 }
+
+pub fn parse_role(input: &str) -> Result<UserRole> {
+    match input.to_lowercase().as_str() {
+        "viewer" => Ok(UserRole::Viewer),
+        "controller" => Ok(UserRole::Controller),
+        "editor" => Ok(UserRole::Editor),
+        "admin" => Ok(UserRole::Admin),
+        _ => anyhow::bail!("Invalid role: {}", input),
+    }
+}
+
+pub fn parse_bitrate(input: &str) -> Result<Option<u32>> {
+    match input.to_lowercase().as_str() {
+        "none" | "unlimited" => Ok(None),
+        x => match x.parse() {
+            Ok(0) | Err(_) => anyhow::bail!("Invalid bitrate: {}", input),
+            Ok(bitrate) => Ok(Some(bitrate)),
+        },
+    }
+}
@@ -4,7 +4,11 @@ use crate::broadcastable;
 use crate::messages::*;
 use crate::utils::RinfRustSignal;
 
-broadcastable!(ScanAudioLibraryProgress, ScanAudioLibraryResponse);
+broadcastable!(
+    ScanAudioLibraryProgress,
+    ScanAudioLibraryResponse,
+    LibraryFilesChanged
+);
 broadcastable!(SetMediaLibraryPathResponse);
 broadcastable!(AnalyzeAudioLibraryProgress, AnalyzeAudioLibraryResponse);
 broadcastable!(
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use log::error;
+use tokio::sync::Mutex;
+
+use ::database::actions::history::{insert_play_event, PlayEvent};
+use ::database::connection::MainDbConnection;
+use ::playback::player::{PlaybackState, PlayerStatus, PlayingItem};
+
+/// Position changes larger than this between two status updates are seeks
+/// and do not count as listened time.
+const MAX_PROGRESS_STEP: Duration = Duration::from_secs(2);
+
+/// Plays shorter than this are not recorded.
+const MIN_LISTENED: Duration = Duration::from_secs(1);
+
+/// Tracks whose source is remembered, the sources of the tracks queued
+/// earliest are dropped first.
+const MAX_SOURCES: usize = 10_000;
+
+#[derive(Debug)]
+struct CurrentPlay {
+    media_file_id: i32,
+    started_at: DateTime<Utc>,
+    duration: f64,
+    last_position: Duration,
+    listened: Duration,
+}
+
+impl CurrentPlay {
+    fn new(media_file_id: i32, duration: f64, position: Duration) -> Self {
+        CurrentPlay {
+            media_file_id,
+            started_at: Utc::now(),
+            duration,
+            last_position: position,
+            listened: Duration::ZERO,
+        }
+    }
+
+    fn into_event(self, source: Option<String>) -> Option<PlayEvent> {
+        if self.listened < MIN_LISTENED {
+            return None;
+        }
+
+        let listened_duration = self.listened.as_secs_f64();
+        Some(PlayEvent {
+            media_file_id: self.media_file_id,
+            started_at: self.started_at,
+            listened_duration,
+            completion_ratio: if self.duration > 0.0 {
+                listened_duration / self.duration
+            } else {
+                0.0
+            },
+            source,
+        })
+    }
+}
+
+/// Follows the player status and turns it into play events, together with
+/// the mix queries each track was queued from.
+#[derive(Debug, Default)]
+pub struct PlayHistoryTracker {
+    /// Source of each track, with the `set_source` call it came from
+    sources: HashMap<i32, (u64, String)>,
+    generation: u64,
+    current: Option<CurrentPlay>,
+}
+
+impl PlayHistoryTracker {
+    /// Remembers where the tracks were queued from, replacing earlier
+    /// sources of the same tracks.
+    pub fn set_source(&mut self, media_file_ids: impl IntoIterator<Item = i32>, source: &str) {
+        self.generation += 1;
+        for id in media_file_ids {
+            self.sources
+                .insert(id, (self.generation, source.to_string()));
+        }
+
+        while self.sources.len() > MAX_SOURCES {
+            let Some(oldest) = self.sources.values().map(|x| x.0).min() else {
+                break;
+            };
+            if oldest == self.generation {
+                break;
+            }
+            self.sources.retain(|_, x| x.0 != oldest);
+        }
+    }
+
+    /// Ends the play in progress, returns it if it is long enough to be
+    /// recorded.
+    pub fn finish(&mut self) -> Option<PlayEvent> {
+        let current = self.current.take()?;
+        let source = self
+            .sources
+            .get(&current.media_file_id)
+            .map(|x| x.1.clone());
+        current.into_event(source)
+    }
+
+    /// Feeds a status update, returns the play that ended with it.
+    ///
+    /// `duration` is the duration of the track in the status, in seconds.
+    pub fn update(&mut self, status: &PlayerStatus, duration: f64) -> Option<PlayEvent> {
+        let media_file_id = match (&status.item, &status.state) {
+            (Some(PlayingItem::InLibrary(id)), PlaybackState::Playing | PlaybackState::Paused) => {
+                *id
+            }
+            _ => return self.finish(),
+        };
+
+        let position = status.position;
+        let mut finished = None;
+
+        if let Some(current) = &self.current {
+            let changed = current.media_file_id != media_file_id;
+            // Jumping back to the start after reaching the end is a repeat
+            let repeated = position < current.last_position
+                && current.last_position + MAX_PROGRESS_STEP
+                    >= Duration::from_secs_f64(current.duration.max(0.0));
+
+            if changed || repeated {
+                finished = self.finish();
+            }
+        }
+
+        match &mut self.current {
+            Some(current) => {
+                if status.state == PlaybackState::Playing && position > current.last_position {
+                    let step = position - current.last_position;
+                    if step <= MAX_PROGRESS_STEP {
+                        current.listened += step;
+                    }
+                }
+                current.last_position = position;
+            }
+            None => {
+                if status.state == PlaybackState::Playing {
+                    self.current = Some(CurrentPlay::new(media_file_id, duration, position));
+                }
+            }
+        }
+
+        finished
+    }
+}
+
+/// Records the play in progress, so the track playing when the library is
+/// closed or the server stops is not lost.
+pub async fn flush_play_history(
+    main_db: &MainDbConnection,
+    play_history: &Mutex<PlayHistoryTracker>,
+) {
+    let Some(event) = play_history.lock().await.finish() else {
+        return;
+    };
+
+    if let Err(e) = insert_play_event(main_db, event)
+        .await
+        .with_context(|| "Unable to record play event")
+    {
+        error!("{:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(id: i32, state: PlaybackState, position: f64) -> PlayerStatus {
+        PlayerStatus {
+            item: Some(PlayingItem::InLibrary(id)),
+            index: Some(0),
+            path: None,
+            position: Duration::from_secs_f64(position),
+            state,
+            playlist: vec![],
+            playback_mode: 0.into(),
+            ready: true,
+            volume: 1.0,
+        }
+    }
+
+    fn play(tracker: &mut PlayHistoryTracker, id: i32, from: f64, to: f64) -> Vec<PlayEvent> {
+        let mut position = from;
+        let mut events = vec![];
+        while position <= to {
+            events.extend(tracker.update(&status(id, PlaybackState::Playing, position), 100.0));
+            position += 0.5;
+        }
+        events
+    }
+
+    #[test]
+    fn test_short_plays_are_not_recorded() {
+        let mut tracker = PlayHistoryTracker::default();
+
+        assert!(play(&mut tracker, 1, 0.0, 0.5).is_empty());
+        assert!(play(&mut tracker, 2, 0.0, 1.0).is_empty());
+
+        let event = tracker.finish().unwrap();
+        assert_eq!(event.media_file_id, 2);
+        assert_eq!(event.listened_duration, 1.0);
+        assert_eq!(event.completion_ratio, 0.01);
+        assert!(tracker.finish().is_none());
+    }
+
+    #[test]
+    fn test_plays_end_with_the_track() {
+        let mut tracker = PlayHistoryTracker::default();
+
+        assert!(play(&mut tracker, 1, 0.0, 10.0).is_empty());
+        let events = play(&mut tracker, 2, 0.0, 0.0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].media_file_id, 1);
+        assert_eq!(events[0].listened_duration, 10.0);
+
+        // Stopping ends the play as well
+        play(&mut tracker, 2, 0.5, 5.0);
+        let event = tracker
+            .update(&status(2, PlaybackState::Stopped, 0.0), 100.0)
+            .unwrap();
+        assert_eq!(event.media_file_id, 2);
+        assert_eq!(event.listened_duration, 5.0);
+    }
+
+    #[test]
+    fn test_seeks_and_pauses_do_not_count() {
+        let mut tracker = PlayHistoryTracker::default();
+
+        play(&mut tracker, 1, 0.0, 2.0);
+        // Seeking forward
+        play(&mut tracker, 1, 50.0, 52.0);
+        // Paused time does not count
+        tracker.update(&status(1, PlaybackState::Paused, 53.0), 100.0);
+        tracker.update(&status(1, PlaybackState::Playing, 53.0), 100.0);
+        // Seeking back within the track is not a repeat
+        play(&mut tracker, 1, 10.0, 11.0);
+
+        let event = tracker.finish().unwrap();
+        assert_eq!(event.listened_duration, 5.0);
+    }
+
+    #[test]
+    fn test_repeats_are_separate_plays() {
+        let mut tracker = PlayHistoryTracker::default();
+
+        play(&mut tracker, 1, 90.0, 99.5);
+        let events = play(&mut tracker, 1, 0.0, 3.0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].listened_duration, 9.5);
+
+        let event = tracker.finish().unwrap();
+        assert_eq!(event.listened_duration, 3.0);
+    }
+
+    #[test]
+    fn test_sources() {
+        let mut tracker = PlayHistoryTracker::default();
+
+        tracker.set_source([1, 2], "lib::album(1)");
+        tracker.set_source([2], "lib::artist(3)");
+        play(&mut tracker, 1, 0.0, 2.0);
+        let events = play(&mut tracker, 2, 0.0, 2.0);
+        assert_eq!(events[0].source.as_deref(), Some("lib::album(1)"));
+        assert_eq!(
+            tracker.finish().unwrap().source.as_deref(),
+            Some("lib::artist(3)")
+        );
+
+        // The sources of the oldest queues are dropped first
+        tracker.set_source(0..MAX_SOURCES as i32, "lib::all(true)");
+        tracker.set_source([-1], "lib::track(-1)");
+        assert_eq!(tracker.sources.len(), 1);
+        assert!(tracker.sources.contains_key(&-1));
+    }
+}
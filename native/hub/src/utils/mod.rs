@@ -1,10 +1,13 @@
 pub mod broadcastable;
+pub mod history;
 pub mod player;
+pub mod scrobble_store;
+pub mod watcher;
 
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, OnceLock};
+use std::sync::{atomic::AtomicU32, Arc, OnceLock};
 
 use anyhow::{Context, Result};
 use dunce::canonicalize;
@@ -15,8 +18,10 @@ use tokio_util::sync::CancellationToken;
 
 use ::database::{
     actions::{
-        collection::CollectionQueryType, cover_art::bake_cover_art_by_media_files,
-        metadata::MetadataSummary, mixes::query_mix_media_files,
+        collection::CollectionQueryType,
+        cover_art::bake_cover_art_by_media_files,
+        metadata::MetadataSummary,
+        mixes::{query_mix_media_files, GroupedQuery},
     },
     connection::{
         check_library_state, connect_main_db, connect_recommendation_db, create_redirect,
@@ -28,11 +33,13 @@ use ::database::{
 use ::discovery::{client::CertValidator, protocol::DiscoveryService, server::PermissionManager};
 use ::playback::{
     player::{Playable, PlayingItem},
+    range::TrackRange,
     sfx_player::SfxPlayer,
 };
 use ::scrobbling::manager::ScrobblingManager;
 
 use crate::backends::{local::local_player_loop, remote::server_player_loop};
+use crate::utils::history::PlayHistoryTracker;
 use crate::messages::*;
 use crate::server::ServerManager;
 
@@ -85,7 +92,11 @@ pub struct GlobalParams {
     pub recommend_db: Arc<RecommendationDbConnection>,
     pub main_token: Arc<CancellationToken>,
     pub task_tokens: Arc<Mutex<TaskTokens>>,
+    /// A number that grows every time a scan or the library watcher changed
+    /// the library
+    pub library_version: Arc<AtomicU32>,
     pub player: Arc<Mutex<dyn Playable>>,
+    pub play_history: Arc<Mutex<PlayHistoryTracker>>,
     pub sfx_player: Arc<Mutex<SfxPlayer>>,
     pub scrobbler: Arc<Mutex<dyn ScrobblingServiceManager>>,
     pub broadcaster: Arc<dyn Broadcaster>,
@@ -324,9 +335,9 @@ pub async fn query_cover_arts(
         &recommend_db,
         queries
             .into_iter()
-            .map(|q| (q.operator, q.parameter))
-            .chain([("filter::with_cover_art".to_string(), "true".to_string())])
-            .collect(),
+            .map(GroupedQuery::from)
+            .chain([GroupedQuery::new("filter::with_cover_art", "true", 0)])
+            .collect::<Vec<_>>(),
         0,
         match n {
             Some(n) => {
@@ -440,7 +451,7 @@ pub async fn parse_media_files(
 pub fn files_to_playback_request(
     lib_path: &String,
     files: &[MediaFileHandle],
-) -> Vec<(PlayingItem, PathBuf)> {
+) -> Vec<(PlayingItem, PathBuf, Option<TrackRange>)> {
     files
         .iter()
         .filter_map(|file| {
@@ -453,7 +464,7 @@ pub fn files_to_playback_request(
             };
 
             match canonicalize(&file_path) {
-                Ok(canonical_path) => Some((file.item.clone(), canonical_path)),
+                Ok(canonical_path) => Some((file.item.clone(), canonical_path, file.range)),
                 Err(_) => None,
             }
         })
@@ -11,6 +11,7 @@ use tokio::sync::{Mutex, RwLock};
 use tokio::task;
 
 use ::scrobbling::manager::ScrobblingServiceManager;
+use ::database::actions::history::insert_play_event;
 use ::database::actions::logging::insert_log;
 use ::database::actions::playback_queue::replace_playback_queue;
 use ::database::actions::stats::increase_played_through;
@@ -30,6 +31,7 @@ use ::playback::MediaPosition;
 use ::scrobbling::ScrobblingTrack;
 
 use crate::messages::*;
+use crate::utils::history::PlayHistoryTracker;
 use crate::utils::Broadcaster;
 
 pub fn metadata_summary_to_scrobbling_track(
@@ -54,6 +56,7 @@ pub async fn initialize_local_player(
     lib_path: Arc<String>,
     main_db: Arc<MainDbConnection>,
     player: Arc<Mutex<dyn Playable>>,
+    play_history: Arc<Mutex<PlayHistoryTracker>>,
     scrobbler: Arc<Mutex<dyn ScrobblingServiceManager>>,
     broadcaster: Arc<dyn Broadcaster>,
     cert_validator: Arc<RwLock<CertValidator>>,
@@ -82,6 +85,7 @@ pub async fn initialize_local_player(
     let dispatcher_for_played_through = Arc::clone(&dispatcher);
 
     let scrobber_for_played_through = Arc::clone(&scrobbler);
+    let player_for_playlist = Arc::clone(&player);
 
     let scrobber_error_receiver = scrobbler.lock().await.subscribe_error();
     let scrobber_status_receiver = scrobbler.lock().await.subscribe_login_status();
@@ -191,6 +195,16 @@ pub async fn initialize_local_player(
                 }
             };
 
+            let finished_play = play_history.lock().await.update(&status, meta.duration);
+            if let Some(event) = finished_play {
+                if let Err(e) = insert_play_event(&main_db, event)
+                    .await
+                    .with_context(|| "Unable to record play event")
+                {
+                    error!("{:?}", e);
+                }
+            }
+
             let position = status.position;
             let duration = meta.duration;
             let progress_percentage = if duration == 0. {
@@ -233,9 +247,30 @@ pub async fn initialize_local_player(
     task::spawn(async move {
         let main_db = Arc::clone(&main_db_for_playlist);
         let broadcaster = Arc::clone(&broadcaster_for_playlist);
+        let player = Arc::clone(&player_for_playlist);
 
         while let Ok(playlist) = playlist_receiver.recv().await {
             send_playlist_update(&main_db, &playlist, &*broadcaster).await;
+            match PlayingItemActionDispatcher::new()
+                .get_replay_gain(&main_db, &playlist.items)
+                .await
+            {
+                Ok(gains) => player
+                    .lock()
+                    .await
+                    .update_replay_gain(gains.into_iter().collect()),
+                Err(e) => error!("Failed to resolve replay gain: {:#?}", e),
+            };
+            match PlayingItemActionDispatcher::new()
+                .get_silence_profile(&main_db, &playlist.items)
+                .await
+            {
+                Ok(profiles) => player
+                    .lock()
+                    .await
+                    .update_silence_profile(profiles.into_iter().collect()),
+                Err(e) => error!("Failed to resolve silence profile: {:#?}", e),
+            };
             match replace_playback_queue(&main_db, extract_in_library_ids(playlist.items)).await {
                 Ok(_) => {}
                 Err(e) => error!("Failed to update playback queue record: {:#?}", e),
@@ -408,6 +443,8 @@ pub async fn initialize_local_player(
                         discovery::server::UserStatus::Pending => 1,
                         discovery::server::UserStatus::Blocked => 2,
                     },
+                    role: ClientRole::from(user.role).into(),
+                    max_bitrate: user.max_bitrate,
                 }),
             });
         }
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use ::database::actions::scrobble_queue::{
+    enqueue_scrobbles, list_pending_scrobbles, mark_scrobbles_delivered, mark_scrobbles_failed,
+    NewScrobble,
+};
+use ::database::connection::MainDbConnection;
+use ::scrobbling::manager::ScrobblingService;
+use ::scrobbling::queue::{PendingScrobble, ScrobbleStore, MAX_DELIVERY_ATTEMPTS};
+use ::scrobbling::ScrobblingTrack;
+
+/// Keeps pending scrobbles in the library database, so scrobbles made
+/// offline are delivered after a restart.
+pub struct DatabaseScrobbleStore {
+    main_db: Arc<MainDbConnection>,
+}
+
+impl DatabaseScrobbleStore {
+    pub fn new(main_db: Arc<MainDbConnection>) -> Self {
+        DatabaseScrobbleStore { main_db }
+    }
+}
+
+#[async_trait]
+impl ScrobbleStore for DatabaseScrobbleStore {
+    async fn push(&self, services: &[ScrobblingService], track: &ScrobblingTrack) -> Result<()> {
+        let services: Vec<String> = services.iter().map(|x| x.to_string()).collect();
+
+        enqueue_scrobbles(
+            &self.main_db,
+            &services,
+            NewScrobble {
+                artist: track.artist.clone(),
+                track: track.track.clone(),
+                album: track.album.clone(),
+                album_artist: track.album_artist.clone(),
+                duration: track.duration.map(|x| x as i32),
+                timestamp: track.timestamp_or_now() as i64,
+            },
+        )
+        .await
+    }
+
+    async fn pending(
+        &self,
+        service: ScrobblingService,
+        limit: usize,
+    ) -> Result<Vec<PendingScrobble>> {
+        let scrobbles = list_pending_scrobbles(
+            &self.main_db,
+            &service.to_string(),
+            MAX_DELIVERY_ATTEMPTS as i32,
+            limit as u64,
+        )
+        .await?;
+
+        Ok(scrobbles
+            .into_iter()
+            .map(|x| PendingScrobble {
+                id: x.id,
+                track: ScrobblingTrack {
+                    artist: x.artist,
+                    track: x.track,
+                    album: x.album,
+                    album_artist: x.album_artist,
+                    duration: x.duration.map(|x| x as u32),
+                    timestamp: Some(x.timestamp as u64),
+                },
+            })
+            .collect())
+    }
+
+    async fn mark_delivered(&self, ids: &[i32]) -> Result<()> {
+        mark_scrobbles_delivered(&self.main_db, ids).await
+    }
+
+    async fn mark_failed(&self, ids: &[i32], error: &str) -> Result<()> {
+        mark_scrobbles_failed(&self.main_db, ids, error).await
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{debug, error, info, warn};
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use ::database::actions::logging::{insert_log, LogLevel};
+use ::database::actions::metadata::sync_library_paths;
+use ::database::connection::MainDbConnection;
+
+use crate::messages::*;
+use crate::utils::{Broadcaster, TaskTokens};
+
+/// Time without new events before the touched paths are synced. Downloads
+/// and copies emit a burst of events, so the files are only read once they
+/// settled down.
+const DEBOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Delay before retrying while a full library scan is running.
+const SCAN_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+fn is_relevant_event(event: &Event) -> bool {
+    match event.kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        _ => false,
+    }
+}
+
+/// The database lives in `.rune` below the library root, writing to it must
+/// not trigger another sync.
+fn is_ignored_path(lib_path: &Path, path: &Path) -> bool {
+    match path.strip_prefix(lib_path) {
+        Ok(rel_path) => rel_path.starts_with(".rune"),
+        Err(_) => true,
+    }
+}
+
+async fn sync_touched_paths(
+    lib_path: &Path,
+    main_db: &Arc<MainDbConnection>,
+    library_version: &AtomicU32,
+    broadcaster: &Arc<dyn Broadcaster>,
+    cancel_token: &CancellationToken,
+    paths: Vec<PathBuf>,
+) -> Result<()> {
+    info!("Syncing {} touched paths", paths.len());
+
+    let main_db_clone = Arc::clone(main_db);
+    let lib_path_clone = lib_path.to_path_buf();
+    let token = cancel_token.clone();
+
+    // Reading the files blocks, so the sync runs on a blocking thread driven
+    // by the runtime the watcher already lives in
+    let handle = Handle::current();
+    let summary = task::spawn_blocking(move || {
+        handle.block_on(async move {
+            sync_library_paths(&main_db_clone, &lib_path_clone, &paths, Some(&token)).await
+        })
+    })
+    .await??;
+
+    info!(
+        "Library watcher synced {} files, relocated {}, removed {}",
+        summary.updated, summary.relocated, summary.removed
+    );
+
+    if summary.updated > 0 || summary.relocated > 0 || summary.removed > 0 {
+        library_version.fetch_add(1, Ordering::Relaxed);
+    }
+    if summary.updated > 0 || summary.removed > 0 {
+        broadcaster.broadcast(&LibraryFilesChanged {
+            path: lib_path.to_string_lossy().into_owned(),
+            updated: summary.updated as i32,
+            relocated: summary.relocated as i32,
+            removed: summary.removed as i32,
+        });
+    }
+
+    Ok(())
+}
+
+/// Watches the library root and syncs the files that were created, modified,
+/// moved or deleted outside of Rune. Runs until `cancel_token` is cancelled,
+/// which happens when the library is closed.
+pub async fn initialize_library_watcher(
+    lib_path: Arc<String>,
+    main_db: Arc<MainDbConnection>,
+    task_tokens: Arc<Mutex<TaskTokens>>,
+    library_version: Arc<AtomicU32>,
+    broadcaster: Arc<dyn Broadcaster>,
+    cancel_token: Arc<CancellationToken>,
+) -> Result<()> {
+    let root_path = PathBuf::from(&*lib_path);
+    let (event_sender, mut event_receiver) = mpsc::unbounded_channel::<notify::Result<Event>>();
+
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(move |event| {
+        let _ = event_sender.send(event);
+    })
+    .with_context(|| "Failed to create the library watcher")?;
+
+    watcher
+        .watch(&root_path, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch the library: {}", root_path.display()))?;
+
+    info!("Watching library: {}", root_path.display());
+
+    let mut touched_paths: HashSet<PathBuf> = HashSet::new();
+    // Set while a full library scan is running, new events don't delay it
+    let mut retry_at: Option<Instant> = None;
+
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => {
+                info!("Library watcher stopped: {}", root_path.display());
+                break;
+            }
+            event = event_receiver.recv() => {
+                match event {
+                    Some(Ok(event)) => {
+                        if !is_relevant_event(&event) {
+                            continue;
+                        }
+
+                        for path in event.paths {
+                            if !is_ignored_path(&root_path, &path) {
+                                debug!("Library path touched: {}", path.display());
+                                touched_paths.insert(path);
+                            }
+                        }
+                    }
+                    Some(Err(e)) => warn!("Library watcher error: {:?}", e),
+                    None => break,
+                }
+            }
+            _ = tokio::time::sleep_until(
+                retry_at.unwrap_or_else(|| Instant::now() + DEBOUNCE_INTERVAL)
+            ), if !touched_paths.is_empty() => {
+                retry_at = None;
+
+                // Wait for a running scan instead of racing it
+                let is_scanning = task_tokens
+                    .lock()
+                    .await
+                    .scan_token
+                    .as_ref()
+                    .map(|token| !token.is_cancelled())
+                    .unwrap_or(false);
+                if is_scanning {
+                    retry_at = Some(Instant::now() + SCAN_RETRY_INTERVAL);
+                    continue;
+                }
+
+                let paths: Vec<PathBuf> = touched_paths.drain().collect();
+                if let Err(e) = sync_touched_paths(
+                    &root_path,
+                    &main_db,
+                    &library_version,
+                    &broadcaster,
+                    &cancel_token,
+                    paths,
+                )
+                .await
+                {
+                    error!("Failed to sync touched paths: {:?}", e);
+                    // The watcher keeps running when the log can not be written
+                    if let Err(e) = insert_log(
+                        &*main_db,
+                        LogLevel::Error,
+                        "utils::watcher::initialize_library_watcher".to_string(),
+                        format!("{:#?}", e),
+                    )
+                    .await
+                    {
+                        error!("Failed to log library watcher error: {:#?}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,31 @@
 use proc_macro::TokenStream;
 use quote::quote;
 
+/// What a remote client must be allowed to do to send a request, mirrors
+/// `discovery::server::Capability`.
+enum Capability {
+    Browse,
+    ControlPlayback,
+    EditLibrary,
+    Admin,
+}
+
+impl Capability {
+    fn name(&self) -> &'static str {
+        match self {
+            Capability::Browse => "Browse",
+            Capability::ControlPlayback => "ControlPlayback",
+            Capability::EditLibrary => "EditLibrary",
+            Capability::Admin => "Admin",
+        }
+    }
+}
+
 struct RequestResponse {
     request: String,
     response: Option<String>,
     local_only: bool,
+    capability: Capability,
 }
 
 #[proc_macro]
@@ -15,438 +36,626 @@ pub fn define_request_types(_input: TokenStream) -> TokenStream {
             request: "TestLibraryInitializedRequest".to_string(),
             response: Some("TestLibraryInitializedResponse".to_string()),
             local_only: true,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "CloseLibraryRequest".to_string(),
             response: Some("CloseLibraryResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "CancelTaskRequest".to_string(),
             response: Some("CancelTaskResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "ScanAudioLibraryRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "AnalyzeAudioLibraryRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::EditLibrary,
+        },
+        RequestResponse {
+            request: "FindDuplicateFilesRequest".to_string(),
+            response: Some("FindDuplicateFilesResponse".to_string()),
+            local_only: false,
+            capability: Capability::EditLibrary,
+        },
+        RequestResponse {
+            request: "MergeDuplicateFilesRequest".to_string(),
+            response: Some("MergeDuplicateFilesResponse".to_string()),
+            local_only: false,
+            capability: Capability::EditLibrary,
         },
         // Playback
         RequestResponse {
             request: "VolumeRequest".to_string(),
             response: Some("VolumeResponse".to_string()),
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "LoadRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "PlayRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "PauseRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "NextRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "PreviousRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "SwitchRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "SeekRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "RemoveRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "SetPlaybackModeRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "MovePlaylistItemRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "SetRealtimeFftEnabledRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         RequestResponse {
             request: "SetAdaptiveSwitchingEnabledRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::ControlPlayback,
+        },
+        RequestResponse {
+            request: "SetNormalizationModeRequest".to_string(),
+            response: None,
+            local_only: false,
+            capability: Capability::ControlPlayback,
+        },
+        RequestResponse {
+            request: "SetCrossfadeRequest".to_string(),
+            response: None,
+            local_only: false,
+            capability: Capability::ControlPlayback,
+        },
+        // Equalizer
+        RequestResponse {
+            request: "SetEqualizerRequest".to_string(),
+            response: None,
+            local_only: false,
+            capability: Capability::ControlPlayback,
+        },
+        RequestResponse {
+            request: "FetchAllEqualizerPresetsRequest".to_string(),
+            response: Some("FetchAllEqualizerPresetsResponse".to_string()),
+            local_only: false,
+            capability: Capability::Browse,
+        },
+        RequestResponse {
+            request: "CreateEqualizerPresetRequest".to_string(),
+            response: Some("CreateEqualizerPresetResponse".to_string()),
+            local_only: false,
+            capability: Capability::EditLibrary,
+        },
+        RequestResponse {
+            request: "UpdateEqualizerPresetRequest".to_string(),
+            response: Some("UpdateEqualizerPresetResponse".to_string()),
+            local_only: false,
+            capability: Capability::EditLibrary,
+        },
+        RequestResponse {
+            request: "RemoveEqualizerPresetRequest".to_string(),
+            response: Some("RemoveEqualizerPresetResponse".to_string()),
+            local_only: false,
+            capability: Capability::EditLibrary,
         },
         // SFX
         RequestResponse {
             request: "SfxPlayRequest".to_string(),
             response: None,
             local_only: true,
+            capability: Capability::ControlPlayback,
         },
         // Analyze
         RequestResponse {
             request: "IfAnalyzeExistsRequest".to_string(),
             response: Some("IfAnalyzeExistsResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "GetAnalyzeCountRequest".to_string(),
             response: Some("GetAnalyzeCountResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         // Media File
         RequestResponse {
             request: "FetchMediaFilesRequest".to_string(),
             response: Some("FetchMediaFilesResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "FetchMediaFileByIdsRequest".to_string(),
             response: Some("FetchMediaFileByIdsResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "FetchParsedMediaFileRequest".to_string(),
             response: Some("FetchParsedMediaFileResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "SearchMediaFileSummaryRequest".to_string(),
             response: Some("SearchMediaFileSummaryResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
+        },
+        RequestResponse {
+            request: "EditFileTagsRequest".to_string(),
+            response: Some("EditFileTagsResponse".to_string()),
+            local_only: false,
+            capability: Capability::EditLibrary,
+        },
+        RequestResponse {
+            request: "UndoTagEditRequest".to_string(),
+            response: Some("UndoTagEditResponse".to_string()),
+            local_only: false,
+            capability: Capability::EditLibrary,
         },
         // Lyric
         RequestResponse {
             request: "GetLyricByTrackIdRequest".to_string(),
             response: Some("GetLyricByTrackIdResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
+        },
+        RequestResponse {
+            request: "SaveLyricRequest".to_string(),
+            response: Some("SaveLyricResponse".to_string()),
+            local_only: false,
+            capability: Capability::EditLibrary,
         },
         // Collection
         RequestResponse {
             request: "FetchCollectionGroupSummaryRequest".to_string(),
             response: Some("CollectionGroupSummaryResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "FetchCollectionGroupsRequest".to_string(),
             response: Some("FetchCollectionGroupsResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "FetchCollectionByIdsRequest".to_string(),
             response: Some("FetchCollectionByIdsResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "SearchCollectionSummaryRequest".to_string(),
             response: Some("SearchCollectionSummaryResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         // Cover Art
         RequestResponse {
             request: "GetCoverArtIdsByMixQueriesRequest".to_string(),
             response: Some("GetCoverArtIdsByMixQueriesResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "GetPrimaryColorByTrackIdRequest".to_string(),
             response: Some("GetPrimaryColorByTrackIdResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         // Playlist
         RequestResponse {
             request: "FetchAllPlaylistsRequest".to_string(),
             response: Some("FetchAllPlaylistsResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "CreatePlaylistRequest".to_string(),
             response: Some("CreatePlaylistResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "CreateM3u8PlaylistRequest".to_string(),
             response: Some("CreateM3u8PlaylistResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "UpdatePlaylistRequest".to_string(),
             response: Some("UpdatePlaylistResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "RemovePlaylistRequest".to_string(),
             response: Some("RemovePlaylistResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "AddItemToPlaylistRequest".to_string(),
             response: Some("AddItemToPlaylistResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "ReorderPlaylistItemPositionRequest".to_string(),
             response: Some("ReorderPlaylistItemPositionResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "GetPlaylistByIdRequest".to_string(),
             response: Some("GetPlaylistByIdResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         // Mix
         RequestResponse {
             request: "FetchAllMixesRequest".to_string(),
             response: Some("FetchAllMixesResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "CreateMixRequest".to_string(),
             response: Some("CreateMixResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "UpdateMixRequest".to_string(),
             response: Some("UpdateMixResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "RemoveMixRequest".to_string(),
             response: Some("RemoveMixResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "AddItemToMixRequest".to_string(),
             response: Some("AddItemToMixResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "GetMixByIdRequest".to_string(),
             response: Some("GetMixByIdResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "MixQueryRequest".to_string(),
             response: Some("MixQueryResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "FetchMixQueriesRequest".to_string(),
             response: Some("FetchMixQueriesResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "OperatePlaybackWithMixQueryRequest".to_string(),
             response: Some("OperatePlaybackWithMixQueryResponse".to_string()),
             local_only: false,
+            capability: Capability::ControlPlayback,
         },
         // Like
         RequestResponse {
             request: "SetLikedRequest".to_string(),
             response: Some("SetLikedResponse".to_string()),
             local_only: false,
+            capability: Capability::EditLibrary,
         },
         RequestResponse {
             request: "GetLikedRequest".to_string(),
             response: Some("GetLikedResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
+        },
+        // History
+        RequestResponse {
+            request: "FetchPlayHistoryRequest".to_string(),
+            response: Some("FetchPlayHistoryResponse".to_string()),
+            local_only: false,
+            capability: Capability::Browse,
+        },
+        RequestResponse {
+            request: "FetchTopPlayedRequest".to_string(),
+            response: Some("FetchTopPlayedResponse".to_string()),
+            local_only: false,
+            capability: Capability::Browse,
         },
         // Query and Search
         RequestResponse {
             request: "ComplexQueryRequest".to_string(),
             response: Some("ComplexQueryResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "SearchForRequest".to_string(),
             response: Some("SearchForResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         // Directory
         RequestResponse {
             request: "FetchDirectoryTreeRequest".to_string(),
             response: Some("FetchDirectoryTreeResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         // Scrobbler
         RequestResponse {
             request: "AuthenticateSingleServiceRequest".to_string(),
             response: Some("AuthenticateSingleServiceResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "AuthenticateMultipleServiceRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "LogoutSingleServiceRequest".to_string(),
             response: None,
             local_only: false,
+            capability: Capability::Admin,
         },
         // Log
         RequestResponse {
             request: "ListLogRequest".to_string(),
             response: Some("ListLogResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "ClearLogRequest".to_string(),
             response: Some("ClearLogResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "RemoveLogRequest".to_string(),
             response: Some("RemoveLogResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         // System
         RequestResponse {
             request: "SystemInfoRequest".to_string(),
             response: Some("SystemInfoResponse".to_string()),
             local_only: false,
+            capability: Capability::Browse,
         },
         // License
         RequestResponse {
             request: "RegisterLicenseRequest".to_string(),
             response: Some("RegisterLicenseResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "ValidateLicenseRequest".to_string(),
             response: Some("ValidateLicenseResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         // Neighbors
         RequestResponse {
             request: "StartBroadcastRequest".to_string(),
             response: None,
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "StopBroadcastRequest".to_string(),
             response: None,
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "StartListeningRequest".to_string(),
             response: None,
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "StopListeningRequest".to_string(),
             response: None,
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "GetDiscoveredDeviceRequest".to_string(),
             response: Some("GetDiscoveredDeviceResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "StartServerRequest".to_string(),
             response: Some("StartServerResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "StopServerRequest".to_string(),
             response: Some("StopServerResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "ListClientsRequest".to_string(),
             response: Some("ListClientsResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "GetSslCertificateFingerprintRequest".to_string(),
             response: Some("GetSslCertificateFingerprintResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "AddTrustedServerRequest".to_string(),
             response: Some("AddTrustedServerResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "RemoveTrustedClientRequest".to_string(),
             response: Some("RemoveTrustedClientResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "UpdateClientStatusRequest".to_string(),
             response: Some("UpdateClientStatusResponse".to_string()),
             local_only: false,
+            capability: Capability::Admin,
+        },
+        RequestResponse {
+            request: "UpdateClientRoleRequest".to_string(),
+            response: Some("UpdateClientRoleResponse".to_string()),
+            local_only: false,
+            capability: Capability::Admin,
+        },
+        RequestResponse {
+            request: "UpdateClientMaxBitrateRequest".to_string(),
+            response: Some("UpdateClientMaxBitrateResponse".to_string()),
+            local_only: false,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "EditHostsRequest".to_string(),
             response: Some("EditHostsResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "RemoveTrustedServerRequest".to_string(),
             response: Some("RemoveTrustedServerResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "ServerAvailabilityTestRequest".to_string(),
             response: Some("ServerAvailabilityTestResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "RegisterDeviceOnServerRequest".to_string(),
             response: Some("RegisterDeviceOnServerResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "CheckDeviceOnServerRequest".to_string(),
             response: Some("CheckDeviceOnServerResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "ConnectRequest".to_string(),
             response: Some("ConnectResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "FetchServerCertificateRequest".to_string(),
             response: Some("FetchServerCertificateResponse".to_string()),
             local_only: true,
+            capability: Capability::Admin,
         },
         RequestResponse {
             request: "FetchRemoteFileRequest".to_string(),
             response: Some("FetchRemoteFileResponse".to_string()),
             local_only: true,
+            capability: Capability::Browse,
         },
         RequestResponse {
             request: "RemoveItemFromPlaylistRequest".to_string(),
             response: Some("RemoveItemFromPlaylistResponse".to_string()),
             local_only: true,
+            capability: Capability::EditLibrary,
+        },
+        RequestResponse {
+            request: "ExportPlaylistRequest".to_string(),
+            response: Some("ExportPlaylistResponse".to_string()),
+            local_only: true,
+            capability: Capability::EditLibrary,
         },
     ];
 
@@ -510,6 +719,15 @@ pub fn define_request_types(_input: TokenStream) -> TokenStream {
         })
         .collect();
 
+    let request_capabilities: Vec<_> = types
+        .iter()
+        .map(|t| {
+            let req_ident = syn::parse_str::<syn::Ident>(&t.request).unwrap();
+            let capability_ident = syn::parse_str::<syn::Ident>(t.capability.name()).unwrap();
+            quote! { #req_ident => #capability_ident }
+        })
+        .collect();
+
     let expanded = quote! {
         #[macro_export]
         macro_rules! for_all_request_pairs {
@@ -574,6 +792,13 @@ pub fn define_request_types(_input: TokenStream) -> TokenStream {
             };
         }
 
+        #[macro_export]
+        macro_rules! for_all_request_capabilities {
+            ($m:tt) => {
+                $m!(#(#request_capabilities),*);
+            }
+        }
+
         #[macro_export]
         macro_rules! for_all_non_local_requests {
             ($m:tt, $params:expr) => {
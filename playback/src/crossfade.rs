@@ -0,0 +1,281 @@
+use std::f32::consts::FRAC_PI_2;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+
+/// The shape of the volume ramps used while two tracks overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrossfadeCurve {
+    Linear,
+    #[default]
+    EqualPower,
+    SCurve,
+}
+
+impl From<u32> for CrossfadeCurve {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => CrossfadeCurve::Linear,
+            1 => CrossfadeCurve::EqualPower,
+            2 => CrossfadeCurve::SCurve,
+            _ => CrossfadeCurve::EqualPower,
+        }
+    }
+}
+
+impl From<CrossfadeCurve> for u32 {
+    fn from(curve: CrossfadeCurve) -> Self {
+        match curve {
+            CrossfadeCurve::Linear => 0,
+            CrossfadeCurve::EqualPower => 1,
+            CrossfadeCurve::SCurve => 2,
+        }
+    }
+}
+
+impl CrossfadeCurve {
+    /// Returns the gain of the incoming track at `progress` (0.0 to 1.0).
+    ///
+    /// The outgoing track uses the mirrored value, `gain(1.0 - progress)`,
+    /// which keeps equal power fades at a constant perceived loudness.
+    pub fn gain(&self, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+
+        match self {
+            CrossfadeCurve::Linear => progress,
+            CrossfadeCurve::EqualPower => (progress * FRAC_PI_2).sin(),
+            CrossfadeCurve::SCurve => progress * progress * (3.0 - 2.0 * progress),
+        }
+    }
+}
+
+/// Crossfade configuration of the player, a zero duration disables crossfading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CrossfadeSettings {
+    pub duration: Duration,
+    pub curve: CrossfadeCurve,
+}
+
+impl CrossfadeSettings {
+    pub fn is_enabled(&self) -> bool {
+        !self.duration.is_zero()
+    }
+}
+
+/// Silent parts at the head and tail of a track, in seconds, as measured by
+/// the analysis. Used to skip the silence when mixing tracks together.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SilenceProfile {
+    pub leading_silence: f32,
+    pub trailing_silence: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FadeDirection {
+    In,
+    Out,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FadeRequest {
+    direction: FadeDirection,
+    duration: Duration,
+    curve: CrossfadeCurve,
+}
+
+/// Controls the volume ramp of a track from outside the audio thread.
+#[derive(Debug, Clone, Default)]
+pub struct FadeHandle {
+    pending: Arc<AtomicBool>,
+    request: Arc<Mutex<Option<FadeRequest>>>,
+}
+
+impl FadeHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ramps the track up from silence.
+    pub fn fade_in(&self, duration: Duration, curve: CrossfadeCurve) {
+        self.request(FadeDirection::In, duration, curve);
+    }
+
+    /// Ramps the track down to silence, the track ends once the fade completes.
+    pub fn fade_out(&self, duration: Duration, curve: CrossfadeCurve) {
+        self.request(FadeDirection::Out, duration, curve);
+    }
+
+    fn request(&self, direction: FadeDirection, duration: Duration, curve: CrossfadeCurve) {
+        if let Ok(mut request) = self.request.lock() {
+            *request = Some(FadeRequest {
+                direction,
+                duration,
+                curve,
+            });
+            self.pending.store(true, Ordering::Release);
+        }
+    }
+
+    fn take(&self) -> Option<FadeRequest> {
+        if !self.pending.swap(false, Ordering::AcqRel) {
+            return None;
+        }
+
+        self.request.lock().ok().and_then(|mut x| x.take())
+    }
+}
+
+struct ActiveFade {
+    direction: FadeDirection,
+    curve: CrossfadeCurve,
+    total_frames: u64,
+    position: u64,
+}
+
+/// Internal function that builds a `Faded` object.
+#[inline]
+pub fn faded<I>(input: I, handle: FadeHandle) -> Faded<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    Faded {
+        source: input,
+        handle,
+        fade: None,
+        gain: 1.0,
+        sample_in_frame: 0,
+        finished: false,
+    }
+}
+
+/// A source whose volume can be ramped by a `FadeHandle`.
+pub struct Faded<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    source: I,
+    handle: FadeHandle,
+    fade: Option<ActiveFade>,
+    /// Gain applied to all samples of the current frame
+    gain: f32,
+    sample_in_frame: u16,
+    finished: bool,
+}
+
+impl<I> Faded<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    fn start_fade(&mut self, request: FadeRequest) {
+        let total_frames =
+            (request.duration.as_secs_f64() * self.source.sample_rate() as f64).round() as u64;
+
+        // Fade outs start from the current gain, so interrupting a fade in
+        // does not click
+        let progress = match request.direction {
+            FadeDirection::In => 0.0,
+            FadeDirection::Out => 1.0 - self.gain,
+        };
+
+        self.fade = Some(ActiveFade {
+            direction: request.direction,
+            curve: request.curve,
+            total_frames: total_frames.max(1),
+            position: (progress.clamp(0.0, 1.0) as f64 * total_frames as f64) as u64,
+        });
+    }
+
+    /// Moves the envelope forward by one frame.
+    fn advance_frame(&mut self) {
+        if let Some(request) = self.handle.take() {
+            self.start_fade(request);
+        }
+
+        let Some(fade) = &mut self.fade else {
+            return;
+        };
+
+        let progress = fade.position as f32 / fade.total_frames as f32;
+        self.gain = match fade.direction {
+            FadeDirection::In => fade.curve.gain(progress),
+            FadeDirection::Out => fade.curve.gain(1.0 - progress),
+        };
+
+        if fade.position >= fade.total_frames {
+            if fade.direction == FadeDirection::Out {
+                self.finished = true;
+            }
+            self.fade = None;
+        } else {
+            fade.position += 1;
+        }
+    }
+}
+
+impl<I> Iterator for Faded<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.sample_in_frame == 0 {
+            self.advance_frame();
+        }
+
+        if self.finished {
+            return None;
+        }
+
+        self.sample_in_frame += 1;
+        if self.sample_in_frame >= self.source.channels().max(1) {
+            self.sample_in_frame = 0;
+        }
+
+        self.source.next().map(|sample| sample.amplify(self.gain))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<I> Source for Faded<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)
+    }
+}
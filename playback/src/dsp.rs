@@ -0,0 +1,473 @@
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::Source;
+
+/// Maximum number of equalizer bands, extra bands are ignored.
+pub const MAX_EQUALIZER_BANDS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterType {
+    #[default]
+    Peaking,
+    LowShelf,
+    HighShelf,
+}
+
+impl From<u32> for FilterType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => FilterType::Peaking,
+            1 => FilterType::LowShelf,
+            2 => FilterType::HighShelf,
+            _ => FilterType::Peaking,
+        }
+    }
+}
+
+impl From<FilterType> for u32 {
+    fn from(filter_type: FilterType) -> Self {
+        match filter_type {
+            FilterType::Peaking => 0,
+            FilterType::LowShelf => 1,
+            FilterType::HighShelf => 2,
+        }
+    }
+}
+
+/// A single band of the parametric equalizer. Frequency is in Hz and gain in dB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqualizerBand {
+    pub filter_type: FilterType,
+    pub frequency: f32,
+    pub gain: f32,
+    pub q: f32,
+}
+
+/// Configuration of the DSP chain. Preamp is in dB, a disabled chain passes
+/// samples through untouched.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DspSettings {
+    pub enabled: bool,
+    pub preamp: f32,
+    pub bands: Vec<EqualizerBand>,
+}
+
+/// Shares the DSP settings between the player and the audio thread.
+///
+/// Every update bumps a generation counter, so the audio thread only takes
+/// the lock when something changed.
+#[derive(Debug, Clone, Default)]
+pub struct DspHandle {
+    generation: Arc<AtomicU64>,
+    settings: Arc<Mutex<DspSettings>>,
+}
+
+impl DspHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, settings: DspSettings) {
+        if let Ok(mut current) = self.settings.lock() {
+            *current = settings;
+            self.generation.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+
+    pub fn get(&self) -> DspSettings {
+        self.settings.lock().map(|x| x.clone()).unwrap_or_default()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+}
+
+/// Normalized biquad coefficients (a0 == 1).
+#[derive(Debug, Clone, Copy)]
+struct BiquadCoefficients {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl BiquadCoefficients {
+    /// Computes the coefficients following the RBJ audio EQ cookbook.
+    fn new(band: &EqualizerBand, sample_rate: u32) -> Option<Self> {
+        let rate = sample_rate as f64;
+        let frequency = band.frequency as f64;
+
+        if !(frequency > 0.0 && frequency < rate / 2.0) || band.q <= 0.0 {
+            return None;
+        }
+
+        let a = 10.0_f64.powf(band.gain as f64 / 40.0);
+        let w0 = 2.0 * PI * frequency / rate;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * band.q as f64);
+
+        let (b0, b1, b2, a0, a1, a2) = match band.filter_type {
+            FilterType::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos_w0,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos_w0,
+                1.0 - alpha / a,
+            ),
+            FilterType::LowShelf => {
+                let k = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 + k),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 - k),
+                    (a + 1.0) + (a - 1.0) * cos_w0 + k,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    (a + 1.0) + (a - 1.0) * cos_w0 - k,
+                )
+            }
+            FilterType::HighShelf => {
+                let k = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 + k),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 - k),
+                    (a + 1.0) - (a - 1.0) * cos_w0 + k,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    (a + 1.0) - (a - 1.0) * cos_w0 - k,
+                )
+            }
+        };
+
+        Some(BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        })
+    }
+}
+
+/// Internal function that builds a `DspChain` object.
+#[inline]
+pub fn dsp_chain<I>(input: I, handle: DspHandle) -> DspChain<I>
+where
+    I: Source<Item = f32>,
+{
+    DspChain {
+        source: input,
+        handle,
+        generation: None,
+        enabled: false,
+        preamp: 1.0,
+        filters: Vec::new(),
+        states: Vec::new(),
+        channels: 0,
+        sample_rate: 0,
+        channel_index: 0,
+    }
+}
+
+/// Applies the preamp and the equalizer bands to the samples of the inner
+/// source. The filters follow format changes between queued tracks.
+///
+/// Boosted bands can push samples past full scale, the sink runs the chain
+/// through `limiter` to keep them from clipping.
+pub struct DspChain<I>
+where
+    I: Source<Item = f32>,
+{
+    source: I,
+    handle: DspHandle,
+    /// Generation of the settings the filters were built from
+    generation: Option<u64>,
+    enabled: bool,
+    /// Linear preamp gain
+    preamp: f32,
+    filters: Vec<BiquadCoefficients>,
+    /// Filter memory, indexed by `band * channels + channel`
+    states: Vec<[f64; 2]>,
+    channels: u16,
+    sample_rate: u32,
+    channel_index: u16,
+}
+
+impl<I> DspChain<I>
+where
+    I: Source<Item = f32>,
+{
+    /// Rebuilds the filters if the settings or the stream format changed.
+    fn refresh(&mut self) {
+        let generation = self.handle.generation();
+        let channels = self.source.channels().max(1);
+        let sample_rate = self.source.sample_rate();
+
+        if self.generation == Some(generation)
+            && self.channels == channels
+            && self.sample_rate == sample_rate
+        {
+            return;
+        }
+
+        let settings = self.handle.get();
+        let format_changed = self.channels != channels || self.sample_rate != sample_rate;
+
+        self.generation = Some(generation);
+        self.channels = channels;
+        self.sample_rate = sample_rate;
+        self.enabled = settings.enabled;
+        self.preamp = 10.0_f32.powf(settings.preamp / 20.0);
+        self.filters = settings
+            .bands
+            .iter()
+            .take(MAX_EQUALIZER_BANDS)
+            .filter_map(|band| BiquadCoefficients::new(band, sample_rate))
+            .collect();
+
+        // Keep the filter memory when only the gains changed, so dragging a
+        // slider does not click
+        let state_len = self.filters.len() * channels as usize;
+        if format_changed || self.states.len() != state_len {
+            self.states = vec![[0.0; 2]; state_len];
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, sample: f32) -> f32 {
+        let channels = self.channels as usize;
+        let channel = self.channel_index as usize;
+
+        let mut x = (sample * self.preamp) as f64;
+        for (band, coefficients) in self.filters.iter().enumerate() {
+            // Transposed direct form II
+            let z = &mut self.states[band * channels + channel];
+            let y = coefficients.b0 * x + z[0];
+            z[0] = coefficients.b1 * x - coefficients.a1 * y + z[1];
+            z[1] = coefficients.b2 * x - coefficients.a2 * y;
+            x = y;
+        }
+
+        x as f32
+    }
+}
+
+impl<I> Iterator for DspChain<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.channel_index == 0 {
+            self.refresh();
+        }
+
+        let sample = self.source.next()?;
+
+        let result = if self.enabled {
+            self.process(sample)
+        } else {
+            sample
+        };
+
+        self.channel_index += 1;
+        if self.channel_index >= self.channels {
+            self.channel_index = 0;
+        }
+
+        Some(result)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<I> Source for DspChain<I>
+where
+    I: Source<Item = f32>,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limiter::{limiter, LIMITER_THRESHOLD};
+    use rodio::buffer::SamplesBuffer;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn band(filter_type: FilterType, frequency: f32, gain: f32) -> EqualizerBand {
+        EqualizerBand {
+            filter_type,
+            frequency,
+            gain,
+            q: 0.707,
+        }
+    }
+
+    /// Magnitude of the filter response at `frequency`, in dB.
+    fn response(coefficients: &BiquadCoefficients, frequency: f64) -> f64 {
+        let w = 2.0 * PI * frequency / SAMPLE_RATE as f64;
+        // Evaluates H(z) at z = e^jw, with z^-1 = cos w - j sin w
+        let (re1, im1) = (w.cos(), -w.sin());
+        let (re2, im2) = ((2.0 * w).cos(), -(2.0 * w).sin());
+
+        let num_re = coefficients.b0 + coefficients.b1 * re1 + coefficients.b2 * re2;
+        let num_im = coefficients.b1 * im1 + coefficients.b2 * im2;
+        let den_re = 1.0 + coefficients.a1 * re1 + coefficients.a2 * re2;
+        let den_im = coefficients.a1 * im1 + coefficients.a2 * im2;
+
+        let magnitude = (num_re.hypot(num_im)) / (den_re.hypot(den_im));
+        20.0 * magnitude.log10()
+    }
+
+    fn sine(frequency: f32, amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                amplitude * (2.0 * std::f32::consts::PI * frequency * t).sin()
+            })
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()))
+    }
+
+    #[test]
+    fn test_flat_band_is_identity() {
+        let coefficients =
+            BiquadCoefficients::new(&band(FilterType::Peaking, 1000.0, 0.0), SAMPLE_RATE).unwrap();
+
+        assert!((coefficients.b0 - 1.0).abs() < 1e-12);
+        assert!((coefficients.b1 - coefficients.a1).abs() < 1e-12);
+        assert!((coefficients.b2 - coefficients.a2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_invalid_bands_are_rejected() {
+        let above_nyquist = band(FilterType::Peaking, 30000.0, 6.0);
+        let zero_frequency = band(FilterType::Peaking, 0.0, 6.0);
+        let zero_q = EqualizerBand {
+            q: 0.0,
+            ..band(FilterType::Peaking, 1000.0, 6.0)
+        };
+
+        assert!(BiquadCoefficients::new(&above_nyquist, SAMPLE_RATE).is_none());
+        assert!(BiquadCoefficients::new(&zero_frequency, SAMPLE_RATE).is_none());
+        assert!(BiquadCoefficients::new(&zero_q, SAMPLE_RATE).is_none());
+    }
+
+    #[test]
+    fn test_peaking_response() {
+        let coefficients =
+            BiquadCoefficients::new(&band(FilterType::Peaking, 1000.0, 6.0), SAMPLE_RATE).unwrap();
+
+        assert!((response(&coefficients, 1000.0) - 6.0).abs() < 0.01);
+        assert!(response(&coefficients, 20.0).abs() < 0.1);
+        assert!(response(&coefficients, 20000.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_shelf_response() {
+        let low =
+            BiquadCoefficients::new(&band(FilterType::LowShelf, 200.0, -9.0), SAMPLE_RATE).unwrap();
+        let high = BiquadCoefficients::new(&band(FilterType::HighShelf, 5000.0, 4.0), SAMPLE_RATE)
+            .unwrap();
+
+        assert!((response(&low, 10.0) + 9.0).abs() < 0.1);
+        assert!(response(&low, 15000.0).abs() < 0.1);
+        assert!((response(&high, 23000.0) - 4.0).abs() < 0.1);
+        assert!(response(&high, 50.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_disabled_chain_passes_through() {
+        let handle = DspHandle::new();
+        handle.set(DspSettings {
+            enabled: false,
+            preamp: -6.0,
+            bands: vec![band(FilterType::Peaking, 1000.0, 12.0)],
+        });
+
+        let input = sine(1000.0, 0.5, 4800);
+        let output: Vec<f32> =
+            dsp_chain(SamplesBuffer::new(1, SAMPLE_RATE, input.clone()), handle).collect();
+
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_chain_applies_preamp_and_boost() {
+        let handle = DspHandle::new();
+        handle.set(DspSettings {
+            enabled: true,
+            preamp: -6.0,
+            bands: vec![band(FilterType::Peaking, 1000.0, 12.0)],
+        });
+
+        let input = sine(1000.0, 0.1, 9600);
+        let output: Vec<f32> =
+            dsp_chain(SamplesBuffer::new(1, SAMPLE_RATE, input), handle).collect();
+
+        // Past the filter transient the tone is 6 dB louder
+        let gain = peak(&output[4800..]) / 0.1;
+        assert!((gain - 2.0).abs() < 0.05, "gain was {}", gain);
+    }
+
+    #[test]
+    fn test_boost_is_limited() {
+        let handle = DspHandle::new();
+        handle.set(DspSettings {
+            enabled: true,
+            preamp: 0.0,
+            bands: vec![band(FilterType::Peaking, 1000.0, 12.0)],
+        });
+
+        let input = sine(1000.0, 0.9, 9600);
+        let boosted: Vec<f32> = dsp_chain(
+            SamplesBuffer::new(1, SAMPLE_RATE, input.clone()),
+            handle.clone(),
+        )
+        .collect();
+        let limited: Vec<f32> =
+            limiter(dsp_chain(SamplesBuffer::new(1, SAMPLE_RATE, input), handle)).collect();
+
+        assert!(peak(&boosted) > 1.0);
+        assert!(peak(&limited) <= LIMITER_THRESHOLD + 1e-6);
+    }
+}
@@ -0,0 +1,279 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+
+const PENDING: u8 = 0;
+const STARTED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// Shared state of a track queued into the sink.
+///
+/// A queued track is either still waiting for the previous one to finish,
+/// already producing samples, or cancelled before it got the chance to play.
+/// The transitions are atomic, so a track can never be cancelled after its
+/// first sample has been handed to the output stream.
+#[derive(Debug, Clone)]
+pub struct TrackHandle {
+    state: Arc<AtomicU8>,
+    /// The source of the track until it starts, so cancelling the track
+    /// releases its decoder without waiting for the sink to reach it
+    pending: Arc<Mutex<Option<Box<dyn Any + Send>>>>,
+    /// Bits of the gain applied to the samples of the track
+    gain: Arc<AtomicU32>,
+}
+
+impl TrackHandle {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(AtomicU8::new(PENDING)),
+            pending: Arc::new(Mutex::new(None)),
+            gain: Arc::new(AtomicU32::new(1.0_f32.to_bits())),
+        }
+    }
+
+    /// Returns true once the output stream pulled the first sample of the track.
+    pub fn has_started(&self) -> bool {
+        self.state.load(Ordering::Acquire) == STARTED
+    }
+
+    /// Cancels a track that has not started yet and drops its source, the
+    /// sink skips the track when the previous one ends.
+    ///
+    /// Returns false if the track already started playing.
+    pub fn cancel(&self) -> bool {
+        match self
+            .state
+            .compare_exchange(PENDING, CANCELLED, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.take();
+                }
+                true
+            }
+            Err(state) => state == CANCELLED,
+        }
+    }
+
+    /// Sets the gain of the track, such as its normalization factor.
+    ///
+    /// The gain travels with the samples of the track, so it switches on the
+    /// exact sample the sink moves on to the track.
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for TrackHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Internal function that builds a `TrackedSource` object.
+#[inline]
+pub fn tracked<I>(input: I, handle: TrackHandle) -> TrackedSource<I>
+where
+    I: Source + Send + 'static,
+    I::Item: Sample,
+{
+    if let Ok(mut pending) = handle.pending.lock() {
+        *pending = Some(Box::new(input));
+    }
+
+    TrackedSource {
+        source: None,
+        handle,
+    }
+}
+
+/// A source that reports the moment it starts playing through a `TrackHandle`.
+pub struct TrackedSource<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    /// Taken from the handle when the track starts
+    source: Option<I>,
+    handle: TrackHandle,
+}
+
+impl<I> TrackedSource<I>
+where
+    I: Source + Send + 'static,
+    I::Item: Sample,
+{
+    /// Marks the track as started and takes its source from the handle.
+    fn start(&mut self) -> Option<&mut I> {
+        self.handle
+            .state
+            .compare_exchange(PENDING, STARTED, Ordering::AcqRel, Ordering::Acquire)
+            .ok()?;
+
+        let pending = self.handle.pending.lock().ok()?.take()?;
+        self.source = pending.downcast::<I>().ok().map(|x| *x);
+        self.source.as_mut()
+    }
+
+    /// Runs `f` on the source, wherever it lives. `None` once cancelled.
+    fn with_source<T>(&self, f: impl FnOnce(&I) -> T) -> Option<T> {
+        if let Some(source) = &self.source {
+            return Some(f(source));
+        }
+
+        let pending = self.handle.pending.lock().ok()?;
+        pending.as_ref()?.downcast_ref::<I>().map(f)
+    }
+}
+
+impl<I> Iterator for TrackedSource<I>
+where
+    I: Source + Send + 'static,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let gain = self.handle.gain();
+        let source = match self.source {
+            Some(ref mut source) => source,
+            None => self.start()?,
+        };
+
+        source.next().map(|x| x.amplify(gain))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.with_source(|x| x.size_hint()).unwrap_or((0, Some(0)))
+    }
+}
+
+impl<I> Source for TrackedSource<I>
+where
+    I: Source + Send + 'static,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.with_source(|x| x.current_frame_len())
+            .unwrap_or(Some(0))
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.with_source(|x| x.channels()).unwrap_or(1)
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.with_source(|x| x.sample_rate()).unwrap_or(48000)
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.with_source(|x| x.total_duration())
+            .unwrap_or(Some(Duration::ZERO))
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        if let Some(source) = &mut self.source {
+            return source.try_seek(pos);
+        }
+
+        // A poisoned lock is treated like a cancelled track
+        let Ok(mut pending) = self.handle.pending.lock() else {
+            return Ok(());
+        };
+        match pending.as_mut().and_then(|x| x.downcast_mut::<I>()) {
+            Some(source) => source.try_seek(pos),
+            // Cancelled tracks have nothing left to play
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use rodio::queue::queue;
+
+    fn samples(value: f32, len: usize) -> SamplesBuffer<f32> {
+        SamplesBuffer::new(2, 44100, vec![value; len])
+    }
+
+    #[test]
+    fn test_started_track_cannot_be_cancelled() {
+        let handle = TrackHandle::new();
+        let mut source = tracked(samples(0.5, 4), handle.clone());
+
+        assert!(!handle.has_started());
+        assert_eq!(source.next(), Some(0.5));
+        assert!(handle.has_started());
+        assert!(!handle.cancel());
+        assert_eq!(source.count(), 3);
+    }
+
+    #[test]
+    fn test_cancel_drops_pending_source() {
+        let marker = Arc::new(());
+        let inner = {
+            let marker = Arc::clone(&marker);
+            samples(0.5, 4).periodic_access(Duration::from_millis(10), move |_| {
+                let _ = &marker;
+            })
+        };
+
+        let handle = TrackHandle::new();
+        let mut source = tracked(inner, handle.clone());
+        assert_eq!(Arc::strong_count(&marker), 2);
+
+        assert!(handle.cancel());
+        // Cancelling twice is fine, the track stays cancelled
+        assert!(handle.cancel());
+        assert_eq!(Arc::strong_count(&marker), 1);
+        assert_eq!(source.next(), None);
+        assert!(!handle.has_started());
+    }
+
+    #[test]
+    fn test_gain_switches_on_track_boundary() {
+        let (input, output) = queue(false);
+
+        let current = TrackHandle::new();
+        let next = TrackHandle::new();
+        current.set_gain(1.0);
+        next.set_gain(0.5);
+        input.append(tracked(samples(0.8, 6), current));
+        input.append(tracked(samples(0.8, 6), next));
+
+        let output: Vec<f32> = output.collect();
+        assert_eq!(output[..6], [0.8; 6]);
+        assert_eq!(output[6..], [0.4; 6]);
+    }
+
+    #[test]
+    fn test_cancelled_track_is_skipped() {
+        let (input, output) = queue(false);
+
+        let cancelled = TrackHandle::new();
+        input.append(tracked(samples(0.8, 4), TrackHandle::new()));
+        input.append(tracked(samples(0.1, 4), cancelled.clone()));
+        input.append(tracked(samples(0.3, 4), TrackHandle::new()));
+        assert!(cancelled.cancel());
+
+        let output: Vec<f32> = output.collect();
+        assert_eq!(output, [0.8, 0.8, 0.8, 0.8, 0.3, 0.3, 0.3, 0.3]);
+    }
+}
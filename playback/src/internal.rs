@@ -1,6 +1,7 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, bail, Context, Result};
@@ -11,9 +12,17 @@ use tokio::sync::mpsc;
 use tokio::time::{interval, sleep_until, Duration, Instant};
 use tokio_util::sync::CancellationToken;
 
+use formats::{registry, FormatSupport};
+
 use crate::buffered::rune_buffered;
+use crate::crossfade::{faded, CrossfadeSettings, FadeHandle, SilenceProfile};
+use crate::dsp::{dsp_chain, DspHandle, DspSettings};
+use crate::gapless::{tracked, TrackHandle};
+use crate::limiter::limiter;
+use crate::normalization::{NormalizationMode, ReplayGainInfo};
 use crate::output_stream::{RuneOutputStream, RuneOutputStreamHandle};
 use crate::player::PlayingItem;
+use crate::range::{ranged, TrackRange};
 use crate::realtime_fft::RealTimeFFT;
 use crate::shared_source::SharedSource;
 use crate::strategies::{
@@ -65,7 +74,7 @@ pub enum PlayerCommand {
     Switch(usize),
     Seek(f64),
     AddToPlaylist {
-        tracks: Vec<(PlayingItem, std::path::PathBuf)>,
+        tracks: Vec<(PlayingItem, std::path::PathBuf, Option<TrackRange>)>,
         mode: AddMode,
     },
     RemoveFromPlaylist {
@@ -80,6 +89,11 @@ pub enum PlayerCommand {
     SetVolume(f32),
     SetRealtimeFFTEnabled(bool),
     SetAdaptiveSwitchingEnabled(bool),
+    SetNormalizationMode(NormalizationMode),
+    UpdateReplayGain(Vec<(PlayingItem, ReplayGainInfo)>),
+    SetCrossfade(CrossfadeSettings),
+    UpdateSilenceProfile(Vec<(PlayingItem, SilenceProfile)>),
+    SetDsp(DspSettings),
 }
 
 #[derive(Debug, Clone)]
@@ -137,6 +151,21 @@ pub enum PlayerEvent {
 pub struct PlaylistItem {
     pub item: PlayingItem,
     pub path: PathBuf,
+    pub range: Option<TrackRange>,
+}
+
+/// The track queued right after the current one in the sink, so the output
+/// stream can move on to it without any gap.
+#[derive(Debug, Clone)]
+struct PreloadedTrack {
+    index: usize,
+    item: PlayingItem,
+    path: PathBuf,
+    handle: TrackHandle,
+    fade: FadeHandle,
+    duration: Option<Duration>,
+    /// Leading silence skipped by seeking the decoder before it was queued
+    skipped_silence: Duration,
 }
 
 #[derive(Debug, PartialEq)]
@@ -146,12 +175,21 @@ enum InternalPlaybackState {
     Stopped,
 }
 
-fn try_new_sink(stream: &RuneOutputStreamHandle) -> Result<Sink, PlayError> {
+fn try_new_sink(stream: &RuneOutputStreamHandle, dsp: &DspHandle) -> Result<Sink, PlayError> {
     let (sink, queue_rx) = Sink::new_idle();
-    stream.play_raw(queue_rx)?;
+    stream.play_raw(limiter(dsp_chain(queue_rx, dsp.clone())))?;
     Ok(sink)
 }
 
+fn open_decoder(path: &Path) -> Result<Decoder<BufReader<File>>> {
+    if let FormatSupport::Unsupported(reason) = registry().check(path) {
+        bail!("Unsupported file {:?}: {}", path, reason);
+    }
+
+    let file = File::open(path).with_context(|| format!("Failed to open file: {:?}", path))?;
+    Decoder::new(BufReader::new(file)).with_context(|| format!("Failed to decode file: {:?}", path))
+}
+
 #[derive(Debug, Clone)]
 pub struct InternalLog {
     pub domain: String,
@@ -191,6 +229,15 @@ pub(crate) struct PlayerInternal {
     current_track_path: Option<PathBuf>,
     sink: Option<Sink>,
     _stream: Option<RuneOutputStream>,
+    stream_handle: Option<RuneOutputStreamHandle>,
+    preloaded: Option<PreloadedTrack>,
+    current_handle: Option<TrackHandle>,
+    current_fade: Option<FadeHandle>,
+    current_duration: Option<Duration>,
+    /// The sink counts the position from where the decoder started, which
+    /// misses the skipped leading silence until the next seek
+    skipped_silence: Duration,
+    fading_sink: Option<Sink>,
     state: InternalPlaybackState,
     debounce_timer: Option<Instant>,
     cancellation_token: CancellationToken,
@@ -201,6 +248,11 @@ pub(crate) struct PlayerInternal {
     stream_error_receiver: mpsc::UnboundedReceiver<String>,
     stream_retry_count: usize,
     adaptive_switching: bool,
+    normalization_mode: NormalizationMode,
+    replay_gains: HashMap<PlayingItem, ReplayGainInfo>,
+    crossfade: CrossfadeSettings,
+    silence_profiles: HashMap<PlayingItem, SilenceProfile>,
+    dsp: DspHandle,
 }
 
 impl PlayerInternal {
@@ -219,6 +271,13 @@ impl PlayerInternal {
             current_track_path: None,
             sink: None,
             _stream: None,
+            stream_handle: None,
+            preloaded: None,
+            current_handle: None,
+            current_fade: None,
+            current_duration: None,
+            skipped_silence: Duration::ZERO,
+            fading_sink: None,
             realtime_fft: Arc::new(Mutex::new(RealTimeFFT::new(512))),
             state: InternalPlaybackState::Stopped,
             debounce_timer: None,
@@ -231,6 +290,11 @@ impl PlayerInternal {
             stream_error_receiver,
             stream_retry_count: 0,
             adaptive_switching: false,
+            normalization_mode: NormalizationMode::Disabled,
+            replay_gains: HashMap::new(),
+            crossfade: CrossfadeSettings::default(),
+            silence_profiles: HashMap::new(),
+            dsp: DspHandle::new(),
         }
     }
 
@@ -277,6 +341,11 @@ impl PlayerInternal {
                         PlayerCommand::SetVolume(volume) => self.set_volume(volume),
                         PlayerCommand::SetRealtimeFFTEnabled(enabled) => self.set_realtime_fft_enabled(enabled),
                         PlayerCommand::SetAdaptiveSwitchingEnabled(enabled) => self.set_adaptive_switching(enabled),
+                        PlayerCommand::SetNormalizationMode(mode) => self.set_normalization_mode(mode),
+                        PlayerCommand::UpdateReplayGain(gains) => self.update_replay_gain(gains),
+                        PlayerCommand::SetCrossfade(settings) => self.set_crossfade(settings),
+                        PlayerCommand::UpdateSilenceProfile(profiles) => self.update_silence_profile(profiles),
+                        PlayerCommand::SetDsp(settings) => self.set_dsp(settings),
                     }?;
                 },
                 Ok(fft_data) = fft_receiver.recv() => {
@@ -349,9 +418,7 @@ impl PlayerInternal {
             }
 
             let item = &self.playlist[mapped_index];
-            let file = File::open(item.path.clone())
-                .with_context(|| format!("Failed to open file: {:?}", item.path))?;
-            let source = Decoder::new(BufReader::new(file));
+            let source = open_decoder(&item.path);
 
             if let Err(error) = source {
                 warn!("Failed to decode file {:?}: {:#?}", item.path, error);
@@ -363,8 +430,13 @@ impl PlayerInternal {
                 return Ok(());
             }
 
-            let source = SharedSource::new(rune_buffered(source.unwrap()));
-            let source_for_fft = Arc::clone(&source.inner);
+            let decoder = source.unwrap();
+            let handle = TrackHandle::new();
+            handle.set_gain(self.normalization_factor(Some(&item.item)));
+            let fade = FadeHandle::new();
+            let source =
+                self.create_track_source(decoder, item.range, handle.clone(), fade.clone());
+            let duration = source.total_duration();
 
             let (stream, stream_handle) = RuneOutputStream::try_default_with_callback({
                 let error_sender = self.stream_error_sender.clone();
@@ -373,40 +445,10 @@ impl PlayerInternal {
                 }
             })
             .context("Failed to create output stream")?;
-            let sink = try_new_sink(&stream_handle).context("Failed to create sink")?;
-
-            // Create a channel to transfer FFT data
-            let (fft_tx, mut fft_rx) = mpsc::unbounded_channel();
-
-            // Create a new thread for calculating realtime FFT
-            let realtime_fft = Arc::clone(&self.realtime_fft);
-            let fft_enabled = Arc::clone(&self.fft_enabled);
-            tokio::spawn(async move {
-                while let Some(data) = fft_rx.recv().await {
-                    if let Ok(enabled) = fft_enabled.lock() {
-                        if *enabled {
-                            if let Ok(fft) = realtime_fft.lock() {
-                                fft.add_data(data);
-                            }
-                        }
-                    }
-                }
-            });
+            let sink = try_new_sink(&stream_handle, &self.dsp).context("Failed to create sink")?;
 
             sink.set_volume(self.volume);
-            sink.append(source.periodic_access(
-                Duration::from_millis(12),
-                move |_sample: &mut SharedSource| {
-                    if let Ok(guard) = source_for_fft.lock() {
-                        let data: Option<Vec<i16>> = guard.current_samples();
-                        if let Some(data) = data {
-                            if fft_tx.send(data).is_err() {
-                                error!("Failed to send FFT data");
-                            }
-                        }
-                    }
-                },
-            ));
+            sink.append(source);
 
             if !play {
                 sink.pause();
@@ -414,6 +456,13 @@ impl PlayerInternal {
 
             self.sink = Some(sink);
             self._stream = Some(stream);
+            self.stream_handle = Some(stream_handle);
+            self.preloaded = None;
+            self.current_handle = Some(handle);
+            self.current_fade = Some(fade);
+            self.current_duration = duration;
+            self.skipped_silence = Duration::ZERO;
+            self.fading_sink = None;
             self.current_track_index = Some(index);
             self.current_item = Some(item.item.clone());
             self.current_track_path = Some(item.path.clone());
@@ -454,13 +503,311 @@ impl PlayerInternal {
                     .context("Failed to send Playing event")?;
                 self.state = InternalPlaybackState::Stopped;
             }
+
+            self.preload_next();
         } else {
             error!("Load command received without index");
         }
         Ok(())
     }
 
+    /// Wraps a decoded track into the source appended to the sink, feeding
+    /// the realtime FFT while it plays.
+    fn create_track_source(
+        &self,
+        decoder: Decoder<BufReader<File>>,
+        range: Option<TrackRange>,
+        handle: TrackHandle,
+        fade: FadeHandle,
+    ) -> impl Source<Item = i16> + Send + 'static {
+        let source = SharedSource::new(rune_buffered(decoder));
+        let source_for_fft = Arc::clone(&source.inner);
+
+        // Create a channel to transfer FFT data
+        let (fft_tx, mut fft_rx) = mpsc::unbounded_channel();
+
+        // Create a new thread for calculating realtime FFT
+        let realtime_fft = Arc::clone(&self.realtime_fft);
+        let fft_enabled = Arc::clone(&self.fft_enabled);
+        tokio::spawn(async move {
+            while let Some(data) = fft_rx.recv().await {
+                if let Ok(enabled) = fft_enabled.lock() {
+                    if *enabled {
+                        if let Ok(fft) = realtime_fft.lock() {
+                            fft.add_data(data);
+                        }
+                    }
+                }
+            }
+        });
+
+        tracked(
+            faded(
+                ranged(
+                    source.periodic_access(
+                        Duration::from_millis(12),
+                        move |_sample: &mut SharedSource| {
+                            if let Ok(guard) = source_for_fft.lock() {
+                                let data: Option<Vec<i16>> = guard.current_samples();
+                                if let Some(data) = data {
+                                    if fft_tx.send(data).is_err() {
+                                        error!("Failed to send FFT data");
+                                    }
+                                }
+                            }
+                        },
+                    ),
+                    range.unwrap_or_default(),
+                ),
+                fade,
+            ),
+            handle,
+        )
+    }
+
+    /// Decodes the track that follows the current one and appends it to the
+    /// sink, so the boundary between both tracks is sample accurate.
+    fn preload_next(&mut self) {
+        let (Some(sink), Some(index)) = (&self.sink, self.current_track_index) else {
+            return;
+        };
+
+        let Some(next_index) = self.playback_strategy.next(index, self.playlist.len()) else {
+            return;
+        };
+
+        let mapped_index = self.get_mapped_track_index(next_index);
+        let Some(item) = self.playlist.get(mapped_index) else {
+            return;
+        };
+
+        let decoder = match open_decoder(&item.path) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                warn!("Failed to preload track: {:#?}", e);
+                return;
+            }
+        };
+
+        let handle = TrackHandle::new();
+        handle.set_gain(self.normalization_factor(Some(&item.item)));
+        let fade = FadeHandle::new();
+        let source = self.create_track_source(decoder, item.range, handle.clone(), fade.clone());
+        let duration = source.total_duration();
+        sink.append(source);
+
+        debug!("Track preloaded: {:?}", item.path);
+        self.preloaded = Some(PreloadedTrack {
+            index: next_index,
+            item: item.item.clone(),
+            path: item.path.clone(),
+            handle,
+            fade,
+            duration,
+            skipped_silence: Duration::ZERO,
+        });
+    }
+
+    /// Drops the preloaded track after the playlist or the playback mode
+    /// changed, and queues the new successor instead.
+    fn refresh_preload(&mut self) {
+        if let Some(preloaded) = self.preloaded.take() {
+            if !preloaded.handle.cancel() {
+                // The track already started, the boundary is handled on the next
+                // progress tick and the successor is queued from there
+                self.preloaded = Some(preloaded);
+                return;
+            }
+        }
+
+        self.preload_next();
+    }
+
+    /// Promotes the preloaded track to the current track once the output
+    /// stream moved on to it.
+    fn advance_to_preloaded(&mut self) -> Result<()> {
+        let Some(preloaded) = self.preloaded.take() else {
+            return Ok(());
+        };
+
+        self.set_current_track(preloaded)?;
+        self.preload_next();
+
+        Ok(())
+    }
+
+    /// Reports the end of the current track and switches the player state to
+    /// a track that is already playing.
+    fn set_current_track(&mut self, track: PreloadedTrack) -> Result<()> {
+        if let (Some(item), Some(index), Some(path)) = (
+            self.current_item.clone(),
+            self.current_track_index,
+            self.current_track_path.clone(),
+        ) {
+            self.event_sender
+                .send(PlayerEvent::EndOfTrack {
+                    item,
+                    index: self.get_mapped_track_index(index),
+                    path,
+                    playback_mode: self.playback_mode,
+                })
+                .with_context(|| "Failed to send EndOfTrack event")?;
+        }
+
+        self.current_track_index = Some(track.index);
+        self.current_item = Some(track.item.clone());
+        self.current_track_path = Some(track.path.clone());
+        self.current_handle = Some(track.handle);
+        self.current_fade = Some(track.fade);
+        self.current_duration = track.duration;
+        self.skipped_silence = track.skipped_silence;
+        self.apply_sink_volume();
+        info!("Track advanced: {:?}", track.path);
+
+        let position = self
+            .sink
+            .as_ref()
+            .map(|sink| self.track_position(sink))
+            .unwrap_or_default();
+        self.event_sender
+            .send(PlayerEvent::Playing {
+                item: track.item,
+                index: self.get_mapped_track_index(track.index),
+                path: track.path,
+                playback_mode: self.playback_mode,
+                position,
+            })
+            .with_context(|| "Failed to send Playing event")?;
+
+        Ok(())
+    }
+
+    /// Returns how long the current track keeps playing before its trailing
+    /// silence, `None` if the duration is unknown.
+    fn remaining_audible(&self) -> Option<Duration> {
+        let sink = self.sink.as_ref()?;
+        let duration = self.current_duration?;
+        let trailing_silence = self
+            .current_item
+            .as_ref()
+            .and_then(|item| self.silence_profiles.get(item))
+            .map(|profile| Duration::from_secs_f32(profile.trailing_silence.max(0.0)))
+            .unwrap_or_default();
+
+        Some(
+            duration
+                .saturating_sub(trailing_silence)
+                .saturating_sub(self.track_position(sink)),
+        )
+    }
+
+    /// Returns the position in the file of the current track.
+    fn track_position(&self, sink: &Sink) -> Duration {
+        sink.get_pos() + self.skipped_silence
+    }
+
+    fn should_start_crossfade(&self) -> bool {
+        if !self.crossfade.is_enabled()
+            || self.state != InternalPlaybackState::Playing
+            || self.fading_sink.is_some()
+        {
+            return false;
+        }
+
+        match self.remaining_audible() {
+            Some(remaining) => !remaining.is_zero() && remaining <= self.crossfade.duration,
+            None => false,
+        }
+    }
+
+    /// Starts the next track on a second sink and fades both tracks, so the
+    /// outgoing one reaches silence where its audible part ends.
+    fn start_crossfade(&mut self) -> Result<()> {
+        let (Some(index), Some(stream_handle), Some(remaining)) = (
+            self.current_track_index,
+            self.stream_handle.clone(),
+            self.remaining_audible(),
+        ) else {
+            return Ok(());
+        };
+
+        let Some(next_index) = self.playback_strategy.next(index, self.playlist.len()) else {
+            return Ok(());
+        };
+
+        let mapped_index = self.get_mapped_track_index(next_index);
+        let Some(item) = self.playlist.get(mapped_index).cloned() else {
+            return Ok(());
+        };
+
+        let mut decoder = match open_decoder(&item.path) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                warn!("Failed to open track for crossfading: {:#?}", e);
+                return Ok(());
+            }
+        };
+
+        // The preloaded track would otherwise start right after the fade out
+        if let Some(preloaded) = self.preloaded.take() {
+            if !preloaded.handle.cancel() {
+                self.preloaded = Some(preloaded);
+                return Ok(());
+            }
+        }
+
+        let leading_silence = self
+            .silence_profiles
+            .get(&item.item)
+            .map(|profile| profile.leading_silence)
+            .unwrap_or_default();
+        // Tracks with a range start at their own offset instead
+        let mut skipped_silence = Duration::ZERO;
+        if leading_silence > 0.0 && item.range.is_none() {
+            let position = Duration::from_secs_f32(leading_silence);
+            match decoder.try_seek(position) {
+                Ok(_) => skipped_silence = position,
+                Err(e) => warn!("Failed to skip leading silence: {:#?}", e),
+            }
+        }
+
+        let handle = TrackHandle::new();
+        handle.set_gain(self.normalization_factor(Some(&item.item)));
+        let fade = FadeHandle::new();
+        fade.fade_in(remaining, self.crossfade.curve);
+
+        let source = self.create_track_source(decoder, item.range, handle.clone(), fade.clone());
+        let duration = source.total_duration();
+
+        let sink = try_new_sink(&stream_handle, &self.dsp).context("Failed to create sink")?;
+        sink.set_volume(self.volume);
+        sink.append(source);
+
+        if let Some(current_fade) = &self.current_fade {
+            current_fade.fade_out(remaining, self.crossfade.curve);
+        }
+
+        info!("Crossfading into {:?} over {:?}", item.path, remaining);
+        self.fading_sink = self.sink.replace(sink);
+        self.set_current_track(PreloadedTrack {
+            index: next_index,
+            item: item.item,
+            path: item.path,
+            handle,
+            fade,
+            duration,
+            skipped_silence,
+        })?;
+        self.preload_next();
+
+        Ok(())
+    }
+
     fn play(&mut self) -> Result<()> {
+        if let Some(sink) = &self.fading_sink {
+            sink.play();
+        }
+
         if let Some(sink) = &self.sink {
             sink.play();
             info!("Playback started");
@@ -493,11 +840,15 @@ impl PlayerInternal {
     }
 
     fn pause(&mut self) -> Result<()> {
+        if let Some(sink) = &self.fading_sink {
+            sink.pause();
+        }
+
         if let Some(sink) = &self.sink {
             sink.pause();
             info!("Playback paused");
 
-            let position = sink.get_pos();
+            let position = self.track_position(sink);
             if let Some(track_index) = self.current_track_index {
                 let track_index = self.get_mapped_track_index(track_index);
                 self.event_sender.send(PlayerEvent::Paused {
@@ -515,6 +866,8 @@ impl PlayerInternal {
     }
 
     fn stop(&mut self) -> Result<()> {
+        self.preloaded = None;
+        self.fading_sink = None;
         if let Some(sink) = self.sink.take() {
             sink.stop();
             info!("Playback stopped");
@@ -569,7 +922,7 @@ impl PlayerInternal {
         if let Some(index) = self.current_track_index {
             match &self.sink {
                 Some(sink) => {
-                    let need_adaptive = sink.get_pos() > Duration::from_secs(3);
+                    let need_adaptive = self.track_position(sink) > Duration::from_secs(3);
 
                     if self.adaptive_switching && need_adaptive {
                         self.load(Some(index), true, true)
@@ -607,6 +960,8 @@ impl PlayerInternal {
                 Ok(_) => {
                     info!("Seeking to position: {} s", position);
 
+                    // Seeking moves the sink to the absolute position
+                    self.skipped_silence = Duration::ZERO;
                     let position = sink.get_pos();
                     if let Some(track_index) = self.current_track_index {
                         let track_index = self.get_mapped_track_index(track_index);
@@ -657,7 +1012,11 @@ impl PlayerInternal {
         Ok(())
     }
 
-    fn add_to_playlist(&mut self, tracks: Vec<(PlayingItem, std::path::PathBuf)>, mode: AddMode) {
+    fn add_to_playlist(
+        &mut self,
+        tracks: Vec<(PlayingItem, std::path::PathBuf, Option<TrackRange>)>,
+        mode: AddMode,
+    ) {
         debug!("Adding tracks to playlist with mode: {:?}", mode);
         let insert_index = match mode {
             AddMode::PlayNext => {
@@ -677,6 +1036,7 @@ impl PlayerInternal {
                     PlaylistItem {
                         item: track.0,
                         path: track.1,
+                        range: track.2,
                     },
                 );
             }
@@ -685,6 +1045,7 @@ impl PlayerInternal {
                 .extend(tracks.into_iter().map(|track| PlaylistItem {
                     item: track.0,
                     path: track.1,
+                    range: track.2,
                 }));
         }
 
@@ -695,6 +1056,7 @@ impl PlayerInternal {
                 index: insert_index,
             },
         );
+        self.refresh_preload();
         self.schedule_playlist_update();
     }
 
@@ -706,6 +1068,7 @@ impl PlayerInternal {
                 self.playlist.len(),
                 UpdateReason::RemoveFromPlaylist { index },
             );
+            self.refresh_preload();
             self.schedule_playlist_update();
         } else {
             bail!(
@@ -724,6 +1087,11 @@ impl PlayerInternal {
         self.current_track_index = None;
         self.sink = None;
         self._stream = None;
+        self.stream_handle = None;
+        self.preloaded = None;
+        self.fading_sink = None;
+        self.replay_gains.clear();
+        self.silence_profiles.clear();
         info!("Playlist cleared");
         self.event_sender
             .send(PlayerEvent::Stopped)
@@ -742,6 +1110,7 @@ impl PlayerInternal {
             PlaybackMode::RepeatAll => Box::new(RepeatAllStrategy),
             PlaybackMode::Shuffle => Box::new(ShuffleStrategy::new(self.playlist.len())),
         };
+        self.refresh_preload();
         self.send_progress()?;
         info!("Playback mode set to {:?}", mode);
 
@@ -754,6 +1123,22 @@ impl PlayerInternal {
     }
 
     fn send_progress(&mut self) -> Result<()> {
+        if self
+            .preloaded
+            .as_ref()
+            .is_some_and(|preloaded| preloaded.handle.has_started())
+        {
+            self.advance_to_preloaded()?;
+        }
+
+        if self.fading_sink.as_ref().is_some_and(|sink| sink.empty()) {
+            self.fading_sink = None;
+        }
+
+        if self.should_start_crossfade() {
+            self.start_crossfade()?;
+        }
+
         let id = self.current_item.clone();
         let index = self.current_track_index;
         let index = index.map(|x| self.get_mapped_track_index(x));
@@ -761,7 +1146,7 @@ impl PlayerInternal {
         let playback_mode = self.playback_mode;
 
         if let Some(sink) = &self.sink {
-            let position = sink.get_pos();
+            let position = self.track_position(sink);
 
             if sink.empty() {
                 self.event_sender
@@ -846,6 +1231,7 @@ impl PlayerInternal {
             }
         }
 
+        self.refresh_preload();
         self.schedule_playlist_update();
     }
 
@@ -865,9 +1251,7 @@ impl PlayerInternal {
 
     fn set_volume(&mut self, volume: f32) -> Result<()> {
         self.volume = volume;
-        if let Some(sink) = &self.sink {
-            sink.set_volume(volume);
-        }
+        self.apply_sink_volume();
         self.event_sender
             .send(PlayerEvent::VolumeUpdate(volume))
             .with_context(|| "Failed to send VolumeUpdate event")?;
@@ -890,4 +1274,87 @@ impl PlayerInternal {
 
         Ok(())
     }
+
+    fn normalization_factor(&self, item: Option<&PlayingItem>) -> f32 {
+        item.and_then(|item| self.replay_gains.get(item))
+            .map(|gain| gain.factor(self.normalization_mode))
+            .unwrap_or(1.0)
+    }
+
+    /// Applies the volume to the sink and the normalization factors to the
+    /// current and the preloaded track, each track carries its own gain.
+    fn apply_sink_volume(&self) {
+        if let Some(sink) = &self.sink {
+            sink.set_volume(self.volume);
+        }
+
+        // The outgoing track of a crossfade follows the volume too
+        if let Some(sink) = &self.fading_sink {
+            sink.set_volume(self.volume);
+        }
+
+        if let Some(handle) = &self.current_handle {
+            handle.set_gain(self.normalization_factor(self.current_item.as_ref()));
+        }
+
+        if let Some(preloaded) = &self.preloaded {
+            preloaded
+                .handle
+                .set_gain(self.normalization_factor(Some(&preloaded.item)));
+        }
+    }
+
+    fn set_normalization_mode(&mut self, mode: NormalizationMode) -> Result<()> {
+        self.normalization_mode = mode;
+        self.apply_sink_volume();
+
+        info!("Normalization mode set to {:?}", mode);
+
+        Ok(())
+    }
+
+    fn update_replay_gain(&mut self, gains: Vec<(PlayingItem, ReplayGainInfo)>) -> Result<()> {
+        debug!("Updating replay gain for {} items", gains.len());
+        // Gains arrive with every playlist update, so items that left the
+        // playlist are dropped here
+        let items: HashSet<&PlayingItem> = self.playlist.iter().map(|x| &x.item).collect();
+        self.replay_gains.retain(|item, _| items.contains(item));
+        self.replay_gains.extend(gains);
+        self.apply_sink_volume();
+
+        Ok(())
+    }
+
+    fn set_crossfade(&mut self, settings: CrossfadeSettings) -> Result<()> {
+        self.crossfade = settings;
+
+        info!("Crossfade set to {:?}", settings);
+
+        Ok(())
+    }
+
+    fn update_silence_profile(
+        &mut self,
+        profiles: Vec<(PlayingItem, SilenceProfile)>,
+    ) -> Result<()> {
+        debug!("Updating silence profile for {} items", profiles.len());
+        // Profiles arrive with every playlist update, like the replay gains
+        let items: HashSet<&PlayingItem> = self.playlist.iter().map(|x| &x.item).collect();
+        self.silence_profiles.retain(|item, _| items.contains(item));
+        self.silence_profiles.extend(profiles);
+
+        Ok(())
+    }
+
+    fn set_dsp(&mut self, settings: DspSettings) -> Result<()> {
+        info!(
+            "DSP chain updated, enabled: {}, preamp: {} dB, bands: {}",
+            settings.enabled,
+            settings.preamp,
+            settings.bands.len()
+        );
+        self.dsp.set(settings);
+
+        Ok(())
+    }
 }
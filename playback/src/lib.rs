@@ -1,3 +1,4 @@
+mod gapless;
 mod internal;
 mod realtime_fft;
 mod sfx_internal;
@@ -5,8 +6,13 @@ mod shared_source;
 
 pub mod buffered;
 pub mod controller;
+pub mod crossfade;
+pub mod dsp;
+pub mod limiter;
+pub mod normalization;
 pub mod output_stream;
 pub mod player;
+pub mod range;
 pub mod sfx_player;
 pub mod strategies;
 
@@ -22,4 +28,4 @@ pub use souvlaki::{MediaMetadata, MediaPlayback, MediaPosition};
 pub use internal::{PlayerCommand, PlayerEvent};
 
 #[cfg(target_os = "android")]
-pub mod android_utils;
\ No newline at end of file
+pub mod android_utils;
@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::Source;
+
+/// Highest amplitude the limiter lets through, slightly below full scale so
+/// the output stays clear of it after format conversion.
+pub const LIMITER_THRESHOLD: f32 = 0.98;
+
+/// Time the gain takes to recover by about two thirds after a peak.
+const RELEASE_TIME: f32 = 0.05;
+
+/// Internal function that builds a `Limiter` object.
+#[inline]
+pub fn limiter<I>(input: I) -> Limiter<I>
+where
+    I: Source<Item = f32>,
+{
+    Limiter {
+        source: input,
+        gain: 1.0,
+        release: 0.0,
+        channels: 0,
+        sample_rate: 0,
+        channel_index: 0,
+    }
+}
+
+/// Keeps the samples of the inner source under `LIMITER_THRESHOLD`.
+///
+/// Normalization and equalizer boosts can push peaks past full scale. The
+/// gain drops at once to keep a peak under the threshold and recovers
+/// smoothly afterwards, so signals that never reach the threshold pass
+/// through untouched. All channels share the gain to keep the stereo image.
+pub struct Limiter<I>
+where
+    I: Source<Item = f32>,
+{
+    source: I,
+    gain: f32,
+    /// Per-sample release coefficient
+    release: f32,
+    channels: u16,
+    sample_rate: u32,
+    channel_index: u16,
+}
+
+impl<I> Limiter<I>
+where
+    I: Source<Item = f32>,
+{
+    fn refresh(&mut self) {
+        let channels = self.source.channels().max(1);
+        let sample_rate = self.source.sample_rate().max(1);
+
+        if self.channels == channels && self.sample_rate == sample_rate {
+            return;
+        }
+
+        self.channels = channels;
+        self.sample_rate = sample_rate;
+        let samples = RELEASE_TIME * sample_rate as f32 * channels as f32;
+        self.release = (-1.0 / samples).exp();
+    }
+
+    #[inline]
+    fn process(&mut self, sample: f32) -> f32 {
+        let level = sample.abs();
+
+        if level * self.gain > LIMITER_THRESHOLD {
+            self.gain = LIMITER_THRESHOLD / level;
+        } else if self.gain < 1.0 {
+            self.gain = 1.0 - (1.0 - self.gain) * self.release;
+            // Recovering must not push the current sample over either
+            if level * self.gain > LIMITER_THRESHOLD {
+                self.gain = LIMITER_THRESHOLD / level;
+            }
+        }
+
+        sample * self.gain
+    }
+}
+
+impl<I> Iterator for Limiter<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.channel_index == 0 {
+            self.refresh();
+        }
+
+        let sample = self.source.next()?;
+        let result = self.process(sample);
+
+        self.channel_index += 1;
+        if self.channel_index >= self.channels {
+            self.channel_index = 0;
+        }
+
+        Some(result)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<I> Source for Limiter<I>
+where
+    I: Source<Item = f32>,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn sine(amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|i| {
+                let x = amplitude * (i as f32 * 0.05).sin();
+                [x, x]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quiet_signal_passes_untouched() {
+        let input = sine(0.5, 4410);
+        let output: Vec<f32> = limiter(SamplesBuffer::new(2, 44100, input.clone())).collect();
+
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_loud_signal_stays_under_threshold() {
+        let input = sine(2.5, 44100);
+        let output: Vec<f32> = limiter(SamplesBuffer::new(2, 44100, input)).collect();
+
+        let peak = output.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        assert!(peak <= LIMITER_THRESHOLD + 1e-6, "peak was {}", peak);
+        assert!(
+            peak > 0.9,
+            "limiter should not squash the signal, peak was {}",
+            peak
+        );
+    }
+
+    #[test]
+    fn test_gain_recovers_after_peak() {
+        let mut input = vec![3.0, 3.0];
+        input.extend(sine(0.5, 44100));
+        let output: Vec<f32> = limiter(SamplesBuffer::new(2, 44100, input.clone())).collect();
+
+        // A second after the peak the gain is back to unity
+        let tail = input.len() - 200;
+        for (x, y) in input[tail..].iter().zip(&output[tail..]) {
+            assert!((x - y).abs() < 1e-4, "{} was limited to {}", x, y);
+        }
+    }
+}
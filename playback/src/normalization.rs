@@ -0,0 +1,110 @@
+/// How the player compensates loudness differences between tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormalizationMode {
+    #[default]
+    Disabled,
+    Track,
+    Album,
+}
+
+impl From<u32> for NormalizationMode {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => NormalizationMode::Track,
+            2 => NormalizationMode::Album,
+            _ => NormalizationMode::Disabled,
+        }
+    }
+}
+
+impl From<NormalizationMode> for u32 {
+    fn from(mode: NormalizationMode) -> Self {
+        match mode {
+            NormalizationMode::Disabled => 0,
+            NormalizationMode::Track => 1,
+            NormalizationMode::Album => 2,
+        }
+    }
+}
+
+/// Per-track gain information. Gains are ReplayGain 2.0 values in dB
+/// (reference -18 LUFS), peaks are linear amplitudes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayGainInfo {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGainInfo {
+    /// Returns the linear amplification factor for the given mode.
+    ///
+    /// Album mode falls back to track values when album values are missing.
+    /// The factor is limited so that the known peak never exceeds full scale,
+    /// peaks the tags miss are caught by the limiter at the end of the output
+    /// chain.
+    pub fn factor(&self, mode: NormalizationMode) -> f32 {
+        let (gain, peak) = match mode {
+            NormalizationMode::Disabled => return 1.0,
+            NormalizationMode::Track => (self.track_gain, self.track_peak),
+            NormalizationMode::Album => match self.album_gain {
+                Some(gain) => (Some(gain), self.album_peak.or(self.track_peak)),
+                None => (self.track_gain, self.track_peak),
+            },
+        };
+
+        let Some(gain) = gain else {
+            return 1.0;
+        };
+
+        let factor = 10.0_f32.powf(gain / 20.0);
+
+        match peak {
+            Some(peak) if peak > 0.0 => factor.min(1.0 / peak),
+            _ => factor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factor_follows_gain() {
+        let info = ReplayGainInfo {
+            track_gain: Some(-6.0),
+            ..Default::default()
+        };
+
+        assert!((info.factor(NormalizationMode::Track) - 0.501).abs() < 1e-3);
+        assert_eq!(info.factor(NormalizationMode::Disabled), 1.0);
+    }
+
+    #[test]
+    fn test_factor_respects_peak() {
+        let info = ReplayGainInfo {
+            track_gain: Some(6.0),
+            track_peak: Some(0.8),
+            ..Default::default()
+        };
+
+        assert_eq!(info.factor(NormalizationMode::Track), 1.25);
+    }
+
+    #[test]
+    fn test_album_falls_back_to_track() {
+        let info = ReplayGainInfo {
+            track_gain: Some(-6.0),
+            track_peak: Some(0.5),
+            album_gain: None,
+            album_peak: None,
+        };
+
+        assert_eq!(
+            info.factor(NormalizationMode::Album),
+            info.factor(NormalizationMode::Track)
+        );
+    }
+}
@@ -8,7 +8,11 @@ use simple_channel::{SimpleChannel, SimpleReceiver, SimpleSender};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
+use crate::crossfade::{CrossfadeSettings, SilenceProfile};
+use crate::dsp::DspSettings;
 use crate::internal::{InternalLog, PlaybackMode, PlayerCommand, PlayerEvent, PlayerInternal};
+use crate::normalization::{NormalizationMode, ReplayGainInfo};
+use crate::range::TrackRange;
 use crate::strategies::AddMode;
 
 #[derive(Debug, Clone)]
@@ -85,7 +89,11 @@ pub trait Playable: Send {
     fn previous(&self);
     fn switch(&self, index: usize);
     fn seek(&self, position_ms: f64);
-    fn add_to_playlist(&self, tracks: Vec<(PlayingItem, PathBuf)>, mode: AddMode);
+    fn add_to_playlist(
+        &self,
+        tracks: Vec<(PlayingItem, PathBuf, Option<TrackRange>)>,
+        mode: AddMode,
+    );
     fn remove_from_playlist(&self, index: usize);
     fn clear_playlist(&self);
     fn move_playlist_item(&self, old_index: usize, new_index: usize);
@@ -93,6 +101,11 @@ pub trait Playable: Send {
     fn set_volume(&mut self, volume: f32);
     fn set_realtime_fft_enabled(&mut self, enabled: bool);
     fn set_adaptive_switching_enabled(&mut self, enabled: bool);
+    fn set_normalization_mode(&mut self, mode: NormalizationMode);
+    fn update_replay_gain(&self, gains: Vec<(PlayingItem, ReplayGainInfo)>);
+    fn set_crossfade(&mut self, settings: CrossfadeSettings);
+    fn update_silence_profile(&self, profiles: Vec<(PlayingItem, SilenceProfile)>);
+    fn set_dsp(&mut self, settings: DspSettings);
     fn terminate(&self);
     fn get_status(&self) -> PlayerStatus;
     fn get_playlist(&self) -> Vec<PlayingItem>;
@@ -361,7 +374,11 @@ impl Playable for Player {
         self.command(PlayerCommand::Seek(position_ms));
     }
 
-    fn add_to_playlist(&self, tracks: Vec<(PlayingItem, PathBuf)>, mode: AddMode) {
+    fn add_to_playlist(
+        &self,
+        tracks: Vec<(PlayingItem, PathBuf, Option<TrackRange>)>,
+        mode: AddMode,
+    ) {
         self.command(PlayerCommand::AddToPlaylist { tracks, mode });
     }
 
@@ -396,6 +413,26 @@ impl Playable for Player {
         self.command(PlayerCommand::SetAdaptiveSwitchingEnabled(enabled));
     }
 
+    fn set_normalization_mode(&mut self, mode: NormalizationMode) {
+        self.command(PlayerCommand::SetNormalizationMode(mode));
+    }
+
+    fn update_replay_gain(&self, gains: Vec<(PlayingItem, ReplayGainInfo)>) {
+        self.command(PlayerCommand::UpdateReplayGain(gains));
+    }
+
+    fn set_crossfade(&mut self, settings: CrossfadeSettings) {
+        self.command(PlayerCommand::SetCrossfade(settings));
+    }
+
+    fn update_silence_profile(&self, profiles: Vec<(PlayingItem, SilenceProfile)>) {
+        self.command(PlayerCommand::UpdateSilenceProfile(profiles));
+    }
+
+    fn set_dsp(&mut self, settings: DspSettings) {
+        self.command(PlayerCommand::SetDsp(settings));
+    }
+
     fn terminate(&self) {
         self.cancellation_token.cancel();
     }
@@ -444,7 +481,12 @@ impl Playable for MockPlayer {
     fn previous(&self) {}
     fn switch(&self, _index: usize) {}
     fn seek(&self, _position_ms: f64) {}
-    fn add_to_playlist(&self, _tracks: Vec<(PlayingItem, PathBuf)>, _mode: AddMode) {}
+    fn add_to_playlist(
+        &self,
+        _tracks: Vec<(PlayingItem, PathBuf, Option<TrackRange>)>,
+        _mode: AddMode,
+    ) {
+    }
     fn remove_from_playlist(&self, _index: usize) {}
     fn clear_playlist(&self) {}
     fn move_playlist_item(&self, _old_index: usize, _new_index: usize) {}
@@ -452,6 +494,11 @@ impl Playable for MockPlayer {
     fn set_volume(&mut self, _volume: f32) {}
     fn set_realtime_fft_enabled(&mut self, _enabled: bool) {}
     fn set_adaptive_switching_enabled(&mut self, _enabled: bool) {}
+    fn set_normalization_mode(&mut self, _mode: NormalizationMode) {}
+    fn update_replay_gain(&self, _gains: Vec<(PlayingItem, ReplayGainInfo)>) {}
+    fn set_crossfade(&mut self, _settings: CrossfadeSettings) {}
+    fn update_silence_profile(&self, _profiles: Vec<(PlayingItem, SilenceProfile)>) {}
+    fn set_dsp(&mut self, _settings: DspSettings) {}
     fn terminate(&self) {}
     fn get_status(&self) -> PlayerStatus {
         PlayerStatus {
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use log::warn;
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+
+/// The part of an audio file that makes up a track. Tracks of a CUE sheet
+/// share one file and only play between their own offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TrackRange {
+    pub start: Duration,
+    /// `None` plays until the end of the file
+    pub end: Option<Duration>,
+}
+
+impl TrackRange {
+    pub fn new(start: Duration, end: Option<Duration>) -> Self {
+        Self { start, end }
+    }
+
+    /// Length of the range, `file_duration` is used for open ended ranges.
+    pub fn duration(&self, file_duration: Option<Duration>) -> Option<Duration> {
+        let end = self.end.or(file_duration)?;
+        Some(end.saturating_sub(self.start))
+    }
+}
+
+/// Internal function that builds a `Ranged` object.
+///
+/// The inner source is moved to the start of the range right away.
+#[inline]
+pub fn ranged<I>(mut input: I, range: TrackRange) -> Ranged<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    if !range.start.is_zero() {
+        if let Err(e) = input.try_seek(range.start) {
+            warn!("Failed to seek to the start of the track: {:#?}", e);
+        }
+    }
+
+    Ranged {
+        source: input,
+        range,
+        elapsed_frames: 0,
+        sample_in_frame: 0,
+    }
+}
+
+/// A source that only plays a range of the inner source. Positions reported
+/// to and received from the sink are relative to the start of the range.
+pub struct Ranged<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    source: I,
+    range: TrackRange,
+    elapsed_frames: u64,
+    sample_in_frame: u16,
+}
+
+impl<I> Ranged<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    fn frames_of(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.source.sample_rate() as f64).round() as u64
+    }
+
+    fn has_reached_end(&self) -> bool {
+        match self.range.duration(None) {
+            Some(length) => self.elapsed_frames >= self.frames_of(length),
+            None => false,
+        }
+    }
+}
+
+impl<I> Iterator for Ranged<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.sample_in_frame == 0 && self.has_reached_end() {
+            return None;
+        }
+
+        let sample = self.source.next()?;
+
+        self.sample_in_frame += 1;
+        if self.sample_in_frame >= self.source.channels().max(1) {
+            self.sample_in_frame = 0;
+            self.elapsed_frames += 1;
+        }
+
+        Some(sample)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.source.size_hint().1)
+    }
+}
+
+impl<I> Source for Ranged<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.range.duration(self.source.total_duration())
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let pos = match self.range.duration(None) {
+            Some(length) => pos.min(length),
+            None => pos,
+        };
+
+        self.source.try_seek(self.range.start + pos)?;
+        self.elapsed_frames = self.frames_of(pos);
+        self.sample_in_frame = 0;
+
+        Ok(())
+    }
+}
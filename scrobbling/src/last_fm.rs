@@ -6,7 +6,10 @@ use async_trait::async_trait;
 use md5;
 use reqwest::{Client, Response};
 
-use crate::{AuthResponse, ScrobblingClient, ScrobblingTrack};
+use crate::{check_audioscrobbler_response, AuthResponse, ScrobblingClient, ScrobblingTrack};
+
+/// Scrobbles the API accepts in a single `track.scrobble` call.
+const MAX_BATCH_SIZE: usize = 50;
 
 #[derive(Clone)]
 pub struct LastFmClient {
@@ -165,6 +168,57 @@ impl ScrobblingClient for LastFmClient {
         Ok(response)
     }
 
+    async fn scrobble_batch(&self, tracks: &[ScrobblingTrack]) -> Result<()> {
+        if self.session_key.is_none() {
+            bail!("Not authenticated");
+        }
+
+        if tracks.len() > MAX_BATCH_SIZE {
+            bail!("At most {} tracks can be scrobbled at once", MAX_BATCH_SIZE);
+        }
+
+        let mut params = HashMap::new();
+        params.insert("method".to_string(), "track.scrobble".to_string());
+        params.insert("api_key".to_string(), self.api_key.clone());
+        params.insert("sk".to_string(), self.session_key.clone().unwrap());
+
+        for (i, track) in tracks.iter().enumerate() {
+            params.insert(format!("artist[{}]", i), track.artist.clone());
+            params.insert(format!("track[{}]", i), track.track.clone());
+            params.insert(
+                format!("timestamp[{}]", i),
+                track.timestamp_or_now().to_string(),
+            );
+
+            if let Some(album) = &track.album {
+                params.insert(format!("album[{}]", i), album.clone());
+            }
+            if let Some(album_artist) = &track.album_artist {
+                params.insert(format!("albumArtist[{}]", i), album_artist.clone());
+            }
+            if let Some(duration) = track.duration {
+                params.insert(format!("duration[{}]", i), duration.to_string());
+            }
+        }
+
+        let api_sig = self.generate_signature(&mut params);
+        params.insert("api_sig".to_string(), api_sig);
+
+        let response = self
+            .client
+            .post(&self.base_url)
+            .form(&params)
+            .query(&[("format", "json")])
+            .send()
+            .await?;
+
+        check_audioscrobbler_response(response).await
+    }
+
+    fn max_batch_size(&self) -> usize {
+        MAX_BATCH_SIZE
+    }
+
     fn session_key(&self) -> Option<&str> {
         self.session_key.as_deref()
     }
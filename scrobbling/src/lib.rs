@@ -2,11 +2,16 @@ pub mod last_fm;
 pub mod libre_fm;
 pub mod listen_brainz;
 pub mod manager;
+pub mod queue;
 
-use anyhow::Result;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
 use async_trait::async_trait;
-use reqwest::Response;
+use reqwest::{Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Debug, Deserialize)]
 struct AuthResponse {
@@ -20,7 +25,7 @@ struct SessionInfo {
     key: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct ScrobblingTrack {
     pub artist: String,
     pub track: String,
@@ -30,10 +35,91 @@ pub struct ScrobblingTrack {
     pub timestamp: Option<u64>,
 }
 
+impl ScrobblingTrack {
+    /// The time the track was played, or now if it was never set.
+    pub fn timestamp_or_now(&self) -> u64 {
+        self.timestamp.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        })
+    }
+}
+
+/// Audioscrobbler error codes for an offline, unavailable or rate limited
+/// service.
+const TRANSIENT_AUDIOSCROBBLER_ERRORS: [i64; 3] = [11, 16, 29];
+
+/// A failure of the service rather than of the request, such as an outage
+/// or rate limiting. The same request may succeed later.
+#[derive(Debug)]
+pub struct TransientError(pub String);
+
+impl fmt::Display for TransientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TransientError {}
+
+/// Whether a request failed because the service could not be reached or
+/// was unavailable, rather than because it rejected the request.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<reqwest::Error>().is_some()
+        || error.downcast_ref::<TransientError>().is_some()
+}
+
+/// The error for a response with an unsuccessful status.
+fn status_error(status: StatusCode, text: &str) -> anyhow::Error {
+    let message = format!("Request failed ({}): {}", status, text);
+
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        TransientError(message).into()
+    } else {
+        anyhow::anyhow!(message)
+    }
+}
+
+/// Checks a response of the Audioscrobbler 2.0 API, which reports some
+/// failures with a successful status and an error in the body.
+async fn check_audioscrobbler_response(response: Response) -> Result<()> {
+    let status = response.status();
+    let text = response.text().await?;
+
+    if !status.is_success() {
+        return Err(status_error(status, &text));
+    }
+
+    if let Ok(json) = serde_json::from_str::<Value>(&text) {
+        if let Some(error) = json.get("error") {
+            let message = format!(
+                "Request failed ({}): {}",
+                error,
+                json["message"].as_str().unwrap_or("Unknown error")
+            );
+
+            if error
+                .as_i64()
+                .is_some_and(|x| TRANSIENT_AUDIOSCROBBLER_ERRORS.contains(&x))
+            {
+                return Err(TransientError(message).into());
+            }
+            bail!(message);
+        }
+    }
+
+    Ok(())
+}
+
 #[async_trait]
 pub trait ScrobblingClient: Send {
     async fn authenticate(&mut self, username: &str, password: &str) -> Result<()>;
     async fn update_now_playing(&self, track: &ScrobblingTrack) -> Result<Response>;
     async fn scrobble(&self, track: &ScrobblingTrack) -> Result<Response>;
+    /// Submits several scrobbles in one request, at most `max_batch_size`.
+    async fn scrobble_batch(&self, tracks: &[ScrobblingTrack]) -> Result<()>;
+    fn max_batch_size(&self) -> usize;
     fn session_key(&self) -> Option<&str>;
 }
@@ -5,7 +5,10 @@ use anyhow::{bail, Result};
 use async_trait::async_trait;
 use reqwest::{Client, Response};
 
-use crate::{AuthResponse, ScrobblingClient, ScrobblingTrack};
+use crate::{check_audioscrobbler_response, AuthResponse, ScrobblingClient, ScrobblingTrack};
+
+/// Scrobbles the API accepts in a single `track.scrobble` call.
+const MAX_BATCH_SIZE: usize = 50;
 
 #[derive(Clone)]
 pub struct LibreFmClient {
@@ -23,6 +26,16 @@ impl LibreFmClient {
             base_url: "https://libre.fm/2.0/".to_string(),
         })
     }
+
+    /// A signed in client talking to another server, for tests.
+    #[cfg(test)]
+    pub(crate) fn with_base_url(base_url: String, session_key: &str) -> Result<Self> {
+        Ok(LibreFmClient {
+            session_key: Some(session_key.to_string()),
+            base_url,
+            ..Self::new()?
+        })
+    }
 }
 
 #[async_trait]
@@ -147,6 +160,54 @@ impl ScrobblingClient for LibreFmClient {
         Ok(response)
     }
 
+    async fn scrobble_batch(&self, tracks: &[ScrobblingTrack]) -> Result<()> {
+        if self.session_key.is_none() {
+            bail!("Not authenticated");
+        }
+
+        if tracks.len() > MAX_BATCH_SIZE {
+            bail!("At most {} tracks can be scrobbled at once", MAX_BATCH_SIZE);
+        }
+
+        let mut params = HashMap::new();
+        params.insert("method".to_string(), "track.scrobble".to_string());
+        params.insert("api_key".to_string(), "0".repeat(32));
+        params.insert("sk".to_string(), self.session_key.clone().unwrap());
+
+        for (i, track) in tracks.iter().enumerate() {
+            params.insert(format!("artist[{}]", i), track.artist.clone());
+            params.insert(format!("track[{}]", i), track.track.clone());
+            params.insert(
+                format!("timestamp[{}]", i),
+                track.timestamp_or_now().to_string(),
+            );
+
+            if let Some(album) = &track.album {
+                params.insert(format!("album[{}]", i), album.clone());
+            }
+            if let Some(album_artist) = &track.album_artist {
+                params.insert(format!("albumArtist[{}]", i), album_artist.clone());
+            }
+            if let Some(duration) = track.duration {
+                params.insert(format!("duration[{}]", i), duration.to_string());
+            }
+        }
+
+        let response = self
+            .client
+            .post(&self.base_url)
+            .form(&params)
+            .query(&[("format", "json")])
+            .send()
+            .await?;
+
+        check_audioscrobbler_response(response).await
+    }
+
+    fn max_batch_size(&self) -> usize {
+        MAX_BATCH_SIZE
+    }
+
     fn session_key(&self) -> Option<&str> {
         self.session_key.as_deref()
     }
@@ -7,7 +7,10 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::{ScrobblingClient, ScrobblingTrack};
+use crate::{status_error, ScrobblingClient, ScrobblingTrack};
+
+/// Listens the API accepts in a single `submit-listens` call.
+const MAX_LISTENS_PER_REQUEST: usize = 1000;
 
 impl From<&ScrobblingTrack> for Value {
     fn from(track: &ScrobblingTrack) -> Self {
@@ -75,10 +78,11 @@ impl ListenBrainzClient {
                 .send()
                 .await?;
 
-            if response.status().is_success() {
+            let status = response.status();
+            if status.is_success() {
                 Ok(response)
             } else {
-                bail!("Request failed: {:?}", response.text().await?)
+                Err(status_error(status, &response.text().await?))
             }
         } else {
             bail!("Client is not authenticated.")
@@ -152,6 +156,46 @@ impl ScrobblingClient for ListenBrainzClient {
         self.post_request("1/submit-listens", &body).await
     }
 
+    async fn scrobble_batch(&self, tracks: &[ScrobblingTrack]) -> Result<()> {
+        if tracks.len() > MAX_LISTENS_PER_REQUEST {
+            bail!(
+                "At most {} listens can be submitted at once",
+                MAX_LISTENS_PER_REQUEST
+            );
+        }
+
+        let listens = tracks
+            .iter()
+            .map(|track| {
+                let mut payload = Map::new();
+                payload.insert(
+                    "listened_at".to_string(),
+                    Value::Number(track.timestamp_or_now().into()),
+                );
+                payload.insert("track_metadata".to_string(), track.into());
+                Value::Object(payload)
+            })
+            .collect();
+
+        // A single listen must be submitted as `single`, several as `import`
+        let listen_type = if tracks.len() == 1 {
+            "single"
+        } else {
+            "import"
+        };
+
+        let mut body = HashMap::new();
+        body.insert("listen_type", Value::String(listen_type.to_string()));
+        body.insert("payload", Value::Array(listens));
+
+        self.post_request("1/submit-listens", &body).await?;
+        Ok(())
+    }
+
+    fn max_batch_size(&self) -> usize {
+        MAX_LISTENS_PER_REQUEST
+    }
+
     fn session_key(&self) -> Option<&str> {
         self.session_key.as_deref()
     }
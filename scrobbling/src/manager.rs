@@ -1,5 +1,6 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::fmt;
+use std::future::Future;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -14,7 +15,14 @@ use tokio::time::sleep;
 use crate::last_fm::LastFmClient;
 use crate::libre_fm::LibreFmClient;
 use crate::listen_brainz::ListenBrainzClient;
-use crate::{ScrobblingClient, ScrobblingTrack};
+use crate::queue::{MemoryScrobbleStore, ScrobbleStore};
+use crate::{is_transient, ScrobblingClient, ScrobblingTrack};
+
+/// Longest wait between two attempts to submit scrobbles.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Scrobbles kept until the services to queue them for are known.
+const MAX_EARLY_SCROBBLES: usize = 64;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ScrobblingService {
@@ -90,6 +98,9 @@ pub trait ScrobblingServiceManager: Send + Sync {
     ) -> Result<()>;
     async fn update_now_playing(&mut self, service: &ScrobblingService, track: ScrobblingTrack);
     fn restore_session(&mut self, service: &ScrobblingService, session_key: String) -> Result<()>;
+    /// Queues scrobbles for the services from now on, before they are
+    /// signed in. The first call also queues the scrobbles made before it.
+    async fn enable(&mut self, services: &[ScrobblingService]);
     fn update_now_playing_all(&mut self, track: ScrobblingTrack);
     async fn scrobble(&mut self, service: ScrobblingService, track: ScrobblingTrack);
     fn scrobble_all(&mut self, track: ScrobblingTrack);
@@ -115,7 +126,14 @@ pub struct ScrobblingManager {
 
     is_authenticating: bool,
     now_playing_cache: VecDeque<ScrobblingTrack>,
-    scrobble_cache: VecDeque<ScrobblingTrack>,
+
+    /// Services scrobbles are queued for, kept from the first sign in
+    /// attempt until logout so scrobbles made offline are not lost.
+    enabled: HashSet<ScrobblingService>,
+    /// Scrobbles made before the services were known, `None` once they are
+    early_scrobbles: Option<VecDeque<ScrobblingTrack>>,
+    store: Arc<dyn ScrobbleStore>,
+    flush_lock: Arc<Mutex<()>>,
 }
 
 pub struct ScrobblingCredential {
@@ -147,10 +165,20 @@ impl ScrobblingManager {
 
             is_authenticating: false,
             now_playing_cache: VecDeque::with_capacity(1),
-            scrobble_cache: VecDeque::with_capacity(48),
+
+            enabled: HashSet::new(),
+            early_scrobbles: Some(VecDeque::new()),
+            store: Arc::new(MemoryScrobbleStore::new()),
+            flush_lock: Arc::new(Mutex::new(())),
         }
     }
 
+    /// Replaces the store scrobbles wait in. Scrobbles left in it by earlier
+    /// runs are delivered once their service is signed in.
+    pub fn set_store(&mut self, store: Arc<dyn ScrobbleStore>) {
+        self.store = store;
+    }
+
     async fn process_cache(&mut self) {
         if self.is_authenticating {
             return;
@@ -160,9 +188,7 @@ impl ScrobblingManager {
             self.update_now_playing_all(track);
         }
 
-        while let Some(track) = self.scrobble_cache.pop_front() {
-            self.scrobble_all(track);
-        }
+        tokio::spawn(self.flush_all());
     }
 
     async fn retry_update_now_playing<T>(
@@ -190,36 +216,150 @@ impl ScrobblingManager {
         }
     }
 
-    async fn retry_scrobble<T>(
-        client: &mut T,
-        track: &ScrobblingTrack,
+    /// Submits a batch, repeating it while the service is unreachable or
+    /// unavailable and waiting twice as long each time. Rejections are
+    /// returned right away.
+    async fn retry_scrobble_batch<T>(
+        client: &T,
+        tracks: &[ScrobblingTrack],
         max_retries: u32,
         retry_delay: Duration,
     ) -> Result<()>
     where
-        T: ScrobblingClient + ?Sized,
+        T: ScrobblingClient + Sync + ?Sized,
     {
         let mut attempts = 0;
+        let mut delay = retry_delay;
 
         loop {
-            match client.scrobble(track).await {
+            match client.scrobble_batch(tracks).await {
                 Ok(_) => return Ok(()),
                 Err(e) => {
                     attempts += 1;
-                    if attempts >= max_retries {
+                    if attempts >= max_retries || !is_transient(&e) {
                         return Err(e);
                     }
-                    sleep(retry_delay).await;
+                    warn!("Retrying scrobbles in {:?}: {}", delay, e);
+                    sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
                 }
             }
         }
     }
 
+    /// Submits the pending scrobbles of a service in batches, oldest first.
+    async fn flush_queue<T>(
+        client: &T,
+        service: ScrobblingService,
+        store: &dyn ScrobbleStore,
+        max_retries: u32,
+        retry_delay: Duration,
+    ) -> Result<()>
+    where
+        T: ScrobblingClient + Sync + ?Sized,
+    {
+        loop {
+            let pending = store.pending(service, client.max_batch_size()).await?;
+            if pending.is_empty() {
+                return Ok(());
+            }
+
+            let ids: Vec<i32> = pending.iter().map(|x| x.id).collect();
+            let tracks: Vec<ScrobblingTrack> = pending.into_iter().map(|x| x.track).collect();
+
+            match Self::retry_scrobble_batch(client, &tracks, max_retries, retry_delay).await {
+                Ok(_) => {
+                    store.mark_delivered(&ids).await?;
+                    info!("Scrobbled {} tracks to {}", tracks.len(), service);
+                }
+                Err(e) => {
+                    // Scrobbles stay pending while the service is unreachable
+                    // or unavailable, only rejections count as failed attempts
+                    if !is_transient(&e) {
+                        store.mark_failed(&ids, &e.to_string()).await?;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Delivers what is pending for a service, if it is signed in.
+    fn flush(&self, service: ScrobblingService) -> impl Future<Output = ()> + Send + 'static {
+        let client: Option<Box<dyn ScrobblingClient + Sync>> = match service {
+            ScrobblingService::LastFm => self
+                .lastfm
+                .clone()
+                .map(|c| Box::new(c) as Box<dyn ScrobblingClient + Sync>),
+            ScrobblingService::LibreFm => self
+                .librefm
+                .clone()
+                .map(|c| Box::new(c) as Box<dyn ScrobblingClient + Sync>),
+            ScrobblingService::ListenBrainz => self
+                .listenbrainz
+                .clone()
+                .map(|c| Box::new(c) as Box<dyn ScrobblingClient + Sync>),
+        };
+        let store = Arc::clone(&self.store);
+        let flush_lock = Arc::clone(&self.flush_lock);
+        let max_retries = self.max_retries;
+        let retry_delay = self.retry_delay;
+        let error_sender = Arc::clone(&self.error_sender);
+
+        async move {
+            let Some(client) = client else {
+                return;
+            };
+
+            if client.session_key().is_none() {
+                warn!("Not authenticated to {}", service);
+                return;
+            }
+
+            // Concurrent flushes would submit the same scrobbles twice
+            let _guard = flush_lock.lock().await;
+            let result =
+                Self::flush_queue(&*client, service, &*store, max_retries, retry_delay).await;
+
+            if let Err(e) = result {
+                error!("Failed to scrobble to {}: {}", service, e);
+
+                error_sender.send(ScrobblingError {
+                    service,
+                    action: ActionType::Scrobbling,
+                    error: e,
+                });
+            }
+        }
+    }
+
+    fn flush_all(&self) -> impl Future<Output = ()> + Send + 'static {
+        let flushes: Vec<_> = [
+            ScrobblingService::LastFm,
+            ScrobblingService::LibreFm,
+            ScrobblingService::ListenBrainz,
+        ]
+        .into_iter()
+        .map(|service| self.flush(service))
+        .collect();
+
+        async move {
+            for flush in flushes {
+                flush.await;
+            }
+        }
+    }
+
     pub fn authenticate_all(
         manager: Arc<Mutex<dyn ScrobblingServiceManager>>,
         credentials_list: Vec<ScrobblingCredential>,
     ) {
         tokio::spawn(async move {
+            // Queue scrobbles for every service while the others sign in
+            let services: Vec<ScrobblingService> =
+                credentials_list.iter().map(|x| x.service).collect();
+            manager.lock().await.enable(&services).await;
+
             for credentials in credentials_list {
                 let mut manager = manager.lock().await;
                 let result = manager
@@ -279,6 +419,7 @@ impl ScrobblingServiceManager for ScrobblingManager {
         enable_retry: bool,
     ) -> Result<()> {
         self.is_authenticating = true;
+        self.enable(&[*service]).await;
         let mut attempts = 0;
 
         loop {
@@ -427,6 +568,21 @@ impl ScrobblingServiceManager for ScrobblingManager {
         Ok(())
     }
 
+    async fn enable(&mut self, services: &[ScrobblingService]) {
+        self.enabled.extend(services.iter().copied());
+
+        let Some(early_scrobbles) = self.early_scrobbles.take() else {
+            return;
+        };
+
+        let services: Vec<ScrobblingService> = self.enabled.iter().copied().collect();
+        for track in early_scrobbles {
+            if let Err(e) = self.store.push(&services, &track).await {
+                error!("Failed to queue scrobble: {}", e);
+            }
+        }
+    }
+
     fn update_now_playing_all(&mut self, track: ScrobblingTrack) {
         if self.is_authenticating {
             self.now_playing_cache.push_back(track);
@@ -484,137 +640,45 @@ impl ScrobblingServiceManager for ScrobblingManager {
         });
     }
 
-    async fn scrobble(&mut self, service: ScrobblingService, track: ScrobblingTrack) {
-        if self.is_authenticating {
-            self.scrobble_cache.push_back(track);
-            if self.scrobble_cache.len() > 48 {
-                self.scrobble_cache.pop_front();
-            }
+    async fn scrobble(&mut self, service: ScrobblingService, mut track: ScrobblingTrack) {
+        track.timestamp = Some(track.timestamp_or_now());
 
-            info!("Caching scrobble for {}", service);
+        if let Err(e) = self.store.push(&[service], &track).await {
+            error!("Failed to queue scrobble for {}: {}", service, e);
+        }
 
+        if self.is_authenticating {
+            info!("Queued scrobble for {}", service);
             return;
         }
 
-        let max_retries = self.max_retries;
-        let retry_delay = self.retry_delay;
-
-        let client: Option<&mut dyn ScrobblingClient> = match service {
-            ScrobblingService::LastFm => {
-                self.lastfm.as_mut().map(|c| c as &mut dyn ScrobblingClient)
-            }
-            ScrobblingService::LibreFm => self
-                .librefm
-                .as_mut()
-                .map(|c| c as &mut dyn ScrobblingClient),
-            ScrobblingService::ListenBrainz => self
-                .listenbrainz
-                .as_mut()
-                .map(|c| c as &mut dyn ScrobblingClient),
-        };
-
-        if let Some(client) = client {
-            if client.session_key().is_some() {
-                let result =
-                    ScrobblingManager::retry_scrobble(client, &track, max_retries, retry_delay)
-                        .await;
-
-                if let Err(e) = result {
-                    error!("Failed to scrobble to {}: {}", service, e);
-
-                    self.error_sender.send(ScrobblingError {
-                        service,
-                        action: ActionType::Scrobbling,
-                        error: e,
-                    });
-                } else {
-                    info!("Scrobbled to {}", service);
-                }
-            } else {
-                warn!("Not authenticated to {}", service);
-            }
-        }
+        self.flush(service).await;
     }
 
-    fn scrobble_all(&mut self, track: ScrobblingTrack) {
-        if self.is_authenticating {
-            self.scrobble_cache.push_back(track);
-            if self.scrobble_cache.len() > 48 {
-                self.scrobble_cache.pop_front();
+    fn scrobble_all(&mut self, mut track: ScrobblingTrack) {
+        track.timestamp = Some(track.timestamp_or_now());
+
+        if let Some(early_scrobbles) = &mut self.early_scrobbles {
+            early_scrobbles.push_back(track);
+            if early_scrobbles.len() > MAX_EARLY_SCROBBLES {
+                early_scrobbles.pop_front();
             }
 
+            info!("Keeping scrobble until the services are known");
             return;
         }
 
-        let lastfm = self.lastfm.clone();
-        let librefm = self.librefm.clone();
-        let listenbrainz = self.listenbrainz.clone();
-        let max_retries = self.max_retries;
-        let retry_delay = self.retry_delay;
-        let error_sender = Arc::clone(&self.error_sender);
+        let services: Vec<ScrobblingService> = self.enabled.iter().copied().collect();
+        let store = Arc::clone(&self.store);
+        let flush = (!self.is_authenticating).then(|| self.flush_all());
 
         tokio::spawn(async move {
-            // Handle Last.fm
-            if let Some(mut client) = lastfm {
-                if client.session_key.is_some() {
-                    let result = ScrobblingManager::retry_scrobble(
-                        &mut client,
-                        &track,
-                        max_retries,
-                        retry_delay,
-                    )
-                    .await;
-
-                    if let Err(e) = result {
-                        error_sender.send(ScrobblingError {
-                            service: ScrobblingService::LastFm,
-                            action: ActionType::Scrobbling,
-                            error: e,
-                        });
-                    }
-                }
-            }
-
-            // Handle Libre.fm
-            if let Some(mut client) = librefm {
-                if client.session_key.is_some() {
-                    let result = ScrobblingManager::retry_scrobble(
-                        &mut client,
-                        &track,
-                        max_retries,
-                        retry_delay,
-                    )
-                    .await;
-
-                    if let Err(e) = result {
-                        error_sender.send(ScrobblingError {
-                            service: ScrobblingService::LibreFm,
-                            action: ActionType::Scrobbling,
-                            error: e,
-                        });
-                    }
-                }
+            if let Err(e) = store.push(&services, &track).await {
+                error!("Failed to queue scrobble: {}", e);
             }
 
-            // Handle ListenBrainz
-            if let Some(mut client) = listenbrainz {
-                if client.session_key.is_some() {
-                    let result = ScrobblingManager::retry_scrobble(
-                        &mut client,
-                        &track,
-                        max_retries,
-                        retry_delay,
-                    )
-                    .await;
-
-                    if let Err(e) = result {
-                        error_sender.send(ScrobblingError {
-                            service: ScrobblingService::ListenBrainz,
-                            action: ActionType::Scrobbling,
-                            error: e,
-                        });
-                    }
-                }
+            if let Some(flush) = flush {
+                flush.await;
             }
         });
     }
@@ -635,6 +699,10 @@ impl ScrobblingServiceManager for ScrobblingManager {
             }
         }
 
+        // Scrobbles already queued stay pending, they are delivered if the
+        // service is signed in again
+        self.enabled.remove(&service);
+
         info!("Logged out from {}", service);
         self.send_login_status().await;
     }
@@ -708,6 +776,10 @@ impl ScrobblingServiceManager for MockScrobblingManager {
         Ok(())
     }
 
+    async fn enable(&mut self, _services: &[ScrobblingService]) {
+        // Mock implementation: do nothing
+    }
+
     fn update_now_playing_all(&mut self, _track: ScrobblingTrack) {
         // Mock implementation: do nothing
     }
@@ -736,3 +808,248 @@ impl ScrobblingServiceManager for MockScrobblingManager {
         Arc::clone(&self.error_sender)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex as StdMutex;
+    use std::time::Instant;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use crate::libre_fm::LibreFmClient;
+    use crate::queue::MAX_DELIVERY_ATTEMPTS;
+
+    use super::*;
+
+    const RETRY_DELAY: Duration = Duration::from_millis(20);
+
+    fn track() -> ScrobblingTrack {
+        ScrobblingTrack {
+            artist: "Artist".to_string(),
+            track: "Track".to_string(),
+            album: None,
+            album_artist: None,
+            duration: Some(180),
+            timestamp: Some(1_700_000_000),
+        }
+    }
+
+    async fn read_request(stream: &mut TcpStream) {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+
+        loop {
+            let n = stream.read(&mut buffer).await.unwrap();
+            if n == 0 {
+                return;
+            }
+            request.extend_from_slice(&buffer[..n]);
+
+            let text = String::from_utf8_lossy(&request);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+
+                if request.len() >= end + 4 + length {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Answers requests with the statuses and bodies in order, and records
+    /// when each request arrived.
+    async fn mock_server(
+        responses: Vec<(u16, &'static str)>,
+    ) -> (String, Arc<StdMutex<Vec<Instant>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/2.0/", listener.local_addr().unwrap());
+        let requests = Arc::new(StdMutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                read_request(&mut stream).await;
+                received.lock().unwrap().push(Instant::now());
+
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors_are_retried() {
+        let (url, requests) = mock_server(vec![
+            (503, "Service Unavailable"),
+            (429, "Too Many Requests"),
+            (
+                200,
+                r#"{"error": 16, "message": "Temporarily unavailable"}"#,
+            ),
+            (200, r#"{"scrobbles": {}}"#),
+        ])
+        .await;
+        let client = LibreFmClient::with_base_url(url, "session").unwrap();
+        let store = MemoryScrobbleStore::new();
+        store
+            .push(&[ScrobblingService::LibreFm], &track())
+            .await
+            .unwrap();
+
+        ScrobblingManager::flush_queue(&client, ScrobblingService::LibreFm, &store, 5, RETRY_DELAY)
+            .await
+            .unwrap();
+
+        assert!(store
+            .pending(ScrobblingService::LibreFm, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Each retry waits twice as long as the one before
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 4);
+        for (i, times) in requests.windows(2).enumerate() {
+            assert!(times[1] - times[0] >= RETRY_DELAY * 2u32.pow(i as u32));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors_keep_scrobbles_pending() {
+        let (url, requests) = mock_server(vec![
+            (503, "Service Unavailable");
+            2 * MAX_DELIVERY_ATTEMPTS as usize
+        ])
+        .await;
+        let client = LibreFmClient::with_base_url(url, "session").unwrap();
+        let store = MemoryScrobbleStore::new();
+        store
+            .push(&[ScrobblingService::LibreFm], &track())
+            .await
+            .unwrap();
+
+        for _ in 0..MAX_DELIVERY_ATTEMPTS {
+            let result = ScrobblingManager::flush_queue(
+                &client,
+                ScrobblingService::LibreFm,
+                &store,
+                2,
+                RETRY_DELAY,
+            )
+            .await;
+            assert!(is_transient(&result.unwrap_err()));
+        }
+        assert_eq!(
+            requests.lock().unwrap().len(),
+            2 * MAX_DELIVERY_ATTEMPTS as usize
+        );
+
+        // An unreachable service is not a rejection either
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/2.0/", listener.local_addr().unwrap());
+        drop(listener);
+        let client = LibreFmClient::with_base_url(url, "session").unwrap();
+        let result = ScrobblingManager::flush_queue(
+            &client,
+            ScrobblingService::LibreFm,
+            &store,
+            1,
+            RETRY_DELAY,
+        )
+        .await;
+        assert!(is_transient(&result.unwrap_err()));
+
+        assert_eq!(
+            store
+                .pending(ScrobblingService::LibreFm, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejections_count_as_attempts() {
+        let mut responses = vec![(200, r#"{"error": 6, "message": "Invalid parameters"}"#)];
+        responses.extend(vec![
+            (400, "Bad Request");
+            MAX_DELIVERY_ATTEMPTS as usize - 1
+        ]);
+        let (url, requests) = mock_server(responses).await;
+        let client = LibreFmClient::with_base_url(url, "session").unwrap();
+        let store = MemoryScrobbleStore::new();
+        store
+            .push(&[ScrobblingService::LibreFm], &track())
+            .await
+            .unwrap();
+
+        for _ in 0..MAX_DELIVERY_ATTEMPTS {
+            let result = ScrobblingManager::flush_queue(
+                &client,
+                ScrobblingService::LibreFm,
+                &store,
+                5,
+                RETRY_DELAY,
+            )
+            .await;
+            assert!(!is_transient(&result.unwrap_err()));
+        }
+
+        // Rejections are not retried, and the scrobble is given up on once
+        // it was rejected too often
+        assert_eq!(
+            requests.lock().unwrap().len(),
+            MAX_DELIVERY_ATTEMPTS as usize
+        );
+        assert!(store
+            .pending(ScrobblingService::LibreFm, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scrobbles_wait_for_the_services() {
+        let mut manager = ScrobblingManager::new(1, RETRY_DELAY);
+        let store = Arc::new(MemoryScrobbleStore::new());
+        manager.set_store(store.clone());
+
+        manager.scrobble_all(track());
+        manager.enable(&[ScrobblingService::ListenBrainz]).await;
+
+        let pending = store
+            .pending(ScrobblingService::ListenBrainz, 10)
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].track.track, "Track");
+
+        // Logging out keeps what is queued
+        manager.logout(ScrobblingService::ListenBrainz).await;
+        assert_eq!(
+            store
+                .pending(ScrobblingService::ListenBrainz, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
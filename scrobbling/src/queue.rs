@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::manager::ScrobblingService;
+use crate::ScrobblingTrack;
+
+/// Scrobbles rejected this many times are no longer submitted.
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 10;
+
+/// Scrobbles the in-memory store keeps, older ones are dropped first.
+const MAX_MEMORY_SCROBBLES: usize = 1024;
+
+/// A scrobble waiting to be delivered to one service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingScrobble {
+    pub id: i32,
+    pub track: ScrobblingTrack,
+}
+
+/// Where scrobbles wait until their service accepts them. Each service has
+/// its own delivery state, so a scrobble may reach Last.fm while it is
+/// still pending for ListenBrainz.
+#[async_trait]
+pub trait ScrobbleStore: Send + Sync {
+    /// Queues a track for the services. Tracks queued before for a service
+    /// with the same artist, title and timestamp are ignored.
+    async fn push(&self, services: &[ScrobblingService], track: &ScrobblingTrack) -> Result<()>;
+    /// Returns the oldest scrobbles of a service that are still worth
+    /// submitting.
+    async fn pending(
+        &self,
+        service: ScrobblingService,
+        limit: usize,
+    ) -> Result<Vec<PendingScrobble>>;
+    async fn mark_delivered(&self, ids: &[i32]) -> Result<()>;
+    /// Records a rejection, counting towards `MAX_DELIVERY_ATTEMPTS`.
+    async fn mark_failed(&self, ids: &[i32], error: &str) -> Result<()>;
+}
+
+#[derive(Debug)]
+struct MemoryScrobble {
+    id: i32,
+    service: ScrobblingService,
+    track: ScrobblingTrack,
+    attempts: u32,
+}
+
+#[derive(Debug, Default)]
+struct MemoryQueue {
+    next_id: i32,
+    scrobbles: VecDeque<MemoryScrobble>,
+}
+
+/// Keeps scrobbles until the process exits, used when no durable store is
+/// configured.
+#[derive(Debug, Default)]
+pub struct MemoryScrobbleStore {
+    queue: Mutex<MemoryQueue>,
+}
+
+impl MemoryScrobbleStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ScrobbleStore for MemoryScrobbleStore {
+    async fn push(&self, services: &[ScrobblingService], track: &ScrobblingTrack) -> Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+
+        for service in services {
+            let queued = queue.scrobbles.iter().any(|x| {
+                x.service == *service
+                    && x.track.artist == track.artist
+                    && x.track.track == track.track
+                    && x.track.timestamp == track.timestamp
+            });
+
+            if queued {
+                continue;
+            }
+
+            queue.next_id += 1;
+            let id = queue.next_id;
+            queue.scrobbles.push_back(MemoryScrobble {
+                id,
+                service: *service,
+                track: track.clone(),
+                attempts: 0,
+            });
+
+            if queue.scrobbles.len() > MAX_MEMORY_SCROBBLES {
+                queue.scrobbles.pop_front();
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn pending(
+        &self,
+        service: ScrobblingService,
+        limit: usize,
+    ) -> Result<Vec<PendingScrobble>> {
+        let queue = self.queue.lock().unwrap();
+
+        Ok(queue
+            .scrobbles
+            .iter()
+            .filter(|x| x.service == service && x.attempts < MAX_DELIVERY_ATTEMPTS)
+            .take(limit)
+            .map(|x| PendingScrobble {
+                id: x.id,
+                track: x.track.clone(),
+            })
+            .collect())
+    }
+
+    async fn mark_delivered(&self, ids: &[i32]) -> Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+        queue.scrobbles.retain(|x| !ids.contains(&x.id));
+
+        Ok(())
+    }
+
+    async fn mark_failed(&self, ids: &[i32], _error: &str) -> Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+        for scrobble in queue.scrobbles.iter_mut() {
+            if ids.contains(&scrobble.id) {
+                scrobble.attempts += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
 pub mod music_brainz;
 pub mod sampler;
 pub mod shazam;
+pub mod writer;
@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::{AudioFile, TaggedFile, TaggedFileExt};
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, ItemValue, Tag, TagItem, TagType};
+use once_cell::sync::Lazy;
+
+/// Keys produced by `metadata::reader` that can be written back, mapped to
+/// the generic lofty keys. lofty translates them into ID3v2 frames, Vorbis
+/// comments, MP4 atoms or APE items depending on the file.
+static WRITABLE_KEYS: Lazy<HashMap<&'static str, ItemKey>> = Lazy::new(|| {
+    HashMap::from([
+        ("track_title", ItemKey::TrackTitle),
+        ("track_subtitle", ItemKey::TrackSubtitle),
+        ("artist", ItemKey::TrackArtist),
+        ("album", ItemKey::AlbumTitle),
+        ("album_artist", ItemKey::AlbumArtist),
+        ("track_number", ItemKey::TrackNumber),
+        ("track_total", ItemKey::TrackTotal),
+        ("disc_number", ItemKey::DiscNumber),
+        ("disc_total", ItemKey::DiscTotal),
+        ("disc_subtitle", ItemKey::SetSubtitle),
+        ("genre", ItemKey::Genre),
+        ("date", ItemKey::RecordingDate),
+        ("release_date", ItemKey::ReleaseDate),
+        ("original_date", ItemKey::OriginalReleaseDate),
+        ("composer", ItemKey::Composer),
+        ("conductor", ItemKey::Conductor),
+        ("arranger", ItemKey::Arranger),
+        ("lyricist", ItemKey::Lyricist),
+        ("writer", ItemKey::Writer),
+        ("performer", ItemKey::Performer),
+        ("remixer", ItemKey::Remixer),
+        ("producer", ItemKey::Producer),
+        ("engineer", ItemKey::Engineer),
+        ("mix_dj", ItemKey::MixDj),
+        ("mix_engineer", ItemKey::MixEngineer),
+        ("label", ItemKey::Label),
+        ("ident_catalog_number", ItemKey::CatalogNumber),
+        ("ident_barcode", ItemKey::Barcode),
+        ("ident_isrc", ItemKey::Isrc),
+        ("bpm", ItemKey::Bpm),
+        ("initial_key", ItemKey::InitialKey),
+        ("mood", ItemKey::Mood),
+        ("language", ItemKey::Language),
+        ("script", ItemKey::Script),
+        ("comment", ItemKey::Comment),
+        ("description", ItemKey::Description),
+        ("copyright", ItemKey::CopyrightMessage),
+        ("license", ItemKey::License),
+        ("lyrics", ItemKey::Lyrics),
+        ("compilation", ItemKey::FlagCompilation),
+        ("content_group", ItemKey::ContentGroup),
+        ("encoded_by", ItemKey::EncodedBy),
+        ("encoder", ItemKey::EncoderSoftware),
+        ("encoder_settings", ItemKey::EncoderSettings),
+        ("original_album", ItemKey::OriginalAlbumTitle),
+        ("original_artist", ItemKey::OriginalArtist),
+        ("movement_name", ItemKey::Movement),
+        ("movement_number", ItemKey::MovementNumber),
+        ("sort_album", ItemKey::AlbumTitleSortOrder),
+        ("sort_album_artist", ItemKey::AlbumArtistSortOrder),
+        ("sort_artist", ItemKey::TrackArtistSortOrder),
+        ("sort_composer", ItemKey::ComposerSortOrder),
+        ("sort_track_title", ItemKey::TrackTitleSortOrder),
+        ("musicbrainz_recording_id", ItemKey::MusicBrainzRecordingId),
+        ("musicbrainz_release_track_id", ItemKey::MusicBrainzTrackId),
+        ("musicbrainz_album_id", ItemKey::MusicBrainzReleaseId),
+        (
+            "musicbrainz_release_group_id",
+            ItemKey::MusicBrainzReleaseGroupId,
+        ),
+        ("musicbrainz_artist_id", ItemKey::MusicBrainzArtistId),
+        (
+            "musicbrainz_album_artist_id",
+            ItemKey::MusicBrainzReleaseArtistId,
+        ),
+        ("musicbrainz_work_id", ItemKey::MusicBrainzWorkId),
+        ("replaygain_album_gain", ItemKey::ReplayGainAlbumGain),
+        ("replaygain_album_peak", ItemKey::ReplayGainAlbumPeak),
+        ("replaygain_track_gain", ItemKey::ReplayGainTrackGain),
+        ("replaygain_track_peak", ItemKey::ReplayGainTrackPeak),
+    ])
+});
+
+/// A change to a single field. Fields like `artist` may hold several
+/// values, they are written in order. No values removes the field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagEdit {
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+/// A change to the embedded front cover.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CoverArtEdit {
+    #[default]
+    Keep,
+    Remove,
+    /// Encoded image data, the format is detected from its content
+    Replace(Vec<u8>),
+}
+
+pub fn is_writable_key(key: &str) -> bool {
+    WRITABLE_KEYS.contains_key(key)
+}
+
+pub fn writable_keys() -> Vec<&'static str> {
+    let mut keys: Vec<&'static str> = WRITABLE_KEYS.keys().copied().collect();
+    keys.sort();
+    keys
+}
+
+fn item_key(key: &str) -> Result<&'static ItemKey> {
+    WRITABLE_KEYS
+        .get(key)
+        .with_context(|| format!("Field can not be written: {}", key))
+}
+
+/// Reads the tags of a file, the audio properties are not needed to edit
+/// them.
+fn read_tagged_file(path: &Path) -> Result<TaggedFile> {
+    Probe::open(path)
+        .and_then(|probe| {
+            probe
+                .options(ParseOptions::new().read_properties(false))
+                .read()
+        })
+        .with_context(|| format!("Failed to read tags: {}", path.display()))
+}
+
+fn primary_tag(tagged_file: &TaggedFile) -> Option<&Tag> {
+    tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+}
+
+/// Reads every current value of `keys`, fields that are not set have no
+/// values.
+pub fn read_tags(path: &Path, keys: &[String]) -> Result<Vec<TagEdit>> {
+    let tagged_file = read_tagged_file(path)?;
+    let tag = primary_tag(&tagged_file);
+
+    keys.iter()
+        .map(|key| {
+            let item_key = item_key(key)?;
+            Ok(TagEdit {
+                key: key.clone(),
+                values: tag
+                    .map(|tag| tag.get_strings(item_key).map(String::from).collect())
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the embedded front cover, falling back to the first picture.
+pub fn read_cover_art(path: &Path) -> Result<Option<Vec<u8>>> {
+    let tagged_file = read_tagged_file(path)?;
+
+    let Some(tag) = primary_tag(&tagged_file) else {
+        return Ok(None);
+    };
+
+    let picture = tag
+        .pictures()
+        .iter()
+        .find(|x| x.pic_type() == PictureType::CoverFront)
+        .or_else(|| tag.pictures().first());
+
+    Ok(picture.map(|x| x.data().to_vec()))
+}
+
+fn detect_mime_type(data: &[u8]) -> Option<MimeType> {
+    match data {
+        [0xFF, 0xD8, 0xFF, ..] => Some(MimeType::Jpeg),
+        [0x89, b'P', b'N', b'G', ..] => Some(MimeType::Png),
+        [b'G', b'I', b'F', b'8', ..] => Some(MimeType::Gif),
+        [b'B', b'M', ..] => Some(MimeType::Bmp),
+        [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => Some(MimeType::Tiff),
+        _ => None,
+    }
+}
+
+/// Applies `edits` and `cover_art` to the tag of the file and saves it.
+///
+/// The tag type follows the file format: ID3v2 for MP3, WAV and AIFF, Vorbis
+/// comments for FLAC and Ogg, MP4 atoms for MP4 and APE tags for APE,
+/// WavPack and Musepack. A tag is created if the file has none.
+pub fn write_tags(path: &Path, edits: &[TagEdit], cover_art: &CoverArtEdit) -> Result<()> {
+    let mut tagged_file = read_tagged_file(path)?;
+
+    let tag_type: TagType = tagged_file.primary_tag_type();
+    if tagged_file.primary_tag().is_none() {
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+
+    let Some(tag) = tagged_file.primary_tag_mut() else {
+        bail!("Failed to create a tag: {}", path.display());
+    };
+
+    for edit in edits {
+        let item_key = item_key(&edit.key)?.clone();
+
+        // Every value is replaced, so the ones that are not edited are not
+        // left behind. Empty values are skipped.
+        tag.remove_key(&item_key);
+        for value in edit.values.iter().filter(|value| !value.is_empty()) {
+            let item = TagItem::new(item_key.clone(), ItemValue::Text(value.clone()));
+            if !tag.push(item) {
+                bail!(
+                    "Field is not supported by {:?} tags: {}",
+                    tag_type,
+                    edit.key
+                );
+            }
+        }
+    }
+
+    match cover_art {
+        CoverArtEdit::Keep => {}
+        CoverArtEdit::Remove => {
+            tag.remove_picture_type(PictureType::CoverFront);
+        }
+        CoverArtEdit::Replace(data) => {
+            let Some(mime_type) = detect_mime_type(data) else {
+                bail!("Unsupported cover art format");
+            };
+
+            tag.remove_picture_type(PictureType::CoverFront);
+            tag.push_picture(Picture::new_unchecked(
+                PictureType::CoverFront,
+                Some(mime_type),
+                None,
+                data.clone(),
+            ));
+        }
+    }
+
+    tagged_file
+        .save_to_path(path, WriteOptions::default())
+        .with_context(|| format!("Failed to write tags: {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const PNG: [u8; 16] = [
+        0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0x0D, b'I', b'H', b'D', b'R',
+    ];
+
+    /// Three MPEG-1 Layer III frames at 128 kbps and 44.1 kHz.
+    fn mp3() -> Vec<u8> {
+        let mut frame = vec![0; 417];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        frame.repeat(3)
+    }
+
+    /// A FLAC stream with only its STREAMINFO block, 44.1 kHz, 16 bits,
+    /// stereo.
+    fn flac() -> Vec<u8> {
+        let mut data = b"fLaC".to_vec();
+        data.extend_from_slice(&[0x80, 0x00, 0x00, 0x22]);
+        data.extend_from_slice(&[0x10, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0x0A, 0xC4, 0x42, 0xF0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0; 16]);
+        data
+    }
+
+    fn atom(name: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(name);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    /// An MP4 file with a movie header and no tracks.
+    fn mp4() -> Vec<u8> {
+        let mut mvhd = vec![0; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[20..24].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        mvhd[24..26].copy_from_slice(&0x0100u16.to_be_bytes());
+
+        let mut data = atom(b"ftyp", b"M4A \0\0\0\0M4A ");
+        data.extend(atom(b"moov", &atom(b"mvhd", &mvhd)));
+        data
+    }
+
+    /// A Monkey's Audio file header without audio.
+    fn ape() -> Vec<u8> {
+        let mut data = b"MAC ".to_vec();
+        data.extend_from_slice(&[0; 96]);
+        data
+    }
+
+    fn write_file(dir: &TempDir, name: &str, data: &[u8]) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn edit(key: &str, values: &[&str]) -> TagEdit {
+        TagEdit {
+            key: key.to_string(),
+            values: values.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    fn keys() -> Vec<String> {
+        ["track_title", "artist", "album", "track_number"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect()
+    }
+
+    fn assert_round_trip(name: &str, data: &[u8], tag_type: TagType) {
+        let dir = TempDir::new().unwrap();
+        let path = write_file(&dir, name, data);
+
+        let edits = vec![
+            edit("track_title", &["Title"]),
+            edit("artist", &["Artist"]),
+            edit("album", &["Album"]),
+            edit("track_number", &["3"]),
+        ];
+        write_tags(&path, &edits, &CoverArtEdit::Keep).unwrap();
+
+        assert_eq!(
+            read_tagged_file(&path).unwrap().primary_tag_type(),
+            tag_type
+        );
+        assert_eq!(read_tags(&path, &keys()).unwrap(), edits);
+
+        // Empty values remove the field, the others are kept
+        write_tags(
+            &path,
+            &[edit("album", &[""]), edit("artist", &[])],
+            &CoverArtEdit::Keep,
+        )
+        .unwrap();
+        assert_eq!(
+            read_tags(&path, &keys()).unwrap(),
+            vec![
+                edit("track_title", &["Title"]),
+                edit("artist", &[]),
+                edit("album", &[]),
+                edit("track_number", &["3"]),
+            ]
+        );
+    }
+
+    fn assert_cover_art_round_trip(name: &str, data: &[u8]) {
+        let dir = TempDir::new().unwrap();
+        let path = write_file(&dir, name, data);
+
+        assert_eq!(read_cover_art(&path).unwrap(), None);
+
+        write_tags(&path, &[], &CoverArtEdit::Replace(PNG.to_vec())).unwrap();
+        assert_eq!(read_cover_art(&path).unwrap(), Some(PNG.to_vec()));
+
+        write_tags(&path, &[], &CoverArtEdit::Remove).unwrap();
+        assert_eq!(read_cover_art(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_id3v2_round_trip() {
+        assert_round_trip("track.mp3", &mp3(), TagType::Id3v2);
+        assert_cover_art_round_trip("cover.mp3", &mp3());
+    }
+
+    #[test]
+    fn test_vorbis_comments_round_trip() {
+        assert_round_trip("track.flac", &flac(), TagType::VorbisComments);
+        assert_cover_art_round_trip("cover.flac", &flac());
+    }
+
+    #[test]
+    fn test_multiple_values_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = write_file(&dir, "duet.flac", &flac());
+
+        let keys = vec!["artist".to_string(), "album".to_string()];
+        let artists = edit("artist", &["First Artist", "Second Artist"]);
+        write_tags(&path, &[artists.clone()], &CoverArtEdit::Keep).unwrap();
+        let original = read_tags(&path, &keys).unwrap();
+        assert_eq!(original, vec![artists.clone(), edit("album", &[])]);
+
+        // Editing another field keeps both artists
+        write_tags(&path, &[edit("album", &["Album"])], &CoverArtEdit::Keep).unwrap();
+        assert_eq!(
+            read_tags(&path, &keys).unwrap(),
+            vec![artists.clone(), edit("album", &["Album"])]
+        );
+
+        // Writing back what was read restores every value
+        write_tags(&path, &[edit("artist", &["Solo"])], &CoverArtEdit::Keep).unwrap();
+        assert_eq!(
+            read_tags(&path, &keys).unwrap()[0],
+            edit("artist", &["Solo"])
+        );
+        write_tags(&path, &original, &CoverArtEdit::Keep).unwrap();
+        assert_eq!(read_tags(&path, &keys).unwrap(), original);
+    }
+
+    #[test]
+    fn test_mp4_round_trip() {
+        assert_round_trip("track.m4a", &mp4(), TagType::Mp4Ilst);
+    }
+
+    #[test]
+    fn test_ape_round_trip() {
+        assert_round_trip("track.ape", &ape(), TagType::Ape);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let path = write_file(&dir, "track.mp3", &mp3());
+
+        assert!(read_tags(&path, &["unknown".to_string()]).is_err());
+        assert!(write_tags(&path, &[edit("unknown", &["x"])], &CoverArtEdit::Keep).is_err());
+        assert!(write_tags(&path, &[], &CoverArtEdit::Replace(b"not an image".to_vec())).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use log::warn;
+use sha2::{Digest, Sha256};
+
+use crate::options::ResolvedOptions;
+
+lazy_static! {
+    pub static ref TRANSCODE_CACHE_DIR: PathBuf = env::temp_dir().join("rune").join("transcoded");
+}
+
+/// Name of the file written once every segment of a stream is cached.
+const COMPLETE_MARKER: &str = "complete";
+
+/// Stores transcoded streams on disk, split into segments.
+///
+/// Each stream gets a directory named after the source file, its
+/// modification time and the encoding options, so editing a file or asking
+/// for other options never returns stale audio. When the cache grows past
+/// its size limit, the streams used least recently are removed.
+pub struct SegmentCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl SegmentCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self { dir, max_size }
+    }
+
+    /// Computes the key of the stream of `path`, or of the `range` of it,
+    /// encoded with `options`.
+    pub fn key(
+        &self,
+        path: &Path,
+        range: Option<(f64, f64)>,
+        options: &ResolvedOptions,
+    ) -> Result<String> {
+        let metadata = fs::metadata(path)
+            .with_context(|| format!("Failed to read metadata of {}", path.display()))?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        let mut hasher = Sha256::new();
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(metadata.len().to_le_bytes());
+        hasher.update(modified.to_le_bytes());
+        if let Some((start, end)) = range {
+            hasher.update(start.to_le_bytes());
+            hasher.update(end.to_le_bytes());
+        }
+        hasher.update(options.to_string().as_bytes());
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    fn stream_dir(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+
+    fn segment_path(&self, key: &str, index: usize) -> PathBuf {
+        self.stream_dir(key).join(format!("{}.seg", index))
+    }
+
+    /// Returns the number of segments of a stream, if all of them are cached.
+    pub fn complete_segments(&self, key: &str) -> Option<usize> {
+        let marker = self.stream_dir(key).join(COMPLETE_MARKER);
+        let count = fs::read_to_string(&marker).ok()?.trim().parse().ok()?;
+
+        // Reading a stream counts as using it
+        let _ = fs::OpenOptions::new()
+            .write(true)
+            .open(&marker)
+            .and_then(|x| x.set_modified(SystemTime::now()));
+
+        Some(count)
+    }
+
+    /// Returns the sizes of the segments of a complete stream.
+    pub fn segment_sizes(&self, key: &str) -> Result<Vec<u64>> {
+        let count = self
+            .complete_segments(key)
+            .context("The stream is not completely cached")?;
+
+        (0..count)
+            .map(|index| Ok(fs::metadata(self.segment_path(key, index))?.len()))
+            .collect()
+    }
+
+    pub fn read_segment(&self, key: &str, index: usize) -> Result<Vec<u8>> {
+        fs::read(self.segment_path(key, index))
+            .with_context(|| format!("Failed to read segment {} of {}", index, key))
+    }
+
+    pub fn write_segment(&self, key: &str, index: usize, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(self.stream_dir(key))?;
+
+        // Written aside first, so readers never see a partial segment. Two
+        // requests may transcode the same stream, each writes its own file.
+        let path = self.segment_path(key, index);
+        let temp_path = path.with_extension(format!("{:?}.tmp", thread::current().id()));
+        fs::write(&temp_path, data)?;
+        fs::rename(&temp_path, &path)?;
+
+        Ok(())
+    }
+
+    /// Marks a stream as complete and makes room for it in the cache.
+    pub fn complete(&self, key: &str, segments: usize) -> Result<()> {
+        fs::write(
+            self.stream_dir(key).join(COMPLETE_MARKER),
+            segments.to_string(),
+        )?;
+
+        if let Err(e) = self.prune(key) {
+            warn!("Failed to prune the transcoding cache: {:#?}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the streams used least recently until the cache fits its size
+    /// limit, `keep` is never removed.
+    fn prune(&self, keep: &str) -> Result<()> {
+        let mut streams = Vec::new();
+        let mut total_size = 0;
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            let size: u64 = fs::read_dir(entry.path())?
+                .filter_map(|x| x.ok()?.metadata().ok())
+                .map(|x| x.len())
+                .sum();
+            total_size += size;
+
+            if entry.file_name() != keep {
+                // Incomplete streams have no marker, they were last used when written
+                let modified = fs::metadata(entry.path().join(COMPLETE_MARKER))
+                    .or_else(|_| entry.metadata())?
+                    .modified()?;
+                streams.push((modified, entry.path(), size));
+            }
+        }
+
+        streams.sort_by_key(|(modified, _, _)| *modified);
+
+        for (_, path, size) in streams {
+            if total_size <= self.max_size {
+                break;
+            }
+
+            fs::remove_dir_all(&path)?;
+            total_size -= size;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn add_stream(cache: &SegmentCache, key: &str, age: u64) {
+        cache.write_segment(key, 0, &[0; 10]).unwrap();
+        cache.complete(key, 1).unwrap();
+        set_last_used(cache, key, age);
+    }
+
+    fn set_last_used(cache: &SegmentCache, key: &str, age: u64) {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(cache.stream_dir(key).join(COMPLETE_MARKER))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+    }
+
+    fn cached(cache: &SegmentCache, key: &str) -> bool {
+        cache.stream_dir(key).exists()
+    }
+
+    #[test]
+    fn test_segments() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = SegmentCache::new(directory.path().to_path_buf(), u64::MAX);
+
+        cache.write_segment("a", 0, &[1; 10]).unwrap();
+        cache.write_segment("a", 1, &[2; 5]).unwrap();
+        // Incomplete streams are not served from the cache
+        assert_eq!(cache.complete_segments("a"), None);
+        assert!(cache.segment_sizes("a").is_err());
+
+        cache.complete("a", 2).unwrap();
+        assert_eq!(cache.complete_segments("a"), Some(2));
+        assert_eq!(cache.segment_sizes("a").unwrap(), vec![10, 5]);
+        assert_eq!(cache.read_segment("a", 1).unwrap(), vec![2; 5]);
+        assert!(cache.read_segment("a", 2).is_err());
+    }
+
+    #[test]
+    fn test_prune() {
+        let directory = tempfile::tempdir().unwrap();
+        // Room for two streams of one segment and their markers
+        let cache = SegmentCache::new(directory.path().to_path_buf(), 25);
+
+        add_stream(&cache, "a", 300);
+        add_stream(&cache, "b", 200);
+        assert!(cached(&cache, "a") && cached(&cache, "b"));
+
+        add_stream(&cache, "c", 100);
+        assert!(!cached(&cache, "a"));
+        assert!(cached(&cache, "b") && cached(&cache, "c"));
+
+        // Reading a stream makes it the most recently used
+        assert_eq!(cache.complete_segments("b"), Some(1));
+        add_stream(&cache, "d", 0);
+        assert!(!cached(&cache, "c"));
+        assert!(cached(&cache, "b") && cached(&cache, "d"));
+
+        // The stream just completed is kept even if it does not fit
+        let cache = SegmentCache::new(directory.path().to_path_buf(), 0);
+        add_stream(&cache, "e", 0);
+        assert!(cached(&cache, "e"));
+        assert!(!cached(&cache, "b") && !cached(&cache, "d"));
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Result};
+use fdk_aac::enc::{AudioObjectType, BitRate, ChannelMode, Encoder, EncoderParams, Transport};
+
+use crate::options::ResolvedOptions;
+
+use super::{to_i16, AudioEncoder};
+
+/// Samples per channel in an AAC-LC frame.
+const FRAME_SIZE: usize = 1024;
+
+/// Frames of silence fed at the end, the encoder can not be flushed and
+/// holds back about two frames of audio.
+const PADDING_FRAMES: usize = 3;
+
+/// Largest ADTS frame for one channel.
+const MAX_FRAME_BYTES: usize = 768;
+
+pub struct AacEncoder {
+    encoder: Encoder,
+    channels: usize,
+    buffer: Vec<i16>,
+}
+
+impl AacEncoder {
+    pub fn new(options: &ResolvedOptions) -> Result<Self> {
+        let encoder = Encoder::new(EncoderParams {
+            bit_rate: BitRate::Cbr(options.bitrate * 1000),
+            sample_rate: options.sample_rate,
+            transport: Transport::Adts,
+            channels: if options.channels == 1 {
+                ChannelMode::Mono
+            } else {
+                ChannelMode::Stereo
+            },
+            audio_object_type: AudioObjectType::Mpeg4LowComplexity,
+        })
+        .map_err(|e| anyhow!("Failed to create the AAC encoder: {:?}", e))?;
+
+        Ok(Self {
+            encoder,
+            channels: options.channels as usize,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Encodes every whole frame in the buffer.
+    fn encode_frames(&mut self) -> Result<Vec<u8>> {
+        let frame_samples = FRAME_SIZE * self.channels;
+        let mut output = Vec::new();
+        let mut frame = vec![0u8; MAX_FRAME_BYTES * self.channels];
+        let mut position = 0;
+
+        while self.buffer.len() - position >= frame_samples {
+            let info = self
+                .encoder
+                .encode(&self.buffer[position..position + frame_samples], &mut frame)
+                .map_err(|e| anyhow!("Failed to encode AAC: {:?}", e))?;
+
+            output.extend_from_slice(&frame[..info.output_size]);
+            if info.input_consumed == 0 {
+                break;
+            }
+            position += info.input_consumed;
+        }
+
+        self.buffer.drain(..position);
+        Ok(output)
+    }
+}
+
+impl AudioEncoder for AacEncoder {
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>> {
+        self.buffer.extend(to_i16(samples));
+        self.encode_frames()
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let frame_samples = FRAME_SIZE * self.channels;
+        let padded = self.buffer.len().div_ceil(frame_samples) * frame_samples
+            + PADDING_FRAMES * frame_samples;
+        self.buffer.resize(padded, 0);
+
+        self.encode_frames()
+    }
+}
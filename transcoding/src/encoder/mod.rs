@@ -0,0 +1,39 @@
+#[cfg(feature = "aac")]
+mod aac;
+mod mp3;
+mod opus;
+
+use anyhow::Result;
+
+use crate::options::{ResolvedOptions, TranscodeFormat};
+
+/// Encodes interleaved samples into a stream that can be sent as it is
+/// produced.
+pub trait AudioEncoder {
+    /// Encodes more samples, returning the bytes ready so far. Encoders
+    /// buffer samples until they have a whole frame.
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>>;
+
+    /// Encodes the buffered samples and ends the stream.
+    fn finish(&mut self) -> Result<Vec<u8>>;
+}
+
+/// Creates the encoder for the options, `input_rate` is the sample rate
+/// of the source file, some containers record it.
+pub fn create_encoder(options: &ResolvedOptions, input_rate: u32) -> Result<Box<dyn AudioEncoder>> {
+    Ok(match options.format {
+        TranscodeFormat::Opus => Box::new(opus::OpusEncoder::new(options, input_rate)?),
+        #[cfg(feature = "aac")]
+        TranscodeFormat::Aac => Box::new(aac::AacEncoder::new(options)?),
+        #[cfg(not(feature = "aac"))]
+        TranscodeFormat::Aac => anyhow::bail!("Unsupported transcoding format: {}", options.format),
+        TranscodeFormat::Mp3 => Box::new(mp3::Mp3Encoder::new(options)?),
+    })
+}
+
+fn to_i16(samples: &[f32]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|x| (x.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+        .collect()
+}
@@ -0,0 +1,112 @@
+use anyhow::{anyhow, Result};
+use mp3lame_encoder::{
+    max_required_buffer_size, Bitrate, Builder, Encoder, FlushNoGap, InterleavedPcm, MonoPcm,
+    Quality,
+};
+
+use crate::options::ResolvedOptions;
+
+use super::{to_i16, AudioEncoder};
+
+/// Bitrates LAME can encode at, in kbps.
+const BITRATES: [(u32, Bitrate); 16] = [
+    (8, Bitrate::Kbps8),
+    (16, Bitrate::Kbps16),
+    (24, Bitrate::Kbps24),
+    (32, Bitrate::Kbps32),
+    (40, Bitrate::Kbps40),
+    (48, Bitrate::Kbps48),
+    (64, Bitrate::Kbps64),
+    (80, Bitrate::Kbps80),
+    (96, Bitrate::Kbps96),
+    (112, Bitrate::Kbps112),
+    (128, Bitrate::Kbps128),
+    (160, Bitrate::Kbps160),
+    (192, Bitrate::Kbps192),
+    (224, Bitrate::Kbps224),
+    (256, Bitrate::Kbps256),
+    (320, Bitrate::Kbps320),
+];
+
+/// Room LAME needs to flush its buffers.
+const FLUSH_BUFFER_SIZE: usize = 7200;
+
+pub struct Mp3Encoder {
+    encoder: Encoder,
+    channels: usize,
+}
+
+impl Mp3Encoder {
+    pub fn new(options: &ResolvedOptions) -> Result<Self> {
+        // The highest bitrate that does not go over the requested one
+        let bitrate = BITRATES
+            .iter()
+            .rev()
+            .find(|(kbps, _)| *kbps <= options.bitrate)
+            .unwrap_or(&BITRATES[0])
+            .1;
+
+        let mut builder =
+            Builder::new().ok_or_else(|| anyhow!("Failed to create the MP3 encoder"))?;
+        builder
+            .set_num_channels(options.channels as u8)
+            .map_err(|e| anyhow!("Invalid channel count: {:?}", e))?;
+        builder
+            .set_sample_rate(options.sample_rate)
+            .map_err(|e| anyhow!("Invalid sample rate: {:?}", e))?;
+        builder
+            .set_brate(bitrate)
+            .map_err(|e| anyhow!("Invalid bitrate: {:?}", e))?;
+        builder
+            .set_quality(Quality::Good)
+            .map_err(|e| anyhow!("Invalid quality: {:?}", e))?;
+
+        Ok(Self {
+            encoder: builder
+                .build()
+                .map_err(|e| anyhow!("Failed to initialize the MP3 encoder: {:?}", e))?,
+            channels: options.channels as usize,
+        })
+    }
+}
+
+impl AudioEncoder for Mp3Encoder {
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>> {
+        let samples = to_i16(samples);
+        let mut output =
+            Vec::with_capacity(max_required_buffer_size(samples.len() / self.channels));
+
+        let size = if self.channels == 1 {
+            self.encoder
+                .encode(MonoPcm(&samples), output.spare_capacity_mut())
+        } else {
+            self.encoder
+                .encode(InterleavedPcm(&samples), output.spare_capacity_mut())
+        }
+        .map_err(|e| anyhow!("Failed to encode MP3: {:?}", e))?;
+
+        Ok(written(output, size))
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(FLUSH_BUFFER_SIZE);
+        let size = self
+            .encoder
+            .flush::<FlushNoGap>(output.spare_capacity_mut())
+            .map_err(|e| anyhow!("Failed to flush MP3: {:?}", e))?;
+
+        Ok(written(output, size))
+    }
+}
+
+/// Marks the bytes LAME wrote into the spare capacity as initialized.
+fn written(mut output: Vec<u8>, size: usize) -> Vec<u8> {
+    assert!(
+        size <= output.capacity(),
+        "LAME wrote past the output buffer"
+    );
+
+    // SAFETY: LAME initialized the first `size` bytes of the spare capacity
+    unsafe { output.set_len(size) };
+    output
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use opus::{Application, Bitrate, Channels, Encoder};
+
+use crate::options::ResolvedOptions;
+
+use super::AudioEncoder;
+
+/// Samples per channel in a 20 ms frame at 48 kHz.
+const FRAME_SIZE: usize = 960;
+
+/// Samples libopus looks ahead at 48 kHz, decoders skip them.
+const PRE_SKIP: u16 = 312;
+
+/// Largest packet libopus produces.
+const MAX_PACKET_BYTES: usize = 4000;
+
+/// Serial number of the only logical stream in the file.
+const STREAM_SERIAL: u32 = 0x52554e45;
+
+/// Encodes Opus packets into an Ogg stream.
+pub struct OpusEncoder {
+    encoder: Encoder,
+    writer: PacketWriter<'static, Vec<u8>>,
+    channels: usize,
+    input_rate: u32,
+    buffer: Vec<f32>,
+    /// Samples per channel encoded so far, padding excluded
+    position: u64,
+    /// Samples per channel in the packets written so far
+    granule_position: u64,
+    headers_written: bool,
+}
+
+impl OpusEncoder {
+    pub fn new(options: &ResolvedOptions, input_rate: u32) -> Result<Self> {
+        let channels = if options.channels == 1 {
+            Channels::Mono
+        } else {
+            Channels::Stereo
+        };
+
+        let mut encoder = Encoder::new(options.sample_rate, channels, Application::Audio)?;
+        encoder.set_bitrate(Bitrate::Bits(options.bitrate as i32 * 1000))?;
+
+        Ok(Self {
+            encoder,
+            writer: PacketWriter::new(Vec::new()),
+            channels: options.channels as usize,
+            input_rate,
+            buffer: Vec::new(),
+            position: 0,
+            granule_position: 0,
+            headers_written: false,
+        })
+    }
+
+    /// Writes the identification and comment headers, each on its own page.
+    fn write_headers(&mut self) -> Result<()> {
+        let mut head = b"OpusHead".to_vec();
+        head.push(1);
+        head.push(self.channels as u8);
+        head.extend_from_slice(&PRE_SKIP.to_le_bytes());
+        head.extend_from_slice(&self.input_rate.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes());
+        head.push(0);
+
+        let vendor = b"rune";
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor);
+        tags.extend_from_slice(&0u32.to_le_bytes());
+
+        self.writer
+            .write_packet(head, STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+        self.writer
+            .write_packet(tags, STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+        self.headers_written = true;
+
+        Ok(())
+    }
+
+    /// Encodes one frame from the start of the buffer. `samples` is the
+    /// number of samples per channel that are audio rather than padding.
+    fn encode_frame(&mut self, samples: usize, end: PacketWriteEndInfo) -> Result<()> {
+        let frame_samples = FRAME_SIZE * self.channels;
+        let mut packet = vec![0u8; MAX_PACKET_BYTES];
+        let size = self
+            .encoder
+            .encode_float(&self.buffer[..frame_samples], &mut packet)?;
+        packet.truncate(size);
+        self.buffer.drain(..frame_samples);
+
+        self.position += samples as u64;
+        self.granule_position += FRAME_SIZE as u64;
+
+        // The granule position of the last page tells decoders where the
+        // audio ends, so the padding is not played
+        let granule_position = match end {
+            PacketWriteEndInfo::EndStream => PRE_SKIP as u64 + self.position,
+            _ => self.granule_position,
+        };
+        self.writer
+            .write_packet(packet, STREAM_SERIAL, end, granule_position)?;
+
+        Ok(())
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(self.writer.inner_mut())
+    }
+}
+
+impl AudioEncoder for OpusEncoder {
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>> {
+        if !self.headers_written {
+            self.write_headers()?;
+        }
+
+        self.buffer.extend_from_slice(samples);
+        while self.buffer.len() >= FRAME_SIZE * self.channels {
+            self.encode_frame(FRAME_SIZE, PacketWriteEndInfo::NormalPacket)?;
+        }
+
+        Ok(self.take_output())
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        if !self.headers_written {
+            self.write_headers()?;
+        }
+
+        // The encoder lags behind by the pre-skip, pad with silence until
+        // every sample made it into a packet
+        let samples = self.buffer.len() / self.channels;
+        if samples + PRE_SKIP as usize > FRAME_SIZE {
+            self.buffer.resize(FRAME_SIZE * self.channels, 0.0);
+            self.encode_frame(samples, PacketWriteEndInfo::NormalPacket)?;
+            self.buffer.resize(FRAME_SIZE * self.channels, 0.0);
+            self.encode_frame(0, PacketWriteEndInfo::EndStream)?;
+        } else {
+            self.buffer.resize(FRAME_SIZE * self.channels, 0.0);
+            self.encode_frame(samples, PacketWriteEndInfo::EndStream)?;
+        }
+
+        Ok(self.take_output())
+    }
+}
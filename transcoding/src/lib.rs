@@ -0,0 +1,10 @@
+pub mod cache;
+pub mod encoder;
+pub mod options;
+mod resampler;
+pub mod source;
+pub mod transcoder;
+
+pub use cache::{SegmentCache, TRANSCODE_CACHE_DIR};
+pub use options::{TranscodeFormat, TranscodeOptions};
+pub use transcoder::Transcoder;
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+
+/// Sample rates accepted by the MP3 and AAC encoders.
+const MPEG_SAMPLE_RATES: [u32; 9] = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+
+/// The only sample rate Opus streams are encoded at.
+const OPUS_SAMPLE_RATE: u32 = 48000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TranscodeFormat {
+    /// Opus in an Ogg container
+    Opus,
+    /// AAC-LC in ADTS frames
+    Aac,
+    Mp3,
+}
+
+impl TranscodeFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            TranscodeFormat::Opus => "audio/ogg",
+            TranscodeFormat::Aac => "audio/aac",
+            TranscodeFormat::Mp3 => "audio/mpeg",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscodeFormat::Opus => "opus",
+            TranscodeFormat::Aac => "aac",
+            TranscodeFormat::Mp3 => "mp3",
+        }
+    }
+
+    /// Whether this build can encode the format. AAC needs the `aac`
+    /// feature, see the License section of the README.
+    pub fn is_available(&self) -> bool {
+        match self {
+            TranscodeFormat::Aac => cfg!(feature = "aac"),
+            TranscodeFormat::Opus | TranscodeFormat::Mp3 => true,
+        }
+    }
+
+    /// Bitrate in kbps used when the client does not ask for one.
+    pub fn default_bitrate(&self) -> u32 {
+        match self {
+            TranscodeFormat::Opus => 96,
+            TranscodeFormat::Aac => 128,
+            TranscodeFormat::Mp3 => 192,
+        }
+    }
+
+    /// Clamps a bitrate in kbps to the range the encoder supports.
+    fn clamp_bitrate(&self, bitrate: u32) -> u32 {
+        match self {
+            TranscodeFormat::Opus => bitrate.clamp(6, 510),
+            TranscodeFormat::Aac => bitrate.clamp(32, 320),
+            TranscodeFormat::Mp3 => bitrate.clamp(8, 320),
+        }
+    }
+
+    /// Picks the sample rate closest to the requested one the encoder supports.
+    fn sample_rate(&self, requested: u32) -> u32 {
+        match self {
+            TranscodeFormat::Opus => OPUS_SAMPLE_RATE,
+            TranscodeFormat::Aac | TranscodeFormat::Mp3 => {
+                if MPEG_SAMPLE_RATES.contains(&requested) {
+                    requested
+                } else if requested.is_multiple_of(11025) {
+                    44100
+                } else {
+                    48000
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for TranscodeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl FromStr for TranscodeFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let format = match s.to_lowercase().as_str() {
+            "opus" | "ogg" => TranscodeFormat::Opus,
+            "aac" | "m4a" => TranscodeFormat::Aac,
+            "mp3" => TranscodeFormat::Mp3,
+            _ => bail!("Unsupported transcoding format: {}", s),
+        };
+
+        if !format.is_available() {
+            bail!(
+                "Unsupported transcoding format: {}, this build has no {} encoder",
+                s,
+                format
+            );
+        }
+
+        Ok(format)
+    }
+}
+
+/// What a client asks the transcoder for. Unset fields follow the source
+/// file, within the limits of the encoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodeOptions {
+    pub format: TranscodeFormat,
+    /// Bitrate in kbps
+    pub bitrate: Option<u32>,
+    pub sample_rate: Option<u32>,
+    /// Only mono and stereo are supported, sources with more channels are downmixed
+    pub channels: Option<u16>,
+}
+
+impl TranscodeOptions {
+    pub fn new(format: TranscodeFormat) -> Self {
+        Self {
+            format,
+            bitrate: None,
+            sample_rate: None,
+            channels: None,
+        }
+    }
+
+    /// Lowers the bitrate to `max_bitrate`, if the client is not allowed more.
+    pub fn cap_bitrate(mut self, max_bitrate: Option<u32>) -> Self {
+        if let Some(max_bitrate) = max_bitrate {
+            let bitrate = self.bitrate.unwrap_or(self.format.default_bitrate());
+            self.bitrate = Some(bitrate.min(max_bitrate));
+        }
+
+        self
+    }
+
+    /// Fills the unset fields from the source and makes every field acceptable
+    /// to the encoder.
+    pub(crate) fn resolve(&self, source_rate: u32, source_channels: u16) -> ResolvedOptions {
+        let channels = self.channels.unwrap_or(source_channels).clamp(1, 2);
+
+        ResolvedOptions {
+            format: self.format,
+            bitrate: self
+                .format
+                .clamp_bitrate(self.bitrate.unwrap_or(self.format.default_bitrate())),
+            sample_rate: self
+                .format
+                .sample_rate(self.sample_rate.unwrap_or(source_rate)),
+            channels,
+        }
+    }
+}
+
+/// Options with every field decided, as passed to the encoders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedOptions {
+    pub format: TranscodeFormat,
+    /// Bitrate in kbps
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl fmt::Display for ResolvedOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}k-{}hz-{}ch",
+            self.format, self.bitrate, self.sample_rate, self.channels
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(format: TranscodeFormat, bitrate: Option<u32>) -> TranscodeOptions {
+        TranscodeOptions {
+            bitrate,
+            ..TranscodeOptions::new(format)
+        }
+    }
+
+    #[test]
+    fn test_cap_bitrate() {
+        let opus = TranscodeFormat::Opus;
+
+        assert_eq!(options(opus, None).cap_bitrate(None).bitrate, None);
+        assert_eq!(
+            options(opus, Some(320)).cap_bitrate(None).bitrate,
+            Some(320)
+        );
+        // The default bitrate of the format counts against the limit
+        assert_eq!(options(opus, None).cap_bitrate(Some(64)).bitrate, Some(64));
+        assert_eq!(options(opus, None).cap_bitrate(Some(128)).bitrate, Some(96));
+        assert_eq!(
+            options(opus, Some(32)).cap_bitrate(Some(64)).bitrate,
+            Some(32)
+        );
+        assert_eq!(
+            options(TranscodeFormat::Mp3, Some(320))
+                .cap_bitrate(Some(128))
+                .bitrate,
+            Some(128)
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let resolved = TranscodeOptions::new(TranscodeFormat::Opus).resolve(44100, 6);
+        assert_eq!(
+            resolved,
+            ResolvedOptions {
+                format: TranscodeFormat::Opus,
+                bitrate: 96,
+                sample_rate: 48000,
+                channels: 2,
+            }
+        );
+        assert_eq!(resolved.to_string(), "opus-96k-48000hz-2ch");
+
+        let resolved = TranscodeOptions {
+            format: TranscodeFormat::Mp3,
+            bitrate: Some(1000),
+            sample_rate: Some(22050),
+            channels: Some(1),
+        }
+        .resolve(44100, 2);
+        assert_eq!(resolved.bitrate, 320);
+        assert_eq!(resolved.sample_rate, 22050);
+        assert_eq!(resolved.channels, 1);
+
+        // Unsupported sample rates fall back to the closest family
+        let aac = TranscodeOptions::new(TranscodeFormat::Aac);
+        assert_eq!(aac.resolve(88200, 2).sample_rate, 44100);
+        assert_eq!(aac.resolve(96000, 2).sample_rate, 48000);
+        assert_eq!(aac.resolve(44100, 0).channels, 1);
+        assert_eq!(
+            options(TranscodeFormat::Aac, Some(8))
+                .resolve(44100, 2)
+                .bitrate,
+            32
+        );
+        assert_eq!(
+            options(TranscodeFormat::Opus, Some(2))
+                .resolve(48000, 2)
+                .bitrate,
+            6
+        );
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            "OGG".parse::<TranscodeFormat>().unwrap(),
+            TranscodeFormat::Opus
+        );
+        // AAC is only encoded with the `aac` feature
+        assert_eq!(
+            "m4a".parse::<TranscodeFormat>().ok(),
+            cfg!(feature = "aac").then_some(TranscodeFormat::Aac)
+        );
+        assert_eq!(
+            "mp3".parse::<TranscodeFormat>().unwrap(),
+            TranscodeFormat::Mp3
+        );
+        assert!("flac".parse::<TranscodeFormat>().is_err());
+    }
+}
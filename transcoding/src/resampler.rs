@@ -0,0 +1,133 @@
+use anyhow::Result;
+use rubato::{FftFixedInOut, Resampler};
+
+/// Number of input frames the resampler works on at once.
+const CHUNK_SIZE: usize = 1024;
+
+/// Resamples planar audio pushed in chunks of any size.
+///
+/// The delay of the resampler is removed, so the output lines up with the
+/// input and ends with it.
+pub struct StreamResampler {
+    resampler: Option<FftFixedInOut<f32>>,
+    ratio: f64,
+    buffer: Vec<Vec<f32>>,
+    /// Output frames still to drop because of the delay of the resampler
+    delay: usize,
+    input_frames: usize,
+    output_frames: usize,
+}
+
+impl StreamResampler {
+    pub fn new(input_rate: u32, output_rate: u32, channels: usize) -> Result<Self> {
+        let resampler = if input_rate == output_rate {
+            None
+        } else {
+            Some(FftFixedInOut::<f32>::new(
+                input_rate as usize,
+                output_rate as usize,
+                CHUNK_SIZE,
+                channels,
+            )?)
+        };
+        let delay = resampler.as_ref().map_or(0, |x| x.output_delay());
+
+        Ok(Self {
+            resampler,
+            ratio: output_rate as f64 / input_rate as f64,
+            buffer: vec![Vec::new(); channels],
+            delay,
+            input_frames: 0,
+            output_frames: 0,
+        })
+    }
+
+    pub fn push(&mut self, input: Vec<Vec<f32>>) -> Result<Vec<Vec<f32>>> {
+        self.input_frames += input.first().map_or(0, |x| x.len());
+
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(input);
+        };
+
+        for (buffer, channel) in self.buffer.iter_mut().zip(input) {
+            buffer.extend(channel);
+        }
+
+        let mut output = vec![Vec::new(); self.buffer.len()];
+        let mut position = 0;
+        loop {
+            let needed = resampler.input_frames_next();
+            if self.buffer[0].len() - position < needed {
+                break;
+            }
+
+            let chunk: Vec<&[f32]> = self
+                .buffer
+                .iter()
+                .map(|x| &x[position..position + needed])
+                .collect();
+            append(&mut output, resampler.process(&chunk, None)?);
+            position += needed;
+        }
+
+        for buffer in self.buffer.iter_mut() {
+            buffer.drain(..position);
+        }
+
+        Ok(self.trim(output, false))
+    }
+
+    /// Resamples what is left in the buffer and flushes the resampler.
+    pub fn finish(&mut self) -> Result<Vec<Vec<f32>>> {
+        let expected = self.expected_frames();
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(vec![Vec::new(); self.buffer.len()]);
+        };
+
+        let mut output = vec![Vec::new(); self.buffer.len()];
+        let rest = std::mem::take(&mut self.buffer);
+        append(
+            &mut output,
+            resampler.process_partial(Some(rest.as_slice()), None)?,
+        );
+
+        // The delayed frames only come out with the chunks after them
+        while self.output_frames + output[0].len().saturating_sub(self.delay) < expected {
+            let flushed = resampler.process_partial(None::<&[Vec<f32>]>, None)?;
+            append(&mut output, flushed);
+        }
+
+        self.buffer = vec![Vec::new(); output.len()];
+        Ok(self.trim(output, true))
+    }
+
+    fn expected_frames(&self) -> usize {
+        (self.input_frames as f64 * self.ratio).round() as usize
+    }
+
+    /// Drops the delay at the start and, at the end, the frames past the input.
+    fn trim(&mut self, mut output: Vec<Vec<f32>>, last: bool) -> Vec<Vec<f32>> {
+        let skipped = self.delay.min(output[0].len());
+        self.delay -= skipped;
+
+        for channel in output.iter_mut() {
+            channel.drain(..skipped);
+        }
+
+        if last {
+            let remaining = self.expected_frames().saturating_sub(self.output_frames);
+            for channel in output.iter_mut() {
+                channel.truncate(remaining);
+            }
+        }
+
+        self.output_frames += output[0].len();
+        output
+    }
+}
+
+fn append(output: &mut [Vec<f32>], chunk: Vec<Vec<f32>>) {
+    for (channel, samples) in output.iter_mut().zip(chunk) {
+        channel.extend(samples);
+    }
+}
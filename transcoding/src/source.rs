@@ -0,0 +1,210 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatReader, SeekMode, SeekTo};
+use symphonia::core::units::{Time, TimeBase};
+use symphonia::default::get_codecs;
+
+use formats::registry;
+
+/// Level of the center channel when folding it into the front channels.
+const CENTER_MIX_LEVEL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SourceInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Duration in seconds, zero if the container does not tell
+    pub duration: f64,
+}
+
+/// Decodes a file into planar samples, remixed to the number of channels
+/// the encoder expects.
+pub struct AudioSource {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    info: SourceInfo,
+    output_channels: usize,
+    /// Frames before this position are dropped, seeking usually stops a
+    /// little before the requested time
+    skip_until: u64,
+    /// Decoding ends at this frame, for tracks that end before the file
+    end_frame: Option<u64>,
+}
+
+impl AudioSource {
+    /// Opens a file and seeks to `start`, in seconds.
+    pub fn open(path: &Path, output_channels: u16, start: f64) -> Result<Self> {
+        let mut format = registry().probe(path)?.format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| anyhow!("No supported audio tracks"))?;
+
+        let codec_params = track.codec_params.clone();
+        let track_id = track.id;
+
+        let sample_rate = codec_params
+            .sample_rate
+            .ok_or_else(|| anyhow!("No sample rate found"))?;
+        let channels = codec_params.channels.map(|x| x.count() as u16).unwrap_or(2);
+        let time_base = codec_params.time_base;
+        let duration = match (codec_params.n_frames, time_base) {
+            (Some(n_frames), Some(time_base)) => seconds(time_base.calc_time(n_frames)),
+            (Some(n_frames), None) => n_frames as f64 / sample_rate as f64,
+            _ => 0.0,
+        };
+
+        let mut decoder = get_codecs()
+            .make(&codec_params, &DecoderOptions::default())
+            .context("Unsupported codec")?;
+
+        if start > 0.0 {
+            format
+                .seek(
+                    SeekMode::Accurate,
+                    SeekTo::Time {
+                        time: Time::from(start),
+                        track_id: Some(track_id),
+                    },
+                )
+                .with_context(|| format!("Failed to seek to {}s", start))?;
+            decoder.reset();
+        }
+
+        Ok(Self {
+            format,
+            decoder,
+            track_id,
+            time_base,
+            info: SourceInfo {
+                sample_rate,
+                channels,
+                duration,
+            },
+            output_channels: output_channels as usize,
+            skip_until: (start * sample_rate as f64).round() as u64,
+            end_frame: None,
+        })
+    }
+
+    /// Ends the stream at `end`, in seconds, instead of the end of the file.
+    pub fn stop_at(&mut self, end: f64) {
+        self.end_frame = Some((end * self.info.sample_rate as f64).round() as u64);
+    }
+
+    pub fn info(&self) -> SourceInfo {
+        self.info
+    }
+
+    /// Decodes the next packet, returns `None` at the end of the file.
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<Vec<f32>>>> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return Ok(None)
+                }
+                Err(Error::ResetRequired) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(e)) => {
+                    warn!("Skipping undecodable packet: {}", e);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let spec = *decoded.spec();
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buffer.copy_interleaved_ref(decoded);
+
+            let channels = spec.channels.count();
+            let samples = buffer.samples();
+
+            // Drop the frames decoded before the position asked for
+            let first_frame = self.frame_position(packet.ts());
+            let skipped = self.skip_until.saturating_sub(first_frame) as usize;
+            if skipped * channels >= samples.len() {
+                continue;
+            }
+
+            // And the frames past the end of the track
+            let mut samples = samples;
+            if let Some(end_frame) = self.end_frame {
+                if first_frame >= end_frame {
+                    return Ok(None);
+                }
+                let frames = (end_frame - first_frame) as usize;
+                samples = &samples[..(frames * channels).min(samples.len())];
+                if skipped * channels >= samples.len() {
+                    return Ok(None);
+                }
+            }
+
+            return Ok(Some(remix(
+                &samples[skipped * channels..],
+                channels,
+                self.output_channels,
+            )));
+        }
+    }
+
+    fn frame_position(&self, ts: u64) -> u64 {
+        match self.time_base {
+            Some(time_base) => {
+                (seconds(time_base.calc_time(ts)) * self.info.sample_rate as f64).round() as u64
+            }
+            None => ts,
+        }
+    }
+}
+
+fn seconds(time: Time) -> f64 {
+    time.seconds as f64 + time.frac
+}
+
+/// Splits interleaved samples into channels, downmixing to mono or stereo.
+///
+/// Sources with more than two channels keep their front channels and fold
+/// the center channel into them, the remaining channels are dropped.
+fn remix(samples: &[f32], input_channels: usize, output_channels: usize) -> Vec<Vec<f32>> {
+    let frames = samples.len() / input_channels;
+    let mut output = vec![Vec::with_capacity(frames); output_channels];
+
+    for frame in samples.chunks_exact(input_channels) {
+        match (input_channels, output_channels) {
+            (_, 1) => output[0].push(frame.iter().sum::<f32>() / input_channels as f32),
+            (1, _) => {
+                output[0].push(frame[0]);
+                output[1].push(frame[0]);
+            }
+            (2, _) => {
+                output[0].push(frame[0]);
+                output[1].push(frame[1]);
+            }
+            _ => {
+                let center = frame[2] * CENTER_MIX_LEVEL;
+                let scale = 1.0 / (1.0 + CENTER_MIX_LEVEL);
+                output[0].push((frame[0] + center) * scale);
+                output[1].push((frame[1] + center) * scale);
+            }
+        }
+    }
+
+    output
+}
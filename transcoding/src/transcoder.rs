@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::info;
+
+use crate::cache::SegmentCache;
+use crate::encoder::create_encoder;
+use crate::options::{ResolvedOptions, TranscodeOptions};
+use crate::resampler::StreamResampler;
+use crate::source::{AudioSource, SourceInfo};
+
+/// Seconds of source audio in each cached segment.
+pub const SEGMENT_SECONDS: f64 = 10.0;
+
+/// Transcodes one file with fixed options.
+///
+/// The whole file is encoded as one continuous stream, so segment
+/// boundaries are seamless. Streams started from the beginning are cached
+/// segment by segment, a stream started at a later time is encoded for
+/// that request only.
+///
+/// Tracks of a CUE sheet only transcode their `range` of the file, given
+/// as start and end offsets in seconds. Times passed to the transcoder are
+/// relative to the start of the range.
+pub struct Transcoder {
+    path: PathBuf,
+    range: Option<(f64, f64)>,
+    options: ResolvedOptions,
+    info: SourceInfo,
+    cache: Arc<SegmentCache>,
+    key: String,
+}
+
+impl Transcoder {
+    pub fn new(
+        path: &Path,
+        range: Option<(f64, f64)>,
+        options: &TranscodeOptions,
+        cache: Arc<SegmentCache>,
+    ) -> Result<Self> {
+        let info = AudioSource::open(path, 2, 0.0)?.info();
+        let options = options.resolve(info.sample_rate, info.channels);
+        let key = cache.key(path, range, &options)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            range,
+            options,
+            info,
+            cache,
+            key,
+        })
+    }
+
+    pub fn options(&self) -> &ResolvedOptions {
+        &self.options
+    }
+
+    /// Duration of the source file or range in seconds, zero if it is unknown.
+    pub fn duration(&self) -> f64 {
+        match self.range {
+            Some((start, end)) if self.info.duration > 0.0 => {
+                (end.min(self.info.duration) - start).max(0.0)
+            }
+            Some((start, end)) => (end - start).max(0.0),
+            None => self.info.duration,
+        }
+    }
+
+    /// Returns the sizes of the cached segments, if the whole stream is cached.
+    pub fn cached_segments(&self) -> Option<Vec<u64>> {
+        self.cache.segment_sizes(&self.key).ok()
+    }
+
+    pub fn read_segment(&self, index: usize) -> Result<Vec<u8>> {
+        self.cache.read_segment(&self.key, index)
+    }
+
+    /// Transcodes the file from `start`, in seconds, handing the encoded
+    /// bytes to `sink` as they are produced. Transcoding stops early if
+    /// `sink` returns `false`, for example when the client went away.
+    pub fn run(&self, start: f64, mut sink: impl FnMut(&[u8]) -> bool) -> Result<()> {
+        let caching = start <= 0.0;
+        let channels = self.options.channels as usize;
+
+        let mut source = match self.range {
+            Some((range_start, range_end)) => {
+                let mut source =
+                    AudioSource::open(&self.path, self.options.channels, range_start + start)?;
+                source.stop_at(range_end);
+                source
+            }
+            None => AudioSource::open(&self.path, self.options.channels, start)?,
+        };
+        let mut resampler =
+            StreamResampler::new(self.info.sample_rate, self.options.sample_rate, channels)?;
+        let mut encoder = create_encoder(&self.options, self.info.sample_rate)?;
+
+        let segment_frames = (SEGMENT_SECONDS * self.info.sample_rate as f64) as usize;
+        let mut segment = Vec::new();
+        let mut segment_index = 0;
+        let mut segment_position = 0;
+
+        while let Some(chunk) = source.next_chunk()? {
+            segment_position += chunk[0].len();
+
+            let bytes = encoder.encode(&interleave(resampler.push(chunk)?))?;
+            if !sink(&bytes) {
+                return Ok(());
+            }
+
+            if caching {
+                segment.extend_from_slice(&bytes);
+
+                if segment_position >= segment_frames {
+                    self.cache
+                        .write_segment(&self.key, segment_index, &segment)?;
+                    segment.clear();
+                    segment_index += 1;
+                    segment_position -= segment_frames;
+                }
+            }
+        }
+
+        let mut bytes = encoder.encode(&interleave(resampler.finish()?))?;
+        bytes.extend(encoder.finish()?);
+        if !sink(&bytes) {
+            return Ok(());
+        }
+
+        if caching {
+            segment.extend_from_slice(&bytes);
+            self.cache
+                .write_segment(&self.key, segment_index, &segment)?;
+            self.cache.complete(&self.key, segment_index + 1)?;
+
+            info!(
+                "Cached {} transcoded to {}",
+                self.path.display(),
+                self.options
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn interleave(planar: Vec<Vec<f32>>) -> Vec<f32> {
+    let frames = planar.first().map_or(0, |x| x.len());
+
+    (0..frames)
+        .flat_map(|frame| planar.iter().map(move |channel| channel[frame]))
+        .collect()
+}